@@ -0,0 +1,162 @@
+//! Audit Log Commands
+//!
+//! Read-only access to the immutable audit trail, plus exports for auditors.
+
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::commands::auth::require_role;
+use crate::models::audit::{
+    AuditEvent, AuditEventFilters, AuditEventPage, AuditExportFormat, AuditExportPayload,
+    AuditExportResult, SignedAuditExport,
+};
+use crate::security::audit;
+use crate::state::AppState;
+
+/// Roles allowed to read the audit trail
+const AUDIT_ROLES: &[&str] = &["admin", "auditor"];
+
+/// Resolve the org of the current tenant, checking the user may read audit logs
+fn authorize(
+    conn: &rusqlite::Connection,
+    state: &State<'_, AppState>,
+) -> Result<(String, String), String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+
+    require_role(conn, &user_id, AUDIT_ROLES)?;
+
+    let org_id: String = conn
+        .query_row(
+            "SELECT org_id FROM tenants WHERE id = ?1",
+            [&tenant_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Error al obtener organización: {}", e))?;
+
+    Ok((org_id, user_id))
+}
+
+/// List audit events with filters and pagination
+#[tauri::command]
+pub async fn list_audit_events(
+    state: State<'_, AppState>,
+    filters: Option<AuditEventFilters>,
+) -> Result<AuditEventPage, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    let (org_id, _) = authorize(&conn, &state)?;
+
+    audit::list_events(&conn, &org_id, &filters.unwrap_or_default())
+}
+
+/// Full history of a single entity (e.g. every event touching an invoice)
+#[tauri::command]
+pub async fn get_entity_history(
+    state: State<'_, AppState>,
+    entity_type: String,
+    entity_id: String,
+) -> Result<Vec<AuditEvent>, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    let (org_id, _) = authorize(&conn, &state)?;
+
+    audit::entity_history(&conn, &org_id, &entity_type, &entity_id)
+}
+
+/// Export audit events to CSV or signed JSON
+#[tauri::command]
+pub async fn export_audit_events(
+    state: State<'_, AppState>,
+    filters: Option<AuditEventFilters>,
+    format: AuditExportFormat,
+    path: String,
+) -> Result<AuditExportResult, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    let (org_id, user_id) = authorize(&conn, &state)?;
+    let filters = filters.unwrap_or_default();
+
+    let events = audit::export_events(&conn, &org_id, &filters)?;
+    let event_count = events.len();
+
+    let contents = match format {
+        AuditExportFormat::Csv => audit::events_to_csv(&events).into_bytes(),
+        AuditExportFormat::Json => {
+            let signed = audit::sign_export(
+                &conn,
+                AuditExportPayload {
+                    generated_at: Utc::now().to_rfc3339(),
+                    generated_by: user_id.clone(),
+                    org_id: org_id.clone(),
+                    filters: filters.clone(),
+                    events,
+                },
+            )?;
+            serde_json::to_vec_pretty(&signed).map_err(|e| e.to_string())?
+        }
+    };
+
+    std::fs::write(&path, &contents).map_err(|e| format!("Error al escribir archivo: {}", e))?;
+    let sha256 = hex::encode(Sha256::digest(&contents));
+
+    audit::log_event(
+        &conn,
+        state.require_tenant().ok().as_deref(),
+        Some(&user_id),
+        audit::AuditEventType::AuditExported,
+        Some("audit_log"),
+        None,
+        &format!(
+            "format={:?}, events={}, sha256={}, path={}",
+            format, event_count, sha256, path
+        ),
+    )
+    .ok();
+
+    Ok(AuditExportResult {
+        path,
+        format,
+        event_count,
+        sha256,
+    })
+}
+
+/// Verify a signed JSON audit export was produced, unmodified, by this installation
+#[tauri::command]
+pub async fn verify_audit_export(state: State<'_, AppState>, path: String) -> Result<bool, String> {
+    let contents = std::fs::read(&path).map_err(|e| format!("Error al leer archivo: {}", e))?;
+    let export: SignedAuditExport = serde_json::from_slice(&contents)
+        .map_err(|e| format!("Formato de exportación inválido: {}", e))?;
+
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    authorize(&conn, &state)?;
+
+    if export.public_key != audit::public_key(&conn)? {
+        return Ok(false);
+    }
+
+    audit::verify_export(&export)
+}
+
+/// Public key auditors use to verify signed exports from this installation
+#[tauri::command]
+pub async fn get_audit_public_key(state: State<'_, AppState>) -> Result<String, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    authorize(&conn, &state)?;
+
+    audit::public_key(&conn)
+}
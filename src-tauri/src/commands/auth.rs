@@ -13,7 +13,7 @@ use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager, State};
 use uuid::Uuid;
 
-use crate::security::audit;
+use crate::security::{audit, license};
 use crate::state::AppState;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,6 +64,32 @@ pub fn verify_password(password: &str, hash: &str) -> bool {
         .is_ok()
 }
 
+/// Ensure a user has one of the given roles
+pub fn require_role(
+    conn: &rusqlite::Connection,
+    user_id: &str,
+    roles: &[&str],
+) -> Result<(), String> {
+    let role: String = conn
+        .query_row(
+            "SELECT role FROM users WHERE id = ?1 AND is_active = 1",
+            [user_id],
+            |row| row.get(0),
+        )
+        .map_err(|_| "Usuario no encontrado o inactivo".to_string())?;
+
+    if roles.contains(&role.as_str()) {
+        Ok(())
+    } else {
+        Err("Acceso denegado: permisos insuficientes".to_string())
+    }
+}
+
+/// Ensure a user is an administrator
+pub fn require_admin(conn: &rusqlite::Connection, user_id: &str) -> Result<(), String> {
+    require_role(conn, user_id, &["admin"])
+}
+
 /// Login with email and password
 #[tauri::command]
 pub async fn login(
@@ -238,6 +264,7 @@ pub async fn register_user(
 
     {
         let conn = state.db.lock().map_err(|e| e.to_string())?;
+        license::require_seat(&conn)?;
 
         conn.execute(
             r#"
@@ -430,19 +457,25 @@ pub async fn setup_initial_admin(
         *tenant_id_lock = Some(tenant_id_clone.clone());
     }
 
-    // Sync installation to Supabase (non-blocking - if fails, local installation continues)
-    let sync_result = crate::services::sync::sync_installation_to_cloud(
-        &state.supabase,
-        &state.db,
-        &tenant_id_clone,
-    )
-    .await;
+    // Register the installation with the sync backend (non-blocking - if it fails or
+    // none is configured yet, the local installation continues)
+    let sync_result = match state.sync_backend.ready(&state.db, &tenant_id_clone).await {
+        Ok(backend) => {
+            crate::services::sync::sync_installation_to_cloud(
+                backend.as_ref(),
+                &state.db,
+                &tenant_id_clone,
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
 
     match sync_result {
-        Ok(_) => println!("✅ Installation registered in Supabase"),
+        Ok(_) => println!("✅ Installation registered with the sync backend"),
         Err(e) => {
             eprintln!(
-                "⚠️ Warning: Failed to sync with Supabase (local installation OK): {}",
+                "⚠️ Warning: Failed to register installation (local installation OK): {}",
                 e
             );
             // Continue anyway - local installation is complete
@@ -0,0 +1,123 @@
+//! Backup Commands
+//!
+//! Encrypted database backups, backup settings and restore.
+
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+use crate::commands::auth::require_admin;
+use crate::models::backup::{BackupInfo, BackupManifest, BackupSettings, UpdateBackupSettingsDto};
+use crate::services::backup;
+use crate::state::AppState;
+
+/// Default backup folder inside the app data directory
+pub fn default_backup_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Error al obtener directorio de la app: {}", e))?;
+
+    Ok(app_dir.join("backups"))
+}
+
+/// Get backup schedule, retention and passphrase status
+#[tauri::command]
+pub async fn get_backup_settings(state: State<'_, AppState>) -> Result<BackupSettings, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    backup::get_settings(&conn).map_err(|e| e.to_string())
+}
+
+/// Update backup settings or set a new recovery passphrase (admin only)
+#[tauri::command]
+pub async fn update_backup_settings(
+    state: State<'_, AppState>,
+    data: UpdateBackupSettingsDto,
+) -> Result<BackupSettings, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    let user_id = state.require_user()?;
+    require_admin(&conn, &user_id)?;
+
+    backup::update_settings(&conn, data).map_err(|e| e.to_string())
+}
+
+/// Create a backup immediately
+#[tauri::command]
+pub async fn run_backup_now(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<BackupInfo, String> {
+    let folder = default_backup_dir(&app)?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    backup::create_backup(&conn, &folder, Some(&user_id)).map_err(|e| e.to_string())
+}
+
+/// List available backups, newest first
+#[tauri::command]
+pub async fn list_backups(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Vec<BackupInfo>, String> {
+    let folder = default_backup_dir(&app)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    backup::list_backups(&conn, &folder).map_err(|e| e.to_string())
+}
+
+/// Check a backup can be restored (passphrase, checksum, schema and
+/// invoice chain)
+#[tauri::command]
+pub async fn verify_backup(
+    state: State<'_, AppState>,
+    path: String,
+    passphrase: String,
+) -> Result<BackupManifest, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    backup::verify_backup(&conn, Path::new(&path), &passphrase).map_err(|e| e.to_string())
+}
+
+/// Restore a backup over the current database (admin only)
+#[tauri::command]
+pub async fn restore_backup(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    passphrase: String,
+) -> Result<BackupManifest, String> {
+    let folder = default_backup_dir(&app)?;
+    let user_id = state.require_user()?;
+    let tenant_id = state.require_tenant().ok();
+    let mut conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    require_admin(&conn, &user_id)?;
+
+    backup::restore_backup(
+        &mut conn,
+        &folder,
+        Path::new(&path),
+        &passphrase,
+        tenant_id.as_deref(),
+        Some(&user_id),
+    )
+    .map_err(|e| e.to_string())
+}
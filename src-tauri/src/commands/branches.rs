@@ -0,0 +1,259 @@
+//! Branch Commands
+//!
+//! Switching the active branch, branch access, stock across branches,
+//! inter-branch transfers and consolidated reports.
+
+use crate::commands::auth::require_admin;
+use crate::models::branch::{
+    Branch, BranchStock, ConsolidatedReport, CreateStockTransferDto, StockTransfer,
+};
+use crate::security::audit::{self, AuditEventType};
+use crate::services::branches;
+use crate::state::AppState;
+use tauri::State;
+
+/// Branches the current user can operate
+#[tauri::command]
+pub async fn list_my_branches(state: State<'_, AppState>) -> Result<Vec<Branch>, String> {
+    let user_id = state.require_user()?;
+    let tenant_id = state.require_tenant().ok();
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    branches::list_branches(&conn, &user_id, tenant_id.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Operate on another branch
+#[tauri::command]
+pub async fn switch_tenant(
+    state: State<'_, AppState>,
+    tenant_id: String,
+) -> Result<Branch, String> {
+    let user_id = state.require_user()?;
+    let previous = state.require_tenant().ok();
+
+    let mut branch = {
+        let conn = state
+            .db
+            .lock()
+            .map_err(|_| "Error al acceder a la base de datos")?;
+        let branch =
+            branches::require_access(&conn, &user_id, &tenant_id).map_err(|e| e.to_string())?;
+
+        audit::log_event(
+            &conn,
+            Some(&tenant_id),
+            Some(&user_id),
+            AuditEventType::TenantSwitched,
+            Some("tenant"),
+            Some(&tenant_id),
+            &format!("from={}", previous.as_deref().unwrap_or("")),
+        )
+        .ok();
+        branch
+    };
+
+    *state
+        .tenant_id
+        .lock()
+        .map_err(|_| "Error al acceder al tenant")? = Some(tenant_id);
+    branch.is_current = true;
+    Ok(branch)
+}
+
+/// Create a branch in the organization (admin)
+#[tauri::command]
+pub async fn create_branch(state: State<'_, AppState>, name: String) -> Result<Branch, String> {
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    require_admin(&conn, &user_id)?;
+
+    let branch = branches::create_branch(&conn, &user_id, &name).map_err(|e| e.to_string())?;
+    crate::commands::units::seed_default_units(&conn, &branch.id).ok();
+    crate::commands::product_types::seed_default_product_types(&conn, &branch.id).ok();
+
+    audit::log_event(
+        &conn,
+        Some(&branch.id),
+        Some(&user_id),
+        AuditEventType::BranchCreated,
+        Some("tenant"),
+        Some(&branch.id),
+        &branch.name,
+    )
+    .ok();
+    Ok(branch)
+}
+
+/// Grant or revoke a user's access to a branch (admin)
+#[tauri::command]
+pub async fn set_branch_access(
+    state: State<'_, AppState>,
+    user_id: String,
+    tenant_id: String,
+    allowed: bool,
+) -> Result<(), String> {
+    let admin_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    require_admin(&conn, &admin_id)?;
+
+    branches::set_branch_access(&conn, &admin_id, &user_id, &tenant_id, allowed)
+        .map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&admin_id),
+        AuditEventType::BranchAccessChanged,
+        Some("user"),
+        Some(&user_id),
+        &format!("tenant_id={}, allowed={}", tenant_id, allowed),
+    )
+    .ok();
+    Ok(())
+}
+
+/// Stock of a product of the active branch in every branch that carries it
+#[tauri::command]
+pub async fn get_branch_stock(
+    state: State<'_, AppState>,
+    product_id: String,
+) -> Result<Vec<BranchStock>, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    branches::branch_stock(&conn, &tenant_id, &product_id).map_err(|e| e.to_string())
+}
+
+/// Transfers sent from or to the active branch
+#[tauri::command]
+pub async fn list_stock_transfers(
+    state: State<'_, AppState>,
+    status: Option<String>,
+) -> Result<Vec<StockTransfer>, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    branches::list_transfers(&conn, &tenant_id, status.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Ship stock from the active branch to another one
+#[tauri::command]
+pub async fn create_stock_transfer(
+    state: State<'_, AppState>,
+    data: CreateStockTransferDto,
+) -> Result<StockTransfer, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    let transfer =
+        branches::create_transfer(&conn, &tenant_id, &user_id, data).map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        AuditEventType::StockTransferShipped,
+        Some("stock_transfer"),
+        Some(&transfer.id),
+        &format!(
+            "to={}, items={}",
+            transfer.to_tenant_id,
+            transfer.items.len()
+        ),
+    )
+    .ok();
+    Ok(transfer)
+}
+
+/// Receive an in-transit transfer in the active branch
+#[tauri::command]
+pub async fn receive_stock_transfer(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<StockTransfer, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    let transfer =
+        branches::receive_transfer(&conn, &tenant_id, &user_id, &id).map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        AuditEventType::StockTransferReceived,
+        Some("stock_transfer"),
+        Some(&id),
+        &format!("from={}", transfer.from_tenant_id),
+    )
+    .ok();
+    Ok(transfer)
+}
+
+/// Cancel an in-transit transfer; the stock returns to the active branch
+#[tauri::command]
+pub async fn cancel_stock_transfer(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<StockTransfer, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    let transfer =
+        branches::cancel_transfer(&conn, &tenant_id, &user_id, &id).map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        AuditEventType::StockTransferCancelled,
+        Some("stock_transfer"),
+        Some(&id),
+        &format!("to={}", transfer.to_tenant_id),
+    )
+    .ok();
+    Ok(transfer)
+}
+
+/// Sales, stock value and transfers of every branch the user can access
+#[tauri::command]
+pub async fn get_consolidated_report(
+    state: State<'_, AppState>,
+    from_date: Option<String>,
+    to_date: Option<String>,
+) -> Result<ConsolidatedReport, String> {
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    branches::consolidated_report(&conn, &user_id, from_date, to_date).map_err(|e| e.to_string())
+}
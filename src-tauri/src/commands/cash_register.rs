@@ -1,8 +1,10 @@
+use crate::commands::settings;
 use crate::models::cash_register::{
-    AddMovementDto, CashMovement, CashRegister, CashRegisterSession, CloseSessionDto,
-    OpenSessionDto,
+    AddMovementDto, CashDenomination, CashMovement, CashRegister, CashRegisterSession,
+    CloseSessionDto, OpenSessionDto, SessionReport, SessionReportFormat,
 };
-use crate::services::cash_register;
+use crate::security::{license, time_guard};
+use crate::services::{cash_register, cash_reports, escpos, pdf_generator};
 use crate::state::AppState;
 use tauri::{command, State};
 
@@ -17,6 +19,19 @@ pub async fn create_register(
     cash_register::create_register(&conn, &tenant_id, &name).map_err(|e| e.to_string())
 }
 
+#[command]
+pub async fn set_register_warehouse(
+    state: State<'_, AppState>,
+    register_id: String,
+    warehouse_id: String,
+) -> Result<(), String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let tenant_id = state.require_tenant()?;
+
+    cash_register::set_register_warehouse(&conn, &tenant_id, &register_id, &warehouse_id)
+        .map_err(|e| e.to_string())
+}
+
 #[command]
 pub async fn open_session(
     state: State<'_, AppState>,
@@ -25,6 +40,7 @@ pub async fn open_session(
     let conn = state.db.lock().map_err(|e| e.to_string())?;
     let tenant_id = state.require_tenant()?;
     let user_id = state.require_user()?;
+    license::require_module(&conn, "invoicing")?;
 
     cash_register::open_session(&conn, &tenant_id, &user_id, data).map_err(|e| e.to_string())
 }
@@ -36,8 +52,11 @@ pub async fn close_session(
 ) -> Result<CashRegisterSession, String> {
     let conn = state.db.lock().map_err(|e| e.to_string())?;
     let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    time_guard::verify_time_integrity(&conn)?;
+    license::require_module(&conn, "invoicing")?;
 
-    cash_register::close_session(&conn, &tenant_id, data).map_err(|e| e.to_string())
+    cash_register::close_session(&conn, &tenant_id, &user_id, data).map_err(|e| e.to_string())
 }
 
 #[command]
@@ -48,6 +67,7 @@ pub async fn add_movement(
     let conn = state.db.lock().map_err(|e| e.to_string())?;
     let tenant_id = state.require_tenant()?;
     let user_id = state.require_user()?;
+    license::require_module(&conn, "invoicing")?;
 
     cash_register::add_movement(&conn, &tenant_id, &user_id, data).map_err(|e| e.to_string())
 }
@@ -70,3 +90,113 @@ pub async fn list_registers(state: State<'_, AppState>) -> Result<Vec<CashRegist
 
     cash_register::list_registers(&conn, &tenant_id).map_err(|e| e.to_string())
 }
+
+#[command]
+pub async fn list_cash_denominations() -> Result<Vec<CashDenomination>, String> {
+    Ok(cash_reports::denominations())
+}
+
+/// Mid-shift X-report; the session stays open
+#[command]
+pub async fn take_x_report(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<SessionReport, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    time_guard::verify_time_integrity(&conn)?;
+    license::require_module(&conn, "invoicing")?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    cash_reports::take_report(&conn, &tenant_id, &user_id, &session_id, "X", &now)
+        .map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn list_session_reports(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<SessionReport>, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let tenant_id = state.require_tenant()?;
+
+    cash_reports::list_reports(&conn, &tenant_id, &session_id).map_err(|e| e.to_string())
+}
+
+/// Write a stored report as a PDF or a plain text thermal ticket of
+/// `width` columns (48 by default, an 80 mm roll)
+#[command]
+pub async fn export_session_report(
+    state: State<'_, AppState>,
+    report_id: String,
+    format: SessionReportFormat,
+    path: String,
+    width: Option<usize>,
+) -> Result<String, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let tenant_id = state.require_tenant()?;
+
+    let report =
+        cash_reports::get_report(&conn, &tenant_id, &report_id).map_err(|e| e.to_string())?;
+    let contents = match format {
+        SessionReportFormat::Pdf => pdf_generator::text_document(
+            &format!("Reporte {} #{}", report.report_type, report.report_number),
+            &cash_reports::render_lines(&report, 80),
+        )?,
+        SessionReportFormat::Ticket => {
+            let mut text =
+                cash_reports::render_lines(&report, width.unwrap_or(48).max(24)).join("\n");
+            text.push('\n');
+            text.into_bytes()
+        }
+    };
+    std::fs::write(&path, contents).map_err(|e| format!("Error al escribir archivo: {}", e))?;
+    Ok(path)
+}
+
+/// Print a deposit or withdrawal slip on the thermal printer
+#[command]
+pub async fn print_cash_movement(
+    state: State<'_, AppState>,
+    movement_id: String,
+    printer: Option<String>,
+) -> Result<String, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let tenant_id = state.require_tenant()?;
+
+    let (movement, register_name) =
+        cash_register::get_movement(&conn, &tenant_id, &movement_id).map_err(|e| e.to_string())?;
+    let settings = settings::query_company_settings(&conn, &tenant_id)
+        .map_err(|e| format!("Error al obtener configuración: {}", e))?;
+
+    let target =
+        escpos::resolve_target(&settings, printer.as_deref()).map_err(|e| e.to_string())?;
+    let logo = escpos::load_logo(&settings);
+    let ticket = escpos::movement_slip(&settings, logo.as_ref(), &movement, &register_name);
+    escpos::send(&target, &ticket).map_err(|e| e.to_string())?;
+    Ok(target.to_string())
+}
+
+/// Print a stored X or Z report on the thermal printer
+#[command]
+pub async fn print_session_report(
+    state: State<'_, AppState>,
+    report_id: String,
+    printer: Option<String>,
+) -> Result<String, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let tenant_id = state.require_tenant()?;
+
+    let report =
+        cash_reports::get_report(&conn, &tenant_id, &report_id).map_err(|e| e.to_string())?;
+    let settings = settings::query_company_settings(&conn, &tenant_id)
+        .map_err(|e| format!("Error al obtener configuración: {}", e))?;
+
+    let target =
+        escpos::resolve_target(&settings, printer.as_deref()).map_err(|e| e.to_string())?;
+    let logo = escpos::load_logo(&settings);
+    let ticket = escpos::session_report(&settings, logo.as_ref(), &report);
+    escpos::send(&target, &ticket).map_err(|e| e.to_string())?;
+    Ok(target.to_string())
+}
@@ -1,6 +1,11 @@
 //! Discount Commands
 
+use crate::models::discount::{
+    CampaignPerformance, CampaignReportFilters, CreateCouponDto, DiscountBundleItem,
+    DiscountCoupon, DiscountRedemption, GenerateCouponsDto,
+};
 use crate::models::{CreateDiscountDto, Discount, UpdateDiscountDto};
+use crate::services::discounts;
 use crate::state::AppState;
 use tauri::State;
 use uuid::Uuid;
@@ -15,6 +20,42 @@ fn get_tenant_id(state: &State<'_, AppState>) -> Result<String, String> {
         .ok_or_else(|| "No hay tenant activo".to_string())
 }
 
+const DISCOUNT_COLUMNS: &str =
+    "id, tenant_id, name, discount_type, value, applies_to, target_id, min_quantity,
+     max_uses, times_used, start_date, end_date, is_active, created_at, updated_at,
+     stackable, priority, campaign, requires_coupon, per_client_limit, buy_quantity,
+     get_quantity, start_time, end_time, weekdays";
+
+fn map_discount(row: &rusqlite::Row) -> rusqlite::Result<Discount> {
+    Ok(Discount {
+        id: row.get(0)?,
+        tenant_id: row.get(1)?,
+        name: row.get(2)?,
+        discount_type: row.get(3)?,
+        value: row.get(4)?,
+        applies_to: row.get(5)?,
+        target_id: row.get(6)?,
+        min_quantity: row.get(7)?,
+        max_uses: row.get(8)?,
+        times_used: row.get(9)?,
+        start_date: row.get(10)?,
+        end_date: row.get(11)?,
+        is_active: row.get::<_, i32>(12)? == 1,
+        created_at: row.get(13)?,
+        updated_at: row.get(14)?,
+        stackable: row.get::<_, i32>(15)? == 1,
+        priority: row.get(16)?,
+        campaign: row.get(17)?,
+        requires_coupon: row.get::<_, i32>(18)? == 1,
+        per_client_limit: row.get(19)?,
+        buy_quantity: row.get(20)?,
+        get_quantity: row.get(21)?,
+        start_time: row.get(22)?,
+        end_time: row.get(23)?,
+        weekdays: row.get(24)?,
+    })
+}
+
 /// List all discounts
 #[tauri::command]
 pub async fn list_discounts(
@@ -27,40 +68,20 @@ pub async fn list_discounts(
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
 
-    let query = if active_only.unwrap_or(false) {
-        "SELECT id, tenant_id, name, discount_type, value, applies_to, target_id, min_quantity,
-                max_uses, times_used, start_date, end_date, is_active, created_at, updated_at
-         FROM discounts WHERE tenant_id = ?1 AND is_active = 1
-         ORDER BY name ASC"
-    } else {
-        "SELECT id, tenant_id, name, discount_type, value, applies_to, target_id, min_quantity,
-                max_uses, times_used, start_date, end_date, is_active, created_at, updated_at
-         FROM discounts WHERE tenant_id = ?1
-         ORDER BY name ASC"
-    };
+    let query = format!(
+        "SELECT {} FROM discounts WHERE tenant_id = ?1 {} ORDER BY name ASC",
+        DISCOUNT_COLUMNS,
+        if active_only.unwrap_or(false) {
+            "AND is_active = 1"
+        } else {
+            ""
+        }
+    );
 
-    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
 
     let discounts = stmt
-        .query_map([&tenant_id], |row| {
-            Ok(Discount {
-                id: row.get(0)?,
-                tenant_id: row.get(1)?,
-                name: row.get(2)?,
-                discount_type: row.get(3)?,
-                value: row.get(4)?,
-                applies_to: row.get(5)?,
-                target_id: row.get(6)?,
-                min_quantity: row.get(7)?,
-                max_uses: row.get(8)?,
-                times_used: row.get(9)?,
-                start_date: row.get(10)?,
-                end_date: row.get(11)?,
-                is_active: row.get::<_, i32>(12)? == 1,
-                created_at: row.get(13)?,
-                updated_at: row.get(14)?,
-            })
-        })
+        .query_map([&tenant_id], map_discount)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
@@ -78,29 +99,12 @@ pub async fn get_discount(state: State<'_, AppState>, id: String) -> Result<Disc
         .map_err(|_| "Error al acceder a la base de datos")?;
 
     conn.query_row(
-        "SELECT id, tenant_id, name, discount_type, value, applies_to, target_id, min_quantity,
-                max_uses, times_used, start_date, end_date, is_active, created_at, updated_at
-         FROM discounts WHERE id = ?1 AND tenant_id = ?2",
+        &format!(
+            "SELECT {} FROM discounts WHERE id = ?1 AND tenant_id = ?2",
+            DISCOUNT_COLUMNS
+        ),
         [&id, &tenant_id],
-        |row| {
-            Ok(Discount {
-                id: row.get(0)?,
-                tenant_id: row.get(1)?,
-                name: row.get(2)?,
-                discount_type: row.get(3)?,
-                value: row.get(4)?,
-                applies_to: row.get(5)?,
-                target_id: row.get(6)?,
-                min_quantity: row.get(7)?,
-                max_uses: row.get(8)?,
-                times_used: row.get(9)?,
-                start_date: row.get(10)?,
-                end_date: row.get(11)?,
-                is_active: row.get::<_, i32>(12)? == 1,
-                created_at: row.get(13)?,
-                updated_at: row.get(14)?,
-            })
-        },
+        map_discount,
     )
     .map_err(|e| format!("Error al obtener descuento: {}", e))
 }
@@ -114,6 +118,13 @@ pub async fn create_discount(
     let tenant_id = get_tenant_id(&state)?;
     let id = Uuid::new_v4().to_string();
     let now = chrono::Utc::now().to_rfc3339();
+    discounts::validate_schedule(
+        data.start_time.as_deref(),
+        data.end_time.as_deref(),
+        data.weekdays.as_deref(),
+    )
+    .map_err(|e| e.to_string())?;
+    discounts::validate_bundle(&data.discount_type, &data.applies_to).map_err(|e| e.to_string())?;
 
     {
         let conn = state
@@ -123,8 +134,11 @@ pub async fn create_discount(
 
         conn.execute(
             "INSERT INTO discounts (id, tenant_id, name, discount_type, value, applies_to, target_id,
-             min_quantity, max_uses, times_used, start_date, end_date, is_active, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 0, ?10, ?11, ?12, ?13, ?13)",
+             min_quantity, max_uses, times_used, start_date, end_date, is_active, created_at, updated_at,
+             stackable, priority, campaign, requires_coupon, per_client_limit, buy_quantity,
+             get_quantity, start_time, end_time, weekdays)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 0, ?10, ?11, ?12, ?13, ?13, ?14, ?15,
+                     ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)",
             rusqlite::params![
                 &id,
                 &tenant_id,
@@ -138,7 +152,17 @@ pub async fn create_discount(
                 &data.start_date,
                 &data.end_date,
                 if data.is_active { 1 } else { 0 },
-                &now
+                &now,
+                if data.stackable.unwrap_or(true) { 1 } else { 0 },
+                data.priority.unwrap_or(0),
+                &data.campaign,
+                if data.requires_coupon.unwrap_or(false) { 1 } else { 0 },
+                data.per_client_limit,
+                data.buy_quantity,
+                data.get_quantity,
+                &data.start_time,
+                &data.end_time,
+                &data.weekdays
             ],
         )
         .map_err(|e| format!("Error al crear descuento: {}", e))?;
@@ -155,14 +179,36 @@ pub async fn update_discount(
     data: UpdateDiscountDto,
 ) -> Result<Discount, String> {
     let tenant_id = get_tenant_id(&state)?;
+    discounts::validate_schedule(
+        data.start_time.as_deref(),
+        data.end_time.as_deref(),
+        data.weekdays.as_deref(),
+    )
+    .map_err(|e| e.to_string())?;
     {
         let conn = state
             .db
             .lock()
             .map_err(|_| "Error al acceder a la base de datos")?;
 
+        if data.discount_type.is_some() || data.applies_to.is_some() {
+            let (discount_type, applies_to): (String, String) = conn
+                .query_row(
+                    "SELECT discount_type, applies_to FROM discounts WHERE id = ?1 AND tenant_id = ?2",
+                    [&id, &tenant_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .map_err(|e| format!("Descuento no encontrado: {}", e))?;
+            discounts::validate_bundle(
+                data.discount_type.as_deref().unwrap_or(&discount_type),
+                data.applies_to.as_deref().unwrap_or(&applies_to),
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
         let now = chrono::Utc::now().to_rfc3339();
         let mut set_clauses = vec![format!("updated_at = '{}'", now)];
+        let mut values: Vec<String> = Vec::new();
 
         if let Some(ref name) = data.name {
             set_clauses.push(format!("name = '{}'", name.replace('\'', "''")));
@@ -194,6 +240,40 @@ pub async fn update_discount(
         if let Some(is_active) = data.is_active {
             set_clauses.push(format!("is_active = {}", if is_active { 1 } else { 0 }));
         }
+        if let Some(stackable) = data.stackable {
+            set_clauses.push(format!("stackable = {}", if stackable { 1 } else { 0 }));
+        }
+        if let Some(priority) = data.priority {
+            set_clauses.push(format!("priority = {}", priority));
+        }
+        if let Some(ref campaign) = data.campaign {
+            set_clauses.push(format!("campaign = '{}'", campaign.replace('\'', "''")));
+        }
+        if let Some(requires_coupon) = data.requires_coupon {
+            set_clauses.push(format!(
+                "requires_coupon = {}",
+                if requires_coupon { 1 } else { 0 }
+            ));
+        }
+        if let Some(limit) = data.per_client_limit {
+            set_clauses.push(format!("per_client_limit = {}", limit));
+        }
+        if let Some(buy_quantity) = data.buy_quantity {
+            set_clauses.push(format!("buy_quantity = {}", buy_quantity));
+        }
+        if let Some(get_quantity) = data.get_quantity {
+            set_clauses.push(format!("get_quantity = {}", get_quantity));
+        }
+        for (column, value) in [
+            ("start_time", &data.start_time),
+            ("end_time", &data.end_time),
+            ("weekdays", &data.weekdays),
+        ] {
+            if let Some(value) = value {
+                values.push(value.clone());
+                set_clauses.push(format!("{} = ?{}", column, values.len()));
+            }
+        }
 
         let query = format!(
             "UPDATE discounts SET {} WHERE id = '{}' AND tenant_id = '{}'",
@@ -202,7 +282,7 @@ pub async fn update_discount(
             tenant_id.replace('\'', "''")
         );
 
-        conn.execute(&query, [])
+        conn.execute(&query, rusqlite::params_from_iter(values.iter()))
             .map_err(|e| format!("Error al actualizar descuento: {}", e))?;
     }
 
@@ -228,20 +308,148 @@ pub async fn delete_discount(state: State<'_, AppState>, id: String) -> Result<(
     Ok(())
 }
 
-/// Increment the times_used counter for a discount
+/// Use a discount outside an invoice; refused if inactive, expired or
+/// exhausted. Invoices use their discounts when issued.
 #[tauri::command]
 pub async fn use_discount(state: State<'_, AppState>, id: String) -> Result<(), String> {
     let tenant_id = get_tenant_id(&state)?;
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
     let conn = state
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
+    let now = chrono::Utc::now().to_rfc3339();
 
-    conn.execute(
-        "UPDATE discounts SET times_used = times_used + 1 WHERE id = ?1 AND tenant_id = ?2",
-        [&id, &tenant_id],
+    discounts::redeem_manual(&conn, &tenant_id, &id, user_id.as_deref(), &now)
+        .map_err(|e| format!("Error al usar descuento: {}", e))
+}
+
+/// Redemption history of a discount or an invoice
+#[tauri::command]
+pub async fn list_discount_redemptions(
+    state: State<'_, AppState>,
+    discount_id: Option<String>,
+    invoice_id: Option<String>,
+) -> Result<Vec<DiscountRedemption>, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    discounts::list_redemptions(
+        &conn,
+        &tenant_id,
+        discount_id.as_deref(),
+        invoice_id.as_deref(),
     )
-    .map_err(|e| format!("Error al usar descuento: {}", e))?;
+    .map_err(|e| e.to_string())
+}
 
-    Ok(())
+/// Create a coupon code for a discount
+#[tauri::command]
+pub async fn create_discount_coupon(
+    state: State<'_, AppState>,
+    data: CreateCouponDto,
+) -> Result<DiscountCoupon, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    discounts::create_coupon(&conn, &tenant_id, data).map_err(|e| e.to_string())
+}
+
+/// Generate a batch of random coupon codes
+#[tauri::command]
+pub async fn generate_discount_coupons(
+    state: State<'_, AppState>,
+    data: GenerateCouponsDto,
+) -> Result<Vec<DiscountCoupon>, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    discounts::generate_coupons(&conn, &tenant_id, data).map_err(|e| e.to_string())
+}
+
+/// List the coupon codes of a discount
+#[tauri::command]
+pub async fn list_discount_coupons(
+    state: State<'_, AppState>,
+    discount_id: String,
+) -> Result<Vec<DiscountCoupon>, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    discounts::list_coupons(&conn, &tenant_id, &discount_id).map_err(|e| e.to_string())
+}
+
+/// Enable or disable a coupon code
+#[tauri::command]
+pub async fn set_discount_coupon_active(
+    state: State<'_, AppState>,
+    id: String,
+    is_active: bool,
+) -> Result<(), String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    discounts::set_coupon_active(&conn, &tenant_id, &id, is_active).map_err(|e| e.to_string())
+}
+
+/// Set the products of a bundle discount
+#[tauri::command]
+pub async fn set_discount_bundle_items(
+    state: State<'_, AppState>,
+    discount_id: String,
+    items: Vec<DiscountBundleItem>,
+) -> Result<(), String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    discounts::set_bundle_items(&conn, &tenant_id, &discount_id, &items).map_err(|e| e.to_string())
+}
+
+/// Get the products of a bundle discount
+#[tauri::command]
+pub async fn list_discount_bundle_items(
+    state: State<'_, AppState>,
+    discount_id: String,
+) -> Result<Vec<DiscountBundleItem>, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    discounts::list_bundle_items(&conn, &tenant_id, &discount_id).map_err(|e| e.to_string())
+}
+
+/// Uses, discounted amount and revenue per discount
+#[tauri::command]
+pub async fn get_campaign_report(
+    state: State<'_, AppState>,
+    filters: Option<CampaignReportFilters>,
+) -> Result<Vec<CampaignPerformance>, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    discounts::campaign_report(&conn, &tenant_id, &filters.unwrap_or_default())
+        .map_err(|e| e.to_string())
 }
@@ -0,0 +1,77 @@
+//! Exchange Rate Commands
+
+use crate::models::exchange_rate::{ExchangeRate, ExchangeRateUpdate, RecordExchangeRateDto};
+use crate::security::audit::{self, AuditEventType};
+use crate::services::exchange_rates;
+use crate::state::AppState;
+use tauri::State;
+
+/// Record a rate; derived price lists are repriced right away
+#[tauri::command]
+pub async fn record_exchange_rate(
+    state: State<'_, AppState>,
+    data: RecordExchangeRateDto,
+) -> Result<ExchangeRateUpdate, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    let update =
+        exchange_rates::record(&conn, &tenant_id, &user_id, data).map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        AuditEventType::ExchangeRateRecorded,
+        Some("exchange_rate"),
+        Some(&update.rate.id),
+        &format!(
+            "currency={}, rate={}, rate_date={}, source={}, repriced_lists={}",
+            update.rate.currency,
+            update.rate.rate,
+            update.rate.rate_date,
+            update.rate.source.as_deref().unwrap_or(""),
+            update.batches.len()
+        ),
+    )
+    .ok();
+
+    Ok(update)
+}
+
+/// Recorded rates, newest first
+#[tauri::command]
+pub async fn list_exchange_rates(
+    state: State<'_, AppState>,
+    currency: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<ExchangeRate>, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    exchange_rates::list_rates(&conn, &tenant_id, currency.as_deref(), limit)
+        .map_err(|e| e.to_string())
+}
+
+/// Rate in effect today, in VES per unit of `currency`
+#[tauri::command]
+pub async fn get_current_exchange_rate(
+    state: State<'_, AppState>,
+    currency: String,
+) -> Result<Option<f64>, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    exchange_rates::latest_rate(&conn, &tenant_id, &currency.to_uppercase())
+        .map_err(|e| e.to_string())
+}
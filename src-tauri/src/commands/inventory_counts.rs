@@ -0,0 +1,131 @@
+//! Physical Inventory Count Commands
+
+use crate::commands::auth::require_admin;
+use crate::models::inventory_count::{
+    CountEntryDto, InventoryCount, InventoryCountDetail, InventoryCountLine, StartCountDto,
+};
+use crate::security::audit::{self, AuditEventType};
+use crate::services::inventory_counts;
+use crate::state::AppState;
+use tauri::State;
+
+/// Start counting a warehouse, or a category of it (cycle count)
+#[tauri::command]
+pub async fn start_inventory_count(
+    state: State<'_, AppState>,
+    data: StartCountDto,
+) -> Result<InventoryCount, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    inventory_counts::start_count(&conn, &tenant_id, &user_id, data).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_inventory_counts(
+    state: State<'_, AppState>,
+    status: Option<String>,
+) -> Result<Vec<InventoryCount>, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    inventory_counts::list_counts(&conn, &tenant_id, status.as_deref()).map_err(|e| e.to_string())
+}
+
+/// A count with its lines, or only the lines with a variance
+#[tauri::command]
+pub async fn get_inventory_count(
+    state: State<'_, AppState>,
+    id: String,
+    variances_only: Option<bool>,
+) -> Result<InventoryCountDetail, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    inventory_counts::get_count(&conn, &tenant_id, &id, variances_only.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+/// Capture a counted quantity by line, product or barcode scan
+#[tauri::command]
+pub async fn record_inventory_count(
+    state: State<'_, AppState>,
+    id: String,
+    data: CountEntryDto,
+) -> Result<InventoryCountLine, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    inventory_counts::record_count(&conn, &tenant_id, &user_id, &id, data)
+        .map_err(|e| e.to_string())
+}
+
+/// Post the variances of a count (admin)
+#[tauri::command]
+pub async fn approve_inventory_count(
+    state: State<'_, AppState>,
+    id: String,
+    zero_uncounted: Option<bool>,
+) -> Result<InventoryCount, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    require_admin(&conn, &user_id)?;
+
+    let (count, adjusted) = inventory_counts::approve_count(
+        &conn,
+        &tenant_id,
+        &user_id,
+        &id,
+        zero_uncounted.unwrap_or(false),
+    )
+    .map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        AuditEventType::InventoryAdjusted,
+        Some("inventory_count"),
+        Some(&id),
+        &format!(
+            "warehouse_id={}, lines_adjusted={}, variance_quantity={}, variance_cost={:.2}",
+            count.warehouse_id, adjusted, count.variance_quantity, count.variance_cost
+        ),
+    )
+    .ok();
+    Ok(count)
+}
+
+/// Discard a count without adjusting stock
+#[tauri::command]
+pub async fn cancel_inventory_count(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<InventoryCount, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    inventory_counts::cancel_count(&conn, &tenant_id, &user_id, &id).map_err(|e| e.to_string())
+}
@@ -1,12 +1,18 @@
 //! Invoice Commands
 
+use crate::commands::{payments, settings};
+use crate::models::cash_register::{PosSale, PosSaleDto};
+use crate::models::pricing::{PriceQuery, ResolvedPrice};
 use crate::models::{
     CreateInvoiceDto, CreateInvoiceItemDto, Invoice, InvoiceFilters, InvoiceItem, UpdateInvoiceDto,
 };
-use crate::services::tax_calculator;
+use crate::security::{audit, license, secure_chain, time_guard};
+use crate::services::{
+    cash_register, discounts, escpos, exchange_rates, inventory, pricing, reservations,
+};
 use crate::state::AppState;
-use rust_decimal::prelude::*;
-use rust_decimal_macros::dec;
+use rusqlite::Connection;
+use rust_decimal::prelude::ToPrimitive;
 use tauri::State;
 use uuid::Uuid;
 
@@ -55,6 +61,478 @@ fn get_user_id(state: &State<'_, AppState>) -> Result<String, String> {
         .ok_or_else(|| "No hay usuario activo".to_string())
 }
 
+const INVOICE_COLUMNS: &str =
+    "id, tenant_id, invoice_number, invoice_type, status, client_id, client_name,
+     client_tax_id, client_address, price_list_id, currency, exchange_rate, issue_date,
+     due_date, payment_terms, subtotal, discount_total, tax_total, total, paid_amount,
+     notes, created_by, created_at, updated_at, warehouse_id, payment_method, coupon_codes,
+     register_id, session_id, change_amount, change_currency";
+
+fn map_invoice(row: &rusqlite::Row) -> rusqlite::Result<Invoice> {
+    Ok(Invoice {
+        id: row.get(0)?,
+        tenant_id: row.get(1)?,
+        invoice_number: row.get(2)?,
+        invoice_type: row.get(3)?,
+        status: row.get(4)?,
+        client_id: row.get(5)?,
+        client_name: row.get(6)?,
+        client_tax_id: row.get(7)?,
+        client_address: row.get(8)?,
+        price_list_id: row.get(9)?,
+        currency: row.get(10)?,
+        exchange_rate: row.get(11)?,
+        issue_date: row.get(12)?,
+        due_date: row.get(13)?,
+        payment_terms: row.get(14)?,
+        subtotal: row.get(15)?,
+        discount_total: row.get(16)?,
+        tax_total: row.get(17)?,
+        total: row.get(18)?,
+        paid_amount: row.get(19)?,
+        notes: row.get(20)?,
+        created_by: row.get(21)?,
+        created_at: row.get(22)?,
+        updated_at: row.get(23)?,
+        warehouse_id: row.get(24)?,
+        payment_method: row.get(25)?,
+        coupon_codes: row
+            .get::<_, Option<String>>(26)?
+            .map(|codes| split_codes(&codes))
+            .unwrap_or_default(),
+        register_id: row.get(27)?,
+        session_id: row.get(28)?,
+        change_amount: row.get(29)?,
+        change_currency: row.get(30)?,
+    })
+}
+
+/// Coupon codes are stored comma separated
+fn split_codes(codes: &str) -> Vec<String> {
+    codes
+        .split(',')
+        .map(|code| code.trim().to_uppercase())
+        .filter(|code| !code.is_empty())
+        .collect()
+}
+
+/// Who, when and how an invoice is priced
+struct PricingContext<'a> {
+    client_id: &'a str,
+    price_list_id: Option<&'a str>,
+    issue_date: &'a str,
+    payment_method: Option<&'a str>,
+    coupon_codes: &'a [String],
+}
+
+/// Client, price list, issue date, payment method and coupon codes of a
+/// stored invoice
+type StoredPricing = (
+    String,
+    Option<String>,
+    String,
+    Option<String>,
+    Option<String>,
+);
+
+/// Resolve price, discounts and tax of each item; client-side amounts are
+/// never trusted
+fn price_items(
+    conn: &Connection,
+    tenant_id: &str,
+    context: &PricingContext,
+    items: &[CreateInvoiceItemDto],
+) -> Result<Vec<ResolvedPrice>, String> {
+    let queries: Vec<PriceQuery> = items
+        .iter()
+        .map(|item| PriceQuery {
+            client_id: Some(context.client_id.to_string()),
+            price_list_id: context.price_list_id.map(str::to_string),
+            product_id: item.product_id.clone(),
+            variant_id: item.variant_id.clone(),
+            quantity: item.quantity,
+            date: Some(context.issue_date.to_string()),
+            payment_method: context.payment_method.map(str::to_string),
+            time: None,
+            coupon_codes: context.coupon_codes.to_vec(),
+        })
+        .collect();
+    pricing::resolve_all(conn, tenant_id, &queries).map_err(|e| e.to_string())
+}
+
+/// Insert priced items, with the rules applied to each, and return the
+/// invoice's (subtotal, discount_total, tax_total, total)
+fn insert_priced_items(
+    conn: &Connection,
+    invoice_id: &str,
+    lines: &[ResolvedPrice],
+    now: &str,
+) -> Result<(f64, f64, f64, f64), String> {
+    let mut subtotal = 0.0;
+    let mut discount_total = 0.0;
+    let mut tax_total = 0.0;
+
+    for line in lines {
+        // Cost is snapshotted for margin reports
+        let (code, description, unit_cost): (String, String, f64) = conn
+            .query_row(
+                "SELECT COALESCE(p.sku, ''), p.name,
+                        COALESCE(NULLIF(v.cost_price, 0), p.cost_price, 0)
+                 FROM products p LEFT JOIN product_variants v ON v.id = ?2
+                 WHERE p.id = ?1",
+                rusqlite::params![&line.product_id, &line.variant_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap_or(("".to_string(), "Producto".to_string(), 0.0));
+        let rules = serde_json::to_string(&line.rules).map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "INSERT INTO billing_invoice_items (id, invoice_id, product_id, variant_id, lot_id, code,
+             description, quantity, unit_price, discount_percent, discount_amount, tax_rate, tax_amount,
+             line_total, pricing_rules, updated_at, unit_cost)
+             VALUES (?1, ?2, ?3, ?4, NULL, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            rusqlite::params![
+                Uuid::new_v4().to_string(),
+                invoice_id,
+                &line.product_id,
+                &line.variant_id,
+                &code,
+                &description,
+                line.quantity,
+                line.unit_price,
+                line.discount_percent,
+                line.discount_amount,
+                line.tax_rate,
+                line.tax_amount,
+                line.total,
+                rules,
+                now,
+                unit_cost
+            ],
+        )
+        .map_err(|e| format!("Error al crear item de factura: {}", e))?;
+
+        subtotal += line.subtotal;
+        discount_total += line.discount_amount;
+        tax_total += line.tax_amount;
+    }
+
+    // Subtotal is already net of discounts
+    Ok((subtotal, discount_total, tax_total, subtotal + tax_total))
+}
+
+/// Move the stock of an invoice's items in its warehouse; `sign` is -1.0 to
+/// take it out and 1.0 to put it back
+fn move_invoice_stock(
+    conn: &rusqlite::Connection,
+    tenant_id: &str,
+    invoice_id: &str,
+    sign: f64,
+    user_id: Option<&str>,
+    now: &str,
+) -> Result<usize, String> {
+    let requested: Option<String> = conn
+        .query_row(
+            "SELECT warehouse_id FROM billing_invoices WHERE id = ?1",
+            [invoice_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let warehouse_id = inventory::resolve_warehouse(conn, tenant_id, requested.as_deref())
+        .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT product_id, variant_id, lot_id, quantity FROM billing_invoice_items WHERE invoice_id = ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let items: Vec<(String, Option<String>, Option<String>, f64)> = stmt
+        .query_map([invoice_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let info = inventory::MovementInfo {
+        reference_type: Some("invoice"),
+        reference_id: Some(invoice_id),
+        notes: None,
+        user_id,
+    };
+    for (product_id, variant_id, lot_id, quantity) in &items {
+        inventory::record(
+            conn,
+            &inventory::StockChange {
+                tenant_id,
+                warehouse_id: &warehouse_id,
+                product_id,
+                variant_id: variant_id.as_deref(),
+                lot_id: lot_id.as_deref(),
+                quantity: sign * quantity,
+            },
+            &info,
+            now,
+        )
+        .map_err(|e| format!("Error al mover stock: {}", e))?;
+    }
+    Ok(items.len())
+}
+
+/// (product, variant, quantity) of an invoice line
+type StockLine = (String, Option<String>, f64);
+
+/// Warehouse of an invoice and its stock lines
+fn invoice_stock_lines(
+    conn: &rusqlite::Connection,
+    tenant_id: &str,
+    invoice_id: &str,
+) -> Result<(String, Vec<StockLine>), String> {
+    let requested: Option<String> = conn
+        .query_row(
+            "SELECT warehouse_id FROM billing_invoices WHERE id = ?1",
+            [invoice_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let warehouse_id = inventory::resolve_warehouse(conn, tenant_id, requested.as_deref())
+        .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare("SELECT product_id, variant_id, quantity FROM billing_invoice_items WHERE invoice_id = ?1")
+        .map_err(|e| e.to_string())?;
+    let items = stmt
+        .query_map([invoice_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok((warehouse_id, items))
+}
+
+fn reservation_lines(items: &[StockLine]) -> Vec<reservations::ReservationLine<'_>> {
+    items
+        .iter()
+        .map(
+            |(product_id, variant_id, quantity)| reservations::ReservationLine {
+                product_id,
+                variant_id: variant_id.as_deref(),
+                quantity: *quantity,
+            },
+        )
+        .collect()
+}
+
+/// Fail if an invoice asks for more than its warehouse has once other
+/// documents' holds are taken out; its own hold does not count
+fn check_invoice_stock(
+    conn: &rusqlite::Connection,
+    tenant_id: &str,
+    invoice_id: &str,
+) -> Result<(), String> {
+    let (warehouse_id, items) = invoice_stock_lines(conn, tenant_id, invoice_id)?;
+    reservations::release_expired(conn, tenant_id).map_err(|e| e.to_string())?;
+    reservations::check_available(
+        conn,
+        &warehouse_id,
+        &reservation_lines(&items),
+        Some(("invoice", invoice_id)),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Hold the stock of a draft invoice or accepted quote in its warehouse,
+/// failing if other documents already hold or sold it
+fn hold_invoice_stock(
+    conn: &rusqlite::Connection,
+    tenant_id: &str,
+    invoice_id: &str,
+    expires_at: &str,
+    user_id: Option<&str>,
+) -> Result<(), String> {
+    let (warehouse_id, items) = invoice_stock_lines(conn, tenant_id, invoice_id)?;
+    let lines = reservation_lines(&items);
+
+    reservations::release_expired(conn, tenant_id).map_err(|e| e.to_string())?;
+    reservations::check_available(conn, &warehouse_id, &lines, Some(("invoice", invoice_id)))
+        .map_err(|e| e.to_string())?;
+    reservations::reserve(
+        conn,
+        &reservations::Hold {
+            tenant_id,
+            warehouse_id: &warehouse_id,
+            reference_type: "invoice",
+            reference_id: invoice_id,
+            expires_at,
+            user_id,
+        },
+        &lines,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Insert a draft invoice with its priced items, holding its stock, inside
+/// the caller's transaction; returns its number and total
+fn insert_invoice(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    id: &str,
+    data: &CreateInvoiceDto,
+    now: &str,
+) -> Result<(String, f64), String> {
+    // Get client details
+    let (client_name, client_tax_id, client_address, client_code): (
+        String,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+    ) = conn
+        .query_row(
+            "SELECT name, tax_id, address, code FROM clients WHERE id = ?1",
+            [&data.client_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(|e| format!("Error al obtener cliente: {}", e))?;
+
+    // Determine client identifier for pattern
+    let client_identifier = if let Some(code) = &client_code {
+        if !code.trim().is_empty() {
+            code.clone()
+        } else {
+            // Fallback if code is empty string
+            sanitize_client_name_for_pattern(&client_name)
+        }
+    } else {
+        sanitize_client_name_for_pattern(&client_name)
+    };
+
+    // Get invoice prefix and pattern from company settings
+    let (prefix, pattern): (String, String) = conn
+        .query_row(
+            "SELECT invoice_prefix, COALESCE(invoice_pattern, '{PREFIX}-{NUMBER}') FROM company_settings WHERE tenant_id = ?1",
+            [tenant_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap_or_else(|_| ("FAC".to_string(), "{PREFIX}-{NUMBER}".to_string()));
+
+    let invoice_number =
+        generate_invoice_number(conn, tenant_id, &prefix, &pattern, &client_identifier);
+
+    // Source warehouse: the requested one, else the open register's, else the default
+    let requested = match data.warehouse_id.clone() {
+        Some(warehouse_id) => Some(warehouse_id),
+        None => {
+            inventory::session_warehouse(conn, tenant_id, user_id).map_err(|e| e.to_string())?
+        }
+    };
+    let warehouse_id = inventory::resolve_warehouse(conn, tenant_id, requested.as_deref())
+        .map_err(|e| e.to_string())?;
+
+    let coupon_codes = split_codes(&data.coupon_codes.clone().unwrap_or_default().join(","));
+    let lines = price_items(
+        conn,
+        tenant_id,
+        &PricingContext {
+            client_id: &data.client_id,
+            price_list_id: data.price_list_id.as_deref(),
+            issue_date: &data.issue_date,
+            payment_method: data.payment_method.as_deref(),
+            coupon_codes: &coupon_codes,
+        },
+        &data.items,
+    )?;
+
+    // Insert invoice
+    conn.execute(
+        "INSERT INTO billing_invoices (id, tenant_id, invoice_number, invoice_type, status, client_id,
+         client_name, client_tax_id, client_address, price_list_id, currency, exchange_rate, issue_date,
+         due_date, payment_terms, subtotal, discount_total, tax_total, total, paid_amount, notes,
+         created_by, created_at, updated_at, warehouse_id, payment_method, coupon_codes)
+         VALUES (?1, ?2, ?3, ?4, 'draft', ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, 0, 0, 0, 0, 0, ?15, ?16, ?17, ?17, ?18, ?19, ?20)",
+        rusqlite::params![
+            id,
+            tenant_id,
+            &invoice_number,
+            &data.invoice_type,
+            &data.client_id,
+            &client_name,
+            &client_tax_id,
+            &client_address,
+            &data.price_list_id,
+            &data.currency,
+            data.exchange_rate,
+            &data.issue_date,
+            &data.due_date,
+            &data.payment_terms,
+            &data.notes,
+            user_id,
+            now,
+            &warehouse_id,
+            &data.payment_method,
+            (!coupon_codes.is_empty()).then(|| coupon_codes.join(","))
+        ],
+    )
+    .map_err(|e| format!("Error al crear factura: {}", e))?;
+
+    // Insert items
+    let (subtotal, discount_total, tax_total, total) = insert_priced_items(conn, id, &lines, now)?;
+    conn.execute(
+        "UPDATE billing_invoices SET subtotal = ?1, discount_total = ?2, tax_total = ?3, total = ?4
+         WHERE id = ?5",
+        rusqlite::params![subtotal, discount_total, tax_total, total, id],
+    )
+    .map_err(|e| format!("Error al crear factura: {}", e))?;
+
+    // Draft invoices hold their stock; quotes do once accepted (issued)
+    if data.invoice_type == "invoice" {
+        let expires_at = reservations::draft_expiry(chrono::Utc::now());
+        hold_invoice_stock(conn, tenant_id, id, &expires_at, Some(user_id))?;
+    }
+
+    Ok((invoice_number, total))
+}
+
+/// Issue a draft inside the caller's transaction: quotes hold their stock,
+/// invoices take it out of the warehouse and use their promotions
+fn issue_in(
+    conn: &Connection,
+    tenant_id: &str,
+    id: &str,
+    invoice_type: &str,
+    due_date: Option<&str>,
+    user_id: Option<&str>,
+    now: &str,
+) -> Result<(), String> {
+    if invoice_type == "quote" {
+        // An accepted quote holds its stock until it expires or is cancelled
+        let expires_at = reservations::quote_expiry(chrono::Utc::now(), due_date);
+        hold_invoice_stock(conn, tenant_id, id, &expires_at, user_id)?;
+    } else {
+        // The draft's hold becomes the sale: deduct stock from the invoice's
+        // warehouse. A hold that expired no longer guarantees it, so check again.
+        check_invoice_stock(conn, tenant_id, id)?;
+        reservations::release(conn, tenant_id, "invoice", id, "consumed")
+            .map_err(|e| e.to_string())?;
+        let moved = move_invoice_stock(conn, tenant_id, id, -1.0, user_id, now)?;
+        if moved == 0 {
+            return Err("No se puede emitir una factura sin items".to_string());
+        }
+        // Promotions are used now, or the whole issue is rolled back
+        discounts::redeem_invoice(conn, tenant_id, id, user_id, now).map_err(|e| e.to_string())?;
+    }
+
+    // Update invoice status
+    conn.execute(
+        "UPDATE billing_invoices SET status = 'issued', updated_at = ?1 WHERE id = ?2",
+        rusqlite::params![now, id],
+    )
+    .map_err(|e| format!("Error al emitir factura: {}", e))?;
+    secure_chain::seal_invoice(conn, tenant_id, id)?;
+    Ok(())
+}
+
 /// Generate next invoice number
 /// Generate next invoice number
 fn generate_invoice_number(
@@ -137,46 +615,16 @@ pub async fn list_invoices(
     }
 
     let query = format!(
-        "SELECT id, tenant_id, invoice_number, invoice_type, status, client_id, client_name,
-                client_tax_id, client_address, price_list_id, currency, exchange_rate, issue_date,
-                due_date, payment_terms, subtotal, discount_total, tax_total, total, paid_amount,
-                notes, created_by, created_at, updated_at
-         FROM billing_invoices WHERE {}
+        "SELECT {} FROM billing_invoices WHERE {}
          ORDER BY created_at DESC",
+        INVOICE_COLUMNS,
         conditions.join(" AND ")
     );
 
     let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
 
     let invoices = stmt
-        .query_map(rusqlite::params_from_iter(params.iter()), |row| {
-            Ok(Invoice {
-                id: row.get(0)?,
-                tenant_id: row.get(1)?,
-                invoice_number: row.get(2)?,
-                invoice_type: row.get(3)?,
-                status: row.get(4)?,
-                client_id: row.get(5)?,
-                client_name: row.get(6)?,
-                client_tax_id: row.get(7)?,
-                client_address: row.get(8)?,
-                price_list_id: row.get(9)?,
-                currency: row.get(10)?,
-                exchange_rate: row.get(11)?,
-                issue_date: row.get(12)?,
-                due_date: row.get(13)?,
-                payment_terms: row.get(14)?,
-                subtotal: row.get(15)?,
-                discount_total: row.get(16)?,
-                tax_total: row.get(17)?,
-                total: row.get(18)?,
-                paid_amount: row.get(19)?,
-                notes: row.get(20)?,
-                created_by: row.get(21)?,
-                created_at: row.get(22)?,
-                updated_at: row.get(23)?,
-            })
-        })
+        .query_map(rusqlite::params_from_iter(params.iter()), map_invoice)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
@@ -196,55 +644,65 @@ pub async fn get_invoice(
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
 
+    load_invoice(&conn, &tenant_id, &id)
+}
+
+/// Print an invoice as a thermal ticket, with its payments and change
+#[tauri::command]
+pub async fn print_invoice_ticket(
+    state: State<'_, AppState>,
+    id: String,
+    printer: Option<String>,
+) -> Result<String, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    let (invoice, items) = load_invoice(&conn, &tenant_id, &id)?;
+    let mut payments = payments::load_payments(&conn, &tenant_id, &id)?;
+    payments.reverse();
+    let settings = settings::query_company_settings(&conn, &tenant_id)
+        .map_err(|e| format!("Error al obtener configuración: {}", e))?;
+
+    let target =
+        escpos::resolve_target(&settings, printer.as_deref()).map_err(|e| e.to_string())?;
+    let logo = escpos::load_logo(&settings);
+    let ticket = escpos::invoice_ticket(&settings, logo.as_ref(), &invoice, &items, &payments);
+    escpos::send(&target, &ticket).map_err(|e| e.to_string())?;
+
+    Ok(target.to_string())
+}
+
+/// An invoice with its items
+pub(crate) fn load_invoice(
+    conn: &Connection,
+    tenant_id: &str,
+    id: &str,
+) -> Result<(Invoice, Vec<InvoiceItem>), String> {
     let invoice = conn
         .query_row(
-            "SELECT id, tenant_id, invoice_number, invoice_type, status, client_id, client_name,
-                    client_tax_id, client_address, price_list_id, currency, exchange_rate, issue_date,
-                    due_date, payment_terms, subtotal, discount_total, tax_total, total, paid_amount,
-                    notes, created_by, created_at, updated_at
-             FROM billing_invoices WHERE id = ?1 AND tenant_id = ?2",
-            [&id, &tenant_id],
-            |row| {
-                Ok(Invoice {
-                    id: row.get(0)?,
-                    tenant_id: row.get(1)?,
-                    invoice_number: row.get(2)?,
-                    invoice_type: row.get(3)?,
-                    status: row.get(4)?,
-                    client_id: row.get(5)?,
-                    client_name: row.get(6)?,
-                    client_tax_id: row.get(7)?,
-                    client_address: row.get(8)?,
-                    price_list_id: row.get(9)?,
-                    currency: row.get(10)?,
-                    exchange_rate: row.get(11)?,
-                    issue_date: row.get(12)?,
-                    due_date: row.get(13)?,
-                    payment_terms: row.get(14)?,
-                    subtotal: row.get(15)?,
-                    discount_total: row.get(16)?,
-                    tax_total: row.get(17)?,
-                    total: row.get(18)?,
-                    paid_amount: row.get(19)?,
-                    notes: row.get(20)?,
-                    created_by: row.get(21)?,
-                    created_at: row.get(22)?,
-                    updated_at: row.get(23)?,
-                })
-            },
+            &format!(
+                "SELECT {} FROM billing_invoices WHERE id = ?1 AND tenant_id = ?2",
+                INVOICE_COLUMNS
+            ),
+            [id, tenant_id],
+            map_invoice,
         )
         .map_err(|e| format!("Error al obtener factura: {}", e))?;
 
     let mut stmt = conn
         .prepare(
             "SELECT id, invoice_id, product_id, variant_id, lot_id, code, description, quantity,
-                    unit_price, discount_percent, discount_amount, tax_rate, tax_amount, line_total
+                    unit_price, discount_percent, discount_amount, tax_rate, tax_amount, line_total,
+                    pricing_rules
              FROM billing_invoice_items WHERE invoice_id = ?1",
         )
         .map_err(|e| e.to_string())?;
 
     let items = stmt
-        .query_map([&id], |row| {
+        .query_map([id], |row| {
             Ok(InvoiceItem {
                 id: row.get(0)?,
                 invoice_id: row.get(1)?,
@@ -260,6 +718,10 @@ pub async fn get_invoice(
                 tax_rate: row.get(11)?,
                 tax_amount: row.get(12)?,
                 line_total: row.get(13)?,
+                pricing_rules: row
+                    .get::<_, Option<String>>(14)?
+                    .and_then(|rules| serde_json::from_str(&rules).ok())
+                    .unwrap_or_default(),
             })
         })
         .map_err(|e| e.to_string())?
@@ -288,193 +750,35 @@ pub async fn create_invoice(
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
-
-    // Get client details
-    let (client_name, client_tax_id, client_address, client_code): (
-        String,
-        Option<String>,
-        Option<String>,
-        Option<String>,
-    ) = conn
-        .query_row(
-            "SELECT name, tax_id, address, code FROM clients WHERE id = ?1",
-            [&data.client_id],
-            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
-        )
-        .map_err(|e| format!("Error al obtener cliente: {}", e))?;
-
-    // Determine client identifier for pattern
-    let client_identifier = if let Some(code) = &client_code {
-        if !code.trim().is_empty() {
-            code.clone()
-        } else {
-            // Fallback if code is empty string
-            sanitize_client_name_for_pattern(&client_name)
-        }
-    } else {
-        sanitize_client_name_for_pattern(&client_name)
-    };
-
-    // Get invoice prefix and pattern from company settings
-    let (prefix, pattern): (String, String) = conn
-        .query_row(
-            "SELECT invoice_prefix, COALESCE(invoice_pattern, '{PREFIX}-{NUMBER}') FROM company_settings WHERE tenant_id = ?1",
-            [&tenant_id],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
-        .unwrap_or_else(|_| ("FAC".to_string(), "{PREFIX}-{NUMBER}".to_string()));
-
-    let invoice_number =
-        generate_invoice_number(&conn, &tenant_id, &prefix, &pattern, &client_identifier);
-
-    // Calculate totals
-    let mut subtotal = 0.0;
-    let mut discount_total = 0.0;
-    let mut tax_total = 0.0;
-
-    let items_with_calcs: Vec<(CreateInvoiceItemDto, f64, f64, f64, String, String)> = data
-        .items
-        .iter()
-        .map(|item| {
-            // Get product details
-            let (code, description): (String, String) = conn
-                .query_row(
-                    "SELECT COALESCE(sku, ''), name FROM products WHERE id = ?1",
-                    [&item.product_id],
-                    |row| Ok((row.get(0)?, row.get(1)?)),
-                )
-                .unwrap_or(("".to_string(), "Producto".to_string()));
-
-            // Use tax_calculator with Decimal for precision
-            let qty = Decimal::from_f64_retain(item.quantity).unwrap_or_default();
-            let price = Decimal::from_f64_retain(item.unit_price).unwrap_or_default();
-            let tax_rate = Decimal::from_f64_retain(item.tax_rate).unwrap_or_default();
-            let discount_pct = Decimal::from_f64_retain(item.discount_percent).unwrap_or_default();
-
-            let (line_sub, line_tax, line_total_dec) =
-                tax_calculator::calculate_line_total(qty, price, tax_rate, discount_pct);
-
-            // Convert back to f64 for storage/DTO
-            let disc_amount = (qty * price * discount_pct / Decimal::from(100))
-                .to_f64()
-                .unwrap_or(0.0);
-            let tax_amount = line_tax.to_f64().unwrap_or(0.0);
-            let line_total = line_total_dec.to_f64().unwrap_or(0.0);
-            let line_subtotal = line_sub.to_f64().unwrap_or(0.0);
-
-            subtotal += line_subtotal;
-            discount_total += disc_amount;
-            tax_total += tax_amount;
-
-            (
-                item.clone(),
-                disc_amount,
-                tax_amount,
-                line_total,
-                code,
-                description,
-            )
-        })
-        .collect();
-
-    let total = subtotal - discount_total + tax_total;
-
-    // Insert invoice
-    conn.execute(
-        "INSERT INTO billing_invoices (id, tenant_id, invoice_number, invoice_type, status, client_id,
-         client_name, client_tax_id, client_address, price_list_id, currency, exchange_rate, issue_date,
-         due_date, payment_terms, subtotal, discount_total, tax_total, total, paid_amount, notes,
-         created_by, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, 'draft', ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, 0, ?19, ?20, ?21, ?21)",
-        rusqlite::params![
-            &id,
-            &tenant_id,
-            &invoice_number,
-            &data.invoice_type,
-            &data.client_id,
-            &client_name,
-            &client_tax_id,
-            &client_address,
-            &data.price_list_id,
-            &data.currency,
-            data.exchange_rate,
-            &data.issue_date,
-            &data.due_date,
-            &data.payment_terms,
-            subtotal,
-            discount_total,
-            tax_total,
-            total,
-            &data.notes,
-            &user_id,
-            &now
-        ],
+    license::require_module(&conn, "invoicing")?;
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let (invoice_number, total) = insert_invoice(&tx, &tenant_id, &user_id, &id, &data, &now)?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        audit::AuditEventType::FiscalDocumentCreated,
+        Some("invoice"),
+        Some(&id),
+        &format!(
+            "number={}, type={}, client_id={}, currency={}, total={}",
+            invoice_number, data.invoice_type, data.client_id, data.currency, total
+        ),
     )
-    .map_err(|e| format!("Error al crear factura: {}", e))?;
-
-    // Insert items
-    for (item, disc_amount, tax_amount, line_total, code, description) in items_with_calcs {
-        let item_id = Uuid::new_v4().to_string();
-        conn.execute(
-            "INSERT INTO billing_invoice_items (id, invoice_id, product_id, variant_id, lot_id, code,
-             description, quantity, unit_price, discount_percent, discount_amount, tax_rate, tax_amount, line_total)
-             VALUES (?1, ?2, ?3, ?4, NULL, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
-            rusqlite::params![
-                &item_id,
-                &id,
-                &item.product_id,
-                &item.variant_id,
-                &code,
-                &description,
-                item.quantity,
-                item.unit_price,
-                item.discount_percent,
-                disc_amount,
-                item.tax_rate,
-                tax_amount,
-                line_total
-            ],
-        )
-        .map_err(|e| format!("Error al crear item de factura: {}", e))?;
-    }
+    .ok();
 
     // Return created invoice
     let result = conn
         .query_row(
-            "SELECT id, tenant_id, invoice_number, invoice_type, status, client_id, client_name,
-                client_tax_id, client_address, price_list_id, currency, exchange_rate, issue_date,
-                due_date, payment_terms, subtotal, discount_total, tax_total, total, paid_amount,
-                notes, created_by, created_at, updated_at
-         FROM billing_invoices WHERE id = ?1",
+            &format!(
+                "SELECT {} FROM billing_invoices WHERE id = ?1",
+                INVOICE_COLUMNS
+            ),
             [&id],
-            |row| {
-                Ok(Invoice {
-                    id: row.get(0)?,
-                    tenant_id: row.get(1)?,
-                    invoice_number: row.get(2)?,
-                    invoice_type: row.get(3)?,
-                    status: row.get(4)?,
-                    client_id: row.get(5)?,
-                    client_name: row.get(6)?,
-                    client_tax_id: row.get(7)?,
-                    client_address: row.get(8)?,
-                    price_list_id: row.get(9)?,
-                    currency: row.get(10)?,
-                    exchange_rate: row.get(11)?,
-                    issue_date: row.get(12)?,
-                    due_date: row.get(13)?,
-                    payment_terms: row.get(14)?,
-                    subtotal: row.get(15)?,
-                    discount_total: row.get(16)?,
-                    tax_total: row.get(17)?,
-                    total: row.get(18)?,
-                    paid_amount: row.get(19)?,
-                    notes: row.get(20)?,
-                    created_by: row.get(21)?,
-                    created_at: row.get(22)?,
-                    updated_at: row.get(23)?,
-                })
-            },
+            map_invoice,
         )
         .map_err(|e| format!("Error al obtener factura creada: {}", e));
 
@@ -495,14 +799,17 @@ pub async fn issue_invoice(state: State<'_, AppState>, id: String) -> Result<Inv
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
+    time_guard::verify_time_integrity(&conn)?;
+    license::require_module(&conn, "invoicing")?;
+
     let now = chrono::Utc::now().to_rfc3339();
 
     // Check invoice is draft
-    let status: String = conn
+    let (status, invoice_type, due_date): (String, String, Option<String>) = conn
         .query_row(
-            "SELECT status FROM billing_invoices WHERE id = ?1 AND tenant_id = ?2",
+            "SELECT status, invoice_type, due_date FROM billing_invoices WHERE id = ?1 AND tenant_id = ?2",
             [&id, &tenant_id],
-            |row| row.get(0),
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
         )
         .map_err(|e| format!("Factura no encontrada: {}", e))?;
 
@@ -510,87 +817,39 @@ pub async fn issue_invoice(state: State<'_, AppState>, id: String) -> Result<Inv
         return Err("Solo se pueden emitir facturas en borrador".to_string());
     }
 
-    // Get items and deduct stock (FIFO for lots)
-    let mut stmt = conn
-        .prepare(
-            "SELECT product_id, variant_id, quantity FROM billing_invoice_items WHERE invoice_id = ?1",
-        )
-        .map_err(|e| e.to_string())?;
-
-    let items: Vec<(String, Option<String>, f64)> = stmt
-        .query_map([&id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
-
-    if items.is_empty() {
-        return Err("No se puede emitir una factura sin items".to_string());
-    }
-
-    drop(stmt);
-
-    for (product_id, variant_id, quantity) in items {
-        // Deduct from main product stock
-        conn.execute(
-            "UPDATE products SET stock_quantity = stock_quantity - ?1, updated_at = ?2 WHERE id = ?3",
-            rusqlite::params![quantity, &now, &product_id],
-        )
-        .map_err(|e| format!("Error al descontar stock: {}", e))?;
-
-        // If variant, also deduct from variant stock
-        if let Some(ref var_id) = variant_id {
-            conn.execute(
-                "UPDATE variant_stock SET quantity = quantity - ?1, last_updated = ?2 WHERE variant_id = ?3",
-                rusqlite::params![quantity, &now, var_id],
-            )
-            .ok();
-        }
-    }
-
-    // Update invoice status
-    conn.execute(
-        "UPDATE billing_invoices SET status = 'issued', updated_at = ?1 WHERE id = ?2",
-        rusqlite::params![&now, &id],
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    issue_in(
+        &tx,
+        &tenant_id,
+        &id,
+        &invoice_type,
+        due_date.as_deref(),
+        user_id.as_deref(),
+        &now,
+    )?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::FiscalDocumentIssued,
+        Some("invoice"),
+        Some(&id),
+        "Status draft -> issued",
     )
-    .map_err(|e| format!("Error al emitir factura: {}", e))?;
+    .ok();
 
     // Return updated invoice
     let result = conn
         .query_row(
-            "SELECT id, tenant_id, invoice_number, invoice_type, status, client_id, client_name,
-                client_tax_id, client_address, price_list_id, currency, exchange_rate, issue_date,
-                due_date, payment_terms, subtotal, discount_total, tax_total, total, paid_amount,
-                notes, created_by, created_at, updated_at
-         FROM billing_invoices WHERE id = ?1",
+            &format!(
+                "SELECT {} FROM billing_invoices WHERE id = ?1",
+                INVOICE_COLUMNS
+            ),
             [&id],
-            |row| {
-                Ok(Invoice {
-                    id: row.get(0)?,
-                    tenant_id: row.get(1)?,
-                    invoice_number: row.get(2)?,
-                    invoice_type: row.get(3)?,
-                    status: row.get(4)?,
-                    client_id: row.get(5)?,
-                    client_name: row.get(6)?,
-                    client_tax_id: row.get(7)?,
-                    client_address: row.get(8)?,
-                    price_list_id: row.get(9)?,
-                    currency: row.get(10)?,
-                    exchange_rate: row.get(11)?,
-                    issue_date: row.get(12)?,
-                    due_date: row.get(13)?,
-                    payment_terms: row.get(14)?,
-                    subtotal: row.get(15)?,
-                    discount_total: row.get(16)?,
-                    tax_total: row.get(17)?,
-                    total: row.get(18)?,
-                    paid_amount: row.get(19)?,
-                    notes: row.get(20)?,
-                    created_by: row.get(21)?,
-                    created_at: row.get(22)?,
-                    updated_at: row.get(23)?,
-                })
-            },
+            map_invoice,
         )
         .map_err(|e| format!("Error al obtener factura: {}", e));
 
@@ -603,6 +862,130 @@ pub async fn issue_invoice(state: State<'_, AppState>, id: String) -> Result<Inv
     result
 }
 
+/// Ring up a point-of-sale sale: create, issue and pay an invoice in one
+/// step against the user's open register session
+#[tauri::command]
+pub async fn create_pos_sale(
+    state: State<'_, AppState>,
+    data: PosSaleDto,
+) -> Result<PosSale, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let user_id = get_user_id(&state)?;
+    let id = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    time_guard::verify_time_integrity(&conn)?;
+    license::require_module(&conn, "invoicing")?;
+
+    let session = cash_register::require_active_session(&conn, &tenant_id, &user_id)
+        .map_err(|e| e.to_string())?;
+    let currency = data.currency.trim().to_uppercase();
+    let exchange_rate = exchange_rates::conversion(&conn, &tenant_id, &currency, "VES")
+        .map_err(|e| e.to_string())?
+        .to_f64()
+        .unwrap_or(1.0);
+
+    // A single way of paying can earn its payment method discounts
+    let methods: Vec<String> = data
+        .tenders
+        .iter()
+        .map(|tender| tender.payment_method.trim().to_lowercase())
+        .collect();
+    let payment_method = methods
+        .first()
+        .filter(|first| methods.iter().all(|method| method == *first))
+        .cloned();
+
+    let invoice = CreateInvoiceDto {
+        invoice_type: "invoice".to_string(),
+        client_id: data.client_id.clone(),
+        price_list_id: data.price_list_id.clone(),
+        currency: currency.clone(),
+        exchange_rate,
+        issue_date: now.get(..10).unwrap_or(&now).to_string(),
+        due_date: None,
+        payment_terms: Some("CONTADO".to_string()),
+        notes: data.notes.clone(),
+        items: data.items.clone(),
+        // The register's warehouse
+        warehouse_id: None,
+        payment_method,
+        coupon_codes: data.coupon_codes.clone(),
+    };
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let (invoice_number, total) = insert_invoice(&tx, &tenant_id, &user_id, &id, &invoice, &now)?;
+    issue_in(&tx, &tenant_id, &id, "invoice", None, Some(&user_id), &now)?;
+    let payments = cash_register::settle_sale(
+        &tx,
+        &tenant_id,
+        &user_id,
+        &cash_register::Settlement {
+            session_id: &session.id,
+            invoice_id: &id,
+            tenders: &data.tenders,
+            change_currency: data.change_currency.as_deref(),
+        },
+        &now,
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    let invoice = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM billing_invoices WHERE id = ?1",
+                INVOICE_COLUMNS
+            ),
+            [&id],
+            map_invoice,
+        )
+        .map_err(|e| format!("Error al obtener factura: {}", e))?;
+    let change_currency = invoice.change_currency.clone().unwrap_or(currency);
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        audit::AuditEventType::FiscalDocumentIssued,
+        Some("invoice"),
+        Some(&id),
+        &format!(
+            "number={}, total={}, pos_session={}",
+            invoice_number, total, session.id
+        ),
+    )
+    .ok();
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        audit::AuditEventType::PosSaleCompleted,
+        Some("invoice"),
+        Some(&id),
+        &format!(
+            "register_id={}, session_id={}, tenders={}, change={} {}",
+            session.register_id,
+            session.id,
+            payments.len(),
+            invoice.change_amount,
+            change_currency
+        ),
+    )
+    .ok();
+
+    Ok(PosSale {
+        change_amount: invoice.change_amount,
+        change_currency,
+        invoice,
+        payments,
+    })
+}
+
 /// Cancel an invoice (only for issued invoices, restores stock)
 #[tauri::command]
 pub async fn cancel_invoice(state: State<'_, AppState>, id: String) -> Result<Invoice, String> {
@@ -611,14 +994,15 @@ pub async fn cancel_invoice(state: State<'_, AppState>, id: String) -> Result<In
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
+    license::require_module(&conn, "invoicing")?;
     let now = chrono::Utc::now().to_rfc3339();
 
     // Check invoice status
-    let status: String = conn
+    let (status, invoice_type): (String, String) = conn
         .query_row(
-            "SELECT status FROM billing_invoices WHERE id = ?1 AND tenant_id = ?2",
+            "SELECT status, invoice_type FROM billing_invoices WHERE id = ?1 AND tenant_id = ?2",
             [&id, &tenant_id],
-            |row| row.get(0),
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )
         .map_err(|e| format!("Factura no encontrada: {}", e))?;
 
@@ -626,82 +1010,44 @@ pub async fn cancel_invoice(state: State<'_, AppState>, id: String) -> Result<In
         return Err("No se pueden anular facturas pagadas".to_string());
     }
 
-    // If issued, restore stock
-    if status == "issued" || status == "partial" {
-        let mut stmt = conn
-            .prepare(
-                "SELECT product_id, variant_id, quantity FROM billing_invoice_items WHERE invoice_id = ?1",
-            )
-            .map_err(|e| e.to_string())?;
-
-        let items: Vec<(String, Option<String>, f64)> = stmt
-            .query_map([&id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
-            .map_err(|e| e.to_string())?
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| e.to_string())?;
-
-        drop(stmt);
-
-        for (product_id, variant_id, quantity) in items {
-            conn.execute(
-                "UPDATE products SET stock_quantity = stock_quantity + ?1, updated_at = ?2 WHERE id = ?3",
-                rusqlite::params![quantity, &now, &product_id],
-            )
-            .ok();
-
-            if let Some(ref var_id) = variant_id {
-                conn.execute(
-                    "UPDATE variant_stock SET quantity = quantity + ?1, last_updated = ?2 WHERE variant_id = ?3",
-                    rusqlite::params![quantity, &now, var_id],
-                )
-                .ok();
-            }
-        }
+    // Release any hold; if issued, restore stock (quotes never took it) and
+    // give back the promotions it used
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    reservations::release(&tx, &tenant_id, "invoice", &id, "released")
+        .map_err(|e| e.to_string())?;
+    if (status == "issued" || status == "partial") && invoice_type != "quote" {
+        move_invoice_stock(&tx, &tenant_id, &id, 1.0, user_id.as_deref(), &now)?;
     }
+    discounts::reverse_invoice(&tx, &tenant_id, &id, &now).map_err(|e| e.to_string())?;
 
     // Update status
-    conn.execute(
+    tx.execute(
         "UPDATE billing_invoices SET status = 'cancelled', updated_at = ?1 WHERE id = ?2",
         rusqlite::params![&now, &id],
     )
     .map_err(|e| format!("Error al anular factura: {}", e))?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::FiscalDocumentCancelled,
+        Some("invoice"),
+        Some(&id),
+        &format!("Status {} -> cancelled", status),
+    )
+    .ok();
 
     // Return updated invoice
     conn.query_row(
-        "SELECT id, tenant_id, invoice_number, invoice_type, status, client_id, client_name,
-                client_tax_id, client_address, price_list_id, currency, exchange_rate, issue_date,
-                due_date, payment_terms, subtotal, discount_total, tax_total, total, paid_amount,
-                notes, created_by, created_at, updated_at
-         FROM billing_invoices WHERE id = ?1",
+        &format!(
+            "SELECT {} FROM billing_invoices WHERE id = ?1",
+            INVOICE_COLUMNS
+        ),
         [&id],
-        |row| {
-            Ok(Invoice {
-                id: row.get(0)?,
-                tenant_id: row.get(1)?,
-                invoice_number: row.get(2)?,
-                invoice_type: row.get(3)?,
-                status: row.get(4)?,
-                client_id: row.get(5)?,
-                client_name: row.get(6)?,
-                client_tax_id: row.get(7)?,
-                client_address: row.get(8)?,
-                price_list_id: row.get(9)?,
-                currency: row.get(10)?,
-                exchange_rate: row.get(11)?,
-                issue_date: row.get(12)?,
-                due_date: row.get(13)?,
-                payment_terms: row.get(14)?,
-                subtotal: row.get(15)?,
-                discount_total: row.get(16)?,
-                tax_total: row.get(17)?,
-                total: row.get(18)?,
-                paid_amount: row.get(19)?,
-                notes: row.get(20)?,
-                created_by: row.get(21)?,
-                created_at: row.get(22)?,
-                updated_at: row.get(23)?,
-            })
-        },
+        map_invoice,
     )
     .map_err(|e| format!("Error al obtener factura: {}", e))
 }
@@ -714,49 +1060,26 @@ pub async fn delete_invoice(state: State<'_, AppState>, id: String) -> Result<()
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
+    license::require_module(&conn, "invoicing")?;
     let now = chrono::Utc::now().to_rfc3339();
 
     // Check invoice status
-    let status: String = conn
+    let (status, invoice_type): (String, String) = conn
         .query_row(
-            "SELECT status FROM billing_invoices WHERE id = ?1 AND tenant_id = ?2",
+            "SELECT status, invoice_type FROM billing_invoices WHERE id = ?1 AND tenant_id = ?2",
             [&id, &tenant_id],
-            |row| row.get(0),
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )
         .map_err(|e| format!("Factura no encontrada: {}", e))?;
 
-    // If issued/partial/paid, restore stock before deleting
-    if status == "issued" || status == "partial" || status == "paid" {
-        let mut stmt = conn
-            .prepare(
-                "SELECT product_id, variant_id, quantity FROM billing_invoice_items WHERE invoice_id = ?1",
-            )
-            .map_err(|e| e.to_string())?;
-
-        let items: Vec<(String, Option<String>, f64)> = stmt
-            .query_map([&id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
-            .map_err(|e| e.to_string())?
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| e.to_string())?;
-
-        drop(stmt);
-
-        for (product_id, variant_id, quantity) in items {
-            conn.execute(
-                "UPDATE products SET stock_quantity = stock_quantity + ?1, updated_at = ?2 WHERE id = ?3",
-                rusqlite::params![quantity, &now, &product_id],
-            )
-            .ok();
-
-            if let Some(ref var_id) = variant_id {
-                conn.execute(
-                    "UPDATE variant_stock SET quantity = quantity + ?1, last_updated = ?2 WHERE variant_id = ?3",
-                    rusqlite::params![quantity, &now, var_id],
-                )
-                .ok();
-            }
-        }
+    // Release any hold; if issued/partial/paid, restore stock before deleting
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    reservations::release(&conn, &tenant_id, "invoice", &id, "released")
+        .map_err(|e| e.to_string())?;
+    if (status == "issued" || status == "partial" || status == "paid") && invoice_type != "quote" {
+        move_invoice_stock(&conn, &tenant_id, &id, 1.0, user_id.as_deref(), &now)?;
     }
+    discounts::reverse_invoice(&conn, &tenant_id, &id, &now).map_err(|e| e.to_string())?;
 
     // Delete items
     conn.execute(
@@ -781,6 +1104,17 @@ pub async fn delete_invoice(state: State<'_, AppState>, id: String) -> Result<()
     )
     .map_err(|e| format!("Error al eliminar factura: {}", e))?;
 
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::FiscalDocumentDeleted,
+        Some("invoice"),
+        Some(&id),
+        &format!("Deleted with status {}", status),
+    )
+    .ok();
+
     Ok(())
 }
 
@@ -800,19 +1134,21 @@ pub async fn update_invoice(
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
+    license::require_module(&conn, "invoicing")?;
 
     // Check invoice exists and is draft
-    let status: String = conn
+    let (status, invoice_type): (String, String) = conn
         .query_row(
-            "SELECT status FROM billing_invoices WHERE id = ?1 AND tenant_id = ?2",
+            "SELECT status, invoice_type FROM billing_invoices WHERE id = ?1 AND tenant_id = ?2",
             [&id, &tenant_id],
-            |row| row.get(0),
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )
         .map_err(|e| format!("Factura no encontrada: {}", e))?;
 
     if status != "draft" {
         return Err("Solo se pueden editar facturas en borrador".to_string());
     }
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
 
     // Update main fields if provided
     if let Some(client_id) = &data.client_id {
@@ -881,123 +1217,144 @@ pub async fn update_invoice(
         .ok();
     }
 
-    // Update items if provided (Replace all)
-    if let Some(items) = data.items {
-        // Delete old items
+    if let Some(warehouse_id) = &data.warehouse_id {
+        let warehouse_id = inventory::resolve_warehouse(&conn, &tenant_id, Some(warehouse_id))
+            .map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE billing_invoices SET warehouse_id = ?1 WHERE id = ?2",
+            rusqlite::params![warehouse_id, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    if let Some(payment_method) = &data.payment_method {
+        conn.execute(
+            "UPDATE billing_invoices SET payment_method = ?1 WHERE id = ?2",
+            rusqlite::params![payment_method, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    if let Some(coupon_codes) = &data.coupon_codes {
+        let codes = split_codes(&coupon_codes.join(","));
+        conn.execute(
+            "UPDATE billing_invoices SET coupon_codes = ?1 WHERE id = ?2",
+            rusqlite::params![(!codes.is_empty()).then(|| codes.join(",")), id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    // Reprice when the items or anything the price depends on changed
+    let items_replaced = data.items.is_some();
+    let repriced = items_replaced
+        || data.client_id.is_some()
+        || data.price_list_id.is_some()
+        || data.issue_date.is_some()
+        || data.payment_method.is_some()
+        || data.coupon_codes.is_some();
+    if repriced {
+        let items = match data.items {
+            Some(items) => items,
+            None => {
+                let mut stmt = conn
+                    .prepare(
+                        "SELECT product_id, variant_id, quantity FROM billing_invoice_items
+                         WHERE invoice_id = ?1",
+                    )
+                    .map_err(|e| e.to_string())?;
+                let items = stmt
+                    .query_map([&id], |row| {
+                        Ok(CreateInvoiceItemDto {
+                            product_id: row.get(0)?,
+                            variant_id: row.get(1)?,
+                            quantity: row.get(2)?,
+                        })
+                    })
+                    .map_err(|e| e.to_string())?
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| e.to_string())?;
+                items
+            }
+        };
+        let (client_id, price_list_id, issue_date, payment_method, coupon_codes): StoredPricing =
+            conn.query_row(
+                "SELECT client_id, price_list_id, issue_date, payment_method, coupon_codes
+                 FROM billing_invoices WHERE id = ?1",
+                [&id],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                },
+            )
+            .map_err(|e| e.to_string())?;
+        let coupon_codes = split_codes(&coupon_codes.unwrap_or_default());
+        let lines = price_items(
+            &conn,
+            &tenant_id,
+            &PricingContext {
+                client_id: &client_id,
+                price_list_id: price_list_id.as_deref(),
+                issue_date: &issue_date,
+                payment_method: payment_method.as_deref(),
+                coupon_codes: &coupon_codes,
+            },
+            &items,
+        )?;
+
+        // Replace all items
         conn.execute(
             "DELETE FROM billing_invoice_items WHERE invoice_id = ?1",
             [&id],
         )
         .map_err(|e| format!("Error al limpiar items: {}", e))?;
-
-        let mut subtotal = Decimal::ZERO;
-        let mut tax_total = Decimal::ZERO;
-        let mut discount_total = Decimal::ZERO;
-
-        for item in items {
-            // Get product details (code, name)
-            let (code, description): (String, String) = conn
-                .query_row(
-                    "SELECT COALESCE(sku, ''), name FROM products WHERE id = ?1",
-                    [&item.product_id],
-                    |row| Ok((row.get(0)?, row.get(1)?)),
-                )
-                .unwrap_or(("".to_string(), "Producto".to_string()));
-
-            // Calc using tax_calculator
-            let qty = Decimal::from_f64_retain(item.quantity).unwrap_or_default();
-            let price = Decimal::from_f64_retain(item.unit_price).unwrap_or_default();
-            let tax_rate = Decimal::from_f64_retain(item.tax_rate).unwrap_or_default();
-            let discount_pct = Decimal::from_f64_retain(item.discount_percent).unwrap_or_default();
-
-            let (line_sub, line_tax, line_total) =
-                tax_calculator::calculate_line_total(qty, price, tax_rate, discount_pct);
-
-            let disc_amount = qty * price * discount_pct / dec!(100);
-
-            subtotal += line_sub;
-            tax_total += line_tax;
-            discount_total += disc_amount;
-
-            // Insert new item
-            let item_id = Uuid::new_v4().to_string();
-            conn.execute(
-            "INSERT INTO billing_invoice_items (id, invoice_id, product_id, variant_id, lot_id, code,
-             description, quantity, unit_price, discount_percent, discount_amount, tax_rate, tax_amount, line_total)
-             VALUES (?1, ?2, ?3, ?4, NULL, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
-            rusqlite::params![
-                &item_id,
-                &id,
-                &item.product_id,
-                &item.variant_id,
-                &code,
-                &description,
-                item.quantity,
-                item.unit_price,
-                item.discount_percent,
-                disc_amount.to_f64().unwrap_or(0.0),
-                item.tax_rate,
-                line_tax.to_f64().unwrap_or(0.0),
-                line_total.to_f64().unwrap_or(0.0)
-            ],
-            ).map_err(|e| format!("Error al insertar item: {}", e))?;
-        }
-
-        let total = subtotal + tax_total; // discount already subtracted from subtotal in calculator usually, let's verify tax calc logic
-                                          // tax_calculator::calculate_line_total returns (subtotal, tax, total) where subtotal = gross - discount.
-                                          // So total invoice = sum(subtotal) + sum(tax).
-                                          // discount_total is separate just for display/record.
+        let (subtotal, discount_total, tax_total, total) =
+            insert_priced_items(&conn, &id, &lines, &now)?;
 
         // Update invoice totals
         conn.execute(
             "UPDATE billing_invoices SET subtotal = ?1, discount_total = ?2, tax_total = ?3, total = ?4, updated_at = ?5 WHERE id = ?6",
-            rusqlite::params![
-                subtotal.to_f64().unwrap_or(0.0),
-                discount_total.to_f64().unwrap_or(0.0),
-                tax_total.to_f64().unwrap_or(0.0),
-                total.to_f64().unwrap_or(0.0),
-                &now,
-                &id
-            ],
-        ).map_err(|e| format!("Error al actualizar totales: {}", e))?;
+            rusqlite::params![subtotal, discount_total, tax_total, total, &now, &id],
+        )
+        .map_err(|e| format!("Error al actualizar totales: {}", e))?;
     }
 
+    // Re-hold the draft's stock for the new items or warehouse
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    if invoice_type == "invoice" && (items_replaced || data.warehouse_id.is_some()) {
+        let expires_at = reservations::draft_expiry(chrono::Utc::now());
+        hold_invoice_stock(&conn, &tenant_id, &id, &expires_at, user_id.as_deref())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::FiscalDocumentUpdated,
+        Some("invoice"),
+        Some(&id),
+        &format!(
+            "client_changed={}, items_replaced={}, repriced={}",
+            data.client_id.is_some(),
+            items_replaced,
+            repriced
+        ),
+    )
+    .ok();
+
     // Return updated invoice
     conn.query_row(
-        "SELECT id, tenant_id, invoice_number, invoice_type, status, client_id, client_name,
-                client_tax_id, client_address, price_list_id, currency, exchange_rate, issue_date,
-                due_date, payment_terms, subtotal, discount_total, tax_total, total, paid_amount,
-                notes, created_by, created_at, updated_at
-         FROM billing_invoices WHERE id = ?1",
+        &format!(
+            "SELECT {} FROM billing_invoices WHERE id = ?1",
+            INVOICE_COLUMNS
+        ),
         [&id],
-        |row| {
-            Ok(Invoice {
-                id: row.get(0)?,
-                tenant_id: row.get(1)?,
-                invoice_number: row.get(2)?,
-                invoice_type: row.get(3)?,
-                status: row.get(4)?,
-                client_id: row.get(5)?,
-                client_name: row.get(6)?,
-                client_tax_id: row.get(7)?,
-                client_address: row.get(8)?,
-                price_list_id: row.get(9)?,
-                currency: row.get(10)?,
-                exchange_rate: row.get(11)?,
-                issue_date: row.get(12)?,
-                due_date: row.get(13)?,
-                payment_terms: row.get(14)?,
-                subtotal: row.get(15)?,
-                discount_total: row.get(16)?,
-                tax_total: row.get(17)?,
-                total: row.get(18)?,
-                paid_amount: row.get(19)?,
-                notes: row.get(20)?,
-                created_by: row.get(21)?,
-                created_at: row.get(22)?,
-                updated_at: row.get(23)?,
-            })
-        },
+        map_invoice,
     )
     .map_err(|e| e.to_string())
 }
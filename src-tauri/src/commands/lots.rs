@@ -1,6 +1,7 @@
 //! Inventory Lot Commands
 
 use crate::models::{AdjustLotDto, CreateLotDto, InventoryLot, LotFilters};
+use crate::services::inventory;
 use crate::state::AppState;
 use tauri::State;
 use uuid::Uuid;
@@ -203,7 +204,7 @@ pub async fn create_lot(
                 &data.product_id,
                 &data.variant_id,
                 &data.lot_number,
-                data.quantity,
+                0.0,
                 data.cost_price,
                 &data.expiration_date,
                 &received_date,
@@ -212,21 +213,23 @@ pub async fn create_lot(
         )
         .map_err(|e| format!("Error al crear lote: {}", e))?;
 
-        // Update product stock
-        conn.execute(
-            "UPDATE products SET stock_quantity = stock_quantity + ?1, updated_at = ?2 WHERE id = ?3",
-            rusqlite::params![data.quantity, &now, &data.product_id],
+        // Receive the lot into the warehouse; updates lot, product and variant stock
+        let warehouse_id =
+            inventory::resolve_warehouse(&conn, &tenant_id, data.warehouse_id.as_deref())
+                .map_err(|e| e.to_string())?;
+        inventory::apply(
+            &conn,
+            &inventory::StockChange {
+                tenant_id: &tenant_id,
+                warehouse_id: &warehouse_id,
+                product_id: &data.product_id,
+                variant_id: data.variant_id.as_deref(),
+                lot_id: Some(&id),
+                quantity: data.quantity,
+            },
+            &now,
         )
-        .ok();
-
-        // If lot is for a variant, also update variant stock
-        if let Some(ref variant_id) = data.variant_id {
-            conn.execute(
-                "UPDATE variant_stock SET quantity = quantity + ?1, last_updated = ?2 WHERE variant_id = ?3",
-                rusqlite::params![data.quantity, &now, variant_id],
-            )
-            .ok();
-        }
+        .map_err(|e| format!("Error al crear lote: {}", e))?;
     }
 
     get_lot(state, id).await
@@ -256,29 +259,35 @@ pub async fn adjust_lot(
             )
             .map_err(|e| format!("Lote no encontrado: {}", e))?;
 
-        // Adjust lot quantity
-        conn.execute(
-            "UPDATE inventory_lots SET quantity = quantity + ?1 WHERE id = ?2 AND tenant_id = ?3",
-            rusqlite::params![data.quantity, &id, &tenant_id],
-        )
-        .map_err(|e| format!("Error al ajustar lote: {}", e))?;
-
-        // Update product stock
+        let requested = match data.warehouse_id {
+            Some(warehouse_id) => Some(warehouse_id),
+            None => conn
+                .query_row(
+                    "SELECT warehouse_id FROM warehouse_stock WHERE lot_id = ?1
+                     ORDER BY quantity DESC LIMIT 1",
+                    [&id],
+                    |row| row.get(0),
+                )
+                .ok(),
+        };
+        let warehouse_id = inventory::resolve_warehouse(&conn, &tenant_id, requested.as_deref())
+            .map_err(|e| e.to_string())?;
+
+        // Adjusts lot, product and variant stock
         let now = chrono::Utc::now().to_rfc3339();
-        conn.execute(
-            "UPDATE products SET stock_quantity = stock_quantity + ?1, updated_at = ?2 WHERE id = ?3",
-            rusqlite::params![data.quantity, &now, &product_id],
+        inventory::apply(
+            &conn,
+            &inventory::StockChange {
+                tenant_id: &tenant_id,
+                warehouse_id: &warehouse_id,
+                product_id: &product_id,
+                variant_id: variant_id.as_deref(),
+                lot_id: Some(&id),
+                quantity: data.quantity,
+            },
+            &now,
         )
-        .ok();
-
-        // If lot is for a variant, also update variant stock
-        if let Some(ref var_id) = variant_id {
-            conn.execute(
-                "UPDATE variant_stock SET quantity = quantity + ?1, last_updated = ?2 WHERE variant_id = ?3",
-                rusqlite::params![data.quantity, &now, var_id],
-            )
-            .ok();
-        }
+        .map_err(|e| format!("Error al ajustar lote: {}", e))?;
     }
 
     get_lot(state, id).await
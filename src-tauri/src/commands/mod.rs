@@ -2,18 +2,27 @@
 //!
 //! All commands exposed to the frontend are organized here.
 
+pub mod audit;
 pub mod auth;
+pub mod backup;
+pub mod branches;
 pub mod cash_register;
 pub mod categories;
 pub mod clients;
 pub mod discounts;
+pub mod exchange_rates;
+pub mod inventory_counts;
 pub mod invoices;
 pub mod lots;
 pub mod payments;
 pub mod price_history;
 pub mod price_lists;
+pub mod pricing;
 pub mod product_types;
 pub mod products;
+pub mod reports;
+pub mod repricing;
+pub mod reservations;
 pub mod security;
 pub mod settings;
 pub mod setup;
@@ -22,3 +31,4 @@ pub mod system;
 pub mod units;
 pub mod updater; // NEW
 pub mod variants;
+pub mod warehouses;
@@ -1,6 +1,9 @@
 //! Payment Commands
 
+use crate::commands::{invoices, settings};
 use crate::models::{CreatePaymentDto, Payment};
+use crate::security::{audit, license, time_guard};
+use crate::services::escpos;
 use crate::state::AppState;
 use tauri::State;
 use uuid::Uuid;
@@ -25,6 +28,52 @@ fn get_user_id(state: &State<'_, AppState>) -> Result<String, String> {
         .ok_or_else(|| "No hay usuario activo".to_string())
 }
 
+const PAYMENT_COLUMNS: &str = "id, tenant_id, invoice_id, amount, currency, exchange_rate, payment_method,
+     reference, bank_account_id, payment_date, notes, created_by, created_at, received_amount, session_id";
+
+fn map_payment(row: &rusqlite::Row) -> rusqlite::Result<Payment> {
+    Ok(Payment {
+        id: row.get(0)?,
+        tenant_id: row.get(1)?,
+        invoice_id: row.get(2)?,
+        amount: row.get(3)?,
+        currency: row.get(4)?,
+        exchange_rate: row.get(5)?,
+        payment_method: row.get(6)?,
+        reference: row.get(7)?,
+        bank_account_id: row.get(8)?,
+        payment_date: row.get(9)?,
+        notes: row.get(10)?,
+        created_by: row.get(11)?,
+        created_at: row.get(12)?,
+        received_amount: row.get(13)?,
+        session_id: row.get(14)?,
+    })
+}
+
+/// Payments of an invoice, newest first
+pub(crate) fn load_payments(
+    conn: &rusqlite::Connection,
+    tenant_id: &str,
+    invoice_id: &str,
+) -> Result<Vec<Payment>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM billing_payments WHERE invoice_id = ?1 AND tenant_id = ?2
+             ORDER BY created_at DESC",
+            PAYMENT_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let payments = stmt
+        .query_map([invoice_id, tenant_id], map_payment)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(payments)
+}
+
 /// List payments for an invoice
 #[tauri::command]
 pub async fn list_payments(
@@ -37,39 +86,43 @@ pub async fn list_payments(
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, tenant_id, invoice_id, amount, currency, exchange_rate, payment_method,
-                    reference, bank_account_id, payment_date, notes, created_by, created_at, received_amount
-             FROM billing_payments WHERE invoice_id = ?1 AND tenant_id = ?2
-             ORDER BY created_at DESC",
-        )
-        .map_err(|e| e.to_string())?;
+    load_payments(&conn, &tenant_id, &invoice_id)
+}
 
-    let payments = stmt
-        .query_map([&invoice_id, &tenant_id], |row| {
-            Ok(Payment {
-                id: row.get(0)?,
-                tenant_id: row.get(1)?,
-                invoice_id: row.get(2)?,
-                amount: row.get(3)?,
-                currency: row.get(4)?,
-                exchange_rate: row.get(5)?,
-                payment_method: row.get(6)?,
-                reference: row.get(7)?,
-                bank_account_id: row.get(8)?,
-                payment_date: row.get(9)?,
-                notes: row.get(10)?,
-                created_by: row.get(11)?,
-                created_at: row.get(12)?,
-                received_amount: row.get(13)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+/// Print the receipt of a payment on the thermal printer
+#[tauri::command]
+pub async fn print_payment_receipt(
+    state: State<'_, AppState>,
+    id: String,
+    printer: Option<String>,
+) -> Result<String, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
 
-    Ok(payments)
+    let payment = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM billing_payments WHERE id = ?1 AND tenant_id = ?2",
+                PAYMENT_COLUMNS
+            ),
+            [&id, &tenant_id],
+            map_payment,
+        )
+        .map_err(|e| format!("Error al obtener pago: {}", e))?;
+    let (invoice, _) = invoices::load_invoice(&conn, &tenant_id, &payment.invoice_id)?;
+    let settings = settings::query_company_settings(&conn, &tenant_id)
+        .map_err(|e| format!("Error al obtener configuración: {}", e))?;
+
+    let target =
+        escpos::resolve_target(&settings, printer.as_deref()).map_err(|e| e.to_string())?;
+    let logo = escpos::load_logo(&settings);
+    let ticket = escpos::payment_receipt(&settings, logo.as_ref(), &invoice, &payment);
+    escpos::send(&target, &ticket).map_err(|e| e.to_string())?;
+
+    Ok(target.to_string())
 }
 
 /// Register a payment for an invoice
@@ -92,6 +145,9 @@ pub async fn register_payment(
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
 
+    time_guard::verify_time_integrity(&conn)?;
+    license::require_module(&conn, "invoicing")?;
+
     println!("DEBUG: Checking invoice status...");
     // Check invoice exists and get current amounts
     let (status, total, paid_amount): (String, f64, f64) = conn
@@ -158,10 +214,24 @@ pub async fn register_payment(
     )
     .map_err(|e| format!("Error al actualizar factura: {}", e))?;
 
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        audit::AuditEventType::PaymentRegistered,
+        Some("payment"),
+        Some(&id),
+        &format!(
+            "invoice_id={}, amount={}, currency={}, method={}, invoice_status={}",
+            data.invoice_id, data.amount, data.currency, data.payment_method, new_status
+        ),
+    )
+    .ok();
+
     // Return created payment
     conn.query_row(
         "SELECT id, tenant_id, invoice_id, amount, currency, exchange_rate, payment_method,
-                reference, bank_account_id, payment_date, notes, created_by, created_at, received_amount
+                reference, bank_account_id, payment_date, notes, created_by, created_at, received_amount, session_id
          FROM billing_payments WHERE id = ?1",
         [&id],
         |row| {
@@ -180,6 +250,7 @@ pub async fn register_payment(
                 created_by: row.get(11)?,
                 created_at: row.get(12)?,
                 received_amount: row.get(13)?,
+                session_id: row.get(14)?,
             })
         },
     )
@@ -194,6 +265,7 @@ pub async fn delete_payment(state: State<'_, AppState>, id: String) -> Result<()
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
+    license::require_module(&conn, "invoicing")?;
     let now = chrono::Utc::now().to_rfc3339();
 
     // Get payment details
@@ -237,6 +309,21 @@ pub async fn delete_payment(state: State<'_, AppState>, id: String) -> Result<()
     )
     .map_err(|e| format!("Error al actualizar factura: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::PaymentDeleted,
+        Some("payment"),
+        Some(&id),
+        &format!(
+            "invoice_id={}, amount={}, invoice_status={}",
+            invoice_id, amount, new_status
+        ),
+    )
+    .ok();
+
     Ok(())
 }
 
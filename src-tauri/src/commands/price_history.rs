@@ -30,7 +30,7 @@ pub async fn list_price_history(
 
     let mut sql = String::from(
         "SELECT id, tenant_id, product_id, variant_id, price_type, old_price, new_price,
-                changed_by, reason, created_at
+                changed_by, reason, created_at, price_list_id, batch_id
          FROM price_history WHERE tenant_id = ?1",
     );
 
@@ -69,6 +69,13 @@ pub async fn list_price_history(
         ));
     }
 
+    if let Some(ref batch_id) = filters.batch_id {
+        sql.push_str(&format!(
+            " AND batch_id = '{}'",
+            batch_id.replace('\'', "''")
+        ));
+    }
+
     sql.push_str(" ORDER BY created_at DESC LIMIT 100");
 
     let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
@@ -85,6 +92,8 @@ pub async fn list_price_history(
                 changed_by: row.get(7)?,
                 reason: row.get(8)?,
                 created_at: row.get(9)?,
+                price_list_id: row.get(10)?,
+                batch_id: row.get(11)?,
             })
         })
         .map_err(|e| e.to_string())?
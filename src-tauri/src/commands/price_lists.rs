@@ -1,8 +1,11 @@
 //! Price List Commands
 
+use crate::models::pricing::PriceBatch;
 use crate::models::{
     CreatePriceListDto, PriceList, ProductPrice, SetProductPriceDto, UpdatePriceListDto,
 };
+use crate::security::audit;
+use crate::services::repricing;
 use crate::state::AppState;
 use tauri::State;
 use uuid::Uuid;
@@ -21,6 +24,30 @@ fn get_tenant_id(state: &State<'_, AppState>) -> Result<String, String> {
 // PRICE LISTS
 // ============================================
 
+const PRICE_LIST_COLUMNS: &str =
+    "id, tenant_id, name, description, currency, discount_percent, is_default, is_active,
+     created_at, updated_at, base_price_list_id, round_to, round_mode, last_rate, repriced_at";
+
+fn map_price_list(row: &rusqlite::Row) -> rusqlite::Result<PriceList> {
+    Ok(PriceList {
+        id: row.get(0)?,
+        tenant_id: row.get(1)?,
+        name: row.get(2)?,
+        description: row.get(3)?,
+        currency: row.get(4)?,
+        discount_percent: row.get(5)?,
+        is_default: row.get::<_, i32>(6)? == 1,
+        is_active: row.get::<_, i32>(7)? == 1,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        base_price_list_id: row.get(10)?,
+        round_to: row.get(11)?,
+        round_mode: row.get(12)?,
+        last_rate: row.get(13)?,
+        repriced_at: row.get(14)?,
+    })
+}
+
 /// List all price lists
 #[tauri::command]
 pub async fn list_price_lists(state: State<'_, AppState>) -> Result<Vec<PriceList>, String> {
@@ -31,29 +58,15 @@ pub async fn list_price_lists(state: State<'_, AppState>) -> Result<Vec<PriceLis
         .map_err(|_| "Error al acceder a la base de datos")?;
 
     let mut stmt = conn
-        .prepare(
-            "SELECT id, tenant_id, name, description, currency, discount_percent, is_default,
-                    is_active, created_at, updated_at
-             FROM price_lists WHERE tenant_id = ?1
+        .prepare(&format!(
+            "SELECT {} FROM price_lists WHERE tenant_id = ?1
              ORDER BY is_default DESC, name ASC",
-        )
+            PRICE_LIST_COLUMNS
+        ))
         .map_err(|e| e.to_string())?;
 
     let lists = stmt
-        .query_map([&tenant_id], |row| {
-            Ok(PriceList {
-                id: row.get(0)?,
-                tenant_id: row.get(1)?,
-                name: row.get(2)?,
-                description: row.get(3)?,
-                currency: row.get(4)?,
-                discount_percent: row.get(5)?,
-                is_default: row.get::<_, i32>(6)? == 1,
-                is_active: row.get::<_, i32>(7)? == 1,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-            })
-        })
+        .query_map([&tenant_id], map_price_list)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
@@ -71,24 +84,12 @@ pub async fn get_price_list(state: State<'_, AppState>, id: String) -> Result<Pr
         .map_err(|_| "Error al acceder a la base de datos")?;
 
     conn.query_row(
-        "SELECT id, tenant_id, name, description, currency, discount_percent, is_default,
-                is_active, created_at, updated_at
-         FROM price_lists WHERE id = ?1 AND tenant_id = ?2",
+        &format!(
+            "SELECT {} FROM price_lists WHERE id = ?1 AND tenant_id = ?2",
+            PRICE_LIST_COLUMNS
+        ),
         [&id, &tenant_id],
-        |row| {
-            Ok(PriceList {
-                id: row.get(0)?,
-                tenant_id: row.get(1)?,
-                name: row.get(2)?,
-                description: row.get(3)?,
-                currency: row.get(4)?,
-                discount_percent: row.get(5)?,
-                is_default: row.get::<_, i32>(6)? == 1,
-                is_active: row.get::<_, i32>(7)? == 1,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-            })
-        },
+        map_price_list,
     )
     .map_err(|e| format!("Error al obtener lista de precios: {}", e))
 }
@@ -108,6 +109,16 @@ pub async fn create_price_list(
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
 
+    let base_price_list_id = data
+        .base_price_list_id
+        .as_deref()
+        .filter(|base| !base.is_empty());
+    if let Some(base) = base_price_list_id {
+        repricing::check_derivation(&conn, &tenant_id, None, base, &data.currency)
+            .map_err(|e| e.to_string())?;
+    }
+    repricing::check_round_mode(data.round_mode.as_deref()).map_err(|e| e.to_string())?;
+
     // If is_default, unset other defaults
     if data.is_default {
         conn.execute(
@@ -118,8 +129,9 @@ pub async fn create_price_list(
     }
 
     conn.execute(
-        "INSERT INTO price_lists (id, tenant_id, name, description, currency, discount_percent, is_default, is_active, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 1, ?8, ?8)",
+        "INSERT INTO price_lists (id, tenant_id, name, description, currency, discount_percent, is_default, is_active, created_at, updated_at,
+         base_price_list_id, round_to, round_mode)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 1, ?8, ?8, ?9, ?10, ?11)",
         rusqlite::params![
             &id,
             &tenant_id,
@@ -128,30 +140,44 @@ pub async fn create_price_list(
             &data.currency,
             data.discount_percent,
             if data.is_default { 1 } else { 0 },
-            &now
+            &now,
+            base_price_list_id,
+            data.round_to,
+            &data.round_mode
         ],
     )
     .map_err(|e| format!("Error al crear lista de precios: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    if base_price_list_id.is_some() {
+        repricing::derive_list(&conn, &tenant_id, user_id.as_deref(), &id)
+            .map_err(|e| e.to_string())?;
+    }
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::PriceListCreated,
+        Some("price_list"),
+        Some(&id),
+        &format!(
+            "name={}, currency={}, discount_percent={}, base_price_list_id={}",
+            data.name,
+            data.currency,
+            data.discount_percent,
+            base_price_list_id.unwrap_or("")
+        ),
+    )
+    .ok();
+
     conn.query_row(
-        "SELECT id, tenant_id, name, description, currency, discount_percent, is_default,
-                is_active, created_at, updated_at
-         FROM price_lists WHERE id = ?1",
+        &format!(
+            "SELECT {} FROM price_lists WHERE id = ?1",
+            PRICE_LIST_COLUMNS
+        ),
         [&id],
-        |row| {
-            Ok(PriceList {
-                id: row.get(0)?,
-                tenant_id: row.get(1)?,
-                name: row.get(2)?,
-                description: row.get(3)?,
-                currency: row.get(4)?,
-                discount_percent: row.get(5)?,
-                is_default: row.get::<_, i32>(6)? == 1,
-                is_active: row.get::<_, i32>(7)? == 1,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-            })
-        },
+        map_price_list,
     )
     .map_err(|e| format!("Error al obtener lista creada: {}", e))
 }
@@ -170,6 +196,32 @@ pub async fn update_price_list(
         .map_err(|_| "Error al acceder a la base de datos")?;
     let now = chrono::Utc::now().to_rfc3339();
 
+    // Derivation after the update, checked before anything changes
+    let (currency, base_price_list_id): (String, Option<String>) = conn
+        .query_row(
+            "SELECT currency, base_price_list_id FROM price_lists WHERE id = ?1 AND tenant_id = ?2",
+            [&id, &tenant_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| format!("Error al obtener lista: {}", e))?;
+    let currency = data.currency.clone().unwrap_or(currency);
+    let base_price_list_id = match &data.base_price_list_id {
+        Some(base) if base.is_empty() => None,
+        Some(base) => Some(base.clone()),
+        None => base_price_list_id,
+    };
+    let derivation_changed = data.base_price_list_id.is_some()
+        || data.currency.is_some()
+        || data.round_to.is_some()
+        || data.round_mode.is_some();
+    if derivation_changed {
+        if let Some(base) = &base_price_list_id {
+            repricing::check_derivation(&conn, &tenant_id, Some(&id), base, &currency)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    repricing::check_round_mode(data.round_mode.as_deref()).map_err(|e| e.to_string())?;
+
     // If setting as default, unset others
     if data.is_default == Some(true) {
         conn.execute(
@@ -199,6 +251,21 @@ pub async fn update_price_list(
     if let Some(is_active) = data.is_active {
         set_clauses.push(format!("is_active = {}", if is_active { 1 } else { 0 }));
     }
+    if data.base_price_list_id.is_some() {
+        match &base_price_list_id {
+            Some(base) => set_clauses.push(format!(
+                "base_price_list_id = '{}'",
+                base.replace('\'', "''")
+            )),
+            None => set_clauses.push("base_price_list_id = NULL".to_string()),
+        }
+    }
+    if let Some(round_to) = data.round_to {
+        set_clauses.push(format!("round_to = {}", round_to));
+    }
+    if let Some(ref round_mode) = data.round_mode {
+        set_clauses.push(format!("round_mode = '{}'", round_mode));
+    }
 
     let query = format!(
         "UPDATE price_lists SET {} WHERE id = '{}' AND tenant_id = '{}'",
@@ -210,29 +277,70 @@ pub async fn update_price_list(
     conn.execute(&query, [])
         .map_err(|e| format!("Error al actualizar lista: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    if derivation_changed && base_price_list_id.is_some() {
+        repricing::derive_list(&conn, &tenant_id, user_id.as_deref(), &id)
+            .map_err(|e| e.to_string())?;
+    }
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::PriceListUpdated,
+        Some("price_list"),
+        Some(&id),
+        &format!("changes: {}", set_clauses[1..].join(", ")),
+    )
+    .ok();
+
     conn.query_row(
-        "SELECT id, tenant_id, name, description, currency, discount_percent, is_default,
-                is_active, created_at, updated_at
-         FROM price_lists WHERE id = ?1",
+        &format!(
+            "SELECT {} FROM price_lists WHERE id = ?1",
+            PRICE_LIST_COLUMNS
+        ),
         [&id],
-        |row| {
-            Ok(PriceList {
-                id: row.get(0)?,
-                tenant_id: row.get(1)?,
-                name: row.get(2)?,
-                description: row.get(3)?,
-                currency: row.get(4)?,
-                discount_percent: row.get(5)?,
-                is_default: row.get::<_, i32>(6)? == 1,
-                is_active: row.get::<_, i32>(7)? == 1,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-            })
-        },
+        map_price_list,
     )
     .map_err(|e| format!("Error al obtener lista: {}", e))
 }
 
+/// Recompute a derived list from its base list and the latest rates; None
+/// if no price changed
+#[tauri::command]
+pub async fn recompute_price_list(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<Option<PriceBatch>, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    let batch = repricing::derive_list(&conn, &tenant_id, user_id.as_deref(), &id)
+        .map_err(|e| e.to_string())?;
+
+    if let Some(batch) = &batch {
+        audit::log_event(
+            &conn,
+            Some(&tenant_id),
+            user_id.as_deref(),
+            audit::AuditEventType::PriceBatchApplied,
+            Some("price_batch"),
+            Some(&batch.id),
+            &format!(
+                "price_list_id={}, items={}, reason={}",
+                id, batch.items_count, batch.reason
+            ),
+        )
+        .ok();
+    }
+
+    Ok(batch)
+}
+
 /// Delete a price list (soft delete)
 #[tauri::command]
 pub async fn delete_price_list(state: State<'_, AppState>, id: String) -> Result<(), String> {
@@ -249,6 +357,18 @@ pub async fn delete_price_list(state: State<'_, AppState>, id: String) -> Result
     )
     .map_err(|e| format!("Error al eliminar lista: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::PriceListDeleted,
+        Some("price_list"),
+        Some(&id),
+        "Soft delete",
+    )
+    .ok();
+
     Ok(())
 }
 
@@ -299,12 +419,20 @@ pub async fn set_product_price(
     state: State<'_, AppState>,
     data: SetProductPriceDto,
 ) -> Result<ProductPrice, String> {
+    let tenant_id = get_tenant_id(&state)?;
     let conn = state
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
     let now = chrono::Utc::now().to_rfc3339();
 
+    conn.query_row(
+        "SELECT id FROM price_lists WHERE id = ?1 AND tenant_id = ?2",
+        [&data.price_list_id, &tenant_id],
+        |row| row.get::<_, String>(0),
+    )
+    .map_err(|_| "Lista de precios no encontrada".to_string())?;
+
     // Check if price already exists
     let existing_id: Option<String> = if data.variant_id.is_some() {
         conn.query_row(
@@ -322,6 +450,15 @@ pub async fn set_product_price(
         .ok()
     };
 
+    let old_price: Option<f64> = existing_id.as_ref().and_then(|existing| {
+        conn.query_row(
+            "SELECT price FROM product_prices WHERE id = ?1",
+            [existing],
+            |row| row.get(0),
+        )
+        .ok()
+    });
+
     let id = if let Some(existing) = existing_id {
         // Update existing
         conn.execute(
@@ -349,6 +486,25 @@ pub async fn set_product_price(
         new_id
     };
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::ProductPriceSet,
+        Some("product_price"),
+        Some(&id),
+        &format!(
+            "price_list_id={}, product_id={}, variant_id={}, price={:?} -> {}",
+            data.price_list_id,
+            data.product_id,
+            data.variant_id.as_deref().unwrap_or(""),
+            old_price,
+            data.price
+        ),
+    )
+    .ok();
+
     conn.query_row(
         "SELECT id, price_list_id, product_id, variant_id, price, created_at, updated_at
          FROM product_prices WHERE id = ?1",
@@ -371,13 +527,40 @@ pub async fn set_product_price(
 /// Delete a product price
 #[tauri::command]
 pub async fn delete_product_price(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    let tenant_id = get_tenant_id(&state)?;
     let conn = state
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
 
+    let (price_list_id, product_id, price): (String, String, f64) = conn
+        .query_row(
+            "SELECT pp.price_list_id, pp.product_id, pp.price
+             FROM product_prices pp
+             JOIN price_lists pl ON pl.id = pp.price_list_id
+             WHERE pp.id = ?1 AND pl.tenant_id = ?2",
+            [&id, &tenant_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|_| "Precio no encontrado".to_string())?;
+
     conn.execute("DELETE FROM product_prices WHERE id = ?1", [&id])
         .map_err(|e| format!("Error al eliminar precio: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::ProductPriceDeleted,
+        Some("product_price"),
+        Some(&id),
+        &format!(
+            "price_list_id={}, product_id={}, price={}",
+            price_list_id, product_id, price
+        ),
+    )
+    .ok();
+
     Ok(())
 }
@@ -0,0 +1,21 @@
+//! Pricing Commands
+
+use crate::models::pricing::{PriceQuery, ResolvedPrice};
+use crate::services::pricing;
+use crate::state::AppState;
+use tauri::State;
+
+/// Price a line as an invoice would: list price, discounts and tax
+#[tauri::command]
+pub async fn resolve_price(
+    state: State<'_, AppState>,
+    query: PriceQuery,
+) -> Result<ResolvedPrice, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    pricing::resolve(&conn, &tenant_id, &query).map_err(|e| e.to_string())
+}
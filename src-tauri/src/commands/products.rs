@@ -1,7 +1,12 @@
 //! Product Commands
 
-use crate::models::{CreateProductDto, Product, ProductFilters, UpdateProductDto};
+use crate::commands::settings;
+use crate::models::barcode::{BarcodeMatch, GeneratedBarcode};
+use crate::models::{CreateProductDto, Product, ProductFilters, ScaleLabelRule, UpdateProductDto};
+use crate::security::audit;
+use crate::services::{barcodes, inventory, reservations};
 use crate::state::AppState;
+use std::collections::HashSet;
 use tauri::State;
 use uuid::Uuid;
 
@@ -59,6 +64,7 @@ pub async fn list_products(
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
+    reservations::release_expired(&conn, &tenant_id).map_err(|e| e.to_string())?;
 
     let mut sql = String::from(
         "SELECT id, tenant_id, sku, barcode, name, description, category_id, unit_id, product_type_id,
@@ -68,7 +74,7 @@ pub async fn list_products(
                 COALESCE(min_stock, 0) as min_stock, COALESCE(max_stock, 0) as max_stock,
                 supplier_reference, image_url, COALESCE(has_variants, 0) as has_variants,
                 COALESCE(track_expiration, 0) as track_expiration, COALESCE(cost_method, 'manual') as cost_method,
-                is_active, created_at, updated_at
+                is_active, created_at, updated_at, COALESCE(reserved_quantity, 0) as reserved_quantity
          FROM products WHERE tenant_id = ?1"
     );
 
@@ -131,6 +137,8 @@ pub async fn list_products(
                 margin_amount: row.get(12)?,
                 tax_rate: row.get(13)?,
                 stock_quantity: row.get(14)?,
+                reserved_quantity: row.get(25)?,
+                available_quantity: row.get::<_, f64>(14)? - row.get::<_, f64>(25)?,
                 min_stock: row.get(15)?,
                 max_stock: row.get(16)?,
                 supplier_reference: row.get(17)?,
@@ -158,6 +166,7 @@ pub async fn get_product(state: State<'_, AppState>, id: String) -> Result<Produ
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
+    reservations::release_expired(&conn, &tenant_id).map_err(|e| e.to_string())?;
 
     conn.query_row(
         "SELECT id, tenant_id, sku, barcode, name, description, category_id, unit_id, product_type_id,
@@ -165,7 +174,7 @@ pub async fn get_product(state: State<'_, AppState>, id: String) -> Result<Produ
                 COALESCE(margin_amount, 0), COALESCE(tax_rate, 16.0), COALESCE(stock_quantity, 0),
                 COALESCE(min_stock, 0), COALESCE(max_stock, 0), supplier_reference, image_url,
                 COALESCE(has_variants, 0), COALESCE(track_expiration, 0), COALESCE(cost_method, 'manual'),
-                is_active, created_at, updated_at
+                is_active, created_at, updated_at, COALESCE(reserved_quantity, 0)
          FROM products WHERE id = ?1 AND tenant_id = ?2",
         [&id, &tenant_id],
         |row| {
@@ -185,6 +194,8 @@ pub async fn get_product(state: State<'_, AppState>, id: String) -> Result<Produ
                 margin_amount: row.get(12)?,
                 tax_rate: row.get(13)?,
                 stock_quantity: row.get(14)?,
+                reserved_quantity: row.get(25)?,
+                available_quantity: row.get::<_, f64>(14)? - row.get::<_, f64>(25)?,
                 min_stock: row.get(15)?,
                 max_stock: row.get(16)?,
                 supplier_reference: row.get(17)?,
@@ -263,6 +274,18 @@ pub async fn create_product(
             ],
         )
         .map_err(|e| format!("Error al crear producto: {}", e))?;
+
+        let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+        audit::log_event(
+            &conn,
+            Some(&tenant_id),
+            user_id.as_deref(),
+            audit::AuditEventType::ProductCreated,
+            Some("product"),
+            Some(&id),
+            &format!("sku={}, name={}, sale_price={}", sku, data.name, sale_price),
+        )
+        .ok();
     }
 
     get_product(state, id).await
@@ -379,9 +402,23 @@ pub async fn update_product(
         conn.execute(&query, [])
             .map_err(|e| format!("Error al actualizar producto: {}", e))?;
 
+        let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+        audit::log_event(
+            &conn,
+            Some(&tenant_id),
+            user_id.as_deref(),
+            audit::AuditEventType::ProductUpdated,
+            Some("product"),
+            Some(&id),
+            &format!(
+                "cost_price={} -> {}, sale_price={} -> {}",
+                current_cost, new_cost, current_sale, new_sale
+            ),
+        )
+        .ok();
+
         // Price History Logic
         if data.sale_price.is_some() || data.cost_price.is_some() {
-            let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
             if let Some(new_sale) = data.sale_price {
                 if new_sale != current_sale {
                     let _ = crate::commands::price_history::record_price_change_db(
@@ -434,6 +471,18 @@ pub async fn delete_product(state: State<'_, AppState>, id: String) -> Result<()
     )
     .map_err(|e| format!("Error al eliminar producto: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::ProductDeleted,
+        Some("product"),
+        Some(&id),
+        "Soft delete",
+    )
+    .ok();
+
     Ok(())
 }
 
@@ -453,16 +502,29 @@ pub async fn restore_product(state: State<'_, AppState>, id: String) -> Result<(
     )
     .map_err(|e| format!("Error al restaurar producto: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::ProductRestored,
+        Some("product"),
+        Some(&id),
+        "Restored",
+    )
+    .ok();
+
     Ok(())
 }
 
-/// Adjust product stock
+/// Adjust product stock in a warehouse (the branch default if none is given)
 #[tauri::command]
 pub async fn adjust_stock(
     state: State<'_, AppState>,
     product_id: String,
     quantity: f64,
     reason: Option<String>,
+    warehouse_id: Option<String>,
 ) -> Result<Product, String> {
     let tenant_id = get_tenant_id(&state)?;
 
@@ -472,47 +534,141 @@ pub async fn adjust_stock(
             .lock()
             .map_err(|_| "Error al acceder a la base de datos")?;
         let now = chrono::Utc::now().to_rfc3339();
-
-        // Update stock
-        conn.execute(
-            "UPDATE products SET stock_quantity = stock_quantity + ?1, updated_at = ?2 WHERE id = ?3 AND tenant_id = ?4",
-            rusqlite::params![quantity, &now, &product_id, &tenant_id],
+        let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+        let warehouse_id = inventory::resolve_warehouse(&conn, &tenant_id, warehouse_id.as_deref())
+            .map_err(|e| e.to_string())?;
+
+        let movement_id = inventory::record(
+            &conn,
+            &inventory::StockChange {
+                tenant_id: &tenant_id,
+                warehouse_id: &warehouse_id,
+                product_id: &product_id,
+                variant_id: None,
+                lot_id: None,
+                quantity,
+            },
+            &inventory::MovementInfo {
+                notes: reason.as_deref(),
+                user_id: user_id.as_deref(),
+                ..Default::default()
+            },
+            &now,
         )
         .map_err(|e| format!("Error al ajustar stock: {}", e))?;
 
-        // Record movement
-        let movement_id = Uuid::new_v4().to_string();
-        let movement_type = if quantity >= 0.0 { "ENTRADA" } else { "SALIDA" };
-
-        conn.execute(
-            "INSERT INTO inventory_movements (id, tenant_id, product_id, movement_type, quantity, notes, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            rusqlite::params![
-                &movement_id,
-                &tenant_id,
-                &product_id,
-                movement_type,
-                quantity.abs(),
-                &reason,
-                &now
-            ],
+        audit::log_event(
+            &conn,
+            Some(&tenant_id),
+            user_id.as_deref(),
+            audit::AuditEventType::InventoryAdjusted,
+            Some("product"),
+            Some(&product_id),
+            &format!(
+                "movement_id={}, warehouse_id={}, quantity={}, reason={}",
+                movement_id,
+                warehouse_id,
+                quantity,
+                reason.as_deref().unwrap_or("")
+            ),
         )
-        .map_err(|e| format!("Error al registrar movimiento: {}", e))?;
+        .ok();
     }
 
     get_product(state, product_id).await
 }
 
-/// Get products with low stock
+/// Get products with low stock in any warehouse, or in the given one
 #[tauri::command]
-pub async fn get_low_stock_products(state: State<'_, AppState>) -> Result<Vec<Product>, String> {
-    list_products(
+pub async fn get_low_stock_products(
+    state: State<'_, AppState>,
+    warehouse_id: Option<String>,
+) -> Result<Vec<Product>, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let low: HashSet<String> = {
+        let conn = state
+            .db
+            .lock()
+            .map_err(|_| "Error al acceder a la base de datos")?;
+        inventory::low_stock(&conn, &tenant_id, warehouse_id.as_deref())
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|item| item.product_id)
+            .collect()
+    };
+
+    let products = list_products(
         state,
         Some(ProductFilters {
-            low_stock: Some(true),
             is_active: Some(true),
             ..Default::default()
         }),
     )
-    .await
+    .await?;
+    Ok(products
+        .into_iter()
+        .filter(|p| low.contains(&p.id))
+        .collect())
+}
+
+/// Resolve a scanned barcode, GS1 string or scale label to a product,
+/// variant or lot, with the quantity it carries
+#[tauri::command]
+pub async fn lookup_by_barcode(
+    state: State<'_, AppState>,
+    code: String,
+) -> Result<BarcodeMatch, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    let rules = settings::query_company_settings(&conn, &tenant_id)
+        .map(|settings| settings.scale_labels)
+        .unwrap_or_else(|_| ScaleLabelRule::defaults());
+    barcodes::lookup(&conn, &tenant_id, &code, &rules).map_err(|e| e.to_string())
+}
+
+/// Give internal EAN-13 barcodes to products and variants that have none
+/// (only the given products, if any)
+#[tauri::command]
+pub async fn generate_internal_barcodes(
+    state: State<'_, AppState>,
+    product_ids: Option<Vec<String>>,
+) -> Result<Vec<GeneratedBarcode>, String> {
+    let tenant_id = get_tenant_id(&state)?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let generated = barcodes::generate_internal_barcodes(
+        &conn,
+        &tenant_id,
+        &product_ids.unwrap_or_default(),
+        &now,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    for item in &generated {
+        audit::log_event(
+            &conn,
+            Some(&tenant_id),
+            user_id.as_deref(),
+            audit::AuditEventType::ProductUpdated,
+            Some("product"),
+            Some(&item.product_id),
+            &format!(
+                "barcode={}, variant_id={}",
+                item.barcode,
+                item.variant_id.as_deref().unwrap_or("-")
+            ),
+        )
+        .ok();
+    }
+
+    Ok(generated)
 }
@@ -0,0 +1,96 @@
+//! Report Commands
+//!
+//! Sales, margin, tax and payment reports over one or more branches.
+
+use crate::models::report::{
+    PaymentMixRow, ReportFilters, SalesGrouping, SalesRow, TaxReportRow, TopProduct, TopProductsBy,
+};
+use crate::services::reports;
+use crate::state::AppState;
+use tauri::State;
+
+/// Sales and gross margin grouped by period, product, category, client,
+/// user or register
+#[tauri::command]
+pub async fn get_sales_report(
+    state: State<'_, AppState>,
+    grouping: SalesGrouping,
+    filters: Option<ReportFilters>,
+) -> Result<Vec<SalesRow>, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    reports::sales(
+        &conn,
+        &user_id,
+        &tenant_id,
+        &filters.unwrap_or_default(),
+        grouping,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Tax collected per rate and currency
+#[tauri::command]
+pub async fn get_tax_report(
+    state: State<'_, AppState>,
+    filters: Option<ReportFilters>,
+) -> Result<Vec<TaxReportRow>, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    reports::taxes(&conn, &user_id, &tenant_id, &filters.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Payments received per method and currency
+#[tauri::command]
+pub async fn get_payment_mix(
+    state: State<'_, AppState>,
+    filters: Option<ReportFilters>,
+) -> Result<Vec<PaymentMixRow>, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    reports::payment_mix(&conn, &user_id, &tenant_id, &filters.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// The `limit` best-selling products (10 by default), by revenue unless
+/// told otherwise
+#[tauri::command]
+pub async fn get_top_products(
+    state: State<'_, AppState>,
+    filters: Option<ReportFilters>,
+    by: Option<TopProductsBy>,
+    limit: Option<usize>,
+) -> Result<Vec<TopProduct>, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    reports::top_products(
+        &conn,
+        &user_id,
+        &tenant_id,
+        &filters.unwrap_or_default(),
+        by.unwrap_or(TopProductsBy::Revenue),
+        limit,
+    )
+    .map_err(|e| e.to_string())
+}
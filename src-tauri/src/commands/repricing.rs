@@ -0,0 +1,101 @@
+//! Bulk Repricing Commands
+
+use crate::commands::auth::require_admin;
+use crate::models::pricing::{BulkRepriceDto, PriceBatch, PriceBatchUndo, PriceChange};
+use crate::security::audit::{self, AuditEventType};
+use crate::services::repricing;
+use crate::state::AppState;
+use tauri::State;
+
+/// Prices a bulk change would set
+#[tauri::command]
+pub async fn preview_bulk_reprice(
+    state: State<'_, AppState>,
+    data: BulkRepriceDto,
+) -> Result<Vec<PriceChange>, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    repricing::preview(&conn, &tenant_id, &data).map_err(|e| e.to_string())
+}
+
+/// Apply a bulk price change as one batch
+#[tauri::command]
+pub async fn apply_bulk_reprice(
+    state: State<'_, AppState>,
+    data: BulkRepriceDto,
+) -> Result<PriceBatch, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    require_admin(&conn, &user_id)?;
+
+    let batch = repricing::apply(&conn, &tenant_id, &user_id, &data).map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        AuditEventType::PriceBatchApplied,
+        Some("price_batch"),
+        Some(&batch.id),
+        &format!(
+            "operation={}, value={}, price_list_id={}, items={}, reason={}",
+            batch.operation,
+            batch.value,
+            batch.price_list_id.as_deref().unwrap_or(""),
+            batch.items_count,
+            batch.reason
+        ),
+    )
+    .ok();
+
+    Ok(batch)
+}
+
+#[tauri::command]
+pub async fn list_price_batches(state: State<'_, AppState>) -> Result<Vec<PriceBatch>, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    repricing::list_batches(&conn, &tenant_id).map_err(|e| e.to_string())
+}
+
+/// Put back the prices of a batch
+#[tauri::command]
+pub async fn undo_price_batch(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<PriceBatchUndo, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    require_admin(&conn, &user_id)?;
+
+    let result = repricing::undo(&conn, &tenant_id, &user_id, &id).map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        AuditEventType::PriceBatchUndone,
+        Some("price_batch"),
+        Some(&id),
+        &format!("restored={}, skipped={}", result.restored, result.skipped),
+    )
+    .ok();
+
+    Ok(result)
+}
@@ -0,0 +1,49 @@
+//! Stock Reservation Commands
+
+use crate::models::reservation::{ReservationFilters, StockReservation};
+use crate::services::reservations;
+use crate::state::AppState;
+use tauri::State;
+
+/// Stock held by draft invoices and accepted quotes of the active branch
+#[tauri::command]
+pub async fn list_stock_reservations(
+    state: State<'_, AppState>,
+    filters: Option<ReservationFilters>,
+) -> Result<Vec<StockReservation>, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    reservations::release_expired(&conn, &tenant_id).map_err(|e| e.to_string())?;
+    reservations::list(&conn, &tenant_id, &filters.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+/// Available-to-promise of a product (or variant) in a warehouse
+#[tauri::command]
+pub async fn get_available_stock(
+    state: State<'_, AppState>,
+    product_id: String,
+    variant_id: Option<String>,
+    warehouse_id: Option<String>,
+) -> Result<f64, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    let warehouse_id =
+        crate::services::inventory::resolve_warehouse(&conn, &tenant_id, warehouse_id.as_deref())
+            .map_err(|e| e.to_string())?;
+    reservations::available(
+        &conn,
+        &warehouse_id,
+        &product_id,
+        variant_id.as_deref(),
+        None,
+    )
+    .map_err(|e| e.to_string())
+}
@@ -1,12 +1,147 @@
+use crate::commands::auth::require_admin;
+use crate::models::license::LicenseStatusKind;
+use crate::security::audit::{self, AuditEventType};
 use crate::security::hardware_lock;
-use tauri::command;
+use crate::security::key_store::{self, KeyStatus};
+use crate::security::license;
+use crate::security::time_guard::{self, TimeIntegrityStatus};
+use crate::state::AppState;
+use tauri::{command, State};
 
 #[command]
 pub fn get_hardware_id() -> String {
     hardware_lock::get_hardware_fingerprint()
 }
 
+/// Verify the installed license offline (signature, machine, expiry and grace)
 #[command]
-pub fn verify_license_locally(expected_id: String) -> bool {
-    hardware_lock::verify_hardware_lock(&expected_id)
+pub async fn verify_license_locally(state: State<'_, AppState>) -> Result<bool, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let status = license::current_state(&conn)?.status;
+
+    Ok(matches!(
+        status,
+        LicenseStatusKind::Valid | LicenseStatusKind::Grace | LicenseStatusKind::Trial
+    ))
+}
+
+#[command]
+pub async fn get_time_integrity_status(
+    state: State<'_, AppState>,
+) -> Result<TimeIntegrityStatus, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    time_guard::get_status(&conn)
+}
+
+/// Accept the current system clock after a rollback alert (admin only)
+#[command]
+pub async fn recover_time_integrity(
+    state: State<'_, AppState>,
+    justification: String,
+) -> Result<TimeIntegrityStatus, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let user_id = state.require_user()?;
+    require_admin(&conn, &user_id)?;
+
+    time_guard::recover_time_integrity(
+        &conn,
+        state.require_tenant().ok().as_deref(),
+        &user_id,
+        &justification,
+    )
+}
+
+/// Status of the database key hierarchy (recovery configured, last rotation)
+#[command]
+pub async fn get_key_status(state: State<'_, AppState>) -> Result<KeyStatus, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let user_id = state.require_user()?;
+    require_admin(&conn, &user_id)?;
+
+    key_store::status(&conn, state.security.uses_device_id())
+}
+
+/// Generate a new recovery code for the database key (admin only).
+///
+/// The code is returned once and never stored; it replaces any previous
+/// recovery code or passphrase.
+#[command]
+pub async fn generate_recovery_code(state: State<'_, AppState>) -> Result<String, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let user_id = state.require_user()?;
+    require_admin(&conn, &user_id)?;
+
+    let code = key_store::generate_recovery_code();
+    key_store::set_recovery_secret(&conn, &state.security.get_key_encryption_key(), &code)?;
+
+    audit::log_event(
+        &conn,
+        state.require_tenant().ok().as_deref(),
+        Some(&user_id),
+        AuditEventType::KeyRecoveryConfigured,
+        Some("database_key"),
+        None,
+        "method=code",
+    )
+    .ok();
+
+    Ok(code)
+}
+
+/// Set a recovery passphrase for the database key (admin only)
+#[command]
+pub async fn set_recovery_passphrase(
+    state: State<'_, AppState>,
+    passphrase: String,
+) -> Result<KeyStatus, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let user_id = state.require_user()?;
+    require_admin(&conn, &user_id)?;
+
+    key_store::set_recovery_secret(&conn, &state.security.get_key_encryption_key(), &passphrase)?;
+
+    audit::log_event(
+        &conn,
+        state.require_tenant().ok().as_deref(),
+        Some(&user_id),
+        AuditEventType::KeyRecoveryConfigured,
+        Some("database_key"),
+        None,
+        "method=passphrase",
+    )
+    .ok();
+
+    key_store::status(&conn, state.security.uses_device_id())
+}
+
+/// Rotate the database data key and re-encrypt the database (admin only)
+#[command]
+pub async fn rotate_database_key(
+    state: State<'_, AppState>,
+    recovery_secret: Option<String>,
+) -> Result<KeyStatus, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    let user_id = state.require_user()?;
+    require_admin(&conn, &user_id)?;
+
+    let previous = key_store::status(&conn, state.security.uses_device_id())?;
+    key_store::rotate_data_key(
+        &conn,
+        &state.security.get_key_encryption_key(),
+        recovery_secret.as_deref(),
+    )?;
+    let status = key_store::status(&conn, state.security.uses_device_id())?;
+
+    audit::log_event(
+        &conn,
+        state.require_tenant().ok().as_deref(),
+        Some(&user_id),
+        AuditEventType::DatabaseKeyRotated,
+        Some("database_key"),
+        Some(&status.key_id),
+        &format!("previous_key_id={}", previous.key_id),
+    )
+    .ok();
+
+    Ok(status)
 }
@@ -2,8 +2,11 @@
 
 use crate::models::{
     BankAccount, CompanySettings, CreateBankAccountDto, CreateTaxSettingDto, InvoiceSequence,
-    TaxSetting, UpdateBankAccountDto, UpdateCompanySettingsDto, UpdateTaxSettingDto,
+    ScaleLabelRule, TaxSetting, UpdateBankAccountDto, UpdateCompanySettingsDto,
+    UpdateTaxSettingDto,
 };
+use crate::security::{audit, license, time_guard};
+use crate::services::{barcodes, escpos};
 use crate::state::AppState;
 use tauri::State;
 use uuid::Uuid;
@@ -23,14 +26,15 @@ fn get_tenant_id(state: &State<'_, AppState>) -> Result<String, String> {
 // ============================================
 
 /// Helper to query company settings by tenant_id
-fn query_company_settings(
+pub(crate) fn query_company_settings(
     conn: &rusqlite::Connection,
     tenant_id: &str,
 ) -> Result<CompanySettings, rusqlite::Error> {
     conn.query_row(
         "SELECT id, tenant_id, name, legal_id, address, city, state, country, postal_code,
                 phone, email, website, logo_path, invoice_prefix, invoice_counter, default_currency,
-                legal_note, invoice_pattern, created_at, updated_at
+                legal_note, invoice_pattern, created_at, updated_at, ticket_template,
+                scale_labels
          FROM company_settings WHERE tenant_id = ?1",
         [tenant_id],
         |row| {
@@ -53,6 +57,14 @@ fn query_company_settings(
                 default_currency: row.get(15)?,
                 legal_note: row.get(16)?,
                 invoice_pattern: row.get(17)?,
+                ticket_template: row
+                    .get::<_, Option<String>>(20)?
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default(),
+                scale_labels: row
+                    .get::<_, Option<String>>(21)?
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_else(ScaleLabelRule::defaults),
                 created_at: row.get(18)?,
                 updated_at: row.get(19)?,
             })
@@ -112,34 +124,7 @@ pub async fn get_company_settings(state: State<'_, AppState>) -> Result<CompanyS
             .lock()
             .map_err(|_| "Error al acceder a la base de datos".to_string())?;
 
-        conn.query_row(
-            "SELECT id, tenant_id, name, legal_id, address, city, state, country, postal_code, phone, email, website, logo_path, invoice_prefix, invoice_counter, default_currency, legal_note, invoice_pattern, created_at, updated_at FROM company_settings WHERE tenant_id = ?1",
-            [&tenant_id],
-            |row| {
-                Ok(CompanySettings {
-                    id: row.get(0)?,
-                    tenant_id: row.get(1)?,
-                    name: row.get(2)?,
-                    legal_id: row.get(3)?,
-                    address: row.get(4)?,
-                    city: row.get(5)?,
-                    state: row.get(6)?,
-                    country: row.get(7)?,
-                    postal_code: row.get(8)?,
-                    phone: row.get(9)?,
-                    email: row.get(10)?,
-                    website: row.get(11)?,
-                    logo_path: row.get(12)?,
-                    invoice_prefix: row.get(13)?,
-                    invoice_counter: row.get(14)?,
-                    default_currency: row.get(15)?,
-                    legal_note: row.get(16)?,
-                    invoice_pattern: row.get(17)?,
-                    created_at: row.get(18)?,
-                    updated_at: row.get(19)?,
-                })
-            },
-        )
+        query_company_settings(&conn, &tenant_id)
     };
 
     match result {
@@ -178,6 +163,21 @@ pub async fn update_company_settings(
         .map_err(|_| "Error al acceder a la base de datos")?;
     let now = chrono::Utc::now().to_rfc3339();
 
+    // Prefix and pattern shape fiscal numbers; change them under the same
+    // guard and audit trail as the sequence itself
+    let sequence_changed = data.invoice_prefix.is_some() || data.invoice_pattern.is_some();
+    let previous_sequence: Option<(String, Option<String>)> = if sequence_changed {
+        time_guard::verify_time_integrity(&conn)?;
+        conn.query_row(
+            "SELECT invoice_prefix, invoice_pattern FROM company_settings WHERE tenant_id = ?1",
+            [&tenant_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .ok()
+    } else {
+        None
+    };
+
     let mut set_clauses = vec![format!("updated_at = '{}'", now)];
 
     if let Some(ref name) = data.name {
@@ -234,6 +234,23 @@ pub async fn update_company_settings(
             pattern.replace('\'', "''")
         ));
     }
+    if let Some(ref template) = data.ticket_template {
+        if template.paper_width != 58 && template.paper_width != 80 {
+            return Err("El ancho del papel debe ser 58 u 80 mm".to_string());
+        }
+        if let Some(ref printer) = template.printer {
+            escpos::PrinterTarget::parse(printer).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string(template).map_err(|e| e.to_string())?;
+        set_clauses.push(format!("ticket_template = '{}'", json.replace('\'', "''")));
+    }
+    if let Some(ref rules) = data.scale_labels {
+        for rule in rules {
+            barcodes::validate_scale_rule(rule).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string(rules).map_err(|e| e.to_string())?;
+        set_clauses.push(format!("scale_labels = '{}'", json.replace('\'', "''")));
+    }
 
     let query = format!(
         "UPDATE company_settings SET {} WHERE tenant_id = '{}'",
@@ -244,6 +261,33 @@ pub async fn update_company_settings(
     conn.execute(&query, [])
         .map_err(|e| format!("Error al actualizar configuración: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::SettingsUpdated,
+        Some("company_settings"),
+        None,
+        &format!("changes: {}", set_clauses[1..].join(", ")),
+    )
+    .ok();
+    if sequence_changed {
+        audit::log_event(
+            &conn,
+            Some(&tenant_id),
+            user_id.as_deref(),
+            audit::AuditEventType::InvoiceSequenceChanged,
+            Some("invoice_sequence"),
+            None,
+            &format!(
+                "previous={:?}, prefix={:?}, pattern={:?}",
+                previous_sequence, data.invoice_prefix, data.invoice_pattern
+            ),
+        )
+        .ok();
+    }
+
     // Query updated settings
     query_company_settings(&conn, &tenant_id)
         .map_err(|e| format!("Error al obtener configuración actualizada: {}", e))
@@ -348,6 +392,21 @@ pub async fn create_bank_account(
     )
     .map_err(|e| format!("Error al crear cuenta bancaria: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::BankAccountCreated,
+        Some("bank_account"),
+        Some(&id),
+        &format!(
+            "bank_name={}, account_number={}, currency={}",
+            data.bank_name, data.account_number, data.currency
+        ),
+    )
+    .ok();
+
     // Get the created account
     conn.query_row(
         "SELECT id, tenant_id, bank_name, account_number, account_type, currency, is_default,
@@ -423,6 +482,18 @@ pub async fn update_bank_account(
     conn.execute(&query, [])
         .map_err(|e| format!("Error al actualizar cuenta: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::BankAccountUpdated,
+        Some("bank_account"),
+        Some(&id),
+        &format!("changes: {}", set_clauses[1..].join(", ")),
+    )
+    .ok();
+
     conn.query_row(
         "SELECT id, tenant_id, bank_name, account_number, account_type, currency, is_default,
                 is_active, created_at, updated_at
@@ -462,6 +533,18 @@ pub async fn delete_bank_account(state: State<'_, AppState>, id: String) -> Resu
     )
     .map_err(|e| format!("Error al eliminar cuenta: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::BankAccountDeleted,
+        Some("bank_account"),
+        Some(&id),
+        "Soft delete",
+    )
+    .ok();
+
     Ok(())
 }
 
@@ -536,6 +619,18 @@ pub async fn create_tax_setting(
     )
     .map_err(|e| format!("Error al crear impuesto: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::TaxSettingCreated,
+        Some("tax_setting"),
+        Some(&id),
+        &format!("name={}, rate={}", data.name, data.rate),
+    )
+    .ok();
+
     conn.query_row(
         "SELECT id, tenant_id, name, rate, applies_to, is_active, created_at, updated_at
          FROM tax_settings WHERE id = ?1",
@@ -595,6 +690,18 @@ pub async fn update_tax_setting(
     conn.execute(&query, [])
         .map_err(|e| format!("Error al actualizar impuesto: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::TaxSettingUpdated,
+        Some("tax_setting"),
+        Some(&id),
+        &format!("changes: {}", set_clauses[1..].join(", ")),
+    )
+    .ok();
+
     conn.query_row(
         "SELECT id, tenant_id, name, rate, applies_to, is_active, created_at, updated_at
          FROM tax_settings WHERE id = ?1",
@@ -630,6 +737,18 @@ pub async fn delete_tax_setting(state: State<'_, AppState>, id: String) -> Resul
     )
     .map_err(|e| format!("Error al eliminar impuesto: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::TaxSettingDeleted,
+        Some("tax_setting"),
+        Some(&id),
+        "Deleted",
+    )
+    .ok();
+
     Ok(())
 }
 
@@ -663,15 +782,27 @@ pub async fn update_invoice_sequence(
         .map_err(|_| "Error al acceder a la base de datos")?;
     let now = chrono::Utc::now().to_rfc3339();
 
+    time_guard::verify_time_integrity(&conn)?;
+    license::require_module(&conn, "invoicing")?;
+
     // Update company settings with new sequence data
     // We update prefix, counter (next_number - 1), and pattern
     // The counter is the *last used* number, so if next is 100, counter should be 99
 
+    let previous: Option<(String, i64, Option<String>)> = conn
+        .query_row(
+            "SELECT invoice_prefix, invoice_counter, invoice_pattern FROM company_settings WHERE tenant_id = ?1",
+            [&tenant_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .ok();
+
     let counter = if data.next_number > 0 {
         data.next_number - 1
     } else {
         0
     };
+    time_guard::verify_sequence_floor(&conn, &tenant_id, counter)?;
 
     conn.execute(
         "UPDATE company_settings 
@@ -681,5 +812,20 @@ pub async fn update_invoice_sequence(
     )
     .map_err(|e| format!("Error al actualizar secuencia: {}", e))?;
 
+    let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        user_id.as_deref(),
+        audit::AuditEventType::InvoiceSequenceChanged,
+        Some("invoice_sequence"),
+        None,
+        &format!(
+            "previous={:?}, prefix={}, counter={}, pattern={}",
+            previous, data.prefix, counter, data.pattern
+        ),
+    )
+    .ok();
+
     Ok(data)
 }
@@ -4,10 +4,12 @@
 //! installation detection, and database migration.
 
 use crate::models::{DatabaseFile, DatabaseInfo, PreviousInstallation};
+use crate::security::{key_store, license};
+use crate::state::AppState;
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use tauri::{AppHandle, Manager};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DbConfig {
@@ -21,23 +23,50 @@ pub struct LicenseStatus {
     pub message: Option<String>,
 }
 
-/// Validate license key (Mock implementation for now)
+/// License accepted by the wizard, installed once the configured database opens
+pub fn pending_license_path(app_dir: &Path) -> PathBuf {
+    app_dir.join(".config").join("pending_license")
+}
+
+/// Validate a signed license and keep it until the configured database is
+/// opened; the wizard picks the database after this step
 #[tauri::command]
-pub async fn validate_license(key: String) -> Result<LicenseStatus, String> {
-    // TODO: Implement actual license validation logic (API call or offline check)
-    if key.len() < 8 {
-        return Ok(LicenseStatus {
+pub async fn validate_license(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    key: String,
+) -> Result<LicenseStatus, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    match license::check_license(&conn, &key) {
+        Ok(checked) => {
+            let app_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+            let pending = pending_license_path(&app_dir);
+            if let Some(parent) = pending.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Error al crear directorio .config: {}", e))?;
+            }
+            std::fs::write(&pending, key.trim())
+                .map_err(|e| format!("Error al guardar la licencia: {}", e))?;
+
+            Ok(LicenseStatus {
+                valid: true,
+                type_: checked
+                    .license
+                    .map(|l| l.plan)
+                    .unwrap_or_else(|| "starter".to_string()),
+                message: checked.message,
+            })
+        }
+        Err(e) => Ok(LicenseStatus {
             valid: false,
             type_: "invalid".to_string(),
-            message: Some("La licencia debe tener al menos 8 caracteres".to_string()),
-        });
+            message: Some(e),
+        }),
     }
-
-    Ok(LicenseStatus {
-        valid: true,
-        type_: "pro".to_string(),
-        message: None,
-    })
 }
 
 /// Detect previous installation of Equinox ERP
@@ -197,12 +226,17 @@ pub async fn check_existing_database(path: String) -> Result<DatabaseInfo, Strin
     })
 }
 
-/// Migrate/copy database to new location
+/// Migrate/copy database to new location.
+///
+/// Databases with a key ring (`<db>.keys`) are re-wrapped for this machine
+/// using the admin recovery code or passphrase.
 #[tauri::command]
 pub async fn migrate_database(
     app: AppHandle,
+    state: State<'_, AppState>,
     source_path: String,
     destination_path: Option<String>,
+    recovery_secret: Option<String>,
 ) -> Result<String, String> {
     let source = PathBuf::from(&source_path);
 
@@ -210,8 +244,10 @@ pub async fn migrate_database(
         return Err("La base de datos de origen no existe".to_string());
     }
 
+    let encrypted = key_store::keyring_path(&source).exists();
+
     // Validate source database
-    if !validate_database_file(&source) {
+    if !encrypted && !validate_database_file(&source) {
         return Err("La base de datos de origen no es válida".to_string());
     }
 
@@ -226,6 +262,18 @@ pub async fn migrate_database(
         app_dir.join("equinox.db")
     };
 
+    if encrypted {
+        let secret =
+            recovery_secret.ok_or_else(|| "Debe indicar el código de recuperación".to_string())?;
+        key_store::migrate_to_this_machine(
+            &source,
+            &dest,
+            &state.security.get_key_encryption_key(),
+            &secret,
+        )?;
+        return Ok(dest.to_string_lossy().to_string());
+    }
+
     // Create parent directory if needed
     if let Some(parent) = dest.parent() {
         std::fs::create_dir_all(parent).map_err(|e| format!("Error al crear directorio: {}", e))?;
@@ -1,6 +1,11 @@
 //! Synchronization Commands
 
-use crate::services::sync;
+use crate::commands::auth::require_admin;
+use crate::models::sync::{
+    ConflictPolicy, ConflictResolution, EntitySyncPolicy, OutboxItem, SyncBackendSettings,
+    SyncConflict, SyncDevice, UpdateSyncBackendDto,
+};
+use crate::services::{sync, sync_backend, sync_conflicts, sync_outbox};
 use crate::state::AppState;
 use tauri::State;
 
@@ -9,6 +14,7 @@ pub struct SyncCommandResult {
     success: bool,
     uploaded_count: i32,
     downloaded_count: i32,
+    pending_conflicts: i64,
     message: String,
 }
 
@@ -17,24 +23,21 @@ pub struct SyncCommandResult {
 pub async fn start_sync(state: State<'_, AppState>) -> Result<SyncCommandResult, String> {
     let tenant_id = state.require_tenant()?;
 
-    // 1. Upload pending data
-    let upload_result = sync::sync_to_cloud(&state.supabase, &state.db, &tenant_id).await;
-    let uploaded = match upload_result {
-        Ok(count) => count,
-        Err(e) => return Err(format!("Upload failed: {}", e)),
-    };
+    let (downloaded, uploaded) = state.sync_scheduler.sync_now(&tenant_id, "manual").await?;
 
-    // 2. Download updates (placeholder)
-    let download_result = sync::sync_from_cloud(&state.supabase, &state.db, &tenant_id).await;
-    let downloaded = match download_result {
-        Ok(count) => count,
-        Err(e) => return Err(format!("Download failed: {}", e)),
+    let pending_conflicts = {
+        let conn = state
+            .db
+            .lock()
+            .map_err(|_| "Error al acceder a la base de datos")?;
+        sync_conflicts::count_pending(&conn)?
     };
 
     Ok(SyncCommandResult {
         success: true,
         uploaded_count: uploaded,
         downloaded_count: downloaded,
+        pending_conflicts,
         message: "Sync completed successfully".to_string(),
     })
 }
@@ -43,7 +46,8 @@ pub async fn start_sync(state: State<'_, AppState>) -> Result<SyncCommandResult,
 #[tauri::command]
 pub async fn check_cloud_updates(state: State<'_, AppState>) -> Result<i64, String> {
     let tenant_id = state.require_tenant().unwrap_or_default();
-    crate::services::sync::check_updates(&state.supabase, &state.db, &tenant_id).await
+    let backend = state.sync_backend.ready(&state.db, &tenant_id).await?;
+    sync::check_updates(backend.as_ref(), &state.db, &tenant_id).await
 }
 
 /// Get last sync status
@@ -51,17 +55,224 @@ pub async fn check_cloud_updates(state: State<'_, AppState>) -> Result<i64, Stri
 pub async fn get_last_sync_status(
     state: State<'_, AppState>,
 ) -> Result<crate::models::sync::SyncStatus, String> {
-    let _tenant_id = state.require_tenant().unwrap_or_default();
-
-    // In a real app, query DB for pending items count
-    // For now, return a basic status
-    let status = crate::models::sync::SyncStatus {
-        last_sync: None, // TODO: query from DB
-        pending_uploads: 0,
-        pending_downloads: 0,
-        is_syncing: false,
-        last_error: None,
-    };
+    let tenant_id = state.require_tenant().unwrap_or_default();
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    sync::get_status(&conn, &tenant_id, state.sync_scheduler.is_syncing())
+}
+
+/// List uploads that failed at least once (poisoned first)
+#[tauri::command]
+pub async fn list_failed_uploads(state: State<'_, AppState>) -> Result<Vec<OutboxItem>, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    sync_outbox::list_failed(&conn, &tenant_id)
+}
+
+/// Queue poisoned uploads again; all of them when `entry_id` is omitted
+#[tauri::command]
+pub async fn retry_failed_uploads(
+    state: State<'_, AppState>,
+    entry_id: Option<i64>,
+) -> Result<usize, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    sync_outbox::retry_failed(&conn, &tenant_id, entry_id)
+}
+
+/// Discard a poisoned upload (admin only)
+#[tauri::command]
+pub async fn discard_failed_upload(
+    state: State<'_, AppState>,
+    entry_id: i64,
+) -> Result<(), String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    let user_id = state.require_user()?;
+    require_admin(&conn, &user_id)?;
+
+    sync_outbox::discard_failed(&conn, &tenant_id, entry_id)
+}
+
+/// List sync conflicts (pending first for review)
+#[tauri::command]
+pub async fn list_sync_conflicts(
+    state: State<'_, AppState>,
+    status: Option<String>,
+    entity: Option<String>,
+) -> Result<Vec<SyncConflict>, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    state.require_user()?;
+
+    sync_conflicts::list_conflicts(&conn, status.as_deref(), entity.as_deref())
+}
+
+/// Resolve a pending sync conflict (admin only)
+#[tauri::command]
+pub async fn resolve_sync_conflict(
+    state: State<'_, AppState>,
+    conflict_id: String,
+    resolution: ConflictResolution,
+    merged_data: Option<serde_json::Value>,
+) -> Result<SyncConflict, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    let user_id = state.require_user()?;
+    require_admin(&conn, &user_id)?;
+
+    sync_conflicts::resolve_conflict(&conn, &conflict_id, resolution, merged_data, &user_id)
+}
+
+/// Get conflict policies of synced entities
+#[tauri::command]
+pub async fn get_sync_policies(
+    state: State<'_, AppState>,
+) -> Result<Vec<EntitySyncPolicy>, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    sync_conflicts::list_policies(&conn)
+}
+
+/// Override the conflict policy of an entity; omit `policy` to restore the default (admin only)
+#[tauri::command]
+pub async fn set_sync_policy(
+    state: State<'_, AppState>,
+    entity: String,
+    policy: Option<ConflictPolicy>,
+) -> Result<EntitySyncPolicy, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    let user_id = state.require_user()?;
+    require_admin(&conn, &user_id)?;
+
+    sync_conflicts::set_policy(&conn, &entity, policy, &user_id)
+}
+
+/// Get the configured sync backend (credentials are never returned)
+#[tauri::command]
+pub async fn get_sync_backend_settings(
+    state: State<'_, AppState>,
+) -> Result<SyncBackendSettings, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    sync_backend::get_settings(&conn)
+}
+
+/// Change the sync backend, URL or credential (admin only)
+#[tauri::command]
+pub async fn update_sync_backend_settings(
+    state: State<'_, AppState>,
+    data: UpdateSyncBackendDto,
+) -> Result<SyncBackendSettings, String> {
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    let user_id = state.require_user()?;
+    require_admin(&conn, &user_id)?;
+
+    let kek = state.security.get_key_encryption_key();
+    let (settings, backend) = sync_backend::update_settings(&conn, &kek, data, &user_id)?;
+    state.sync_backend.replace(backend);
+
+    Ok(settings)
+}
+
+/// Check that the configured sync backend is reachable
+#[tauri::command]
+pub async fn test_sync_backend(state: State<'_, AppState>) -> Result<(), String> {
+    let backend = state.sync_backend.get()?;
+    backend.ping().await
+}
+
+/// List the devices registered for this tenant at the sync backend (admin only)
+#[tauri::command]
+pub async fn list_sync_devices(state: State<'_, AppState>) -> Result<Vec<SyncDevice>, String> {
+    let tenant_id = state.require_tenant()?;
+    {
+        let conn = state
+            .db
+            .lock()
+            .map_err(|_| "Error al acceder a la base de datos")?;
+        let user_id = state.require_user()?;
+        require_admin(&conn, &user_id)?;
+    }
+
+    let backend = state.sync_backend.ready(&state.db, &tenant_id).await?;
+    backend.list_devices().await
+}
+
+/// Revoke a lost or retired device so its tokens stop working (admin only)
+#[tauri::command]
+pub async fn revoke_sync_device(
+    state: State<'_, AppState>,
+    device_id: String,
+) -> Result<(), String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    {
+        let conn = state
+            .db
+            .lock()
+            .map_err(|_| "Error al acceder a la base de datos")?;
+        require_admin(&conn, &user_id)?;
+    }
+
+    let backend = state.sync_backend.ready(&state.db, &tenant_id).await?;
+    sync_backend::revoke_device(
+        backend.as_ref(),
+        &state.db,
+        &tenant_id,
+        &user_id,
+        &device_id,
+    )
+    .await
+}
+
+/// One-time code for registering another installation of this
+/// organization, optionally restricted to one branch (admin only)
+#[tauri::command]
+pub async fn create_sync_enrollment_token(
+    state: State<'_, AppState>,
+    tenant_id: Option<String>,
+) -> Result<String, String> {
+    let current_tenant = state.require_tenant()?;
+    {
+        let conn = state
+            .db
+            .lock()
+            .map_err(|_| "Error al acceder a la base de datos")?;
+        let user_id = state.require_user()?;
+        require_admin(&conn, &user_id)?;
+    }
 
-    Ok(status)
+    let backend = state.sync_backend.ready(&state.db, &current_tenant).await?;
+    backend.create_enrollment_token(tenant_id.as_deref()).await
 }
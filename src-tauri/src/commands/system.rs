@@ -3,6 +3,8 @@
 use serde::Serialize;
 use tauri::State;
 
+use crate::models::license::LicenseState;
+use crate::security::license;
 use crate::state::AppState;
 
 #[derive(Debug, Serialize)]
@@ -11,12 +13,6 @@ pub struct AppInfo {
     pub tenant_id: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct LicenseStatus {
-    pub status: String,
-    pub expires_at: Option<String>,
-}
-
 /// Get application information
 #[tauri::command]
 pub async fn get_app_info(state: State<'_, AppState>) -> Result<AppInfo, String> {
@@ -28,14 +24,11 @@ pub async fn get_app_info(state: State<'_, AppState>) -> Result<AppInfo, String>
     })
 }
 
-/// Check license status
+/// Check license status (verified offline against the signed license)
 #[tauri::command]
-pub async fn check_license(_state: State<'_, AppState>) -> Result<LicenseStatus, String> {
-    // TODO: Implement actual license checking
-    Ok(LicenseStatus {
-        status: "valid".to_string(),
-        expires_at: None,
-    })
+pub async fn check_license(state: State<'_, AppState>) -> Result<LicenseState, String> {
+    let conn = state.db.lock().map_err(|e| e.to_string())?;
+    license::current_state(&conn)
 }
 
 /// Seed default inventory data (units and product types) for current tenant
@@ -1,6 +1,7 @@
 //! Product Variant Commands
 
 use crate::models::{CreateVariantDto, ProductVariant, UpdateVariantDto};
+use crate::services::{inventory, reservations};
 use crate::state::AppState;
 use tauri::State;
 use uuid::Uuid;
@@ -59,11 +60,12 @@ pub async fn list_variants(
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
+    reservations::release_expired(&conn, &tenant_id).map_err(|e| e.to_string())?;
 
     let mut stmt = conn.prepare(
         "SELECT pv.id, pv.tenant_id, pv.product_id, pv.sku, pv.name, pv.attributes,
                 pv.cost_price, pv.sale_price, pv.barcode, COALESCE(vs.quantity, 0) as stock_quantity,
-                pv.is_active, pv.created_at, pv.updated_at
+                pv.is_active, pv.created_at, pv.updated_at, COALESCE(vs.reserved_quantity, 0)
          FROM product_variants pv
          LEFT JOIN variant_stock vs ON pv.id = vs.variant_id
          WHERE pv.product_id = ?1 AND pv.tenant_id = ?2 AND pv.is_active = 1
@@ -83,6 +85,8 @@ pub async fn list_variants(
                 sale_price: row.get(7)?,
                 barcode: row.get(8)?,
                 stock_quantity: row.get(9)?,
+                reserved_quantity: row.get(13)?,
+                available_quantity: row.get::<_, f64>(9)? - row.get::<_, f64>(13)?,
                 is_active: row.get::<_, i32>(10)? == 1,
                 created_at: row.get(11)?,
                 updated_at: row.get(12)?,
@@ -103,11 +107,12 @@ pub async fn get_variant(state: State<'_, AppState>, id: String) -> Result<Produ
         .db
         .lock()
         .map_err(|_| "Error al acceder a la base de datos")?;
+    reservations::release_expired(&conn, &tenant_id).map_err(|e| e.to_string())?;
 
     conn.query_row(
         "SELECT pv.id, pv.tenant_id, pv.product_id, pv.sku, pv.name, pv.attributes,
                 pv.cost_price, pv.sale_price, pv.barcode, COALESCE(vs.quantity, 0),
-                pv.is_active, pv.created_at, pv.updated_at
+                pv.is_active, pv.created_at, pv.updated_at, COALESCE(vs.reserved_quantity, 0)
          FROM product_variants pv
          LEFT JOIN variant_stock vs ON pv.id = vs.variant_id
          WHERE pv.id = ?1 AND pv.tenant_id = ?2",
@@ -124,6 +129,8 @@ pub async fn get_variant(state: State<'_, AppState>, id: String) -> Result<Produ
                 sale_price: row.get(7)?,
                 barcode: row.get(8)?,
                 stock_quantity: row.get(9)?,
+                reserved_quantity: row.get(13)?,
+                available_quantity: row.get::<_, f64>(9)? - row.get::<_, f64>(13)?,
                 is_active: row.get::<_, i32>(10)? == 1,
                 created_at: row.get(11)?,
                 updated_at: row.get(12)?,
@@ -304,15 +311,16 @@ pub async fn delete_variant(state: State<'_, AppState>, id: String) -> Result<()
     Ok(())
 }
 
-/// Adjust variant stock
+/// Adjust variant stock in a warehouse (the branch default if none is given)
 #[tauri::command]
 pub async fn adjust_variant_stock(
     state: State<'_, AppState>,
     variant_id: String,
     quantity: f64,
-    _reason: Option<String>,
+    reason: Option<String>,
+    warehouse_id: Option<String>,
 ) -> Result<ProductVariant, String> {
-    let _tenant_id = get_tenant_id(&state)?;
+    let tenant_id = get_tenant_id(&state)?;
 
     {
         let conn = state
@@ -320,10 +328,34 @@ pub async fn adjust_variant_stock(
             .lock()
             .map_err(|_| "Error al acceder a la base de datos")?;
         let now = chrono::Utc::now().to_rfc3339();
-
-        conn.execute(
-            "UPDATE variant_stock SET quantity = quantity + ?1, last_updated = ?2 WHERE variant_id = ?3",
-            rusqlite::params![quantity, &now, &variant_id],
+        let user_id = state.user_id.lock().ok().and_then(|u| u.clone());
+
+        let product_id: String = conn
+            .query_row(
+                "SELECT product_id FROM product_variants WHERE id = ?1 AND tenant_id = ?2",
+                [&variant_id, &tenant_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Variante no encontrada: {}", e))?;
+        let warehouse_id = inventory::resolve_warehouse(&conn, &tenant_id, warehouse_id.as_deref())
+            .map_err(|e| e.to_string())?;
+
+        inventory::record(
+            &conn,
+            &inventory::StockChange {
+                tenant_id: &tenant_id,
+                warehouse_id: &warehouse_id,
+                product_id: &product_id,
+                variant_id: Some(&variant_id),
+                lot_id: None,
+                quantity,
+            },
+            &inventory::MovementInfo {
+                notes: reason.as_deref(),
+                user_id: user_id.as_deref(),
+                ..Default::default()
+            },
+            &now,
         )
         .map_err(|e| format!("Error al ajustar stock: {}", e))?;
     }
@@ -0,0 +1,170 @@
+//! Warehouse Commands
+//!
+//! Warehouses of the active branch, stock per warehouse and bin, moves
+//! between warehouses and low stock per warehouse.
+
+use crate::commands::auth::require_admin;
+use crate::models::warehouse::{
+    CreateWarehouseDto, CreateWarehouseTransferDto, LowStockItem, SetStockLocationDto,
+    UpdateWarehouseDto, Warehouse, WarehouseStock, WarehouseStockFilters, WarehouseTransfer,
+};
+use crate::security::audit::{self, AuditEventType};
+use crate::services::inventory;
+use crate::state::AppState;
+use tauri::State;
+
+/// Warehouses of the active branch
+#[tauri::command]
+pub async fn list_warehouses(state: State<'_, AppState>) -> Result<Vec<Warehouse>, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    inventory::list_warehouses(&conn, &tenant_id).map_err(|e| e.to_string())
+}
+
+/// Create a warehouse in the active branch (admin)
+#[tauri::command]
+pub async fn create_warehouse(
+    state: State<'_, AppState>,
+    data: CreateWarehouseDto,
+) -> Result<Warehouse, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    require_admin(&conn, &user_id)?;
+
+    let warehouse =
+        inventory::create_warehouse(&conn, &tenant_id, data).map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        AuditEventType::WarehouseCreated,
+        Some("warehouse"),
+        Some(&warehouse.id),
+        &format!("code={}, name={}", warehouse.code, warehouse.name),
+    )
+    .ok();
+    Ok(warehouse)
+}
+
+/// Rename, (de)activate or make a warehouse the default (admin)
+#[tauri::command]
+pub async fn update_warehouse(
+    state: State<'_, AppState>,
+    id: String,
+    data: UpdateWarehouseDto,
+) -> Result<Warehouse, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+    require_admin(&conn, &user_id)?;
+
+    let warehouse =
+        inventory::update_warehouse(&conn, &tenant_id, &id, data).map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        AuditEventType::WarehouseUpdated,
+        Some("warehouse"),
+        Some(&id),
+        &format!(
+            "is_default={}, is_active={}",
+            warehouse.is_default, warehouse.is_active
+        ),
+    )
+    .ok();
+    Ok(warehouse)
+}
+
+/// Stock per warehouse, product, variant and lot
+#[tauri::command]
+pub async fn list_warehouse_stock(
+    state: State<'_, AppState>,
+    filters: Option<WarehouseStockFilters>,
+) -> Result<Vec<WarehouseStock>, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    inventory::list_stock(&conn, &tenant_id, &filters.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Set where a product is stored in a warehouse and its minimum there
+#[tauri::command]
+pub async fn set_stock_location(
+    state: State<'_, AppState>,
+    data: SetStockLocationDto,
+) -> Result<(), String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    inventory::set_stock_location(&conn, &tenant_id, data).map_err(|e| e.to_string())
+}
+
+/// Move stock between two warehouses of the active branch
+#[tauri::command]
+pub async fn create_warehouse_transfer(
+    state: State<'_, AppState>,
+    data: CreateWarehouseTransferDto,
+) -> Result<WarehouseTransfer, String> {
+    let tenant_id = state.require_tenant()?;
+    let user_id = state.require_user()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    let transfer = inventory::transfer_between_warehouses(&conn, &tenant_id, &user_id, data)
+        .map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &conn,
+        Some(&tenant_id),
+        Some(&user_id),
+        AuditEventType::WarehouseTransferCreated,
+        Some("warehouse_transfer"),
+        Some(&transfer.id),
+        &format!(
+            "from={}, to={}, items={}",
+            transfer.from_warehouse_id,
+            transfer.to_warehouse_id,
+            transfer.items.len()
+        ),
+    )
+    .ok();
+    Ok(transfer)
+}
+
+/// Products at or below their minimum in each warehouse
+#[tauri::command]
+pub async fn get_low_stock_by_warehouse(
+    state: State<'_, AppState>,
+    warehouse_id: Option<String>,
+) -> Result<Vec<LowStockItem>, String> {
+    let tenant_id = state.require_tenant()?;
+    let conn = state
+        .db
+        .lock()
+        .map_err(|_| "Error al acceder a la base de datos")?;
+
+    inventory::low_stock(&conn, &tenant_id, warehouse_id.as_deref()).map_err(|e| e.to_string())
+}
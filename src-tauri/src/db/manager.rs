@@ -8,8 +8,10 @@ use tauri::AppHandle;
 use tauri::Manager;
 
 use super::migrations;
-use crate::commands::setup::DbConfig;
-use crate::security::SecurityManager;
+use crate::commands::setup::{self, DbConfig};
+use crate::security::audit::{self, AuditEventType};
+use crate::security::{key_store, license, time_guard, SecurityManager};
+use crate::services::sync_outbox;
 
 pub struct DatabaseManager {
     pub connection: Connection,
@@ -51,12 +53,12 @@ impl DatabaseManager {
         println!("📂 App Data Dir: {:?}", app_dir);
         println!("📂 Database path: {:?}", db_path);
 
-        // Open database
-        let conn = Connection::open(&db_path)?;
-
-        // Initialize encryption with hardware-derived key
-        let key = security.get_db_key();
-        conn.pragma_update(None, "key", key.as_str())?;
+        // Open database with the data key unwrapped by this machine's key
+        let conn = key_store::open_database(
+            &db_path,
+            &security.get_key_encryption_key(),
+            &security.legacy_db_keys(),
+        )?;
 
         // Enable WAL mode for better concurrency
         conn.pragma_update(None, "journal_mode", "WAL")?;
@@ -70,6 +72,35 @@ impl DatabaseManager {
         // Apply compliance triggers
         migrations::apply_compliance_triggers(&conn)?;
 
+        // Queue local changes for sync upload
+        sync_outbox::apply_sync_triggers(&conn)?;
+
+        // Check the clock at startup; a rollback only blocks fiscal operations,
+        // the app still opens so an admin can review and recover
+        if let Err(e) = time_guard::verify_time_integrity(&conn) {
+            eprintln!("{}", e);
+        }
+
+        // Install the license accepted by the setup wizard into this database
+        let pending_license = setup::pending_license_path(&app_dir);
+        if let Ok(token) = std::fs::read_to_string(&pending_license) {
+            if let Err(e) = license::install_license(&conn, &token, None) {
+                eprintln!("Licencia pendiente no instalada: {}", e);
+            }
+            std::fs::remove_file(&pending_license).ok();
+        }
+
+        audit::log_event(
+            &conn,
+            None,
+            None,
+            AuditEventType::SystemStartup,
+            Some("system"),
+            None,
+            &format!("version={}", env!("CARGO_PKG_VERSION")),
+        )
+        .ok();
+
         #[cfg(debug_assertions)]
         println!("✅ Database initialized successfully");
 
@@ -391,6 +391,124 @@ pub fn run_migrations(conn: &Connection) -> Result<(), rusqlite::Error> {
         conn.execute("INSERT INTO schema_migrations (version) VALUES (9)", [])?;
     }
 
+    // Migration 10: Audit log query indexes
+    if current_version < 10 {
+        conn.execute_batch(include_str!("migrations/008_audit_log_indexes.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (10)", [])?;
+    }
+
+    // Migration 11: Generic sync engine (watermarks, change timestamps)
+    if current_version < 11 {
+        conn.execute_batch(include_str!("migrations/009_sync_engine.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (11)", [])?;
+    }
+
+    // Migration 12: Sync conflict detection
+    if current_version < 12 {
+        conn.execute_batch(include_str!("migrations/010_sync_conflicts.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (12)", [])?;
+    }
+
+    // Migration 13: Sync outbox
+    if current_version < 13 {
+        conn.execute_batch(include_str!("migrations/011_sync_outbox.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (13)", [])?;
+    }
+
+    // Migration 14: Multi-branch (branch access, inter-branch transfers)
+    if current_version < 14 {
+        conn.execute_batch(include_str!("migrations/012_branches.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (14)", [])?;
+    }
+
+    // Migration 15: Warehouses and bin locations
+    if current_version < 15 {
+        conn.execute_batch(include_str!("migrations/013_warehouses.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (15)", [])?;
+    }
+
+    // Migration 16: Stock reservations
+    if current_version < 16 {
+        conn.execute_batch(include_str!("migrations/014_stock_reservations.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (16)", [])?;
+    }
+
+    // Migration 17: Physical inventory counts
+    if current_version < 17 {
+        conn.execute_batch(include_str!("migrations/015_inventory_counts.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (17)", [])?;
+    }
+
+    // Migration 18: Pricing rules (discount stacking, applied rules per line)
+    if current_version < 18 {
+        conn.execute_batch(include_str!("migrations/016_pricing_rules.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (18)", [])?;
+    }
+
+    // Migration 19: Discount redemptions
+    if current_version < 19 {
+        conn.execute_batch(include_str!("migrations/017_discount_redemptions.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (19)", [])?;
+    }
+
+    // Migration 20: Coupon codes, bundles, time windows and campaigns
+    if current_version < 20 {
+        conn.execute_batch(include_str!("migrations/018_coupons_campaigns.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (20)", [])?;
+    }
+
+    // Migration 21: Bulk repricing batches
+    if current_version < 21 {
+        conn.execute_batch(include_str!("migrations/019_price_batches.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (21)", [])?;
+    }
+
+    // Migration 22: Exchange rates and derived price lists
+    if current_version < 22 {
+        conn.execute_batch(include_str!("migrations/020_exchange_rates.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (22)", [])?;
+    }
+
+    if current_version < 23 {
+        conn.execute_batch(include_str!("migrations/021_pos_sales.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (23)", [])?;
+    }
+
+    if current_version < 24 {
+        conn.execute_batch(include_str!("migrations/022_cash_session_reports.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (24)", [])?;
+    }
+
+    if current_version < 25 {
+        conn.execute_batch(include_str!("migrations/023_ticket_template.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (25)", [])?;
+    }
+
+    if current_version < 26 {
+        conn.execute_batch(include_str!("migrations/024_scale_labels.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (26)", [])?;
+    }
+
+    if current_version < 27 {
+        conn.execute_batch(include_str!("migrations/025_invoice_item_costs.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (27)", [])?;
+    }
+
+    if current_version < 28 {
+        conn.execute_batch(include_str!("migrations/026_license_trial.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (28)", [])?;
+    }
+
+    if current_version < 29 {
+        conn.execute_batch(include_str!("migrations/027_transfer_source_warehouse.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (29)", [])?;
+    }
+
+    if current_version < 30 {
+        conn.execute_batch(include_str!("migrations/028_invoice_chain.sql"))?;
+        conn.execute("INSERT INTO schema_migrations (version) VALUES (30)", [])?;
+    }
+
     Ok(())
 }
 
@@ -427,6 +545,14 @@ pub fn apply_compliance_triggers(conn: &Connection) -> Result<(), rusqlite::Erro
         BEGIN
             SELECT RAISE(ABORT, '⛔ INTEGRITY VIOLATION: Invoice hash cannot be modified after issuance');
         END;
+
+        DROP TRIGGER IF EXISTS trg_billing_invoices_no_modify_hash;
+        CREATE TRIGGER trg_billing_invoices_no_modify_hash
+        BEFORE UPDATE ON billing_invoices
+        WHEN OLD.hash IS NOT NULL AND (NEW.hash IS NOT OLD.hash OR NEW.prev_hash IS NOT OLD.prev_hash)
+        BEGIN
+            SELECT RAISE(ABORT, '⛔ INTEGRITY VIOLATION: Invoice hash cannot be modified after issuance');
+        END;
     "#)?;
 
     Ok(())
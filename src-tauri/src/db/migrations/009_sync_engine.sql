@@ -0,0 +1,84 @@
+-- Migration 11: Generic Sync Engine
+-- Created: 2026-10-18
+
+-- Per-entity sync watermarks (replaces security_metadata 'last_down_sync')
+CREATE TABLE IF NOT EXISTS sync_watermarks (
+    entity TEXT PRIMARY KEY NOT NULL,
+    last_pulled_at TEXT, -- Max remote change timestamp applied locally
+    last_pushed_at TEXT, -- Max local change timestamp uploaded
+    updated_at TEXT NOT NULL
+);
+
+-- Carry over the clients-only download watermark
+INSERT OR IGNORE INTO sync_watermarks (entity, last_pulled_at, updated_at)
+SELECT 'clients', value, CURRENT_TIMESTAMP FROM security_metadata WHERE key = 'last_down_sync';
+
+DELETE FROM security_metadata WHERE key = 'last_down_sync';
+
+-- Change timestamps for synced tables that had none
+ALTER TABLE units ADD COLUMN updated_at TEXT;
+ALTER TABLE product_types ADD COLUMN updated_at TEXT;
+ALTER TABLE inventory_lots ADD COLUMN updated_at TEXT;
+ALTER TABLE billing_invoice_items ADD COLUMN updated_at TEXT;
+
+UPDATE units SET updated_at = created_at WHERE updated_at IS NULL;
+UPDATE product_types SET updated_at = created_at WHERE updated_at IS NULL;
+UPDATE inventory_lots SET updated_at = created_at WHERE updated_at IS NULL;
+UPDATE billing_invoice_items SET updated_at = (
+    SELECT updated_at FROM billing_invoices WHERE billing_invoices.id = billing_invoice_items.invoice_id
+) WHERE updated_at IS NULL;
+
+-- Keep those timestamps current without touching every command
+CREATE TRIGGER IF NOT EXISTS trg_units_touch_insert AFTER INSERT ON units
+WHEN NEW.updated_at IS NULL
+BEGIN
+    UPDATE units SET updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_units_touch_update AFTER UPDATE ON units
+WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE units SET updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_product_types_touch_insert AFTER INSERT ON product_types
+WHEN NEW.updated_at IS NULL
+BEGIN
+    UPDATE product_types SET updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_product_types_touch_update AFTER UPDATE ON product_types
+WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE product_types SET updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_inventory_lots_touch_insert AFTER INSERT ON inventory_lots
+WHEN NEW.updated_at IS NULL
+BEGIN
+    UPDATE inventory_lots SET updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_inventory_lots_touch_update AFTER UPDATE ON inventory_lots
+WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE inventory_lots SET updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_billing_invoice_items_touch_insert AFTER INSERT ON billing_invoice_items
+WHEN NEW.updated_at IS NULL
+BEGIN
+    UPDATE billing_invoice_items SET updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_billing_invoice_items_touch_update AFTER UPDATE ON billing_invoice_items
+WHEN NEW.updated_at IS OLD.updated_at
+BEGIN
+    UPDATE billing_invoice_items SET updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now') WHERE id = NEW.id;
+END;
+
+-- Change-column indexes used by the upload scan
+CREATE INDEX IF NOT EXISTS idx_products_updated ON products(updated_at);
+CREATE INDEX IF NOT EXISTS idx_clients_updated ON clients(updated_at);
+CREATE INDEX IF NOT EXISTS idx_billing_invoices_updated ON billing_invoices(updated_at);
+CREATE INDEX IF NOT EXISTS idx_inventory_movements_created ON inventory_movements(created_at);
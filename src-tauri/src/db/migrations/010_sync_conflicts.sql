@@ -0,0 +1,39 @@
+-- Migration 12: Sync Conflict Detection
+-- Created: 2026-10-18
+
+-- Conflicts found while applying remote rows. Rows settled automatically by
+-- policy are kept for review with a resolved_* status.
+CREATE TABLE IF NOT EXISTS sync_conflicts (
+    id TEXT PRIMARY KEY,
+    tenant_id TEXT,
+    entity TEXT NOT NULL,
+    row_id TEXT NOT NULL,
+    policy TEXT NOT NULL,
+    local_data TEXT NOT NULL,               -- JSON row
+    remote_data TEXT NOT NULL,              -- JSON row
+    conflicting_fields TEXT NOT NULL DEFAULT '[]', -- JSON array
+    status TEXT NOT NULL DEFAULT 'pending', -- pending, resolved_local, resolved_remote, resolved_merged
+    detected_at TEXT NOT NULL,
+    resolved_at TEXT,
+    resolved_by TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_conflicts_status ON sync_conflicts(status, entity);
+CREATE INDEX IF NOT EXISTS idx_sync_conflicts_row ON sync_conflicts(entity, row_id);
+
+-- Last state of each row known to match the cloud (common ancestor for merges)
+CREATE TABLE IF NOT EXISTS sync_base_rows (
+    entity TEXT NOT NULL,
+    row_id TEXT NOT NULL,
+    data TEXT NOT NULL,
+    synced_at TEXT NOT NULL,
+    PRIMARY KEY (entity, row_id)
+);
+
+-- Per-entity overrides of the default conflict policy
+CREATE TABLE IF NOT EXISTS sync_entity_policies (
+    entity TEXT PRIMARY KEY,
+    policy TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    updated_by TEXT
+);
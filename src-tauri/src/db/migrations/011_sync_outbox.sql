@@ -0,0 +1,39 @@
+-- Migration 13: Sync Outbox
+-- Created: 2026-10-18
+
+-- Local changes waiting to be uploaded. Filled by triggers generated from the
+-- sync entity descriptors (see services/sync_outbox.rs).
+CREATE TABLE IF NOT EXISTS sync_outbox (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entity TEXT NOT NULL,
+    row_id TEXT NOT NULL,
+    tenant_id TEXT,
+    operation TEXT NOT NULL,               -- upsert, delete
+    payload TEXT,                          -- JSON row snapshot (tombstones only)
+    attempts INTEGER NOT NULL DEFAULT 0,
+    status TEXT NOT NULL DEFAULT 'pending', -- pending, poisoned
+    last_error TEXT,
+    next_attempt_at TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_outbox_due ON sync_outbox(status, tenant_id, next_attempt_at);
+CREATE INDEX IF NOT EXISTS idx_sync_outbox_row ON sync_outbox(entity, row_id);
+
+-- Set while remote rows are applied so they are not queued for upload again
+CREATE TABLE IF NOT EXISTS sync_outbox_guard (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    suppressed INTEGER NOT NULL DEFAULT 0
+);
+INSERT OR IGNORE INTO sync_outbox_guard (id, suppressed) VALUES (1, 0);
+
+-- Outcome of the last sync runs
+CREATE TABLE IF NOT EXISTS sync_status (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    last_success_at TEXT,
+    last_attempt_at TEXT,
+    last_error TEXT,
+    last_error_at TEXT,
+    pending_downloads INTEGER NOT NULL DEFAULT 0
+);
+INSERT OR IGNORE INTO sync_status (id) VALUES (1);
@@ -0,0 +1,52 @@
+-- Migration 14: Multi-Branch Operation
+-- Created: 2026-10-19
+
+-- Extra branches a user may switch to (users.tenant_id is the home branch;
+-- admins may operate every branch of their organization)
+CREATE TABLE IF NOT EXISTS user_tenants (
+    user_id TEXT NOT NULL,
+    tenant_id TEXT NOT NULL,
+    granted_by TEXT,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (user_id, tenant_id),
+    FOREIGN KEY (user_id) REFERENCES users(id),
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id)
+);
+
+-- Stock sent from one branch to another. Stock leaves the source when the
+-- transfer is shipped and enters the destination when it is received.
+CREATE TABLE IF NOT EXISTS stock_transfers (
+    id TEXT PRIMARY KEY,
+    org_id TEXT NOT NULL,
+    from_tenant_id TEXT NOT NULL,
+    to_tenant_id TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'in_transit', -- in_transit, received, cancelled
+    notes TEXT,
+    shipped_by TEXT NOT NULL,
+    shipped_at TEXT NOT NULL,
+    received_by TEXT,
+    received_at TEXT,
+    cancelled_by TEXT,
+    cancelled_at TEXT,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (org_id) REFERENCES organizations(id),
+    FOREIGN KEY (from_tenant_id) REFERENCES tenants(id),
+    FOREIGN KEY (to_tenant_id) REFERENCES tenants(id)
+);
+
+CREATE TABLE IF NOT EXISTS stock_transfer_items (
+    id TEXT PRIMARY KEY,
+    transfer_id TEXT NOT NULL,
+    product_id TEXT NOT NULL,           -- Source branch product
+    destination_product_id TEXT,        -- Set on receipt
+    quantity REAL NOT NULL,
+    FOREIGN KEY (transfer_id) REFERENCES stock_transfers(id),
+    FOREIGN KEY (product_id) REFERENCES products(id),
+    FOREIGN KEY (destination_product_id) REFERENCES products(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_stock_transfers_from ON stock_transfers(from_tenant_id, status);
+CREATE INDEX IF NOT EXISTS idx_stock_transfers_to ON stock_transfers(to_tenant_id, status);
+CREATE INDEX IF NOT EXISTS idx_stock_transfer_items_transfer ON stock_transfer_items(transfer_id);
+CREATE INDEX IF NOT EXISTS idx_inventory_movements_reference ON inventory_movements(reference_type, reference_id);
+CREATE INDEX IF NOT EXISTS idx_products_sku ON products(tenant_id, sku);
@@ -0,0 +1,107 @@
+-- Migration 15: Warehouses and Bin Locations
+-- Created: 2026-10-19
+
+CREATE TABLE IF NOT EXISTS warehouses (
+    id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    code TEXT NOT NULL,
+    name TEXT NOT NULL,
+    is_default INTEGER NOT NULL DEFAULT 0,
+    is_active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE(tenant_id, code),
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id)
+);
+
+-- Stock per warehouse/product/variant/lot. products.stock_quantity,
+-- variant_stock.quantity and inventory_lots.quantity remain the branch totals.
+CREATE TABLE IF NOT EXISTS warehouse_stock (
+    id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    warehouse_id TEXT NOT NULL,
+    product_id TEXT NOT NULL,
+    variant_id TEXT,
+    lot_id TEXT,
+    quantity REAL NOT NULL DEFAULT 0,
+    min_stock REAL,          -- Overrides products.min_stock in this warehouse
+    bin_location TEXT,       -- Aisle/shelf/bin code
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id),
+    FOREIGN KEY (warehouse_id) REFERENCES warehouses(id),
+    FOREIGN KEY (product_id) REFERENCES products(id),
+    FOREIGN KEY (variant_id) REFERENCES product_variants(id),
+    FOREIGN KEY (lot_id) REFERENCES inventory_lots(id)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_warehouse_stock_key
+    ON warehouse_stock(warehouse_id, product_id, IFNULL(variant_id, ''), IFNULL(lot_id, ''));
+CREATE INDEX IF NOT EXISTS idx_warehouse_stock_product ON warehouse_stock(product_id);
+CREATE INDEX IF NOT EXISTS idx_warehouse_stock_updated ON warehouse_stock(updated_at);
+
+-- Moves between warehouses of the same branch (applied immediately)
+CREATE TABLE IF NOT EXISTS warehouse_transfers (
+    id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    from_warehouse_id TEXT NOT NULL,
+    to_warehouse_id TEXT NOT NULL,
+    notes TEXT,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id),
+    FOREIGN KEY (from_warehouse_id) REFERENCES warehouses(id),
+    FOREIGN KEY (to_warehouse_id) REFERENCES warehouses(id)
+);
+
+CREATE TABLE IF NOT EXISTS warehouse_transfer_items (
+    id TEXT PRIMARY KEY,
+    transfer_id TEXT NOT NULL,
+    product_id TEXT NOT NULL,
+    variant_id TEXT,
+    lot_id TEXT,
+    quantity REAL NOT NULL,
+    FOREIGN KEY (transfer_id) REFERENCES warehouse_transfers(id),
+    FOREIGN KEY (product_id) REFERENCES products(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_warehouse_transfers_tenant ON warehouse_transfers(tenant_id, created_at);
+
+-- Where stock comes from
+ALTER TABLE cash_registers ADD COLUMN warehouse_id TEXT REFERENCES warehouses(id);
+ALTER TABLE billing_invoices ADD COLUMN warehouse_id TEXT REFERENCES warehouses(id);
+ALTER TABLE inventory_movements ADD COLUMN warehouse_id TEXT REFERENCES warehouses(id);
+
+-- One main warehouse per existing branch holding its current stock
+INSERT INTO warehouses (id, tenant_id, code, name, is_default, is_active, created_at, updated_at)
+SELECT lower(hex(randomblob(16))), id, 'PRINCIPAL', 'Almacén principal', 1, 1,
+       strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now'), strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')
+FROM tenants;
+
+-- Lots first, then variant stock not in lots, then the rest of the product
+INSERT INTO warehouse_stock (id, tenant_id, warehouse_id, product_id, variant_id, lot_id, quantity, updated_at)
+SELECT lower(hex(randomblob(16))), l.tenant_id, w.id, l.product_id, l.variant_id, l.id, l.quantity,
+       strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')
+FROM inventory_lots l
+JOIN warehouses w ON w.tenant_id = l.tenant_id AND w.is_default = 1
+WHERE l.is_active = 1 AND l.quantity != 0;
+
+INSERT INTO warehouse_stock (id, tenant_id, warehouse_id, product_id, variant_id, lot_id, quantity, updated_at)
+SELECT lower(hex(randomblob(16))), v.tenant_id, w.id, v.product_id, v.id, NULL,
+       vs.quantity - COALESCE((SELECT SUM(ws.quantity) FROM warehouse_stock ws WHERE ws.variant_id = v.id), 0),
+       strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')
+FROM variant_stock vs
+JOIN product_variants v ON v.id = vs.variant_id
+JOIN warehouses w ON w.tenant_id = v.tenant_id AND w.is_default = 1
+WHERE vs.quantity - COALESCE((SELECT SUM(ws.quantity) FROM warehouse_stock ws WHERE ws.variant_id = v.id), 0) != 0;
+
+INSERT INTO warehouse_stock (id, tenant_id, warehouse_id, product_id, variant_id, lot_id, quantity, updated_at)
+SELECT lower(hex(randomblob(16))), p.tenant_id, w.id, p.id, NULL, NULL,
+       COALESCE(p.stock_quantity, 0) - COALESCE((SELECT SUM(ws.quantity) FROM warehouse_stock ws WHERE ws.product_id = p.id), 0),
+       strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')
+FROM products p
+JOIN warehouses w ON w.tenant_id = p.tenant_id AND w.is_default = 1
+WHERE COALESCE(p.stock_quantity, 0) - COALESCE((SELECT SUM(ws.quantity) FROM warehouse_stock ws WHERE ws.product_id = p.id), 0) != 0;
+
+UPDATE cash_registers SET warehouse_id = (
+    SELECT id FROM warehouses WHERE warehouses.tenant_id = cash_registers.tenant_id AND is_default = 1
+);
@@ -0,0 +1,31 @@
+-- Migration 16: Stock Reservations
+-- Created: 2026-10-19
+
+-- Stock held for a document until it is issued, cancelled or the hold expires
+CREATE TABLE IF NOT EXISTS stock_reservations (
+    id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    warehouse_id TEXT NOT NULL,
+    product_id TEXT NOT NULL,
+    variant_id TEXT,
+    quantity REAL NOT NULL,
+    reference_type TEXT NOT NULL,  -- 'invoice' (draft invoices, accepted quotes)
+    reference_id TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'active', -- active, consumed, released, expired
+    expires_at TEXT NOT NULL,
+    created_by TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id),
+    FOREIGN KEY (warehouse_id) REFERENCES warehouses(id),
+    FOREIGN KEY (product_id) REFERENCES products(id),
+    FOREIGN KEY (variant_id) REFERENCES product_variants(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_stock_reservations_reference ON stock_reservations(reference_type, reference_id);
+CREATE INDEX IF NOT EXISTS idx_stock_reservations_active ON stock_reservations(tenant_id, status, expires_at);
+CREATE INDEX IF NOT EXISTS idx_stock_reservations_product ON stock_reservations(product_id, status);
+
+-- Active reservations per product; variant_stock.reserved_quantity per variant
+ALTER TABLE products ADD COLUMN reserved_quantity REAL DEFAULT 0;
+UPDATE variant_stock SET reserved_quantity = 0;
@@ -0,0 +1,45 @@
+-- Migration 17: Physical Inventory Counts
+-- Created: 2026-10-19
+
+-- A count of one warehouse, optionally limited to a category tree (cycle count)
+CREATE TABLE IF NOT EXISTS inventory_counts (
+    id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    warehouse_id TEXT NOT NULL,
+    category_id TEXT,
+    status TEXT NOT NULL DEFAULT 'counting', -- counting, approved, cancelled
+    notes TEXT,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    approved_by TEXT,
+    approved_at TEXT,
+    cancelled_by TEXT,
+    cancelled_at TEXT,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id),
+    FOREIGN KEY (warehouse_id) REFERENCES warehouses(id),
+    FOREIGN KEY (category_id) REFERENCES categories(id)
+);
+
+-- Expected quantities frozen when the count starts; variances post as
+-- counted - expected, so sales made while counting are kept
+CREATE TABLE IF NOT EXISTS inventory_count_lines (
+    id TEXT PRIMARY KEY,
+    count_id TEXT NOT NULL,
+    product_id TEXT NOT NULL,
+    variant_id TEXT,
+    lot_id TEXT,
+    expected_quantity REAL NOT NULL DEFAULT 0,
+    counted_quantity REAL,
+    unit_cost REAL NOT NULL DEFAULT 0,
+    counted_by TEXT,
+    counted_at TEXT,
+    FOREIGN KEY (count_id) REFERENCES inventory_counts(id),
+    FOREIGN KEY (product_id) REFERENCES products(id),
+    FOREIGN KEY (variant_id) REFERENCES product_variants(id),
+    FOREIGN KEY (lot_id) REFERENCES inventory_lots(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_inventory_counts_tenant ON inventory_counts(tenant_id, status, created_at);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_inventory_count_lines_key
+    ON inventory_count_lines(count_id, product_id, IFNULL(variant_id, ''), IFNULL(lot_id, ''));
@@ -0,0 +1,13 @@
+-- Migration 18: Pricing Rules
+-- Created: 2026-10-19
+
+-- Stacking: stackable discounts compound; an exclusive one applies alone,
+-- and only when it beats the stacked ones. Higher priority applies first.
+ALTER TABLE discounts ADD COLUMN stackable INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE discounts ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+
+-- Payment method the invoice was priced for (payment_method discounts)
+ALTER TABLE billing_invoices ADD COLUMN payment_method TEXT;
+
+-- Price source and discounts applied to each line (JSON array)
+ALTER TABLE billing_invoice_items ADD COLUMN pricing_rules TEXT;
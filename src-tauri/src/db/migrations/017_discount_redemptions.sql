@@ -0,0 +1,22 @@
+-- Migration 19: Discount Redemptions
+-- Created: 2026-10-19
+
+-- One row per discount used by an issued invoice; reversed when the
+-- invoice is cancelled or deleted (times_used goes back down)
+CREATE TABLE IF NOT EXISTS discount_redemptions (
+    id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    discount_id TEXT NOT NULL,
+    invoice_id TEXT,               -- NULL for manual redemptions
+    client_id TEXT,
+    amount REAL NOT NULL DEFAULT 0, -- Discount given on the invoice
+    status TEXT NOT NULL DEFAULT 'redeemed', -- redeemed, reversed
+    redeemed_by TEXT,
+    redeemed_at TEXT NOT NULL,
+    reversed_at TEXT,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id),
+    FOREIGN KEY (discount_id) REFERENCES discounts(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_discount_redemptions_discount ON discount_redemptions(discount_id, status);
+CREATE INDEX IF NOT EXISTS idx_discount_redemptions_invoice ON discount_redemptions(invoice_id);
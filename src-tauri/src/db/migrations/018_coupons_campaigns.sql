@@ -0,0 +1,55 @@
+-- Migration 20: Coupons and Campaigns
+-- Created: 2026-10-19
+
+-- Campaign grouping, coupon requirement and per-client limit
+ALTER TABLE discounts ADD COLUMN campaign TEXT;
+ALTER TABLE discounts ADD COLUMN requires_coupon INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE discounts ADD COLUMN per_client_limit INTEGER;
+-- buy_x_get_y: every buy_quantity + get_quantity units, get_quantity are
+-- discounted by `value` percent (100 = free)
+ALTER TABLE discounts ADD COLUMN buy_quantity REAL;
+ALTER TABLE discounts ADD COLUMN get_quantity REAL;
+-- Time-of-day window ('HH:MM', end exclusive, may cross midnight) and
+-- ISO weekdays ('1,2,3,4,5' = Monday to Friday)
+ALTER TABLE discounts ADD COLUMN start_time TEXT;
+ALTER TABLE discounts ADD COLUMN end_time TEXT;
+ALTER TABLE discounts ADD COLUMN weekdays TEXT;
+
+-- Coupon codes of a discount; max_uses 1 = single use, NULL = unlimited
+CREATE TABLE IF NOT EXISTS discount_coupons (
+    id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    discount_id TEXT NOT NULL,
+    code TEXT NOT NULL,
+    max_uses INTEGER,
+    times_used INTEGER NOT NULL DEFAULT 0,
+    client_id TEXT,                -- Only this client may use it
+    batch_id TEXT,                 -- Generated together
+    is_active INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE(tenant_id, code),
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id),
+    FOREIGN KEY (discount_id) REFERENCES discounts(id),
+    FOREIGN KEY (client_id) REFERENCES clients(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_discount_coupons_discount ON discount_coupons(discount_id);
+
+-- Products (and quantities) that make up a bundle discount
+CREATE TABLE IF NOT EXISTS discount_bundle_items (
+    id TEXT PRIMARY KEY,
+    discount_id TEXT NOT NULL,
+    product_id TEXT NOT NULL,
+    quantity REAL NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE(discount_id, product_id),
+    FOREIGN KEY (discount_id) REFERENCES discounts(id),
+    FOREIGN KEY (product_id) REFERENCES products(id)
+);
+
+ALTER TABLE discount_redemptions ADD COLUMN coupon_id TEXT REFERENCES discount_coupons(id);
+
+-- Coupon codes entered on the invoice, comma separated
+ALTER TABLE billing_invoices ADD COLUMN coupon_codes TEXT;
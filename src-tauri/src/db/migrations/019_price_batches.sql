@@ -0,0 +1,31 @@
+-- Migration 21: Bulk Repricing
+-- Created: 2026-10-19
+
+-- A bulk price change, kept so it can be undone
+CREATE TABLE IF NOT EXISTS price_batches (
+    id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    price_list_id TEXT,                      -- NULL: product sale prices
+    operation TEXT NOT NULL,                 -- percentage, fixed, cost_plus, round
+    value REAL NOT NULL DEFAULT 0,
+    round_to REAL,
+    round_mode TEXT,                         -- nearest, up, down
+    filters TEXT,                            -- JSON of the filters used
+    reason TEXT NOT NULL,
+    items_count INTEGER NOT NULL DEFAULT 0,
+    status TEXT NOT NULL DEFAULT 'applied',  -- applied, undone
+    created_by TEXT,
+    created_at TEXT NOT NULL,
+    undone_by TEXT,
+    undone_at TEXT,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id),
+    FOREIGN KEY (price_list_id) REFERENCES price_lists(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_price_batches_tenant ON price_batches(tenant_id, created_at);
+
+-- List prices and batches in the price history
+ALTER TABLE price_history ADD COLUMN price_list_id TEXT;
+ALTER TABLE price_history ADD COLUMN batch_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_price_history_batch ON price_history(batch_id);
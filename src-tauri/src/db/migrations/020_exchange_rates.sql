@@ -0,0 +1,25 @@
+-- Migration 22: Exchange Rates and Derived Price Lists
+-- Created: 2026-10-19
+
+-- Recorded rates, in VES per unit of the currency (as invoices store them)
+CREATE TABLE IF NOT EXISTS exchange_rates (
+    id TEXT PRIMARY KEY,
+    tenant_id TEXT NOT NULL,
+    currency TEXT NOT NULL,          -- USD, EUR
+    rate REAL NOT NULL,
+    rate_date TEXT NOT NULL,         -- YYYY-MM-DD the rate applies from
+    source TEXT,                     -- BCV, paralelo, manual
+    created_by TEXT,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_exchange_rates_lookup
+    ON exchange_rates(tenant_id, currency, rate_date);
+
+-- A list derived from a base list: base price x rate, rounded
+ALTER TABLE price_lists ADD COLUMN base_price_list_id TEXT REFERENCES price_lists(id);
+ALTER TABLE price_lists ADD COLUMN round_to REAL;
+ALTER TABLE price_lists ADD COLUMN round_mode TEXT;
+ALTER TABLE price_lists ADD COLUMN last_rate REAL;
+ALTER TABLE price_lists ADD COLUMN repriced_at TEXT;
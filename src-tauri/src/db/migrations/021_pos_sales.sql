@@ -0,0 +1,10 @@
+-- Migration 23: Point-of-Sale Checkout
+-- Created: 2026-10-19
+
+-- Register and session a sale was rung up at, and the change handed back
+ALTER TABLE billing_invoices ADD COLUMN register_id TEXT REFERENCES cash_registers(id);
+ALTER TABLE billing_invoices ADD COLUMN session_id TEXT REFERENCES cash_register_sessions(id);
+ALTER TABLE billing_invoices ADD COLUMN change_amount REAL NOT NULL DEFAULT 0;
+ALTER TABLE billing_invoices ADD COLUMN change_currency TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_billing_invoices_session ON billing_invoices(session_id);
@@ -0,0 +1,68 @@
+-- Migration 24: Cash Session Denomination Counts and X/Z Reports
+-- Created: 2026-10-19
+
+-- Bills and coins counted when a session is closed
+CREATE TABLE IF NOT EXISTS cash_session_counts (
+    id TEXT PRIMARY KEY NOT NULL,
+    tenant_id TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    currency TEXT NOT NULL, -- USD, VES, EUR
+    denomination REAL NOT NULL,
+    kind TEXT NOT NULL, -- bill, coin
+    quantity INTEGER NOT NULL,
+    amount REAL NOT NULL,
+    created_at TEXT NOT NULL,
+
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id),
+    FOREIGN KEY (session_id) REFERENCES cash_register_sessions(id)
+);
+
+-- Report snapshots: X mid-shift, Z at close. Content is the report as JSON
+-- and hash its SHA-256
+CREATE TABLE IF NOT EXISTS cash_session_reports (
+    id TEXT PRIMARY KEY NOT NULL,
+    tenant_id TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    register_id TEXT NOT NULL,
+    report_type TEXT NOT NULL, -- X, Z
+    report_number INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id),
+    FOREIGN KEY (session_id) REFERENCES cash_register_sessions(id),
+    FOREIGN KEY (register_id) REFERENCES cash_registers(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_cash_session_counts_session ON cash_session_counts(session_id);
+CREATE INDEX IF NOT EXISTS idx_cash_session_reports_session ON cash_session_reports(session_id);
+CREATE INDEX IF NOT EXISTS idx_cash_session_reports_register ON cash_session_reports(register_id, report_type);
+
+-- Reports and counts are never rewritten; re-applying the same row is allowed
+CREATE TRIGGER IF NOT EXISTS trg_cash_session_reports_no_update
+BEFORE UPDATE ON cash_session_reports
+WHEN OLD.content != NEW.content OR OLD.hash != NEW.hash
+BEGIN
+    SELECT RAISE(ABORT, '⛔ INTEGRITY VIOLATION: Cash session reports are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_cash_session_reports_no_delete
+BEFORE DELETE ON cash_session_reports
+BEGIN
+    SELECT RAISE(ABORT, '⛔ INTEGRITY VIOLATION: Cash session reports cannot be deleted');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_cash_session_counts_no_update
+BEFORE UPDATE ON cash_session_counts
+WHEN OLD.quantity != NEW.quantity OR OLD.denomination != NEW.denomination
+BEGIN
+    SELECT RAISE(ABORT, '⛔ INTEGRITY VIOLATION: Closing counts are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS trg_cash_session_counts_no_delete
+BEFORE DELETE ON cash_session_counts
+BEGIN
+    SELECT RAISE(ABORT, '⛔ INTEGRITY VIOLATION: Closing counts cannot be deleted');
+END;
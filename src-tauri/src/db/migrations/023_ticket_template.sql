@@ -0,0 +1,5 @@
+-- Migration 25: Thermal Ticket Template
+-- Created: 2026-10-19
+
+-- ESC/POS ticket layout and default printer, stored as JSON
+ALTER TABLE company_settings ADD COLUMN ticket_template TEXT;
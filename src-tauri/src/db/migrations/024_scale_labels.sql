@@ -0,0 +1,5 @@
+-- Migration 26: Deli Scale Label Rules
+-- Created: 2026-10-19
+
+-- In-store EAN-13 layouts (prefix, PLU, weight or price), stored as JSON
+ALTER TABLE company_settings ADD COLUMN scale_labels TEXT;
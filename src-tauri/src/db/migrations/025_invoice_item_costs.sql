@@ -0,0 +1,15 @@
+-- Migration 27: Invoice Line Cost Snapshots
+-- Created: 2026-10-19
+
+-- Unit cost when the line was invoiced, for margin reports
+ALTER TABLE billing_invoice_items ADD COLUMN unit_cost REAL;
+
+-- Earlier lines take the lot, variant or product cost as it stands today
+UPDATE billing_invoice_items SET unit_cost = COALESCE(
+    (SELECT cost_price FROM inventory_lots WHERE id = billing_invoice_items.lot_id),
+    (SELECT NULLIF(cost_price, 0) FROM product_variants WHERE id = billing_invoice_items.variant_id),
+    (SELECT cost_price FROM products WHERE id = billing_invoice_items.product_id),
+    0
+);
+
+CREATE INDEX IF NOT EXISTS idx_billing_invoices_tenant_date ON billing_invoices(tenant_id, issue_date);
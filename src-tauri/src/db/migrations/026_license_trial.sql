@@ -0,0 +1,8 @@
+-- Migration 28: License Trial
+-- Created: 2026-10-19
+
+-- Installations without a license keep every module for 30 days from the
+-- upgrade (or from setup), so existing users are not locked out before
+-- they can install the signed license.
+INSERT OR IGNORE INTO security_metadata (key, value)
+VALUES ('license_trial_until', strftime('%Y-%m-%dT%H:%M:%SZ', 'now', '+30 days'));
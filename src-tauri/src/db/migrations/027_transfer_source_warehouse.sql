@@ -0,0 +1,7 @@
+-- Migration 29: Transfer Source Warehouse
+-- Created: 2026-10-19
+
+-- Branch transfers ship from one warehouse of the source branch and a
+-- cancellation returns the stock there. Older transfers (NULL) used the
+-- branch's default warehouse.
+ALTER TABLE stock_transfers ADD COLUMN from_warehouse_id TEXT REFERENCES warehouses(id);
@@ -0,0 +1,12 @@
+-- Migration 30: Invoice Chain
+-- Created: 2026-10-19
+
+-- Issued documents are hash-chained per branch (security/secure_chain.rs):
+-- `hash` covers the document and `prev_hash`, the hash of the one issued
+-- before it on this installation. Documents issued earlier stay unchained.
+ALTER TABLE billing_invoices ADD COLUMN prev_hash TEXT;
+ALTER TABLE billing_invoices ADD COLUMN hash TEXT;
+
+-- Two documents cannot follow the same one
+CREATE UNIQUE INDEX IF NOT EXISTS idx_billing_invoices_chain
+    ON billing_invoices(tenant_id, prev_hash) WHERE prev_hash IS NOT NULL;
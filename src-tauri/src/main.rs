@@ -25,6 +25,14 @@ fn main() {
         .setup(|app| {
             // Initialize database and state
             let app_state = AppState::new(app.handle().clone())?;
+
+            // Scheduled encrypted backups
+            let backup_dir = commands::backup::default_backup_dir(app.handle())?;
+            services::backup::start_backup_scheduler(app_state.db.clone(), backup_dir);
+
+            // Background cloud sync
+            app_state.sync_scheduler.start();
+
             app.manage(app_state);
 
             Ok(())
@@ -42,6 +50,19 @@ fn main() {
             commands::auth::change_password,
             commands::auth::setup_initial_admin,
             commands::auth::check_setup_required,
+            // Audit
+            commands::audit::list_audit_events,
+            commands::audit::get_entity_history,
+            commands::audit::export_audit_events,
+            commands::audit::verify_audit_export,
+            commands::audit::get_audit_public_key,
+            // Backup
+            commands::backup::get_backup_settings,
+            commands::backup::update_backup_settings,
+            commands::backup::run_backup_now,
+            commands::backup::list_backups,
+            commands::backup::verify_backup,
+            commands::backup::restore_backup,
             // Clients
             commands::clients::create_client,
             commands::clients::get_client,
@@ -74,6 +95,8 @@ fn main() {
             commands::products::restore_product,
             commands::products::adjust_stock,
             commands::products::get_low_stock_products,
+            commands::products::lookup_by_barcode,
+            commands::products::generate_internal_barcodes,
             // Variants
             commands::variants::list_variants,
             commands::variants::get_variant,
@@ -112,6 +135,11 @@ fn main() {
             commands::price_lists::list_product_prices,
             commands::price_lists::set_product_price,
             commands::price_lists::delete_product_price,
+            commands::price_lists::recompute_price_list,
+            // Exchange rates
+            commands::exchange_rates::record_exchange_rate,
+            commands::exchange_rates::list_exchange_rates,
+            commands::exchange_rates::get_current_exchange_rate,
             // Discounts
             commands::discounts::list_discounts,
             commands::discounts::get_discount,
@@ -119,18 +147,36 @@ fn main() {
             commands::discounts::update_discount,
             commands::discounts::delete_discount,
             commands::discounts::use_discount,
+            commands::discounts::list_discount_redemptions,
+            commands::discounts::create_discount_coupon,
+            commands::discounts::generate_discount_coupons,
+            commands::discounts::list_discount_coupons,
+            commands::discounts::set_discount_coupon_active,
+            commands::discounts::set_discount_bundle_items,
+            commands::discounts::list_discount_bundle_items,
+            commands::discounts::get_campaign_report,
+            // Pricing
+            commands::pricing::resolve_price,
+            // Bulk repricing
+            commands::repricing::preview_bulk_reprice,
+            commands::repricing::apply_bulk_reprice,
+            commands::repricing::list_price_batches,
+            commands::repricing::undo_price_batch,
             // Invoices
             commands::invoices::list_invoices,
             commands::invoices::get_invoice,
             commands::invoices::create_invoice,
             commands::invoices::update_invoice,
             commands::invoices::issue_invoice,
+            commands::invoices::create_pos_sale,
             commands::invoices::cancel_invoice,
             commands::invoices::delete_invoice,
+            commands::invoices::print_invoice_ticket,
             // Payments
             commands::payments::list_payments,
             commands::payments::register_payment,
             commands::payments::delete_payment,
+            commands::payments::print_payment_receipt,
             commands::payments::get_account_balances,
             commands::payments::get_account_balances,
             commands::payments::get_recent_movements,
@@ -147,21 +193,88 @@ fn main() {
             commands::sync::start_sync,
             commands::sync::get_last_sync_status,
             commands::sync::check_cloud_updates,
+            commands::sync::list_sync_conflicts,
+            commands::sync::resolve_sync_conflict,
+            commands::sync::get_sync_policies,
+            commands::sync::set_sync_policy,
+            commands::sync::list_failed_uploads,
+            commands::sync::retry_failed_uploads,
+            commands::sync::discard_failed_upload,
+            commands::sync::get_sync_backend_settings,
+            commands::sync::update_sync_backend_settings,
+            commands::sync::test_sync_backend,
+            commands::sync::list_sync_devices,
+            commands::sync::revoke_sync_device,
+            commands::sync::create_sync_enrollment_token,
             // Security
             commands::security::get_hardware_id,
             commands::security::get_hardware_id,
             commands::security::verify_license_locally,
+            commands::security::get_time_integrity_status,
+            commands::security::recover_time_integrity,
+            commands::security::get_key_status,
+            commands::security::generate_recovery_code,
+            commands::security::set_recovery_passphrase,
+            commands::security::rotate_database_key,
             // Cash Register
             commands::cash_register::create_register,
+            commands::cash_register::set_register_warehouse,
             commands::cash_register::open_session,
             commands::cash_register::close_session,
             commands::cash_register::add_movement,
             commands::cash_register::get_active_session,
             commands::cash_register::list_registers,
+            commands::cash_register::list_cash_denominations,
+            commands::cash_register::take_x_report,
+            commands::cash_register::list_session_reports,
+            commands::cash_register::export_session_report,
+            commands::cash_register::print_cash_movement,
+            commands::cash_register::print_session_report,
+            // Branches
+            commands::branches::list_my_branches,
+            commands::branches::switch_tenant,
+            commands::branches::create_branch,
+            commands::branches::set_branch_access,
+            commands::branches::get_branch_stock,
+            commands::branches::list_stock_transfers,
+            commands::branches::create_stock_transfer,
+            commands::branches::receive_stock_transfer,
+            commands::branches::cancel_stock_transfer,
+            commands::branches::get_consolidated_report,
+            // Reports
+            commands::reports::get_sales_report,
+            commands::reports::get_tax_report,
+            commands::reports::get_payment_mix,
+            commands::reports::get_top_products,
+            // Warehouses
+            commands::warehouses::list_warehouses,
+            commands::warehouses::create_warehouse,
+            commands::warehouses::update_warehouse,
+            commands::warehouses::list_warehouse_stock,
+            commands::warehouses::set_stock_location,
+            commands::warehouses::create_warehouse_transfer,
+            commands::warehouses::get_low_stock_by_warehouse,
+            // Stock reservations
+            commands::reservations::list_stock_reservations,
+            commands::reservations::get_available_stock,
+            // Physical inventory counts
+            commands::inventory_counts::start_inventory_count,
+            commands::inventory_counts::list_inventory_counts,
+            commands::inventory_counts::get_inventory_count,
+            commands::inventory_counts::record_inventory_count,
+            commands::inventory_counts::approve_inventory_count,
+            commands::inventory_counts::cancel_inventory_count,
             // Updater
             commands::updater::check_for_updates,
             commands::updater::install_update,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Flush pending uploads before the process exits
+            if let tauri::RunEvent::Exit = event {
+                let scheduler = app.state::<AppState>().sync_scheduler.clone();
+                tauri::async_runtime::block_on(scheduler.shutdown());
+            }
+        });
 }
@@ -0,0 +1,80 @@
+//! Audit Log Models
+
+use serde::{Deserialize, Serialize};
+
+/// Audit Event - A single immutable entry from audit_logs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub tenant_id: Option<String>,
+    pub user_id: Option<String>,
+    pub user_name: Option<String>, // Resolved from users at read time
+    pub event_type: String,        // "FISCAL_DOC_ISSUED", "PRODUCT_UPDATED", ...
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    pub details: Option<String>,
+    pub timestamp: String,
+}
+
+/// Audit event filters
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AuditEventFilters {
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+    pub user_id: Option<String>,
+    pub event_type: Option<String>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    pub tenant_id: Option<String>,
+    pub page: Option<u32>,      // 1-based
+    pub page_size: Option<u32>, // Default 50, max 500
+}
+
+/// A page of audit events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEventPage {
+    pub items: Vec<AuditEvent>,
+    pub total: i64,
+    pub page: u32,
+    pub page_size: u32,
+}
+
+/// Export format for audit events
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditExportFormat {
+    Csv,
+    Json,
+}
+
+/// Signed content of a JSON audit export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditExportPayload {
+    pub generated_at: String,
+    pub generated_by: String,
+    pub org_id: String,
+    pub filters: AuditEventFilters,
+    pub events: Vec<AuditEvent>,
+}
+
+/// Signed JSON audit export for auditors
+///
+/// `signature` is an Ed25519 signature over the compact JSON serialization
+/// of `payload`, made with this installation's audit signing key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedAuditExport {
+    pub algorithm: String, // "Ed25519"
+    pub public_key: String,
+    pub payload_sha256: String,
+    pub signature: String,
+    pub payload: AuditExportPayload,
+}
+
+/// Result of an audit export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditExportResult {
+    pub path: String,
+    pub format: AuditExportFormat,
+    pub event_count: usize,
+    pub sha256: String, // Hash of the written file
+}
@@ -0,0 +1,74 @@
+//! Backup Models
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Tell a missing field (leave unchanged) from an explicit `null` (clear it)
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Backup schedule and retention settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupSettings {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    pub keep_last: u32,            // Rotation: newest N backups kept per folder
+    pub max_age_days: Option<u32>, // Retention: delete backups older than this
+    pub external_folder: Option<String>, // User-chosen folder or USB drive (mirror)
+    pub last_backup_at: Option<String>,
+    pub last_error: Option<String>,
+    pub has_passphrase: bool, // Recovery passphrase configured
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_hours: 4,
+            keep_last: 14,
+            max_age_days: Some(90),
+            external_folder: None,
+            last_backup_at: None,
+            last_error: None,
+            has_passphrase: false,
+        }
+    }
+}
+
+/// DTO for updating backup settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateBackupSettingsDto {
+    pub enabled: Option<bool>,
+    pub interval_hours: Option<u32>,
+    pub keep_last: Option<u32>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub max_age_days: Option<Option<u32>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub external_folder: Option<Option<String>>,
+    pub passphrase: Option<String>, // New recovery passphrase (never stored)
+}
+
+/// Manifest written next to every backup file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub file_name: String,
+    pub created_at: String,
+    pub app_version: String,
+    pub schema_version: i32,
+    pub kdf: String,  // "argon2id"
+    pub salt: String, // Hex salt for the passphrase KDF
+    pub sha256: String,
+    pub size_bytes: u64,
+}
+
+/// A backup found on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub path: String,
+    pub folder: String,
+    pub manifest: BackupManifest,
+}
@@ -0,0 +1,44 @@
+//! Barcode Models
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Symbology {
+    Ean13,
+    Ean8,
+    UpcA,
+    Gtin14,
+    Gs1,        // GS1-128 / DataMatrix element string
+    ScaleLabel, // In-store EAN-13 with embedded weight or price
+    Code128,    // Anything else: SKUs, lot numbers, free text
+}
+
+/// What a scanned code resolved to, with the quantity to sell
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BarcodeMatch {
+    pub code: String,
+    pub symbology: Symbology,
+    pub product_id: String,
+    pub product_name: String,
+    pub sku: Option<String>,
+    pub variant_id: Option<String>,
+    pub variant_name: Option<String>,
+    pub lot_id: Option<String>,
+    pub lot_number: Option<String>,
+    pub expiration_date: Option<String>,
+    pub serial: Option<String>,
+    pub unit_price: f64,
+    /// Embedded weight or count; 1 for plain codes
+    pub quantity: f64,
+    /// Line total printed on price-embedded labels
+    pub embedded_price: Option<f64>,
+}
+
+/// Internal barcode given to a product or variant that had none
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeneratedBarcode {
+    pub product_id: String,
+    pub variant_id: Option<String>,
+    pub barcode: String,
+}
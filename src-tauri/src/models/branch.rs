@@ -0,0 +1,112 @@
+//! Branch (Tenant) Model
+
+use serde::{Deserialize, Serialize};
+
+/// Branch a user can operate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Branch {
+    pub id: String,
+    pub org_id: String,
+    pub name: String,
+    pub is_active: bool,
+    /// The user's own branch (users.tenant_id)
+    pub is_home: bool,
+    /// The branch the session is operating on
+    pub is_current: bool,
+}
+
+/// Stock of one product (matched by SKU) in a branch of the organization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchStock {
+    pub tenant_id: String,
+    pub tenant_name: String,
+    pub product_id: String,
+    pub stock_quantity: f64,
+    pub min_stock: f64,
+    /// Shipped to this branch and not received yet
+    pub incoming_quantity: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockTransferItem {
+    pub id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub sku: Option<String>,
+    pub destination_product_id: Option<String>,
+    pub quantity: f64,
+}
+
+/// Inter-branch stock transfer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockTransfer {
+    pub id: String,
+    pub org_id: String,
+    pub from_tenant_id: String,
+    pub from_tenant_name: String,
+    /// Source warehouse; None for transfers shipped from the default one
+    pub from_warehouse_id: Option<String>,
+    pub to_tenant_id: String,
+    pub to_tenant_name: String,
+    /// Status: "in_transit", "received" or "cancelled"
+    pub status: String,
+    pub notes: Option<String>,
+    pub shipped_by: String,
+    pub shipped_at: String,
+    pub received_by: Option<String>,
+    pub received_at: Option<String>,
+    pub cancelled_by: Option<String>,
+    pub cancelled_at: Option<String>,
+    pub items: Vec<StockTransferItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferItemDto {
+    pub product_id: String,
+    pub quantity: f64,
+}
+
+/// Ship stock from the active branch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateStockTransferDto {
+    pub to_tenant_id: String,
+    /// Warehouse the stock leaves from (default warehouse if omitted)
+    #[serde(default)]
+    pub from_warehouse_id: Option<String>,
+    pub items: Vec<TransferItemDto>,
+    pub notes: Option<String>,
+}
+
+/// Invoiced sales in one currency
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CurrencyTotals {
+    pub currency: String,
+    pub invoice_count: i64,
+    pub subtotal: f64,
+    pub tax_total: f64,
+    pub total: f64,
+    pub paid_amount: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchReport {
+    pub tenant_id: String,
+    pub tenant_name: String,
+    pub sales: Vec<CurrencyTotals>,
+    /// Stock valued at cost price
+    pub stock_value: f64,
+    pub low_stock_count: i64,
+    pub transfers_in_transit_out: i64,
+    pub transfers_in_transit_in: i64,
+}
+
+/// Figures of every branch the user can access, plus their sum
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsolidatedReport {
+    pub org_id: String,
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+    pub branches: Vec<BranchReport>,
+    pub sales: Vec<CurrencyTotals>,
+    pub stock_value: f64,
+}
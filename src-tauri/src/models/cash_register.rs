@@ -1,3 +1,4 @@
+use crate::models::{CreateInvoiceItemDto, Invoice, Payment};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -7,6 +8,8 @@ pub struct CashRegister {
     pub name: String,
     pub status: String, // open, closed
     pub current_session_id: Option<String>,
+    /// Warehouse sales at this register take stock from
+    pub warehouse_id: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -44,7 +47,7 @@ pub struct CashRegisterSession {
     pub updated_at: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CashMovement {
     pub id: String,
     pub tenant_id: String,
@@ -77,6 +80,9 @@ pub struct CloseSessionDto {
     pub closing_amount_ves: f64,
     pub closing_amount_eur: f64,
     pub notes: Option<String>,
+    /// Bills and coins counted; when given, the closing amounts are their sums
+    #[serde(default)]
+    pub counts: Vec<DenominationCountDto>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -88,3 +94,157 @@ pub struct AddMovementDto {
     pub reason: String,
     pub reference: String,
 }
+
+/// One tender of a point-of-sale sale, in its own currency
+#[derive(Debug, Clone, Deserialize)]
+pub struct PosTenderDto {
+    pub payment_method: String, // cash, card, mobile, transfer
+    pub currency: String,       // USD, VES, EUR
+    pub amount: f64,
+    pub reference: Option<String>,
+    pub bank_account_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PosSaleDto {
+    pub client_id: String,
+    pub price_list_id: Option<String>,
+    pub currency: String,
+    pub items: Vec<CreateInvoiceItemDto>,
+    pub tenders: Vec<PosTenderDto>,
+    /// Currency the change is given in; defaults to the invoice's
+    pub change_currency: Option<String>,
+    #[serde(default)]
+    pub coupon_codes: Option<Vec<String>>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PosSale {
+    pub invoice: Invoice,
+    pub payments: Vec<Payment>,
+    pub change_amount: f64,
+    pub change_currency: String,
+}
+
+/// A bill or coin a drawer can hold
+#[derive(Debug, Clone, Serialize)]
+pub struct CashDenomination {
+    pub currency: String,
+    pub denomination: f64,
+    pub kind: String, // bill, coin
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DenominationCountDto {
+    pub currency: String,
+    pub denomination: f64,
+    pub quantity: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DenominationCount {
+    pub currency: String,
+    pub denomination: f64,
+    pub kind: String,
+    pub quantity: i64,
+    pub amount: f64,
+}
+
+/// Drawer reconciliation for one currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrencyBalance {
+    pub currency: String,
+    pub opening: f64,
+    pub cash_sales: f64,
+    pub change_given: f64,
+    pub deposits: f64,
+    pub withdrawals: f64,
+    pub expected: f64,
+    /// Declared at close; absent on X-reports
+    pub counted: Option<f64>,
+    /// Counted less expected: positive is over, negative short
+    pub over_short: Option<f64>,
+}
+
+/// Tenders taken with a method, in the tender currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentMethodTotal {
+    pub payment_method: String,
+    pub currency: String,
+    pub count: i64,
+    pub amount: f64,
+}
+
+/// Invoices of the session in one invoice currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SalesTotal {
+    pub currency: String,
+    pub invoices: i64,
+    pub subtotal: f64,
+    pub discount_total: f64,
+    pub tax_total: f64,
+    pub total: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaxTotal {
+    pub currency: String,
+    pub tax_rate: f64,
+    pub taxable: f64,
+    pub tax: f64,
+}
+
+/// Credit notes and cancelled invoices of the session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefundTotal {
+    pub currency: String,
+    pub count: i64,
+    pub total: f64,
+}
+
+/// Rate (VES per unit) payments of the session were taken at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateUsed {
+    pub currency: String,
+    pub rate: f64,
+    pub payments: i64,
+}
+
+/// X (mid-shift) or Z (closing) report of a session. Stored reports are
+/// never modified; `hash` is the SHA-256 of the stored content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionReport {
+    pub id: String,
+    pub report_type: String, // X, Z
+    /// Z-reports are numbered per register, X-reports per session
+    pub report_number: i64,
+    pub session_id: String,
+    pub register_id: String,
+    pub register_name: String,
+    pub user_id: String,
+    pub user_name: Option<String>,
+    pub start_time: String,
+    pub end_time: Option<String>,
+    pub opening_exchange_rate_ves: f64,
+    pub opening_exchange_rate_eur: f64,
+    pub balances: Vec<CurrencyBalance>,
+    pub payments: Vec<PaymentMethodTotal>,
+    pub sales: Vec<SalesTotal>,
+    pub taxes: Vec<TaxTotal>,
+    pub refunds: Vec<RefundTotal>,
+    pub movements: Vec<CashMovement>,
+    pub denominations: Vec<DenominationCount>,
+    pub rates: Vec<RateUsed>,
+    pub created_by: String,
+    pub created_at: String,
+    #[serde(default)]
+    pub hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionReportFormat {
+    Pdf,
+    Ticket,
+}
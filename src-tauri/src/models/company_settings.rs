@@ -23,6 +23,10 @@ pub struct CompanySettings {
     pub default_currency: String,        // "USD", "VES", "EUR"
     pub legal_note: Option<String>,      // Footer text for invoices
     pub invoice_pattern: Option<String>, // e.g. "{PREFIX}-{YEAR}-{NUMBER}"
+    #[serde(default)]
+    pub ticket_template: TicketTemplate,
+    #[serde(default = "ScaleLabelRule::defaults")]
+    pub scale_labels: Vec<ScaleLabelRule>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -45,6 +49,75 @@ pub struct UpdateCompanySettingsDto {
     pub invoice_pattern: Option<String>,
     pub default_currency: Option<String>,
     pub legal_note: Option<String>,
+    pub ticket_template: Option<TicketTemplate>,
+    pub scale_labels: Option<Vec<ScaleLabelRule>>,
+}
+
+/// Thermal ticket layout and default printer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TicketTemplate {
+    pub paper_width: u16,    // 58 or 80 (mm)
+    pub header: Vec<String>, // Extra lines under the company data
+    pub footer: Vec<String>, // Lines above the cut, e.g. "Gracias por su compra"
+    pub show_logo: bool,     // Print logo_path (monochrome BMP) as a raster image
+    pub show_qr: bool,       // QR with the invoice fingerprint
+    pub cut: bool,
+    pub feed_lines: u8,          // Blank lines before the cut
+    pub printer: Option<String>, // "serial:/dev/ttyS0", "usb:/dev/usb/lp0", "network:10.0.0.5:9100", "file:/tmp/ticket.bin"
+}
+
+impl Default for TicketTemplate {
+    fn default() -> Self {
+        Self {
+            paper_width: 80,
+            header: Vec::new(),
+            footer: Vec::new(),
+            show_logo: true,
+            show_qr: true,
+            cut: true,
+            feed_lines: 3,
+            printer: None,
+        }
+    }
+}
+
+/// What the digits after the PLU of a scale label stand for
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleLabelValue {
+    Weight, // Kilograms
+    Price,  // Line total in the default currency
+}
+
+/// Layout of an in-store EAN-13 printed by a deli scale:
+/// prefix, PLU, value, check digit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScaleLabelRule {
+    pub prefix: String,    // "20".."28"; 29 is taken by internal barcodes
+    pub plu_digits: usize, // Matched against the product's SKU or barcode
+    pub value: ScaleLabelValue,
+    pub decimals: u32, // 3 for grams, 2 for cents
+}
+
+impl ScaleLabelRule {
+    /// 20 + PLU(5) + grams(5), and 21 + PLU(5) + cents(5)
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self {
+                prefix: "20".to_string(),
+                plu_digits: 5,
+                value: ScaleLabelValue::Weight,
+                decimals: 3,
+            },
+            Self {
+                prefix: "21".to_string(),
+                plu_digits: 5,
+                value: ScaleLabelValue::Price,
+                decimals: 2,
+            },
+        ]
+    }
 }
 
 /// Invoice Sequence Settings
@@ -8,16 +8,26 @@ pub struct Discount {
     pub id: String,
     pub tenant_id: String,
     pub name: String,
-    pub discount_type: String,      // "percentage", "fixed", "volume"
-    pub value: f64,                 // Percentage or fixed amount
-    pub applies_to: String,         // "product", "category", "client", "payment_method", "all"
-    pub target_id: Option<String>,  // ID of product/category/client if applicable
-    pub min_quantity: Option<f64>,  // For volume discounts
-    pub max_uses: Option<i64>,      // Max times this discount can be used (null = unlimited)
-    pub times_used: i64,            // Counter of times used
+    pub discount_type: String, // "percentage", "fixed", "volume", "buy_x_get_y", "bundle"
+    pub value: f64,            // Percentage or fixed amount
+    pub applies_to: String,    // "product", "category", "client", "payment_method", "bundle", "all"
+    pub target_id: Option<String>, // ID of product/category/client if applicable
+    pub min_quantity: Option<f64>, // For volume discounts
+    pub max_uses: Option<i64>, // Max times this discount can be used (null = unlimited)
+    pub times_used: i64,       // Counter of times used
     pub start_date: Option<String>, // Promotion start date
-    pub end_date: Option<String>,   // Promotion end date
+    pub end_date: Option<String>, // Promotion end date
     pub is_active: bool,
+    pub stackable: bool, // Compounds with others; if not, applies alone
+    pub priority: i64,   // Higher applies first
+    pub campaign: Option<String>,
+    pub requires_coupon: bool, // Only applies with one of its coupon codes
+    pub per_client_limit: Option<i64>, // Uses per client
+    pub buy_quantity: Option<f64>, // buy_x_get_y: buy X...
+    pub get_quantity: Option<f64>, // ...get Y at `value` percent off
+    pub start_time: Option<String>, // "HH:MM", happy hour
+    pub end_time: Option<String>, // "HH:MM", exclusive; may cross midnight
+    pub weekdays: Option<String>, // ISO weekdays, "1,2,3,4,5" = Mon-Fri
     pub created_at: String,
     pub updated_at: String,
 }
@@ -35,6 +45,24 @@ pub struct CreateDiscountDto {
     pub start_date: Option<String>,
     pub end_date: Option<String>,
     pub is_active: bool,
+    pub stackable: Option<bool>, // Default true
+    pub priority: Option<i64>,
+    #[serde(default)]
+    pub campaign: Option<String>,
+    #[serde(default)]
+    pub requires_coupon: Option<bool>,
+    #[serde(default)]
+    pub per_client_limit: Option<i64>,
+    #[serde(default)]
+    pub buy_quantity: Option<f64>,
+    #[serde(default)]
+    pub get_quantity: Option<f64>,
+    #[serde(default)]
+    pub start_time: Option<String>,
+    #[serde(default)]
+    pub end_time: Option<String>,
+    #[serde(default)]
+    pub weekdays: Option<String>,
 }
 
 /// DTO for updating a discount
@@ -50,4 +78,103 @@ pub struct UpdateDiscountDto {
     pub start_date: Option<String>,
     pub end_date: Option<String>,
     pub is_active: Option<bool>,
+    pub stackable: Option<bool>,
+    pub priority: Option<i64>,
+    #[serde(default)]
+    pub campaign: Option<String>,
+    #[serde(default)]
+    pub requires_coupon: Option<bool>,
+    #[serde(default)]
+    pub per_client_limit: Option<i64>,
+    #[serde(default)]
+    pub buy_quantity: Option<f64>,
+    #[serde(default)]
+    pub get_quantity: Option<f64>,
+    #[serde(default)]
+    pub start_time: Option<String>,
+    #[serde(default)]
+    pub end_time: Option<String>,
+    #[serde(default)]
+    pub weekdays: Option<String>,
+}
+
+/// Discount Redemption - One use of a discount, by an invoice or manual
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscountRedemption {
+    pub id: String,
+    pub discount_id: String,
+    pub discount_name: String,
+    pub invoice_id: Option<String>,
+    pub invoice_number: Option<String>,
+    pub client_id: Option<String>,
+    pub coupon_id: Option<String>,
+    pub coupon_code: Option<String>,
+    pub amount: f64,
+    pub status: String, // "redeemed", "reversed"
+    pub redeemed_by: Option<String>,
+    pub redeemed_at: String,
+    pub reversed_at: Option<String>,
+}
+
+/// Discount Coupon - Code that unlocks a discount
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscountCoupon {
+    pub id: String,
+    pub discount_id: String,
+    pub code: String,
+    pub max_uses: Option<i64>, // 1 = single use, null = unlimited
+    pub times_used: i64,
+    pub client_id: Option<String>, // Only this client may use it
+    pub batch_id: Option<String>,
+    pub is_active: bool,
+    pub created_at: String,
+}
+
+/// DTO for creating one coupon with a chosen code
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateCouponDto {
+    pub discount_id: String,
+    pub code: String,
+    pub max_uses: Option<i64>,
+    pub client_id: Option<String>,
+}
+
+/// DTO for generating a batch of random coupon codes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerateCouponsDto {
+    pub discount_id: String,
+    pub count: u32,
+    pub prefix: Option<String>,
+    pub length: Option<usize>, // Random part, default 8
+    pub max_uses: Option<i64>, // Default 1 (single use)
+    pub client_id: Option<String>,
+}
+
+/// Discount Bundle Item - Product and quantity that make up a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscountBundleItem {
+    pub product_id: String,
+    pub quantity: f64,
+}
+
+/// Campaign performance filters
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CampaignReportFilters {
+    pub campaign: Option<String>,
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+}
+
+/// Campaign performance of one discount
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CampaignPerformance {
+    pub discount_id: String,
+    pub name: String,
+    pub campaign: Option<String>,
+    pub uses: i64,
+    pub clients: i64,
+    pub coupons_issued: i64,
+    pub coupons_used: i64,
+    pub discounted_amount: f64,
+    pub revenue: f64, // Total of the invoices that used it
 }
@@ -0,0 +1,32 @@
+//! Exchange Rate Models
+
+use crate::models::pricing::PriceBatch;
+use serde::{Deserialize, Serialize};
+
+/// A recorded rate: VES per unit of `currency`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeRate {
+    pub id: String,
+    pub currency: String, // "USD", "EUR"
+    pub rate: f64,
+    pub rate_date: String,
+    pub source: Option<String>, // "BCV", "manual"
+    pub created_by: Option<String>,
+    pub created_at: String,
+}
+
+/// DTO for recording a rate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordExchangeRateDto {
+    pub currency: String,
+    pub rate: f64,
+    pub rate_date: Option<String>, // Today if not given
+    pub source: Option<String>,
+}
+
+/// A recorded rate and the derived lists it repriced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeRateUpdate {
+    pub rate: ExchangeRate,
+    pub batches: Vec<PriceBatch>,
+}
@@ -0,0 +1,72 @@
+//! Physical Inventory Count Model
+
+use serde::{Deserialize, Serialize};
+
+/// Count session of a warehouse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryCount {
+    pub id: String,
+    pub tenant_id: String,
+    pub warehouse_id: String,
+    pub warehouse_name: String,
+    /// Set for cycle counts of a category (and its subcategories)
+    pub category_id: Option<String>,
+    /// Status: "counting", "approved" or "cancelled"
+    pub status: String,
+    pub notes: Option<String>,
+    pub created_by: String,
+    pub created_at: String,
+    pub approved_by: Option<String>,
+    pub approved_at: Option<String>,
+    pub line_count: i64,
+    pub counted_lines: i64,
+    /// Sum of counted - expected over counted lines
+    pub variance_quantity: f64,
+    /// Variance valued at the snapshot's unit cost
+    pub variance_cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryCountLine {
+    pub id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub sku: Option<String>,
+    pub variant_id: Option<String>,
+    pub variant_name: Option<String>,
+    pub lot_id: Option<String>,
+    pub lot_number: Option<String>,
+    pub expected_quantity: f64,
+    pub counted_quantity: Option<f64>,
+    pub variance: Option<f64>,
+    pub unit_cost: f64,
+    pub variance_cost: Option<f64>,
+    pub counted_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InventoryCountDetail {
+    pub count: InventoryCount,
+    pub lines: Vec<InventoryCountLine>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartCountDto {
+    pub warehouse_id: Option<String>,
+    /// Cycle count of this category tree; the whole warehouse if not given
+    pub category_id: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// A count entry, identified by line, by product/variant/lot or by barcode
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CountEntryDto {
+    pub line_id: Option<String>,
+    pub barcode: Option<String>,
+    pub product_id: Option<String>,
+    pub variant_id: Option<String>,
+    pub lot_id: Option<String>,
+    pub quantity: f64,
+    /// Add to the counted quantity instead of replacing it (scans)
+    pub add: Option<bool>,
+}
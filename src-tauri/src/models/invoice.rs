@@ -1,5 +1,6 @@
 //! Invoice Models
 
+use crate::models::pricing::AppliedPriceRule;
 use serde::{Deserialize, Serialize};
 
 /// Invoice - Main billing document
@@ -26,6 +27,23 @@ pub struct Invoice {
     pub total: f64,
     pub paid_amount: f64,
     pub notes: Option<String>,
+    /// Warehouse the stock is taken from
+    pub warehouse_id: Option<String>,
+    /// Payment method the invoice was priced for
+    pub payment_method: Option<String>,
+    /// Coupon codes applied when pricing
+    #[serde(default)]
+    pub coupon_codes: Vec<String>,
+    /// Register and session of a point-of-sale sale
+    #[serde(default)]
+    pub register_id: Option<String>,
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Cash handed back to the customer, in `change_currency`
+    #[serde(default)]
+    pub change_amount: f64,
+    #[serde(default)]
+    pub change_currency: Option<String>,
     pub created_by: String,
     pub created_at: String,
     pub updated_at: String,
@@ -48,6 +66,9 @@ pub struct InvoiceItem {
     pub tax_rate: f64,
     pub tax_amount: f64,
     pub line_total: f64,
+    /// Price source and discounts applied by the pricing service
+    #[serde(default)]
+    pub pricing_rules: Vec<AppliedPriceRule>,
 }
 
 /// DTO for creating an invoice
@@ -63,17 +84,24 @@ pub struct CreateInvoiceDto {
     pub payment_terms: Option<String>,
     pub notes: Option<String>,
     pub items: Vec<CreateInvoiceItemDto>,
+    /// Source warehouse; defaults to the open register's, then the branch's
+    #[serde(default)]
+    pub warehouse_id: Option<String>,
+    /// Payment method, for payment method discounts
+    #[serde(default)]
+    pub payment_method: Option<String>,
+    /// Coupon codes to apply
+    #[serde(default)]
+    pub coupon_codes: Option<Vec<String>>,
 }
 
-/// DTO for creating an invoice item
+/// DTO for creating an invoice item; price, discounts and tax are resolved
+/// by the pricing service
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateInvoiceItemDto {
     pub product_id: String,
     pub variant_id: Option<String>,
     pub quantity: f64,
-    pub unit_price: f64,
-    pub discount_percent: f64,
-    pub tax_rate: f64,
 }
 
 /// DTO for updating an invoice (draft only)
@@ -88,6 +116,12 @@ pub struct UpdateInvoiceDto {
     pub payment_terms: Option<String>,
     pub notes: Option<String>,
     pub items: Option<Vec<CreateInvoiceItemDto>>,
+    #[serde(default)]
+    pub warehouse_id: Option<String>,
+    #[serde(default)]
+    pub payment_method: Option<String>,
+    #[serde(default)]
+    pub coupon_codes: Option<Vec<String>>,
 }
 
 /// Invoice filters
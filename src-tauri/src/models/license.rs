@@ -0,0 +1,46 @@
+//! License Models
+
+use serde::{Deserialize, Serialize};
+
+fn default_grace_days() -> u32 {
+    15
+}
+
+/// Signed license contents, issued by Equinox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LicensePayload {
+    pub license_id: String,
+    pub org_name: String,
+    pub org_tax_id: Option<String>, // RIF; checked against the local organization
+    pub plan: String,               // "starter", "pro", "enterprise"
+    pub modules: Vec<String>,       // Entitled modules ("invoicing", "accounting", ...)
+    pub seats: u32,                 // Maximum active users
+    pub hardware_id: Option<String>, // Required when fiscal modules are granted
+    pub issued_at: String,
+    pub expires_at: String,
+    #[serde(default = "default_grace_days")]
+    pub grace_days: u32,
+}
+
+/// Outcome of verifying the installed license
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LicenseStatusKind {
+    Valid,
+    Grace, // Expired but within the grace period; features keep working
+    Trial, // No license yet, within the trial seeded at upgrade; every module works
+    Expired,
+    Invalid, // Bad signature, wrong machine or wrong organization
+    Missing,
+}
+
+/// Current license state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LicenseState {
+    pub status: LicenseStatusKind,
+    pub license: Option<LicensePayload>,
+    pub grace_until: Option<String>,
+    pub days_remaining: Option<i64>,
+    pub active_users: i64,
+    pub message: Option<String>,
+}
@@ -28,6 +28,9 @@ pub struct CreateLotDto {
     pub cost_price: Option<f64>,
     pub expiration_date: Option<String>,
     pub received_date: Option<String>,
+    /// Receiving warehouse; the branch default if not given
+    #[serde(default)]
+    pub warehouse_id: Option<String>,
 }
 
 /// DTO for adjusting lot quantity
@@ -35,6 +38,9 @@ pub struct CreateLotDto {
 pub struct AdjustLotDto {
     pub quantity: f64,
     pub reason: Option<String>,
+    /// Warehouse to adjust; where the lot is stored if not given
+    #[serde(default)]
+    pub warehouse_id: Option<String>,
 }
 
 /// Lot filters
@@ -1,23 +1,34 @@
 //! Data Models Module
 
+pub mod audit;
+pub mod backup;
 pub mod bank_account;
+pub mod barcode;
+pub mod branch;
 pub mod cash_register;
 pub mod category;
 pub mod client;
 pub mod company_settings;
 pub mod discount;
+pub mod exchange_rate;
 pub mod installation;
+pub mod inventory_count;
 pub mod invoice;
+pub mod license;
 pub mod lot;
 pub mod payment;
 pub mod price_history;
 pub mod price_list;
+pub mod pricing;
 pub mod product;
 pub mod product_type;
+pub mod report;
+pub mod reservation;
 pub mod sync;
 pub mod tax_setting;
 pub mod unit;
 pub mod variant;
+pub mod warehouse;
 
 pub use bank_account::*;
 pub use category::*;
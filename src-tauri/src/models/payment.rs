@@ -17,6 +17,9 @@ pub struct Payment {
     pub bank_account_id: Option<String>, // Linked bank account
     pub payment_date: String,
     pub notes: Option<String>,
+    /// Register session the payment was taken at
+    #[serde(default)]
+    pub session_id: Option<String>,
     pub created_by: String,
     pub created_at: String,
 }
@@ -16,6 +16,12 @@ pub struct PriceHistory {
     pub changed_by: Option<String>,
     pub reason: Option<String>,
     pub created_at: String,
+    /// Set for list prices
+    #[serde(default)]
+    pub price_list_id: Option<String>,
+    /// Bulk change the entry belongs to
+    #[serde(default)]
+    pub batch_id: Option<String>,
 }
 
 /// Price history filters
@@ -26,4 +32,6 @@ pub struct PriceHistoryFilters {
     pub price_type: Option<String>,
     pub from_date: Option<String>,
     pub to_date: Option<String>,
+    #[serde(default)]
+    pub batch_id: Option<String>,
 }
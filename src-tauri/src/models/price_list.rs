@@ -15,6 +15,12 @@ pub struct PriceList {
     pub is_active: bool,
     pub created_at: String,
     pub updated_at: String,
+    /// Derived lists: base list price x exchange rate, rounded
+    pub base_price_list_id: Option<String>,
+    pub round_to: Option<f64>,
+    pub round_mode: Option<String>, // "nearest", "up", "down"
+    pub last_rate: Option<f64>,     // Conversion factor last applied
+    pub repriced_at: Option<String>,
 }
 
 /// Product Price - Specific price for a product in a price list
@@ -37,6 +43,12 @@ pub struct CreatePriceListDto {
     pub currency: String,
     pub discount_percent: f64,
     pub is_default: bool,
+    #[serde(default)]
+    pub base_price_list_id: Option<String>,
+    #[serde(default)]
+    pub round_to: Option<f64>,
+    #[serde(default)]
+    pub round_mode: Option<String>,
 }
 
 /// DTO for updating a price list
@@ -48,6 +60,13 @@ pub struct UpdatePriceListDto {
     pub discount_percent: Option<f64>,
     pub is_default: Option<bool>,
     pub is_active: Option<bool>,
+    /// Empty string stops deriving the list
+    #[serde(default)]
+    pub base_price_list_id: Option<String>,
+    #[serde(default)]
+    pub round_to: Option<f64>,
+    #[serde(default)]
+    pub round_mode: Option<String>,
 }
 
 /// DTO for setting a product price in a list
@@ -0,0 +1,114 @@
+//! Pricing Models
+
+use serde::{Deserialize, Serialize};
+
+/// What to price: one product or variant for a client, list, quantity,
+/// date and payment method
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PriceQuery {
+    pub client_id: Option<String>,
+    pub price_list_id: Option<String>, // Default list if not given
+    pub product_id: String,
+    pub variant_id: Option<String>,
+    pub quantity: f64,
+    pub date: Option<String>, // YYYY-MM-DD, today if not given
+    pub payment_method: Option<String>,
+    #[serde(default)]
+    pub time: Option<String>, // HH:MM, local time now if not given
+    #[serde(default)]
+    pub coupon_codes: Vec<String>,
+}
+
+/// A price or discount rule that contributed to a line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppliedPriceRule {
+    pub rule_type: String, // "base_price", "price_list", "list_discount", "discount"
+    pub rule_id: Option<String>,
+    pub name: String,
+    pub value: f64,  // Price, percentage or fixed amount
+    pub amount: f64, // Discount taken off the line (0 for prices)
+    #[serde(default)]
+    pub coupon_id: Option<String>, // Coupon that unlocked the discount
+}
+
+/// Resolved price, discounts and tax for one line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedPrice {
+    pub product_id: String,
+    pub variant_id: Option<String>,
+    pub price_list_id: Option<String>,
+    pub quantity: f64,
+    pub base_price: f64, // Product/variant sale price
+    pub unit_price: f64, // Price list price, before discounts
+    pub discount_amount: f64,
+    pub discount_percent: f64, // Effective, all discounts combined
+    pub tax_rate: f64,
+    pub subtotal: f64, // After discounts, before tax
+    pub tax_amount: f64,
+    pub total: f64,
+    pub rules: Vec<AppliedPriceRule>,
+}
+
+/// Products a bulk price change applies to
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct BulkPriceFilters {
+    pub category_id: Option<String>, // Includes subcategories
+    pub product_type_id: Option<String>,
+    pub supplier_reference: Option<String>,
+    pub price_list_id: Option<String>, // Change this list's prices instead of sale prices
+    #[serde(default)]
+    pub product_ids: Vec<String>,
+}
+
+/// Bulk price change: which prices, how, and why
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkRepriceDto {
+    #[serde(default)]
+    pub filters: BulkPriceFilters,
+    pub operation: String, // "percentage", "fixed", "cost_plus", "round"
+    #[serde(default)]
+    pub value: f64, // Percent, amount or margin over cost
+    pub round_to: Option<f64>, // e.g. 0.05, 1, 10
+    pub round_mode: Option<String>, // "nearest" (default), "up", "down"
+    pub reason: String,
+}
+
+/// One price a bulk change would set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceChange {
+    pub product_id: String,
+    #[serde(default)]
+    pub variant_id: Option<String>,
+    pub sku: Option<String>,
+    pub name: String,
+    pub price_list_id: Option<String>,
+    pub cost_price: f64,
+    pub old_price: Option<f64>, // None: the list had no price for it
+    pub new_price: f64,
+}
+
+/// An applied bulk price change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceBatch {
+    pub id: String,
+    pub price_list_id: Option<String>,
+    pub operation: String, // Bulk operations, or "exchange_rate" for derived lists
+    pub value: f64,        // For "exchange_rate", the conversion factor
+    pub round_to: Option<f64>,
+    pub round_mode: Option<String>,
+    pub reason: String,
+    pub items_count: i64,
+    pub status: String, // "applied", "undone", "partially_undone"
+    pub created_by: Option<String>,
+    pub created_at: String,
+    pub undone_by: Option<String>,
+    pub undone_at: Option<String>,
+}
+
+/// Result of undoing a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceBatchUndo {
+    pub batch: PriceBatch,
+    pub restored: usize,
+    pub skipped: usize, // Changed again since the batch; left as they are
+}
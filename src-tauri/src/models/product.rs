@@ -20,6 +20,10 @@ pub struct Product {
     pub margin_amount: f64,
     pub tax_rate: f64,
     pub stock_quantity: f64,
+    /// Held by draft invoices and accepted quotes
+    pub reserved_quantity: f64,
+    /// On hand minus reserved
+    pub available_quantity: f64,
     pub min_stock: f64,
     pub max_stock: f64,
     pub supplier_reference: Option<String>,
@@ -0,0 +1,100 @@
+//! Report Models
+
+use serde::{Deserialize, Serialize};
+
+/// Filters shared by every report
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ReportFilters {
+    pub from_date: Option<String>, // YYYY-MM-DD, inclusive
+    pub to_date: Option<String>,
+    /// Branches (tenants) to include; the current branch if empty
+    #[serde(default)]
+    pub branch_ids: Vec<String>,
+    /// Currency amounts are also converted to; the company default if not given
+    pub reporting_currency: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SalesGrouping {
+    Day,
+    Week, // ISO week, e.g. "2026-W42"
+    Month,
+    Product,
+    Category,
+    Client,
+    User,
+    Register,
+}
+
+/// Sales of one group in one invoice currency, with the same figures in
+/// the reporting currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SalesRow {
+    pub key: String,   // Date, week, month or id
+    pub label: String, // Name of the product, client, etc.
+    pub currency: String,
+    pub invoices: i64,
+    pub quantity: f64,
+    pub net: f64, // After discounts, before tax
+    pub tax: f64,
+    pub total: f64,
+    /// Quantity times the unit cost snapshotted on each line
+    pub cost: f64,
+    pub margin: f64,
+    pub margin_percent: f64, // Margin over net
+    pub reporting_currency: String,
+    pub net_reporting: f64,
+    pub tax_reporting: f64,
+    pub total_reporting: f64,
+    pub cost_reporting: f64,
+    pub margin_reporting: f64,
+}
+
+/// Tax collected at one rate in one invoice currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaxReportRow {
+    pub currency: String,
+    pub tax_rate: f64,
+    pub taxable: f64,
+    pub tax: f64,
+    pub reporting_currency: String,
+    pub taxable_reporting: f64,
+    pub tax_reporting: f64,
+}
+
+/// Payments taken with one method in one tender currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentMixRow {
+    pub payment_method: String,
+    pub currency: String,
+    pub payments: i64,
+    /// Tendered, in the payment currency
+    pub received: f64,
+    pub reporting_currency: String,
+    /// Applied to invoices, in the reporting currency
+    pub amount_reporting: f64,
+    pub share_percent: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TopProductsBy {
+    Revenue,
+    Quantity,
+    Margin,
+}
+
+/// A best-selling product across invoice currencies, in the reporting
+/// currency
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopProduct {
+    pub rank: usize,
+    pub product_id: String,
+    pub product_name: String,
+    pub quantity: f64,
+    pub invoices: i64,
+    pub reporting_currency: String,
+    pub net_reporting: f64,
+    pub margin_reporting: f64,
+}
@@ -0,0 +1,29 @@
+//! Stock Reservation Model
+
+use serde::{Deserialize, Serialize};
+
+/// Stock held for a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockReservation {
+    pub id: String,
+    pub warehouse_id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub variant_id: Option<String>,
+    pub quantity: f64,
+    pub reference_type: String,
+    pub reference_id: String,
+    /// Status: "active", "consumed", "released" or "expired"
+    pub status: String,
+    pub expires_at: String,
+    pub created_by: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ReservationFilters {
+    pub product_id: Option<String>,
+    pub variant_id: Option<String>,
+    pub reference_id: Option<String>,
+    pub status: Option<String>,
+}
@@ -3,11 +3,14 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncStatus {
-    pub last_sync: Option<DateTime<Utc>>,
+    pub last_sync: Option<DateTime<Utc>>, // Last successful sync
     pub pending_uploads: u32,
-    pub pending_downloads: u32,
+    pub pending_downloads: u32, // As of the last check for updates
+    pub failed_uploads: u32,    // Poisoned outbox entries
+    pub pending_conflicts: u32,
     pub is_syncing: bool,
     pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -27,13 +30,178 @@ pub struct SyncResult {
     pub errors: Vec<String>,
 }
 
+/// How a conflicting remote row is settled for an entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    LastWriterWins, // Newest change timestamp wins
+    LocalWins,      // Keep the local row; it is uploaded on the next sync
+    FieldMerge,     // Three-way merge per column; overlapping edits go to review
+    Manual,         // Keep local and queue for review
+    AppendOnly,     // Fiscal records: existing rows are never overwritten
+}
+
+impl ConflictPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LastWriterWins => "last_writer_wins",
+            Self::LocalWins => "local_wins",
+            Self::FieldMerge => "field_merge",
+            Self::Manual => "manual",
+            Self::AppendOnly => "append_only",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "last_writer_wins" => Some(Self::LastWriterWins),
+            "local_wins" => Some(Self::LocalWins),
+            "field_merge" => Some(Self::FieldMerge),
+            "manual" => Some(Self::Manual),
+            "append_only" => Some(Self::AppendOnly),
+            _ => None,
+        }
+    }
+}
+
+/// Effective conflict policy of a synced entity
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[allow(dead_code)]
-pub struct ConflictResolution {
-    pub table: String,
-    pub local_id: String,
-    pub remote_id: String,
-    pub resolution: String, // "local", "remote"
+pub struct EntitySyncPolicy {
+    pub entity: String,
+    pub policy: ConflictPolicy,
+    pub default_policy: ConflictPolicy,
+    pub overridden: bool,
+}
+
+/// A conflict detected while applying remote changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConflict {
+    pub id: String,
+    pub tenant_id: Option<String>,
+    pub entity: String,
+    pub row_id: String,
+    pub policy: String,
+    pub local_data: serde_json::Value,
+    pub remote_data: serde_json::Value,
+    pub conflicting_fields: Vec<String>,
+    pub status: String, // "pending", "resolved_local", "resolved_remote", "resolved_merged"
+    pub detected_at: String,
+    pub resolved_at: Option<String>,
+    pub resolved_by: Option<String>,
+}
+
+/// How the user settles a pending conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictResolution {
+    Local,
+    Remote,
+    Merged,
+}
+
+/// Payload of the `sync://progress` and `sync://error` events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncProgressEvent {
+    pub phase: String,   // "started", "completed", "failed", "offline", "online"
+    pub trigger: String, // "interval", "commit", "manual"
+    pub downloaded: i32,
+    pub uploaded: i32,
+    pub error: Option<String>,
+}
+
+impl SyncProgressEvent {
+    pub fn new(phase: &str, trigger: &str) -> Self {
+        Self {
+            phase: phase.to_string(),
+            trigger: trigger.to_string(),
+            downloaded: 0,
+            uploaded: 0,
+            error: None,
+        }
+    }
+}
+
+/// Outbox entry shown for failed uploads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxItem {
+    pub id: i64,
+    pub entity: String,
+    pub row_id: String,
+    pub operation: String, // "upsert", "delete"
+    pub attempts: i64,
+    pub status: String, // "pending", "poisoned"
+    pub last_error: Option<String>,
+    pub next_attempt_at: String,
+    pub created_at: String,
+}
+
+/// Where the sync engine sends and fetches rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncBackendKind {
+    #[default]
+    Disabled,
+    Supabase,      // Supabase / PostgREST project
+    EquinoxServer, // Self-hosted equinox-sync-server
+}
+
+/// Sync backend settings (credentials are never returned)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncBackendSettings {
+    pub kind: SyncBackendKind,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub has_credentials: bool,
+    pub updated_at: Option<String>,
+    pub updated_by: Option<String>,
+    #[serde(default)]
+    pub device_id: Option<String>, // This installation's device at the backend
+}
+
+/// DTO for changing the sync backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateSyncBackendDto {
+    pub kind: SyncBackendKind,
+    pub url: Option<String>,
+    pub api_key: Option<String>, // None keeps the stored credential
+    /// One-time code to register this installation into an existing
+    /// organization (Supabase); the sync server enrolls with its token
+    #[serde(default)]
+    pub enrollment_token: Option<String>,
+}
+
+/// Sent once per installation to obtain device credentials
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceRegistration {
+    pub org_id: String,
+    pub org_name: String,
+    pub tenant_id: String,
+    pub tenant_name: String,
+    pub hardware_id: String,
+    pub device_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enrollment_token: Option<String>,
+}
+
+/// Tokens issued by the backend; `refresh_token` only comes with a registration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceCredentials {
+    pub device_id: String,
+    pub access_token: String,
+    pub expires_at: String,
+    pub refresh_token: Option<String>,
+}
+
+/// A device registered for the tenant at the sync backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncDevice {
+    pub id: String,
+    pub tenant_id: String,
+    pub device_name: String,
+    pub hardware_id: String,
+    pub created_at: String,
+    pub last_seen_at: Option<String>,
+    pub revoked_at: Option<String>,
 }
 
 // Sync Models for Supabase Installation Registration
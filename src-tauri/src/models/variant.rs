@@ -16,6 +16,10 @@ pub struct ProductVariant {
     pub sale_price: f64,
     pub barcode: Option<String>,
     pub stock_quantity: f64,
+    /// Held by draft invoices and accepted quotes
+    pub reserved_quantity: f64,
+    /// On hand minus reserved
+    pub available_quantity: f64,
     pub is_active: bool,
     pub created_at: String,
     pub updated_at: String,
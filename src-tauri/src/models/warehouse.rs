@@ -0,0 +1,109 @@
+//! Warehouse Model
+
+use serde::{Deserialize, Serialize};
+
+/// Warehouse within a branch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Warehouse {
+    pub id: String,
+    pub tenant_id: String,
+    pub code: String,
+    pub name: String,
+    /// Used when a document does not pick a warehouse
+    pub is_default: bool,
+    pub is_active: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWarehouseDto {
+    pub code: String,
+    pub name: String,
+    pub is_default: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateWarehouseDto {
+    pub name: Option<String>,
+    pub is_default: Option<bool>,
+    pub is_active: Option<bool>,
+}
+
+/// Stock of a product (variant, lot) in a warehouse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarehouseStock {
+    pub id: String,
+    pub warehouse_id: String,
+    pub warehouse_name: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub sku: Option<String>,
+    pub variant_id: Option<String>,
+    pub variant_name: Option<String>,
+    pub lot_id: Option<String>,
+    pub lot_number: Option<String>,
+    pub quantity: f64,
+    pub min_stock: Option<f64>,
+    pub bin_location: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct WarehouseStockFilters {
+    pub warehouse_id: Option<String>,
+    pub product_id: Option<String>,
+    /// Include rows with zero quantity
+    pub include_empty: Option<bool>,
+}
+
+/// Bin location and minimum of a product in a warehouse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetStockLocationDto {
+    pub warehouse_id: String,
+    pub product_id: String,
+    pub variant_id: Option<String>,
+    pub lot_id: Option<String>,
+    pub bin_location: Option<String>,
+    pub min_stock: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarehouseTransferItem {
+    pub product_id: String,
+    pub variant_id: Option<String>,
+    pub lot_id: Option<String>,
+    pub quantity: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWarehouseTransferDto {
+    pub from_warehouse_id: String,
+    pub to_warehouse_id: String,
+    pub items: Vec<WarehouseTransferItem>,
+    pub notes: Option<String>,
+}
+
+/// Move between warehouses of the same branch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarehouseTransfer {
+    pub id: String,
+    pub tenant_id: String,
+    pub from_warehouse_id: String,
+    pub to_warehouse_id: String,
+    pub notes: Option<String>,
+    pub created_by: String,
+    pub created_at: String,
+    pub items: Vec<WarehouseTransferItem>,
+}
+
+/// Product at or below its minimum in a warehouse
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LowStockItem {
+    pub warehouse_id: String,
+    pub warehouse_name: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub sku: Option<String>,
+    pub quantity: f64,
+    pub min_stock: f64,
+}
@@ -3,7 +3,18 @@
 //! Immutable event logging for SENIAT compliance.
 
 use chrono::Utc;
-use rusqlite::{params, Connection};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::rngs::OsRng;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::models::audit::{
+    AuditEvent, AuditEventFilters, AuditEventPage, AuditExportPayload, SignedAuditExport,
+};
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+const SIGNING_KEY_METADATA: &str = "audit_signing_key";
 
 /// Audit event types
 #[derive(Debug, Clone, Copy)]
@@ -24,6 +35,52 @@ pub enum AuditEventType {
     SystemTimeAnomaly,
     DatabaseBackup,
     ChainIntegrityCheck,
+    FiscalDocumentUpdated,
+    FiscalDocumentCancelled,
+    FiscalDocumentDeleted,
+    PaymentRegistered,
+    PaymentDeleted,
+    ProductCreated,
+    ProductUpdated,
+    ProductDeleted,
+    ProductRestored,
+    PriceListCreated,
+    PriceListUpdated,
+    PriceListDeleted,
+    ProductPriceSet,
+    ProductPriceDeleted,
+    SettingsUpdated,
+    BankAccountCreated,
+    BankAccountUpdated,
+    BankAccountDeleted,
+    TaxSettingCreated,
+    TaxSettingUpdated,
+    TaxSettingDeleted,
+    InvoiceSequenceChanged,
+    AuditExported,
+    TimeIntegrityRecovered,
+    DatabaseRestored,
+    KeyRecoveryConfigured,
+    DatabaseKeyRotated,
+    LicenseInstalled,
+    SyncConflictResolved,
+    SyncPolicyChanged,
+    SyncBackendChanged,
+    SyncDeviceRegistered,
+    SyncDeviceRevoked,
+    BranchCreated,
+    BranchAccessChanged,
+    TenantSwitched,
+    StockTransferShipped,
+    StockTransferReceived,
+    StockTransferCancelled,
+    WarehouseCreated,
+    WarehouseUpdated,
+    WarehouseTransferCreated,
+    PriceBatchApplied,
+    PriceBatchUndone,
+    ExchangeRateRecorded,
+    PosSaleCompleted,
 }
 
 impl AuditEventType {
@@ -44,6 +101,52 @@ impl AuditEventType {
             Self::SystemTimeAnomaly => "SYSTEM_TIME_ANOMALY",
             Self::DatabaseBackup => "DATABASE_BACKUP",
             Self::ChainIntegrityCheck => "CHAIN_INTEGRITY_CHECK",
+            Self::FiscalDocumentUpdated => "FISCAL_DOC_UPDATED",
+            Self::FiscalDocumentCancelled => "FISCAL_DOC_CANCELLED",
+            Self::FiscalDocumentDeleted => "FISCAL_DOC_DELETED",
+            Self::PaymentRegistered => "PAYMENT_REGISTERED",
+            Self::PaymentDeleted => "PAYMENT_DELETED",
+            Self::ProductCreated => "PRODUCT_CREATED",
+            Self::ProductUpdated => "PRODUCT_UPDATED",
+            Self::ProductDeleted => "PRODUCT_DELETED",
+            Self::ProductRestored => "PRODUCT_RESTORED",
+            Self::PriceListCreated => "PRICE_LIST_CREATED",
+            Self::PriceListUpdated => "PRICE_LIST_UPDATED",
+            Self::PriceListDeleted => "PRICE_LIST_DELETED",
+            Self::ProductPriceSet => "PRODUCT_PRICE_SET",
+            Self::ProductPriceDeleted => "PRODUCT_PRICE_DELETED",
+            Self::SettingsUpdated => "SETTINGS_UPDATED",
+            Self::BankAccountCreated => "BANK_ACCOUNT_CREATED",
+            Self::BankAccountUpdated => "BANK_ACCOUNT_UPDATED",
+            Self::BankAccountDeleted => "BANK_ACCOUNT_DELETED",
+            Self::TaxSettingCreated => "TAX_SETTING_CREATED",
+            Self::TaxSettingUpdated => "TAX_SETTING_UPDATED",
+            Self::TaxSettingDeleted => "TAX_SETTING_DELETED",
+            Self::InvoiceSequenceChanged => "INVOICE_SEQUENCE_CHANGED",
+            Self::AuditExported => "AUDIT_EXPORTED",
+            Self::TimeIntegrityRecovered => "TIME_INTEGRITY_RECOVERED",
+            Self::DatabaseRestored => "DATABASE_RESTORED",
+            Self::KeyRecoveryConfigured => "KEY_RECOVERY_CONFIGURED",
+            Self::DatabaseKeyRotated => "DATABASE_KEY_ROTATED",
+            Self::LicenseInstalled => "LICENSE_INSTALLED",
+            Self::SyncConflictResolved => "SYNC_CONFLICT_RESOLVED",
+            Self::SyncPolicyChanged => "SYNC_POLICY_CHANGED",
+            Self::SyncBackendChanged => "SYNC_BACKEND_CHANGED",
+            Self::SyncDeviceRegistered => "SYNC_DEVICE_REGISTERED",
+            Self::SyncDeviceRevoked => "SYNC_DEVICE_REVOKED",
+            Self::BranchCreated => "BRANCH_CREATED",
+            Self::BranchAccessChanged => "BRANCH_ACCESS_CHANGED",
+            Self::TenantSwitched => "TENANT_SWITCHED",
+            Self::StockTransferShipped => "STOCK_TRANSFER_SHIPPED",
+            Self::StockTransferReceived => "STOCK_TRANSFER_RECEIVED",
+            Self::StockTransferCancelled => "STOCK_TRANSFER_CANCELLED",
+            Self::WarehouseCreated => "WAREHOUSE_CREATED",
+            Self::WarehouseUpdated => "WAREHOUSE_UPDATED",
+            Self::WarehouseTransferCreated => "WAREHOUSE_TRANSFER_CREATED",
+            Self::PriceBatchApplied => "PRICE_BATCH_APPLIED",
+            Self::PriceBatchUndone => "PRICE_BATCH_UNDONE",
+            Self::ExchangeRateRecorded => "EXCHANGE_RATE_RECORDED",
+            Self::PosSaleCompleted => "POS_SALE_COMPLETED",
         }
     }
 }
@@ -98,3 +201,363 @@ pub fn log_login(
 
     log_event(conn, None, None, event_type, Some("user"), None, &details)
 }
+
+/// Build the WHERE clause for audit queries, scoped to an organization.
+///
+/// Events without tenant (logins, startup) are visible to every org on this
+/// installation.
+fn build_filter(org_id: &str, filters: &AuditEventFilters) -> (String, Vec<String>) {
+    let mut conditions = vec![
+        "(a.tenant_id IS NULL OR a.tenant_id IN (SELECT id FROM tenants WHERE org_id = ?1))"
+            .to_string(),
+    ];
+    let mut params: Vec<String> = vec![org_id.to_string()];
+
+    if let Some(ref from) = filters.from_date {
+        params.push(from.clone());
+        conditions.push(format!("a.timestamp >= ?{}", params.len()));
+    }
+    if let Some(ref to) = filters.to_date {
+        // A bare date includes the whole day, so compare only as many
+        // characters of the RFC 3339 timestamp as the bound has
+        params.push(to.clone());
+        conditions.push(format!(
+            "substr(a.timestamp, 1, length(?{n})) <= ?{n}",
+            n = params.len()
+        ));
+    }
+    if let Some(ref u) = filters.user_id {
+        params.push(u.clone());
+        conditions.push(format!("a.user_id = ?{}", params.len()));
+    }
+    if let Some(ref e) = filters.event_type {
+        params.push(e.clone());
+        conditions.push(format!("a.event_type = ?{}", params.len()));
+    }
+    if let Some(ref t) = filters.entity_type {
+        params.push(t.clone());
+        conditions.push(format!("a.entity_type = ?{}", params.len()));
+    }
+    if let Some(ref id) = filters.entity_id {
+        params.push(id.clone());
+        conditions.push(format!("a.entity_id = ?{}", params.len()));
+    }
+    if let Some(ref t) = filters.tenant_id {
+        params.push(t.clone());
+        conditions.push(format!("a.tenant_id = ?{}", params.len()));
+    }
+
+    (conditions.join(" AND "), params)
+}
+
+fn select_events(
+    conn: &Connection,
+    where_clause: &str,
+    params: &[String],
+    limit: Option<(u32, u32)>,
+) -> Result<Vec<AuditEvent>, String> {
+    let limit_clause = match limit {
+        Some((page_size, offset)) => format!("LIMIT {} OFFSET {}", page_size, offset),
+        None => String::new(),
+    };
+
+    let query = format!(
+        "SELECT a.id, a.tenant_id, a.user_id, u.name, a.event_type, a.entity_type, a.entity_id,
+                a.details, a.timestamp
+         FROM audit_logs a
+         LEFT JOIN users u ON u.id = a.user_id
+         WHERE {}
+         ORDER BY a.id DESC {}",
+        where_clause, limit_clause
+    );
+
+    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
+
+    let events = stmt
+        .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+            Ok(AuditEvent {
+                id: row.get(0)?,
+                tenant_id: row.get(1)?,
+                user_id: row.get(2)?,
+                user_name: row.get(3)?,
+                event_type: row.get(4)?,
+                entity_type: row.get(5)?,
+                entity_id: row.get(6)?,
+                details: row.get(7)?,
+                timestamp: row.get(8)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(events)
+}
+
+/// List audit events for an organization, newest first
+pub fn list_events(
+    conn: &Connection,
+    org_id: &str,
+    filters: &AuditEventFilters,
+) -> Result<AuditEventPage, String> {
+    let page = filters.page.unwrap_or(1).max(1);
+    let page_size = filters
+        .page_size
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let (where_clause, params) = build_filter(org_id, filters);
+
+    let total: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM audit_logs a WHERE {}", where_clause),
+            rusqlite::params_from_iter(params.iter()),
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let items = select_events(
+        conn,
+        &where_clause,
+        &params,
+        Some((page_size, (page - 1) * page_size)),
+    )?;
+
+    Ok(AuditEventPage {
+        items,
+        total,
+        page,
+        page_size,
+    })
+}
+
+/// All audit events matching the filters (pagination ignored), for export
+pub fn export_events(
+    conn: &Connection,
+    org_id: &str,
+    filters: &AuditEventFilters,
+) -> Result<Vec<AuditEvent>, String> {
+    let (where_clause, params) = build_filter(org_id, filters);
+    select_events(conn, &where_clause, &params, None)
+}
+
+/// Escape `%`, `_` and the escape character itself for a `LIKE ... ESCAPE '\'`
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Every event touching an entity, oldest first.
+///
+/// Includes events logged against the entity itself and events on related
+/// entities that reference it in their details (e.g. payments of an invoice).
+pub fn entity_history(
+    conn: &Connection,
+    org_id: &str,
+    entity_type: &str,
+    entity_id: &str,
+) -> Result<Vec<AuditEvent>, String> {
+    let (where_clause, mut params) = build_filter(org_id, &AuditEventFilters::default());
+
+    params.push(entity_type.to_string());
+    let type_idx = params.len();
+    params.push(entity_id.to_string());
+    let id_idx = params.len();
+    params.push(format!("%{}%", escape_like(entity_id)));
+    let like_idx = params.len();
+
+    let where_clause = format!(
+        "{} AND ((a.entity_type = ?{t} AND a.entity_id = ?{i}) OR a.details LIKE ?{l} ESCAPE '\\')",
+        where_clause,
+        t = type_idx,
+        i = id_idx,
+        l = like_idx
+    );
+
+    let mut events = select_events(conn, &where_clause, &params, None)?;
+    events.reverse();
+    Ok(events)
+}
+
+/// Escape a CSV field (RFC 4180), neutralizing spreadsheet formulas
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+/// Render audit events as CSV
+pub fn events_to_csv(events: &[AuditEvent]) -> String {
+    let mut out = String::from(
+        "id,timestamp,tenant_id,user_id,user_name,event_type,entity_type,entity_id,details\r\n",
+    );
+
+    for e in events {
+        let fields = [
+            e.id.to_string(),
+            e.timestamp.clone(),
+            e.tenant_id.clone().unwrap_or_default(),
+            e.user_id.clone().unwrap_or_default(),
+            e.user_name.clone().unwrap_or_default(),
+            e.event_type.clone(),
+            e.entity_type.clone().unwrap_or_default(),
+            e.entity_id.clone().unwrap_or_default(),
+            e.details.clone().unwrap_or_default(),
+        ];
+        let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&line.join(","));
+        out.push_str("\r\n");
+    }
+
+    out
+}
+
+/// Get (or create on first use) this installation's audit signing key
+fn get_signing_key(conn: &Connection) -> Result<SigningKey, String> {
+    let stored: Option<String> = conn
+        .query_row(
+            "SELECT value FROM security_metadata WHERE key = ?1",
+            [SIGNING_KEY_METADATA],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    if let Some(hex_key) = stored {
+        let bytes: [u8; 32] = hex::decode(hex_key)
+            .map_err(|e| e.to_string())?
+            .try_into()
+            .map_err(|_| "Invalid audit signing key".to_string())?;
+        return Ok(SigningKey::from_bytes(&bytes));
+    }
+
+    let key = SigningKey::generate(&mut OsRng);
+    conn.execute(
+        "INSERT INTO security_metadata (key, value) VALUES (?1, ?2)",
+        params![SIGNING_KEY_METADATA, hex::encode(key.to_bytes())],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(key)
+}
+
+/// Hex-encoded public half of the audit signing key
+pub fn public_key(conn: &Connection) -> Result<String, String> {
+    Ok(hex::encode(
+        get_signing_key(conn)?.verifying_key().to_bytes(),
+    ))
+}
+
+/// Sign an export payload with the installation's audit key
+pub fn sign_export(
+    conn: &Connection,
+    payload: AuditExportPayload,
+) -> Result<SignedAuditExport, String> {
+    let key = get_signing_key(conn)?;
+    let bytes = serde_json::to_vec(&payload).map_err(|e| e.to_string())?;
+
+    Ok(SignedAuditExport {
+        algorithm: "Ed25519".to_string(),
+        public_key: hex::encode(key.verifying_key().to_bytes()),
+        payload_sha256: hex::encode(Sha256::digest(&bytes)),
+        signature: hex::encode(key.sign(&bytes).to_bytes()),
+        payload,
+    })
+}
+
+/// Verify the signature and digest of a signed export
+pub fn verify_export(export: &SignedAuditExport) -> Result<bool, String> {
+    let bytes = serde_json::to_vec(&export.payload).map_err(|e| e.to_string())?;
+
+    if hex::encode(Sha256::digest(&bytes)) != export.payload_sha256 {
+        return Ok(false);
+    }
+
+    let public_key: [u8; 32] = hex::decode(&export.public_key)
+        .map_err(|e| e.to_string())?
+        .try_into()
+        .map_err(|_| "Invalid public key".to_string())?;
+    let signature: [u8; 64] = hex::decode(&export.signature)
+        .map_err(|e| e.to_string())?
+        .try_into()
+        .map_err(|_| "Invalid signature".to_string())?;
+
+    let verifying_key = VerifyingKey::from_bytes(&public_key).map_err(|e| e.to_string())?;
+
+    Ok(verifying_key
+        .verify(&bytes, &Signature::from_bytes(&signature))
+        .is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_escaping() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("=SUM(A1)"), "'=SUM(A1)");
+    }
+
+    #[test]
+    fn test_entity_history_and_date_filter() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO audit_logs (tenant_id, event_type, entity_type, entity_id, details, timestamp)
+                 VALUES ('t1', 'invoice_issued', 'invoice', 'a_1', 'issued', '2026-03-01T18:30:00+00:00'),
+                        ('t1', 'payment_registered', 'payment', 'p1', 'invoice=a_1', '2026-03-02T09:00:00+00:00'),
+                        ('t1', 'payment_registered', 'payment', 'p2', 'invoice=ab1', '2026-03-02T09:00:00+00:00');",
+        )
+        .unwrap();
+
+        // `_` matches itself only
+        let history = entity_history(&conn, "o1", "invoice", "a_1").unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(entity_history(&conn, "o1", "invoice", "%").unwrap().is_empty());
+
+        // A bare end date keeps the events later that day
+        let filters = AuditEventFilters {
+            to_date: Some("2026-03-01".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(list_events(&conn, "o1", &filters).unwrap().total, 1);
+    }
+
+    #[test]
+    fn test_signed_export_roundtrip() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE security_metadata (key TEXT PRIMARY KEY, value TEXT NOT NULL);",
+        )
+        .unwrap();
+
+        let payload = AuditExportPayload {
+            generated_at: Utc::now().to_rfc3339(),
+            generated_by: "admin".to_string(),
+            org_id: "org".to_string(),
+            filters: AuditEventFilters::default(),
+            events: vec![],
+        };
+
+        let mut export = sign_export(&conn, payload).unwrap();
+        assert!(verify_export(&export).unwrap());
+
+        export.payload.generated_by = "someone else".to_string();
+        assert!(!verify_export(&export).unwrap());
+    }
+}
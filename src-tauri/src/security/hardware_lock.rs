@@ -4,10 +4,10 @@
 
 use sha2::{Digest, Sha256};
 
-/// Get the current hardware fingerprint
-pub fn get_hardware_fingerprint() -> String {
-    let machine_id = machine_uid::get().unwrap_or_else(|_| "fallback".to_string());
+/// Modules that require a hardware-bound license
+pub const FISCAL_MODULES: &[&str] = &["invoicing", "accounting", "fiscal_reports"];
 
+fn fingerprint_for(machine_id: &str) -> String {
     let salt = "equinox-hw-v1";
     let raw = format!("{}{}", machine_id, salt);
 
@@ -16,13 +16,25 @@ pub fn get_hardware_fingerprint() -> String {
     hex::encode(hasher.finalize())
 }
 
-/// Verify if the current hardware matches the expected ID
+/// Hardware fingerprint, or None when the machine id is unavailable
+pub fn machine_fingerprint() -> Option<String> {
+    machine_uid::get().ok().map(|id| fingerprint_for(&id))
+}
+
+/// Get the current hardware fingerprint
+pub fn get_hardware_fingerprint() -> String {
+    machine_fingerprint().unwrap_or_else(|| fingerprint_for("fallback"))
+}
+
+/// Verify if the current hardware matches the expected ID.
+///
+/// Machines without a readable id never match, so the shared fallback
+/// fingerprint cannot be used to move a license between machines.
 pub fn verify_hardware_lock(expected_hardware_id: &str) -> bool {
-    get_hardware_fingerprint() == expected_hardware_id
+    machine_fingerprint().is_some_and(|id| id == expected_hardware_id)
 }
 
 /// Helper to identify fiscal modules
-#[allow(dead_code)]
-fn is_fiscal_module(device_name: &str) -> bool {
-    matches!(device_name, "invoicing" | "accounting" | "fiscal_reports")
+pub fn is_fiscal_module(device_name: &str) -> bool {
+    FISCAL_MODULES.contains(&device_name)
 }
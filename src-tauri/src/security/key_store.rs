@@ -0,0 +1,478 @@
+//! Key Store
+//!
+//! Key hierarchy for the SQLCipher database. A random data key encrypts the
+//! database; it is stored next to the database file wrapped twice: by the
+//! hardware key-encryption key of this machine and, optionally, by a key
+//! derived from an admin recovery code or passphrase. Losing the hardware
+//! (motherboard change, OS reinstall) only requires the recovery secret.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use chrono::Utc;
+use rand::{Rng, RngCore};
+use rusqlite::backup::Backup;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use super::SecureString;
+
+const KEYRING_EXTENSION: &str = "keys";
+const RECOVERY_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+const OTHER_MACHINE_ERROR: &str = "La base de datos fue cifrada en otro equipo. \
+     Use la migración con su código de recuperación.";
+
+type DataKey = Zeroizing<[u8; 32]>;
+
+/// A data key encrypted with AES-256-GCM under a key-encryption key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WrappedKey {
+    kdf: String, // "hardware" | "argon2id"
+    salt: Option<String>,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Key ring file stored next to the database (`<db>.keys`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyRing {
+    version: u32,
+    key_id: String,
+    created_at: String,
+    rotated_at: Option<String>,
+    hardware: WrappedKey,
+    recovery: Option<WrappedKey>,
+    /// Previous key while a rotation is in progress
+    previous: Option<WrappedKey>,
+}
+
+/// Key hierarchy status shown to admins
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyStatus {
+    pub key_id: String,
+    pub created_at: String,
+    pub rotated_at: Option<String>,
+    pub has_recovery: bool,
+    pub uses_device_id: bool,
+}
+
+/// Key ring path for a database file
+pub fn keyring_path(db_path: &Path) -> PathBuf {
+    let mut name = db_path.as_os_str().to_owned();
+    name.push(".");
+    name.push(KEYRING_EXTENSION);
+    PathBuf::from(name)
+}
+
+fn connection_keyring_path(conn: &Connection) -> Result<PathBuf, String> {
+    conn.path()
+        .filter(|p| !p.is_empty())
+        .map(|p| keyring_path(Path::new(p)))
+        .ok_or_else(|| "La base de datos no tiene ruta en disco".to_string())
+}
+
+fn load(path: &Path) -> Result<KeyRing, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("Error al leer llavero: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Llavero inválido: {}", e))
+}
+
+/// Write atomically so a crash never leaves a half-written key ring
+fn save(path: &Path, ring: &KeyRing) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(ring).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("keys.tmp");
+    std::fs::write(&tmp, json).map_err(|e| format!("Error al guardar llavero: {}", e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Error al guardar llavero: {}", e))
+}
+
+fn new_data_key() -> DataKey {
+    let mut key = Zeroizing::new([0u8; 32]);
+    rand::rngs::OsRng.fill_bytes(key.as_mut());
+    key
+}
+
+/// SQLCipher raw key pragma value
+fn key_pragma(key: &DataKey) -> SecureString {
+    SecureString::new(format!("x'{}'", hex::encode(&key[..])))
+}
+
+fn recovery_kek(secret: &str, salt: &[u8]) -> Result<DataKey, String> {
+    let mut kek = Zeroizing::new([0u8; 32]);
+    Argon2::default()
+        .hash_password_into(secret.trim().as_bytes(), salt, kek.as_mut())
+        .map_err(|e| format!("Error derivando clave: {}", e))?;
+    Ok(kek)
+}
+
+fn wrap(
+    kek: &[u8; 32],
+    key: &DataKey,
+    kdf: &str,
+    salt: Option<&[u8]>,
+) -> Result<WrappedKey, String> {
+    let cipher = Aes256Gcm::new_from_slice(kek).map_err(|e| e.to_string())?;
+    let mut nonce = [0u8; 12];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), &key[..])
+        .map_err(|_| "Error al cifrar la clave de datos".to_string())?;
+
+    Ok(WrappedKey {
+        kdf: kdf.to_string(),
+        salt: salt.map(hex::encode),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+fn unwrap(kek: &[u8; 32], wrapped: &WrappedKey) -> Option<DataKey> {
+    let cipher = Aes256Gcm::new_from_slice(kek).ok()?;
+    let nonce = hex::decode(&wrapped.nonce).ok()?;
+    let ciphertext = hex::decode(&wrapped.ciphertext).ok()?;
+    if nonce.len() != 12 {
+        return None;
+    }
+
+    let plain = Zeroizing::new(
+        cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .ok()?,
+    );
+    let mut key = Zeroizing::new([0u8; 32]);
+    if plain.len() != key.len() {
+        return None;
+    }
+    key.copy_from_slice(&plain);
+    Some(key)
+}
+
+fn wrap_recovery(key: &DataKey, secret: &str) -> Result<WrappedKey, String> {
+    if secret.trim().chars().count() < 12 {
+        return Err("La frase de recuperación debe tener al menos 12 caracteres".to_string());
+    }
+    let mut salt = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut salt);
+    let kek = recovery_kek(secret, &salt)?;
+    wrap(&kek, key, "argon2id", Some(&salt))
+}
+
+fn unwrap_recovery(ring: &KeyRing, secret: &str) -> Result<DataKey, String> {
+    let wrapped = ring
+        .recovery
+        .as_ref()
+        .ok_or_else(|| "No hay código de recuperación configurado".to_string())?;
+    let salt = wrapped
+        .salt
+        .as_deref()
+        .and_then(|s| hex::decode(s).ok())
+        .ok_or_else(|| "Llavero inválido: salt".to_string())?;
+
+    let kek = recovery_kek(secret, &salt)?;
+    unwrap(&kek, wrapped).ok_or_else(|| "Código de recuperación incorrecto".to_string())
+}
+
+/// Open a connection and check the key decrypts it
+fn open_with_key(db_path: &Path, key: &str) -> Option<Connection> {
+    let conn = Connection::open(db_path).ok()?;
+    conn.pragma_update(None, "key", key).ok()?;
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| {
+        row.get::<_, i64>(0)
+    })
+    .ok()?;
+    Some(conn)
+}
+
+/// Re-encrypt an open database with a new key
+fn rekey(conn: &Connection, key: &DataKey) -> Result<(), String> {
+    // SQLCipher cannot rekey a database in WAL mode
+    let journal: String = conn
+        .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if journal.eq_ignore_ascii_case("wal") {
+        conn.pragma_update(None, "journal_mode", "DELETE")
+            .map_err(|e| e.to_string())?;
+    }
+
+    conn.pragma_update(None, "rekey", key_pragma(key).as_str())
+        .map_err(|e| format!("Error al cambiar la clave: {}", e))?;
+
+    if journal.eq_ignore_ascii_case("wal") {
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Open the encrypted database at `db_path`.
+///
+/// Creates the key ring for new databases, migrates databases keyed directly
+/// from hardware (`legacy_keys`) to a random data key, and finishes a key
+/// rotation interrupted by a crash.
+pub fn open_database(
+    db_path: &Path,
+    kek: &[u8; 32],
+    legacy_keys: &[SecureString],
+) -> Result<Connection, String> {
+    let ring_path = keyring_path(db_path);
+
+    if ring_path.exists() {
+        let mut ring = load(&ring_path)?;
+        let key = unwrap(kek, &ring.hardware).ok_or_else(|| OTHER_MACHINE_ERROR.to_string())?;
+
+        if let Some(conn) = open_with_key(db_path, key_pragma(&key).as_str()) {
+            if ring.previous.take().is_some() {
+                save(&ring_path, &ring)?;
+            }
+            return Ok(conn);
+        }
+
+        // Rotation or legacy migration interrupted before the rekey finished
+        let previous = ring
+            .previous
+            .as_ref()
+            .and_then(|w| unwrap(kek, w))
+            .map(|k| key_pragma(&k));
+        for old in previous.iter().chain(legacy_keys) {
+            if let Some(conn) = open_with_key(db_path, old.as_str()) {
+                rekey(&conn, &key)?;
+                ring.previous = None;
+                save(&ring_path, &ring)?;
+                return Ok(conn);
+            }
+        }
+
+        return Err("No se pudo descifrar la base de datos con el llavero".to_string());
+    }
+
+    let key = new_data_key();
+    let is_new = std::fs::metadata(db_path)
+        .map(|m| m.len() == 0)
+        .unwrap_or(true);
+
+    let ring = KeyRing {
+        version: 1,
+        key_id: uuid::Uuid::new_v4().to_string(),
+        created_at: Utc::now().to_rfc3339(),
+        rotated_at: None,
+        hardware: wrap(kek, &key, "hardware", None)?,
+        recovery: None,
+        previous: None,
+    };
+
+    if is_new {
+        save(&ring_path, &ring)?;
+        let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "key", key_pragma(&key).as_str())
+            .map_err(|e| e.to_string())?;
+        return Ok(conn);
+    }
+
+    // Existing database keyed from hardware: move it to the data key
+    let conn = legacy_keys
+        .iter()
+        .find_map(|k| open_with_key(db_path, k.as_str()))
+        .ok_or_else(|| OTHER_MACHINE_ERROR.to_string())?;
+
+    // Key ring first: if we crash mid-rekey the next start finishes it
+    save(&ring_path, &ring)?;
+    rekey(&conn, &key)?;
+
+    println!("🔐 Database migrated to key hierarchy");
+    Ok(conn)
+}
+
+/// Current key hierarchy status for the open database
+pub fn status(conn: &Connection, uses_device_id: bool) -> Result<KeyStatus, String> {
+    let ring = load(&connection_keyring_path(conn)?)?;
+    Ok(KeyStatus {
+        key_id: ring.key_id,
+        created_at: ring.created_at,
+        rotated_at: ring.rotated_at,
+        has_recovery: ring.recovery.is_some(),
+        uses_device_id,
+    })
+}
+
+/// Generate a random recovery code (`XXXXX-XXXXX-XXXXX-XXXXX-XXXXX`)
+pub fn generate_recovery_code() -> String {
+    let mut rng = rand::rngs::OsRng;
+    (0..5)
+        .map(|_| {
+            (0..5)
+                .map(|_| {
+                    RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Wrap the data key with a recovery code or passphrase, replacing any previous one
+pub fn set_recovery_secret(conn: &Connection, kek: &[u8; 32], secret: &str) -> Result<(), String> {
+    let path = connection_keyring_path(conn)?;
+    let mut ring = load(&path)?;
+    let key = unwrap(kek, &ring.hardware).ok_or_else(|| OTHER_MACHINE_ERROR.to_string())?;
+
+    ring.recovery = Some(wrap_recovery(&key, secret)?);
+    save(&path, &ring)
+}
+
+/// Replace the data key and re-encrypt the database with `PRAGMA rekey`.
+///
+/// When a recovery secret is configured it must be supplied, so the new key
+/// can be wrapped with it as well.
+pub fn rotate_data_key(
+    conn: &Connection,
+    kek: &[u8; 32],
+    recovery_secret: Option<&str>,
+) -> Result<(), String> {
+    let path = connection_keyring_path(conn)?;
+    let original = load(&path)?;
+    unwrap(kek, &original.hardware).ok_or_else(|| OTHER_MACHINE_ERROR.to_string())?;
+
+    let recovery = match (&original.recovery, recovery_secret) {
+        (Some(_), Some(secret)) => {
+            unwrap_recovery(&original, secret)?;
+            Some(secret)
+        }
+        (Some(_), None) => {
+            return Err("Debe indicar el código de recuperación para rotar la clave".to_string())
+        }
+        (None, _) => None,
+    };
+
+    let new_key = new_data_key();
+    let mut ring = KeyRing {
+        key_id: uuid::Uuid::new_v4().to_string(),
+        rotated_at: Some(Utc::now().to_rfc3339()),
+        hardware: wrap(kek, &new_key, "hardware", None)?,
+        recovery: recovery.map(|s| wrap_recovery(&new_key, s)).transpose()?,
+        previous: Some(original.hardware.clone()),
+        ..original.clone()
+    };
+    save(&path, &ring)?;
+
+    if let Err(e) = rekey(conn, &new_key) {
+        // Database still uses the old key
+        save(&path, &original)?;
+        return Err(e);
+    }
+
+    ring.previous = None;
+    save(&path, &ring)
+}
+
+/// Copy a database from another machine, re-wrapping its data key for this one.
+///
+/// `source` must have its key ring (`<db>.keys`) next to it; the recovery
+/// secret unlocks the data key and the copy is made through the backup API so
+/// pending WAL content is included.
+pub fn migrate_to_this_machine(
+    source: &Path,
+    dest: &Path,
+    kek: &[u8; 32],
+    recovery_secret: &str,
+) -> Result<(), String> {
+    let source_ring = load(&keyring_path(source))?;
+    let key = unwrap_recovery(&source_ring, recovery_secret)?;
+    let pragma = key_pragma(&key);
+
+    let source_conn = open_with_key(source, pragma.as_str())
+        .ok_or_else(|| "No se pudo descifrar la base de datos de origen".to_string())?;
+
+    if dest.exists() {
+        return Err("Ya existe una base de datos en el destino".to_string());
+    }
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Error al crear directorio: {}", e))?;
+    }
+
+    {
+        let mut dest_conn = Connection::open(dest).map_err(|e| e.to_string())?;
+        dest_conn
+            .pragma_update(None, "key", pragma.as_str())
+            .map_err(|e| e.to_string())?;
+        let backup = Backup::new(&source_conn, &mut dest_conn).map_err(|e| e.to_string())?;
+        backup
+            .run_to_completion(256, std::time::Duration::ZERO, None)
+            .map_err(|e| format!("Error al copiar base de datos: {}", e))?;
+    }
+
+    open_with_key(dest, pragma.as_str())
+        .ok_or_else(|| "La base de datos migrada no es válida".to_string())?;
+
+    let ring = KeyRing {
+        hardware: wrap(kek, &key, "hardware", None)?,
+        previous: None,
+        ..source_ring
+    };
+    save(&keyring_path(dest), &ring)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db() -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("equinox-keys-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("equinox.db");
+        (dir, db)
+    }
+
+    #[test]
+    fn test_legacy_migration_rotation_and_recovery() {
+        let (dir, db) = temp_db();
+        let legacy = vec![SecureString::new("legacy-hardware-key".to_string())];
+        let kek = [7u8; 32];
+
+        // Legacy database keyed directly from hardware
+        {
+            let conn = Connection::open(&db).unwrap();
+            conn.pragma_update(None, "key", legacy[0].as_str()).unwrap();
+            conn.pragma_update(None, "journal_mode", "WAL").unwrap();
+            conn.execute_batch("CREATE TABLE t (v TEXT); INSERT INTO t VALUES ('data');")
+                .unwrap();
+        }
+
+        let conn = open_database(&db, &kek, &legacy).unwrap();
+        assert!(open_with_key(&db, legacy[0].as_str()).is_none());
+
+        set_recovery_secret(&conn, &kek, "recovery passphrase").unwrap();
+        assert!(rotate_data_key(&conn, &kek, None).is_err());
+        rotate_data_key(&conn, &kek, Some("recovery passphrase")).unwrap();
+        drop(conn);
+
+        // Reopen on this machine
+        let conn = open_database(&db, &kek, &legacy).unwrap();
+        assert!(status(&conn, false).unwrap().rotated_at.is_some());
+        drop(conn);
+
+        // Another machine cannot open it without the recovery secret
+        let other_kek = [9u8; 32];
+        assert!(open_database(&db, &other_kek, &[]).is_err());
+
+        let dest = dir.join("moved").join("equinox.db");
+        assert!(migrate_to_this_machine(&db, &dest, &other_kek, "wrong secret!!").is_err());
+        migrate_to_this_machine(&db, &dest, &other_kek, "recovery passphrase").unwrap();
+
+        let conn = open_database(&dest, &other_kek, &[]).unwrap();
+        let v: String = conn.query_row("SELECT v FROM t", [], |r| r.get(0)).unwrap();
+        assert_eq!(v, "data");
+
+        drop(conn);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_recovery_code_format() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 29);
+        assert_eq!(code.split('-').count(), 5);
+    }
+}
@@ -0,0 +1,460 @@
+//! License Verification
+//!
+//! Offline verification of license tokens signed by Equinox with Ed25519.
+//! A token is `EQX1.<hex payload JSON>.<hex signature>`; the signature covers
+//! the payload bytes exactly as issued.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::audit::{self, AuditEventType};
+use super::hardware_lock;
+use super::time_guard;
+use crate::models::license::{LicensePayload, LicenseState, LicenseStatusKind};
+
+const TOKEN_PREFIX: &str = "EQX1";
+const LICENSE_METADATA: &str = "license_token";
+const TRIAL_METADATA: &str = "license_trial_until";
+
+/// Equinox license signing public key (overridable at build time for staging)
+const LICENSE_PUBLIC_KEY: &str = match option_env!("EQUINOX_LICENSE_PUBLIC_KEY") {
+    Some(key) => key,
+    None => "ede05079a7031f00a02d377ef6ac15cef63e4fc05a022440f55c89e624a6368d",
+};
+
+fn vendor_key() -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(LICENSE_PUBLIC_KEY)
+        .map_err(|e| e.to_string())?
+        .try_into()
+        .map_err(|_| "Clave pública de licencias inválida".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| e.to_string())
+}
+
+/// Parse a token and check its signature
+fn verify_token(token: &str, key: &VerifyingKey) -> Result<LicensePayload, String> {
+    let invalid = || "Formato de licencia inválido".to_string();
+
+    let mut parts = token.trim().split('.');
+    let (Some(prefix), Some(payload_hex), Some(sig_hex), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    if prefix != TOKEN_PREFIX {
+        return Err(invalid());
+    }
+
+    let payload = hex::decode(payload_hex).map_err(|_| invalid())?;
+    let sig_bytes: [u8; 64] = hex::decode(sig_hex)
+        .map_err(|_| invalid())?
+        .try_into()
+        .map_err(|_| invalid())?;
+
+    key.verify(&payload, &Signature::from_bytes(&sig_bytes))
+        .map_err(|_| "Firma de licencia inválida".to_string())?;
+
+    serde_json::from_slice(&payload).map_err(|e| format!("Licencia inválida: {}", e))
+}
+
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    // Plain dates expire at the end of the day
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(23, 59, 59))
+        .map(|dt| dt.and_utc())
+}
+
+fn normalize_tax_id(tax_id: &str) -> String {
+    tax_id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// Evaluate a verified license against this machine, organization and clock
+fn evaluate(
+    license: &LicensePayload,
+    now: DateTime<Utc>,
+    hardware_matches: Option<bool>,
+    local_tax_id: Option<&str>,
+) -> (LicenseStatusKind, Option<String>, Option<DateTime<Utc>>) {
+    let Some(expires_at) = parse_date(&license.expires_at) else {
+        return (
+            LicenseStatusKind::Invalid,
+            Some("Fecha de vencimiento inválida".to_string()),
+            None,
+        );
+    };
+    let grace_until = expires_at + Duration::days(license.grace_days as i64);
+
+    let fiscal = license
+        .modules
+        .iter()
+        .any(|m| hardware_lock::is_fiscal_module(m));
+    if fiscal && license.hardware_id.is_none() {
+        return (
+            LicenseStatusKind::Invalid,
+            Some("Los módulos fiscales requieren una licencia vinculada al equipo".to_string()),
+            None,
+        );
+    }
+    if hardware_matches == Some(false) {
+        return (
+            LicenseStatusKind::Invalid,
+            Some("La licencia pertenece a otro equipo".to_string()),
+            None,
+        );
+    }
+
+    if let (Some(licensed), Some(local)) = (license.org_tax_id.as_deref(), local_tax_id) {
+        if normalize_tax_id(licensed) != normalize_tax_id(local) {
+            return (
+                LicenseStatusKind::Invalid,
+                Some("La licencia pertenece a otra organización".to_string()),
+                None,
+            );
+        }
+    }
+
+    if now <= expires_at {
+        (LicenseStatusKind::Valid, None, Some(grace_until))
+    } else if now <= grace_until {
+        (
+            LicenseStatusKind::Grace,
+            Some(format!(
+                "Licencia vencida; período de gracia hasta {}",
+                grace_until.format("%Y-%m-%d")
+            )),
+            Some(grace_until),
+        )
+    } else {
+        (
+            LicenseStatusKind::Expired,
+            Some("Licencia vencida".to_string()),
+            Some(grace_until),
+        )
+    }
+}
+
+/// The organization licenses are bound to: id and tax id of the first one
+fn local_organization(conn: &Connection) -> Result<Option<(String, Option<String>)>, String> {
+    conn.query_row(
+        "SELECT id, tax_id FROM organizations ORDER BY created_at LIMIT 1",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+fn local_tax_id(conn: &Connection) -> Result<Option<String>, String> {
+    Ok(local_organization(conn)?
+        .and_then(|(_, tax_id)| tax_id)
+        .filter(|t| !t.trim().is_empty()))
+}
+
+fn active_users(conn: &Connection) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM users WHERE is_active = 1",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn state_for(conn: &Connection, license: LicensePayload) -> Result<LicenseState, String> {
+    let hardware_matches = license
+        .hardware_id
+        .as_deref()
+        .map(hardware_lock::verify_hardware_lock);
+    let now = time_guard::trusted_now(conn)?;
+    let (status, message, grace_until) = evaluate(
+        &license,
+        now,
+        hardware_matches,
+        local_tax_id(conn)?.as_deref(),
+    );
+
+    Ok(LicenseState {
+        status,
+        days_remaining: parse_date(&license.expires_at).map(|e| (e - now).num_days()),
+        grace_until: grace_until.map(|g| g.to_rfc3339()),
+        license: Some(license),
+        active_users: active_users(conn)?,
+        message,
+    })
+}
+
+fn get_metadata(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT value FROM security_metadata WHERE key = ?1",
+        [key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// State of an installation without a license: on trial until the date
+/// seeded by the migration, missing afterwards
+fn unlicensed_state(conn: &Connection) -> Result<LicenseState, String> {
+    let now = time_guard::trusted_now(conn)?;
+    let trial_until = get_metadata(conn, TRIAL_METADATA)?
+        .as_deref()
+        .and_then(parse_date);
+
+    let (status, message) = match trial_until {
+        Some(until) if now <= until => (
+            LicenseStatusKind::Trial,
+            format!(
+                "Período de prueba hasta {}; instale la licencia",
+                until.format("%Y-%m-%d")
+            ),
+        ),
+        _ => (
+            LicenseStatusKind::Missing,
+            "No hay licencia instalada".to_string(),
+        ),
+    };
+
+    Ok(LicenseState {
+        status,
+        license: None,
+        grace_until: trial_until.map(|t| t.to_rfc3339()),
+        days_remaining: trial_until.map(|t| (t - now).num_days()),
+        active_users: active_users(conn)?,
+        message: Some(message),
+    })
+}
+
+/// Verify the installed license offline
+pub fn current_state(conn: &Connection) -> Result<LicenseState, String> {
+    let Some(token) = get_metadata(conn, LICENSE_METADATA)? else {
+        return unlicensed_state(conn);
+    };
+
+    match verify_token(&token, &vendor_key()?) {
+        Ok(license) => state_for(conn, license),
+        Err(e) => Ok(LicenseState {
+            status: LicenseStatusKind::Invalid,
+            license: None,
+            grace_until: None,
+            days_remaining: None,
+            active_users: active_users(conn)?,
+            message: Some(e),
+        }),
+    }
+}
+
+/// Verify a license token against this database without installing it
+pub fn check_license(conn: &Connection, token: &str) -> Result<LicenseState, String> {
+    let license = verify_token(token, &vendor_key()?)?;
+    let state = state_for(conn, license)?;
+
+    if matches!(
+        state.status,
+        LicenseStatusKind::Invalid | LicenseStatusKind::Expired
+    ) {
+        return Err(state
+            .message
+            .unwrap_or_else(|| "Licencia inválida".to_string()));
+    }
+    Ok(state)
+}
+
+/// Verify and install a license token, replacing the current one
+pub fn install_license(
+    conn: &Connection,
+    token: &str,
+    user_id: Option<&str>,
+) -> Result<LicenseState, String> {
+    let state = check_license(conn, token)?;
+    let Some(license) = state.license.clone() else {
+        return Err("Licencia inválida".to_string());
+    };
+
+    conn.execute(
+        "INSERT OR REPLACE INTO security_metadata (key, value) VALUES (?1, ?2)",
+        params![LICENSE_METADATA, token.trim()],
+    )
+    .map_err(|e| e.to_string())?;
+
+    // Only the organization the license was checked against
+    if let Some((org_id, _)) = local_organization(conn)? {
+        conn.execute(
+            "UPDATE organizations SET plan = ?1, subscription_expires_at = ?2 WHERE id = ?3",
+            params![license.plan, license.expires_at, org_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    audit::log_event(
+        conn,
+        None,
+        user_id,
+        AuditEventType::LicenseInstalled,
+        Some("license"),
+        Some(&license.license_id),
+        &format!(
+            "plan={}, modules={}, seats={}, expires_at={}",
+            license.plan,
+            license.modules.join(","),
+            license.seats,
+            license.expires_at
+        ),
+    )
+    .ok();
+
+    Ok(state)
+}
+
+/// Ensure the license entitles a module. Non-fiscal modules are always available.
+pub fn require_module(conn: &Connection, module: &str) -> Result<(), String> {
+    if !hardware_lock::is_fiscal_module(module) {
+        return Ok(());
+    }
+
+    let state = current_state(conn)?;
+    match state.status {
+        LicenseStatusKind::Trial => return Ok(()),
+        LicenseStatusKind::Valid | LicenseStatusKind::Grace => {}
+        _ => {
+            return Err(format!(
+                "Licencia requerida para el módulo '{}': {}",
+                module,
+                state.message.unwrap_or_default()
+            ))
+        }
+    }
+
+    let entitled = state
+        .license
+        .is_some_and(|l| l.modules.iter().any(|m| m == module));
+    if !entitled {
+        return Err(format!("La licencia no incluye el módulo '{}'", module));
+    }
+
+    Ok(())
+}
+
+/// Ensure another active user fits in the licensed seats
+pub fn require_seat(conn: &Connection) -> Result<(), String> {
+    let state = current_state(conn)?;
+    if let Some(license) = state.license {
+        if state.active_users >= license.seats as i64 {
+            return Err(format!(
+                "La licencia permite {} usuarios activos",
+                license.seats
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn payload(expires_at: &str) -> LicensePayload {
+        LicensePayload {
+            license_id: "lic-1".to_string(),
+            org_name: "Demo".to_string(),
+            org_tax_id: Some("J-12345678-9".to_string()),
+            plan: "pro".to_string(),
+            modules: vec!["invoicing".to_string()],
+            seats: 3,
+            hardware_id: Some("hw".to_string()),
+            issued_at: "2026-01-01".to_string(),
+            expires_at: expires_at.to_string(),
+            grace_days: 15,
+        }
+    }
+
+    fn sign(payload: &LicensePayload, key: &SigningKey) -> String {
+        let bytes = serde_json::to_vec(payload).unwrap();
+        let sig = key.sign(&bytes);
+        format!(
+            "{}.{}.{}",
+            TOKEN_PREFIX,
+            hex::encode(&bytes),
+            hex::encode(sig.to_bytes())
+        )
+    }
+
+    #[test]
+    fn test_token_signature() {
+        let key = SigningKey::from_bytes(&[3u8; 32]);
+        let token = sign(&payload("2027-01-01"), &key);
+
+        let verified = verify_token(&token, &key.verifying_key()).unwrap();
+        assert_eq!(verified.plan, "pro");
+
+        let other = SigningKey::from_bytes(&[4u8; 32]);
+        assert!(verify_token(&token, &other.verifying_key()).is_err());
+
+        // Tampered payload
+        let mut tampered = payload("2099-01-01");
+        tampered.seats = 100;
+        let forged = format!(
+            "{}.{}.{}",
+            TOKEN_PREFIX,
+            hex::encode(serde_json::to_vec(&tampered).unwrap()),
+            token.rsplit('.').next().unwrap()
+        );
+        assert!(verify_token(&forged, &key.verifying_key()).is_err());
+    }
+
+    #[test]
+    fn test_trial_until_seeded_date() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+
+        let state = current_state(&conn).unwrap();
+        assert_eq!(state.status, LicenseStatusKind::Trial);
+        assert!(require_module(&conn, "invoicing").is_ok());
+
+        conn.execute(
+            "UPDATE security_metadata SET value = '2026-01-01T00:00:00Z' WHERE key = ?1",
+            [TRIAL_METADATA],
+        )
+        .unwrap();
+        assert_eq!(
+            current_state(&conn).unwrap().status,
+            LicenseStatusKind::Missing
+        );
+        assert!(require_module(&conn, "invoicing").is_err());
+        assert!(require_module(&conn, "inventory").is_ok());
+    }
+
+    #[test]
+    fn test_evaluate_expiry_grace_and_binding() {
+        let now = parse_date("2027-01-10").unwrap();
+        let tax = Some("J123456789");
+
+        let (status, _, _) = evaluate(&payload("2027-02-01"), now, Some(true), tax);
+        assert_eq!(status, LicenseStatusKind::Valid);
+
+        let (status, _, _) = evaluate(&payload("2027-01-01"), now, Some(true), tax);
+        assert_eq!(status, LicenseStatusKind::Grace);
+
+        let (status, _, _) = evaluate(&payload("2026-12-01"), now, Some(true), tax);
+        assert_eq!(status, LicenseStatusKind::Expired);
+
+        let (status, _, _) = evaluate(&payload("2027-02-01"), now, Some(false), tax);
+        assert_eq!(status, LicenseStatusKind::Invalid);
+
+        let (status, _, _) = evaluate(&payload("2027-02-01"), now, Some(true), Some("J-999"));
+        assert_eq!(status, LicenseStatusKind::Invalid);
+
+        let mut unbound = payload("2027-02-01");
+        unbound.hardware_id = None;
+        let (status, _, _) = evaluate(&unbound, now, None, tax);
+        assert_eq!(status, LicenseStatusKind::Invalid);
+    }
+}
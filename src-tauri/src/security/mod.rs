@@ -4,40 +4,73 @@
 
 pub mod audit;
 pub mod hardware_lock;
+pub mod key_store;
+pub mod license;
 pub mod secure_chain;
 pub mod time_guard;
 
 use sha2::{Digest, Sha256};
-use tauri::AppHandle;
+use tauri::{AppHandle, Manager};
 
-use zeroize::Zeroize;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Machine id used by builds that silently fell back when `machine_uid` failed
+const LEGACY_FALLBACK_ID: &str = "equinox-fallback-id";
 
 /// Security Manager for hardware-based encryption
 pub struct SecurityManager {
     hardware_fingerprint: String,
+    /// Fingerprint older builds derived on this machine, if it differs
+    legacy_fingerprint: Option<String>,
+    /// The machine id could not be read and a persisted device id is used
+    uses_device_id: bool,
 }
 
 impl SecurityManager {
     /// Create a new security manager
-    pub fn new(_app: &AppHandle) -> Self {
-        let fingerprint = Self::generate_hardware_fingerprint();
+    pub fn new(app: &AppHandle) -> Result<Self, Box<dyn std::error::Error>> {
+        let (machine_id, uses_device_id) = match machine_uid::get() {
+            Ok(id) => (id, false),
+            Err(e) => {
+                eprintln!(
+                    "⚠️ WARNING: Failed to get Machine UID ({}). Using device id.",
+                    e
+                );
+                (Self::persisted_device_id(app)?, true)
+            }
+        };
 
         #[cfg(debug_assertions)]
         println!("🔐 Hardware fingerprint generated");
 
-        Self {
-            hardware_fingerprint: fingerprint,
+        Ok(Self {
+            hardware_fingerprint: Self::fingerprint_for(&machine_id),
+            legacy_fingerprint: uses_device_id.then(|| Self::fingerprint_for(LEGACY_FALLBACK_ID)),
+            uses_device_id,
+        })
+    }
+
+    /// Random device id persisted in the app config, used when the OS does
+    /// not expose a machine id. Stable across restarts, unlike a constant.
+    fn persisted_device_id(app: &AppHandle) -> Result<String, Box<dyn std::error::Error>> {
+        let config_dir = app.path().app_data_dir()?.join(".config");
+        let path = config_dir.join("device_id");
+
+        if let Ok(id) = std::fs::read_to_string(&path) {
+            let id = id.trim().to_string();
+            if !id.is_empty() {
+                return Ok(id);
+            }
         }
+
+        std::fs::create_dir_all(&config_dir)?;
+        let id = uuid::Uuid::new_v4().to_string();
+        std::fs::write(&path, &id)?;
+        Ok(id)
     }
 
     /// Generate a unique hardware fingerprint
-    fn generate_hardware_fingerprint() -> String {
-        let machine_id = machine_uid::get().unwrap_or_else(|_| {
-            #[cfg(debug_assertions)]
-            eprintln!("⚠️ WARNING: Failed to get Machine UID. Using fallback.");
-            "equinox-fallback-id".to_string()
-        });
-
+    fn fingerprint_for(machine_id: &str) -> String {
         let salt = "equinox-v1-salt-x8k2m5n7p9q3";
         let raw = format!("{}{}", machine_id, salt);
 
@@ -46,18 +79,39 @@ impl SecurityManager {
         hex::encode(hasher.finalize())
     }
 
-    /// Get the database encryption key derived from hardware
-    pub fn get_db_key(&self) -> SecureString {
+    fn db_key_for(fingerprint: &str) -> SecureString {
         let salt = "equinox-db-key-v1";
-        let raw = format!("{}{}", self.hardware_fingerprint, salt);
+        let raw = format!("{}{}", fingerprint, salt);
 
         let mut hasher = Sha256::new();
         hasher.update(raw.as_bytes());
         SecureString::new(hex::encode(hasher.finalize()))
     }
 
+    /// Database keys derived directly from hardware, used by databases created
+    /// before the key hierarchy. Only needed to migrate them.
+    pub fn legacy_db_keys(&self) -> Vec<SecureString> {
+        let mut keys = vec![Self::db_key_for(&self.hardware_fingerprint)];
+        if let Some(ref legacy) = self.legacy_fingerprint {
+            keys.push(Self::db_key_for(legacy));
+        }
+        keys
+    }
+
+    /// Key-encryption key bound to this machine; wraps the random data key
+    pub fn get_key_encryption_key(&self) -> Zeroizing<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        hasher.update(self.hardware_fingerprint.as_bytes());
+        hasher.update(b"equinox-kek-v1");
+        Zeroizing::new(hasher.finalize().into())
+    }
+
+    /// Whether the fingerprint comes from a persisted device id
+    pub fn uses_device_id(&self) -> bool {
+        self.uses_device_id
+    }
+
     /// Get the hardware fingerprint
-    #[allow(dead_code)]
     pub fn get_hardware_id(&self) -> &str {
         &self.hardware_fingerprint
     }
@@ -1,14 +1,17 @@
 //! Secure Chain (Blockchain-Lite)
 //!
 //! Provides integrity verification for fiscal documents using hash chains.
+//! Each document issued on this installation is hashed together with the
+//! hash of the one issued before it in the same branch, so editing,
+//! removing or reordering an issued document breaks the chain.
 
+use rusqlite::{params, Connection, OptionalExtension};
 use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 
-#[allow(dead_code)]
 const CHAIN_SALT: &str = "equinox-chain-v1";
 
 /// Calculate SHA256 hash of data with salt
-#[allow(dead_code)]
 fn calculate_hash(prev_hash: &str, payload: &str) -> String {
     let mut hasher = Sha256::new();
     hasher.update(prev_hash);
@@ -18,13 +21,244 @@ fn calculate_hash(prev_hash: &str, payload: &str) -> String {
 }
 
 /// Verify integrity of a hash
-#[allow(dead_code)]
 pub fn verify_hash(prev_hash: &str, payload: &str, expected_hash: &str) -> bool {
     calculate_hash(prev_hash, payload) == expected_hash
 }
 
 /// Get genesis block hash
-#[allow(dead_code)]
 pub fn get_genesis_hash() -> String {
     "0".repeat(64)
 }
+
+/// The fields of an issued invoice that may not change, in a fixed order
+fn invoice_payload(conn: &Connection, invoice_id: &str) -> Result<String, String> {
+    let header: Vec<serde_json::Value> = conn
+        .query_row(
+            "SELECT tenant_id, invoice_number, invoice_type, client_id, client_tax_id, currency,
+                    exchange_rate, issue_date, subtotal, discount_total, tax_total, total
+             FROM billing_invoices WHERE id = ?1",
+            [invoice_id],
+            |row| {
+                Ok(vec![
+                    row.get::<_, String>(0)?.into(),
+                    row.get::<_, String>(1)?.into(),
+                    row.get::<_, String>(2)?.into(),
+                    row.get::<_, String>(3)?.into(),
+                    row.get::<_, Option<String>>(4)?.into(),
+                    row.get::<_, String>(5)?.into(),
+                    row.get::<_, f64>(6)?.into(),
+                    row.get::<_, String>(7)?.into(),
+                    row.get::<_, f64>(8)?.into(),
+                    row.get::<_, f64>(9)?.into(),
+                    row.get::<_, f64>(10)?.into(),
+                    row.get::<_, f64>(11)?.into(),
+                ])
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, product_id, quantity, unit_price, discount_amount, tax_rate, tax_amount,
+                    line_total
+             FROM billing_invoice_items WHERE invoice_id = ?1 ORDER BY id",
+        )
+        .map_err(|e| e.to_string())?;
+    let items = stmt
+        .query_map([invoice_id], |row| {
+            Ok(serde_json::Value::from(vec![
+                serde_json::Value::from(row.get::<_, String>(0)?),
+                row.get::<_, String>(1)?.into(),
+                row.get::<_, f64>(2)?.into(),
+                row.get::<_, f64>(3)?.into(),
+                row.get::<_, f64>(4)?.into(),
+                row.get::<_, f64>(5)?.into(),
+                row.get::<_, f64>(6)?.into(),
+                row.get::<_, f64>(7)?.into(),
+            ]))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(serde_json::json!([invoice_id, header, items]).to_string())
+}
+
+/// Append an issued invoice to its branch's chain; returns its hash
+pub fn seal_invoice(
+    conn: &Connection,
+    tenant_id: &str,
+    invoice_id: &str,
+) -> Result<String, String> {
+    // The last link is the hash no other document points back to
+    let prev_hash = conn
+        .query_row(
+            "SELECT b.hash FROM billing_invoices b
+             WHERE b.tenant_id = ?1 AND b.hash IS NOT NULL AND NOT EXISTS (
+                 SELECT 1 FROM billing_invoices n
+                 WHERE n.tenant_id = b.tenant_id AND n.prev_hash = b.hash)",
+            [tenant_id],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(get_genesis_hash);
+
+    let hash = calculate_hash(&prev_hash, &invoice_payload(conn, invoice_id)?);
+    conn.execute(
+        "UPDATE billing_invoices SET prev_hash = ?1, hash = ?2 WHERE id = ?3 AND tenant_id = ?4",
+        params![prev_hash, hash, invoice_id, tenant_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(hash)
+}
+
+/// Verify every chained invoice still matches its hash and each branch's
+/// chain runs unbroken from genesis. Databases from before the chain pass.
+pub fn verify_invoice_chain(conn: &Connection) -> Result<(), String> {
+    let has_chain: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('billing_invoices') WHERE name = 'hash'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if has_chain == 0 {
+        return Ok(());
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT tenant_id, id, invoice_number, prev_hash, hash FROM billing_invoices
+             WHERE hash IS NOT NULL",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                row.get::<_, String>(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    // Per branch: previous hash -> (id, number, hash)
+    let mut links: HashMap<String, HashMap<String, (String, String, String)>> = HashMap::new();
+    for (tenant_id, id, number, prev_hash, hash) in rows {
+        let branch = links.entry(tenant_id).or_default();
+        if branch
+            .insert(prev_hash, (id, number.clone(), hash))
+            .is_some()
+        {
+            return Err(format!(
+                "Cadena de integridad rota en la factura {}",
+                number
+            ));
+        }
+    }
+
+    for mut branch in links.into_values() {
+        let mut prev_hash = get_genesis_hash();
+        while let Some((id, number, hash)) = branch.remove(&prev_hash) {
+            if !verify_hash(&prev_hash, &invoice_payload(conn, &id)?, &hash) {
+                return Err(format!("La factura {} fue modificada", number));
+            }
+            prev_hash = hash;
+        }
+        if let Some((_, number, _)) = branch.into_values().next() {
+            return Err(format!(
+                "Cadena de integridad rota en la factura {}",
+                number
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        crate::db::migrations::apply_compliance_triggers(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO clients (id, tenant_id, name) VALUES ('c1', 't1', 'Cliente');
+             INSERT INTO products (id, tenant_id, name, unit_price) VALUES ('p1', 't1', 'Arroz', 10.0);",
+        )
+        .unwrap();
+        for invoice in ["i1", "i2", "i3"] {
+            conn.execute(
+                "INSERT INTO billing_invoices (id, tenant_id, invoice_number, invoice_type, status, client_id,
+                     client_name, issue_date, subtotal, total, created_by, created_at, updated_at)
+                 VALUES (?1, 't1', ?1, 'invoice', 'issued', 'c1', 'Cliente', '2026-03-01', 10, 10, 'u1',
+                     '2026-03-01', '2026-03-01')",
+                [invoice],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO billing_invoice_items (id, invoice_id, product_id, code, description, quantity,
+                     unit_price, line_total)
+                 VALUES (?1 || '-1', ?1, 'p1', 'P1', 'Arroz', 1, 10, 10)",
+                [invoice],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn test_invoice_chain_detects_edits_and_gaps() {
+        let conn = setup();
+        let first = seal_invoice(&conn, "t1", "i1").unwrap();
+        seal_invoice(&conn, "t1", "i2").unwrap();
+        seal_invoice(&conn, "t1", "i3").unwrap();
+        let prev: String = conn
+            .query_row(
+                "SELECT prev_hash FROM billing_invoices WHERE id = 'i2'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(prev, first);
+        assert!(verify_invoice_chain(&conn).is_ok());
+
+        // The hash itself cannot be rewritten in place
+        assert!(conn
+            .execute("UPDATE billing_invoices SET hash = 'x' WHERE id = 'i2'", [])
+            .is_err());
+
+        // An edited line no longer matches its hash
+        conn.execute(
+            "UPDATE billing_invoice_items SET quantity = 2 WHERE id = 'i2-1'",
+            [],
+        )
+        .unwrap();
+        let err = verify_invoice_chain(&conn).unwrap_err();
+        assert!(err.contains("i2"));
+        conn.execute(
+            "UPDATE billing_invoice_items SET quantity = 1 WHERE id = 'i2-1'",
+            [],
+        )
+        .unwrap();
+        assert!(verify_invoice_chain(&conn).is_ok());
+
+        // A document taken out of the middle breaks the chain
+        conn.execute(
+            "DELETE FROM billing_invoice_items WHERE invoice_id = 'i2'",
+            [],
+        )
+        .unwrap();
+        conn.execute("DELETE FROM billing_invoices WHERE id = 'i2'", [])
+            .unwrap();
+        assert!(verify_invoice_chain(&conn).is_err());
+    }
+}
@@ -2,55 +2,257 @@
 //!
 //! Detects system clock manipulation to prevent backdating fiscal documents.
 
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use super::audit::{self, AuditEventType};
+
+const ROLLBACK_ERROR: &str =
+    "⛔ SECURITY: System clock has been rolled back. Fiscal operations disabled.";
+
+/// Current state of the clock checks
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeIntegrityStatus {
+    pub ok: bool,
+    pub now: String,
+    pub last_seen: Option<String>,
+    pub latest_record: Option<String>,
+    pub message: Option<String>,
+}
+
+fn tolerance() -> Duration {
+    Duration::hours(1)
+}
+
+/// Parse a stored timestamp (RFC3339 or plain `YYYY-MM-DD` dates)
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    // Dates without time (issue_date) are compared at the start of the day
+    // in UTC; the one-day slack in `latest_record_time` covers timezones.
+    NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc())
+}
+
+fn get_metadata(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT value FROM security_metadata WHERE key = ?1",
+        [key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+fn set_metadata(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO security_metadata (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Latest fiscal timestamp recorded in the database.
+///
+/// Date-only values (issue_date) are shifted back a day so a document issued
+/// "today" in a timezone ahead of UTC does not trip the guard.
+fn latest_record_time(conn: &Connection) -> Result<Option<DateTime<Utc>>, String> {
+    let queries: [(&str, bool); 3] = [
+        (
+            "SELECT MAX(issue_date) FROM billing_invoices WHERE status != 'draft'",
+            true,
+        ),
+        ("SELECT MAX(created_at) FROM billing_invoices", false),
+        ("SELECT MAX(created_at) FROM billing_payments", false),
+    ];
+
+    let mut latest: Option<DateTime<Utc>> = None;
+    for (query, date_only) in queries {
+        let value: Option<String> = conn
+            .query_row(query, [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+
+        let parsed = value.as_deref().and_then(parse_timestamp).map(|dt| {
+            if date_only {
+                dt - Duration::days(1)
+            } else {
+                dt
+            }
+        });
+
+        if parsed > latest {
+            latest = parsed;
+        }
+    }
+
+    // After an admin recovery, records up to the acknowledged floor are ignored
+    if let Some(floor) = get_metadata(conn, "time_guard_floor")?
+        .as_deref()
+        .and_then(parse_timestamp)
+    {
+        if latest.is_some_and(|l| l <= floor) {
+            return Ok(None);
+        }
+    }
+
+    Ok(latest)
+}
+
+/// Run both checks without side effects
+fn check(conn: &Connection, now: DateTime<Utc>) -> Result<TimeIntegrityStatus, String> {
+    let last_seen = get_metadata(conn, "last_seen_timestamp")?;
+    let latest_record = latest_record_time(conn)?;
+
+    let mut message = None;
+
+    if let Some(last) = last_seen.as_deref().and_then(parse_timestamp) {
+        if now < last - tolerance() {
+            message = Some(format!(
+                "Clock reversed from {} to {}",
+                last.to_rfc3339(),
+                now.to_rfc3339()
+            ));
+        }
+    }
+
+    if message.is_none() {
+        if let Some(latest) = latest_record {
+            if now < latest - tolerance() {
+                message = Some(format!(
+                    "Clock {} is earlier than latest fiscal record {}",
+                    now.to_rfc3339(),
+                    latest.to_rfc3339()
+                ));
+            }
+        }
+    }
+
+    Ok(TimeIntegrityStatus {
+        ok: message.is_none(),
+        now: now.to_rfc3339(),
+        last_seen,
+        latest_record: latest_record.map(|dt| dt.to_rfc3339()),
+        message,
+    })
+}
 
 /// Verify system time hasn't been rolled back
-#[allow(dead_code)]
 pub fn verify_time_integrity(conn: &Connection) -> Result<(), String> {
-    let last_seen: Option<String> = conn
-        .query_row(
-            "SELECT value FROM security_metadata WHERE key = 'last_seen_timestamp'",
-            [],
-            |row| row.get(0),
+    let now = Utc::now();
+    let status = check(conn, now)?;
+
+    if let Some(anomaly) = status.message {
+        // Log the anomaly
+        audit::log_event(
+            conn,
+            None,
+            None,
+            AuditEventType::SystemTimeAnomaly,
+            Some("system"),
+            None,
+            &anomaly,
         )
-        .optional()
-        .map_err(|e| e.to_string())?;
+        .ok();
+
+        return Err(ROLLBACK_ERROR.to_string());
+    }
+
+    // Update timestamp
+    set_metadata(conn, "last_seen_timestamp", &now.to_rfc3339())?;
+
+    Ok(())
+}
 
+/// Current time, never earlier than the last time the guard saw.
+///
+/// Expiry checks use this so winding the clock back cannot revive an
+/// expired license or trial.
+pub fn trusted_now(conn: &Connection) -> Result<DateTime<Utc>, String> {
     let now = Utc::now();
+    let last_seen = get_metadata(conn, "last_seen_timestamp")?
+        .as_deref()
+        .and_then(parse_timestamp);
+    Ok(last_seen.map_or(now, |last| last.max(now)))
+}
 
-    if let Some(last_str) = last_seen {
-        if let Ok(last) = DateTime::parse_from_rfc3339(&last_str) {
-            let tolerance = Duration::hours(1);
-            if now < last.with_timezone(&Utc) - tolerance {
-                // Log the anomaly
-                conn.execute(
-                    r#"
-                    INSERT INTO audit_logs (event_type, details, timestamp)
-                    VALUES ('SYSTEM_TIME_ANOMALY', ?1, ?2)
-                    "#,
-                    params![
-                        format!("Clock reversed from {} to {}", last_str, now.to_rfc3339()),
-                        now.to_rfc3339()
-                    ],
-                )
-                .ok();
-
-                return Err(
-                    "⛔ SECURITY: System clock has been rolled back. Fiscal operations disabled."
-                        .to_string(),
-                );
-            }
-        }
+/// Report the clock status without logging or updating the watermark
+pub fn get_status(conn: &Connection) -> Result<TimeIntegrityStatus, String> {
+    check(conn, Utc::now())
+}
+
+/// Accept the current clock as correct after an anomaly (admin only).
+///
+/// Resets `last_seen_timestamp` and records the latest fiscal timestamp as a
+/// floor so records written under the wrong clock stop blocking operations.
+pub fn recover_time_integrity(
+    conn: &Connection,
+    tenant_id: Option<&str>,
+    user_id: &str,
+    justification: &str,
+) -> Result<TimeIntegrityStatus, String> {
+    if justification.trim().len() < 10 {
+        return Err("Debe indicar una justificación (mínimo 10 caracteres)".to_string());
     }
 
-    // Update timestamp
-    conn.execute(
-        "INSERT OR REPLACE INTO security_metadata (key, value) VALUES ('last_seen_timestamp', ?1)",
-        [now.to_rfc3339()],
-    )
-    .map_err(|e| e.to_string())?;
+    let now = Utc::now();
+    let before = check(conn, now)?;
+    if before.ok {
+        return Err("No hay anomalía de reloj que recuperar".to_string());
+    }
+
+    set_metadata(conn, "last_seen_timestamp", &now.to_rfc3339())?;
+    if let Some(ref latest) = before.latest_record {
+        set_metadata(conn, "time_guard_floor", latest)?;
+    }
+
+    audit::log_event(
+        conn,
+        tenant_id,
+        Some(user_id),
+        AuditEventType::TimeIntegrityRecovered,
+        Some("system"),
+        None,
+        &format!(
+            "anomaly={}, last_seen={}, latest_record={}, justification={}",
+            before.message.unwrap_or_default(),
+            before.last_seen.unwrap_or_default(),
+            before.latest_record.unwrap_or_default(),
+            justification.trim()
+        ),
+    )?;
 
+    check(conn, now)
+}
+
+/// Reject moving the invoice sequence back.
+///
+/// `invoice_counter` is the last number issued and only grows as invoices
+/// are numbered, so a new counter below it would reuse fiscal numbers.
+pub fn verify_sequence_floor(
+    conn: &Connection,
+    tenant_id: &str,
+    new_counter: i64,
+) -> Result<(), String> {
+    let issued: i64 = conn
+        .query_row(
+            "SELECT COALESCE(MAX(invoice_counter), 0) FROM company_settings WHERE tenant_id = ?1",
+            [tenant_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    if new_counter < issued {
+        return Err(format!(
+            "El siguiente número no puede ser menor a {} (ya emitido hasta {})",
+            issued + 1,
+            issued
+        ));
+    }
     Ok(())
 }
 
@@ -71,3 +273,71 @@ pub fn get_last_seen_time(conn: &Connection) -> Option<DateTime<Utc>> {
             .map(|dt| dt.with_timezone(&Utc))
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn at(value: &str) -> DateTime<Utc> {
+        parse_timestamp(value).unwrap()
+    }
+
+    #[test]
+    fn test_rollback_detection_and_tolerance() {
+        let conn = setup();
+        assert!(check(&conn, at("2026-03-01T12:00:00Z")).unwrap().ok);
+
+        set_metadata(&conn, "last_seen_timestamp", "2026-03-01T12:00:00+00:00").unwrap();
+        // Drift within the hour of tolerance is accepted
+        assert!(check(&conn, at("2026-03-01T11:10:00Z")).unwrap().ok);
+        let status = check(&conn, at("2026-03-01T10:30:00Z")).unwrap();
+        assert!(!status.ok);
+        assert!(status.message.unwrap().starts_with("Clock reversed"));
+
+        // A fiscal record later than the clock trips the guard too; the
+        // issue date alone is given a day of slack for timezones
+        set_metadata(&conn, "last_seen_timestamp", "2026-01-01T00:00:00+00:00").unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO clients (id, tenant_id, name) VALUES ('cl1', 't1', 'Cliente');
+             INSERT INTO billing_invoices (id, tenant_id, invoice_number, invoice_type, status, client_id,
+                 client_name, currency, exchange_rate, issue_date, subtotal, tax_total, total,
+                 created_by, created_at, updated_at)
+                 VALUES ('i1', 't1', 'FAC-1', 'invoice', 'issued', 'cl1', 'Cliente', 'USD', 1,
+                         '2026-03-10', 1, 0, 1, 'u1', '2026-03-01T12:00:00+00:00', '2026-03-01');",
+        )
+        .unwrap();
+        assert!(check(&conn, at("2026-03-09T01:00:00Z")).unwrap().ok);
+        assert!(!check(&conn, at("2026-03-08T12:00:00Z")).unwrap().ok);
+
+        // Once recovered, records up to the floor no longer block
+        set_metadata(&conn, "time_guard_floor", "2026-03-09T00:00:00+00:00").unwrap();
+        assert!(check(&conn, at("2026-03-08T12:00:00Z")).unwrap().ok);
+    }
+
+    #[test]
+    fn test_sequence_cannot_go_back() {
+        let conn = setup();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO company_settings (id, tenant_id, name, legal_id, address, city, state,
+                 country, invoice_counter, created_at, updated_at)
+                 VALUES ('s1', 't1', 'Empresa', 'J-1', 'Calle', 'Caracas', 'DC', 'VE', 41,
+                         '2026-01-01', '2026-01-01');",
+        )
+        .unwrap();
+
+        assert!(verify_sequence_floor(&conn, "t1", 40).is_err());
+        assert!(verify_sequence_floor(&conn, "t1", 41).is_ok());
+        assert!(verify_sequence_floor(&conn, "t1", 500).is_ok());
+        assert!(verify_sequence_floor(&conn, "t2", 0).is_ok());
+    }
+}
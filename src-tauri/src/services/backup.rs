@@ -0,0 +1,603 @@
+//! Database Backup Service
+//!
+//! Online backups through the SQLite backup API, encrypted with a key derived
+//! from the user's recovery passphrase instead of the hardware key, so they
+//! can be restored on any machine.
+
+use argon2::Argon2;
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use rusqlite::backup::Backup;
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::db::migrations;
+use crate::models::backup::{BackupInfo, BackupManifest, BackupSettings, UpdateBackupSettingsDto};
+use crate::security::audit::{self, AuditEventType};
+use crate::security::{secure_chain, SecureString};
+use crate::services::sync_outbox;
+use crate::state::ServiceError;
+
+const BACKUP_EXTENSION: &str = "eqbk";
+const MANIFEST_EXTENSION: &str = "eqbk.json";
+const SETTINGS_KEY: &str = "backup_settings";
+const BACKUP_KEY: &str = "backup_key";
+const BACKUP_SALT: &str = "backup_key_salt";
+
+/// How often the scheduler checks whether a backup is due
+const SCHEDULER_TICK_SECS: u64 = 600;
+
+fn db_err(e: impl std::fmt::Display) -> ServiceError {
+    ServiceError::Database(e.to_string())
+}
+
+fn get_metadata(conn: &Connection, key: &str) -> Result<Option<String>, ServiceError> {
+    conn.query_row(
+        "SELECT value FROM security_metadata WHERE key = ?1",
+        [key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(db_err)
+}
+
+fn set_metadata(conn: &Connection, key: &str, value: &str) -> Result<(), ServiceError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO security_metadata (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )
+    .map_err(db_err)?;
+    Ok(())
+}
+
+/// Derive a 256-bit SQLCipher key from the recovery passphrase
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<SecureString, ServiceError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| ServiceError::Validation(format!("Error derivando clave: {}", e)))?;
+
+    // Raw key syntax so SQLCipher skips its own KDF
+    let pragma = SecureString::new(format!("x'{}'", hex::encode(key)));
+    zeroize::Zeroize::zeroize(&mut key);
+    Ok(pragma)
+}
+
+/// Current backup settings
+pub fn get_settings(conn: &Connection) -> Result<BackupSettings, ServiceError> {
+    let mut settings: BackupSettings = match get_metadata(conn, SETTINGS_KEY)? {
+        Some(json) => serde_json::from_str(&json).unwrap_or_default(),
+        None => BackupSettings::default(),
+    };
+    settings.has_passphrase = get_metadata(conn, BACKUP_KEY)?.is_some();
+    Ok(settings)
+}
+
+fn save_settings(conn: &Connection, settings: &BackupSettings) -> Result<(), ServiceError> {
+    let json = serde_json::to_string(settings).map_err(db_err)?;
+    set_metadata(conn, SETTINGS_KEY, &json)
+}
+
+/// Update backup settings, optionally setting a new recovery passphrase
+pub fn update_settings(
+    conn: &Connection,
+    data: UpdateBackupSettingsDto,
+) -> Result<BackupSettings, ServiceError> {
+    let mut settings = get_settings(conn)?;
+
+    if let Some(enabled) = data.enabled {
+        settings.enabled = enabled;
+    }
+    if let Some(hours) = data.interval_hours {
+        if hours == 0 {
+            return Err(ServiceError::Validation(
+                "El intervalo debe ser de al menos 1 hora".to_string(),
+            ));
+        }
+        settings.interval_hours = hours;
+    }
+    if let Some(keep_last) = data.keep_last {
+        settings.keep_last = keep_last.max(1);
+    }
+    if let Some(max_age_days) = data.max_age_days {
+        settings.max_age_days = max_age_days;
+    }
+    if let Some(folder) = data.external_folder {
+        if let Some(ref f) = folder {
+            std::fs::create_dir_all(f).map_err(|e| {
+                ServiceError::Validation(format!("Carpeta de respaldo no disponible: {}", e))
+            })?;
+        }
+        settings.external_folder = folder;
+    }
+    if let Some(ref passphrase) = data.passphrase {
+        if passphrase.chars().count() < 12 {
+            return Err(ServiceError::Validation(
+                "La frase de recuperación debe tener al menos 12 caracteres".to_string(),
+            ));
+        }
+        let mut salt = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        let key = derive_key(passphrase, &salt)?;
+        set_metadata(conn, BACKUP_KEY, key.as_str())?;
+        set_metadata(conn, BACKUP_SALT, &hex::encode(salt))?;
+    }
+
+    save_settings(conn, &settings)?;
+    get_settings(conn)
+}
+
+fn schema_version(conn: &Connection) -> Result<i32, ServiceError> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(db_err)
+}
+
+fn file_sha256(path: &Path) -> Result<(String, u64), ServiceError> {
+    let bytes = std::fs::read(path).map_err(db_err)?;
+    Ok((hex::encode(Sha256::digest(&bytes)), bytes.len() as u64))
+}
+
+fn manifest_path(backup_path: &Path) -> PathBuf {
+    backup_path.with_extension(MANIFEST_EXTENSION)
+}
+
+/// Copy the live database into `dest` through the backup API
+fn write_backup_file(
+    conn: &Connection,
+    dest: &Path,
+    key: &SecureString,
+) -> Result<(), ServiceError> {
+    let partial = dest.with_extension("partial");
+    {
+        let mut dest_conn = Connection::open(&partial).map_err(db_err)?;
+        dest_conn
+            .pragma_update(None, "key", key.as_str())
+            .map_err(db_err)?;
+
+        let backup = Backup::new(conn, &mut dest_conn).map_err(db_err)?;
+        backup
+            .run_to_completion(256, std::time::Duration::ZERO, None)
+            .map_err(db_err)?;
+        drop(backup);
+
+        // Single-file artifact, no WAL sidecar
+        dest_conn
+            .pragma_update(None, "journal_mode", "DELETE")
+            .map_err(db_err)?;
+    }
+    std::fs::rename(&partial, dest).map_err(db_err)?;
+    Ok(())
+}
+
+/// Rotate backups in a folder: keep the newest `keep_last`, drop expired ones
+fn rotate(folder: &Path, settings: &BackupSettings) -> Result<usize, ServiceError> {
+    let mut backups = scan_folder(folder);
+    backups.sort_by(|a, b| b.manifest.created_at.cmp(&a.manifest.created_at));
+
+    let cutoff = settings
+        .max_age_days
+        .map(|days| Utc::now() - Duration::days(days as i64));
+
+    let mut removed = 0;
+    for (idx, info) in backups.iter().enumerate() {
+        let expired = match (
+            cutoff,
+            DateTime::parse_from_rfc3339(&info.manifest.created_at),
+        ) {
+            (Some(cutoff), Ok(created)) => created.with_timezone(&Utc) < cutoff,
+            _ => false,
+        };
+
+        // Never delete the newest backup, even when expired
+        if idx > 0 && (idx >= settings.keep_last as usize || expired) {
+            let path = PathBuf::from(&info.path);
+            std::fs::remove_file(&path).ok();
+            std::fs::remove_file(manifest_path(&path)).ok();
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+fn scan_folder(folder: &Path) -> Vec<BackupInfo> {
+    let Ok(entries) = std::fs::read_dir(folder) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some(BACKUP_EXTENSION))
+        .filter_map(|path| {
+            let content = std::fs::read_to_string(manifest_path(&path)).ok()?;
+            let manifest: BackupManifest = serde_json::from_str(&content).ok()?;
+            Some(BackupInfo {
+                path: path.to_string_lossy().to_string(),
+                folder: folder.to_string_lossy().to_string(),
+                manifest,
+            })
+        })
+        .collect()
+}
+
+/// Create a backup in the default folder and mirror it to the external one
+pub fn create_backup(
+    conn: &Connection,
+    default_folder: &Path,
+    user_id: Option<&str>,
+) -> Result<BackupInfo, ServiceError> {
+    let mut settings = get_settings(conn)?;
+
+    let result: Result<(BackupInfo, Option<String>), ServiceError> = (|| {
+        let key = get_metadata(conn, BACKUP_KEY)?
+            .map(SecureString::new)
+            .ok_or_else(|| {
+                ServiceError::Validation(
+                    "Configure una frase de recuperación antes de respaldar".to_string(),
+                )
+            })?;
+        let salt = get_metadata(conn, BACKUP_SALT)?.unwrap_or_default();
+
+        std::fs::create_dir_all(default_folder).map_err(db_err)?;
+
+        let now = Utc::now();
+        let file_name = format!(
+            "equinox-backup-{}.{}",
+            now.format("%Y%m%d-%H%M%S"),
+            BACKUP_EXTENSION
+        );
+        let path = default_folder.join(&file_name);
+
+        write_backup_file(conn, &path, &key)?;
+
+        let (sha256, size_bytes) = file_sha256(&path)?;
+        let manifest = BackupManifest {
+            file_name,
+            created_at: now.to_rfc3339(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: schema_version(conn)?,
+            kdf: "argon2id".to_string(),
+            salt,
+            sha256,
+            size_bytes,
+        };
+        let manifest_json = serde_json::to_string_pretty(&manifest).map_err(db_err)?;
+        std::fs::write(manifest_path(&path), &manifest_json).map_err(db_err)?;
+        rotate(default_folder, &settings)?;
+
+        // Mirror to the user folder / USB drive; a missing drive is not fatal
+        let mut mirror_error = None;
+        if let Some(ref external) = settings.external_folder {
+            let external = PathBuf::from(external);
+            let mirrored = std::fs::create_dir_all(&external)
+                .and_then(|_| std::fs::copy(&path, external.join(&manifest.file_name)))
+                .and_then(|_| {
+                    std::fs::write(
+                        manifest_path(&external.join(&manifest.file_name)),
+                        &manifest_json,
+                    )
+                });
+            match mirrored {
+                Ok(_) => {
+                    rotate(&external, &settings)?;
+                }
+                Err(e) => mirror_error = Some(format!("Carpeta externa no disponible: {}", e)),
+            }
+        }
+
+        Ok((
+            BackupInfo {
+                path: path.to_string_lossy().to_string(),
+                folder: default_folder.to_string_lossy().to_string(),
+                manifest,
+            },
+            mirror_error,
+        ))
+    })();
+
+    match result {
+        Ok((info, mirror_error)) => {
+            settings.last_backup_at = Some(info.manifest.created_at.clone());
+            settings.last_error = mirror_error;
+            save_settings(conn, &settings)?;
+
+            audit::log_event(
+                conn,
+                None,
+                user_id,
+                AuditEventType::DatabaseBackup,
+                Some("backup"),
+                Some(&info.manifest.file_name),
+                &format!(
+                    "sha256={}, schema_version={}, size={}, external={}",
+                    info.manifest.sha256,
+                    info.manifest.schema_version,
+                    info.manifest.size_bytes,
+                    settings.external_folder.as_deref().unwrap_or("")
+                ),
+            )
+            .ok();
+
+            Ok(info)
+        }
+        Err(e) => {
+            settings.last_error = Some(e.to_string());
+            save_settings(conn, &settings).ok();
+            Err(e)
+        }
+    }
+}
+
+/// List backups found in the default and external folders, newest first
+pub fn list_backups(
+    conn: &Connection,
+    default_folder: &Path,
+) -> Result<Vec<BackupInfo>, ServiceError> {
+    let settings = get_settings(conn)?;
+
+    let mut backups = scan_folder(default_folder);
+    if let Some(ref external) = settings.external_folder {
+        backups.extend(scan_folder(Path::new(external)));
+    }
+    backups.sort_by(|a, b| b.manifest.created_at.cmp(&a.manifest.created_at));
+
+    Ok(backups)
+}
+
+/// Open a backup and check it can be restored into `live`: checksum,
+/// passphrase, schema version and the chain of issued invoices
+fn open_validated_backup(
+    live: &Connection,
+    path: &Path,
+    passphrase: &str,
+) -> Result<(Connection, BackupManifest), ServiceError> {
+    let manifest: BackupManifest = std::fs::read_to_string(manifest_path(path))
+        .map_err(|_| ServiceError::NotFound("Manifiesto del respaldo no encontrado".to_string()))
+        .and_then(|c| {
+            serde_json::from_str(&c)
+                .map_err(|e| ServiceError::Validation(format!("Manifiesto inválido: {}", e)))
+        })?;
+
+    let (sha256, _) = file_sha256(path)?;
+    if sha256 != manifest.sha256 {
+        return Err(ServiceError::Validation(
+            "El archivo de respaldo fue modificado o está dañado".to_string(),
+        ));
+    }
+
+    let salt = hex::decode(&manifest.salt)
+        .map_err(|_| ServiceError::Validation("Manifiesto inválido: salt".to_string()))?;
+    let key = derive_key(passphrase, &salt)?;
+
+    let backup_conn = Connection::open(path).map_err(db_err)?;
+    backup_conn
+        .pragma_update(None, "key", key.as_str())
+        .map_err(db_err)?;
+
+    let integrity: String = backup_conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|_| {
+            ServiceError::Validation(
+                "Frase de recuperación incorrecta o respaldo dañado".to_string(),
+            )
+        })?;
+    if integrity != "ok" {
+        return Err(ServiceError::Validation(format!(
+            "Respaldo dañado: {}",
+            integrity
+        )));
+    }
+
+    let backup_version = schema_version(&backup_conn)?;
+    if backup_version != manifest.schema_version {
+        return Err(ServiceError::Validation(
+            "La versión de esquema no coincide con el manifiesto".to_string(),
+        ));
+    }
+    if backup_version > schema_version(live)? {
+        return Err(ServiceError::Validation(format!(
+            "El respaldo usa un esquema más reciente ({}); actualice la aplicación",
+            backup_version
+        )));
+    }
+
+    secure_chain::verify_invoice_chain(&backup_conn).map_err(ServiceError::Validation)?;
+
+    Ok((backup_conn, manifest))
+}
+
+/// Validate a backup without restoring it
+pub fn verify_backup(
+    live: &Connection,
+    path: &Path,
+    passphrase: &str,
+) -> Result<BackupManifest, ServiceError> {
+    open_validated_backup(live, path, passphrase).map(|(_, manifest)| manifest)
+}
+
+/// Restore a backup over the live database.
+///
+/// The current database is first snapshotted (still under the hardware key)
+/// to `default_folder`; then the backup pages are copied into the live
+/// connection, which re-encrypts them with the hardware key.
+pub fn restore_backup(
+    live: &mut Connection,
+    default_folder: &Path,
+    path: &Path,
+    passphrase: &str,
+    tenant_id: Option<&str>,
+    user_id: Option<&str>,
+) -> Result<BackupManifest, ServiceError> {
+    let (backup_conn, manifest) = open_validated_backup(live, path, passphrase)?;
+
+    std::fs::create_dir_all(default_folder).map_err(db_err)?;
+    let snapshot = default_folder.join(format!(
+        "pre-restore-{}.db",
+        Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    live.execute("VACUUM INTO ?1", [snapshot.to_string_lossy().to_string()])
+        .map_err(db_err)?;
+
+    {
+        let backup = Backup::new(&backup_conn, live).map_err(db_err)?;
+        backup
+            .run_to_completion(256, std::time::Duration::ZERO, None)
+            .map_err(db_err)?;
+    }
+
+    // Bring an older backup up to the current schema
+    migrations::run_migrations(live).map_err(db_err)?;
+    migrations::apply_compliance_triggers(live).map_err(db_err)?;
+    sync_outbox::apply_sync_triggers(live).map_err(db_err)?;
+
+    audit::log_event(
+        live,
+        tenant_id,
+        user_id,
+        AuditEventType::DatabaseRestored,
+        Some("backup"),
+        Some(&manifest.file_name),
+        &format!(
+            "sha256={}, schema_version={}, snapshot={}",
+            manifest.sha256,
+            manifest.schema_version,
+            snapshot.to_string_lossy()
+        ),
+    )
+    .ok();
+
+    Ok(manifest)
+}
+
+/// Whether a scheduled backup is due
+fn backup_due(settings: &BackupSettings) -> bool {
+    if !settings.enabled || !settings.has_passphrase {
+        return false;
+    }
+    match settings
+        .last_backup_at
+        .as_deref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+    {
+        Some(last) => {
+            Utc::now() - last.with_timezone(&Utc) >= Duration::hours(settings.interval_hours as i64)
+        }
+        None => true,
+    }
+}
+
+/// Start the background backup scheduler
+pub fn start_backup_scheduler(db: Arc<Mutex<Connection>>, default_folder: PathBuf) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(SCHEDULER_TICK_SECS)).await;
+
+            let Ok(conn) = db.lock() else {
+                continue;
+            };
+
+            let due = get_settings(&conn).map(|s| backup_due(&s)).unwrap_or(false);
+            if !due {
+                continue;
+            }
+
+            match create_backup(&conn, &default_folder, None) {
+                Ok(info) => println!("✅ Auto-Backup successful: {}", info.path),
+                Err(e) => eprintln!("❌ Auto-Backup failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_keyed(path: &Path, key: &str) -> Connection {
+        let conn = Connection::open(path).unwrap();
+        conn.pragma_update(None, "key", key).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_null_clears_optional_settings() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::run_migrations(&conn).unwrap();
+
+        let clear: UpdateBackupSettingsDto =
+            serde_json::from_str(r#"{"max_age_days": null, "external_folder": null}"#).unwrap();
+        assert_eq!(clear.max_age_days, Some(None));
+        assert_eq!(update_settings(&conn, clear).unwrap().max_age_days, None);
+
+        // Fields left out keep their value
+        let keep: UpdateBackupSettingsDto = serde_json::from_str(r#"{"keep_last": 3}"#).unwrap();
+        assert_eq!(keep.max_age_days, None);
+        let settings = update_settings(&conn, keep).unwrap();
+        assert_eq!((settings.keep_last, settings.max_age_days), (3, None));
+    }
+
+    #[test]
+    fn test_backup_and_restore_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("equinox-backup-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let backups = dir.join("backups");
+
+        let live = open_keyed(&dir.join("live.db"), "hardware-key");
+        migrations::run_migrations(&live).unwrap();
+        migrations::apply_compliance_triggers(&live).unwrap();
+        live.execute(
+            "INSERT INTO organizations (id, name) VALUES ('org', 'Before')",
+            [],
+        )
+        .unwrap();
+
+        update_settings(
+            &live,
+            UpdateBackupSettingsDto {
+                enabled: None,
+                interval_hours: None,
+                keep_last: None,
+                max_age_days: None,
+                external_folder: None,
+                passphrase: Some("correct horse battery".to_string()),
+            },
+        )
+        .unwrap();
+
+        let info = create_backup(&live, &backups, None).unwrap();
+        let path = PathBuf::from(&info.path);
+
+        live.execute("UPDATE organizations SET name = 'After'", [])
+            .unwrap();
+
+        assert!(verify_backup(&live, &path, "wrong passphrase!").is_err());
+
+        let mut live = live;
+        restore_backup(
+            &mut live,
+            &backups,
+            &path,
+            "correct horse battery",
+            None,
+            None,
+        )
+        .unwrap();
+
+        let name: String = live
+            .query_row("SELECT name FROM organizations WHERE id = 'org'", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+        assert_eq!(name, "Before");
+
+        drop(live);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,693 @@
+//! Barcode Lookup
+//!
+//! Resolves a scanned code to a product, variant or lot. EAN-13, UPC-A,
+//! EAN-8 and Code 128 codes match stored barcodes (zero-padded GTIN forms
+//! included), then lot numbers, then SKUs. GS1 element strings carry the
+//! GTIN plus lot, expiry, serial, weight or price; in-store EAN-13 labels
+//! printed by deli scales carry a PLU and an embedded weight or price.
+//! Products without a barcode can be given an internal EAN-13 under the
+//! reserved in-store prefix 29.
+
+use crate::models::barcode::{BarcodeMatch, GeneratedBarcode, Symbology};
+use crate::models::{ScaleLabelRule, ScaleLabelValue};
+use crate::state::ServiceError;
+use chrono::NaiveDate;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+
+fn db(e: rusqlite::Error) -> ServiceError {
+    ServiceError::Database(e.to_string())
+}
+
+/// In-store prefix of generated barcodes
+pub const INTERNAL_PREFIX: &str = "29";
+
+/// GS1 group separator (FNC1 as transmitted by scanners)
+const GS: char = '\u{1d}';
+
+/// Mod-10 check digit over the digits before it
+fn check_digit(payload: &str) -> u32 {
+    let sum: u32 = payload
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { d * 3 } else { d })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+fn is_valid_gtin(code: &str) -> bool {
+    matches!(code.len(), 8 | 12 | 13 | 14)
+        && code.chars().all(|c| c.is_ascii_digit())
+        && check_digit(&code[..code.len() - 1]) == code[code.len() - 1..].parse().unwrap_or(10)
+}
+
+pub fn symbology(code: &str) -> Symbology {
+    if is_gs1(code) {
+        return Symbology::Gs1;
+    }
+    if !is_valid_gtin(code) {
+        return Symbology::Code128;
+    }
+    match code.len() {
+        8 => Symbology::Ean8,
+        12 => Symbology::UpcA,
+        13 => Symbology::Ean13,
+        _ => Symbology::Gtin14,
+    }
+}
+
+/// The same GTIN as it may have been stored: 8, 12, 13 or 14 digits
+fn gtin_forms(code: &str) -> Vec<String> {
+    let mut forms = vec![code.to_string()];
+    if is_valid_gtin(code) {
+        let core = code.trim_start_matches('0');
+        for len in [8, 12, 13, 14] {
+            let form = format!("{:0>width$}", core, width = len);
+            if form.len() == len && !forms.contains(&form) {
+                forms.push(form);
+            }
+        }
+    }
+    forms
+}
+
+/// AIM symbology identifiers scanners may prepend to GS1 codes
+const GS1_IDENTIFIERS: [&str; 4] = ["]C1", "]e0", "]d2", "]Q3"];
+
+fn is_gs1(code: &str) -> bool {
+    GS1_IDENTIFIERS.iter().any(|id| code.starts_with(id))
+        || code.contains(GS)
+        || (code.starts_with('(') && code.contains(')'))
+}
+
+/// Fields of a GS1 element string this lookup uses
+#[derive(Debug, Default, PartialEq)]
+pub struct Gs1Data {
+    pub gtin: Option<String>,
+    pub lot: Option<String>,
+    pub expiry: Option<String>,
+    pub serial: Option<String>,
+    pub weight: Option<f64>, // Kilograms, AI 310n
+    pub price: Option<f64>,  // AI 392n / 393n
+    pub count: Option<f64>,  // AI 30 / 37
+}
+
+/// Data length of a supported application identifier, by its leading
+/// digits: Some(n) for fixed, None for variable (ended by FNC1)
+fn ai_length(ai: &str) -> Option<Option<usize>> {
+    match ai {
+        "00" => Some(Some(18)),
+        "01" | "02" => Some(Some(14)),
+        "11" | "13" | "15" | "16" | "17" => Some(Some(6)),
+        "10" | "21" | "30" | "37" => Some(None),
+        _ if ai.len() == 4 && ai.starts_with("310") => Some(Some(6)),
+        _ if ai.len() == 4 && (ai.starts_with("392") || ai.starts_with("393")) => Some(None),
+        _ => None,
+    }
+}
+
+/// YYMMDD to a date; day 00 means the last day of the month
+fn gs1_date(value: &str) -> Result<String, ServiceError> {
+    let invalid = || ServiceError::Validation(format!("Fecha GS1 inválida: {}", value));
+    let number = |range: std::ops::Range<usize>| -> Result<u32, ServiceError> {
+        value
+            .get(range)
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(invalid)
+    };
+    let (year, month, day) = (2000 + number(0..2)? as i32, number(2..4)?, number(4..6)?);
+    let date = if day == 0 {
+        NaiveDate::from_ymd_opt(year, month, 1)
+            .and_then(|first| first.checked_add_months(chrono::Months::new(1)))
+            .and_then(|next| next.pred_opt())
+    } else {
+        NaiveDate::from_ymd_opt(year, month, day)
+    };
+    date.map(|d| d.format("%Y-%m-%d").to_string())
+        .ok_or_else(invalid)
+}
+
+fn decimal(value: &str, decimals: u32) -> Result<f64, ServiceError> {
+    value
+        .parse::<u64>()
+        .map(|v| v as f64 / 10f64.powi(decimals as i32))
+        .map_err(|_| ServiceError::Validation(format!("Valor GS1 inválido: {}", value)))
+}
+
+/// Splits a GS1 element string, in bracketed "(01)…(10)…" form or raw with
+/// FNC1 separators, into the fields the lookup uses
+pub fn parse_gs1(code: &str) -> Result<Gs1Data, ServiceError> {
+    let mut rest = code;
+    for id in GS1_IDENTIFIERS {
+        rest = rest.strip_prefix(id).unwrap_or(rest);
+    }
+    let unsupported = |ai: &str| ServiceError::Validation(format!("AI GS1 no soportado: {}", ai));
+
+    let mut elements = Vec::new();
+    if rest.starts_with('(') {
+        for part in rest.split('(').skip(1) {
+            let (ai, value) = part
+                .split_once(')')
+                .ok_or_else(|| ServiceError::Validation("Código GS1 mal formado".to_string()))?;
+            ai_length(ai).ok_or_else(|| unsupported(ai))?;
+            elements.push((ai.to_string(), value.trim_end_matches(GS).to_string()));
+        }
+    } else {
+        let mut rest = rest.trim_start_matches(GS);
+        while !rest.is_empty() {
+            let (ai, length) = [2, 4]
+                .iter()
+                .filter_map(|&n| {
+                    let ai = rest.get(..n)?;
+                    ai_length(ai).map(|length| (ai, length))
+                })
+                .next()
+                .ok_or_else(|| unsupported(rest.get(..4).unwrap_or(rest)))?;
+            let data = &rest[ai.len()..];
+            let (value, next) = match length {
+                Some(n) => (
+                    data.get(..n).ok_or_else(|| {
+                        ServiceError::Validation(format!("AI GS1 {} incompleto", ai))
+                    })?,
+                    &data[n.min(data.len())..],
+                ),
+                None => data.split_once(GS).unwrap_or((data, "")),
+            };
+            elements.push((ai.to_string(), value.to_string()));
+            rest = next.trim_start_matches(GS);
+        }
+    }
+
+    let mut data = Gs1Data::default();
+    for (ai, value) in elements {
+        let decimals = ai.get(3..4).and_then(|d| d.parse().ok()).unwrap_or(0);
+        match ai.as_str() {
+            "01" | "02" => data.gtin = Some(value),
+            "10" => data.lot = Some(value),
+            "17" => data.expiry = Some(gs1_date(&value)?),
+            "21" => data.serial = Some(value),
+            "30" | "37" => data.count = Some(decimal(&value, 0)?),
+            _ if ai.starts_with("310") => data.weight = Some(decimal(&value, decimals)?),
+            _ if ai.starts_with("392") => data.price = Some(decimal(&value, decimals)?),
+            // ISO 4217 numeric currency, then the amount
+            _ if ai.starts_with("393") => {
+                data.price = Some(decimal(value.get(3..).unwrap_or(""), decimals)?)
+            }
+            _ => {}
+        }
+    }
+    if let Some(ref gtin) = data.gtin {
+        if !is_valid_gtin(gtin) {
+            return Err(ServiceError::Validation(format!("GTIN inválido: {}", gtin)));
+        }
+    }
+    Ok(data)
+}
+
+/// Digits of the embedded value: what is left of the 13 after the prefix,
+/// the PLU and the check digit
+fn value_digits(rule: &ScaleLabelRule) -> usize {
+    13usize.saturating_sub(rule.prefix.len() + rule.plu_digits + 1)
+}
+
+pub fn validate_scale_rule(rule: &ScaleLabelRule) -> Result<(), ServiceError> {
+    if rule.prefix.is_empty() || !rule.prefix.chars().all(|c| c.is_ascii_digit()) {
+        return Err(ServiceError::Validation(
+            "El prefijo de la etiqueta debe ser numérico".to_string(),
+        ));
+    }
+    // A shorter prefix such as "2" would also capture internal codes
+    if rule.prefix.starts_with(INTERNAL_PREFIX) || INTERNAL_PREFIX.starts_with(&rule.prefix) {
+        return Err(ServiceError::Validation(format!(
+            "El prefijo {} está reservado para códigos internos",
+            INTERNAL_PREFIX
+        )));
+    }
+    if rule.plu_digits == 0 || value_digits(rule) < 3 || rule.prefix.len() + rule.plu_digits >= 12 {
+        return Err(ServiceError::Validation(
+            "La etiqueta no deja dígitos para el PLU y el valor".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// PLU and embedded value of an in-store EAN-13, if a rule's prefix matches
+pub fn parse_scale_label(
+    code: &str,
+    rules: &[ScaleLabelRule],
+) -> Option<(String, ScaleLabelValue, f64)> {
+    if code.len() != 13 || !is_valid_gtin(code) {
+        return None;
+    }
+    let rule = rules
+        .iter()
+        .filter(|rule| validate_scale_rule(rule).is_ok() && code.starts_with(&rule.prefix))
+        .max_by_key(|rule| rule.prefix.len())?;
+    let plu_start = rule.prefix.len();
+    let value_start = plu_start + rule.plu_digits;
+    let plu = code[plu_start..value_start].to_string();
+    let value = decimal(&code[value_start..12], rule.decimals).ok()?;
+    Some((plu, rule.value, value))
+}
+
+/// Product or variant a code points at
+struct Item {
+    product_id: String,
+    product_name: String,
+    sku: Option<String>,
+    variant_id: Option<String>,
+    variant_name: Option<String>,
+    unit_price: f64,
+}
+
+fn map_item(row: &rusqlite::Row) -> rusqlite::Result<Item> {
+    Ok(Item {
+        product_id: row.get(0)?,
+        product_name: row.get(1)?,
+        sku: row.get(2)?,
+        variant_id: row.get(3)?,
+        variant_name: row.get(4)?,
+        unit_price: row.get::<_, Option<f64>>(5)?.unwrap_or_default(),
+    })
+}
+
+/// Active variant, else product, whose `column` (barcode or sku) is one of
+/// `codes`
+fn find_item(
+    conn: &Connection,
+    tenant_id: &str,
+    column: &str,
+    codes: &[String],
+) -> Result<Option<Item>, ServiceError> {
+    let placeholders = (0..codes.len())
+        .map(|i| format!("?{}", i + 2))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let sql = format!(
+        "SELECT p.id, p.name, COALESCE(v.sku, p.sku), v.id, v.name,
+                COALESCE(NULLIF(v.sale_price, 0), NULLIF(p.sale_price, 0), p.unit_price), 1 AS rank
+         FROM product_variants v JOIN products p ON p.id = v.product_id
+         WHERE v.tenant_id = ?1 AND v.is_active = 1 AND p.is_active = 1 AND v.{column} IN ({list})
+         UNION ALL
+         SELECT p.id, p.name, p.sku, NULL, NULL, COALESCE(NULLIF(p.sale_price, 0), p.unit_price), 2
+         FROM products p
+         WHERE p.tenant_id = ?1 AND p.is_active = 1 AND p.{column} IN ({list})
+         ORDER BY rank LIMIT 1",
+        column = column,
+        list = placeholders
+    );
+    let values = std::iter::once(tenant_id).chain(codes.iter().map(String::as_str));
+    conn.query_row(&sql, params_from_iter(values), map_item)
+        .optional()
+        .map_err(db)
+}
+
+fn item_by_id(
+    conn: &Connection,
+    tenant_id: &str,
+    product_id: &str,
+    variant_id: Option<&str>,
+) -> Result<Item, ServiceError> {
+    conn.query_row(
+        "SELECT p.id, p.name, COALESCE(v.sku, p.sku), v.id, v.name,
+                COALESCE(NULLIF(v.sale_price, 0), NULLIF(p.sale_price, 0), p.unit_price)
+         FROM products p LEFT JOIN product_variants v ON v.id = ?3 AND v.product_id = p.id
+         WHERE p.id = ?2 AND p.tenant_id = ?1",
+        params![tenant_id, product_id, variant_id],
+        map_item,
+    )
+    .optional()
+    .map_err(db)?
+    .ok_or_else(|| ServiceError::NotFound("Producto no encontrado".to_string()))
+}
+
+/// Lot id, product id, variant id and expiration date
+type LotRow = (String, String, Option<String>, Option<String>);
+
+/// Active lot with this number, soonest to expire first, optionally
+/// limited to one product
+fn find_lot(
+    conn: &Connection,
+    tenant_id: &str,
+    lot_number: &str,
+    item: Option<&Item>,
+) -> Result<Option<LotRow>, ServiceError> {
+    conn.query_row(
+        "SELECT id, product_id, variant_id, expiration_date FROM inventory_lots
+         WHERE tenant_id = ?1 AND lot_number = ?2 AND is_active = 1
+           AND (?3 IS NULL OR product_id = ?3) AND (?4 IS NULL OR variant_id IS ?4)
+         ORDER BY expiration_date IS NULL, expiration_date LIMIT 1",
+        params![
+            tenant_id,
+            lot_number,
+            item.map(|i| &i.product_id),
+            item.and_then(|i| i.variant_id.as_ref())
+        ],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )
+    .optional()
+    .map_err(db)
+}
+
+fn matched(code: &str, symbology: Symbology, item: Item) -> BarcodeMatch {
+    BarcodeMatch {
+        code: code.to_string(),
+        symbology,
+        product_id: item.product_id,
+        product_name: item.product_name,
+        sku: item.sku,
+        variant_id: item.variant_id,
+        variant_name: item.variant_name,
+        lot_id: None,
+        lot_number: None,
+        expiration_date: None,
+        serial: None,
+        unit_price: item.unit_price,
+        quantity: 1.0,
+        embedded_price: None,
+    }
+}
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+/// Product, variant or lot a scanned code belongs to, with the quantity
+/// the code carries
+pub fn lookup(
+    conn: &Connection,
+    tenant_id: &str,
+    code: &str,
+    scale_labels: &[ScaleLabelRule],
+) -> Result<BarcodeMatch, ServiceError> {
+    let code = code.trim();
+    if code.is_empty() {
+        return Err(ServiceError::Validation("Código vacío".to_string()));
+    }
+    let not_found = || ServiceError::NotFound(format!("Código {} no encontrado", code));
+
+    if is_gs1(code) {
+        let data = parse_gs1(code)?;
+        let item = match data.gtin {
+            Some(ref gtin) => Some(
+                find_item(conn, tenant_id, "barcode", &gtin_forms(gtin))?.ok_or_else(not_found)?,
+            ),
+            None => None,
+        };
+        let lot = match data.lot {
+            Some(ref number) => find_lot(conn, tenant_id, number, item.as_ref())?,
+            None => None,
+        };
+        let item = match (item, &lot) {
+            (Some(item), _) => item,
+            (None, Some((_, product_id, variant_id, _))) => {
+                item_by_id(conn, tenant_id, product_id, variant_id.as_deref())?
+            }
+            (None, None) => return Err(not_found()),
+        };
+        let mut result = matched(code, Symbology::Gs1, item);
+        result.lot_number = data.lot;
+        result.serial = data.serial;
+        result.expiration_date = data.expiry;
+        if let Some((lot_id, _, _, expiration)) = lot {
+            result.lot_id = Some(lot_id);
+            result.expiration_date = result.expiration_date.or(expiration);
+        }
+        result.quantity = data.weight.or(data.count).unwrap_or(1.0);
+        result.embedded_price = data.price;
+        return Ok(result);
+    }
+
+    if let Some((plu, value, amount)) = parse_scale_label(code, scale_labels) {
+        let mut forms = vec![plu.clone()];
+        let trimmed = plu.trim_start_matches('0');
+        if !trimmed.is_empty() && trimmed != plu {
+            forms.push(trimmed.to_string());
+        }
+        let item = match find_item(conn, tenant_id, "sku", &forms)? {
+            Some(item) => item,
+            None => find_item(conn, tenant_id, "barcode", &forms)?.ok_or_else(|| {
+                ServiceError::NotFound(format!("PLU {} de la balanza no encontrado", plu))
+            })?,
+        };
+        let mut result = matched(code, Symbology::ScaleLabel, item);
+        match value {
+            ScaleLabelValue::Weight => result.quantity = amount,
+            ScaleLabelValue::Price => {
+                if result.unit_price <= 0.0 {
+                    return Err(ServiceError::Validation(format!(
+                        "{} no tiene precio para calcular el peso",
+                        result.product_name
+                    )));
+                }
+                result.quantity = round(amount / result.unit_price, 3);
+                result.embedded_price = Some(amount);
+            }
+        }
+        return Ok(result);
+    }
+
+    let symbology = symbology(code);
+    if let Some(item) = find_item(conn, tenant_id, "barcode", &gtin_forms(code))? {
+        return Ok(matched(code, symbology, item));
+    }
+    if let Some((lot_id, product_id, variant_id, expiration)) =
+        find_lot(conn, tenant_id, code, None)?
+    {
+        let item = item_by_id(conn, tenant_id, &product_id, variant_id.as_deref())?;
+        let mut result = matched(code, symbology, item);
+        result.lot_id = Some(lot_id);
+        result.lot_number = Some(code.to_string());
+        result.expiration_date = expiration;
+        return Ok(result);
+    }
+    find_item(conn, tenant_id, "sku", &[code.to_string()])?
+        .map(|item| matched(code, symbology, item))
+        .ok_or_else(not_found)
+}
+
+/// Next unused internal EAN-13: 29, a ten-digit sequence, check digit
+fn next_internal_sequence(conn: &Connection, tenant_id: &str) -> Result<i64, ServiceError> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(CAST(SUBSTR(barcode, 3, 10) AS INTEGER)), 0) + 1 FROM (
+             SELECT barcode FROM products WHERE tenant_id = ?1
+             UNION ALL
+             SELECT barcode FROM product_variants WHERE tenant_id = ?1
+         ) WHERE LENGTH(barcode) = 13 AND SUBSTR(barcode, 1, 2) = ?2",
+        params![tenant_id, INTERNAL_PREFIX],
+        |row| row.get(0),
+    )
+    .map_err(db)
+}
+
+fn internal_barcode(sequence: i64) -> String {
+    let payload = format!("{}{:010}", INTERNAL_PREFIX, sequence);
+    format!("{}{}", payload, check_digit(&payload))
+}
+
+/// Gives internal barcodes to the active products, and their variants,
+/// that have none; all products if `product_ids` is empty
+pub fn generate_internal_barcodes(
+    conn: &Connection,
+    tenant_id: &str,
+    product_ids: &[String],
+    now: &str,
+) -> Result<Vec<GeneratedBarcode>, ServiceError> {
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    let mut missing: Vec<(String, Option<String>)> = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT p.id, NULL FROM products p
+                 WHERE p.tenant_id = ?1 AND p.is_active = 1 AND NULLIF(TRIM(p.barcode), '') IS NULL
+                 UNION ALL
+                 SELECT v.product_id, v.id FROM product_variants v
+                 JOIN products p ON p.id = v.product_id AND p.is_active = 1
+                 WHERE v.tenant_id = ?1 AND v.is_active = 1 AND NULLIF(TRIM(v.barcode), '') IS NULL
+                 ORDER BY 1, 2",
+            )
+            .map_err(db)?;
+        let rows = stmt
+            .query_map([tenant_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(db)?;
+        rows.collect::<Result<_, _>>().map_err(db)?
+    };
+    if !product_ids.is_empty() {
+        missing.retain(|(product_id, _)| product_ids.contains(product_id));
+    }
+
+    let first = next_internal_sequence(&tx, tenant_id)?;
+    let mut generated = Vec::with_capacity(missing.len());
+    for (sequence, (product_id, variant_id)) in (first..).zip(missing) {
+        let barcode = internal_barcode(sequence);
+        match variant_id {
+            Some(ref variant_id) => tx.execute(
+                "UPDATE product_variants SET barcode = ?1, updated_at = ?2 WHERE id = ?3",
+                params![barcode, now, variant_id],
+            ),
+            None => tx.execute(
+                "UPDATE products SET barcode = ?1, updated_at = ?2 WHERE id = ?3",
+                params![barcode, now, product_id],
+            ),
+        }
+        .map_err(db)?;
+        generated.push(GeneratedBarcode {
+            product_id,
+            variant_id,
+            barcode,
+        });
+    }
+    tx.commit().map_err(db)?;
+    Ok(generated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO products (id, tenant_id, sku, barcode, name, unit_price, is_active)
+                 VALUES ('p1', 't1', 'ARR-1', '7591234000018', 'Arroz', 2.0, 1);
+             INSERT INTO products (id, tenant_id, sku, barcode, name, unit_price, is_active)
+                 VALUES ('p2', 't1', 'SOD-1', '036000291452', 'Refresco', 1.0, 1);
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, is_active)
+                 VALUES ('p3', 't1', '01234', 'Jamón', 12.5, 1);
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, has_variants, is_active)
+                 VALUES ('p4', 't1', 'FRANELA', 'Franela', 8.0, 1, 1);
+             INSERT INTO product_variants (id, tenant_id, product_id, sku, name, sale_price)
+                 VALUES ('v1', 't1', 'p4', 'FRANELA-M', 'M', 9.0);
+             INSERT INTO inventory_lots (id, tenant_id, product_id, lot_number, quantity, expiration_date)
+                 VALUES ('l1', 't1', 'p1', 'L2026-07', 10, '2027-01-31');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_check_digits_and_gs1_parsing() {
+        assert!(is_valid_gtin("7591234000018"));
+        assert!(is_valid_gtin("036000291452"));
+        assert!(!is_valid_gtin("7591234000012"));
+        assert_eq!(symbology("96385074"), Symbology::Ean8);
+        assert_eq!(symbology("FRANELA-M"), Symbology::Code128);
+        assert_eq!(internal_barcode(1), "2900000000018");
+
+        let bracketed = parse_gs1("(01)07591234000018(17)270100(10)L2026-07(3103)001250").unwrap();
+        let raw = parse_gs1("]C1010759123400001817270100310300125010L2026-07").unwrap();
+        assert_eq!(bracketed, raw);
+        assert_eq!(raw.gtin.as_deref(), Some("07591234000018"));
+        assert_eq!(raw.expiry.as_deref(), Some("2027-01-31"));
+        assert_eq!(raw.weight, Some(1.25));
+
+        let with_separator = parse_gs1("01075912340000181012A\u{1d}3922350").unwrap();
+        assert_eq!(with_separator.lot.as_deref(), Some("12A"));
+        assert_eq!(with_separator.price, Some(3.5));
+        assert!(parse_gs1("(99)X(01)07591234000012").is_err());
+    }
+
+    #[test]
+    fn test_scale_rules_cannot_overlap_internal_prefix() {
+        let rule = |prefix: &str| ScaleLabelRule {
+            prefix: prefix.to_string(),
+            plu_digits: 5,
+            value: ScaleLabelValue::Weight,
+            decimals: 3,
+        };
+        assert!(validate_scale_rule(&rule("20")).is_ok());
+        assert!(validate_scale_rule(&rule("2")).is_err());
+        assert!(validate_scale_rule(&rule("29")).is_err());
+        assert!(validate_scale_rule(&rule("291")).is_err());
+
+        // An internal code is never read as a weight label
+        assert!(parse_scale_label(&internal_barcode(1), &[rule("2")]).is_none());
+    }
+
+    #[test]
+    fn test_lookup_plain_gs1_and_scale_labels() {
+        let conn = setup();
+        let rules = ScaleLabelRule::defaults();
+
+        let ean = lookup(&conn, "t1", "7591234000018", &rules).unwrap();
+        assert_eq!(
+            (ean.product_id.as_str(), ean.symbology),
+            ("p1", Symbology::Ean13)
+        );
+        // UPC-A stored, scanned as its EAN-13 form
+        let upc = lookup(&conn, "t1", "0036000291452", &rules).unwrap();
+        assert_eq!(upc.product_id, "p2");
+        let variant = lookup(&conn, "t1", "FRANELA-M", &rules).unwrap();
+        assert_eq!(variant.variant_id.as_deref(), Some("v1"));
+        assert_eq!(variant.unit_price, 9.0);
+        let lot = lookup(&conn, "t1", "L2026-07", &rules).unwrap();
+        assert_eq!(lot.lot_id.as_deref(), Some("l1"));
+        assert_eq!(lot.expiration_date.as_deref(), Some("2027-01-31"));
+
+        let gs1 = lookup(
+            &conn,
+            "t1",
+            "(01)07591234000018(10)L2026-07(3103)001250",
+            &rules,
+        )
+        .unwrap();
+        assert_eq!((gs1.lot_id.as_deref(), gs1.quantity), (Some("l1"), 1.25));
+
+        // 20 + PLU 01234 + 0.750 kg; 21 + PLU 01234 + 25.00
+        let payload = "200123400750";
+        let weighed = format!("{}{}", payload, check_digit(payload));
+        let label = lookup(&conn, "t1", &weighed, &rules).unwrap();
+        assert_eq!(
+            (label.product_id.as_str(), label.symbology, label.quantity),
+            ("p3", Symbology::ScaleLabel, 0.75)
+        );
+        let payload = "210123402500";
+        let priced = format!("{}{}", payload, check_digit(payload));
+        let label = lookup(&conn, "t1", &priced, &rules).unwrap();
+        assert_eq!((label.quantity, label.embedded_price), (2.0, Some(25.0)));
+
+        assert!(matches!(
+            lookup(&conn, "t1", "0000000000000", &rules),
+            Err(ServiceError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_internal_barcodes_fill_only_missing_codes() {
+        let conn = setup();
+        let now = "2026-10-19T10:00:00Z";
+        let generated = generate_internal_barcodes(&conn, "t1", &[], now).unwrap();
+        let codes: Vec<_> = generated
+            .iter()
+            .map(|g| {
+                (
+                    g.product_id.as_str(),
+                    g.variant_id.as_deref(),
+                    g.barcode.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                ("p3", None, "2900000000018"),
+                ("p4", None, "2900000000025"),
+                ("p4", Some("v1"), "2900000000032"),
+            ]
+        );
+        assert!(generate_internal_barcodes(&conn, "t1", &[], now)
+            .unwrap()
+            .is_empty());
+
+        let found = lookup(&conn, "t1", "2900000000032", &[]).unwrap();
+        assert_eq!(found.variant_id.as_deref(), Some("v1"));
+        conn.execute("UPDATE products SET barcode = NULL WHERE id = 'p3'", [])
+            .unwrap();
+        let again = generate_internal_barcodes(&conn, "t1", &["p3".to_string()], now).unwrap();
+        assert_eq!(again[0].barcode, "2900000000049");
+    }
+}
@@ -0,0 +1,958 @@
+//! Multi-Branch Operation
+//!
+//! Branches are the `tenants` of an organization. A user operates their home
+//! branch plus any granted in `user_tenants`; admins operate every branch of
+//! their organization. Products are per branch, so the same product in two
+//! branches is matched by SKU (or barcode) for stock views and transfers.
+
+use crate::models::branch::{
+    Branch, BranchReport, BranchStock, ConsolidatedReport, CreateStockTransferDto, CurrencyTotals,
+    StockTransfer, StockTransferItem,
+};
+use crate::services::inventory;
+use crate::services::reservations::{self, ReservationLine};
+use crate::state::ServiceError;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+pub const TRANSFER_REFERENCE: &str = "stock_transfer";
+
+fn db(e: rusqlite::Error) -> ServiceError {
+    ServiceError::Database(e.to_string())
+}
+
+/// (org_id, home tenant, role) of an active user
+fn user_scope(
+    conn: &Connection,
+    user_id: &str,
+) -> Result<(String, Option<String>, String), ServiceError> {
+    conn.query_row(
+        "SELECT org_id, tenant_id, role FROM users WHERE id = ?1 AND is_active = 1",
+        [user_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+    .optional()
+    .map_err(db)?
+    .ok_or_else(|| ServiceError::Unauthorized("Usuario no encontrado o inactivo".to_string()))
+}
+
+/// Branches the user may switch to
+pub fn list_branches(
+    conn: &Connection,
+    user_id: &str,
+    current_tenant_id: Option<&str>,
+) -> Result<Vec<Branch>, ServiceError> {
+    let (org_id, home, role) = user_scope(conn, user_id)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.id, t.org_id, t.name, COALESCE(t.is_active, 1)
+             FROM tenants t
+             WHERE t.org_id = ?1
+               AND (?2 = 'admin' OR t.id = ?3
+                    OR t.id IN (SELECT tenant_id FROM user_tenants WHERE user_id = ?4))
+             ORDER BY t.created_at, t.name",
+        )
+        .map_err(db)?;
+
+    let branches = stmt
+        .query_map(params![org_id, role, home, user_id], |row| {
+            let id: String = row.get(0)?;
+            Ok(Branch {
+                is_home: home.as_deref() == Some(id.as_str()),
+                is_current: current_tenant_id == Some(id.as_str()),
+                org_id: row.get(1)?,
+                name: row.get(2)?,
+                is_active: row.get(3)?,
+                id,
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(branches)
+}
+
+/// Check the user may operate an active branch
+pub fn require_access(
+    conn: &Connection,
+    user_id: &str,
+    tenant_id: &str,
+) -> Result<Branch, ServiceError> {
+    let branch = list_branches(conn, user_id, None)?
+        .into_iter()
+        .find(|b| b.id == tenant_id)
+        .ok_or_else(|| ServiceError::Unauthorized("Sin acceso a la sucursal".to_string()))?;
+
+    if !branch.is_active {
+        return Err(ServiceError::Validation(
+            "La sucursal está inactiva".to_string(),
+        ));
+    }
+    Ok(branch)
+}
+
+/// Create a branch in the user's organization
+pub fn create_branch(conn: &Connection, user_id: &str, name: &str) -> Result<Branch, ServiceError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ServiceError::Validation(
+            "El nombre de la sucursal es requerido".to_string(),
+        ));
+    }
+    let (org_id, _, _) = user_scope(conn, user_id)?;
+    let id = Uuid::new_v4().to_string();
+
+    conn.execute(
+        "INSERT INTO tenants (id, org_id, name, is_active, created_at) VALUES (?1, ?2, ?3, 1, ?4)",
+        params![id, org_id, name, Utc::now().to_rfc3339()],
+    )
+    .map_err(db)?;
+
+    Ok(Branch {
+        id,
+        org_id,
+        name: name.to_string(),
+        is_active: true,
+        is_home: false,
+        is_current: false,
+    })
+}
+
+/// Grant or revoke a user's access to a branch of the same organization
+pub fn set_branch_access(
+    conn: &Connection,
+    granted_by: &str,
+    user_id: &str,
+    tenant_id: &str,
+    allowed: bool,
+) -> Result<(), ServiceError> {
+    let (org_id, _, _) = user_scope(conn, granted_by)?;
+    let same_org: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM users WHERE id = ?1 AND org_id = ?3)
+                AND EXISTS (SELECT 1 FROM tenants WHERE id = ?2 AND org_id = ?3)",
+            params![user_id, tenant_id, org_id],
+            |row| row.get(0),
+        )
+        .map_err(db)?;
+    if !same_org {
+        return Err(ServiceError::NotFound(
+            "Usuario o sucursal no encontrados".to_string(),
+        ));
+    }
+
+    if allowed {
+        conn.execute(
+            "INSERT OR IGNORE INTO user_tenants (user_id, tenant_id, granted_by, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![user_id, tenant_id, granted_by, Utc::now().to_rfc3339()],
+        )
+    } else {
+        conn.execute(
+            "DELETE FROM user_tenants WHERE user_id = ?1 AND tenant_id = ?2",
+            params![user_id, tenant_id],
+        )
+    }
+    .map_err(db)?;
+    Ok(())
+}
+
+/// Stock of a product in every branch of the organization that carries it
+pub fn branch_stock(
+    conn: &Connection,
+    tenant_id: &str,
+    product_id: &str,
+) -> Result<Vec<BranchStock>, ServiceError> {
+    let (org_id, sku): (String, Option<String>) = conn
+        .query_row(
+            "SELECT t.org_id, NULLIF(TRIM(p.sku), '')
+             FROM products p JOIN tenants t ON t.id = p.tenant_id
+             WHERE p.id = ?1 AND p.tenant_id = ?2",
+            params![product_id, tenant_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(db)?
+        .ok_or_else(|| ServiceError::NotFound("Producto no encontrado".to_string()))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.id, t.name, p.id, COALESCE(p.stock_quantity, 0), COALESCE(p.min_stock, 0),
+                    COALESCE((SELECT SUM(i.quantity)
+                              FROM stock_transfer_items i
+                              JOIN stock_transfers s ON s.id = i.transfer_id
+                              JOIN products src ON src.id = i.product_id
+                              WHERE s.to_tenant_id = t.id AND s.status = 'in_transit'
+                                AND (src.id = p.id OR src.sku = p.sku)), 0)
+             FROM products p
+             JOIN tenants t ON t.id = p.tenant_id
+             WHERE t.org_id = ?1 AND p.is_active = 1
+               AND (p.id = ?2 OR (?3 IS NOT NULL AND p.sku = ?3))
+             ORDER BY t.created_at, t.name",
+        )
+        .map_err(db)?;
+
+    let stock = stmt
+        .query_map(params![org_id, product_id, sku], |row| {
+            Ok(BranchStock {
+                tenant_id: row.get(0)?,
+                tenant_name: row.get(1)?,
+                product_id: row.get(2)?,
+                stock_quantity: row.get(3)?,
+                min_stock: row.get(4)?,
+                incoming_quantity: row.get(5)?,
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(stock)
+}
+
+/// The transfer a stock movement belongs to
+struct TransferRef<'a> {
+    id: &'a str,
+    notes: &'a str,
+    user_id: &'a str,
+    now: &'a str,
+}
+
+/// Change a product's stock in a warehouse of the branch and record it in
+/// the inventory ledger
+fn record_movement(
+    conn: &Connection,
+    tenant_id: &str,
+    warehouse_id: &str,
+    product_id: &str,
+    quantity: f64,
+    transfer: &TransferRef,
+) -> Result<(), ServiceError> {
+    inventory::record(
+        conn,
+        &inventory::StockChange {
+            tenant_id,
+            warehouse_id,
+            product_id,
+            variant_id: None,
+            lot_id: None,
+            quantity,
+        },
+        &inventory::MovementInfo {
+            reference_type: Some(TRANSFER_REFERENCE),
+            reference_id: Some(transfer.id),
+            notes: Some(transfer.notes),
+            user_id: Some(transfer.user_id),
+        },
+        transfer.now,
+    )?;
+    Ok(())
+}
+
+fn tenant_name(conn: &Connection, tenant_id: &str) -> Result<String, ServiceError> {
+    conn.query_row(
+        "SELECT name FROM tenants WHERE id = ?1",
+        [tenant_id],
+        |row| row.get(0),
+    )
+    .map_err(db)
+}
+
+/// Ship stock from the active branch; it is in transit until received
+pub fn create_transfer(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    data: CreateStockTransferDto,
+) -> Result<StockTransfer, ServiceError> {
+    if data.items.is_empty() {
+        return Err(ServiceError::Validation(
+            "El traslado no tiene productos".to_string(),
+        ));
+    }
+    if data.to_tenant_id == tenant_id {
+        return Err(ServiceError::Validation(
+            "La sucursal destino debe ser otra".to_string(),
+        ));
+    }
+
+    let org_id: String = conn
+        .query_row(
+            "SELECT org_id FROM tenants WHERE id = ?1",
+            [tenant_id],
+            |row| row.get(0),
+        )
+        .map_err(db)?;
+    let destination: Option<bool> = conn
+        .query_row(
+            "SELECT COALESCE(is_active, 1) FROM tenants WHERE id = ?1 AND org_id = ?2",
+            params![data.to_tenant_id, org_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db)?;
+    match destination {
+        None => {
+            return Err(ServiceError::NotFound(
+                "Sucursal destino no encontrada".to_string(),
+            ))
+        }
+        Some(false) => {
+            return Err(ServiceError::Validation(
+                "La sucursal destino está inactiva".to_string(),
+            ))
+        }
+        Some(true) => {}
+    }
+
+    let from_warehouse =
+        inventory::resolve_warehouse(conn, tenant_id, data.from_warehouse_id.as_deref())?;
+
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+    let notes = format!("Traslado a {}", tenant_name(conn, &data.to_tenant_id)?);
+
+    let shipment = TransferRef {
+        id: &id,
+        notes: &notes,
+        user_id,
+        now: &now,
+    };
+
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    tx.execute(
+        "INSERT INTO stock_transfers
+            (id, org_id, from_tenant_id, from_warehouse_id, to_tenant_id, status, notes,
+             shipped_by, shipped_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, 'in_transit', ?6, ?7, ?8, ?8)",
+        params![
+            id,
+            org_id,
+            tenant_id,
+            from_warehouse,
+            data.to_tenant_id,
+            data.notes,
+            user_id,
+            now
+        ],
+    )
+    .map_err(db)?;
+
+    for item in &data.items {
+        if item.quantity <= 0.0 {
+            return Err(ServiceError::Validation(
+                "La cantidad a trasladar debe ser mayor a cero".to_string(),
+            ));
+        }
+        let exists: Option<i64> = tx
+            .query_row(
+                "SELECT 1 FROM products WHERE id = ?1 AND tenant_id = ?2",
+                params![item.product_id, tenant_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db)?;
+        if exists.is_none() {
+            return Err(ServiceError::NotFound("Producto no encontrado".to_string()));
+        }
+    }
+
+    // Stock held by drafts and quotes in the warehouse cannot be shipped
+    let lines: Vec<ReservationLine> = data
+        .items
+        .iter()
+        .map(|item| ReservationLine {
+            product_id: &item.product_id,
+            variant_id: None,
+            quantity: item.quantity,
+        })
+        .collect();
+    reservations::check_available(&tx, &from_warehouse, &lines, None)?;
+
+    for item in &data.items {
+        tx.execute(
+            "INSERT INTO stock_transfer_items (id, transfer_id, product_id, quantity)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                Uuid::new_v4().to_string(),
+                id,
+                item.product_id,
+                item.quantity
+            ],
+        )
+        .map_err(db)?;
+        record_movement(
+            &tx,
+            tenant_id,
+            &from_warehouse,
+            &item.product_id,
+            -item.quantity,
+            &shipment,
+        )?;
+    }
+    tx.commit().map_err(db)?;
+
+    get_transfer(conn, &id)
+}
+
+/// Product of the destination branch matching a source product by SKU or
+/// barcode; copied (without stock) when the branch does not carry it yet
+fn destination_product(
+    conn: &Connection,
+    product_id: &str,
+    to_tenant_id: &str,
+    now: &str,
+) -> Result<String, ServiceError> {
+    let existing: Option<String> = conn
+        .query_row(
+            "SELECT d.id FROM products src
+             JOIN products d ON d.tenant_id = ?2
+              AND ((NULLIF(TRIM(src.sku), '') IS NOT NULL AND d.sku = src.sku)
+                   OR (NULLIF(TRIM(src.barcode), '') IS NOT NULL AND d.barcode = src.barcode))
+             WHERE src.id = ?1
+             ORDER BY (d.sku = src.sku) DESC, d.is_active DESC
+             LIMIT 1",
+            params![product_id, to_tenant_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db)?;
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+
+    // Categories, units and types are per branch; the copy starts without them
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO products
+            (id, tenant_id, sku, barcode, name, description, category, unit, unit_price,
+             cost_price, tax_rate, stock_quantity, min_stock, sale_price, margin_percent,
+             margin_amount, supplier_reference, max_stock, image_url, has_variants,
+             track_expiration, cost_method, is_active, created_at, updated_at)
+         SELECT ?1, ?2, sku, barcode, name, description, category, unit, unit_price,
+                cost_price, tax_rate, 0, min_stock, sale_price, margin_percent,
+                margin_amount, supplier_reference, max_stock, image_url, 0,
+                track_expiration, cost_method, 1, ?3, ?3
+         FROM products WHERE id = ?4",
+        params![id, to_tenant_id, now, product_id],
+    )
+    .map_err(db)?;
+    Ok(id)
+}
+
+fn transfer_status(conn: &Connection, id: &str) -> Result<(String, String, String), ServiceError> {
+    conn.query_row(
+        "SELECT from_tenant_id, to_tenant_id, status FROM stock_transfers WHERE id = ?1",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+    .optional()
+    .map_err(db)?
+    .ok_or_else(|| ServiceError::NotFound("Traslado no encontrado".to_string()))
+}
+
+fn transfer_lines(conn: &Connection, id: &str) -> Result<Vec<(String, String, f64)>, ServiceError> {
+    let mut stmt = conn
+        .prepare("SELECT id, product_id, quantity FROM stock_transfer_items WHERE transfer_id = ?1")
+        .map_err(db)?;
+    let lines = stmt
+        .query_map([id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(lines)
+}
+
+/// Receive an in-transit transfer in the active (destination) branch
+pub fn receive_transfer(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    id: &str,
+) -> Result<StockTransfer, ServiceError> {
+    let (from_tenant_id, to_tenant_id, status) = transfer_status(conn, id)?;
+    if to_tenant_id != tenant_id {
+        return Err(ServiceError::Validation(
+            "El traslado se recibe en la sucursal destino".to_string(),
+        ));
+    }
+    if status != "in_transit" {
+        return Err(ServiceError::Validation(
+            "El traslado no está en tránsito".to_string(),
+        ));
+    }
+
+    let now = Utc::now().to_rfc3339();
+    let notes = format!("Traslado desde {}", tenant_name(conn, &from_tenant_id)?);
+    let receipt = TransferRef {
+        id,
+        notes: &notes,
+        user_id,
+        now: &now,
+    };
+
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    let warehouse_id = inventory::default_warehouse(&tx, tenant_id)?;
+    for (item_id, product_id, quantity) in transfer_lines(&tx, id)? {
+        let destination_id = destination_product(&tx, &product_id, tenant_id, &now)?;
+        tx.execute(
+            "UPDATE stock_transfer_items SET destination_product_id = ?1 WHERE id = ?2",
+            params![destination_id, item_id],
+        )
+        .map_err(db)?;
+        record_movement(
+            &tx,
+            tenant_id,
+            &warehouse_id,
+            &destination_id,
+            quantity,
+            &receipt,
+        )?;
+    }
+    tx.execute(
+        "UPDATE stock_transfers SET status = 'received', received_by = ?1, received_at = ?2, updated_at = ?2
+         WHERE id = ?3",
+        params![user_id, now, id],
+    )
+    .map_err(db)?;
+    tx.commit().map_err(db)?;
+
+    get_transfer(conn, id)
+}
+
+/// Cancel an in-transit transfer from the source branch; stock returns there
+pub fn cancel_transfer(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    id: &str,
+) -> Result<StockTransfer, ServiceError> {
+    let (from_tenant_id, _, status) = transfer_status(conn, id)?;
+    if from_tenant_id != tenant_id {
+        return Err(ServiceError::Validation(
+            "El traslado se anula en la sucursal de origen".to_string(),
+        ));
+    }
+    if status != "in_transit" {
+        return Err(ServiceError::Validation(
+            "Solo se pueden anular traslados en tránsito".to_string(),
+        ));
+    }
+
+    let now = Utc::now().to_rfc3339();
+    let cancellation = TransferRef {
+        id,
+        notes: "Traslado anulado",
+        user_id,
+        now: &now,
+    };
+
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    let shipped_from: Option<String> = tx
+        .query_row(
+            "SELECT from_warehouse_id FROM stock_transfers WHERE id = ?1",
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(db)?;
+    let warehouse_id = match shipped_from {
+        Some(warehouse_id) => warehouse_id,
+        None => inventory::default_warehouse(&tx, tenant_id)?,
+    };
+    for (_, product_id, quantity) in transfer_lines(&tx, id)? {
+        record_movement(
+            &tx,
+            tenant_id,
+            &warehouse_id,
+            &product_id,
+            quantity,
+            &cancellation,
+        )?;
+    }
+    tx.execute(
+        "UPDATE stock_transfers SET status = 'cancelled', cancelled_by = ?1, cancelled_at = ?2, updated_at = ?2
+         WHERE id = ?3",
+        params![user_id, now, id],
+    )
+    .map_err(db)?;
+    tx.commit().map_err(db)?;
+
+    get_transfer(conn, id)
+}
+
+const TRANSFER_SELECT: &str = "
+    SELECT s.id, s.org_id, s.from_tenant_id, f.name, s.to_tenant_id, t.name, s.status, s.notes,
+           s.shipped_by, s.shipped_at, s.received_by, s.received_at, s.cancelled_by, s.cancelled_at,
+           s.from_warehouse_id
+    FROM stock_transfers s
+    JOIN tenants f ON f.id = s.from_tenant_id
+    JOIN tenants t ON t.id = s.to_tenant_id";
+
+fn map_transfer(row: &rusqlite::Row) -> rusqlite::Result<StockTransfer> {
+    Ok(StockTransfer {
+        id: row.get(0)?,
+        org_id: row.get(1)?,
+        from_tenant_id: row.get(2)?,
+        from_tenant_name: row.get(3)?,
+        to_tenant_id: row.get(4)?,
+        to_tenant_name: row.get(5)?,
+        status: row.get(6)?,
+        notes: row.get(7)?,
+        shipped_by: row.get(8)?,
+        shipped_at: row.get(9)?,
+        received_by: row.get(10)?,
+        received_at: row.get(11)?,
+        cancelled_by: row.get(12)?,
+        cancelled_at: row.get(13)?,
+        from_warehouse_id: row.get(14)?,
+        items: Vec::new(),
+    })
+}
+
+fn load_items(conn: &Connection, transfer: &mut StockTransfer) -> Result<(), ServiceError> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT i.id, i.product_id, p.name, p.sku, i.destination_product_id, i.quantity
+             FROM stock_transfer_items i
+             JOIN products p ON p.id = i.product_id
+             WHERE i.transfer_id = ?1
+             ORDER BY p.name",
+        )
+        .map_err(db)?;
+    transfer.items = stmt
+        .query_map([&transfer.id], |row| {
+            Ok(StockTransferItem {
+                id: row.get(0)?,
+                product_id: row.get(1)?,
+                product_name: row.get(2)?,
+                sku: row.get(3)?,
+                destination_product_id: row.get(4)?,
+                quantity: row.get(5)?,
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(())
+}
+
+pub fn get_transfer(conn: &Connection, id: &str) -> Result<StockTransfer, ServiceError> {
+    let mut transfer = conn
+        .query_row(
+            &format!("{} WHERE s.id = ?1", TRANSFER_SELECT),
+            [id],
+            map_transfer,
+        )
+        .optional()
+        .map_err(db)?
+        .ok_or_else(|| ServiceError::NotFound("Traslado no encontrado".to_string()))?;
+    load_items(conn, &mut transfer)?;
+    Ok(transfer)
+}
+
+/// Transfers sent from or to a branch, newest first
+pub fn list_transfers(
+    conn: &Connection,
+    tenant_id: &str,
+    status: Option<&str>,
+) -> Result<Vec<StockTransfer>, ServiceError> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} WHERE (s.from_tenant_id = ?1 OR s.to_tenant_id = ?1) AND (?2 IS NULL OR s.status = ?2)
+             ORDER BY s.shipped_at DESC",
+            TRANSFER_SELECT
+        ))
+        .map_err(db)?;
+    let mut transfers = stmt
+        .query_map(params![tenant_id, status], map_transfer)
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    for transfer in &mut transfers {
+        load_items(conn, transfer)?;
+    }
+    Ok(transfers)
+}
+
+fn add_totals(into: &mut BTreeMap<String, CurrencyTotals>, totals: &CurrencyTotals) {
+    let entry = into
+        .entry(totals.currency.clone())
+        .or_insert_with(|| CurrencyTotals {
+            currency: totals.currency.clone(),
+            ..Default::default()
+        });
+    entry.invoice_count += totals.invoice_count;
+    entry.subtotal += totals.subtotal;
+    entry.tax_total += totals.tax_total;
+    entry.total += totals.total;
+    entry.paid_amount += totals.paid_amount;
+}
+
+/// Sales, stock value and transfers of every branch the user can access.
+/// Sales are issued invoices, kept apart per invoice currency.
+pub fn consolidated_report(
+    conn: &Connection,
+    user_id: &str,
+    from_date: Option<String>,
+    to_date: Option<String>,
+) -> Result<ConsolidatedReport, ServiceError> {
+    let (org_id, _, _) = user_scope(conn, user_id)?;
+    let mut report = ConsolidatedReport {
+        org_id,
+        from_date,
+        to_date,
+        branches: Vec::new(),
+        sales: Vec::new(),
+        stock_value: 0.0,
+    };
+    let mut org_sales = BTreeMap::new();
+
+    for branch in list_branches(conn, user_id, None)? {
+        let mut stmt = conn
+            .prepare(
+                "SELECT currency, COUNT(*), COALESCE(SUM(subtotal), 0), COALESCE(SUM(tax_total), 0),
+                        COALESCE(SUM(total), 0), COALESCE(SUM(paid_amount), 0)
+                 FROM billing_invoices
+                 WHERE tenant_id = ?1 AND invoice_type = 'invoice'
+                   AND status IN ('issued', 'partial', 'paid')
+                   AND (?2 IS NULL OR issue_date >= ?2)
+                   AND (?3 IS NULL OR issue_date <= ?3)
+                 GROUP BY currency
+                 ORDER BY currency",
+            )
+            .map_err(db)?;
+        let sales = stmt
+            .query_map(
+                params![branch.id, report.from_date, report.to_date],
+                |row| {
+                    Ok(CurrencyTotals {
+                        currency: row.get(0)?,
+                        invoice_count: row.get(1)?,
+                        subtotal: row.get(2)?,
+                        tax_total: row.get(3)?,
+                        total: row.get(4)?,
+                        paid_amount: row.get(5)?,
+                    })
+                },
+            )
+            .map_err(db)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db)?;
+
+        let (stock_value, low_stock_count): (f64, i64) = conn
+            .query_row(
+                "SELECT COALESCE(SUM(MAX(COALESCE(stock_quantity, 0), 0) * COALESCE(cost_price, 0)), 0),
+                        COALESCE(SUM(CASE WHEN COALESCE(stock_quantity, 0) <= COALESCE(min_stock, 0)
+                                           AND COALESCE(min_stock, 0) > 0 THEN 1 ELSE 0 END), 0)
+                 FROM products WHERE tenant_id = ?1 AND is_active = 1",
+                [&branch.id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(db)?;
+
+        let (transfers_in_transit_out, transfers_in_transit_in): (i64, i64) = conn
+            .query_row(
+                "SELECT COALESCE(SUM(from_tenant_id = ?1), 0), COALESCE(SUM(to_tenant_id = ?1), 0)
+                 FROM stock_transfers WHERE status = 'in_transit'",
+                [&branch.id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(db)?;
+
+        for totals in &sales {
+            add_totals(&mut org_sales, totals);
+        }
+        report.stock_value += stock_value;
+        report.branches.push(BranchReport {
+            tenant_id: branch.id,
+            tenant_name: branch.name,
+            sales,
+            stock_value,
+            low_stock_count,
+            transfers_in_transit_out,
+            transfers_in_transit_in,
+        });
+    }
+
+    report.sales = org_sales.into_values().collect();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::branch::TransferItemDto;
+    use crate::models::warehouse::CreateWarehouseDto;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t2', 'o1', 'Este');
+             INSERT INTO users (id, org_id, tenant_id, email, password_hash, name, role)
+                 VALUES ('u1', 'o1', 't1', 'op@x.com', 'x', 'Op', 'operator');
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, cost_price, stock_quantity, is_active)
+                 VALUES ('p1', 't1', 'SKU-1', 'Arroz', 2.0, 1.5, 0, 1);",
+        )
+        .unwrap();
+        let warehouse_id = inventory::default_warehouse(&conn, "t1").unwrap();
+        put_stock(&conn, &warehouse_id, 10.0);
+        conn
+    }
+
+    fn put_stock(conn: &Connection, warehouse_id: &str, quantity: f64) {
+        inventory::apply(
+            conn,
+            &inventory::StockChange {
+                tenant_id: "t1",
+                warehouse_id,
+                product_id: "p1",
+                variant_id: None,
+                lot_id: None,
+                quantity,
+            },
+            &Utc::now().to_rfc3339(),
+        )
+        .unwrap();
+    }
+
+    fn available_in(conn: &Connection, warehouse_id: &str) -> f64 {
+        reservations::available(conn, warehouse_id, "p1", None, None).unwrap()
+    }
+
+    fn transfer_dto(from_warehouse_id: Option<&str>, quantity: f64) -> CreateStockTransferDto {
+        CreateStockTransferDto {
+            to_tenant_id: "t2".to_string(),
+            from_warehouse_id: from_warehouse_id.map(str::to_string),
+            items: vec![TransferItemDto {
+                product_id: "p1".to_string(),
+                quantity,
+            }],
+            notes: None,
+        }
+    }
+
+    fn stock(conn: &Connection, tenant_id: &str) -> f64 {
+        conn.query_row(
+            "SELECT stock_quantity FROM products WHERE tenant_id = ?1 AND sku = 'SKU-1'",
+            [tenant_id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_branch_access_requires_grant() {
+        let conn = setup();
+        assert_eq!(list_branches(&conn, "u1", Some("t1")).unwrap().len(), 1);
+        assert!(require_access(&conn, "u1", "t2").is_err());
+
+        set_branch_access(&conn, "u1", "u1", "t2", true).unwrap();
+        let branches = list_branches(&conn, "u1", Some("t2")).unwrap();
+        assert_eq!(branches.len(), 2);
+        assert!(branches
+            .iter()
+            .any(|b| b.id == "t2" && b.is_current && !b.is_home));
+        assert!(require_access(&conn, "u1", "t2").is_ok());
+    }
+
+    #[test]
+    fn test_transfer_moves_stock_through_transit() {
+        let conn = setup();
+        let transfer = create_transfer(&conn, "t1", "u1", transfer_dto(None, 4.0)).unwrap();
+        assert_eq!(transfer.status, "in_transit");
+        assert_eq!(stock(&conn, "t1"), 6.0);
+
+        let in_transit = branch_stock(&conn, "t1", "p1").unwrap();
+        assert_eq!(in_transit.len(), 1); // Destination does not carry it yet
+        assert!(receive_transfer(&conn, "t1", "u1", &transfer.id).is_err());
+
+        let received = receive_transfer(&conn, "t2", "u1", &transfer.id).unwrap();
+        assert_eq!(received.status, "received");
+        assert_eq!(stock(&conn, "t2"), 4.0);
+        assert!(cancel_transfer(&conn, "t1", "u1", &transfer.id).is_err());
+
+        // Both sides are in the ledger
+        let movements: Vec<(String, String)> = conn
+            .prepare(
+                "SELECT tenant_id, movement_type FROM inventory_movements
+                 WHERE reference_id = ?1 ORDER BY tenant_id",
+            )
+            .unwrap()
+            .query_map([&transfer.id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            movements,
+            vec![
+                ("t1".to_string(), "SALIDA".to_string()),
+                ("t2".to_string(), "ENTRADA".to_string())
+            ]
+        );
+        assert_eq!(branch_stock(&conn, "t1", "p1").unwrap().len(), 2);
+
+        // Insufficient stock is refused without touching anything
+        let refused = create_transfer(&conn, "t1", "u1", transfer_dto(None, 50.0));
+        assert!(refused.is_err());
+        assert_eq!(stock(&conn, "t1"), 6.0);
+        assert_eq!(list_transfers(&conn, "t1", None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_transfer_ships_from_and_returns_to_its_warehouse() {
+        let conn = setup();
+        let main = inventory::default_warehouse(&conn, "t1").unwrap();
+        let back = inventory::create_warehouse(
+            &conn,
+            "t1",
+            CreateWarehouseDto {
+                code: "DEP".to_string(),
+                name: "Depósito".to_string(),
+                is_default: Some(false),
+            },
+        )
+        .unwrap()
+        .id;
+        put_stock(&conn, &back, 3.0);
+
+        // The branch has 13, but the back warehouse only 3
+        assert!(create_transfer(&conn, "t1", "u1", transfer_dto(Some(&back), 5.0)).is_err());
+
+        let transfer = create_transfer(&conn, "t1", "u1", transfer_dto(Some(&back), 3.0)).unwrap();
+        assert_eq!(transfer.from_warehouse_id.as_deref(), Some(back.as_str()));
+        assert_eq!(available_in(&conn, &back), 0.0);
+        assert_eq!(available_in(&conn, &main), 10.0);
+
+        cancel_transfer(&conn, "t1", "u1", &transfer.id).unwrap();
+        assert_eq!(available_in(&conn, &back), 3.0);
+        assert_eq!(available_in(&conn, &main), 10.0);
+
+        // Stock held by a draft cannot be shipped
+        reservations::reserve(
+            &conn,
+            &reservations::Hold {
+                tenant_id: "t1",
+                warehouse_id: &main,
+                reference_type: "invoice",
+                reference_id: "inv-1",
+                expires_at: "2999-01-01T00:00:00+00:00",
+                user_id: Some("u1"),
+            },
+            &[ReservationLine {
+                product_id: "p1",
+                variant_id: None,
+                quantity: 8.0,
+            }],
+        )
+        .unwrap();
+        assert!(create_transfer(&conn, "t1", "u1", transfer_dto(None, 3.0)).is_err());
+        assert!(create_transfer(&conn, "t1", "u1", transfer_dto(None, 2.0)).is_ok());
+        assert_eq!(available_in(&conn, &main), 0.0);
+    }
+}
@@ -1,12 +1,21 @@
 use crate::models::cash_register::{
     AddMovementDto, CashMovement, CashRegister, CashRegisterSession, CloseSessionDto,
-    OpenSessionDto,
+    OpenSessionDto, PosTenderDto,
 };
+use crate::models::Payment;
+use crate::services::{cash_reports, exchange_rates, inventory};
 use crate::state::ServiceError;
 use chrono::Utc;
 use rusqlite::{params, Connection, OptionalExtension};
+use rust_decimal::prelude::*;
 use uuid::Uuid;
 
+/// Currencies a drawer holds cash in
+const DRAWER_CURRENCIES: [&str; 3] = ["USD", "VES", "EUR"];
+
+/// Ways a point-of-sale sale can be paid
+const TENDER_METHODS: [&str; 4] = ["cash", "card", "mobile", "transfer"];
+
 /// Create a new cash register
 pub fn create_register(
     conn: &Connection,
@@ -15,10 +24,11 @@ pub fn create_register(
 ) -> Result<CashRegister, ServiceError> {
     let id = Uuid::new_v4().to_string();
     let now = Utc::now().to_rfc3339();
+    let warehouse_id = inventory::default_warehouse(conn, tenant_id)?;
 
     conn.execute(
-        "INSERT INTO cash_registers (id, tenant_id, name, status, created_at, updated_at) VALUES (?1, ?2, ?3, 'closed', ?4, ?4)",
-        params![id, tenant_id, name, now],
+        "INSERT INTO cash_registers (id, tenant_id, name, status, warehouse_id, created_at, updated_at) VALUES (?1, ?2, ?3, 'closed', ?4, ?5, ?5)",
+        params![id, tenant_id, name, warehouse_id, now],
     )
     .map_err(|e| ServiceError::Database(e.to_string()))?;
 
@@ -28,11 +38,32 @@ pub fn create_register(
         name: name.to_string(),
         status: "closed".to_string(),
         current_session_id: None,
+        warehouse_id: Some(warehouse_id),
         created_at: now.clone(),
         updated_at: now,
     })
 }
 
+/// Set the warehouse a register sells from
+pub fn set_register_warehouse(
+    conn: &Connection,
+    tenant_id: &str,
+    register_id: &str,
+    warehouse_id: &str,
+) -> Result<(), ServiceError> {
+    let warehouse_id = inventory::resolve_warehouse(conn, tenant_id, Some(warehouse_id))?;
+    let updated = conn
+        .execute(
+            "UPDATE cash_registers SET warehouse_id = ?1, updated_at = ?2 WHERE id = ?3 AND tenant_id = ?4",
+            params![warehouse_id, Utc::now().to_rfc3339(), register_id, tenant_id],
+        )
+        .map_err(|e| ServiceError::Database(e.to_string()))?;
+    if updated == 0 {
+        return Err(ServiceError::NotFound("Caja no encontrada".to_string()));
+    }
+    Ok(())
+}
+
 /// Start a new session (Open Register)
 pub fn open_session(
     conn: &Connection,
@@ -96,19 +127,62 @@ pub fn open_session(
     get_session(conn, &session_id)
 }
 
-/// Close a session
+/// Close a session, storing its denomination counts and Z-report
 pub fn close_session(
     conn: &Connection,
     tenant_id: &str,
+    user_id: &str,
     data: CloseSessionDto,
 ) -> Result<CashRegisterSession, ServiceError> {
     let now = Utc::now().to_rfc3339();
 
+    let status: Option<String> = conn
+        .query_row(
+            "SELECT status FROM cash_register_sessions WHERE id = ?1 AND tenant_id = ?2",
+            params![data.session_id, tenant_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| ServiceError::Database(e.to_string()))?;
+    match status.as_deref() {
+        None => {
+            return Err(ServiceError::NotFound(
+                "Sesión de caja no encontrada".to_string(),
+            ))
+        }
+        Some("active") => {}
+        Some(_) => {
+            return Err(ServiceError::Validation(
+                "La sesión ya está cerrada".to_string(),
+            ))
+        }
+    }
+
+    // Counted bills and coins, when given, are the closing amounts
+    let counts = cash_reports::price_counts(&data.counts)?;
+    let (closing_usd, closing_ves, closing_eur) = if counts.is_empty() {
+        (
+            data.closing_amount_usd,
+            data.closing_amount_ves,
+            data.closing_amount_eur,
+        )
+    } else {
+        (
+            cash_reports::counted_total(&counts, "USD"),
+            cash_reports::counted_total(&counts, "VES"),
+            cash_reports::counted_total(&counts, "EUR"),
+        )
+    };
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|e| ServiceError::Database(e.to_string()))?;
+
     // 1. Calculate Expected Totals
-    let (exp_usd, exp_ves, exp_eur) = calculate_expected_totals(conn, &data.session_id)?;
+    let (exp_usd, exp_ves, exp_eur) = calculate_expected_totals(&tx, &data.session_id)?;
 
     // 2. Update Session
-    conn.execute(
+    tx.execute(
         r#"
         UPDATE cash_register_sessions SET 
             status = 'closed',
@@ -125,9 +199,9 @@ pub fn close_session(
         "#,
         params![
             now,
-            data.closing_amount_usd,
-            data.closing_amount_ves,
-            data.closing_amount_eur,
+            closing_usd,
+            closing_ves,
+            closing_eur,
             exp_usd,
             exp_ves,
             exp_eur,
@@ -137,9 +211,10 @@ pub fn close_session(
         ],
     )
     .map_err(|e| ServiceError::Database(e.to_string()))?;
+    cash_reports::record_counts(&tx, tenant_id, &data.session_id, &counts, &now)?;
 
     // 3. Release Register
-    let register_id: String = conn
+    let register_id: String = tx
         .query_row(
             "SELECT register_id FROM cash_register_sessions WHERE id = ?1",
             params![data.session_id],
@@ -147,12 +222,17 @@ pub fn close_session(
         )
         .map_err(|e| ServiceError::Database(e.to_string()))?;
 
-    conn.execute(
+    tx.execute(
         "UPDATE cash_registers SET status = 'closed', current_session_id = NULL, updated_at = ?1 WHERE id = ?2",
         params![now, register_id],
     )
     .map_err(|e| ServiceError::Database(e.to_string()))?;
 
+    // 4. Z-report, frozen with the session
+    cash_reports::take_report(&tx, tenant_id, user_id, &data.session_id, "Z", &now)?;
+    tx.commit()
+        .map_err(|e| ServiceError::Database(e.to_string()))?;
+
     get_session(conn, &data.session_id)
 }
 
@@ -203,13 +283,51 @@ pub fn add_movement(
     })
 }
 
+/// A movement, with the name of the register it was made at
+pub fn get_movement(
+    conn: &Connection,
+    tenant_id: &str,
+    id: &str,
+) -> Result<(CashMovement, String), ServiceError> {
+    conn.query_row(
+        "SELECT m.id, m.tenant_id, m.session_id, m.user_id, m.type, m.amount, m.currency,
+                m.exchange_rate, m.reason, m.reference, m.created_at, r.name
+         FROM cash_movements m
+         JOIN cash_register_sessions s ON s.id = m.session_id
+         JOIN cash_registers r ON r.id = s.register_id
+         WHERE m.id = ?1 AND m.tenant_id = ?2",
+        params![id, tenant_id],
+        |row| {
+            Ok((
+                CashMovement {
+                    id: row.get(0)?,
+                    tenant_id: row.get(1)?,
+                    session_id: row.get(2)?,
+                    user_id: row.get(3)?,
+                    movement_type: row.get(4)?,
+                    amount: row.get(5)?,
+                    currency: row.get(6)?,
+                    exchange_rate: row.get(7)?,
+                    reason: row.get(8)?,
+                    reference: row.get(9)?,
+                    created_at: row.get(10)?,
+                },
+                row.get(11)?,
+            ))
+        },
+    )
+    .optional()
+    .map_err(|e| ServiceError::Database(e.to_string()))?
+    .ok_or_else(|| ServiceError::NotFound("Movimiento no encontrado".to_string()))
+}
+
 /// List all cash registers
 pub fn list_registers(
     conn: &Connection,
     tenant_id: &str,
 ) -> Result<Vec<CashRegister>, ServiceError> {
     let mut stmt = conn
-        .prepare("SELECT id, name, status, current_session_id, created_at, updated_at FROM cash_registers WHERE tenant_id = ?1")
+        .prepare("SELECT id, name, status, current_session_id, warehouse_id, created_at, updated_at FROM cash_registers WHERE tenant_id = ?1")
         .map_err(|e| ServiceError::Database(e.to_string()))?;
 
     let rows = stmt
@@ -220,8 +338,9 @@ pub fn list_registers(
                 name: row.get(1)?,
                 status: row.get(2)?,
                 current_session_id: row.get(3)?,
-                created_at: row.get(4)?,
-                updated_at: row.get(5)?,
+                warehouse_id: row.get(4)?,
+                created_at: row.get(5)?,
+                updated_at: row.get(6)?,
             })
         })
         .map_err(|e| ServiceError::Database(e.to_string()))?;
@@ -255,8 +374,216 @@ pub fn get_active_session(
     }
 }
 
+/// Active session of the user, required to ring up a sale
+pub fn require_active_session(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+) -> Result<CashRegisterSession, ServiceError> {
+    get_active_session(conn, tenant_id, user_id)?
+        .ok_or_else(|| ServiceError::Validation("No hay una caja abierta".to_string()))
+}
+
+/// An issued point-of-sale invoice and how it is paid
+pub struct Settlement<'a> {
+    pub session_id: &'a str,
+    pub invoice_id: &'a str,
+    pub tenders: &'a [PosTenderDto],
+    pub change_currency: Option<&'a str>,
+}
+
+/// Pay an issued point-of-sale invoice in full with split tenders, taken at
+/// the active `session_id`, and link the invoice to its register.
+///
+/// Tenders are converted to the invoice currency at the current rates.
+/// Card, mobile and transfer tenders are applied first; whatever the tenders
+/// exceed the total by is handed back from the cash tenders as change, in
+/// `change_currency` (the invoice's by default).
+pub fn settle_sale(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    settlement: &Settlement,
+    now: &str,
+) -> Result<Vec<Payment>, ServiceError> {
+    let Settlement {
+        session_id,
+        invoice_id,
+        tenders,
+        change_currency,
+    } = *settlement;
+    let register_id: String = conn
+        .query_row(
+            "SELECT register_id FROM cash_register_sessions
+             WHERE id = ?1 AND tenant_id = ?2 AND status = 'active'",
+            params![session_id, tenant_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| ServiceError::Database(e.to_string()))?
+        .ok_or_else(|| ServiceError::Validation("La sesión de caja no está activa".to_string()))?;
+    let (status, currency, total): (String, String, f64) = conn
+        .query_row(
+            "SELECT status, currency, total FROM billing_invoices WHERE id = ?1 AND tenant_id = ?2",
+            params![invoice_id, tenant_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(|e| ServiceError::Database(e.to_string()))?
+        .ok_or_else(|| ServiceError::NotFound("Factura no encontrada".to_string()))?;
+    if status != "issued" {
+        return Err(ServiceError::Validation(
+            "Solo se cobran facturas emitidas".to_string(),
+        ));
+    }
+    if tenders.is_empty() {
+        return Err(ServiceError::Validation(
+            "Indique al menos una forma de pago".to_string(),
+        ));
+    }
+    let change_currency = change_currency
+        .map(|c| c.trim().to_uppercase())
+        .unwrap_or_else(|| currency.clone());
+    if !DRAWER_CURRENCIES.contains(&change_currency.as_str()) {
+        return Err(ServiceError::Validation(format!(
+            "Moneda de vuelto inválida: {}",
+            change_currency
+        )));
+    }
+
+    // Value of each tender in the invoice currency
+    let mut lines = Vec::with_capacity(tenders.len());
+    for tender in tenders {
+        let method = tender.payment_method.trim().to_lowercase();
+        let tender_currency = tender.currency.trim().to_uppercase();
+        if !TENDER_METHODS.contains(&method.as_str()) {
+            return Err(ServiceError::Validation(format!(
+                "Forma de pago inválida: {}",
+                tender.payment_method
+            )));
+        }
+        if !DRAWER_CURRENCIES.contains(&tender_currency.as_str()) {
+            return Err(ServiceError::Validation(format!(
+                "Moneda inválida: {}",
+                tender.currency
+            )));
+        }
+        let amount = Decimal::from_f64(tender.amount)
+            .filter(|amount| *amount > Decimal::ZERO)
+            .ok_or_else(|| {
+                ServiceError::Validation("Los montos deben ser mayores a cero".to_string())
+            })?;
+        let factor = exchange_rates::conversion(conn, tenant_id, &tender_currency, &currency)?;
+        lines.push((
+            tender,
+            method,
+            tender_currency,
+            (amount * factor).round_dp(2),
+        ));
+    }
+
+    let total = Decimal::from_f64(total).unwrap_or_default().round_dp(2);
+    let tendered: Decimal = lines.iter().map(|line| line.3).sum();
+    let cash: Decimal = lines
+        .iter()
+        .filter(|line| line.1 == "cash")
+        .map(|line| line.3)
+        .sum();
+    if tendered + Decimal::new(1, 2) < total {
+        return Err(ServiceError::Validation(format!(
+            "Pago insuficiente: faltan {:.2} {}",
+            total - tendered,
+            currency
+        )));
+    }
+    let excess = (tendered - total).max(Decimal::ZERO);
+    if excess > cash {
+        return Err(ServiceError::Validation(
+            "Solo se da vuelto de pagos en efectivo".to_string(),
+        ));
+    }
+    let change = (excess
+        * exchange_rates::conversion(conn, tenant_id, &currency, &change_currency)?)
+    .round_dp(2);
+
+    // Non-cash tenders go in whole; cash covers the rest
+    let mut order: Vec<usize> = (0..lines.len()).collect();
+    order.sort_by_key(|&i| lines[i].1 == "cash");
+    let mut remaining = total;
+    let mut applied = vec![Decimal::ZERO; lines.len()];
+    for i in order {
+        applied[i] = lines[i].3.min(remaining);
+        remaining -= applied[i];
+    }
+
+    let payment_date = now.get(..10).unwrap_or(now).to_string();
+    let mut payments = Vec::with_capacity(lines.len());
+    for ((tender, method, tender_currency, _), amount) in lines.into_iter().zip(applied) {
+        let payment = Payment {
+            id: Uuid::new_v4().to_string(),
+            tenant_id: tenant_id.to_string(),
+            invoice_id: invoice_id.to_string(),
+            amount: amount.to_f64().unwrap_or_default(),
+            currency: tender_currency.clone(),
+            received_amount: Some(tender.amount),
+            exchange_rate: exchange_rates::latest_rate(conn, tenant_id, &tender_currency)?
+                .unwrap_or(1.0),
+            payment_method: method,
+            reference: tender.reference.clone(),
+            bank_account_id: tender.bank_account_id.clone(),
+            payment_date: payment_date.clone(),
+            notes: None,
+            session_id: Some(session_id.to_string()),
+            created_by: user_id.to_string(),
+            created_at: now.to_string(),
+        };
+        conn.execute(
+            "INSERT INTO billing_payments (id, tenant_id, invoice_id, amount, currency, exchange_rate,
+             payment_method, reference, bank_account_id, payment_date, notes, created_by, created_at,
+             received_amount, session_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, NULL, ?11, ?12, ?13, ?14)",
+            params![
+                payment.id,
+                tenant_id,
+                invoice_id,
+                payment.amount,
+                payment.currency,
+                payment.exchange_rate,
+                payment.payment_method,
+                payment.reference,
+                payment.bank_account_id,
+                payment.payment_date,
+                user_id,
+                now,
+                payment.received_amount,
+                session_id
+            ],
+        )
+        .map_err(|e| ServiceError::Database(e.to_string()))?;
+        payments.push(payment);
+    }
+
+    let paid = (total - remaining).to_f64().unwrap_or_default();
+    conn.execute(
+        "UPDATE billing_invoices SET paid_amount = ?1, status = 'paid', change_amount = ?2,
+         change_currency = ?3, register_id = ?4, session_id = ?5, updated_at = ?6 WHERE id = ?7",
+        params![
+            paid,
+            change.to_f64().unwrap_or_default(),
+            change_currency,
+            register_id,
+            session_id,
+            now,
+            invoice_id
+        ],
+    )
+    .map_err(|e| ServiceError::Database(e.to_string()))?;
+
+    Ok(payments)
+}
+
 // Helpers
-fn get_session(conn: &Connection, id: &str) -> Result<CashRegisterSession, ServiceError> {
+pub fn get_session(conn: &Connection, id: &str) -> Result<CashRegisterSession, ServiceError> {
     conn.query_row(
         r#"
         SELECT 
@@ -300,7 +627,7 @@ fn get_session(conn: &Connection, id: &str) -> Result<CashRegisterSession, Servi
     .map_err(|e| ServiceError::Database(e.to_string()))
 }
 
-fn calculate_expected_totals(
+pub(crate) fn calculate_expected_totals(
     conn: &Connection,
     session_id: &str,
 ) -> Result<(f64, f64, f64), ServiceError> {
@@ -341,16 +668,19 @@ fn calculate_expected_totals(
         }
     }
 
-    // Sales (Payments within session)
+    // Sales: cash taken at the session, less the change handed back
     let mut sales_usd = 0.0;
     let mut sales_ves = 0.0;
     let mut sales_eur = 0.0;
 
-    // TODO: Query billing_payments where session_id = current.
-    // This assumes payments logic properly assigns session_id.
-
     let mut p_stmt = conn
-        .prepare("SELECT amount, currency FROM billing_payments WHERE session_id = ?1")
+        .prepare(
+            "SELECT COALESCE(received_amount, amount), currency FROM billing_payments
+             WHERE session_id = ?1 AND payment_method = 'cash'
+             UNION ALL
+             SELECT -change_amount, change_currency FROM billing_invoices
+             WHERE session_id = ?1 AND change_amount > 0",
+        )
         .map_err(|e| ServiceError::Database(e.to_string()))?;
 
     let p_rows = p_stmt
@@ -375,3 +705,92 @@ fn calculate_expected_totals(
         open_eur + mov_eur + sales_eur,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tender(method: &str, currency: &str, amount: f64) -> PosTenderDto {
+        PosTenderDto {
+            payment_method: method.to_string(),
+            currency: currency.to_string(),
+            amount,
+            reference: None,
+            bank_account_id: None,
+        }
+    }
+
+    #[test]
+    fn test_split_tenders_with_change_feed_expected_totals() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO clients (id, tenant_id, name) VALUES ('cl1', 't1', 'Cliente');
+             INSERT INTO exchange_rates (id, tenant_id, currency, rate, rate_date, created_at)
+                 VALUES ('r1', 't1', 'USD', 40, '2026-01-01', '2026-01-01');
+             INSERT INTO cash_registers (id, tenant_id, name, status, created_at, updated_at)
+                 VALUES ('c1', 't1', 'Caja 1', 'closed', '2026-01-01', '2026-01-01');
+             INSERT INTO billing_invoices (id, tenant_id, invoice_number, invoice_type, status, client_id,
+                 client_name, currency, exchange_rate, issue_date, total, created_by, created_at, updated_at)
+                 VALUES ('i1', 't1', 'FAC-1', 'invoice', 'issued', 'cl1', 'Cliente', 'USD', 40,
+                         '2026-01-01', 25, 'u1', '2026-01-01', '2026-01-01');",
+        )
+        .unwrap();
+        let session = open_session(
+            &conn,
+            "t1",
+            "u1",
+            OpenSessionDto {
+                register_id: "c1".to_string(),
+                opening_amount_usd: 10.0,
+                opening_amount_ves: 500.0,
+                opening_amount_eur: 0.0,
+                notes: None,
+                exchange_rate_ves: 40.0,
+                exchange_rate_eur: 0.0,
+            },
+        )
+        .unwrap();
+        let settle = |tenders: &[PosTenderDto]| {
+            settle_sale(
+                &conn,
+                "t1",
+                "u1",
+                &Settlement {
+                    session_id: &session.id,
+                    invoice_id: "i1",
+                    tenders,
+                    change_currency: Some("VES"),
+                },
+                "2026-01-01T10:00:00Z",
+            )
+        };
+
+        // Short, and change that only a card could give
+        assert!(settle(&[tender("cash", "USD", 10.0)]).is_err());
+        assert!(settle(&[tender("card", "USD", 30.0)]).is_err());
+
+        // 400 Bs by card (10 USD) and a 20 USD bill for 25 USD: 5 USD back as 200 Bs
+        let payments =
+            settle(&[tender("cash", "USD", 20.0), tender("card", "VES", 400.0)]).unwrap();
+        assert_eq!(payments[0].amount, 15.0);
+        assert_eq!(payments[0].received_amount, Some(20.0));
+        assert_eq!(payments[1].amount, 10.0);
+        let (paid, status, change): (f64, String, f64) = conn
+            .query_row(
+                "SELECT paid_amount, status, change_amount FROM billing_invoices WHERE id = 'i1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((paid, status.as_str(), change), (25.0, "paid", 200.0));
+
+        // Cards never reach the drawer; the change left it
+        assert_eq!(
+            calculate_expected_totals(&conn, &session.id).unwrap(),
+            (30.0, 300.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,949 @@
+//! Cash Session Reports
+//!
+//! An X-report snapshots an open session without closing it; the Z-report
+//! is taken once, as the session closes. Both are stored as JSON next to
+//! their SHA-256 and, like the closing denomination counts, database
+//! triggers keep them from ever being changed or deleted.
+
+use crate::models::cash_register::{
+    CashDenomination, CashMovement, CurrencyBalance, DenominationCount, DenominationCountDto,
+    PaymentMethodTotal, RateUsed, RefundTotal, SalesTotal, SessionReport, TaxTotal,
+};
+use crate::services::cash_register;
+use crate::state::ServiceError;
+use rusqlite::{params, Connection, OptionalExtension};
+use rust_decimal::prelude::*;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+fn db(e: rusqlite::Error) -> ServiceError {
+    ServiceError::Database(e.to_string())
+}
+
+/// Bills, then coins, of each drawer currency
+const DENOMINATIONS: [(&str, &[f64], &[f64]); 3] = [
+    (
+        "USD",
+        &[100.0, 50.0, 20.0, 10.0, 5.0, 2.0, 1.0],
+        &[0.25, 0.10, 0.05, 0.01],
+    ),
+    (
+        "VES",
+        &[500.0, 200.0, 100.0, 50.0, 20.0, 10.0, 5.0],
+        &[1.0, 0.50],
+    ),
+    (
+        "EUR",
+        &[500.0, 200.0, 100.0, 50.0, 20.0, 10.0, 5.0],
+        &[2.0, 1.0, 0.50, 0.20, 0.10, 0.05, 0.02, 0.01],
+    ),
+];
+
+/// Bills and coins that can be counted at close
+pub fn denominations() -> Vec<CashDenomination> {
+    let mut all = Vec::new();
+    for (currency, bills, coins) in DENOMINATIONS {
+        for (kind, values) in [("bill", bills), ("coin", coins)] {
+            all.extend(values.iter().map(|denomination| CashDenomination {
+                currency: currency.to_string(),
+                denomination: *denomination,
+                kind: kind.to_string(),
+            }));
+        }
+    }
+    all
+}
+
+/// Validate counted bills and coins and price them; zero quantities are
+/// dropped and repeated denominations added up
+pub fn price_counts(
+    counts: &[DenominationCountDto],
+) -> Result<Vec<DenominationCount>, ServiceError> {
+    let catalog = denominations();
+    let mut priced: Vec<DenominationCount> = Vec::new();
+    for count in counts {
+        let currency = count.currency.trim().to_uppercase();
+        let known = catalog
+            .iter()
+            .find(|d| d.currency == currency && (d.denomination - count.denomination).abs() < 1e-9)
+            .ok_or_else(|| {
+                ServiceError::Validation(format!(
+                    "Denominación inválida: {} {}",
+                    count.denomination, currency
+                ))
+            })?;
+        if count.quantity < 0 {
+            return Err(ServiceError::Validation(
+                "Las cantidades no pueden ser negativas".to_string(),
+            ));
+        }
+        if count.quantity == 0 {
+            continue;
+        }
+        match priced
+            .iter_mut()
+            .find(|p| p.currency == currency && p.denomination == known.denomination)
+        {
+            Some(existing) => existing.quantity += count.quantity,
+            None => priced.push(DenominationCount {
+                currency,
+                denomination: known.denomination,
+                kind: known.kind.clone(),
+                quantity: count.quantity,
+                amount: 0.0,
+            }),
+        }
+    }
+    for count in &mut priced {
+        count.amount = (Decimal::from_f64(count.denomination).unwrap_or_default()
+            * Decimal::from(count.quantity))
+        .round_dp(2)
+        .to_f64()
+        .unwrap_or_default();
+    }
+    Ok(priced)
+}
+
+/// Counted total of a currency
+pub fn counted_total(counts: &[DenominationCount], currency: &str) -> f64 {
+    counts
+        .iter()
+        .filter(|count| count.currency == currency)
+        .map(|count| Decimal::from_f64(count.amount).unwrap_or_default())
+        .sum::<Decimal>()
+        .to_f64()
+        .unwrap_or_default()
+}
+
+/// Store the closing counts of a session
+pub fn record_counts(
+    conn: &Connection,
+    tenant_id: &str,
+    session_id: &str,
+    counts: &[DenominationCount],
+    now: &str,
+) -> Result<(), ServiceError> {
+    for count in counts {
+        conn.execute(
+            "INSERT INTO cash_session_counts (id, tenant_id, session_id, currency, denomination, kind,
+             quantity, amount, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                Uuid::new_v4().to_string(),
+                tenant_id,
+                session_id,
+                count.currency,
+                count.denomination,
+                count.kind,
+                count.quantity,
+                count.amount,
+                now
+            ],
+        )
+        .map_err(db)?;
+    }
+    Ok(())
+}
+
+/// Sum of `amount` per currency from a two-column (currency, amount) query
+fn per_currency(
+    conn: &Connection,
+    sql: &str,
+    session_id: &str,
+) -> Result<Vec<(String, f64)>, ServiceError> {
+    let mut stmt = conn.prepare(sql).map_err(db)?;
+    let rows = stmt
+        .query_map([session_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(rows)
+}
+
+fn amount_in(rows: &[(String, f64)], currency: &str) -> f64 {
+    rows.iter()
+        .filter(|(c, _)| c == currency)
+        .map(|(_, amount)| amount)
+        .sum()
+}
+
+fn round(value: f64) -> f64 {
+    Decimal::from_f64(value)
+        .unwrap_or_default()
+        .round_dp(2)
+        .to_f64()
+        .unwrap_or_default()
+}
+
+/// Build, but do not store, a report of the session as it stands
+fn build_report(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    session_id: &str,
+    report_type: &str,
+    now: &str,
+) -> Result<SessionReport, ServiceError> {
+    let session = cash_register::get_session(conn, session_id)
+        .ok()
+        .filter(|session| session.tenant_id == tenant_id)
+        .ok_or_else(|| ServiceError::NotFound("Sesión de caja no encontrada".to_string()))?;
+    let register_name: String = conn
+        .query_row(
+            "SELECT name FROM cash_registers WHERE id = ?1",
+            [&session.register_id],
+            |row| row.get(0),
+        )
+        .map_err(db)?;
+    let user_name: Option<String> = conn
+        .query_row(
+            "SELECT name FROM users WHERE id = ?1",
+            [&session.user_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db)?;
+
+    let report_number: i64 = if report_type == "Z" {
+        conn.query_row(
+            "SELECT COALESCE(MAX(report_number), 0) + 1 FROM cash_session_reports
+             WHERE register_id = ?1 AND report_type = 'Z'",
+            [&session.register_id],
+            |row| row.get(0),
+        )
+    } else {
+        conn.query_row(
+            "SELECT COALESCE(MAX(report_number), 0) + 1 FROM cash_session_reports
+             WHERE session_id = ?1 AND report_type = 'X'",
+            [session_id],
+            |row| row.get(0),
+        )
+    }
+    .map_err(db)?;
+
+    // Drawer: opening, cash taken, change handed back and movements
+    let cash_sales = per_currency(
+        conn,
+        "SELECT currency, COALESCE(received_amount, amount) FROM billing_payments
+         WHERE session_id = ?1 AND payment_method = 'cash'",
+        session_id,
+    )?;
+    let change = per_currency(
+        conn,
+        "SELECT change_currency, change_amount FROM billing_invoices
+         WHERE session_id = ?1 AND change_amount > 0",
+        session_id,
+    )?;
+    let deposits = per_currency(
+        conn,
+        "SELECT currency, amount FROM cash_movements WHERE session_id = ?1 AND type = 'deposit'",
+        session_id,
+    )?;
+    let withdrawals = per_currency(
+        conn,
+        "SELECT currency, amount FROM cash_movements WHERE session_id = ?1 AND type != 'deposit'",
+        session_id,
+    )?;
+    // What the drawer should hold: fixed at close for a Z, so it always
+    // matches the session; computed the same way for an X on an open session
+    let closed = report_type == "Z";
+    let (expected_usd, expected_ves, expected_eur) = if closed {
+        (
+            session.expected_amount_usd.unwrap_or_default(),
+            session.expected_amount_ves.unwrap_or_default(),
+            session.expected_amount_eur.unwrap_or_default(),
+        )
+    } else {
+        cash_register::calculate_expected_totals(conn, session_id)?
+    };
+    let balances = [
+        (
+            "USD",
+            session.opening_amount_usd,
+            session.closing_amount_usd,
+            expected_usd,
+        ),
+        (
+            "VES",
+            session.opening_amount_ves,
+            session.closing_amount_ves,
+            expected_ves,
+        ),
+        (
+            "EUR",
+            session.opening_amount_eur,
+            session.closing_amount_eur,
+            expected_eur,
+        ),
+    ]
+    .into_iter()
+    .map(|(currency, opening, closing, expected)| {
+        let expected = round(expected);
+        let counted = closing.filter(|_| closed);
+        CurrencyBalance {
+            currency: currency.to_string(),
+            opening,
+            cash_sales: round(amount_in(&cash_sales, currency)),
+            change_given: round(amount_in(&change, currency)),
+            deposits: round(amount_in(&deposits, currency)),
+            withdrawals: round(amount_in(&withdrawals, currency)),
+            expected,
+            counted,
+            over_short: counted.map(|counted| round(counted - expected)),
+        }
+    })
+    .collect();
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT payment_method, currency, COUNT(*), COALESCE(SUM(amount), 0)
+             FROM billing_payments WHERE session_id = ?1
+             GROUP BY payment_method, currency ORDER BY payment_method, currency",
+        )
+        .map_err(db)?;
+    let payments = stmt
+        .query_map([session_id], |row| {
+            Ok(PaymentMethodTotal {
+                payment_method: row.get(0)?,
+                currency: row.get(1)?,
+                count: row.get(2)?,
+                amount: round(row.get(3)?),
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT currency, COUNT(*), SUM(subtotal), SUM(discount_total), SUM(tax_total), SUM(total)
+             FROM billing_invoices
+             WHERE session_id = ?1 AND invoice_type = 'invoice' AND status NOT IN ('draft', 'cancelled')
+             GROUP BY currency ORDER BY currency",
+        )
+        .map_err(db)?;
+    let sales = stmt
+        .query_map([session_id], |row| {
+            Ok(SalesTotal {
+                currency: row.get(0)?,
+                invoices: row.get(1)?,
+                subtotal: round(row.get(2)?),
+                discount_total: round(row.get(3)?),
+                tax_total: round(row.get(4)?),
+                total: round(row.get(5)?),
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT i.currency, it.tax_rate, SUM(it.line_total - it.tax_amount), SUM(it.tax_amount)
+             FROM billing_invoice_items it
+             JOIN billing_invoices i ON i.id = it.invoice_id
+             WHERE i.session_id = ?1 AND i.invoice_type = 'invoice'
+               AND i.status NOT IN ('draft', 'cancelled')
+             GROUP BY i.currency, it.tax_rate ORDER BY i.currency, it.tax_rate",
+        )
+        .map_err(db)?;
+    let taxes = stmt
+        .query_map([session_id], |row| {
+            Ok(TaxTotal {
+                currency: row.get(0)?,
+                tax_rate: row.get(1)?,
+                taxable: round(row.get(2)?),
+                tax: round(row.get(3)?),
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT currency, COUNT(*), COALESCE(SUM(total), 0) FROM billing_invoices
+             WHERE session_id = ?1 AND (invoice_type = 'credit_note' OR status = 'cancelled')
+             GROUP BY currency ORDER BY currency",
+        )
+        .map_err(db)?;
+    let refunds = stmt
+        .query_map([session_id], |row| {
+            Ok(RefundTotal {
+                currency: row.get(0)?,
+                count: row.get(1)?,
+                total: round(row.get(2)?),
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, tenant_id, session_id, user_id, type, amount, currency, exchange_rate,
+                    reason, reference, created_at
+             FROM cash_movements WHERE session_id = ?1 ORDER BY created_at",
+        )
+        .map_err(db)?;
+    let movements = stmt
+        .query_map([session_id], |row| {
+            Ok(CashMovement {
+                id: row.get(0)?,
+                tenant_id: row.get(1)?,
+                session_id: row.get(2)?,
+                user_id: row.get(3)?,
+                movement_type: row.get(4)?,
+                amount: row.get(5)?,
+                currency: row.get(6)?,
+                exchange_rate: row.get(7)?,
+                reason: row.get(8)?,
+                reference: row.get(9)?,
+                created_at: row.get(10)?,
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT currency, denomination, kind, quantity, amount FROM cash_session_counts
+             WHERE session_id = ?1 ORDER BY currency DESC, kind, denomination DESC",
+        )
+        .map_err(db)?;
+    let denominations = stmt
+        .query_map([session_id], |row| {
+            Ok(DenominationCount {
+                currency: row.get(0)?,
+                denomination: row.get(1)?,
+                kind: row.get(2)?,
+                quantity: row.get(3)?,
+                amount: row.get(4)?,
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT currency, exchange_rate, COUNT(*) FROM billing_payments
+             WHERE session_id = ?1 AND currency != 'VES'
+             GROUP BY currency, exchange_rate ORDER BY currency, exchange_rate",
+        )
+        .map_err(db)?;
+    let rates = stmt
+        .query_map([session_id], |row| {
+            Ok(RateUsed {
+                currency: row.get(0)?,
+                rate: row.get(1)?,
+                payments: row.get(2)?,
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    Ok(SessionReport {
+        id: Uuid::new_v4().to_string(),
+        report_type: report_type.to_string(),
+        report_number,
+        session_id: session.id,
+        register_id: session.register_id,
+        register_name,
+        user_id: session.user_id,
+        user_name,
+        start_time: session.start_time,
+        end_time: session.end_time,
+        opening_exchange_rate_ves: session.opening_exchange_rate_ves,
+        opening_exchange_rate_eur: session.opening_exchange_rate_eur,
+        balances,
+        payments,
+        sales,
+        taxes,
+        refunds,
+        movements,
+        denominations,
+        rates,
+        created_by: user_id.to_string(),
+        created_at: now.to_string(),
+        hash: None,
+    })
+}
+
+/// Build and store a report; Z-reports only for a session that has just
+/// been closed, and only once
+pub fn take_report(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    session_id: &str,
+    report_type: &str,
+    now: &str,
+) -> Result<SessionReport, ServiceError> {
+    let status: String = conn
+        .query_row(
+            "SELECT status FROM cash_register_sessions WHERE id = ?1 AND tenant_id = ?2",
+            params![session_id, tenant_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db)?
+        .ok_or_else(|| ServiceError::NotFound("Sesión de caja no encontrada".to_string()))?;
+    match report_type {
+        "X" if status != "active" => {
+            return Err(ServiceError::Validation(
+                "El reporte X es solo para sesiones abiertas".to_string(),
+            ))
+        }
+        "Z" if status != "closed" => {
+            return Err(ServiceError::Validation(
+                "El reporte Z se emite al cerrar la sesión".to_string(),
+            ))
+        }
+        "X" | "Z" => {}
+        _ => {
+            return Err(ServiceError::Validation(format!(
+                "Tipo de reporte inválido: {}",
+                report_type
+            )))
+        }
+    }
+    if report_type == "Z" {
+        let taken: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM cash_session_reports WHERE session_id = ?1 AND report_type = 'Z'",
+                [session_id],
+                |row| row.get(0),
+            )
+            .map_err(db)?;
+        if taken > 0 {
+            return Err(ServiceError::Validation(
+                "La sesión ya tiene reporte Z".to_string(),
+            ));
+        }
+    }
+
+    let mut report = build_report(conn, tenant_id, user_id, session_id, report_type, now)?;
+    let content =
+        serde_json::to_string(&report).map_err(|e| ServiceError::Database(e.to_string()))?;
+    let hash = hex::encode(Sha256::digest(content.as_bytes()));
+    conn.execute(
+        "INSERT INTO cash_session_reports (id, tenant_id, session_id, register_id, report_type,
+         report_number, content, hash, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            report.id,
+            tenant_id,
+            session_id,
+            report.register_id,
+            report_type,
+            report.report_number,
+            content,
+            hash,
+            user_id,
+            now
+        ],
+    )
+    .map_err(db)?;
+    report.hash = Some(hash);
+    Ok(report)
+}
+
+/// A stored report, checked against its hash
+pub fn get_report(
+    conn: &Connection,
+    tenant_id: &str,
+    id: &str,
+) -> Result<SessionReport, ServiceError> {
+    let (content, hash): (String, String) = conn
+        .query_row(
+            "SELECT content, hash FROM cash_session_reports WHERE id = ?1 AND tenant_id = ?2",
+            params![id, tenant_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(db)?
+        .ok_or_else(|| ServiceError::NotFound("Reporte no encontrado".to_string()))?;
+    load(content, hash)
+}
+
+/// Reports of a session, oldest first
+pub fn list_reports(
+    conn: &Connection,
+    tenant_id: &str,
+    session_id: &str,
+) -> Result<Vec<SessionReport>, ServiceError> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT content, hash FROM cash_session_reports
+             WHERE session_id = ?1 AND tenant_id = ?2 ORDER BY created_at, report_type",
+        )
+        .map_err(db)?;
+    let rows = stmt
+        .query_map(params![session_id, tenant_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    rows.into_iter()
+        .map(|(content, hash)| load(content, hash))
+        .collect()
+}
+
+fn load(content: String, hash: String) -> Result<SessionReport, ServiceError> {
+    if hex::encode(Sha256::digest(content.as_bytes())) != hash {
+        return Err(ServiceError::Validation(
+            "El reporte no coincide con su hash".to_string(),
+        ));
+    }
+    let mut report: SessionReport =
+        serde_json::from_str(&content).map_err(|e| ServiceError::Database(e.to_string()))?;
+    report.hash = Some(hash);
+    Ok(report)
+}
+
+pub(crate) fn method_label(method: &str) -> &str {
+    match method {
+        "cash" => "Efectivo",
+        "card" => "Tarjeta",
+        "mobile" => "Pago móvil",
+        "transfer" => "Transferencia",
+        other => other,
+    }
+}
+
+/// Label on the left, value on the right; the label is cut to fit
+pub(crate) fn row(label: &str, value: &str, width: usize) -> String {
+    let room = width.saturating_sub(value.chars().count() + 1);
+    let label: String = label.chars().take(room).collect();
+    let gap = width.saturating_sub(label.chars().count() + value.chars().count());
+    format!("{}{}{}", label, " ".repeat(gap.max(1)), value)
+}
+
+/// Date and time to the minute
+pub(crate) fn stamp(timestamp: &str) -> String {
+    timestamp.get(..16).unwrap_or(timestamp).replace('T', " ")
+}
+
+fn centered(text: &str, width: usize) -> String {
+    let pad = width.saturating_sub(text.chars().count()) / 2;
+    format!("{}{}", " ".repeat(pad), text)
+}
+
+/// The report as plain text lines of at most `width` columns, for a
+/// thermal ticket or a PDF page
+pub fn render_lines(report: &SessionReport, width: usize) -> Vec<String> {
+    let rule = "-".repeat(width);
+    let money = |amount: f64| format!("{:.2}", amount);
+    let mut lines = vec![
+        centered(
+            &format!("REPORTE {} #{}", report.report_type, report.report_number),
+            width,
+        ),
+        row("Caja:", &report.register_name, width),
+        row(
+            "Cajero:",
+            report.user_name.as_deref().unwrap_or(&report.user_id),
+            width,
+        ),
+        row("Apertura:", &stamp(&report.start_time), width),
+    ];
+    if let Some(end_time) = &report.end_time {
+        lines.push(row("Cierre:", &stamp(end_time), width));
+    }
+    lines.push(row("Emitido:", &stamp(&report.created_at), width));
+
+    lines.push(rule.clone());
+    lines.push("VENTAS".to_string());
+    for sales in &report.sales {
+        lines.push(row(
+            &format!("{} facturas", sales.currency),
+            &sales.invoices.to_string(),
+            width,
+        ));
+        lines.push(row("  Subtotal", &money(sales.subtotal), width));
+        lines.push(row("  Descuentos", &money(sales.discount_total), width));
+        lines.push(row("  Impuestos", &money(sales.tax_total), width));
+        lines.push(row("  Total", &money(sales.total), width));
+    }
+    if !report.taxes.is_empty() {
+        lines.push("IMPUESTOS".to_string());
+        for tax in &report.taxes {
+            lines.push(row(
+                &format!(
+                    "{} {}% base {}",
+                    tax.currency,
+                    tax.tax_rate,
+                    money(tax.taxable)
+                ),
+                &money(tax.tax),
+                width,
+            ));
+        }
+    }
+
+    lines.push(rule.clone());
+    lines.push("FORMAS DE PAGO".to_string());
+    for payment in &report.payments {
+        lines.push(row(
+            &format!(
+                "{} {} ({})",
+                method_label(&payment.payment_method),
+                payment.currency,
+                payment.count
+            ),
+            &money(payment.amount),
+            width,
+        ));
+    }
+    if !report.refunds.is_empty() {
+        lines.push("DEVOLUCIONES".to_string());
+        for refund in &report.refunds {
+            lines.push(row(
+                &format!("{} ({})", refund.currency, refund.count),
+                &money(refund.total),
+                width,
+            ));
+        }
+    }
+    if !report.movements.is_empty() {
+        lines.push("MOVIMIENTOS".to_string());
+        for movement in &report.movements {
+            let label = if movement.movement_type == "deposit" {
+                "Entrada"
+            } else {
+                "Salida"
+            };
+            lines.push(row(
+                &format!("{} {}", label, movement.currency),
+                &money(movement.amount),
+                width,
+            ));
+            if let Some(reason) = movement.reason.as_deref().filter(|r| !r.is_empty()) {
+                lines.push(format!(
+                    "  {}",
+                    reason.chars().take(width - 2).collect::<String>()
+                ));
+            }
+        }
+    }
+
+    if !report.denominations.is_empty() {
+        lines.push(rule.clone());
+        lines.push("CONTEO".to_string());
+        for count in &report.denominations {
+            lines.push(row(
+                &format!(
+                    "{} {} x {}",
+                    count.currency,
+                    money(count.denomination),
+                    count.quantity
+                ),
+                &money(count.amount),
+                width,
+            ));
+        }
+    }
+
+    lines.push(rule.clone());
+    lines.push("CUADRE".to_string());
+    for balance in &report.balances {
+        lines.push(balance.currency.clone());
+        lines.push(row("  Apertura", &money(balance.opening), width));
+        lines.push(row("  Ventas efectivo", &money(balance.cash_sales), width));
+        lines.push(row("  Vuelto", &money(-balance.change_given), width));
+        lines.push(row("  Entradas", &money(balance.deposits), width));
+        lines.push(row("  Salidas", &money(-balance.withdrawals), width));
+        lines.push(row("  Esperado", &money(balance.expected), width));
+        if let (Some(counted), Some(over_short)) = (balance.counted, balance.over_short) {
+            lines.push(row("  Contado", &money(counted), width));
+            let label = if over_short < 0.0 {
+                "  Faltante"
+            } else {
+                "  Sobrante"
+            };
+            lines.push(row(label, &money(over_short), width));
+        }
+    }
+
+    lines.push(rule.clone());
+    lines.push("TASAS".to_string());
+    lines.push(row(
+        "Apertura VES/EUR",
+        &format!(
+            "{} / {}",
+            money(report.opening_exchange_rate_ves),
+            money(report.opening_exchange_rate_eur)
+        ),
+        width,
+    ));
+    for rate in &report.rates {
+        lines.push(row(
+            &format!("{} ({} pagos)", rate.currency, rate.payments),
+            &format!("{:.4}", rate.rate),
+            width,
+        ));
+    }
+    if let Some(hash) = &report.hash {
+        lines.push(rule);
+        lines.push(hash.chars().take(width).collect());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::cash_register::{
+        AddMovementDto, CloseSessionDto, OpenSessionDto, PosTenderDto,
+    };
+
+    #[test]
+    fn test_x_and_z_reports_with_denomination_counts() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO clients (id, tenant_id, name) VALUES ('cl1', 't1', 'Cliente');
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, sale_price)
+                 VALUES ('p1', 't1', 'ARR-1', 'Arroz', 2.0, 2.0);
+             INSERT INTO exchange_rates (id, tenant_id, currency, rate, rate_date, created_at)
+                 VALUES ('r1', 't1', 'USD', 40, '2026-01-01', '2026-01-01');
+             INSERT INTO cash_registers (id, tenant_id, name, status, created_at, updated_at)
+                 VALUES ('c1', 't1', 'Caja 1', 'closed', '2026-01-01', '2026-01-01');
+             INSERT INTO billing_invoices (id, tenant_id, invoice_number, invoice_type, status, client_id,
+                 client_name, currency, exchange_rate, issue_date, subtotal, tax_total, total,
+                 created_by, created_at, updated_at)
+                 VALUES ('i1', 't1', 'FAC-1', 'invoice', 'issued', 'cl1', 'Cliente', 'USD', 40,
+                         '2026-01-01', 10, 1.6, 11.6, 'u1', '2026-01-01', '2026-01-01');
+             INSERT INTO billing_invoice_items (id, invoice_id, product_id, code, description, quantity,
+                 unit_price, tax_rate, tax_amount, line_total)
+                 VALUES ('it1', 'i1', 'p1', 'ARR-1', 'Arroz', 5, 2, 16, 1.6, 11.6);",
+        )
+        .unwrap();
+        let session = cash_register::open_session(
+            &conn,
+            "t1",
+            "u1",
+            OpenSessionDto {
+                register_id: "c1".to_string(),
+                opening_amount_usd: 20.0,
+                opening_amount_ves: 0.0,
+                opening_amount_eur: 0.0,
+                notes: None,
+                exchange_rate_ves: 40.0,
+                exchange_rate_eur: 0.0,
+            },
+        )
+        .unwrap();
+        // A 20 USD bill for 11.60 USD, with 8.40 USD back
+        cash_register::settle_sale(
+            &conn,
+            "t1",
+            "u1",
+            &cash_register::Settlement {
+                session_id: &session.id,
+                invoice_id: "i1",
+                tenders: &[PosTenderDto {
+                    payment_method: "cash".to_string(),
+                    currency: "USD".to_string(),
+                    amount: 20.0,
+                    reference: None,
+                    bank_account_id: None,
+                }],
+                change_currency: None,
+            },
+            "2026-01-01T10:00:00Z",
+        )
+        .unwrap();
+        cash_register::add_movement(
+            &conn,
+            "t1",
+            "u1",
+            AddMovementDto {
+                session_id: session.id.clone(),
+                movement_type: "withdrawal".to_string(),
+                amount: 10.0,
+                currency: "USD".to_string(),
+                reason: "Retiro parcial".to_string(),
+                reference: String::new(),
+            },
+        )
+        .unwrap();
+
+        // X-report: a snapshot that leaves the session open
+        let x = take_report(&conn, "t1", "u1", &session.id, "X", "2026-01-01T12:00:00Z").unwrap();
+        assert_eq!(x.balances[0].expected, 21.6);
+        assert_eq!(x.balances[0].counted, None);
+        assert_eq!(x.taxes[0].taxable, 10.0);
+        assert!(take_report(&conn, "t1", "u1", &session.id, "Z", "2026-01-01T12:00:00Z").is_err());
+
+        let count = |denomination, quantity| DenominationCountDto {
+            currency: "usd".to_string(),
+            denomination,
+            quantity,
+        };
+        assert!(price_counts(&[count(3.0, 1)]).is_err());
+        let closed = cash_register::close_session(
+            &conn,
+            "t1",
+            "u1",
+            CloseSessionDto {
+                session_id: session.id.clone(),
+                closing_amount_usd: 0.0,
+                closing_amount_ves: 0.0,
+                closing_amount_eur: 0.0,
+                notes: None,
+                counts: vec![count(20.0, 1), count(1.0, 1), count(0.25, 2)],
+            },
+        )
+        .unwrap();
+        assert_eq!(closed.closing_amount_usd, Some(21.5));
+        assert_eq!(closed.expected_amount_usd, Some(21.6));
+
+        let reports = list_reports(&conn, "t1", &session.id).unwrap();
+        let z = reports.iter().find(|r| r.report_type == "Z").unwrap();
+        assert_eq!(z.report_number, 1);
+        assert_eq!(z.balances[0].over_short, Some(-0.1));
+        assert_eq!(z.denominations.len(), 3);
+        assert_eq!(z.payments[0].amount, 11.6);
+        assert_eq!(z.balances[0].change_given, 8.4);
+        assert!(render_lines(z, 32)
+            .iter()
+            .all(|line| line.chars().count() <= 32));
+        assert!(pdf_generator_smoke(z));
+
+        // Closed is final: no second close, no edits
+        assert!(cash_register::close_session(
+            &conn,
+            "t1",
+            "u1",
+            CloseSessionDto {
+                session_id: session.id.clone(),
+                closing_amount_usd: 0.0,
+                closing_amount_ves: 0.0,
+                closing_amount_eur: 0.0,
+                notes: None,
+                counts: Vec::new(),
+            },
+        )
+        .is_err());
+        assert!(conn
+            .execute(
+                "UPDATE cash_session_reports SET content = '{}' WHERE id = ?1",
+                [&z.id]
+            )
+            .is_err());
+        assert!(conn.execute("DELETE FROM cash_session_counts", []).is_err());
+    }
+
+    fn pdf_generator_smoke(report: &SessionReport) -> bool {
+        crate::services::pdf_generator::text_document("Reporte", &render_lines(report, 80))
+            .unwrap()
+            .starts_with(b"%PDF")
+    }
+}
@@ -0,0 +1,968 @@
+//! Discount Redemptions
+//!
+//! A discount is used when an invoice that applied it is issued, in the same
+//! transaction: the counter only moves if the discount is still active, in
+//! its date window and hours and below `max_uses`, so two registers cannot
+//! both take the last use. Coupons and per-client limits are checked the
+//! same way. Cancelling or deleting the invoice gives the uses back.
+//!
+//! Also here: coupon codes (single, or generated in batches), bundle
+//! contents and the campaign performance report.
+
+use crate::models::discount::{
+    CampaignPerformance, CampaignReportFilters, CreateCouponDto, DiscountBundleItem,
+    DiscountCoupon, DiscountRedemption, GenerateCouponsDto,
+};
+use crate::models::pricing::AppliedPriceRule;
+use crate::services::pricing;
+use crate::state::ServiceError;
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+fn db(e: rusqlite::Error) -> ServiceError {
+    ServiceError::Database(e.to_string())
+}
+
+/// When a discount is used: date, local time (HH:MM) and ISO weekday, read
+/// the way pricing reads them
+struct Moment<'a> {
+    date: &'a str,
+    time: String,
+    weekday: u32,
+}
+
+impl<'a> Moment<'a> {
+    fn at(now: &'a str) -> Self {
+        let date = &now[..10];
+        let time = DateTime::parse_from_rfc3339(now)
+            .map(|t| t.with_timezone(&Local).format("%H:%M").to_string())
+            .unwrap_or_else(|_| now.get(11..16).unwrap_or_default().to_string());
+        let weekday = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(|d| d.weekday().number_from_monday())
+            .unwrap_or_else(|_| Local::now().weekday().number_from_monday());
+        Self {
+            date,
+            time,
+            weekday,
+        }
+    }
+
+    fn in_window(
+        &self,
+        start_time: Option<&str>,
+        end_time: Option<&str>,
+        weekdays: Option<&str>,
+    ) -> bool {
+        pricing::in_window(start_time, end_time, weekdays, &self.time, self.weekday)
+    }
+}
+
+/// Why a discount can't be used at `moment`
+fn unavailable(
+    conn: &Connection,
+    tenant_id: &str,
+    discount_id: &str,
+    moment: &Moment,
+) -> Result<ServiceError, ServiceError> {
+    type Row = (
+        String,
+        bool,
+        Option<i64>,
+        i64,
+        Option<String>,
+        Option<String>,
+        bool,
+    );
+    let date = moment.date;
+    let row: Option<Row> = conn
+        .query_row(
+            "SELECT name, is_active, max_uses, times_used, start_date, end_date,
+                    start_time, end_time, weekdays
+             FROM discounts WHERE id = ?1 AND tenant_id = ?2",
+            params![discount_id, tenant_id],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get::<_, i32>(1)? == 1,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    moment.in_window(
+                        row.get::<_, Option<String>>(6)?.as_deref(),
+                        row.get::<_, Option<String>>(7)?.as_deref(),
+                        row.get::<_, Option<String>>(8)?.as_deref(),
+                    ),
+                ))
+            },
+        )
+        .optional()
+        .map_err(db)?;
+    let Some((name, is_active, max_uses, times_used, start_date, end_date, in_hours)) = row else {
+        return Ok(ServiceError::NotFound(
+            "Descuento no encontrado".to_string(),
+        ));
+    };
+
+    let day = |value: &Option<String>| {
+        value
+            .as_deref()
+            .filter(|v| !v.is_empty())
+            .map(|v| v.get(..10).unwrap_or(v).to_string())
+    };
+    let reason = if !is_active {
+        "inactiva"
+    } else if max_uses.is_some_and(|max| times_used >= max) {
+        "agotada"
+    } else if day(&start_date).is_some_and(|start| start.as_str() > date) {
+        "aún no vigente"
+    } else if day(&end_date).is_some_and(|end| end.as_str() < date) {
+        "vencida"
+    } else if !in_hours {
+        "fuera de horario"
+    } else {
+        "no disponible"
+    };
+    Ok(ServiceError::Validation(format!(
+        "La promoción {} no se puede aplicar ({}); actualice la factura para recalcular los precios",
+        name, reason
+    )))
+}
+
+/// Take one use of a discount, or fail if it is inactive, exhausted or out
+/// of its date window or hours. Atomic: the check and the increment are one
+/// UPDATE, after the hours are checked in the same transaction.
+fn consume(
+    conn: &Connection,
+    tenant_id: &str,
+    discount_id: &str,
+    now: &str,
+) -> Result<(), ServiceError> {
+    let moment = Moment::at(now);
+    let date = moment.date;
+    let in_hours = conn
+        .query_row(
+            "SELECT start_time, end_time, weekdays FROM discounts WHERE id = ?1 AND tenant_id = ?2",
+            params![discount_id, tenant_id],
+            |row| {
+                Ok(moment.in_window(
+                    row.get::<_, Option<String>>(0)?.as_deref(),
+                    row.get::<_, Option<String>>(1)?.as_deref(),
+                    row.get::<_, Option<String>>(2)?.as_deref(),
+                ))
+            },
+        )
+        .optional()
+        .map_err(db)?
+        .unwrap_or(true);
+    if !in_hours {
+        return Err(unavailable(conn, tenant_id, discount_id, &moment)?);
+    }
+    let updated = conn
+        .execute(
+            "UPDATE discounts SET times_used = times_used + 1, updated_at = ?3
+             WHERE id = ?1 AND tenant_id = ?2 AND is_active = 1
+               AND (max_uses IS NULL OR times_used < max_uses)
+               AND (start_date IS NULL OR start_date = '' OR substr(start_date, 1, 10) <= ?4)
+               AND (end_date IS NULL OR end_date = '' OR substr(end_date, 1, 10) >= ?4)",
+            params![discount_id, tenant_id, now, date],
+        )
+        .map_err(db)?;
+    if updated == 0 {
+        return Err(unavailable(conn, tenant_id, discount_id, &moment)?);
+    }
+    Ok(())
+}
+
+/// Enforce a discount's uses per client
+fn check_client_limit(
+    conn: &Connection,
+    tenant_id: &str,
+    discount_id: &str,
+    client_id: Option<&str>,
+) -> Result<(), ServiceError> {
+    let Some(client_id) = client_id else {
+        return Ok(());
+    };
+    let (name, limit, used): (String, Option<i64>, i64) = conn
+        .query_row(
+            "SELECT name, per_client_limit,
+                    (SELECT COUNT(*) FROM discount_redemptions
+                     WHERE discount_id = ?1 AND client_id = ?3 AND status = 'redeemed')
+             FROM discounts WHERE id = ?1 AND tenant_id = ?2",
+            params![discount_id, tenant_id, client_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(db)?;
+    if limit.is_some_and(|limit| used >= limit) {
+        return Err(ServiceError::Validation(format!(
+            "El cliente ya usó la promoción {} el máximo de veces permitido",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Take one use of a coupon, atomically like `consume`
+fn consume_coupon(conn: &Connection, coupon_id: &str, now: &str) -> Result<(), ServiceError> {
+    let updated = conn
+        .execute(
+            "UPDATE discount_coupons SET times_used = times_used + 1, updated_at = ?2
+             WHERE id = ?1 AND is_active = 1 AND (max_uses IS NULL OR times_used < max_uses)",
+            params![coupon_id, now],
+        )
+        .map_err(db)?;
+    if updated == 0 {
+        let code: String = conn
+            .query_row(
+                "SELECT code FROM discount_coupons WHERE id = ?1",
+                [coupon_id],
+                |row| row.get(0),
+            )
+            .map_err(db)?;
+        return Err(ServiceError::Validation(format!(
+            "El cupón {} ya fue usado o está inactivo",
+            code
+        )));
+    }
+    Ok(())
+}
+
+/// A use of a discount to record
+struct Redemption<'a> {
+    tenant_id: &'a str,
+    discount_id: &'a str,
+    coupon_id: Option<&'a str>,
+    invoice_id: Option<&'a str>,
+    client_id: Option<&'a str>,
+    amount: f64,
+    user_id: Option<&'a str>,
+}
+
+fn insert_redemption(
+    conn: &Connection,
+    redemption: &Redemption,
+    now: &str,
+) -> Result<(), ServiceError> {
+    conn.execute(
+        "INSERT INTO discount_redemptions
+            (id, tenant_id, discount_id, coupon_id, invoice_id, client_id, amount, status,
+             redeemed_by, redeemed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'redeemed', ?8, ?9)",
+        params![
+            Uuid::new_v4().to_string(),
+            redemption.tenant_id,
+            redemption.discount_id,
+            redemption.coupon_id,
+            redemption.invoice_id,
+            redemption.client_id,
+            redemption.amount,
+            redemption.user_id,
+            now
+        ],
+    )
+    .map_err(db)?;
+    Ok(())
+}
+
+/// Use every discount applied to an invoice's lines; call inside the
+/// issuing transaction. Returns the number of discounts used.
+pub fn redeem_invoice(
+    conn: &Connection,
+    tenant_id: &str,
+    invoice_id: &str,
+    user_id: Option<&str>,
+    now: &str,
+) -> Result<usize, ServiceError> {
+    let client_id: Option<String> = conn
+        .query_row(
+            "SELECT client_id FROM billing_invoices WHERE id = ?1 AND tenant_id = ?2",
+            params![invoice_id, tenant_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db)?;
+
+    // Discount amounts per discount and coupon, over all lines
+    let mut stmt = conn
+        .prepare(
+            "SELECT pricing_rules FROM billing_invoice_items
+             WHERE invoice_id = ?1 AND pricing_rules IS NOT NULL",
+        )
+        .map_err(db)?;
+    let mut used: BTreeMap<(String, Option<String>), f64> = BTreeMap::new();
+    for rules in stmt
+        .query_map([invoice_id], |row| row.get::<_, String>(0))
+        .map_err(db)?
+    {
+        let rules: Vec<AppliedPriceRule> = serde_json::from_str(&rules.map_err(db)?)
+            .map_err(|e| ServiceError::Validation(e.to_string()))?;
+        for rule in rules.into_iter().filter(|r| r.rule_type == "discount") {
+            if let Some(discount_id) = rule.rule_id {
+                *used.entry((discount_id, rule.coupon_id)).or_default() += rule.amount;
+            }
+        }
+    }
+
+    for ((discount_id, coupon_id), amount) in &used {
+        check_client_limit(conn, tenant_id, discount_id, client_id.as_deref())?;
+        consume(conn, tenant_id, discount_id, now)?;
+        if let Some(coupon_id) = coupon_id {
+            consume_coupon(conn, coupon_id, now)?;
+        }
+        insert_redemption(
+            conn,
+            &Redemption {
+                tenant_id,
+                discount_id,
+                coupon_id: coupon_id.as_deref(),
+                invoice_id: Some(invoice_id),
+                client_id: client_id.as_deref(),
+                amount: *amount,
+                user_id,
+            },
+            now,
+        )?;
+    }
+    Ok(used.len())
+}
+
+/// Give back the uses taken by an invoice. Returns the number reversed.
+pub fn reverse_invoice(
+    conn: &Connection,
+    tenant_id: &str,
+    invoice_id: &str,
+    now: &str,
+) -> Result<usize, ServiceError> {
+    conn.execute(
+        "UPDATE discounts SET updated_at = ?3, times_used = MAX(times_used - (
+             SELECT COUNT(*) FROM discount_redemptions r
+             WHERE r.invoice_id = ?1 AND r.status = 'redeemed' AND r.discount_id = discounts.id), 0)
+         WHERE tenant_id = ?2 AND id IN (
+             SELECT discount_id FROM discount_redemptions
+             WHERE invoice_id = ?1 AND status = 'redeemed')",
+        params![invoice_id, tenant_id, now],
+    )
+    .map_err(db)?;
+    conn.execute(
+        "UPDATE discount_coupons SET updated_at = ?3, times_used = MAX(times_used - (
+             SELECT COUNT(*) FROM discount_redemptions r
+             WHERE r.invoice_id = ?1 AND r.status = 'redeemed' AND r.coupon_id = discount_coupons.id), 0)
+         WHERE tenant_id = ?2 AND id IN (
+             SELECT coupon_id FROM discount_redemptions
+             WHERE invoice_id = ?1 AND status = 'redeemed')",
+        params![invoice_id, tenant_id, now],
+    )
+    .map_err(db)?;
+    conn.execute(
+        "UPDATE discount_redemptions SET status = 'reversed', reversed_at = ?3
+         WHERE invoice_id = ?1 AND tenant_id = ?2 AND status = 'redeemed'",
+        params![invoice_id, tenant_id, now],
+    )
+    .map_err(db)
+}
+
+/// Use a discount outside an invoice, with the same checks
+pub fn redeem_manual(
+    conn: &Connection,
+    tenant_id: &str,
+    discount_id: &str,
+    user_id: Option<&str>,
+    now: &str,
+) -> Result<(), ServiceError> {
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    consume(&tx, tenant_id, discount_id, now)?;
+    insert_redemption(
+        &tx,
+        &Redemption {
+            tenant_id,
+            discount_id,
+            coupon_id: None,
+            invoice_id: None,
+            client_id: None,
+            amount: 0.0,
+            user_id,
+        },
+        now,
+    )?;
+    tx.commit().map_err(db)
+}
+
+/// Redemption history of a discount or an invoice, newest first
+pub fn list_redemptions(
+    conn: &Connection,
+    tenant_id: &str,
+    discount_id: Option<&str>,
+    invoice_id: Option<&str>,
+) -> Result<Vec<DiscountRedemption>, ServiceError> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT r.id, r.discount_id, d.name, r.invoice_id, i.invoice_number, r.client_id,
+                    r.coupon_id, c.code, r.amount, r.status, r.redeemed_by, r.redeemed_at,
+                    r.reversed_at
+             FROM discount_redemptions r
+             JOIN discounts d ON d.id = r.discount_id
+             LEFT JOIN billing_invoices i ON i.id = r.invoice_id
+             LEFT JOIN discount_coupons c ON c.id = r.coupon_id
+             WHERE r.tenant_id = ?1
+               AND (?2 IS NULL OR r.discount_id = ?2)
+               AND (?3 IS NULL OR r.invoice_id = ?3)
+             ORDER BY r.redeemed_at DESC",
+        )
+        .map_err(db)?;
+    let redemptions = stmt
+        .query_map(params![tenant_id, discount_id, invoice_id], |row| {
+            Ok(DiscountRedemption {
+                id: row.get(0)?,
+                discount_id: row.get(1)?,
+                discount_name: row.get(2)?,
+                invoice_id: row.get(3)?,
+                invoice_number: row.get(4)?,
+                client_id: row.get(5)?,
+                coupon_id: row.get(6)?,
+                coupon_code: row.get(7)?,
+                amount: row.get(8)?,
+                status: row.get(9)?,
+                redeemed_by: row.get(10)?,
+                redeemed_at: row.get(11)?,
+                reversed_at: row.get(12)?,
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(redemptions)
+}
+
+/// Check happy hour times ("HH:MM") and ISO weekdays ("1".."7"); empty
+/// values clear them
+pub fn validate_schedule(
+    start_time: Option<&str>,
+    end_time: Option<&str>,
+    weekdays: Option<&str>,
+) -> Result<(), ServiceError> {
+    for time in [start_time, end_time]
+        .into_iter()
+        .flatten()
+        .filter(|t| !t.is_empty())
+    {
+        if chrono::NaiveTime::parse_from_str(time, "%H:%M").is_err() {
+            return Err(ServiceError::Validation(format!(
+                "Hora inválida: {} (use HH:MM)",
+                time
+            )));
+        }
+    }
+    if let Some(weekdays) = weekdays.filter(|d| !d.trim().is_empty()) {
+        let valid = weekdays
+            .split(',')
+            .all(|day| matches!(day.trim().parse::<u32>(), Ok(1..=7)));
+        if !valid {
+            return Err(ServiceError::Validation(
+                "Días inválidos: use 1 (lunes) a 7 (domingo) separados por coma".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Bundle discounts only apply to complete bundles, so the type and the
+/// target go together
+pub fn validate_bundle(discount_type: &str, applies_to: &str) -> Result<(), ServiceError> {
+    if (discount_type == "bundle") != (applies_to == "bundle") {
+        return Err(ServiceError::Validation(
+            "Un combo debe ser de tipo combo y aplicarse a combos".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// ============================================
+// COUPONS
+// ============================================
+
+/// Letters and digits that can't be mistaken for each other
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+fn normalize_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+fn require_discount(
+    conn: &Connection,
+    tenant_id: &str,
+    discount_id: &str,
+) -> Result<(), ServiceError> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM discounts WHERE id = ?1 AND tenant_id = ?2)",
+            params![discount_id, tenant_id],
+            |row| row.get(0),
+        )
+        .map_err(db)?;
+    if !exists {
+        return Err(ServiceError::NotFound(
+            "Descuento no encontrado".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn map_coupon(row: &rusqlite::Row) -> rusqlite::Result<DiscountCoupon> {
+    Ok(DiscountCoupon {
+        id: row.get(0)?,
+        discount_id: row.get(1)?,
+        code: row.get(2)?,
+        max_uses: row.get(3)?,
+        times_used: row.get(4)?,
+        client_id: row.get(5)?,
+        batch_id: row.get(6)?,
+        is_active: row.get::<_, i32>(7)? == 1,
+        created_at: row.get(8)?,
+    })
+}
+
+const COUPON_COLUMNS: &str =
+    "id, discount_id, code, max_uses, times_used, client_id, batch_id, is_active, created_at";
+
+/// A coupon to insert
+struct NewCoupon<'a> {
+    tenant_id: &'a str,
+    discount_id: &'a str,
+    code: &'a str,
+    max_uses: Option<i64>,
+    client_id: Option<&'a str>,
+    batch_id: Option<&'a str>,
+}
+
+/// Insert a coupon; false if the code is already taken
+fn insert_coupon(conn: &Connection, coupon: &NewCoupon, now: &str) -> Result<bool, ServiceError> {
+    let inserted = conn
+        .execute(
+            "INSERT OR IGNORE INTO discount_coupons
+                (id, tenant_id, discount_id, code, max_uses, times_used, client_id, batch_id,
+                 is_active, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, 0, ?6, ?7, 1, ?8, ?8)",
+            params![
+                Uuid::new_v4().to_string(),
+                coupon.tenant_id,
+                coupon.discount_id,
+                coupon.code,
+                coupon.max_uses,
+                coupon.client_id,
+                coupon.batch_id,
+                now
+            ],
+        )
+        .map_err(db)?;
+    Ok(inserted == 1)
+}
+
+/// Create a coupon with a chosen code
+pub fn create_coupon(
+    conn: &Connection,
+    tenant_id: &str,
+    data: CreateCouponDto,
+) -> Result<DiscountCoupon, ServiceError> {
+    require_discount(conn, tenant_id, &data.discount_id)?;
+    let code = normalize_code(&data.code);
+    if code.is_empty() || code.contains(',') {
+        return Err(ServiceError::Validation(
+            "Código de cupón inválido".to_string(),
+        ));
+    }
+    let now = Utc::now().to_rfc3339();
+    if !insert_coupon(
+        conn,
+        &NewCoupon {
+            tenant_id,
+            discount_id: &data.discount_id,
+            code: &code,
+            max_uses: data.max_uses,
+            client_id: data.client_id.as_deref(),
+            batch_id: None,
+        },
+        &now,
+    )? {
+        return Err(ServiceError::Validation(format!(
+            "El código {} ya existe",
+            code
+        )));
+    }
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM discount_coupons WHERE tenant_id = ?1 AND code = ?2",
+            COUPON_COLUMNS
+        ),
+        params![tenant_id, code],
+        map_coupon,
+    )
+    .map_err(db)
+}
+
+/// Generate a batch of random, unique codes
+pub fn generate_coupons(
+    conn: &Connection,
+    tenant_id: &str,
+    data: GenerateCouponsDto,
+) -> Result<Vec<DiscountCoupon>, ServiceError> {
+    require_discount(conn, tenant_id, &data.discount_id)?;
+    if data.count == 0 || data.count > 10_000 {
+        return Err(ServiceError::Validation(
+            "Se pueden generar entre 1 y 10000 cupones".to_string(),
+        ));
+    }
+    let length = data.length.unwrap_or(8).clamp(4, 32);
+    let prefix = data
+        .prefix
+        .as_deref()
+        .map(normalize_code)
+        .unwrap_or_default();
+    let max_uses = Some(data.max_uses.unwrap_or(1));
+    let batch_id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+    let mut rng = rand::thread_rng();
+
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    let mut created = 0;
+    let mut attempts = 0;
+    while created < data.count {
+        attempts += 1;
+        if attempts > data.count * 20 {
+            return Err(ServiceError::Validation(
+                "No se pudieron generar códigos únicos; aumente la longitud".to_string(),
+            ));
+        }
+        let random: String = (0..length)
+            .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+            .collect();
+        let code = format!("{}{}", prefix, random);
+        if insert_coupon(
+            &tx,
+            &NewCoupon {
+                tenant_id,
+                discount_id: &data.discount_id,
+                code: &code,
+                max_uses,
+                client_id: data.client_id.as_deref(),
+                batch_id: Some(&batch_id),
+            },
+            &now,
+        )? {
+            created += 1;
+        }
+    }
+    tx.commit().map_err(db)?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM discount_coupons WHERE batch_id = ?1 ORDER BY code",
+            COUPON_COLUMNS
+        ))
+        .map_err(db)?;
+    let coupons = stmt
+        .query_map([&batch_id], map_coupon)
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(coupons)
+}
+
+pub fn list_coupons(
+    conn: &Connection,
+    tenant_id: &str,
+    discount_id: &str,
+) -> Result<Vec<DiscountCoupon>, ServiceError> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM discount_coupons WHERE tenant_id = ?1 AND discount_id = ?2
+             ORDER BY created_at DESC, code",
+            COUPON_COLUMNS
+        ))
+        .map_err(db)?;
+    let coupons = stmt
+        .query_map(params![tenant_id, discount_id], map_coupon)
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(coupons)
+}
+
+/// Turn a coupon off (or back on)
+pub fn set_coupon_active(
+    conn: &Connection,
+    tenant_id: &str,
+    coupon_id: &str,
+    is_active: bool,
+) -> Result<(), ServiceError> {
+    let updated = conn
+        .execute(
+            "UPDATE discount_coupons SET is_active = ?1, updated_at = ?2
+             WHERE id = ?3 AND tenant_id = ?4",
+            params![is_active, Utc::now().to_rfc3339(), coupon_id, tenant_id],
+        )
+        .map_err(db)?;
+    if updated == 0 {
+        return Err(ServiceError::NotFound("Cupón no encontrado".to_string()));
+    }
+    Ok(())
+}
+
+// ============================================
+// BUNDLES
+// ============================================
+
+/// Replace the products that make up a bundle discount
+pub fn set_bundle_items(
+    conn: &Connection,
+    tenant_id: &str,
+    discount_id: &str,
+    items: &[DiscountBundleItem],
+) -> Result<(), ServiceError> {
+    require_discount(conn, tenant_id, discount_id)?;
+    if items.iter().any(|item| item.quantity <= 0.0) {
+        return Err(ServiceError::Validation(
+            "La cantidad de cada producto debe ser mayor a cero".to_string(),
+        ));
+    }
+    let now = Utc::now().to_rfc3339();
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    tx.execute(
+        "DELETE FROM discount_bundle_items WHERE discount_id = ?1",
+        [discount_id],
+    )
+    .map_err(db)?;
+    for item in items {
+        tx.execute(
+            "INSERT INTO discount_bundle_items (id, discount_id, product_id, quantity, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+             ON CONFLICT(discount_id, product_id) DO UPDATE SET quantity = quantity + excluded.quantity",
+            params![Uuid::new_v4().to_string(), discount_id, item.product_id, item.quantity, now],
+        )
+        .map_err(db)?;
+    }
+    tx.execute(
+        "UPDATE discounts SET updated_at = ?1 WHERE id = ?2",
+        params![now, discount_id],
+    )
+    .map_err(db)?;
+    tx.commit().map_err(db)
+}
+
+pub fn list_bundle_items(
+    conn: &Connection,
+    tenant_id: &str,
+    discount_id: &str,
+) -> Result<Vec<DiscountBundleItem>, ServiceError> {
+    require_discount(conn, tenant_id, discount_id)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT product_id, quantity FROM discount_bundle_items WHERE discount_id = ?1
+             ORDER BY created_at",
+        )
+        .map_err(db)?;
+    let items = stmt
+        .query_map([discount_id], |row| {
+            Ok(DiscountBundleItem {
+                product_id: row.get(0)?,
+                quantity: row.get(1)?,
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(items)
+}
+
+// ============================================
+// CAMPAIGN REPORT
+// ============================================
+
+/// Uses, amount given away and revenue of the invoices that used each
+/// discount, best performers first
+pub fn campaign_report(
+    conn: &Connection,
+    tenant_id: &str,
+    filters: &CampaignReportFilters,
+) -> Result<Vec<CampaignPerformance>, ServiceError> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT d.id, d.name, d.campaign,
+                    COUNT(r.id), COUNT(DISTINCT r.client_id),
+                    (SELECT COUNT(*) FROM discount_coupons c WHERE c.discount_id = d.id),
+                    (SELECT COUNT(*) FROM discount_coupons c
+                     WHERE c.discount_id = d.id AND c.times_used > 0),
+                    COALESCE(SUM(r.amount), 0), COALESCE(SUM(i.total), 0)
+             FROM discounts d
+             LEFT JOIN discount_redemptions r ON r.discount_id = d.id AND r.status = 'redeemed'
+                  AND (?3 IS NULL OR substr(r.redeemed_at, 1, 10) >= ?3)
+                  AND (?4 IS NULL OR substr(r.redeemed_at, 1, 10) <= ?4)
+             LEFT JOIN billing_invoices i ON i.id = r.invoice_id
+             WHERE d.tenant_id = ?1 AND (?2 IS NULL OR d.campaign = ?2)
+             GROUP BY d.id
+             ORDER BY COALESCE(SUM(i.total), 0) DESC, d.name",
+        )
+        .map_err(db)?;
+    let report = stmt
+        .query_map(
+            params![
+                tenant_id,
+                filters.campaign,
+                filters.from_date,
+                filters.to_date
+            ],
+            |row| {
+                Ok(CampaignPerformance {
+                    discount_id: row.get(0)?,
+                    name: row.get(1)?,
+                    campaign: row.get(2)?,
+                    uses: row.get(3)?,
+                    clients: row.get(4)?,
+                    coupons_issued: row.get(5)?,
+                    coupons_used: row.get(6)?,
+                    discounted_amount: row.get(7)?,
+                    revenue: row.get(8)?,
+                })
+            },
+        )
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        let rules = r#"[{"rule_type":"base_price","rule_id":null,"name":"Precio de venta","value":10.0,"amount":0.0},
+                        {"rule_type":"discount","rule_id":"d1","name":"Lanzamiento","value":10.0,"amount":1.0}]"#;
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO clients (id, tenant_id, name) VALUES ('c1', 't1', 'Cliente');
+             INSERT INTO products (id, tenant_id, name, unit_price) VALUES ('p1', 't1', 'Arroz', 10.0);
+             INSERT INTO discounts (id, tenant_id, name, discount_type, value, applies_to, max_uses, end_date, created_at, updated_at)
+                 VALUES ('d1', 't1', 'Lanzamiento', 'percentage', 10, 'all', 1, '2026-12-31', '2026-01-01', '2026-01-01');",
+        )
+        .unwrap();
+        for invoice in ["i1", "i2"] {
+            conn.execute(
+                "INSERT INTO billing_invoices (id, tenant_id, invoice_number, invoice_type, client_id, client_name,
+                     issue_date, created_by, created_at, updated_at)
+                 VALUES (?1, 't1', ?1, 'invoice', 'c1', 'Cliente', '2026-03-01', 'u1', '2026-03-01', '2026-03-01')",
+                [invoice],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO billing_invoice_items (id, invoice_id, product_id, code, description, quantity,
+                     unit_price, discount_amount, line_total, pricing_rules)
+                 VALUES (?1 || '-1', ?1, 'p1', 'P1', 'Arroz', 1, 10, 1, 9, ?2)",
+                params![invoice, rules],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn times_used(conn: &Connection) -> i64 {
+        conn.query_row(
+            "SELECT times_used FROM discounts WHERE id = 'd1'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_max_uses_enforced_and_given_back_on_reversal() {
+        let conn = setup();
+        let now = "2026-03-01T10:00:00+00:00";
+
+        assert_eq!(
+            redeem_invoice(&conn, "t1", "i1", Some("u1"), now).unwrap(),
+            1
+        );
+        assert_eq!(times_used(&conn), 1);
+
+        // The last use is taken: the second invoice is refused, nothing recorded
+        let tx = conn.unchecked_transaction().unwrap();
+        let err = redeem_invoice(&tx, "t1", "i2", Some("u1"), now).unwrap_err();
+        assert!(err.to_string().contains("agotada"));
+        drop(tx);
+        assert_eq!(times_used(&conn), 1);
+
+        // Cancelling the first invoice frees the use
+        assert_eq!(reverse_invoice(&conn, "t1", "i1", now).unwrap(), 1);
+        assert_eq!(times_used(&conn), 0);
+        redeem_invoice(&conn, "t1", "i2", Some("u1"), now).unwrap();
+
+        let history = list_redemptions(&conn, "t1", Some("d1"), None).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.iter().filter(|r| r.status == "reversed").count(), 1);
+
+        // Expired promotions are refused too
+        reverse_invoice(&conn, "t1", "i2", now).unwrap();
+        let err = redeem_invoice(&conn, "t1", "i1", None, "2027-01-05T10:00:00+00:00").unwrap_err();
+        assert!(err.to_string().contains("vencida"));
+
+        // So are those outside their days: 2026-03-01 is a Sunday
+        conn.execute(
+            "UPDATE discounts SET weekdays = '1,2,3,4,5' WHERE id = 'd1'",
+            [],
+        )
+        .unwrap();
+        let err = redeem_invoice(&conn, "t1", "i1", None, now).unwrap_err();
+        assert!(err.to_string().contains("fuera de horario"));
+        assert_eq!(times_used(&conn), 0);
+    }
+
+    #[test]
+    fn test_bundle_type_and_target_go_together() {
+        assert!(validate_bundle("bundle", "bundle").is_ok());
+        assert!(validate_bundle("percentage", "product").is_ok());
+        assert!(validate_bundle("percentage", "bundle").is_err());
+        assert!(validate_bundle("bundle", "all").is_err());
+    }
+
+    #[test]
+    fn test_reversal_gives_back_every_use_of_a_discount() {
+        let conn = setup();
+        let now = "2026-03-01T10:00:00+00:00";
+        let rules = r#"[{"rule_type":"discount","rule_id":"d1","coupon_id":"k1","name":"Lanzamiento","value":10.0,"amount":1.0}]"#;
+        conn.execute_batch(
+            "UPDATE discounts SET max_uses = NULL WHERE id = 'd1';
+             INSERT INTO discount_coupons (id, tenant_id, discount_id, code, created_at, updated_at)
+                 VALUES ('k1', 't1', 'd1', 'ABC', '2026-01-01', '2026-01-01');",
+        )
+        .unwrap();
+        // One line with the coupon, one without: two uses of the same discount
+        conn.execute(
+            "INSERT INTO billing_invoice_items (id, invoice_id, product_id, code, description, quantity,
+                 unit_price, discount_amount, line_total, pricing_rules)
+             VALUES ('i1-2', 'i1', 'p1', 'P1', 'Arroz', 1, 10, 1, 9, ?1)",
+            [rules],
+        )
+        .unwrap();
+
+        assert_eq!(redeem_invoice(&conn, "t1", "i1", None, now).unwrap(), 2);
+        assert_eq!(times_used(&conn), 2);
+
+        assert_eq!(reverse_invoice(&conn, "t1", "i1", now).unwrap(), 2);
+        assert_eq!(times_used(&conn), 0);
+        let coupon_uses: i64 = conn
+            .query_row(
+                "SELECT times_used FROM discount_coupons WHERE id = 'k1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(coupon_uses, 0);
+    }
+}
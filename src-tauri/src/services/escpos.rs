@@ -0,0 +1,1051 @@
+//! ESC/POS Thermal Tickets
+//!
+//! Renders invoices, payment receipts, cash movement slips and session
+//! reports as ESC/POS byte streams for 58 and 80 mm printers, laid out by
+//! the `TicketTemplate` in the company settings, and sends them to a
+//! serial, USB or network printer, or to a file.
+
+use crate::models::cash_register::{CashMovement, SessionReport};
+use crate::models::{CompanySettings, Invoice, InvoiceItem, Payment};
+use crate::services::cash_reports::{self, method_label, row, stamp};
+use crate::state::ServiceError;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+const LF: u8 = 0x0a;
+
+/// Port raw ESC/POS network printers listen on
+const DEFAULT_PORT: u16 = 9100;
+
+pub enum Align {
+    Left,
+    Center,
+}
+
+/// ESC/POS command stream for one ticket
+pub struct Ticket {
+    bytes: Vec<u8>,
+    columns: usize,
+    dots: usize,
+}
+
+impl Ticket {
+    /// Resets the printer and selects the Windows-1252 code page so
+    /// accents, ñ and € print as such
+    pub fn new(paper_width: u16) -> Self {
+        let (columns, dots) = if paper_width == 58 {
+            (32, 384)
+        } else {
+            (48, 576)
+        };
+        let mut ticket = Self {
+            bytes: Vec::new(),
+            columns,
+            dots,
+        };
+        ticket.raw(&[ESC, b'@', ESC, b't', 16]);
+        ticket
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    fn raw(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn align(&mut self, align: Align) {
+        let n = match align {
+            Align::Left => 0,
+            Align::Center => 1,
+        };
+        self.raw(&[ESC, b'a', n]);
+    }
+
+    pub fn bold(&mut self, on: bool) {
+        self.raw(&[ESC, b'E', on as u8]);
+    }
+
+    /// Double-height characters; the line keeps its column count
+    pub fn tall(&mut self, on: bool) {
+        self.raw(&[GS, b'!', if on { 0x01 } else { 0x00 }]);
+    }
+
+    /// Prints the text, wrapped at the paper width
+    pub fn text(&mut self, text: &str) {
+        for line in wrap(text, self.columns) {
+            self.line(&line);
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        let encoded = encode(line);
+        self.raw(&encoded);
+        self.raw(&[LF]);
+    }
+
+    /// Label on the left, value on the right
+    pub fn row(&mut self, label: &str, value: &str) {
+        let line = row(label, value, self.columns);
+        self.line(&line);
+    }
+
+    pub fn rule(&mut self) {
+        let line = "-".repeat(self.columns);
+        self.line(&line);
+    }
+
+    pub fn feed(&mut self, lines: u8) {
+        if lines > 0 {
+            self.raw(&[ESC, b'd', lines]);
+        }
+    }
+
+    /// Native QR code (model 2, error correction M)
+    pub fn qr(&mut self, data: &str) {
+        let module = if self.dots < 576 { 4 } else { 6 };
+        let len = data.len() + 3;
+        self.raw(&[GS, b'(', b'k', 4, 0, 49, 65, 50, 0]);
+        self.raw(&[GS, b'(', b'k', 3, 0, 49, 67, module]);
+        self.raw(&[GS, b'(', b'k', 3, 0, 49, 69, 49]);
+        self.raw(&[
+            GS,
+            b'(',
+            b'k',
+            (len % 256) as u8,
+            (len / 256) as u8,
+            49,
+            80,
+            48,
+        ]);
+        self.raw(data.as_bytes());
+        self.raw(&[GS, b'(', b'k', 3, 0, 49, 81, 48]);
+        self.raw(&[LF]);
+    }
+
+    /// Raster image, scaled down to the printable width
+    pub fn image(&mut self, bitmap: &Bitmap) {
+        let bitmap = bitmap.fit(self.dots);
+        let width_bytes = bitmap.width.div_ceil(8);
+        self.raw(&[
+            GS,
+            b'v',
+            b'0',
+            0,
+            (width_bytes % 256) as u8,
+            (width_bytes / 256) as u8,
+            (bitmap.height % 256) as u8,
+            (bitmap.height / 256) as u8,
+        ]);
+        for y in 0..bitmap.height {
+            for byte in 0..width_bytes {
+                let mut bits = 0u8;
+                for bit in 0..8 {
+                    let x = byte * 8 + bit;
+                    if x < bitmap.width && bitmap.pixels[y * bitmap.width + x] {
+                        bits |= 0x80 >> bit;
+                    }
+                }
+                self.bytes.push(bits);
+            }
+        }
+        self.raw(&[LF]);
+    }
+
+    /// Feeds to the cutter and makes a partial cut
+    pub fn cut(&mut self) {
+        self.raw(&[GS, b'V', 66, 0]);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Windows-1252 bytes; anything outside the code page becomes '?'
+fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x20..=0x7e | 0xa0..=0xff => c as u32 as u8,
+            _ if c == '€' => 0x80,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Greedy word wrap; words longer than a line are split
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    if text.is_empty() {
+        lines.push(String::new());
+    }
+    for paragraph in text.lines() {
+        let mut current = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            while word.len() > width {
+                if !current.is_empty() {
+                    lines.push(std::mem::take(&mut current));
+                }
+                lines.push(word.drain(..width).collect());
+            }
+            let needed = current.chars().count() + word.len() + usize::from(!current.is_empty());
+            if needed > width && !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.extend(word);
+        }
+        lines.push(current);
+    }
+    lines
+}
+
+/// Monochrome image, one flag per pixel (true = black)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Bitmap {
+    /// Nearest-neighbour downscale to at most `max_width` pixels wide
+    fn fit(&self, max_width: usize) -> Bitmap {
+        if self.width <= max_width {
+            return self.clone();
+        }
+        let height = (self.height * max_width / self.width).max(1);
+        let mut pixels = Vec::with_capacity(max_width * height);
+        for y in 0..height {
+            let sy = y * self.height / height;
+            for x in 0..max_width {
+                pixels.push(self.pixels[sy * self.width + x * self.width / max_width]);
+            }
+        }
+        Bitmap {
+            width: max_width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// Decodes an uncompressed BMP (1, 8, 24 or 32 bits per pixel), turning
+/// pixels darker than mid-grey black
+pub fn decode_bmp(data: &[u8]) -> Result<Bitmap, String> {
+    let u16_at = |at: usize| -> Result<u16, String> {
+        data.get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or_else(|| "BMP incompleto".to_string())
+    };
+    let u32_at = |at: usize| -> Result<u32, String> {
+        data.get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| "BMP incompleto".to_string())
+    };
+
+    if data.get(..2) != Some(b"BM") {
+        return Err("El logo debe ser un BMP".to_string());
+    }
+    let offset = u32_at(10)? as usize;
+    let header_size = u32_at(14)? as usize;
+    let width = u32_at(18)? as i32;
+    let height = u32_at(22)? as i32;
+    let bpp = u16_at(28)?;
+    let compression = u32_at(30)?;
+    if width <= 0 || height == 0 {
+        return Err("BMP sin dimensiones".to_string());
+    }
+    if !(compression == 0 || (compression == 3 && bpp == 32)) || ![1, 8, 24, 32].contains(&bpp) {
+        return Err(format!(
+            "BMP no soportado ({} bits, compresión {})",
+            bpp, compression
+        ));
+    }
+
+    let palette = if bpp <= 8 {
+        let used = u32_at(46).unwrap_or(0) as usize;
+        let count = if used == 0 { 1 << bpp } else { used };
+        (0..count)
+            .map(|i| {
+                let at = 14 + header_size + i * 4;
+                data.get(at..at + 3)
+                    .map(|c| luma(c[2], c[1], c[0]))
+                    .ok_or_else(|| "Paleta BMP incompleta".to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        Vec::new()
+    };
+
+    let width = width as usize;
+    let rows = height.unsigned_abs() as usize;
+    let stride = (width * bpp as usize).div_ceil(32) * 4;
+    let mut pixels = Vec::with_capacity(width * rows);
+    for y in 0..rows {
+        // Positive heights are stored bottom-up
+        let source = if height > 0 { rows - 1 - y } else { y };
+        let start = offset + source * stride;
+        let line = data
+            .get(start..start + stride)
+            .ok_or_else(|| "BMP incompleto".to_string())?;
+        for x in 0..width {
+            let level = match bpp {
+                1 => palette
+                    .get(((line[x / 8] >> (7 - x % 8)) & 1) as usize)
+                    .copied(),
+                8 => palette.get(line[x] as usize).copied(),
+                _ => {
+                    let at = x * (bpp as usize / 8);
+                    Some(luma(line[at + 2], line[at + 1], line[at]))
+                }
+            };
+            pixels.push(level.unwrap_or(255) < 128);
+        }
+    }
+
+    Ok(Bitmap {
+        width,
+        height: rows,
+        pixels,
+    })
+}
+
+fn luma(r: u8, g: u8, b: u8) -> u32 {
+    (299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000
+}
+
+/// The company logo, when the template asks for it and the file can be read
+pub fn load_logo(settings: &CompanySettings) -> Option<Bitmap> {
+    if !settings.ticket_template.show_logo {
+        return None;
+    }
+    let data = std::fs::read(settings.logo_path.as_deref()?).ok()?;
+    decode_bmp(&data).ok()
+}
+
+/// Where a ticket is sent
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrinterTarget {
+    Serial(String),
+    Usb(String),
+    Network(String),
+    File(String),
+}
+
+impl PrinterTarget {
+    /// Parses "serial:/dev/ttyS0", "usb:/dev/usb/lp0", "network:host[:port]"
+    /// or "file:/path"
+    pub fn parse(target: &str) -> Result<Self, ServiceError> {
+        let invalid = || {
+            ServiceError::Validation(format!(
+                "Impresora inválida '{}': use serial:, usb:, network: o file:",
+                target
+            ))
+        };
+        let (kind, rest) = target.split_once(':').ok_or_else(invalid)?;
+        let rest = rest.trim();
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+        match kind.trim() {
+            "serial" => Ok(Self::Serial(rest.to_string())),
+            "usb" => Ok(Self::Usb(rest.to_string())),
+            "network" => {
+                let has_port = rest
+                    .rsplit_once(':')
+                    .is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+                Ok(Self::Network(if has_port {
+                    rest.to_string()
+                } else {
+                    format!("{}:{}", rest, DEFAULT_PORT)
+                }))
+            }
+            "file" => Ok(Self::File(rest.to_string())),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for PrinterTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serial(path) => write!(f, "serial:{}", path),
+            Self::Usb(path) => write!(f, "usb:{}", path),
+            Self::Network(address) => write!(f, "network:{}", address),
+            Self::File(path) => write!(f, "file:{}", path),
+        }
+    }
+}
+
+/// Writes the ticket to the printer. Serial ports are opened as device
+/// files, so their line speed is whatever the system configured.
+pub fn send(target: &PrinterTarget, bytes: &[u8]) -> Result<(), ServiceError> {
+    let failed = |e: std::io::Error| {
+        ServiceError::Validation(format!("No se pudo imprimir en {}: {}", target, e))
+    };
+    match target {
+        PrinterTarget::Serial(path) | PrinterTarget::Usb(path) => {
+            let mut device = std::fs::OpenOptions::new()
+                .write(true)
+                .open(path)
+                .map_err(failed)?;
+            device.write_all(bytes).map_err(failed)?;
+            device.flush().map_err(failed)
+        }
+        PrinterTarget::Network(address) => {
+            let address = address
+                .to_socket_addrs()
+                .map_err(failed)?
+                .next()
+                .ok_or_else(|| {
+                    ServiceError::Validation(format!("Impresora no encontrada: {}", target))
+                })?;
+            let mut stream =
+                TcpStream::connect_timeout(&address, Duration::from_secs(5)).map_err(failed)?;
+            stream
+                .set_write_timeout(Some(Duration::from_secs(10)))
+                .map_err(failed)?;
+            stream.write_all(bytes).map_err(failed)?;
+            stream.flush().map_err(failed)
+        }
+        PrinterTarget::File(path) => std::fs::write(path, bytes).map_err(failed),
+    }
+}
+
+/// The target given, or else the template's default printer
+pub fn resolve_target(
+    settings: &CompanySettings,
+    target: Option<&str>,
+) -> Result<PrinterTarget, ServiceError> {
+    let target = target
+        .or(settings.ticket_template.printer.as_deref())
+        .ok_or_else(|| ServiceError::Validation("No hay impresora configurada".to_string()))?;
+    PrinterTarget::parse(target)
+}
+
+/// SHA-256 over the invoice's identifying fields, printed as a QR so the
+/// ticket can be checked against the books
+pub fn invoice_fingerprint(invoice: &Invoice) -> String {
+    let payload = format!(
+        "{}|{}|{}|{}|{:.2}|{}",
+        invoice.tenant_id,
+        invoice.invoice_number,
+        invoice.issue_date,
+        invoice.client_tax_id.as_deref().unwrap_or(""),
+        invoice.total,
+        invoice.currency
+    );
+    hex::encode(Sha256::digest(payload.as_bytes()))
+}
+
+fn money(amount: f64) -> String {
+    format!("{:.2}", amount)
+}
+
+fn quantity(quantity: f64) -> String {
+    format!("{}", (quantity * 1000.0).round() / 1000.0)
+}
+
+/// Logo, company data and the template's header lines
+fn header(ticket: &mut Ticket, settings: &CompanySettings, logo: Option<&Bitmap>) {
+    ticket.align(Align::Center);
+    if let Some(logo) = logo {
+        ticket.image(logo);
+    }
+    ticket.bold(true);
+    ticket.text(&settings.name);
+    ticket.bold(false);
+    ticket.text(&format!("RIF: {}", settings.legal_id));
+    if !settings.address.is_empty() {
+        ticket.text(&settings.address);
+    }
+    let place: Vec<&str> = [settings.city.as_str(), settings.state.as_str()]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect();
+    if !place.is_empty() {
+        ticket.text(&place.join(", "));
+    }
+    if let Some(ref phone) = settings.phone {
+        ticket.text(&format!("Tel: {}", phone));
+    }
+    for line in &settings.ticket_template.header {
+        ticket.text(line);
+    }
+    ticket.align(Align::Left);
+    ticket.rule();
+}
+
+fn title(ticket: &mut Ticket, title: &str) {
+    ticket.align(Align::Center);
+    ticket.bold(true);
+    ticket.text(title);
+    ticket.bold(false);
+    ticket.align(Align::Left);
+}
+
+/// Footer lines, feed and cut
+fn footer(ticket: &mut Ticket, settings: &CompanySettings, note: Option<&str>) {
+    let template = &settings.ticket_template;
+    ticket.align(Align::Center);
+    if let Some(note) = note {
+        ticket.text(note);
+    }
+    for line in &template.footer {
+        ticket.text(line);
+    }
+    ticket.align(Align::Left);
+    ticket.feed(template.feed_lines);
+    if template.cut {
+        ticket.cut();
+    }
+}
+
+fn document_label(invoice_type: &str) -> &str {
+    match invoice_type {
+        "quote" => "PRESUPUESTO",
+        "credit_note" => "NOTA DE CRÉDITO",
+        "debit_note" => "NOTA DE DÉBITO",
+        _ => "FACTURA",
+    }
+}
+
+/// Invoice ticket: lines, totals by tax rate, tenders, change and a QR
+/// with the invoice fingerprint
+pub fn invoice_ticket(
+    settings: &CompanySettings,
+    logo: Option<&Bitmap>,
+    invoice: &Invoice,
+    items: &[InvoiceItem],
+    payments: &[Payment],
+) -> Vec<u8> {
+    let mut ticket = Ticket::new(settings.ticket_template.paper_width);
+    header(&mut ticket, settings, logo);
+    title(
+        &mut ticket,
+        &format!(
+            "{} {}",
+            document_label(&invoice.invoice_type),
+            invoice.invoice_number
+        ),
+    );
+    ticket.row("Fecha:", &stamp(&invoice.issue_date));
+    ticket.text(&format!("Cliente: {}", invoice.client_name));
+    if let Some(ref tax_id) = invoice.client_tax_id {
+        ticket.text(&format!("RIF/CI: {}", tax_id));
+    }
+    ticket.rule();
+
+    let mut taxes: BTreeMap<i64, f64> = BTreeMap::new();
+    for item in items {
+        ticket.text(&item.description);
+        ticket.row(
+            &format!("  {} x {}", quantity(item.quantity), money(item.unit_price)),
+            &money(item.line_total),
+        );
+        if item.discount_amount > 0.0 {
+            ticket.row("  Descuento", &money(-item.discount_amount));
+        }
+        *taxes
+            .entry((item.tax_rate * 100.0).round() as i64)
+            .or_default() += item.tax_amount;
+    }
+    ticket.rule();
+
+    ticket.row("Subtotal", &money(invoice.subtotal));
+    if invoice.discount_total > 0.0 {
+        ticket.row("Descuento", &money(-invoice.discount_total));
+    }
+    if taxes.is_empty() {
+        ticket.row("IVA", &money(invoice.tax_total));
+    }
+    for (rate, tax) in taxes {
+        let label = if rate == 0 {
+            "Exento".to_string()
+        } else {
+            format!("IVA {}%", rate as f64 / 100.0)
+        };
+        ticket.row(&label, &money(tax));
+    }
+    ticket.bold(true);
+    ticket.tall(true);
+    ticket.row(
+        &format!("TOTAL {}", invoice.currency),
+        &money(invoice.total),
+    );
+    ticket.tall(false);
+    ticket.bold(false);
+    if invoice.currency != "VES" && invoice.exchange_rate > 0.0 {
+        ticket.row("Tasa BCV", &money(invoice.exchange_rate));
+        ticket.row("Total VES", &money(invoice.total * invoice.exchange_rate));
+    }
+
+    if !payments.is_empty() {
+        ticket.rule();
+        for payment in payments {
+            ticket.row(
+                &format!(
+                    "{} {}",
+                    method_label(&payment.payment_method),
+                    payment.currency
+                ),
+                &money(payment.received_amount.unwrap_or(payment.amount)),
+            );
+        }
+        if invoice.change_amount > 0.0 {
+            ticket.row(
+                &format!(
+                    "Vuelto {}",
+                    invoice
+                        .change_currency
+                        .as_deref()
+                        .unwrap_or(&invoice.currency)
+                ),
+                &money(invoice.change_amount),
+            );
+        }
+    }
+    if let Some(ref notes) = invoice.notes {
+        ticket.rule();
+        ticket.text(notes);
+    }
+
+    if settings.ticket_template.show_qr && invoice.invoice_type != "quote" {
+        let fingerprint = invoice_fingerprint(invoice);
+        ticket.feed(1);
+        ticket.align(Align::Center);
+        ticket.qr(&format!("{}|{}", invoice.invoice_number, fingerprint));
+        ticket.text(&fingerprint[..16]);
+        ticket.align(Align::Left);
+    }
+    footer(&mut ticket, settings, settings.legal_note.as_deref());
+    ticket.into_bytes()
+}
+
+/// Receipt for one payment against an invoice
+pub fn payment_receipt(
+    settings: &CompanySettings,
+    logo: Option<&Bitmap>,
+    invoice: &Invoice,
+    payment: &Payment,
+) -> Vec<u8> {
+    let mut ticket = Ticket::new(settings.ticket_template.paper_width);
+    header(&mut ticket, settings, logo);
+    title(&mut ticket, "RECIBO DE PAGO");
+    ticket.row("Fecha:", &stamp(&payment.payment_date));
+    ticket.row("Factura:", &invoice.invoice_number);
+    ticket.text(&format!("Cliente: {}", invoice.client_name));
+    ticket.rule();
+    ticket.row("Forma de pago:", method_label(&payment.payment_method));
+    if let Some(ref reference) = payment.reference {
+        ticket.row("Referencia:", reference);
+    }
+    if let Some(received) = payment.received_amount {
+        ticket.row(&format!("Recibido {}", payment.currency), &money(received));
+    }
+    ticket.bold(true);
+    ticket.row(
+        &format!("Abonado {}", invoice.currency),
+        &money(payment.amount),
+    );
+    ticket.bold(false);
+    ticket.row(
+        "Saldo pendiente",
+        &money((invoice.total - invoice.paid_amount).max(0.0)),
+    );
+    if let Some(ref notes) = payment.notes {
+        ticket.rule();
+        ticket.text(notes);
+    }
+    footer(&mut ticket, settings, None);
+    ticket.into_bytes()
+}
+
+/// Slip for a cash deposit or withdrawal, with signature lines
+pub fn movement_slip(
+    settings: &CompanySettings,
+    logo: Option<&Bitmap>,
+    movement: &CashMovement,
+    register_name: &str,
+) -> Vec<u8> {
+    let mut ticket = Ticket::new(settings.ticket_template.paper_width);
+    header(&mut ticket, settings, logo);
+    title(
+        &mut ticket,
+        if movement.movement_type == "deposit" {
+            "ENTRADA DE EFECTIVO"
+        } else {
+            "SALIDA DE EFECTIVO"
+        },
+    );
+    ticket.row("Caja:", register_name);
+    ticket.row("Fecha:", &stamp(&movement.created_at));
+    ticket.bold(true);
+    ticket.row(
+        &format!("Monto {}", movement.currency),
+        &money(movement.amount),
+    );
+    ticket.bold(false);
+    if let Some(ref reason) = movement.reason {
+        ticket.text(&format!("Motivo: {}", reason));
+    }
+    if let Some(ref reference) = movement.reference {
+        ticket.row("Referencia:", reference);
+    }
+    ticket.feed(3);
+    ticket.align(Align::Center);
+    let signature = "_".repeat(ticket.columns() * 2 / 3);
+    ticket.text(&signature);
+    ticket.text("Entregado por");
+    ticket.feed(3);
+    ticket.text(&signature);
+    ticket.text("Recibido por");
+    ticket.align(Align::Left);
+    footer(&mut ticket, settings, None);
+    ticket.into_bytes()
+}
+
+/// X or Z report, with its content hash as a QR
+pub fn session_report(
+    settings: &CompanySettings,
+    logo: Option<&Bitmap>,
+    report: &SessionReport,
+) -> Vec<u8> {
+    let mut ticket = Ticket::new(settings.ticket_template.paper_width);
+    header(&mut ticket, settings, logo);
+    let mut lines = cash_reports::render_lines(report, ticket.columns()).into_iter();
+    if let Some(first) = lines.next() {
+        title(&mut ticket, first.trim());
+    }
+    for line in lines {
+        ticket.line(&line);
+    }
+    if let (true, Some(hash)) = (settings.ticket_template.show_qr, report.hash.as_deref()) {
+        ticket.feed(1);
+        ticket.align(Align::Center);
+        ticket.qr(hash);
+        ticket.align(Align::Left);
+    }
+    footer(&mut ticket, settings, None);
+    ticket.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::cash_register::{CurrencyBalance, PaymentMethodTotal, SalesTotal, TaxTotal};
+    use crate::models::{ScaleLabelRule, TicketTemplate};
+    use std::path::PathBuf;
+
+    /// Compares against `testdata/escpos/<name>.bin`; run with
+    /// EQUINOX_UPDATE_GOLDEN=1 to rewrite the files after an intended change
+    fn assert_golden(name: &str, bytes: &[u8]) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/services/testdata/escpos")
+            .join(format!("{}.bin", name));
+        if std::env::var_os("EQUINOX_UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, bytes).unwrap();
+            return;
+        }
+        let expected = std::fs::read(&path).unwrap_or_else(|e| {
+            panic!(
+                "{}: {} (EQUINOX_UPDATE_GOLDEN=1 crea el archivo)",
+                path.display(),
+                e
+            )
+        });
+        if let Some(at) = expected.iter().zip(bytes).position(|(a, b)| a != b) {
+            panic!(
+                "{}: difiere en el byte {} (esperado {:#04x}, obtenido {:#04x})",
+                name, at, expected[at], bytes[at]
+            );
+        }
+        assert_eq!(expected.len(), bytes.len(), "{}: longitud distinta", name);
+    }
+
+    fn settings(paper_width: u16) -> CompanySettings {
+        CompanySettings {
+            id: "cs-1".to_string(),
+            tenant_id: "t1".to_string(),
+            name: "Bodegón La Esquina C.A.".to_string(),
+            legal_id: "J-12345678-9".to_string(),
+            address: "Av. Bolívar, Local 3".to_string(),
+            city: "Valencia".to_string(),
+            state: "Carabobo".to_string(),
+            country: "Venezuela".to_string(),
+            postal_code: None,
+            phone: Some("0241-5551234".to_string()),
+            email: None,
+            website: None,
+            logo_path: None,
+            invoice_prefix: "FAC".to_string(),
+            invoice_counter: 41,
+            default_currency: "USD".to_string(),
+            legal_note: Some("Precios incluyen IVA cuando aplica".to_string()),
+            invoice_pattern: None,
+            ticket_template: TicketTemplate {
+                paper_width,
+                footer: vec!["¡Gracias por su compra!".to_string()],
+                ..TicketTemplate::default()
+            },
+            scale_labels: ScaleLabelRule::defaults(),
+            created_at: "2026-01-01T00:00:00Z".to_string(),
+            updated_at: "2026-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    fn invoice() -> Invoice {
+        Invoice {
+            id: "inv-1".to_string(),
+            tenant_id: "t1".to_string(),
+            invoice_number: "FAC-00000042".to_string(),
+            invoice_type: "invoice".to_string(),
+            status: "paid".to_string(),
+            client_id: "c1".to_string(),
+            client_name: "María Pérez".to_string(),
+            client_tax_id: Some("V-12345678".to_string()),
+            client_address: None,
+            price_list_id: None,
+            currency: "USD".to_string(),
+            exchange_rate: 40.5,
+            issue_date: "2026-10-19T14:30:00Z".to_string(),
+            due_date: None,
+            payment_terms: Some("CONTADO".to_string()),
+            subtotal: 15.0,
+            discount_total: 0.5,
+            tax_total: 1.6,
+            total: 16.1,
+            paid_amount: 16.1,
+            notes: None,
+            warehouse_id: None,
+            payment_method: None,
+            coupon_codes: Vec::new(),
+            register_id: Some("r1".to_string()),
+            session_id: Some("s1".to_string()),
+            change_amount: 157.95,
+            change_currency: Some("VES".to_string()),
+            created_by: "u1".to_string(),
+            created_at: "2026-10-19T14:30:00Z".to_string(),
+            updated_at: "2026-10-19T14:30:00Z".to_string(),
+        }
+    }
+
+    fn item(description: &str, quantity: f64, price: f64, discount: f64, rate: f64) -> InvoiceItem {
+        let net = quantity * price - discount;
+        InvoiceItem {
+            id: format!("it-{}", description.len()),
+            invoice_id: "inv-1".to_string(),
+            product_id: "p1".to_string(),
+            variant_id: None,
+            lot_id: None,
+            code: "SKU".to_string(),
+            description: description.to_string(),
+            quantity,
+            unit_price: price,
+            discount_percent: 0.0,
+            discount_amount: discount,
+            tax_rate: rate,
+            tax_amount: net * rate / 100.0,
+            line_total: net,
+            pricing_rules: Vec::new(),
+        }
+    }
+
+    fn payment(method: &str, currency: &str, amount: f64, received: f64) -> Payment {
+        Payment {
+            id: format!("pay-{}", method),
+            tenant_id: "t1".to_string(),
+            invoice_id: "inv-1".to_string(),
+            amount,
+            currency: currency.to_string(),
+            received_amount: Some(received),
+            exchange_rate: 1.0,
+            payment_method: method.to_string(),
+            reference: (method == "card").then(|| "000123".to_string()),
+            bank_account_id: None,
+            payment_date: "2026-10-19".to_string(),
+            notes: None,
+            session_id: Some("s1".to_string()),
+            created_by: "u1".to_string(),
+            created_at: "2026-10-19T14:30:00Z".to_string(),
+        }
+    }
+
+    fn movement() -> CashMovement {
+        CashMovement {
+            id: "m1".to_string(),
+            tenant_id: "t1".to_string(),
+            session_id: "s1".to_string(),
+            user_id: "u1".to_string(),
+            movement_type: "withdrawal".to_string(),
+            amount: 50.0,
+            currency: "USD".to_string(),
+            exchange_rate: 1.0,
+            reason: Some("Pago a proveedor de hielo".to_string()),
+            reference: Some("REC-7".to_string()),
+            created_at: "2026-10-19T12:05:09Z".to_string(),
+        }
+    }
+
+    fn report() -> SessionReport {
+        SessionReport {
+            id: "rep-1".to_string(),
+            report_type: "Z".to_string(),
+            report_number: 7,
+            session_id: "s1".to_string(),
+            register_id: "r1".to_string(),
+            register_name: "Caja 1".to_string(),
+            user_id: "u1".to_string(),
+            user_name: Some("Ana".to_string()),
+            start_time: "2026-10-19T08:00:00Z".to_string(),
+            end_time: Some("2026-10-19T18:00:00Z".to_string()),
+            opening_exchange_rate_ves: 40.5,
+            opening_exchange_rate_eur: 0.92,
+            balances: vec![CurrencyBalance {
+                currency: "USD".to_string(),
+                opening: 20.0,
+                cash_sales: 120.0,
+                change_given: 0.0,
+                deposits: 0.0,
+                withdrawals: 50.0,
+                expected: 90.0,
+                counted: Some(89.0),
+                over_short: Some(-1.0),
+            }],
+            payments: vec![PaymentMethodTotal {
+                payment_method: "cash".to_string(),
+                currency: "USD".to_string(),
+                count: 8,
+                amount: 120.0,
+            }],
+            sales: vec![SalesTotal {
+                currency: "USD".to_string(),
+                invoices: 8,
+                subtotal: 110.0,
+                discount_total: 0.0,
+                tax_total: 10.0,
+                total: 120.0,
+            }],
+            taxes: vec![TaxTotal {
+                currency: "USD".to_string(),
+                tax_rate: 16.0,
+                taxable: 62.5,
+                tax: 10.0,
+            }],
+            refunds: Vec::new(),
+            movements: vec![movement()],
+            denominations: Vec::new(),
+            rates: Vec::new(),
+            created_by: "u1".to_string(),
+            created_at: "2026-10-19T18:00:00Z".to_string(),
+            hash: Some("ab".repeat(32)),
+        }
+    }
+
+    /// 1-bit BMP, 10x2, left half black
+    fn bmp() -> Vec<u8> {
+        let mut data = b"BM".to_vec();
+        data.extend_from_slice(&(62u32 + 8).to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend_from_slice(&62u32.to_le_bytes());
+        data.extend_from_slice(&40u32.to_le_bytes());
+        data.extend_from_slice(&10i32.to_le_bytes());
+        data.extend_from_slice(&2i32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&[0; 24]);
+        data.extend_from_slice(&[0, 0, 0, 0, 255, 255, 255, 0]);
+        for _ in 0..2 {
+            data.extend_from_slice(&[0b0000_0111, 0b1100_0000, 0, 0]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_decode_bmp_and_printer_targets() {
+        let logo = decode_bmp(&bmp()).unwrap();
+        assert_eq!((logo.width, logo.height), (10, 2));
+        assert_eq!(
+            logo.pixels[..10],
+            [true, true, true, true, true, false, false, false, false, false]
+        );
+        assert_eq!(logo.fit(5).pixels[..5], [true, true, true, false, false]);
+        assert!(decode_bmp(b"GIF89a").is_err());
+
+        assert_eq!(
+            PrinterTarget::parse("network:10.0.0.5").unwrap(),
+            PrinterTarget::Network("10.0.0.5:9100".to_string())
+        );
+        assert_eq!(
+            PrinterTarget::parse("usb:/dev/usb/lp0").unwrap(),
+            PrinterTarget::Usb("/dev/usb/lp0".to_string())
+        );
+        assert!(PrinterTarget::parse("bluetooth:printer").is_err());
+        assert!(PrinterTarget::parse("file:").is_err());
+
+        let path = std::env::temp_dir().join(format!("escpos-{}.bin", std::process::id()));
+        let target = PrinterTarget::parse(&format!("file:{}", path.display())).unwrap();
+        send(&target, b"\x1b@").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"\x1b@");
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_tickets_match_golden_files() {
+        let logo = decode_bmp(&bmp()).unwrap();
+        let invoice = invoice();
+        let items = vec![
+            item("Harina PAN 1kg", 2.0, 1.5, 0.0, 0.0),
+            item(
+                "Queso blanco duro, pieza de exportación",
+                0.75,
+                16.0,
+                0.5,
+                16.0,
+            ),
+        ];
+        let payments = vec![
+            payment("card", "USD", 10.0, 10.0),
+            payment("cash", "VES", 6.1, 405.0),
+        ];
+
+        for width in [58, 80] {
+            let settings = settings(width);
+            assert_golden(
+                &format!("invoice_{}", width),
+                &invoice_ticket(&settings, Some(&logo), &invoice, &items, &payments),
+            );
+        }
+        let settings = settings(80);
+        assert_golden(
+            "payment_receipt_80",
+            &payment_receipt(&settings, None, &invoice, &payments[1]),
+        );
+        assert_golden(
+            "movement_slip_58",
+            &movement_slip(&self::settings(58), None, &movement(), "Caja 1"),
+        );
+        assert_golden("z_report_80", &session_report(&settings, None, &report()));
+
+        // The QR carries the fingerprint, and accents go out in Windows-1252
+        let bytes = invoice_ticket(&settings, None, &invoice, &items, &payments);
+        let fingerprint = invoice_fingerprint(&invoice);
+        assert!(bytes
+            .windows(fingerprint.len())
+            .any(|w| w == fingerprint.as_bytes()));
+        assert!(bytes.windows(5).any(|w| w == b"Mar\xeda"));
+        assert!(bytes.ends_with(&[GS, b'V', 66, 0]));
+    }
+}
@@ -0,0 +1,301 @@
+//! Exchange Rates
+//!
+//! Rates are recorded as VES per unit of a currency, the way invoices and
+//! register sessions store them; VES itself is always 1. Recording a rate
+//! recomputes every derived price list that converts from or to that
+//! currency. A rate applies from its date; dates ahead of today are
+//! rejected because nothing would recompute the lists when they arrive.
+
+use crate::models::exchange_rate::{ExchangeRate, ExchangeRateUpdate, RecordExchangeRateDto};
+use crate::services::repricing;
+use crate::state::ServiceError;
+use chrono::{NaiveDate, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use rust_decimal::prelude::*;
+use uuid::Uuid;
+
+fn db(e: rusqlite::Error) -> ServiceError {
+    ServiceError::Database(e.to_string())
+}
+
+const BASE_CURRENCY: &str = "VES";
+
+const RATE_COLUMNS: &str = "id, currency, rate, rate_date, source, created_by, created_at";
+
+fn map_rate(row: &rusqlite::Row) -> rusqlite::Result<ExchangeRate> {
+    Ok(ExchangeRate {
+        id: row.get(0)?,
+        currency: row.get(1)?,
+        rate: row.get(2)?,
+        rate_date: row.get(3)?,
+        source: row.get(4)?,
+        created_by: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+/// Rate in effect today for a currency, in VES per unit
+pub fn latest_rate(
+    conn: &Connection,
+    tenant_id: &str,
+    currency: &str,
+) -> Result<Option<f64>, ServiceError> {
+    rate_on(
+        conn,
+        tenant_id,
+        currency,
+        &Utc::now().format("%Y-%m-%d").to_string(),
+    )
+}
+
+/// Rate in effect on a date (YYYY-MM-DD), in VES per unit
+pub fn rate_on(
+    conn: &Connection,
+    tenant_id: &str,
+    currency: &str,
+    date: &str,
+) -> Result<Option<f64>, ServiceError> {
+    if currency == BASE_CURRENCY {
+        return Ok(Some(1.0));
+    }
+    conn.query_row(
+        "SELECT rate FROM exchange_rates
+         WHERE tenant_id = ?1 AND currency = ?2 AND rate_date <= ?3
+         ORDER BY rate_date DESC, created_at DESC LIMIT 1",
+        params![tenant_id, currency, date],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(db)
+}
+
+/// Factor that converts an amount in `from` to `to`
+pub fn conversion(
+    conn: &Connection,
+    tenant_id: &str,
+    from: &str,
+    to: &str,
+) -> Result<Decimal, ServiceError> {
+    if from == to {
+        return Ok(Decimal::ONE);
+    }
+    let rate = |currency: &str| -> Result<Decimal, ServiceError> {
+        latest_rate(conn, tenant_id, currency)?
+            .filter(|rate| *rate > 0.0)
+            .and_then(Decimal::from_f64)
+            .ok_or_else(|| {
+                ServiceError::Validation(format!("No hay tasa registrada para {}", currency))
+            })
+    };
+    Ok(rate(from)? / rate(to)?)
+}
+
+/// Record a rate and recompute the derived lists that depend on it
+pub fn record(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    data: RecordExchangeRateDto,
+) -> Result<ExchangeRateUpdate, ServiceError> {
+    let currency = data.currency.trim().to_uppercase();
+    if currency.is_empty() || currency == BASE_CURRENCY {
+        return Err(ServiceError::Validation(format!(
+            "Moneda inválida: las tasas se expresan en {} por unidad",
+            BASE_CURRENCY
+        )));
+    }
+    if !data.rate.is_finite() || data.rate <= 0.0 {
+        return Err(ServiceError::Validation(
+            "La tasa debe ser mayor a cero".to_string(),
+        ));
+    }
+    let today = Utc::now().date_naive();
+    let rate_date = match data.rate_date {
+        Some(date) => NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .map_err(|_| ServiceError::Validation(format!("Fecha inválida: {}", date)))?,
+        None => today,
+    };
+    if rate_date > today {
+        return Err(ServiceError::Validation(
+            "La fecha de la tasa no puede ser posterior a hoy".to_string(),
+        ));
+    }
+    let rate_date = rate_date.to_string();
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    tx.execute(
+        "INSERT INTO exchange_rates (id, tenant_id, currency, rate, rate_date, source, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![id, tenant_id, currency, data.rate, rate_date, data.source, user_id, now],
+    )
+    .map_err(db)?;
+
+    let mut stmt = tx
+        .prepare(
+            "SELECT l.id FROM price_lists l
+             JOIN price_lists b ON b.id = l.base_price_list_id
+             WHERE l.tenant_id = ?1 AND l.is_active = 1 AND (l.currency = ?2 OR b.currency = ?2)
+             ORDER BY l.name",
+        )
+        .map_err(db)?;
+    let lists = stmt
+        .query_map(params![tenant_id, currency], |row| row.get::<_, String>(0))
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    drop(stmt);
+
+    let mut batches = Vec::new();
+    for list_id in lists {
+        match repricing::derive_list_in(&tx, tenant_id, Some(user_id), &list_id, &now) {
+            Ok(Some(batch)) => batches.push(batch),
+            Ok(None) => {}
+            // The other currency of the list has no rate yet
+            Err(ServiceError::Validation(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    let rate = tx
+        .query_row(
+            &format!("SELECT {} FROM exchange_rates WHERE id = ?1", RATE_COLUMNS),
+            [&id],
+            map_rate,
+        )
+        .map_err(db)?;
+    tx.commit().map_err(db)?;
+
+    Ok(ExchangeRateUpdate { rate, batches })
+}
+
+/// Recorded rates, newest first
+pub fn list_rates(
+    conn: &Connection,
+    tenant_id: &str,
+    currency: Option<&str>,
+    limit: Option<u32>,
+) -> Result<Vec<ExchangeRate>, ServiceError> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM exchange_rates
+             WHERE tenant_id = ?1 AND (?2 IS NULL OR currency = ?2)
+             ORDER BY rate_date DESC, created_at DESC LIMIT ?3",
+            RATE_COLUMNS
+        ))
+        .map_err(db)?;
+    let rates = stmt
+        .query_map(params![tenant_id, currency, limit.unwrap_or(100)], map_rate)
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(rates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record_rate(conn: &Connection, rate: f64) -> ExchangeRateUpdate {
+        record(
+            conn,
+            "t1",
+            "u1",
+            RecordExchangeRateDto {
+                currency: "usd".to_string(),
+                rate,
+                rate_date: None,
+                source: Some("BCV".to_string()),
+            },
+        )
+        .unwrap()
+    }
+
+    fn ves_price(conn: &Connection, product_id: &str) -> f64 {
+        conn.query_row(
+            "SELECT price FROM product_prices WHERE price_list_id = 'ves' AND product_id = ?1",
+            [product_id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rate_reprices_derived_list_and_undo() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, sale_price)
+                 VALUES ('p1', 't1', 'ARR-1', 'Arroz', 2.0, 2.0),
+                        ('p2', 't1', 'HAR-1', 'Harina', 1.3, 1.3);
+             INSERT INTO price_lists (id, tenant_id, name, currency, discount_percent, created_at, updated_at)
+                 VALUES ('usd', 't1', 'Detal USD', 'USD', 0, '2026-01-01', '2026-01-01');
+             INSERT INTO price_lists (id, tenant_id, name, currency, discount_percent, base_price_list_id, round_to, round_mode, created_at, updated_at)
+                 VALUES ('ves', 't1', 'Detal Bs', 'VES', 0, 'usd', 1, 'up', '2026-01-01', '2026-01-01');
+             INSERT INTO product_prices (id, price_list_id, product_id, price, created_at, updated_at)
+                 VALUES ('pp1', 'usd', 'p2', 1.5, '2026-01-01', '2026-01-01');",
+        )
+        .unwrap();
+        assert!(repricing::check_derivation(&conn, "t1", Some("ves"), "usd", "VES").is_err());
+
+        // A rate dated ahead would never be applied
+        let tomorrow = Utc::now().date_naive() + chrono::Duration::days(1);
+        assert!(record(
+            &conn,
+            "t1",
+            "u1",
+            RecordExchangeRateDto {
+                currency: "USD".to_string(),
+                rate: 36.2,
+                rate_date: Some(tomorrow.to_string()),
+                source: None,
+            },
+        )
+        .is_err());
+
+        // 36.20 Bs/USD, rounded up to whole bolívares; list price wins over sale price
+        let update = record_rate(&conn, 36.2);
+        let first_batch = update.batches[0].id.clone();
+        assert_eq!(update.batches.len(), 1);
+        assert_eq!(update.batches[0].items_count, 2);
+        assert_eq!(ves_price(&conn, "p1"), 73.0);
+        assert_eq!(ves_price(&conn, "p2"), 55.0);
+
+        // Same prices after rounding: nothing to record
+        assert!(record_rate(&conn, 36.4).batches.is_empty());
+
+        let update = record_rate(&conn, 40.0);
+        assert_eq!(ves_price(&conn, "p1"), 80.0);
+        let history: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM price_history WHERE price_list_id = 'ves'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(history, 4);
+
+        // Undoing the latest batch puts back the previous rate's prices
+        let undone = repricing::undo(&conn, "t1", "u1", &update.batches[0].id).unwrap();
+        assert_eq!(undone.restored, 2);
+        assert_eq!(undone.batch.status, "undone");
+        assert_eq!(ves_price(&conn, "p1"), 73.0);
+
+        // Undoing the first batch removes the rows it added, with history
+        let undone = repricing::undo(&conn, "t1", "u1", &first_batch).unwrap();
+        assert_eq!(undone.restored, 2);
+        let (rows, removed): (i64, i64) = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM product_prices WHERE price_list_id = 'ves'),
+                        (SELECT COUNT(*) FROM price_history
+                         WHERE price_list_id = 'ves' AND batch_id IS NULL AND new_price = old_price)",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((rows, removed), (0, 2));
+    }
+}
@@ -0,0 +1,739 @@
+//! Warehouse Stock
+//!
+//! Stock is kept per warehouse, product, variant and lot in `warehouse_stock`.
+//! Every stock change goes through `apply`, which also keeps the branch
+//! totals the rest of the app reads (`products.stock_quantity`,
+//! `variant_stock.quantity`, `inventory_lots.quantity`).
+
+use crate::models::warehouse::{
+    CreateWarehouseDto, CreateWarehouseTransferDto, LowStockItem, SetStockLocationDto,
+    UpdateWarehouseDto, Warehouse, WarehouseStock, WarehouseStockFilters, WarehouseTransfer,
+};
+use crate::state::ServiceError;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+
+pub const DEFAULT_WAREHOUSE_CODE: &str = "PRINCIPAL";
+
+fn db(e: rusqlite::Error) -> ServiceError {
+    ServiceError::Database(e.to_string())
+}
+
+/// A signed stock change in one warehouse
+pub struct StockChange<'a> {
+    pub tenant_id: &'a str,
+    pub warehouse_id: &'a str,
+    pub product_id: &'a str,
+    pub variant_id: Option<&'a str>,
+    pub lot_id: Option<&'a str>,
+    pub quantity: f64,
+}
+
+/// What caused a stock movement, for the inventory ledger
+#[derive(Default)]
+pub struct MovementInfo<'a> {
+    pub reference_type: Option<&'a str>,
+    pub reference_id: Option<&'a str>,
+    pub notes: Option<&'a str>,
+    pub user_id: Option<&'a str>,
+}
+
+/// Default warehouse of a branch; created on first use
+pub fn default_warehouse(conn: &Connection, tenant_id: &str) -> Result<String, ServiceError> {
+    let existing: Option<String> = conn
+        .query_row(
+            "SELECT id FROM warehouses WHERE tenant_id = ?1 AND is_active = 1
+             ORDER BY is_default DESC, created_at LIMIT 1",
+            [tenant_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db)?;
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+
+    let warehouse = create_warehouse(
+        conn,
+        tenant_id,
+        CreateWarehouseDto {
+            code: DEFAULT_WAREHOUSE_CODE.to_string(),
+            name: "Almacén principal".to_string(),
+            is_default: Some(true),
+        },
+    )?;
+    Ok(warehouse.id)
+}
+
+/// The requested warehouse if it is an active one of the branch, else the default
+pub fn resolve_warehouse(
+    conn: &Connection,
+    tenant_id: &str,
+    requested: Option<&str>,
+) -> Result<String, ServiceError> {
+    let Some(id) = requested.filter(|id| !id.is_empty()) else {
+        return default_warehouse(conn, tenant_id);
+    };
+
+    let active: Option<bool> = conn
+        .query_row(
+            "SELECT is_active FROM warehouses WHERE id = ?1 AND tenant_id = ?2",
+            params![id, tenant_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db)?;
+    match active {
+        Some(true) => Ok(id.to_string()),
+        Some(false) => Err(ServiceError::Validation(
+            "El almacén está inactivo".to_string(),
+        )),
+        None => Err(ServiceError::NotFound("Almacén no encontrado".to_string())),
+    }
+}
+
+/// Warehouse of the register the user has open, if any
+pub fn session_warehouse(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+) -> Result<Option<String>, ServiceError> {
+    conn.query_row(
+        "SELECT r.warehouse_id FROM cash_register_sessions s
+         JOIN cash_registers r ON r.id = s.register_id
+         WHERE s.tenant_id = ?1 AND s.user_id = ?2 AND s.status = 'active'",
+        params![tenant_id, user_id],
+        |row| row.get::<_, Option<String>>(0),
+    )
+    .optional()
+    .map(Option::flatten)
+    .map_err(db)
+}
+
+/// Change stock in a warehouse and the branch totals
+pub fn apply(conn: &Connection, change: &StockChange, now: &str) -> Result<(), ServiceError> {
+    let updated = conn
+        .execute(
+            "UPDATE warehouse_stock SET quantity = quantity + ?1, updated_at = ?2
+             WHERE warehouse_id = ?3 AND product_id = ?4 AND variant_id IS ?5 AND lot_id IS ?6",
+            params![
+                change.quantity,
+                now,
+                change.warehouse_id,
+                change.product_id,
+                change.variant_id,
+                change.lot_id
+            ],
+        )
+        .map_err(db)?;
+    if updated == 0 {
+        conn.execute(
+            "INSERT INTO warehouse_stock
+                (id, tenant_id, warehouse_id, product_id, variant_id, lot_id, quantity, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                Uuid::new_v4().to_string(),
+                change.tenant_id,
+                change.warehouse_id,
+                change.product_id,
+                change.variant_id,
+                change.lot_id,
+                change.quantity,
+                now
+            ],
+        )
+        .map_err(db)?;
+    }
+
+    conn.execute(
+        "UPDATE products SET stock_quantity = COALESCE(stock_quantity, 0) + ?1, updated_at = ?2
+         WHERE id = ?3 AND tenant_id = ?4",
+        params![change.quantity, now, change.product_id, change.tenant_id],
+    )
+    .map_err(db)?;
+
+    if let Some(variant_id) = change.variant_id {
+        conn.execute(
+            "UPDATE variant_stock SET quantity = quantity + ?1, last_updated = ?2 WHERE variant_id = ?3",
+            params![change.quantity, now, variant_id],
+        )
+        .map_err(db)?;
+    }
+    if let Some(lot_id) = change.lot_id {
+        conn.execute(
+            "UPDATE inventory_lots SET quantity = quantity + ?1 WHERE id = ?2 AND tenant_id = ?3",
+            params![change.quantity, lot_id, change.tenant_id],
+        )
+        .map_err(db)?;
+    }
+    Ok(())
+}
+
+/// Apply a stock change and record it in `inventory_movements`
+pub fn record(
+    conn: &Connection,
+    change: &StockChange,
+    info: &MovementInfo,
+    now: &str,
+) -> Result<String, ServiceError> {
+    apply(conn, change, now)?;
+
+    let id = Uuid::new_v4().to_string();
+    let movement_type = if change.quantity >= 0.0 {
+        "ENTRADA"
+    } else {
+        "SALIDA"
+    };
+    conn.execute(
+        "INSERT INTO inventory_movements
+            (id, tenant_id, product_id, movement_type, quantity, reference_type, reference_id,
+             notes, created_by, created_at, warehouse_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            id,
+            change.tenant_id,
+            change.product_id,
+            movement_type,
+            change.quantity.abs(),
+            info.reference_type,
+            info.reference_id,
+            info.notes,
+            info.user_id,
+            now,
+            change.warehouse_id
+        ],
+    )
+    .map_err(db)?;
+    Ok(id)
+}
+
+fn map_warehouse(row: &rusqlite::Row) -> rusqlite::Result<Warehouse> {
+    Ok(Warehouse {
+        id: row.get(0)?,
+        tenant_id: row.get(1)?,
+        code: row.get(2)?,
+        name: row.get(3)?,
+        is_default: row.get(4)?,
+        is_active: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
+const WAREHOUSE_COLUMNS: &str =
+    "id, tenant_id, code, name, is_default, is_active, created_at, updated_at";
+
+pub fn list_warehouses(conn: &Connection, tenant_id: &str) -> Result<Vec<Warehouse>, ServiceError> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM warehouses WHERE tenant_id = ?1 ORDER BY is_default DESC, name",
+            WAREHOUSE_COLUMNS
+        ))
+        .map_err(db)?;
+    let warehouses = stmt
+        .query_map([tenant_id], map_warehouse)
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(warehouses)
+}
+
+pub fn get_warehouse(
+    conn: &Connection,
+    tenant_id: &str,
+    id: &str,
+) -> Result<Warehouse, ServiceError> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM warehouses WHERE id = ?1 AND tenant_id = ?2",
+            WAREHOUSE_COLUMNS
+        ),
+        params![id, tenant_id],
+        map_warehouse,
+    )
+    .optional()
+    .map_err(db)?
+    .ok_or_else(|| ServiceError::NotFound("Almacén no encontrado".to_string()))
+}
+
+fn clear_default(conn: &Connection, tenant_id: &str, now: &str) -> Result<(), ServiceError> {
+    conn.execute(
+        "UPDATE warehouses SET is_default = 0, updated_at = ?1 WHERE tenant_id = ?2 AND is_default = 1",
+        params![now, tenant_id],
+    )
+    .map_err(db)?;
+    Ok(())
+}
+
+pub fn create_warehouse(
+    conn: &Connection,
+    tenant_id: &str,
+    data: CreateWarehouseDto,
+) -> Result<Warehouse, ServiceError> {
+    let code = data.code.trim().to_uppercase();
+    let name = data.name.trim();
+    if code.is_empty() || name.is_empty() {
+        return Err(ServiceError::Validation(
+            "Código y nombre del almacén son requeridos".to_string(),
+        ));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+    let has_default: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM warehouses WHERE tenant_id = ?1 AND is_default = 1)",
+            [tenant_id],
+            |row| row.get(0),
+        )
+        .map_err(db)?;
+    let is_default = data.is_default.unwrap_or(false) || !has_default;
+    if is_default {
+        clear_default(conn, tenant_id, &now)?;
+    }
+
+    conn.execute(
+        "INSERT INTO warehouses (id, tenant_id, code, name, is_default, is_active, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, 1, ?6, ?6)",
+        params![id, tenant_id, code, name, is_default, now],
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::SqliteFailure(f, _)
+            if f.code == rusqlite::ErrorCode::ConstraintViolation =>
+        {
+            ServiceError::Validation(format!("Ya existe un almacén con código {}", code))
+        }
+        e => db(e),
+    })?;
+
+    get_warehouse(conn, tenant_id, &id)
+}
+
+pub fn update_warehouse(
+    conn: &Connection,
+    tenant_id: &str,
+    id: &str,
+    data: UpdateWarehouseDto,
+) -> Result<Warehouse, ServiceError> {
+    let current = get_warehouse(conn, tenant_id, id)?;
+    let now = Utc::now().to_rfc3339();
+
+    let is_active = data.is_active.unwrap_or(current.is_active);
+    let is_default = data.is_default.unwrap_or(current.is_default);
+    if is_default && !is_active {
+        return Err(ServiceError::Validation(
+            "El almacén predeterminado no puede desactivarse".to_string(),
+        ));
+    }
+    if !is_active {
+        let stock: f64 = conn
+            .query_row(
+                "SELECT COALESCE(SUM(ABS(quantity)), 0) FROM warehouse_stock WHERE warehouse_id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .map_err(db)?;
+        if stock > 0.0 {
+            return Err(ServiceError::Validation(
+                "El almacén tiene existencias; trasládelas antes de desactivarlo".to_string(),
+            ));
+        }
+    }
+    if is_default && !current.is_default {
+        clear_default(conn, tenant_id, &now)?;
+    }
+
+    conn.execute(
+        "UPDATE warehouses SET name = ?1, is_default = ?2, is_active = ?3, updated_at = ?4
+         WHERE id = ?5 AND tenant_id = ?6",
+        params![
+            data.name
+                .as_deref()
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .unwrap_or(&current.name),
+            is_default,
+            is_active,
+            now,
+            id,
+            tenant_id
+        ],
+    )
+    .map_err(db)?;
+
+    get_warehouse(conn, tenant_id, id)
+}
+
+/// Stock rows of the branch, optionally for one warehouse or product
+pub fn list_stock(
+    conn: &Connection,
+    tenant_id: &str,
+    filters: &WarehouseStockFilters,
+) -> Result<Vec<WarehouseStock>, ServiceError> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT ws.id, ws.warehouse_id, w.name, ws.product_id, p.name, p.sku,
+                    ws.variant_id, v.name, ws.lot_id, l.lot_number, ws.quantity, ws.min_stock,
+                    ws.bin_location
+             FROM warehouse_stock ws
+             JOIN warehouses w ON w.id = ws.warehouse_id
+             JOIN products p ON p.id = ws.product_id
+             LEFT JOIN product_variants v ON v.id = ws.variant_id
+             LEFT JOIN inventory_lots l ON l.id = ws.lot_id
+             WHERE ws.tenant_id = ?1
+               AND (?2 IS NULL OR ws.warehouse_id = ?2)
+               AND (?3 IS NULL OR ws.product_id = ?3)
+               AND (?4 OR ws.quantity != 0)
+             ORDER BY w.name, p.name, v.name, l.expiration_date",
+        )
+        .map_err(db)?;
+
+    let rows = stmt
+        .query_map(
+            params![
+                tenant_id,
+                filters.warehouse_id,
+                filters.product_id,
+                filters.include_empty.unwrap_or(false)
+            ],
+            |row| {
+                Ok(WarehouseStock {
+                    id: row.get(0)?,
+                    warehouse_id: row.get(1)?,
+                    warehouse_name: row.get(2)?,
+                    product_id: row.get(3)?,
+                    product_name: row.get(4)?,
+                    sku: row.get(5)?,
+                    variant_id: row.get(6)?,
+                    variant_name: row.get(7)?,
+                    lot_id: row.get(8)?,
+                    lot_number: row.get(9)?,
+                    quantity: row.get(10)?,
+                    min_stock: row.get(11)?,
+                    bin_location: row.get(12)?,
+                })
+            },
+        )
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(rows)
+}
+
+/// Set the bin and minimum of a product in a warehouse
+pub fn set_stock_location(
+    conn: &Connection,
+    tenant_id: &str,
+    data: SetStockLocationDto,
+) -> Result<(), ServiceError> {
+    let warehouse_id = resolve_warehouse(conn, tenant_id, Some(&data.warehouse_id))?;
+    let now = Utc::now().to_rfc3339();
+
+    // A zero change makes sure the row exists
+    apply(
+        conn,
+        &StockChange {
+            tenant_id,
+            warehouse_id: &warehouse_id,
+            product_id: &data.product_id,
+            variant_id: data.variant_id.as_deref(),
+            lot_id: data.lot_id.as_deref(),
+            quantity: 0.0,
+        },
+        &now,
+    )?;
+    conn.execute(
+        "UPDATE warehouse_stock SET bin_location = ?1, min_stock = ?2, updated_at = ?3
+         WHERE warehouse_id = ?4 AND product_id = ?5 AND variant_id IS ?6 AND lot_id IS ?7",
+        params![
+            data.bin_location
+                .as_deref()
+                .map(str::trim)
+                .filter(|b| !b.is_empty()),
+            data.min_stock,
+            now,
+            warehouse_id,
+            data.product_id,
+            data.variant_id,
+            data.lot_id
+        ],
+    )
+    .map_err(db)?;
+    Ok(())
+}
+
+/// Move stock between two warehouses of the branch in one transaction
+pub fn transfer_between_warehouses(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    data: CreateWarehouseTransferDto,
+) -> Result<WarehouseTransfer, ServiceError> {
+    if data.items.is_empty() {
+        return Err(ServiceError::Validation(
+            "El traslado no tiene productos".to_string(),
+        ));
+    }
+    let from = resolve_warehouse(conn, tenant_id, Some(&data.from_warehouse_id))?;
+    let to = resolve_warehouse(conn, tenant_id, Some(&data.to_warehouse_id))?;
+    if from == to {
+        return Err(ServiceError::Validation(
+            "El almacén destino debe ser otro".to_string(),
+        ));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+    let info = MovementInfo {
+        reference_type: Some("warehouse_transfer"),
+        reference_id: Some(&id),
+        notes: data.notes.as_deref(),
+        user_id: Some(user_id),
+    };
+
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    tx.execute(
+        "INSERT INTO warehouse_transfers (id, tenant_id, from_warehouse_id, to_warehouse_id, notes, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![id, tenant_id, from, to, data.notes, user_id, now],
+    )
+    .map_err(db)?;
+
+    for item in &data.items {
+        if item.quantity <= 0.0 {
+            return Err(ServiceError::Validation(
+                "La cantidad a trasladar debe ser mayor a cero".to_string(),
+            ));
+        }
+        let available: f64 = tx
+            .query_row(
+                "SELECT COALESCE(SUM(quantity), 0) FROM warehouse_stock
+                 WHERE warehouse_id = ?1 AND product_id = ?2 AND variant_id IS ?3 AND lot_id IS ?4",
+                params![from, item.product_id, item.variant_id, item.lot_id],
+                |row| row.get(0),
+            )
+            .map_err(db)?;
+        if available < item.quantity {
+            return Err(ServiceError::Validation(format!(
+                "Stock insuficiente en el almacén de origen ({} disponible)",
+                available
+            )));
+        }
+
+        tx.execute(
+            "INSERT INTO warehouse_transfer_items (id, transfer_id, product_id, variant_id, lot_id, quantity)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                Uuid::new_v4().to_string(),
+                id,
+                item.product_id,
+                item.variant_id,
+                item.lot_id,
+                item.quantity
+            ],
+        )
+        .map_err(db)?;
+
+        for (warehouse_id, quantity) in [(&from, -item.quantity), (&to, item.quantity)] {
+            record(
+                &tx,
+                &StockChange {
+                    tenant_id,
+                    warehouse_id,
+                    product_id: &item.product_id,
+                    variant_id: item.variant_id.as_deref(),
+                    lot_id: item.lot_id.as_deref(),
+                    quantity,
+                },
+                &info,
+                &now,
+            )?;
+        }
+    }
+    tx.commit().map_err(db)?;
+
+    Ok(WarehouseTransfer {
+        id,
+        tenant_id: tenant_id.to_string(),
+        from_warehouse_id: from,
+        to_warehouse_id: to,
+        notes: data.notes,
+        created_by: user_id.to_string(),
+        created_at: now,
+        items: data.items,
+    })
+}
+
+/// Products at or below their minimum, evaluated per warehouse. The minimum
+/// set for the warehouse wins over the product's.
+pub fn low_stock(
+    conn: &Connection,
+    tenant_id: &str,
+    warehouse_id: Option<&str>,
+) -> Result<Vec<LowStockItem>, ServiceError> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT w.id, w.name, p.id, p.name, p.sku,
+                    COALESCE(SUM(ws.quantity), 0) AS quantity,
+                    COALESCE(MAX(ws.min_stock), p.min_stock, 0) AS minimum
+             FROM warehouses w
+             JOIN products p ON p.tenant_id = w.tenant_id AND p.is_active = 1
+             LEFT JOIN warehouse_stock ws ON ws.warehouse_id = w.id AND ws.product_id = p.id
+             WHERE w.tenant_id = ?1 AND w.is_active = 1 AND (?2 IS NULL OR w.id = ?2)
+             GROUP BY w.id, p.id
+             HAVING minimum > 0 AND quantity <= minimum
+                -- Product minimums only apply where the product is stocked
+                AND (COUNT(ws.id) > 0 OR w.is_default = 1)
+             ORDER BY w.name, p.name",
+        )
+        .map_err(db)?;
+
+    let items = stmt
+        .query_map(params![tenant_id, warehouse_id], |row| {
+            Ok(LowStockItem {
+                warehouse_id: row.get(0)?,
+                warehouse_name: row.get(1)?,
+                product_id: row.get(2)?,
+                product_name: row.get(3)?,
+                sku: row.get(4)?,
+                quantity: row.get(5)?,
+                min_stock: row.get(6)?,
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::warehouse::WarehouseTransferItem;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, stock_quantity, min_stock, is_active)
+                 VALUES ('p1', 't1', 'SKU-1', 'Arroz', 2.0, 0, 5, 1);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn stock_in(conn: &Connection, warehouse_id: &str) -> f64 {
+        conn.query_row(
+            "SELECT COALESCE(SUM(quantity), 0) FROM warehouse_stock WHERE warehouse_id = ?1",
+            [warehouse_id],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_transfer_keeps_branch_total_and_low_stock_is_per_warehouse() {
+        let conn = setup();
+        let main = default_warehouse(&conn, "t1").unwrap();
+        let back = create_warehouse(
+            &conn,
+            "t1",
+            CreateWarehouseDto {
+                code: "dep".to_string(),
+                name: "Depósito".to_string(),
+                is_default: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(back.code, "DEP");
+        assert!(!back.is_default);
+
+        let now = Utc::now().to_rfc3339();
+        apply(
+            &conn,
+            &StockChange {
+                tenant_id: "t1",
+                warehouse_id: &main,
+                product_id: "p1",
+                variant_id: None,
+                lot_id: None,
+                quantity: 10.0,
+            },
+            &now,
+        )
+        .unwrap();
+
+        transfer_between_warehouses(
+            &conn,
+            "t1",
+            "u1",
+            CreateWarehouseTransferDto {
+                from_warehouse_id: main.clone(),
+                to_warehouse_id: back.id.clone(),
+                items: vec![WarehouseTransferItem {
+                    product_id: "p1".to_string(),
+                    variant_id: None,
+                    lot_id: None,
+                    quantity: 7.0,
+                }],
+                notes: None,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(stock_in(&conn, &main), 3.0);
+        assert_eq!(stock_in(&conn, &back.id), 7.0);
+        let total: f64 = conn
+            .query_row(
+                "SELECT stock_quantity FROM products WHERE id = 'p1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(total, 10.0);
+
+        // The branch total is above the minimum, the main warehouse is not
+        let low = low_stock(&conn, "t1", None).unwrap();
+        assert_eq!(low.len(), 1);
+        assert_eq!(low[0].warehouse_id, main);
+
+        // A warehouse minimum overrides the product's
+        set_stock_location(
+            &conn,
+            "t1",
+            SetStockLocationDto {
+                warehouse_id: back.id.clone(),
+                product_id: "p1".to_string(),
+                variant_id: None,
+                lot_id: None,
+                bin_location: Some("A-01".to_string()),
+                min_stock: Some(8.0),
+            },
+        )
+        .unwrap();
+        assert_eq!(low_stock(&conn, "t1", Some(&back.id)).unwrap().len(), 1);
+
+        // Over-transfers are refused
+        assert!(transfer_between_warehouses(
+            &conn,
+            "t1",
+            "u1",
+            CreateWarehouseTransferDto {
+                from_warehouse_id: main.clone(),
+                to_warehouse_id: back.id.clone(),
+                items: vec![WarehouseTransferItem {
+                    product_id: "p1".to_string(),
+                    variant_id: None,
+                    lot_id: None,
+                    quantity: 5.0,
+                }],
+                notes: None,
+            },
+        )
+        .is_err());
+        assert_eq!(stock_in(&conn, &main), 3.0);
+    }
+}
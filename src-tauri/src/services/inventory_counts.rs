@@ -0,0 +1,673 @@
+//! Physical Inventory Counts
+//!
+//! A count freezes the expected quantity of every stock row of a warehouse
+//! (or of one category tree, for cycle counts). Counted quantities are
+//! captured per line or by barcode scan; approval posts each variance
+//! (counted - expected) to the movement ledger in one transaction.
+
+use crate::models::inventory_count::{
+    CountEntryDto, InventoryCount, InventoryCountDetail, InventoryCountLine, StartCountDto,
+};
+use crate::services::inventory;
+use crate::state::ServiceError;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+
+pub const COUNT_REFERENCE: &str = "inventory_count";
+
+fn db(e: rusqlite::Error) -> ServiceError {
+    ServiceError::Database(e.to_string())
+}
+
+/// Categories counted: the given one and its descendants
+const CATEGORY_TREE: &str = "WITH RECURSIVE tree(id) AS (
+        SELECT id FROM categories WHERE id = ?3
+        UNION ALL
+        SELECT c.id FROM categories c JOIN tree t ON c.parent_id = t.id
+    )";
+
+/// Products a count covers
+const IN_SCOPE: &str = "p.is_active = 1
+    AND COALESCE(pt.affects_stock, 1) = 1
+    AND (?3 IS NULL OR p.category_id IN (SELECT id FROM tree))";
+
+const COUNT_COLUMNS: &str = "c.id, c.tenant_id, c.warehouse_id, w.name, c.category_id, c.status,
+    c.notes, c.created_by, c.created_at, c.approved_by, c.approved_at,
+    (SELECT COUNT(*) FROM inventory_count_lines l WHERE l.count_id = c.id),
+    (SELECT COUNT(*) FROM inventory_count_lines l
+        WHERE l.count_id = c.id AND l.counted_quantity IS NOT NULL),
+    (SELECT COALESCE(SUM(l.counted_quantity - l.expected_quantity), 0)
+        FROM inventory_count_lines l WHERE l.count_id = c.id AND l.counted_quantity IS NOT NULL),
+    (SELECT COALESCE(SUM((l.counted_quantity - l.expected_quantity) * l.unit_cost), 0)
+        FROM inventory_count_lines l WHERE l.count_id = c.id AND l.counted_quantity IS NOT NULL)";
+
+fn map_count(row: &rusqlite::Row) -> rusqlite::Result<InventoryCount> {
+    Ok(InventoryCount {
+        id: row.get(0)?,
+        tenant_id: row.get(1)?,
+        warehouse_id: row.get(2)?,
+        warehouse_name: row.get(3)?,
+        category_id: row.get(4)?,
+        status: row.get(5)?,
+        notes: row.get(6)?,
+        created_by: row.get(7)?,
+        created_at: row.get(8)?,
+        approved_by: row.get(9)?,
+        approved_at: row.get(10)?,
+        line_count: row.get(11)?,
+        counted_lines: row.get(12)?,
+        variance_quantity: row.get(13)?,
+        variance_cost: row.get(14)?,
+    })
+}
+
+/// Open a count and freeze the expected quantities
+pub fn start_count(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    data: StartCountDto,
+) -> Result<InventoryCount, ServiceError> {
+    let warehouse_id = inventory::resolve_warehouse(conn, tenant_id, data.warehouse_id.as_deref())?;
+    let open: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM inventory_counts WHERE warehouse_id = ?1 AND status = 'counting')",
+            [&warehouse_id],
+            |row| row.get(0),
+        )
+        .map_err(db)?;
+    if open {
+        return Err(ServiceError::Validation(
+            "Ya hay un conteo abierto en este almacén".to_string(),
+        ));
+    }
+    if let Some(category_id) = &data.category_id {
+        let exists: bool = conn
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM categories WHERE id = ?1 AND tenant_id = ?2)",
+                params![category_id, tenant_id],
+                |row| row.get(0),
+            )
+            .map_err(db)?;
+        if !exists {
+            return Err(ServiceError::NotFound(
+                "Categoría no encontrada".to_string(),
+            ));
+        }
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    tx.execute(
+        "INSERT INTO inventory_counts (id, tenant_id, warehouse_id, category_id, status, notes, created_by, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, 'counting', ?5, ?6, ?7, ?7)",
+        params![id, tenant_id, warehouse_id, data.category_id, data.notes, user_id, now],
+    )
+    .map_err(db)?;
+
+    // Every stock row of the warehouse in scope...
+    tx.execute(
+        &format!(
+            "{CATEGORY_TREE}
+             INSERT INTO inventory_count_lines
+                (id, count_id, product_id, variant_id, lot_id, expected_quantity, unit_cost)
+             SELECT lower(hex(randomblob(16))), ?1, ws.product_id, ws.variant_id, ws.lot_id,
+                    ws.quantity, COALESCE(l.cost_price, NULLIF(v.cost_price, 0), p.cost_price, 0)
+             FROM warehouse_stock ws
+             JOIN products p ON p.id = ws.product_id
+             LEFT JOIN product_types pt ON pt.id = p.product_type_id
+             LEFT JOIN product_variants v ON v.id = ws.variant_id
+             LEFT JOIN inventory_lots l ON l.id = ws.lot_id
+             WHERE ws.warehouse_id = ?2 AND {IN_SCOPE}"
+        ),
+        params![id, warehouse_id, data.category_id],
+    )
+    .map_err(db)?;
+
+    // ...plus products never stocked there, in case some is found
+    tx.execute(
+        &format!(
+            "{CATEGORY_TREE}
+             INSERT INTO inventory_count_lines
+                (id, count_id, product_id, expected_quantity, unit_cost)
+             SELECT lower(hex(randomblob(16))), ?1, p.id, 0, COALESCE(p.cost_price, 0)
+             FROM products p
+             LEFT JOIN product_types pt ON pt.id = p.product_type_id
+             WHERE p.tenant_id = ?4 AND {IN_SCOPE}
+               AND NOT EXISTS (SELECT 1 FROM warehouse_stock ws
+                               WHERE ws.warehouse_id = ?2 AND ws.product_id = p.id)"
+        ),
+        params![id, warehouse_id, data.category_id, tenant_id],
+    )
+    .map_err(db)?;
+    tx.commit().map_err(db)?;
+
+    get_count_summary(conn, tenant_id, &id)
+}
+
+pub fn get_count_summary(
+    conn: &Connection,
+    tenant_id: &str,
+    id: &str,
+) -> Result<InventoryCount, ServiceError> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM inventory_counts c JOIN warehouses w ON w.id = c.warehouse_id
+             WHERE c.id = ?1 AND c.tenant_id = ?2",
+            COUNT_COLUMNS
+        ),
+        params![id, tenant_id],
+        map_count,
+    )
+    .optional()
+    .map_err(db)?
+    .ok_or_else(|| ServiceError::NotFound("Conteo no encontrado".to_string()))
+}
+
+pub fn list_counts(
+    conn: &Connection,
+    tenant_id: &str,
+    status: Option<&str>,
+) -> Result<Vec<InventoryCount>, ServiceError> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM inventory_counts c JOIN warehouses w ON w.id = c.warehouse_id
+             WHERE c.tenant_id = ?1 AND (?2 IS NULL OR c.status = ?2)
+             ORDER BY c.created_at DESC",
+            COUNT_COLUMNS
+        ))
+        .map_err(db)?;
+    let counts = stmt
+        .query_map(params![tenant_id, status], map_count)
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(counts)
+}
+
+const LINE_SELECT: &str = "SELECT cl.id, cl.product_id, p.name, p.sku, cl.variant_id, v.name,
+        cl.lot_id, lt.lot_number, cl.expected_quantity, cl.counted_quantity, cl.unit_cost,
+        cl.counted_at
+    FROM inventory_count_lines cl
+    JOIN products p ON p.id = cl.product_id
+    LEFT JOIN product_variants v ON v.id = cl.variant_id
+    LEFT JOIN inventory_lots lt ON lt.id = cl.lot_id";
+
+fn map_line(row: &rusqlite::Row) -> rusqlite::Result<InventoryCountLine> {
+    let expected: f64 = row.get(8)?;
+    let counted: Option<f64> = row.get(9)?;
+    let unit_cost: f64 = row.get(10)?;
+    let variance = counted.map(|c| c - expected);
+    Ok(InventoryCountLine {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        product_name: row.get(2)?,
+        sku: row.get(3)?,
+        variant_id: row.get(4)?,
+        variant_name: row.get(5)?,
+        lot_id: row.get(6)?,
+        lot_number: row.get(7)?,
+        expected_quantity: expected,
+        counted_quantity: counted,
+        variance,
+        unit_cost,
+        variance_cost: variance.map(|v| v * unit_cost),
+        counted_at: row.get(11)?,
+    })
+}
+
+/// A count with its lines; only lines with a variance if `variances_only`
+pub fn get_count(
+    conn: &Connection,
+    tenant_id: &str,
+    id: &str,
+    variances_only: bool,
+) -> Result<InventoryCountDetail, ServiceError> {
+    let count = get_count_summary(conn, tenant_id, id)?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} WHERE cl.count_id = ?1
+               AND (?2 = 0 OR (cl.counted_quantity IS NOT NULL
+                               AND cl.counted_quantity != cl.expected_quantity))
+             ORDER BY p.name, v.name, lt.lot_number",
+            LINE_SELECT
+        ))
+        .map_err(db)?;
+    let lines = stmt
+        .query_map(params![id, variances_only], map_line)
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(InventoryCountDetail { count, lines })
+}
+
+fn require_counting(
+    conn: &Connection,
+    tenant_id: &str,
+    id: &str,
+) -> Result<InventoryCount, ServiceError> {
+    let count = get_count_summary(conn, tenant_id, id)?;
+    if count.status != "counting" {
+        return Err(ServiceError::Validation(
+            "El conteo ya fue cerrado".to_string(),
+        ));
+    }
+    Ok(count)
+}
+
+/// Product and variant a barcode (or SKU) belongs to
+fn resolve_barcode(
+    conn: &Connection,
+    tenant_id: &str,
+    code: &str,
+) -> Result<(String, Option<String>), ServiceError> {
+    conn.query_row(
+        "SELECT product_id, variant_id FROM (
+             SELECT product_id, id AS variant_id, 1 AS rank FROM product_variants
+                 WHERE tenant_id = ?1 AND barcode = ?2 AND is_active = 1
+             UNION ALL
+             SELECT id, NULL, 2 FROM products WHERE tenant_id = ?1 AND barcode = ?2 AND is_active = 1
+             UNION ALL
+             SELECT product_id, id, 3 FROM product_variants
+                 WHERE tenant_id = ?1 AND sku = ?2 AND is_active = 1
+             UNION ALL
+             SELECT id, NULL, 4 FROM products WHERE tenant_id = ?1 AND sku = ?2 AND is_active = 1
+         ) ORDER BY rank LIMIT 1",
+        params![tenant_id, code.trim()],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+    .map_err(db)?
+    .ok_or_else(|| ServiceError::NotFound(format!("Código {} no encontrado", code)))
+}
+
+/// Line of the count for a product/variant/lot, added if the product is in
+/// scope but had no line (e.g. a variant never stocked in the warehouse)
+fn find_or_add_line(
+    conn: &Connection,
+    count: &InventoryCount,
+    product_id: &str,
+    variant_id: Option<&str>,
+    lot_id: Option<&str>,
+) -> Result<String, ServiceError> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, lot_id FROM inventory_count_lines
+             WHERE count_id = ?1 AND product_id = ?2 AND variant_id IS ?3",
+        )
+        .map_err(db)?;
+    let lines: Vec<(String, Option<String>)> = stmt
+        .query_map(params![count.id, product_id, variant_id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    if let Some((id, _)) = lines.iter().find(|(_, lot)| lot.as_deref() == lot_id) {
+        return Ok(id.clone());
+    }
+    if lot_id.is_none() {
+        match lines.as_slice() {
+            [(id, _)] => return Ok(id.clone()),
+            [_, ..] => {
+                return Err(ServiceError::Validation(
+                    "El producto tiene varios lotes; indique el lote contado".to_string(),
+                ))
+            }
+            [] => {}
+        }
+    }
+
+    let in_scope: bool = conn
+        .query_row(
+            &format!(
+                "{CATEGORY_TREE}
+                 SELECT EXISTS (SELECT 1 FROM products p
+                     LEFT JOIN product_types pt ON pt.id = p.product_type_id
+                     WHERE p.id = ?1 AND p.tenant_id = ?2 AND {IN_SCOPE})"
+            ),
+            params![product_id, count.tenant_id, count.category_id],
+            |row| row.get(0),
+        )
+        .map_err(db)?;
+    if !in_scope {
+        return Err(ServiceError::Validation(
+            "El producto no forma parte de este conteo".to_string(),
+        ));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO inventory_count_lines
+            (id, count_id, product_id, variant_id, lot_id, expected_quantity, unit_cost)
+         SELECT ?1, ?2, p.id, ?4, ?5,
+                COALESCE((SELECT SUM(quantity) FROM warehouse_stock
+                          WHERE warehouse_id = ?6 AND product_id = p.id
+                            AND variant_id IS ?4 AND lot_id IS ?5), 0),
+                COALESCE((SELECT cost_price FROM inventory_lots WHERE id = ?5),
+                         (SELECT NULLIF(cost_price, 0) FROM product_variants WHERE id = ?4),
+                         p.cost_price, 0)
+         FROM products p WHERE p.id = ?3",
+        params![
+            id,
+            count.id,
+            product_id,
+            variant_id,
+            lot_id,
+            count.warehouse_id
+        ],
+    )
+    .map_err(db)?;
+    Ok(id)
+}
+
+/// Capture a counted quantity
+pub fn record_count(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    count_id: &str,
+    entry: CountEntryDto,
+) -> Result<InventoryCountLine, ServiceError> {
+    let count = require_counting(conn, tenant_id, count_id)?;
+
+    let line_id = if let Some(line_id) = entry.line_id {
+        line_id
+    } else {
+        let (product_id, variant_id) = match (&entry.barcode, &entry.product_id) {
+            (Some(code), _) if !code.trim().is_empty() => resolve_barcode(conn, tenant_id, code)?,
+            (_, Some(product_id)) => (product_id.clone(), entry.variant_id.clone()),
+            _ => {
+                return Err(ServiceError::Validation(
+                    "Indique la línea, el producto o el código de barras".to_string(),
+                ))
+            }
+        };
+        find_or_add_line(
+            conn,
+            &count,
+            &product_id,
+            variant_id.as_deref(),
+            entry.lot_id.as_deref(),
+        )?
+    };
+
+    let current: Option<f64> = conn
+        .query_row(
+            "SELECT counted_quantity FROM inventory_count_lines WHERE id = ?1 AND count_id = ?2",
+            params![line_id, count_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db)?
+        .ok_or_else(|| ServiceError::NotFound("Línea de conteo no encontrada".to_string()))?;
+    let counted = if entry.add.unwrap_or(false) {
+        current.unwrap_or(0.0) + entry.quantity
+    } else {
+        entry.quantity
+    };
+    if counted < 0.0 {
+        return Err(ServiceError::Validation(
+            "La cantidad contada no puede ser negativa".to_string(),
+        ));
+    }
+
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "UPDATE inventory_count_lines SET counted_quantity = ?1, counted_by = ?2, counted_at = ?3
+         WHERE id = ?4",
+        params![counted, user_id, now, line_id],
+    )
+    .map_err(db)?;
+    conn.execute(
+        "UPDATE inventory_counts SET updated_at = ?1 WHERE id = ?2",
+        params![now, count_id],
+    )
+    .map_err(db)?;
+
+    conn.query_row(
+        &format!("{} WHERE cl.id = ?1", LINE_SELECT),
+        [&line_id],
+        map_line,
+    )
+    .map_err(db)
+}
+
+/// Post every variance in one transaction and close the count. Lines not
+/// counted are left as they are unless `zero_uncounted`.
+pub fn approve_count(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    count_id: &str,
+    zero_uncounted: bool,
+) -> Result<(InventoryCount, usize), ServiceError> {
+    let count = require_counting(conn, tenant_id, count_id)?;
+    let now = Utc::now().to_rfc3339();
+
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    if zero_uncounted {
+        tx.execute(
+            "UPDATE inventory_count_lines SET counted_quantity = 0, counted_by = ?1, counted_at = ?2
+             WHERE count_id = ?3 AND counted_quantity IS NULL",
+            params![user_id, now, count_id],
+        )
+        .map_err(db)?;
+    }
+
+    let mut stmt = tx
+        .prepare(
+            "SELECT product_id, variant_id, lot_id, counted_quantity - expected_quantity
+             FROM inventory_count_lines
+             WHERE count_id = ?1 AND counted_quantity IS NOT NULL
+               AND counted_quantity != expected_quantity",
+        )
+        .map_err(db)?;
+    let variances: Vec<(String, Option<String>, Option<String>, f64)> = stmt
+        .query_map([count_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    drop(stmt);
+
+    let info = inventory::MovementInfo {
+        reference_type: Some(COUNT_REFERENCE),
+        reference_id: Some(count_id),
+        notes: Some("Conteo físico"),
+        user_id: Some(user_id),
+    };
+    for (product_id, variant_id, lot_id, variance) in &variances {
+        inventory::record(
+            &tx,
+            &inventory::StockChange {
+                tenant_id,
+                warehouse_id: &count.warehouse_id,
+                product_id,
+                variant_id: variant_id.as_deref(),
+                lot_id: lot_id.as_deref(),
+                quantity: *variance,
+            },
+            &info,
+            &now,
+        )?;
+    }
+
+    tx.execute(
+        "UPDATE inventory_counts SET status = 'approved', approved_by = ?1, approved_at = ?2, updated_at = ?2
+         WHERE id = ?3",
+        params![user_id, now, count_id],
+    )
+    .map_err(db)?;
+    tx.commit().map_err(db)?;
+
+    Ok((
+        get_count_summary(conn, tenant_id, count_id)?,
+        variances.len(),
+    ))
+}
+
+/// Discard a count without touching stock
+pub fn cancel_count(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    count_id: &str,
+) -> Result<InventoryCount, ServiceError> {
+    require_counting(conn, tenant_id, count_id)?;
+    let now = Utc::now().to_rfc3339();
+    conn.execute(
+        "UPDATE inventory_counts SET status = 'cancelled', cancelled_by = ?1, cancelled_at = ?2, updated_at = ?2
+         WHERE id = ?3",
+        params![user_id, now, count_id],
+    )
+    .map_err(db)?;
+    get_count_summary(conn, tenant_id, count_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (Connection, String) {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO categories (id, tenant_id, name) VALUES ('food', 't1', 'Víveres');
+             INSERT INTO categories (id, tenant_id, parent_id, name) VALUES ('rice', 't1', 'food', 'Arroz');
+             INSERT INTO categories (id, tenant_id, name) VALUES ('tools', 't1', 'Ferretería');
+             INSERT INTO products (id, tenant_id, sku, barcode, name, unit_price, cost_price, stock_quantity, category_id, is_active)
+                 VALUES ('p1', 't1', 'ARR-1', '7591234000011', 'Arroz', 2.0, 1.5, 0, 'rice', 1);
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, cost_price, stock_quantity, category_id, is_active)
+                 VALUES ('p2', 't1', 'MAR-1', 'Martillo', 10.0, 6.0, 0, 'tools', 1);",
+        )
+        .unwrap();
+        let warehouse = inventory::default_warehouse(&conn, "t1").unwrap();
+        for (product, quantity) in [("p1", 10.0), ("p2", 4.0)] {
+            inventory::apply(
+                &conn,
+                &inventory::StockChange {
+                    tenant_id: "t1",
+                    warehouse_id: &warehouse,
+                    product_id: product,
+                    variant_id: None,
+                    lot_id: None,
+                    quantity,
+                },
+                &Utc::now().to_rfc3339(),
+            )
+            .unwrap();
+        }
+        (conn, warehouse)
+    }
+
+    fn stock(conn: &Connection, product: &str) -> f64 {
+        conn.query_row(
+            "SELECT stock_quantity FROM products WHERE id = ?1",
+            [product],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_cycle_count_posts_variances_against_snapshot() {
+        let (conn, warehouse) = setup();
+        let count = start_count(
+            &conn,
+            "t1",
+            "u1",
+            StartCountDto {
+                warehouse_id: None,
+                category_id: Some("food".to_string()),
+                notes: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(count.warehouse_id, warehouse);
+        // Only the food tree is counted
+        assert_eq!(count.line_count, 1);
+
+        // Three scans and a correction by line
+        for _ in 0..3 {
+            record_count(
+                &conn,
+                "t1",
+                "u1",
+                &count.id,
+                CountEntryDto {
+                    barcode: Some("7591234000011".to_string()),
+                    quantity: 1.0,
+                    add: Some(true),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+        let line = record_count(
+            &conn,
+            "t1",
+            "u1",
+            &count.id,
+            CountEntryDto {
+                product_id: Some("p1".to_string()),
+                quantity: 5.0,
+                add: Some(true),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(line.counted_quantity, Some(8.0));
+        assert_eq!(line.variance_cost, Some(-3.0));
+
+        // Out of scope products are refused
+        assert!(record_count(
+            &conn,
+            "t1",
+            "u1",
+            &count.id,
+            CountEntryDto {
+                product_id: Some("p2".to_string()),
+                quantity: 1.0,
+                ..Default::default()
+            },
+        )
+        .is_err());
+
+        // A sale while counting is kept: the variance is applied as a delta
+        inventory::apply(
+            &conn,
+            &inventory::StockChange {
+                tenant_id: "t1",
+                warehouse_id: &warehouse,
+                product_id: "p1",
+                variant_id: None,
+                lot_id: None,
+                quantity: -1.0,
+            },
+            &Utc::now().to_rfc3339(),
+        )
+        .unwrap();
+
+        let (approved, posted) = approve_count(&conn, "t1", "u1", &count.id, false).unwrap();
+        assert_eq!(approved.status, "approved");
+        assert_eq!(posted, 1);
+        assert_eq!(stock(&conn, "p1"), 7.0);
+        assert_eq!(stock(&conn, "p2"), 4.0);
+        let movements: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM inventory_movements WHERE reference_type = ?1 AND reference_id = ?2",
+                params![COUNT_REFERENCE, count.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(movements, 1);
+        assert!(approve_count(&conn, "t1", "u1", &count.id, false).is_err());
+    }
+}
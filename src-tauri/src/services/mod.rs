@@ -1,6 +1,24 @@
 //! Business Services Module
 
+pub mod backup;
+pub mod barcodes;
+pub mod branches;
 pub mod cash_register;
+pub mod cash_reports;
+pub mod discounts;
+pub mod escpos;
+pub mod exchange_rates;
+pub mod inventory;
+pub mod inventory_counts;
 pub mod pdf_generator;
+pub mod pricing;
+pub mod reports;
+pub mod repricing;
+pub mod reservations;
 pub mod sync;
+pub mod sync_backend;
+pub mod sync_conflicts;
+pub mod sync_entities;
+pub mod sync_outbox;
+pub mod sync_scheduler;
 pub mod tax_calculator;
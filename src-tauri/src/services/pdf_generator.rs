@@ -1,3 +1,44 @@
 //! PDF Generator Service
 
-// TODO: Implement with printpdf crate
+use printpdf::{BuiltinFont, Mm, PdfDocument};
+
+/// Letter page, in millimetres
+const PAGE_WIDTH: f32 = 215.9;
+const PAGE_HEIGHT: f32 = 279.4;
+const MARGIN: f32 = 15.0;
+const FONT_SIZE: f32 = 9.0;
+const LINE_HEIGHT: f32 = 4.2;
+
+/// Lay out pre-formatted lines in a monospaced font, one per row, over as
+/// many pages as they need
+pub fn text_document(title: &str, lines: &[String]) -> Result<Vec<u8>, String> {
+    let per_page = ((PAGE_HEIGHT - 2.0 * MARGIN) / LINE_HEIGHT) as usize;
+    let (doc, first_page, first_layer) =
+        PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Capa 1");
+    let font = doc
+        .add_builtin_font(BuiltinFont::Courier)
+        .map_err(|e| format!("Error al generar PDF: {}", e))?;
+
+    let mut pages = lines.chunks(per_page.max(1));
+    let mut layer = doc.get_page(first_page).get_layer(first_layer);
+    let mut page_number = 1;
+    loop {
+        for (row, line) in pages.next().unwrap_or_default().iter().enumerate() {
+            let y = PAGE_HEIGHT - MARGIN - LINE_HEIGHT * (row as f32 + 1.0);
+            layer.use_text(line.as_str(), FONT_SIZE, Mm(MARGIN), Mm(y), &font);
+        }
+        if pages.len() == 0 {
+            break;
+        }
+        page_number += 1;
+        let (page, page_layer) = doc.add_page(
+            Mm(PAGE_WIDTH),
+            Mm(PAGE_HEIGHT),
+            format!("Capa {}", page_number),
+        );
+        layer = doc.get_page(page).get_layer(page_layer);
+    }
+
+    doc.save_to_bytes()
+        .map_err(|e| format!("Error al generar PDF: {}", e))
+}
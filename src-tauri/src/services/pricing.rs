@@ -0,0 +1,743 @@
+//! Pricing Service
+//!
+//! Resolves the price of a line from the product or variant, the price list
+//! (specific price or the list's global discount), the promotional discounts
+//! that apply and the product's tax rate.
+//!
+//! Promotions may need a coupon code, hold only at some hours or weekdays,
+//! give units away (buy X get Y) or need a set of products on the same
+//! document (bundles, hence `resolve_all`).
+//!
+//! Stacking: the list discount always applies first. Stackable discounts then
+//! compound in priority order; an exclusive (non-stackable) discount applies
+//! alone, and only if it takes off more than the stacked ones together.
+
+use crate::models::pricing::{AppliedPriceRule, PriceQuery, ResolvedPrice};
+use crate::services::tax_calculator;
+use crate::state::ServiceError;
+use chrono::{Datelike, Local, NaiveDate, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::dec;
+use std::collections::HashMap;
+
+fn db(e: rusqlite::Error) -> ServiceError {
+    ServiceError::Database(e.to_string())
+}
+
+fn decimal(value: f64) -> Decimal {
+    Decimal::from_f64_retain(value).unwrap_or_default()
+}
+
+fn float(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or(0.0)
+}
+
+/// Promotional discount that matches a line
+struct Candidate {
+    id: String,
+    name: String,
+    discount_type: String,
+    value: Decimal,
+    stackable: bool,
+    buy_quantity: Decimal,
+    get_quantity: Decimal,
+    bundle_share: Decimal, // Part of the line inside complete bundles
+    coupon_id: Option<String>,
+}
+
+impl Candidate {
+    /// Amount taken off `remaining` for `quantity` units
+    fn amount(&self, remaining: Decimal, quantity: Decimal) -> Decimal {
+        let value = self.value.max(Decimal::ZERO);
+        let percent = |share: Decimal| remaining * share * value.min(dec!(100)) / dec!(100);
+        let amount = match self.discount_type.as_str() {
+            "fixed" => value * quantity,
+            "buy_x_get_y" => {
+                let group = self.buy_quantity + self.get_quantity;
+                if self.buy_quantity <= Decimal::ZERO || self.get_quantity <= Decimal::ZERO {
+                    return Decimal::ZERO;
+                }
+                let groups = (quantity / group).floor();
+                let rest = quantity - groups * group;
+                let free = groups * self.get_quantity
+                    + (rest - self.buy_quantity)
+                        .max(Decimal::ZERO)
+                        .min(self.get_quantity);
+                percent(free / quantity)
+            }
+            "bundle" => percent(self.bundle_share),
+            // "percentage" and "volume" (a percentage with a minimum quantity)
+            _ => percent(Decimal::ONE),
+        };
+        amount.min(remaining)
+    }
+
+    fn applied(&self, amount: Decimal) -> AppliedPriceRule {
+        AppliedPriceRule {
+            rule_type: "discount".to_string(),
+            rule_id: Some(self.id.clone()),
+            name: self.name.clone(),
+            value: float(self.value),
+            amount: float(amount),
+            coupon_id: self.coupon_id.clone(),
+        }
+    }
+}
+
+/// Whether `time` (HH:MM) on ISO `weekday` falls in a discount's window
+pub(crate) fn in_window(
+    start_time: Option<&str>,
+    end_time: Option<&str>,
+    weekdays: Option<&str>,
+    time: &str,
+    weekday: u32,
+) -> bool {
+    if let Some(days) = weekdays.filter(|d| !d.trim().is_empty()) {
+        let today = weekday.to_string();
+        if !days.split(',').any(|d| d.trim() == today) {
+            return false;
+        }
+    }
+    match (
+        start_time.filter(|t| !t.is_empty()),
+        end_time.filter(|t| !t.is_empty()),
+    ) {
+        (Some(start), Some(end)) if start <= end => time >= start && time < end,
+        // Crosses midnight, e.g. 22:00-02:00
+        (Some(start), Some(end)) => time >= start || time < end,
+        (Some(start), None) => time >= start,
+        (None, Some(end)) => time < end,
+        (None, None) => true,
+    }
+}
+
+/// Coupon codes entered, validated for the client: discount_id -> coupon_id
+fn coupons(
+    conn: &Connection,
+    tenant_id: &str,
+    codes: &[String],
+    client_id: Option<&str>,
+) -> Result<HashMap<String, String>, ServiceError> {
+    let mut unlocked = HashMap::new();
+    for code in codes.iter().map(|c| c.trim().to_uppercase()) {
+        if code.is_empty() {
+            continue;
+        }
+        let coupon: Option<(String, String, Option<String>, bool)> = conn
+            .query_row(
+                "SELECT id, discount_id, client_id,
+                        is_active = 1 AND (max_uses IS NULL OR times_used < max_uses)
+                 FROM discount_coupons WHERE tenant_id = ?1 AND code = ?2",
+                params![tenant_id, code],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .optional()
+            .map_err(db)?;
+        let Some((coupon_id, discount_id, owner, usable)) = coupon else {
+            return Err(ServiceError::Validation(format!(
+                "El cupón {} no es válido",
+                code
+            )));
+        };
+        if !usable {
+            return Err(ServiceError::Validation(format!(
+                "El cupón {} ya fue usado o está inactivo",
+                code
+            )));
+        }
+        if owner.is_some() && owner.as_deref() != client_id {
+            return Err(ServiceError::Validation(format!(
+                "El cupón {} no corresponde a este cliente",
+                code
+            )));
+        }
+        unlocked.insert(discount_id, coupon_id);
+    }
+    Ok(unlocked)
+}
+
+/// Complete bundles across the lines priced together
+#[derive(Default)]
+struct Bundles {
+    counts: HashMap<String, Decimal>, // discount_id -> complete bundles
+    items: HashMap<(String, String), Decimal>, // (discount_id, product_id) -> quantity per bundle
+    totals: HashMap<String, Decimal>, // product_id -> quantity over all lines
+}
+
+impl Bundles {
+    fn load(
+        conn: &Connection,
+        tenant_id: &str,
+        queries: &[PriceQuery],
+    ) -> Result<Self, ServiceError> {
+        let mut bundles = Bundles::default();
+        for query in queries {
+            *bundles.totals.entry(query.product_id.clone()).or_default() += decimal(query.quantity);
+        }
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT b.discount_id, b.product_id, b.quantity FROM discount_bundle_items b
+                 JOIN discounts d ON d.id = b.discount_id
+                 WHERE d.tenant_id = ?1 AND d.is_active = 1 AND d.applies_to = 'bundle'",
+            )
+            .map_err(db)?;
+        let rows = stmt
+            .query_map([tenant_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, f64>(2)?,
+                ))
+            })
+            .map_err(db)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db)?;
+
+        for (discount_id, product_id, quantity) in rows {
+            let per_bundle = decimal(quantity).max(dec!(0.0001));
+            let available = bundles.totals.get(&product_id).copied().unwrap_or_default();
+            let complete = (available / per_bundle).floor();
+            bundles
+                .counts
+                .entry(discount_id.clone())
+                .and_modify(|count| *count = (*count).min(complete))
+                .or_insert(complete);
+            bundles.items.insert((discount_id, product_id), per_bundle);
+        }
+        Ok(bundles)
+    }
+
+    /// Part of a product's quantity that sits in complete bundles
+    fn share(&self, discount_id: &str, product_id: &str) -> Decimal {
+        let count = self.counts.get(discount_id).copied().unwrap_or_default();
+        let per_bundle = self
+            .items
+            .get(&(discount_id.to_string(), product_id.to_string()))
+            .copied()
+            .unwrap_or_default();
+        let total = self.totals.get(product_id).copied().unwrap_or_default();
+        if total <= Decimal::ZERO {
+            return Decimal::ZERO;
+        }
+        (count * per_bundle / total).min(Decimal::ONE)
+    }
+}
+
+/// Everything that selects discounts besides the line itself
+struct Context<'a> {
+    date: &'a str,
+    time: &'a str,
+    weekday: u32,
+    coupons: &'a HashMap<String, String>,
+    bundles: &'a Bundles,
+}
+
+/// Active, unexhausted discounts in their date and time window that match
+/// the line
+fn candidates(
+    conn: &Connection,
+    tenant_id: &str,
+    query: &PriceQuery,
+    category_id: Option<&str>,
+    context: &Context,
+) -> Result<Vec<Candidate>, ServiceError> {
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE ancestors(id) AS (
+                 SELECT ?1
+                 UNION ALL
+                 SELECT c.parent_id FROM categories c JOIN ancestors a ON c.id = a.id
+                 WHERE c.parent_id IS NOT NULL
+             )
+             SELECT id, name, discount_type, value, stackable, COALESCE(buy_quantity, 0),
+                    COALESCE(get_quantity, 0), requires_coupon, start_time, end_time, weekdays
+             FROM discounts
+             WHERE tenant_id = ?2 AND is_active = 1
+               AND (max_uses IS NULL OR times_used < max_uses)
+               AND (start_date IS NULL OR start_date = '' OR substr(start_date, 1, 10) <= ?3)
+               AND (end_date IS NULL OR end_date = '' OR substr(end_date, 1, 10) >= ?3)
+               AND (min_quantity IS NULL OR ?4 >= min_quantity)
+               AND (per_client_limit IS NULL OR ?7 IS NULL
+                    OR (SELECT COUNT(*) FROM discount_redemptions r
+                        WHERE r.discount_id = discounts.id AND r.client_id = ?7
+                          AND r.status = 'redeemed') < per_client_limit)
+               AND (applies_to = 'all'
+                    OR (applies_to = 'product' AND target_id IN (?5, ?6))
+                    OR (applies_to = 'category' AND target_id IN (SELECT id FROM ancestors))
+                    OR (applies_to = 'client' AND target_id = ?7)
+                    OR (applies_to = 'payment_method' AND target_id = ?8)
+                    OR (applies_to = 'bundle' AND id IN (
+                        SELECT discount_id FROM discount_bundle_items WHERE product_id = ?5)))
+             ORDER BY priority DESC, created_at ASC",
+        )
+        .map_err(db)?;
+    let rows = stmt
+        .query_map(
+            params![
+                category_id,
+                tenant_id,
+                context.date,
+                query.quantity,
+                query.product_id,
+                query.variant_id,
+                query.client_id,
+                query.payment_method
+            ],
+            |row| {
+                Ok((
+                    Candidate {
+                        id: row.get(0)?,
+                        name: row.get(1)?,
+                        discount_type: row.get(2)?,
+                        value: decimal(row.get(3)?),
+                        stackable: row.get::<_, i32>(4)? == 1,
+                        buy_quantity: decimal(row.get(5)?),
+                        get_quantity: decimal(row.get(6)?),
+                        bundle_share: Decimal::ZERO,
+                        coupon_id: None,
+                    },
+                    row.get::<_, i32>(7)? == 1,
+                    row.get::<_, Option<String>>(8)?,
+                    row.get::<_, Option<String>>(9)?,
+                    row.get::<_, Option<String>>(10)?,
+                ))
+            },
+        )
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    let mut discounts = Vec::new();
+    for (mut candidate, requires_coupon, start_time, end_time, weekdays) in rows {
+        if !in_window(
+            start_time.as_deref(),
+            end_time.as_deref(),
+            weekdays.as_deref(),
+            context.time,
+            context.weekday,
+        ) {
+            continue;
+        }
+        if requires_coupon {
+            match context.coupons.get(&candidate.id) {
+                Some(coupon_id) => candidate.coupon_id = Some(coupon_id.clone()),
+                None => continue,
+            }
+        }
+        if candidate.discount_type == "bundle" {
+            candidate.bundle_share = context.bundles.share(&candidate.id, &query.product_id);
+        }
+        discounts.push(candidate);
+    }
+    Ok(discounts)
+}
+
+/// Price list to use: the requested one, else the tenant's default
+fn price_list(
+    conn: &Connection,
+    tenant_id: &str,
+    price_list_id: Option<&str>,
+) -> Result<Option<(String, String, f64)>, ServiceError> {
+    match price_list_id {
+        Some(id) => conn
+            .query_row(
+                "SELECT id, name, discount_percent FROM price_lists
+                 WHERE id = ?1 AND tenant_id = ?2 AND is_active = 1",
+                params![id, tenant_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .map_err(db)?
+            .map(Some)
+            .ok_or_else(|| {
+                ServiceError::Validation("La lista de precios no está disponible".to_string())
+            }),
+        None => conn
+            .query_row(
+                "SELECT id, name, discount_percent FROM price_lists
+                 WHERE tenant_id = ?1 AND is_default = 1 AND is_active = 1
+                 ORDER BY created_at LIMIT 1",
+                [tenant_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .map_err(db),
+    }
+}
+
+/// Resolve price, discounts and tax for one line
+pub fn resolve(
+    conn: &Connection,
+    tenant_id: &str,
+    query: &PriceQuery,
+) -> Result<ResolvedPrice, ServiceError> {
+    let mut prices = resolve_all(conn, tenant_id, std::slice::from_ref(query))?;
+    Ok(prices.remove(0))
+}
+
+/// Resolve the lines of one document together, so bundles can span lines.
+/// Client, date, time, payment method and coupons are taken from the first
+/// line.
+pub fn resolve_all(
+    conn: &Connection,
+    tenant_id: &str,
+    queries: &[PriceQuery],
+) -> Result<Vec<ResolvedPrice>, ServiceError> {
+    let Some(first) = queries.first() else {
+        return Ok(Vec::new());
+    };
+    let date = match &first.date {
+        Some(date) if date.len() >= 10 => date[..10].to_string(),
+        _ => Utc::now().format("%Y-%m-%d").to_string(),
+    };
+    let weekday = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+        .map(|d| d.weekday().number_from_monday())
+        .unwrap_or_else(|_| Local::now().weekday().number_from_monday());
+    let time = match &first.time {
+        Some(time) if time.len() >= 5 => time[..5].to_string(),
+        _ => Local::now().format("%H:%M").to_string(),
+    };
+    let coupons = coupons(
+        conn,
+        tenant_id,
+        &first.coupon_codes,
+        first.client_id.as_deref(),
+    )?;
+    let bundles = Bundles::load(conn, tenant_id, queries)?;
+    let context = Context {
+        date: &date,
+        time: &time,
+        weekday,
+        coupons: &coupons,
+        bundles: &bundles,
+    };
+
+    queries
+        .iter()
+        .map(|query| resolve_line(conn, tenant_id, query, &context))
+        .collect()
+}
+
+fn resolve_line(
+    conn: &Connection,
+    tenant_id: &str,
+    query: &PriceQuery,
+    context: &Context,
+) -> Result<ResolvedPrice, ServiceError> {
+    if query.quantity <= 0.0 {
+        return Err(ServiceError::Validation(
+            "La cantidad debe ser mayor a cero".to_string(),
+        ));
+    }
+
+    let (product_price, tax_rate, category_id): (f64, f64, Option<String>) = conn
+        .query_row(
+            "SELECT COALESCE(sale_price, unit_price), COALESCE(tax_rate, 0), category_id
+             FROM products WHERE id = ?1 AND tenant_id = ?2",
+            params![query.product_id, tenant_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(db)?
+        .ok_or_else(|| ServiceError::NotFound("Producto no encontrado".to_string()))?;
+
+    // A variant with its own price overrides the product's
+    let base_price = match &query.variant_id {
+        Some(variant_id) => {
+            let variant_price: f64 = conn
+                .query_row(
+                    "SELECT COALESCE(sale_price, 0) FROM product_variants
+                     WHERE id = ?1 AND product_id = ?2",
+                    params![variant_id, query.product_id],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(db)?
+                .ok_or_else(|| ServiceError::NotFound("Variante no encontrada".to_string()))?;
+            if variant_price > 0.0 {
+                variant_price
+            } else {
+                product_price
+            }
+        }
+        None => product_price,
+    };
+
+    let mut rules = vec![AppliedPriceRule {
+        rule_type: "base_price".to_string(),
+        rule_id: None,
+        name: "Precio de venta".to_string(),
+        value: base_price,
+        amount: 0.0,
+        coupon_id: None,
+    }];
+
+    // Price list: a specific price for the variant or product, else the
+    // base price with the list's global discount
+    let list = price_list(conn, tenant_id, query.price_list_id.as_deref())?;
+    let mut unit_price = base_price;
+    let mut list_discount = 0.0;
+    if let Some((list_id, list_name, discount_percent)) = &list {
+        let specific: Option<(String, f64)> = conn
+            .query_row(
+                "SELECT id, price FROM product_prices
+                 WHERE price_list_id = ?1 AND product_id = ?2
+                   AND (variant_id IS ?3 OR variant_id IS NULL)
+                 ORDER BY variant_id IS NULL LIMIT 1",
+                params![list_id, query.product_id, query.variant_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(db)?;
+        match specific {
+            Some((price_id, price)) => {
+                unit_price = price;
+                rules.push(AppliedPriceRule {
+                    rule_type: "price_list".to_string(),
+                    rule_id: Some(price_id),
+                    name: list_name.clone(),
+                    value: price,
+                    amount: 0.0,
+                    coupon_id: None,
+                });
+            }
+            None => list_discount = *discount_percent,
+        }
+    }
+
+    let quantity = decimal(query.quantity);
+    let gross = quantity * decimal(unit_price);
+    let mut remaining = gross;
+
+    if list_discount > 0.0 {
+        let amount = remaining * decimal(list_discount).min(dec!(100)) / dec!(100);
+        remaining -= amount;
+        let (list_id, list_name, _) = list.as_ref().expect("list discount without list");
+        rules.push(AppliedPriceRule {
+            rule_type: "list_discount".to_string(),
+            rule_id: Some(list_id.clone()),
+            name: list_name.clone(),
+            value: list_discount,
+            amount: float(amount),
+            coupon_id: None,
+        });
+    }
+
+    let discounts = candidates(conn, tenant_id, query, category_id.as_deref(), context)?;
+
+    // Stackable discounts compound on what is left...
+    let mut stacked = Vec::new();
+    let mut stacked_remaining = remaining;
+    for discount in discounts.iter().filter(|d| d.stackable) {
+        let amount = discount.amount(stacked_remaining, quantity);
+        if amount > Decimal::ZERO {
+            stacked_remaining -= amount;
+            stacked.push(discount.applied(amount));
+        }
+    }
+    // ...unless an exclusive one is worth more on its own
+    let exclusive = discounts
+        .iter()
+        .filter(|d| !d.stackable)
+        .map(|d| (d, d.amount(remaining, quantity)))
+        .filter(|(_, amount)| *amount > Decimal::ZERO)
+        .fold(
+            None::<(&Candidate, Decimal)>,
+            |best, (d, amount)| match best {
+                Some((_, best_amount)) if best_amount >= amount => best,
+                _ => Some((d, amount)),
+            },
+        );
+    match exclusive {
+        Some((discount, amount)) if amount > remaining - stacked_remaining => {
+            remaining -= amount;
+            rules.push(discount.applied(amount));
+        }
+        _ => {
+            remaining = stacked_remaining;
+            rules.extend(stacked);
+        }
+    }
+
+    let discount_amount = gross - remaining;
+    let discount_percent = if gross > Decimal::ZERO {
+        discount_amount * dec!(100) / gross
+    } else {
+        Decimal::ZERO
+    };
+    let tax_amount = tax_calculator::calculate_iva(remaining, decimal(tax_rate));
+
+    Ok(ResolvedPrice {
+        product_id: query.product_id.clone(),
+        variant_id: query.variant_id.clone(),
+        price_list_id: list.map(|(id, _, _)| id),
+        quantity: query.quantity,
+        base_price,
+        unit_price,
+        discount_amount: float(discount_amount),
+        discount_percent: float(discount_percent),
+        tax_rate,
+        subtotal: float(remaining),
+        tax_amount: float(tax_amount),
+        total: float(remaining + tax_amount),
+        rules,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO categories (id, tenant_id, name) VALUES ('food', 't1', 'Víveres');
+             INSERT INTO categories (id, tenant_id, parent_id, name) VALUES ('rice', 't1', 'food', 'Arroz');
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, sale_price, tax_rate, category_id)
+                 VALUES ('p1', 't1', 'ARR-1', 'Arroz', 10.0, 10.0, 16.0, 'rice');
+             INSERT INTO price_lists (id, tenant_id, name, currency, discount_percent, is_default, created_at, updated_at)
+                 VALUES ('retail', 't1', 'Detal', 'USD', 0, 1, '2026-01-01', '2026-01-01');
+             INSERT INTO price_lists (id, tenant_id, name, currency, discount_percent, is_default, created_at, updated_at)
+                 VALUES ('wholesale', 't1', 'Mayor', 'USD', 10, 0, '2026-01-01', '2026-01-01');
+             INSERT INTO discounts (id, tenant_id, name, discount_type, value, applies_to, target_id, min_quantity, start_date, end_date, stackable, priority, created_at, updated_at)
+                 VALUES ('d-cat', 't1', 'Víveres 5%', 'percentage', 5, 'category', 'food', NULL, NULL, NULL, 1, 1, '2026-01-01', '2026-01-01'),
+                        ('d-vol', 't1', 'Por volumen', 'volume', 10, 'product', 'p1', 12, NULL, NULL, 1, 0, '2026-01-01', '2026-01-01'),
+                        ('d-cash', 't1', 'Pago móvil', 'fixed', 4, 'payment_method', 'mobile', NULL, NULL, NULL, 0, 0, '2026-01-01', '2026-01-01'),
+                        ('d-old', 't1', 'Vencido', 'percentage', 50, 'all', NULL, NULL, '2025-01-01', '2025-01-31', 1, 0, '2026-01-01', '2026-01-01');",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn query(quantity: f64) -> PriceQuery {
+        PriceQuery {
+            product_id: "p1".to_string(),
+            quantity,
+            date: Some("2026-03-01".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_resolve_stacks_list_category_and_volume_discounts() {
+        let conn = setup();
+
+        // Default list, category discount inherited from the parent category
+        let price = resolve(&conn, "t1", &query(2.0)).unwrap();
+        assert_eq!(price.price_list_id.as_deref(), Some("retail"));
+        assert!((price.discount_amount - 1.0).abs() < 1e-9);
+        assert!((price.total - 19.0 * 1.16).abs() < 1e-9);
+
+        // Wholesale 10% first, then 5% and 10% volume compound
+        let mut wholesale = query(12.0);
+        wholesale.price_list_id = Some("wholesale".to_string());
+        let price = resolve(&conn, "t1", &wholesale).unwrap();
+        let expected = 120.0 * 0.9 * 0.95 * 0.9;
+        assert!((price.subtotal - expected).abs() < 1e-9);
+        let applied: Vec<_> = price.rules.iter().map(|r| r.rule_type.as_str()).collect();
+        assert_eq!(
+            applied,
+            vec!["base_price", "list_discount", "discount", "discount"]
+        );
+
+        // A specific list price replaces the list discount
+        conn.execute(
+            "INSERT INTO product_prices (id, price_list_id, product_id, price, created_at, updated_at)
+             VALUES ('pp1', 'wholesale', 'p1', 8.0, '2026-01-01', '2026-01-01')",
+            [],
+        )
+        .unwrap();
+        let mut wholesale = query(1.0);
+        wholesale.price_list_id = Some("wholesale".to_string());
+        let price = resolve(&conn, "t1", &wholesale).unwrap();
+        assert_eq!(price.unit_price, 8.0);
+        assert!((price.subtotal - 7.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_exclusive_discount_only_when_better() {
+        let conn = setup();
+
+        // 4 off per unit beats 5%: applied alone
+        let mut mobile = query(1.0);
+        mobile.payment_method = Some("mobile".to_string());
+        let price = resolve(&conn, "t1", &mobile).unwrap();
+        assert!((price.subtotal - 6.0).abs() < 1e-9);
+        assert_eq!(price.rules.len(), 2);
+        assert_eq!(price.rules[1].rule_id.as_deref(), Some("d-cash"));
+
+        // Exhausted discounts no longer apply
+        conn.execute(
+            "UPDATE discounts SET max_uses = 1, times_used = 1 WHERE id = 'd-cash'",
+            [],
+        )
+        .unwrap();
+        let price = resolve(&conn, "t1", &mobile).unwrap();
+        assert!((price.subtotal - 9.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_coupons_buy_x_get_y_bundles_and_time_windows() {
+        let conn = setup();
+        conn.execute_batch(
+            "INSERT INTO products (id, tenant_id, sku, name, unit_price, sale_price, tax_rate)
+                 VALUES ('p2', 't1', 'CAF-1', 'Café', 5.0, 5.0, 0),
+                        ('p3', 't1', 'JUG-1', 'Jugo', 3.0, 3.0, 0),
+                        ('p4', 't1', 'PAN-1', 'Pan', 2.0, 2.0, 0);
+             INSERT INTO discounts (id, tenant_id, name, discount_type, value, applies_to, target_id, requires_coupon, buy_quantity, get_quantity, start_time, end_time, created_at, updated_at)
+                 VALUES ('d-cpn', 't1', 'Cupón café', 'percentage', 20, 'product', 'p2', 1, NULL, NULL, NULL, NULL, '2026-01-01', '2026-01-01'),
+                        ('d-2x1', 't1', 'Lleva 3 paga 2', 'buy_x_get_y', 100, 'product', 'p3', 0, 2, 1, NULL, NULL, '2026-01-01', '2026-01-01'),
+                        ('d-combo', 't1', 'Desayuno', 'bundle', 10, 'bundle', NULL, 0, NULL, NULL, NULL, NULL, '2026-01-01', '2026-01-01'),
+                        ('d-happy', 't1', 'Hora feliz', 'percentage', 50, 'product', 'p4', 0, NULL, NULL, '17:00', '19:00', '2026-01-01', '2026-01-01');
+             INSERT INTO discount_coupons (id, tenant_id, discount_id, code, max_uses, created_at, updated_at)
+                 VALUES ('c1', 't1', 'd-cpn', 'VERANO', 1, '2026-01-01', '2026-01-01');
+             INSERT INTO discount_bundle_items (id, discount_id, product_id, quantity, created_at, updated_at)
+                 VALUES ('b1', 'd-combo', 'p2', 1, '2026-01-01', '2026-01-01'),
+                        ('b2', 'd-combo', 'p4', 2, '2026-01-01', '2026-01-01');",
+        )
+        .unwrap();
+        let line = |product: &str, quantity: f64, time: &str, codes: &[&str]| PriceQuery {
+            product_id: product.to_string(),
+            quantity,
+            date: Some("2026-03-01".to_string()),
+            time: Some(time.to_string()),
+            coupon_codes: codes.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        };
+
+        // Coupon-only discount: nothing without the code, 20% with it
+        let price = resolve(&conn, "t1", &line("p2", 1.0, "12:00", &[])).unwrap();
+        assert_eq!(price.subtotal, 5.0);
+        let price = resolve(&conn, "t1", &line("p2", 1.0, "12:00", &[" verano"])).unwrap();
+        assert!((price.subtotal - 4.0).abs() < 1e-9);
+        assert_eq!(price.rules[1].coupon_id.as_deref(), Some("c1"));
+        conn.execute("UPDATE discount_coupons SET times_used = 1", [])
+            .unwrap();
+        assert!(resolve(&conn, "t1", &line("p2", 1.0, "12:00", &["VERANO"])).is_err());
+
+        // Buy 2 get 1: 7 units, 2 free
+        let price = resolve(&conn, "t1", &line("p3", 7.0, "12:00", &[])).unwrap();
+        assert!((price.subtotal - 15.0).abs() < 1e-9);
+
+        // One complete bundle out of 2 coffees and 3 breads
+        let lines = resolve_all(
+            &conn,
+            "t1",
+            &[line("p2", 2.0, "12:00", &[]), line("p4", 3.0, "12:00", &[])],
+        )
+        .unwrap();
+        assert!((lines[0].discount_amount - 0.5).abs() < 1e-9);
+        assert!((lines[1].discount_amount - 0.4).abs() < 1e-9);
+
+        // Happy hour only inside its window
+        let price = resolve(&conn, "t1", &line("p4", 1.0, "18:30", &[])).unwrap();
+        assert_eq!(price.subtotal, 1.0);
+        let price = resolve(&conn, "t1", &line("p4", 1.0, "19:00", &[])).unwrap();
+        assert_eq!(price.subtotal, 2.0);
+    }
+}
@@ -0,0 +1,688 @@
+//! Sales Reports
+//!
+//! Reports read issued invoices (not quotes, notes or drafts) line by line.
+//! Every figure is given in the invoice currency and again in a reporting
+//! currency, converted with the rates recorded for the branch on the invoice
+//! date, so a report over months does not drift with today's rate. Margin
+//! uses the unit cost snapshotted on each line when the invoice was created.
+
+use crate::models::report::{
+    PaymentMixRow, ReportFilters, SalesGrouping, SalesRow, TaxReportRow, TopProduct, TopProductsBy,
+};
+use crate::services::{branches, exchange_rates};
+use crate::state::ServiceError;
+use chrono::NaiveDate;
+use rusqlite::{params_from_iter, Connection, OptionalExtension};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+fn db(e: rusqlite::Error) -> ServiceError {
+    ServiceError::Database(e.to_string())
+}
+
+const DEFAULT_TOP_LIMIT: usize = 10;
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+/// Branches, dates and reporting currency a report runs over
+struct Scope {
+    tenant_ids: Vec<String>,
+    from_date: Option<String>,
+    to_date: Option<String>,
+    currency: String,
+}
+
+fn parse_date(value: &Option<String>) -> Result<Option<String>, ServiceError> {
+    match value.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+        None => Ok(None),
+        Some(v) => NaiveDate::parse_from_str(v, "%Y-%m-%d")
+            .map(|d| Some(d.format("%Y-%m-%d").to_string()))
+            .map_err(|_| ServiceError::Validation(format!("Fecha inválida: {}", v))),
+    }
+}
+
+fn resolve_scope(
+    conn: &Connection,
+    user_id: &str,
+    tenant_id: &str,
+    filters: &ReportFilters,
+) -> Result<Scope, ServiceError> {
+    let tenant_ids = if filters.branch_ids.is_empty() {
+        vec![tenant_id.to_string()]
+    } else {
+        let allowed: HashSet<String> = branches::list_branches(conn, user_id, Some(tenant_id))?
+            .into_iter()
+            .map(|b| b.id)
+            .collect();
+        let mut ids = Vec::new();
+        for id in &filters.branch_ids {
+            if !allowed.contains(id) {
+                return Err(ServiceError::Unauthorized(
+                    "Sin acceso a la sucursal".to_string(),
+                ));
+            }
+            if !ids.contains(id) {
+                ids.push(id.clone());
+            }
+        }
+        ids
+    };
+
+    let from_date = parse_date(&filters.from_date)?;
+    let to_date = parse_date(&filters.to_date)?;
+    if let (Some(from), Some(to)) = (&from_date, &to_date) {
+        if from > to {
+            return Err(ServiceError::Validation(
+                "La fecha inicial es posterior a la final".to_string(),
+            ));
+        }
+    }
+
+    let currency = match filters
+        .reporting_currency
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+    {
+        Some(c) => c.to_uppercase(),
+        None => conn
+            .query_row(
+                "SELECT default_currency FROM company_settings WHERE tenant_id = ?1",
+                [tenant_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(db)?
+            .unwrap_or_else(|| "USD".to_string()),
+    };
+
+    Ok(Scope {
+        tenant_ids,
+        from_date,
+        to_date,
+        currency,
+    })
+}
+
+/// Converts invoice amounts to the reporting currency, one rate lookup per
+/// branch, currency and day
+struct Converter<'a> {
+    conn: &'a Connection,
+    to: String,
+    rates: HashMap<(String, String, String), f64>,
+}
+
+impl<'a> Converter<'a> {
+    fn new(conn: &'a Connection, to: &str) -> Self {
+        Self {
+            conn,
+            to: to.to_string(),
+            rates: HashMap::new(),
+        }
+    }
+
+    fn rate(&mut self, tenant_id: &str, currency: &str, day: &str) -> Result<f64, ServiceError> {
+        let key = (tenant_id.to_string(), currency.to_string(), day.to_string());
+        if let Some(rate) = self.rates.get(&key) {
+            return Ok(*rate);
+        }
+        let rate = exchange_rates::rate_on(self.conn, tenant_id, currency, day)?
+            .filter(|r| *r > 0.0)
+            .ok_or_else(|| {
+                ServiceError::Validation(format!("No hay tasa de {} para el {}", currency, day))
+            })?;
+        self.rates.insert(key, rate);
+        Ok(rate)
+    }
+
+    /// Factor from `currency` to the reporting currency on `date`
+    fn factor(&mut self, tenant_id: &str, currency: &str, date: &str) -> Result<f64, ServiceError> {
+        if currency == self.to {
+            return Ok(1.0);
+        }
+        let day = date.get(..10).unwrap_or(date);
+        let to = self.to.clone();
+        Ok(self.rate(tenant_id, currency, day)? / self.rate(tenant_id, &to, day)?)
+    }
+}
+
+/// One invoice line with the names reports group by
+struct Line {
+    tenant_id: String,
+    invoice_id: String,
+    issue_date: String,
+    currency: String,
+    product_id: Option<String>,
+    product_name: String,
+    category_id: Option<String>,
+    category_name: Option<String>,
+    client_id: String,
+    client_name: String,
+    user_id: String,
+    user_name: Option<String>,
+    register_id: Option<String>,
+    register_name: Option<String>,
+    quantity: f64,
+    net: f64,
+    tax: f64,
+    total: f64,
+    cost: f64,
+    tax_rate: f64,
+}
+
+fn placeholders(count: usize, first: usize) -> String {
+    (first..first + count)
+        .map(|i| format!("?{}", i))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn load_lines(conn: &Connection, scope: &Scope) -> Result<Vec<Line>, ServiceError> {
+    let sql = format!(
+        "SELECT i.tenant_id, i.id, i.issue_date, i.currency,
+                it.product_id, COALESCE(p.name, it.description), p.category_id, c.name,
+                i.client_id, i.client_name, i.created_by, u.name, i.register_id, r.name,
+                it.quantity, it.line_total - it.tax_amount, it.tax_amount, it.line_total,
+                COALESCE(it.unit_cost, 0) * it.quantity, it.tax_rate
+         FROM billing_invoice_items it
+         JOIN billing_invoices i ON i.id = it.invoice_id
+         LEFT JOIN products p ON p.id = it.product_id
+         LEFT JOIN categories c ON c.id = p.category_id
+         LEFT JOIN users u ON u.id = i.created_by
+         LEFT JOIN cash_registers r ON r.id = i.register_id
+         WHERE i.invoice_type = 'invoice'
+           AND i.status IN ('issued', 'partial', 'paid')
+           AND (?1 IS NULL OR substr(i.issue_date, 1, 10) >= ?1)
+           AND (?2 IS NULL OR substr(i.issue_date, 1, 10) <= ?2)
+           AND i.tenant_id IN ({})
+         ORDER BY i.issue_date, i.id, it.id",
+        placeholders(scope.tenant_ids.len(), 3)
+    );
+    let mut values = vec![scope.from_date.clone(), scope.to_date.clone()];
+    values.extend(scope.tenant_ids.iter().cloned().map(Some));
+
+    let mut stmt = conn.prepare(&sql).map_err(db)?;
+    let lines = stmt
+        .query_map(params_from_iter(values.iter()), |row| {
+            Ok(Line {
+                tenant_id: row.get(0)?,
+                invoice_id: row.get(1)?,
+                issue_date: row.get(2)?,
+                currency: row.get(3)?,
+                product_id: row.get(4)?,
+                product_name: row.get(5)?,
+                category_id: row.get(6)?,
+                category_name: row.get(7)?,
+                client_id: row.get(8)?,
+                client_name: row.get(9)?,
+                user_id: row.get(10)?,
+                user_name: row.get(11)?,
+                register_id: row.get(12)?,
+                register_name: row.get(13)?,
+                quantity: row.get(14)?,
+                net: row.get(15)?,
+                tax: row.get(16)?,
+                total: row.get(17)?,
+                cost: row.get(18)?,
+                tax_rate: row.get(19)?,
+            })
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(lines)
+}
+
+/// (key, label) of the group a line falls in
+fn group_of(line: &Line, grouping: SalesGrouping) -> (String, String) {
+    let day = line.issue_date.get(..10).unwrap_or(&line.issue_date);
+    match grouping {
+        SalesGrouping::Day => (day.to_string(), day.to_string()),
+        SalesGrouping::Week => {
+            let week = NaiveDate::parse_from_str(day, "%Y-%m-%d")
+                .map(|d| d.format("%G-W%V").to_string())
+                .unwrap_or_else(|_| day.to_string());
+            (week.clone(), week)
+        }
+        SalesGrouping::Month => {
+            let month = day.get(..7).unwrap_or(day).to_string();
+            (month.clone(), month)
+        }
+        SalesGrouping::Product => (
+            line.product_id.clone().unwrap_or_default(),
+            line.product_name.clone(),
+        ),
+        SalesGrouping::Category => (
+            line.category_id.clone().unwrap_or_default(),
+            line.category_name
+                .clone()
+                .unwrap_or_else(|| "Sin categoría".to_string()),
+        ),
+        SalesGrouping::Client => (line.client_id.clone(), line.client_name.clone()),
+        SalesGrouping::User => (
+            line.user_id.clone(),
+            line.user_name
+                .clone()
+                .unwrap_or_else(|| line.user_id.clone()),
+        ),
+        SalesGrouping::Register => (
+            line.register_id.clone().unwrap_or_default(),
+            line.register_name
+                .clone()
+                .unwrap_or_else(|| "Sin caja".to_string()),
+        ),
+    }
+}
+
+#[derive(Default)]
+struct Totals {
+    label: String,
+    invoices: HashSet<String>,
+    quantity: f64,
+    net: f64,
+    tax: f64,
+    total: f64,
+    cost: f64,
+    net_reporting: f64,
+    tax_reporting: f64,
+    total_reporting: f64,
+    cost_reporting: f64,
+}
+
+impl Totals {
+    fn add(&mut self, line: &Line, factor: f64) {
+        self.invoices.insert(line.invoice_id.clone());
+        self.quantity += line.quantity;
+        self.net += line.net;
+        self.tax += line.tax;
+        self.total += line.total;
+        self.cost += line.cost;
+        self.net_reporting += line.net * factor;
+        self.tax_reporting += line.tax * factor;
+        self.total_reporting += line.total * factor;
+        self.cost_reporting += line.cost * factor;
+    }
+}
+
+/// Sales per period or per product, category, client, user or register, one
+/// row per group and invoice currency
+pub fn sales(
+    conn: &Connection,
+    user_id: &str,
+    tenant_id: &str,
+    filters: &ReportFilters,
+    grouping: SalesGrouping,
+) -> Result<Vec<SalesRow>, ServiceError> {
+    let scope = resolve_scope(conn, user_id, tenant_id, filters)?;
+    let mut converter = Converter::new(conn, &scope.currency);
+    let mut groups: BTreeMap<(String, String), Totals> = BTreeMap::new();
+
+    for line in load_lines(conn, &scope)? {
+        let factor = converter.factor(&line.tenant_id, &line.currency, &line.issue_date)?;
+        let (key, label) = group_of(&line, grouping);
+        let totals = groups.entry((key, line.currency.clone())).or_default();
+        totals.label = label;
+        totals.add(&line, factor);
+    }
+
+    let mut rows: Vec<SalesRow> = groups
+        .into_iter()
+        .map(|((key, currency), t)| {
+            let margin = t.net - t.cost;
+            SalesRow {
+                key,
+                label: t.label,
+                currency,
+                invoices: t.invoices.len() as i64,
+                quantity: round(t.quantity, 3),
+                net: round(t.net, 2),
+                tax: round(t.tax, 2),
+                total: round(t.total, 2),
+                cost: round(t.cost, 2),
+                margin: round(margin, 2),
+                margin_percent: if t.net.abs() > f64::EPSILON {
+                    round(margin / t.net * 100.0, 2)
+                } else {
+                    0.0
+                },
+                reporting_currency: scope.currency.clone(),
+                net_reporting: round(t.net_reporting, 2),
+                tax_reporting: round(t.tax_reporting, 2),
+                total_reporting: round(t.total_reporting, 2),
+                cost_reporting: round(t.cost_reporting, 2),
+                margin_reporting: round(t.net_reporting - t.cost_reporting, 2),
+            }
+        })
+        .collect();
+
+    // Periods read in order; everything else from the best seller down
+    if !matches!(
+        grouping,
+        SalesGrouping::Day | SalesGrouping::Week | SalesGrouping::Month
+    ) {
+        rows.sort_by(|a, b| b.total_reporting.total_cmp(&a.total_reporting));
+    }
+    Ok(rows)
+}
+
+/// Tax collected per rate and invoice currency
+pub fn taxes(
+    conn: &Connection,
+    user_id: &str,
+    tenant_id: &str,
+    filters: &ReportFilters,
+) -> Result<Vec<TaxReportRow>, ServiceError> {
+    let scope = resolve_scope(conn, user_id, tenant_id, filters)?;
+    let mut converter = Converter::new(conn, &scope.currency);
+    // Rates are keyed in hundredths so they can be map keys
+    let mut groups: BTreeMap<(String, i64), [f64; 4]> = BTreeMap::new();
+
+    for line in load_lines(conn, &scope)? {
+        let factor = converter.factor(&line.tenant_id, &line.currency, &line.issue_date)?;
+        let key = (
+            line.currency.clone(),
+            (line.tax_rate * 100.0).round() as i64,
+        );
+        let sums = groups.entry(key).or_default();
+        sums[0] += line.net;
+        sums[1] += line.tax;
+        sums[2] += line.net * factor;
+        sums[3] += line.tax * factor;
+    }
+
+    Ok(groups
+        .into_iter()
+        .map(|((currency, rate), sums)| TaxReportRow {
+            currency,
+            tax_rate: rate as f64 / 100.0,
+            taxable: round(sums[0], 2),
+            tax: round(sums[1], 2),
+            reporting_currency: scope.currency.clone(),
+            taxable_reporting: round(sums[2], 2),
+            tax_reporting: round(sums[3], 2),
+        })
+        .collect())
+}
+
+/// Payments received per method and tender currency; the share is of the
+/// amount applied to invoices, in the reporting currency
+pub fn payment_mix(
+    conn: &Connection,
+    user_id: &str,
+    tenant_id: &str,
+    filters: &ReportFilters,
+) -> Result<Vec<PaymentMixRow>, ServiceError> {
+    let scope = resolve_scope(conn, user_id, tenant_id, filters)?;
+    let mut converter = Converter::new(conn, &scope.currency);
+
+    let sql = format!(
+        "SELECT p.tenant_id, p.payment_method, p.currency, p.payment_date, i.currency,
+                p.amount, COALESCE(p.received_amount, p.amount)
+         FROM billing_payments p
+         JOIN billing_invoices i ON i.id = p.invoice_id
+         WHERE i.status != 'cancelled'
+           AND (?1 IS NULL OR substr(p.payment_date, 1, 10) >= ?1)
+           AND (?2 IS NULL OR substr(p.payment_date, 1, 10) <= ?2)
+           AND p.tenant_id IN ({})",
+        placeholders(scope.tenant_ids.len(), 3)
+    );
+    let mut values = vec![scope.from_date.clone(), scope.to_date.clone()];
+    values.extend(scope.tenant_ids.iter().cloned().map(Some));
+
+    let mut stmt = conn.prepare(&sql).map_err(db)?;
+    #[allow(clippy::type_complexity)]
+    let payments: Vec<(String, String, String, String, String, f64, f64)> = stmt
+        .query_map(params_from_iter(values.iter()), |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+                row.get(6)?,
+            ))
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    let mut groups: BTreeMap<(String, String), (i64, f64, f64)> = BTreeMap::new();
+    for (tenant, method, currency, date, invoice_currency, amount, received) in payments {
+        let factor = converter.factor(&tenant, &invoice_currency, &date)?;
+        let sums = groups.entry((method, currency)).or_default();
+        sums.0 += 1;
+        sums.1 += received;
+        sums.2 += amount * factor;
+    }
+
+    let grand_total: f64 = groups.values().map(|s| s.2).sum();
+    let mut rows: Vec<PaymentMixRow> = groups
+        .into_iter()
+        .map(
+            |((payment_method, currency), (count, received, amount))| PaymentMixRow {
+                payment_method,
+                currency,
+                payments: count,
+                received: round(received, 2),
+                reporting_currency: scope.currency.clone(),
+                amount_reporting: round(amount, 2),
+                share_percent: if grand_total.abs() > f64::EPSILON {
+                    round(amount / grand_total * 100.0, 2)
+                } else {
+                    0.0
+                },
+            },
+        )
+        .collect();
+    rows.sort_by(|a, b| b.amount_reporting.total_cmp(&a.amount_reporting));
+    Ok(rows)
+}
+
+/// Best-selling products across invoice currencies, by revenue, quantity
+/// or margin in the reporting currency
+pub fn top_products(
+    conn: &Connection,
+    user_id: &str,
+    tenant_id: &str,
+    filters: &ReportFilters,
+    by: TopProductsBy,
+    limit: Option<usize>,
+) -> Result<Vec<TopProduct>, ServiceError> {
+    let scope = resolve_scope(conn, user_id, tenant_id, filters)?;
+    let mut converter = Converter::new(conn, &scope.currency);
+    let mut groups: HashMap<String, Totals> = HashMap::new();
+
+    for line in load_lines(conn, &scope)? {
+        let Some(product_id) = line.product_id.clone() else {
+            continue;
+        };
+        let factor = converter.factor(&line.tenant_id, &line.currency, &line.issue_date)?;
+        let totals = groups.entry(product_id).or_default();
+        totals.label = line.product_name.clone();
+        totals.add(&line, factor);
+    }
+
+    let metric = |t: &Totals| match by {
+        TopProductsBy::Revenue => t.net_reporting,
+        TopProductsBy::Quantity => t.quantity,
+        TopProductsBy::Margin => t.net_reporting - t.cost_reporting,
+    };
+    let mut ranked: Vec<(String, Totals)> = groups.into_iter().collect();
+    ranked.sort_by(|a, b| metric(&b.1).total_cmp(&metric(&a.1)).then(a.0.cmp(&b.0)));
+
+    Ok(ranked
+        .into_iter()
+        .take(limit.unwrap_or(DEFAULT_TOP_LIMIT).max(1))
+        .enumerate()
+        .map(|(i, (product_id, t))| TopProduct {
+            rank: i + 1,
+            product_id,
+            product_name: t.label,
+            quantity: round(t.quantity, 3),
+            invoices: t.invoices.len() as i64,
+            reporting_currency: scope.currency.clone(),
+            net_reporting: round(t.net_reporting, 2),
+            margin_reporting: round(t.net_reporting - t.cost_reporting, 2),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t2', 'o1', 'Este');
+             INSERT INTO users (id, org_id, tenant_id, email, password_hash, name, role)
+                 VALUES ('u1', 'o1', 't1', 'admin@x.com', 'x', 'Ana', 'admin');
+             INSERT INTO users (id, org_id, tenant_id, email, password_hash, name, role)
+                 VALUES ('u2', 'o1', 't1', 'op@x.com', 'x', 'Op', 'operator');
+             INSERT INTO categories (id, tenant_id, name) VALUES ('cat1', 't1', 'Granos');
+             INSERT INTO clients (id, tenant_id, name) VALUES ('cl1', 't1', 'Cliente');
+             INSERT INTO clients (id, tenant_id, name) VALUES ('cl2', 't2', 'Otro');
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, cost_price, category_id, is_active)
+                 VALUES ('p1', 't1', 'ARR-1', 'Arroz', 2.0, 1.5, 'cat1', 1);
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, cost_price, is_active)
+                 VALUES ('p2', 't1', 'CAF-1', 'Café', 10.0, 6.0, 1);
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, cost_price, category_id, is_active)
+                 VALUES ('p3', 't2', 'ARR-1', 'Arroz', 2.0, 1.5, NULL, 1);
+             INSERT INTO exchange_rates (id, tenant_id, currency, rate, rate_date, created_at)
+                 VALUES ('r1', 't1', 'USD', 40, '2026-01-01', '2026-01-01'),
+                        ('r2', 't1', 'USD', 50, '2026-02-01', '2026-02-01'),
+                        ('r3', 't2', 'USD', 40, '2026-01-01', '2026-01-01');
+             INSERT INTO billing_invoices (id, tenant_id, invoice_number, invoice_type, status, client_id,
+                 client_name, currency, exchange_rate, issue_date, subtotal, tax_total, total,
+                 created_by, created_at, updated_at)
+                 VALUES ('i1', 't1', 'FAC-1', 'invoice', 'paid', 'cl1', 'Cliente', 'USD', 40,
+                         '2026-01-10', 20, 3.2, 23.2, 'u1', '2026-01-10', '2026-01-10'),
+                        ('i2', 't1', 'FAC-2', 'invoice', 'issued', 'cl1', 'Cliente', 'VES', 50,
+                         '2026-02-03T10:00:00Z', 1000, 160, 1160, 'u2', '2026-02-03', '2026-02-03'),
+                        ('i3', 't2', 'FAC-1', 'invoice', 'issued', 'cl2', 'Otro', 'USD', 40,
+                         '2026-01-10', 4, 0.64, 4.64, 'u1', '2026-01-10', '2026-01-10'),
+                        ('q1', 't1', 'COT-1', 'quote', 'draft', 'cl1', 'Cliente', 'USD', 40,
+                         '2026-01-10', 100, 16, 116, 'u1', '2026-01-10', '2026-01-10');
+             INSERT INTO billing_invoice_items (id, invoice_id, product_id, code, description, quantity,
+                 unit_price, tax_rate, tax_amount, line_total, unit_cost)
+                 VALUES ('it1', 'i1', 'p1', 'ARR-1', 'Arroz', 5, 2, 16, 1.6, 11.6, 1.5),
+                        ('it2', 'i1', 'p2', 'CAF-1', 'Café', 1, 10, 16, 1.6, 11.6, 6),
+                        ('it3', 'i2', 'p1', 'ARR-1', 'Arroz', 10, 100, 16, 160, 1160, 75),
+                        ('it4', 'i3', 'p3', 'ARR-1', 'Arroz', 2, 2, 16, 0.64, 4.64, 1.5),
+                        ('it5', 'q1', 'p2', 'CAF-1', 'Café', 10, 10, 16, 16, 116, 6);
+             INSERT INTO billing_payments (id, tenant_id, invoice_id, amount, currency, exchange_rate,
+                 payment_method, payment_date, received_amount, created_by, created_at)
+                 VALUES ('pay1', 't1', 'i1', 20, 'USD', 40, 'cash', '2026-01-10', 20, 'u1', '2026-01-10'),
+                        ('pay2', 't1', 'i1', 3.2, 'VES', 40, 'mobile', '2026-01-10', 128, 'u1', '2026-01-10');",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn filters(branch_ids: &[&str], currency: &str) -> ReportFilters {
+        ReportFilters {
+            branch_ids: branch_ids.iter().map(|s| s.to_string()).collect(),
+            reporting_currency: Some(currency.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sales_margin_taxes_and_mix_in_reporting_currency() {
+        let conn = setup();
+        let usd = filters(&[], "USD");
+
+        // Quotes are left out; the VES invoice converts at February's rate
+        let days = sales(&conn, "u1", "t1", &usd, SalesGrouping::Day).unwrap();
+        assert_eq!(days.len(), 2);
+        assert_eq!((days[0].key.as_str(), days[0].invoices), ("2026-01-10", 1));
+        assert_eq!(
+            (days[0].net, days[0].cost, days[0].margin),
+            (20.0, 13.5, 6.5)
+        );
+        assert_eq!(
+            (days[1].key.as_str(), days[1].currency.as_str()),
+            ("2026-02-03", "VES")
+        );
+        assert_eq!(
+            (days[1].net_reporting, days[1].margin_reporting),
+            (20.0, 5.0)
+        );
+
+        let months = sales(
+            &conn,
+            "u1",
+            "t1",
+            &filters(&[], "VES"),
+            SalesGrouping::Month,
+        )
+        .unwrap();
+        assert_eq!(months[0].total_reporting, 928.0);
+
+        let categories = sales(&conn, "u1", "t1", &usd, SalesGrouping::Category).unwrap();
+        assert_eq!(categories[0].label, "Granos");
+        assert!(categories.iter().any(|r| r.label == "Sin categoría"));
+
+        let users = sales(&conn, "u1", "t1", &usd, SalesGrouping::User).unwrap();
+        assert_eq!(users.len(), 2);
+
+        // Both branches, then a date range that keeps only January
+        let both = filters(&["t1", "t2"], "USD");
+        let products = sales(&conn, "u1", "t1", &both, SalesGrouping::Product).unwrap();
+        assert_eq!(products.len(), 4);
+        let january = ReportFilters {
+            to_date: Some("2026-01-31".to_string()),
+            ..both.clone()
+        };
+        let weeks = sales(&conn, "u1", "t1", &january, SalesGrouping::Week).unwrap();
+        assert_eq!(weeks.len(), 1);
+        assert_eq!((weeks[0].key.as_str(), weeks[0].invoices), ("2026-W02", 2));
+
+        let top = top_products(&conn, "u1", "t1", &both, TopProductsBy::Revenue, Some(2)).unwrap();
+        assert_eq!(top.len(), 2);
+        assert_eq!((top[0].rank, top[0].product_id.as_str()), (1, "p1"));
+        assert_eq!((top[0].quantity, top[0].net_reporting), (15.0, 30.0));
+        let by_margin =
+            top_products(&conn, "u1", "t1", &both, TopProductsBy::Margin, None).unwrap();
+        assert_eq!(by_margin[0].product_id, "p1");
+        assert_eq!(by_margin[0].margin_reporting, 7.5);
+
+        // An operator only sees their home branch
+        assert!(matches!(
+            sales(&conn, "u2", "t1", &both, SalesGrouping::Day),
+            Err(ServiceError::Unauthorized(_))
+        ));
+
+        let tax = taxes(&conn, "u1", "t1", &usd).unwrap();
+        assert_eq!(tax.len(), 2);
+        assert_eq!((tax[0].currency.as_str(), tax[0].tax_rate), ("USD", 16.0));
+        assert_eq!((tax[0].taxable, tax[0].tax), (20.0, 3.2));
+        assert_eq!(tax[1].tax_reporting, 3.2);
+
+        let mix = payment_mix(&conn, "u1", "t1", &usd).unwrap();
+        assert_eq!(mix[0].payment_method, "cash");
+        assert_eq!((mix[1].currency.as_str(), mix[1].received), ("VES", 128.0));
+        assert_eq!(mix[1].amount_reporting, 3.2);
+        assert_eq!((mix[0].share_percent, mix[1].share_percent), (86.21, 13.79));
+
+        // No EUR rate recorded
+        assert!(sales(&conn, "u1", "t1", &filters(&[], "EUR"), SalesGrouping::Day).is_err());
+        assert!(sales(
+            &conn,
+            "u1",
+            "t1",
+            &ReportFilters {
+                from_date: Some("10/01/2026".to_string()),
+                ..usd
+            },
+            SalesGrouping::Day
+        )
+        .is_err());
+    }
+}
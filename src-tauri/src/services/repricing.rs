@@ -0,0 +1,892 @@
+//! Bulk Repricing
+//!
+//! Changes the sale price (or one price list's prices) of every product that
+//! matches a set of filters: by a percentage, a fixed amount, a margin over
+//! cost, or just a rounding rule. Changes can be previewed; applying them
+//! writes one batch, in one transaction, with a `price_history` entry per
+//! price so the batch can be undone later.
+//!
+//! Derived price lists (a VES list from a USD one, say) are recomputed here
+//! too: base list price × exchange rate, rounded, as one batch per list.
+
+use crate::models::pricing::{BulkRepriceDto, PriceBatch, PriceBatchUndo, PriceChange};
+use crate::services::exchange_rates;
+use crate::state::ServiceError;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use rust_decimal::prelude::*;
+use rust_decimal_macros::dec;
+use uuid::Uuid;
+
+fn db(e: rusqlite::Error) -> ServiceError {
+    ServiceError::Database(e.to_string())
+}
+
+/// Shortest decimal of the float, so rounding up 72.4 doesn't see 72.4000…1
+fn decimal(value: f64) -> Decimal {
+    Decimal::from_f64(value).unwrap_or_default()
+}
+
+const BATCH_COLUMNS: &str = "id, price_list_id, operation, value, round_to, round_mode, reason,
+    items_count, status, created_by, created_at, undone_by, undone_at";
+
+fn map_batch(row: &rusqlite::Row) -> rusqlite::Result<PriceBatch> {
+    Ok(PriceBatch {
+        id: row.get(0)?,
+        price_list_id: row.get(1)?,
+        operation: row.get(2)?,
+        value: row.get(3)?,
+        round_to: row.get(4)?,
+        round_mode: row.get(5)?,
+        reason: row.get(6)?,
+        items_count: row.get(7)?,
+        status: row.get(8)?,
+        created_by: row.get(9)?,
+        created_at: row.get(10)?,
+        undone_by: row.get(11)?,
+        undone_at: row.get(12)?,
+    })
+}
+
+/// Round to a multiple of `step` (half away from zero), or to cents
+fn round_price(price: Decimal, step: Option<Decimal>, mode: &str) -> Decimal {
+    match step.filter(|step| *step > Decimal::ZERO) {
+        Some(step) => {
+            let units = price / step;
+            let units = match mode {
+                "up" => units.ceil(),
+                "down" => units.floor(),
+                _ => units.round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero),
+            };
+            units * step
+        }
+        None => price.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero),
+    }
+}
+
+fn validate(data: &BulkRepriceDto) -> Result<(), ServiceError> {
+    if data.reason.trim().is_empty() {
+        return Err(ServiceError::Validation(
+            "Indique el motivo del cambio de precios".to_string(),
+        ));
+    }
+    match data.operation.as_str() {
+        "percentage" if data.value <= -100.0 => Err(ServiceError::Validation(
+            "El porcentaje debe ser mayor a -100".to_string(),
+        )),
+        "percentage" | "fixed" | "cost_plus" => Ok(()),
+        "round" if data.round_to.unwrap_or(0.0) <= 0.0 => Err(ServiceError::Validation(
+            "Indique a qué múltiplo redondear".to_string(),
+        )),
+        "round" => Ok(()),
+        other => Err(ServiceError::Validation(format!(
+            "Operación inválida: {}",
+            other
+        ))),
+    }?;
+    check_round_mode(data.round_mode.as_deref())
+}
+
+pub fn check_round_mode(mode: Option<&str>) -> Result<(), ServiceError> {
+    match mode {
+        None | Some("nearest") | Some("up") | Some("down") => Ok(()),
+        Some(other) => Err(ServiceError::Validation(format!(
+            "Redondeo inválido: {}",
+            other
+        ))),
+    }
+}
+
+/// A price to change and the row it lives in
+struct Target {
+    change: PriceChange,
+    product_price_id: Option<String>,
+}
+
+/// Matching products whose price actually changes
+fn targets(
+    conn: &Connection,
+    tenant_id: &str,
+    data: &BulkRepriceDto,
+) -> Result<Vec<Target>, ServiceError> {
+    validate(data)?;
+    let filters = &data.filters;
+    if let Some(price_list_id) = &filters.price_list_id {
+        conn.query_row(
+            "SELECT id FROM price_lists WHERE id = ?1 AND tenant_id = ?2",
+            params![price_list_id, tenant_id],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(db)?
+        .ok_or_else(|| ServiceError::NotFound("Lista de precios no encontrada".to_string()))?;
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE tree(id) AS (
+                 SELECT id FROM categories WHERE id = ?2
+                 UNION ALL
+                 SELECT c.id FROM categories c JOIN tree t ON c.parent_id = t.id
+             )
+             SELECT p.id, p.sku, p.name, COALESCE(p.cost_price, 0),
+                    COALESCE(pp.price, p.sale_price, p.unit_price), pp.id
+             FROM products p
+             LEFT JOIN product_prices pp ON pp.product_id = p.id AND pp.variant_id IS NULL
+                  AND pp.price_list_id = ?5
+             WHERE p.tenant_id = ?1 AND p.is_active = 1
+               AND (?2 IS NULL OR p.category_id IN (SELECT id FROM tree))
+               AND (?3 IS NULL OR p.product_type_id = ?3)
+               AND (?4 IS NULL OR p.supplier_reference = ?4)
+               AND (?5 IS NULL OR pp.id IS NOT NULL)
+             ORDER BY p.name",
+        )
+        .map_err(db)?;
+    let rows = stmt
+        .query_map(
+            params![
+                tenant_id,
+                filters.category_id,
+                filters.product_type_id,
+                filters.supplier_reference,
+                filters.price_list_id
+            ],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, f64>(3)?,
+                    row.get::<_, f64>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                ))
+            },
+        )
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    let value = decimal(data.value);
+    let step = data.round_to.map(decimal);
+    let mode = data.round_mode.as_deref().unwrap_or("nearest");
+    let mut targets = Vec::new();
+    for (product_id, sku, name, cost, old_price, product_price_id) in rows {
+        if !filters.product_ids.is_empty() && !filters.product_ids.contains(&product_id) {
+            continue;
+        }
+        let old = decimal(old_price);
+        let new = match data.operation.as_str() {
+            "percentage" => old * (Decimal::ONE + value / dec!(100)),
+            "fixed" => old + value,
+            // No cost, no margin to add
+            "cost_plus" if cost <= 0.0 => continue,
+            "cost_plus" => decimal(cost) * (Decimal::ONE + value / dec!(100)),
+            _ => old,
+        };
+        let new = round_price(new, step, mode);
+        if new < Decimal::ZERO {
+            return Err(ServiceError::Validation(format!(
+                "El precio de {} quedaría negativo",
+                name
+            )));
+        }
+        let new_price = new.to_f64().unwrap_or(0.0);
+        if (new_price - old_price).abs() < 1e-9 {
+            continue;
+        }
+        targets.push(Target {
+            change: PriceChange {
+                product_id,
+                variant_id: None,
+                sku,
+                name,
+                price_list_id: filters.price_list_id.clone(),
+                cost_price: cost,
+                old_price: Some(old_price),
+                new_price,
+            },
+            product_price_id,
+        });
+    }
+    Ok(targets)
+}
+
+/// Prices a bulk change would set, without changing anything
+pub fn preview(
+    conn: &Connection,
+    tenant_id: &str,
+    data: &BulkRepriceDto,
+) -> Result<Vec<PriceChange>, ServiceError> {
+    Ok(targets(conn, tenant_id, data)?
+        .into_iter()
+        .map(|target| target.change)
+        .collect())
+}
+
+fn record_history(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: Option<&str>,
+    change: &PriceChange,
+    reason: &str,
+    batch_id: Option<&str>,
+    now: &str,
+) -> Result<(), ServiceError> {
+    let price_type = if change.price_list_id.is_some() {
+        "list_price"
+    } else {
+        "sale_price"
+    };
+    conn.execute(
+        "INSERT INTO price_history (id, tenant_id, product_id, variant_id, price_type, old_price,
+             new_price, changed_by, reason, created_at, price_list_id, batch_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            Uuid::new_v4().to_string(),
+            tenant_id,
+            change.product_id,
+            change.variant_id,
+            price_type,
+            change.old_price,
+            change.new_price,
+            user_id,
+            reason,
+            now,
+            change.price_list_id,
+            batch_id
+        ],
+    )
+    .map_err(db)?;
+    Ok(())
+}
+
+/// Write a product's sale price, keeping its margins in step
+fn set_sale_price(
+    conn: &Connection,
+    product_id: &str,
+    price: f64,
+    now: &str,
+) -> Result<(), ServiceError> {
+    conn.execute(
+        "UPDATE products SET sale_price = ?1,
+             margin_amount = ?1 - COALESCE(cost_price, 0),
+             margin_percent = CASE WHEN COALESCE(cost_price, 0) > 0
+                 THEN (?1 - cost_price) / cost_price * 100 ELSE 0 END,
+             updated_at = ?2
+         WHERE id = ?3",
+        params![price, now, product_id],
+    )
+    .map_err(db)?;
+    Ok(())
+}
+
+fn insert_batch(
+    conn: &Connection,
+    tenant_id: &str,
+    batch: &PriceBatch,
+    filters: Option<&str>,
+) -> Result<(), ServiceError> {
+    conn.execute(
+        "INSERT INTO price_batches (id, tenant_id, price_list_id, operation, value, round_to,
+             round_mode, filters, reason, items_count, status, created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, 'applied', ?11, ?12)",
+        params![
+            batch.id,
+            tenant_id,
+            batch.price_list_id,
+            batch.operation,
+            batch.value,
+            batch.round_to,
+            batch.round_mode,
+            filters,
+            batch.reason,
+            batch.items_count,
+            batch.created_by,
+            batch.created_at
+        ],
+    )
+    .map_err(db)?;
+    Ok(())
+}
+
+/// Apply a bulk change in one transaction
+pub fn apply(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    data: &BulkRepriceDto,
+) -> Result<PriceBatch, ServiceError> {
+    let targets = targets(conn, tenant_id, data)?;
+    if targets.is_empty() {
+        return Err(ServiceError::Validation(
+            "Ningún precio cambia con estos filtros".to_string(),
+        ));
+    }
+    let now = Utc::now().to_rfc3339();
+    let batch = PriceBatch {
+        id: Uuid::new_v4().to_string(),
+        price_list_id: data.filters.price_list_id.clone(),
+        operation: data.operation.clone(),
+        value: data.value,
+        round_to: data.round_to,
+        round_mode: data.round_mode.clone(),
+        reason: data.reason.trim().to_string(),
+        items_count: targets.len() as i64,
+        status: "applied".to_string(),
+        created_by: Some(user_id.to_string()),
+        created_at: now.clone(),
+        undone_by: None,
+        undone_at: None,
+    };
+    let filters = serde_json::to_string(&data.filters)
+        .map_err(|e| ServiceError::Validation(e.to_string()))?;
+
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    insert_batch(&tx, tenant_id, &batch, Some(&filters))?;
+    for target in &targets {
+        let change = &target.change;
+        match &target.product_price_id {
+            Some(product_price_id) => {
+                tx.execute(
+                    "UPDATE product_prices SET price = ?1, updated_at = ?2 WHERE id = ?3",
+                    params![change.new_price, now, product_price_id],
+                )
+                .map_err(db)?;
+            }
+            None => set_sale_price(&tx, &change.product_id, change.new_price, &now)?,
+        }
+        record_history(
+            &tx,
+            tenant_id,
+            Some(user_id),
+            change,
+            &batch.reason,
+            Some(&batch.id),
+            &now,
+        )?;
+    }
+    tx.commit().map_err(db)?;
+
+    Ok(batch)
+}
+
+pub fn get_batch(conn: &Connection, tenant_id: &str, id: &str) -> Result<PriceBatch, ServiceError> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM price_batches WHERE id = ?1 AND tenant_id = ?2",
+            BATCH_COLUMNS
+        ),
+        params![id, tenant_id],
+        map_batch,
+    )
+    .optional()
+    .map_err(db)?
+    .ok_or_else(|| ServiceError::NotFound("Lote de precios no encontrado".to_string()))
+}
+
+pub fn list_batches(conn: &Connection, tenant_id: &str) -> Result<Vec<PriceBatch>, ServiceError> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM price_batches WHERE tenant_id = ?1 ORDER BY created_at DESC",
+            BATCH_COLUMNS
+        ))
+        .map_err(db)?;
+    let batches = stmt
+        .query_map([tenant_id], map_batch)
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(batches)
+}
+
+/// Put back the prices a batch changed. Prices changed again since then
+/// are left alone and the batch stays partially undone.
+pub fn undo(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: &str,
+    id: &str,
+) -> Result<PriceBatchUndo, ServiceError> {
+    let batch = get_batch(conn, tenant_id, id)?;
+    if batch.status != "applied" {
+        return Err(ServiceError::Validation(
+            "El lote ya fue deshecho".to_string(),
+        ));
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT h.product_id, h.variant_id, p.sku, p.name, COALESCE(p.cost_price, 0),
+                    h.price_list_id, h.old_price, h.new_price,
+                    COALESCE(pp.price, p.sale_price, p.unit_price), pp.id
+             FROM price_history h
+             JOIN products p ON p.id = h.product_id
+             LEFT JOIN product_prices pp ON pp.product_id = h.product_id
+                  AND pp.variant_id IS h.variant_id AND pp.price_list_id = h.price_list_id
+             WHERE h.batch_id = ?1 AND h.tenant_id = ?2",
+        )
+        .map_err(db)?;
+    let rows = stmt
+        .query_map(params![id, tenant_id], |row| {
+            Ok((
+                PriceChange {
+                    product_id: row.get(0)?,
+                    variant_id: row.get(1)?,
+                    sku: row.get(2)?,
+                    name: row.get(3)?,
+                    cost_price: row.get(4)?,
+                    price_list_id: row.get(5)?,
+                    old_price: row.get(6)?,
+                    new_price: row.get(7)?,
+                },
+                row.get::<_, f64>(8)?,
+                row.get::<_, Option<String>>(9)?,
+            ))
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    let now = Utc::now().to_rfc3339();
+    let reason = format!("Reversión de lote: {}", batch.reason);
+    let mut restored = 0;
+    let mut skipped = 0;
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    for (change, current, product_price_id) in &rows {
+        let list_price_gone = change.price_list_id.is_some() && product_price_id.is_none();
+        if list_price_gone || (current - change.new_price).abs() > 1e-9 {
+            skipped += 1;
+            continue;
+        }
+        match (product_price_id, change.old_price) {
+            (Some(product_price_id), Some(old_price)) => {
+                tx.execute(
+                    "UPDATE product_prices SET price = ?1, updated_at = ?2 WHERE id = ?3",
+                    params![old_price, now, product_price_id],
+                )
+                .map_err(db)?;
+            }
+            // Added by the batch
+            (Some(product_price_id), None) => {
+                tx.execute(
+                    "DELETE FROM product_prices WHERE id = ?1",
+                    [product_price_id],
+                )
+                .map_err(db)?;
+            }
+            (None, old_price) => {
+                set_sale_price(&tx, &change.product_id, old_price.unwrap_or_default(), &now)?
+            }
+        }
+        // A row the batch added is logged as removed at the price it had
+        let restored_price = match product_price_id {
+            Some(_) => Some(change.old_price.unwrap_or(change.new_price)),
+            None => change.old_price,
+        };
+        if let Some(old_price) = restored_price {
+            let reverted = PriceChange {
+                old_price: Some(change.new_price),
+                new_price: old_price,
+                ..change.clone()
+            };
+            record_history(
+                &tx,
+                tenant_id,
+                Some(user_id),
+                &reverted,
+                &reason,
+                None,
+                &now,
+            )?;
+        }
+        restored += 1;
+    }
+    let status = if skipped > 0 {
+        "partially_undone"
+    } else {
+        "undone"
+    };
+    tx.execute(
+        "UPDATE price_batches SET status = ?1, undone_by = ?2, undone_at = ?3
+         WHERE id = ?4",
+        params![status, user_id, now, id],
+    )
+    .map_err(db)?;
+    tx.commit().map_err(db)?;
+
+    Ok(PriceBatchUndo {
+        batch: get_batch(conn, tenant_id, id)?,
+        restored,
+        skipped,
+    })
+}
+
+// ============================================
+// DERIVED PRICE LISTS
+// ============================================
+
+/// How a derived list is computed
+struct Derivation {
+    base_price_list_id: String,
+    base_currency: String,
+    base_discount: f64,
+    currency: String,
+    round_to: Option<f64>,
+    round_mode: Option<String>,
+}
+
+fn derivation(
+    conn: &Connection,
+    tenant_id: &str,
+    price_list_id: &str,
+) -> Result<Option<Derivation>, ServiceError> {
+    conn.query_row(
+        "SELECT l.base_price_list_id, b.currency, COALESCE(b.discount_percent, 0), l.currency,
+                l.round_to, l.round_mode
+         FROM price_lists l
+         JOIN price_lists b ON b.id = l.base_price_list_id
+         WHERE l.id = ?1 AND l.tenant_id = ?2",
+        params![price_list_id, tenant_id],
+        |row| {
+            Ok(Derivation {
+                base_price_list_id: row.get(0)?,
+                base_currency: row.get(1)?,
+                base_discount: row.get(2)?,
+                currency: row.get(3)?,
+                round_to: row.get(4)?,
+                round_mode: row.get(5)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(db)
+}
+
+/// Check a list can derive from `base_price_list_id` in `currency`: the base
+/// exists, neither list is part of another derivation, and the rates to
+/// convert are recorded
+pub fn check_derivation(
+    conn: &Connection,
+    tenant_id: &str,
+    price_list_id: Option<&str>,
+    base_price_list_id: &str,
+    currency: &str,
+) -> Result<(), ServiceError> {
+    if let Some(price_list_id) = price_list_id {
+        if price_list_id == base_price_list_id {
+            return Err(ServiceError::Validation(
+                "Una lista no puede derivarse de sí misma".to_string(),
+            ));
+        }
+        let is_base: bool = conn
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM price_lists WHERE base_price_list_id = ?1)",
+                [price_list_id],
+                |row| row.get(0),
+            )
+            .map_err(db)?;
+        if is_base {
+            return Err(ServiceError::Validation(
+                "La lista es base de otras listas y no puede ser derivada".to_string(),
+            ));
+        }
+    }
+    let base: Option<(String, Option<String>)> = conn
+        .query_row(
+            "SELECT currency, base_price_list_id FROM price_lists WHERE id = ?1 AND tenant_id = ?2",
+            params![base_price_list_id, tenant_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(db)?;
+    let Some((base_currency, base_of_base)) = base else {
+        return Err(ServiceError::NotFound(
+            "Lista de precios base no encontrada".to_string(),
+        ));
+    };
+    if base_of_base.is_some() {
+        return Err(ServiceError::Validation(
+            "La lista base no puede ser a su vez derivada".to_string(),
+        ));
+    }
+    exchange_rates::conversion(conn, tenant_id, &base_currency, currency)?;
+    Ok(())
+}
+
+/// Recompute a derived list inside the caller's transaction. Returns the
+/// batch, or None if no price changed (or the list is not derived).
+pub(crate) fn derive_list_in(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: Option<&str>,
+    price_list_id: &str,
+    now: &str,
+) -> Result<Option<PriceBatch>, ServiceError> {
+    let Some(derivation) = derivation(conn, tenant_id, price_list_id)? else {
+        return Ok(None);
+    };
+    let factor = exchange_rates::conversion(
+        conn,
+        tenant_id,
+        &derivation.base_currency,
+        &derivation.currency,
+    )?;
+
+    // Base prices: the base list's own, else the product's with the list discount
+    let mut stmt = conn
+        .prepare(
+            "SELECT p.id, NULL, p.sku, p.name, COALESCE(p.cost_price, 0),
+                    COALESCE(bp.price, COALESCE(p.sale_price, p.unit_price) * (1 - ?3 / 100.0)),
+                    dp.id, dp.price
+             FROM products p
+             LEFT JOIN product_prices bp ON bp.product_id = p.id AND bp.variant_id IS NULL
+                  AND bp.price_list_id = ?2
+             LEFT JOIN product_prices dp ON dp.product_id = p.id AND dp.variant_id IS NULL
+                  AND dp.price_list_id = ?4
+             WHERE p.tenant_id = ?1 AND p.is_active = 1
+             UNION ALL
+             SELECT bp.product_id, bp.variant_id, COALESCE(v.sku, p.sku), p.name,
+                    COALESCE(p.cost_price, 0), bp.price, dp.id, dp.price
+             FROM product_prices bp
+             JOIN products p ON p.id = bp.product_id
+             LEFT JOIN product_variants v ON v.id = bp.variant_id
+             LEFT JOIN product_prices dp ON dp.product_id = bp.product_id
+                  AND dp.variant_id = bp.variant_id AND dp.price_list_id = ?4
+             WHERE bp.price_list_id = ?2 AND bp.variant_id IS NOT NULL AND p.is_active = 1",
+        )
+        .map_err(db)?;
+    let rows = stmt
+        .query_map(
+            params![
+                tenant_id,
+                derivation.base_price_list_id,
+                derivation.base_discount,
+                price_list_id
+            ],
+            |row| {
+                Ok((
+                    PriceChange {
+                        product_id: row.get(0)?,
+                        variant_id: row.get(1)?,
+                        sku: row.get(2)?,
+                        name: row.get(3)?,
+                        cost_price: row.get(4)?,
+                        price_list_id: Some(price_list_id.to_string()),
+                        old_price: row.get(7)?,
+                        new_price: row.get(5)?, // Base price until converted
+                    },
+                    row.get::<_, Option<String>>(6)?,
+                ))
+            },
+        )
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    let step = derivation.round_to.map(decimal);
+    let mode = derivation.round_mode.as_deref().unwrap_or("nearest");
+    let mut changes = Vec::new();
+    for (mut change, product_price_id) in rows {
+        let price = round_price(decimal(change.new_price) * factor, step, mode);
+        change.new_price = price.to_f64().unwrap_or(0.0);
+        if change
+            .old_price
+            .is_some_and(|old| (old - change.new_price).abs() < 1e-9)
+        {
+            continue;
+        }
+        changes.push((change, product_price_id));
+    }
+
+    let factor = factor.to_f64().unwrap_or(0.0);
+    conn.execute(
+        "UPDATE price_lists SET last_rate = ?1, repriced_at = ?2, updated_at = ?2 WHERE id = ?3",
+        params![factor, now, price_list_id],
+    )
+    .map_err(db)?;
+    if changes.is_empty() {
+        return Ok(None);
+    }
+
+    let batch = PriceBatch {
+        id: Uuid::new_v4().to_string(),
+        price_list_id: Some(price_list_id.to_string()),
+        operation: "exchange_rate".to_string(),
+        value: factor,
+        round_to: derivation.round_to,
+        round_mode: derivation.round_mode.clone(),
+        reason: format!(
+            "Tasa {}/{} {}",
+            derivation.base_currency, derivation.currency, factor
+        ),
+        items_count: changes.len() as i64,
+        status: "applied".to_string(),
+        created_by: user_id.map(str::to_string),
+        created_at: now.to_string(),
+        undone_by: None,
+        undone_at: None,
+    };
+    insert_batch(conn, tenant_id, &batch, None)?;
+    for (change, product_price_id) in &changes {
+        match product_price_id {
+            Some(product_price_id) => {
+                conn.execute(
+                    "UPDATE product_prices SET price = ?1, updated_at = ?2 WHERE id = ?3",
+                    params![change.new_price, now, product_price_id],
+                )
+                .map_err(db)?;
+            }
+            None => {
+                conn.execute(
+                    "INSERT INTO product_prices (id, price_list_id, product_id, variant_id, price,
+                         created_at, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+                    params![
+                        Uuid::new_v4().to_string(),
+                        price_list_id,
+                        change.product_id,
+                        change.variant_id,
+                        change.new_price,
+                        now
+                    ],
+                )
+                .map_err(db)?;
+            }
+        }
+        record_history(
+            conn,
+            tenant_id,
+            user_id,
+            change,
+            &batch.reason,
+            Some(&batch.id),
+            now,
+        )?;
+    }
+    Ok(Some(batch))
+}
+
+/// Recompute a derived list from its base list and the latest rates
+pub fn derive_list(
+    conn: &Connection,
+    tenant_id: &str,
+    user_id: Option<&str>,
+    price_list_id: &str,
+) -> Result<Option<PriceBatch>, ServiceError> {
+    let tx = conn.unchecked_transaction().map_err(db)?;
+    let batch = derive_list_in(
+        &tx,
+        tenant_id,
+        user_id,
+        price_list_id,
+        &Utc::now().to_rfc3339(),
+    )?;
+    tx.commit().map_err(db)?;
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::pricing::BulkPriceFilters;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO categories (id, tenant_id, name) VALUES ('food', 't1', 'Víveres');
+             INSERT INTO categories (id, tenant_id, parent_id, name) VALUES ('rice', 't1', 'food', 'Arroz');
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, cost_price, sale_price, category_id)
+                 VALUES ('p1', 't1', 'ARR-1', 'Arroz', 10.0, 8.0, 10.0, 'rice'),
+                        ('p2', 't1', 'HAR-1', 'Harina', 4.0, 3.0, 4.0, 'food'),
+                        ('p3', 't1', 'JAB-1', 'Jabón', 2.0, 1.0, 2.0, NULL);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn dto(operation: &str, value: f64) -> BulkRepriceDto {
+        BulkRepriceDto {
+            filters: BulkPriceFilters {
+                category_id: Some("food".to_string()),
+                ..Default::default()
+            },
+            operation: operation.to_string(),
+            value,
+            round_to: None,
+            round_mode: None,
+            reason: "Inflación semanal".to_string(),
+        }
+    }
+
+    fn sale_price(conn: &Connection, id: &str) -> f64 {
+        conn.query_row("SELECT sale_price FROM products WHERE id = ?1", [id], |r| {
+            r.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_apply_and_undo_batch() {
+        let conn = setup();
+
+        // 12.5% over the category tree, rounded up to 0.50
+        let mut data = dto("percentage", 12.5);
+        data.round_to = Some(0.5);
+        data.round_mode = Some("up".to_string());
+        let changes = preview(&conn, "t1", &data).unwrap();
+        let prices: Vec<_> = changes
+            .iter()
+            .map(|c| (c.sku.clone(), c.new_price))
+            .collect();
+        assert_eq!(
+            prices,
+            vec![
+                (Some("ARR-1".to_string()), 11.5),
+                (Some("HAR-1".to_string()), 4.5)
+            ]
+        );
+        assert_eq!(sale_price(&conn, "p1"), 10.0);
+
+        let batch = apply(&conn, "t1", "u1", &data).unwrap();
+        assert_eq!(batch.items_count, 2);
+        assert_eq!(sale_price(&conn, "p1"), 11.5);
+        assert_eq!(sale_price(&conn, "p3"), 2.0);
+
+        // Changed again since the batch: left alone on undo
+        conn.execute("UPDATE products SET sale_price = 5 WHERE id = 'p2'", [])
+            .unwrap();
+        let undone = undo(&conn, "t1", "u1", &batch.id).unwrap();
+        assert_eq!((undone.restored, undone.skipped), (1, 1));
+        assert_eq!(undone.batch.status, "partially_undone");
+        assert_eq!(sale_price(&conn, "p1"), 10.0);
+        assert_eq!(sale_price(&conn, "p2"), 5.0);
+        assert!(undo(&conn, "t1", "u1", &batch.id).is_err());
+
+        // Cost plus 50% on a price list's prices only
+        conn.execute_batch(
+            "INSERT INTO price_lists (id, tenant_id, name, currency, created_at, updated_at)
+                 VALUES ('l1', 't1', 'Mayor', 'USD', '2026-01-01', '2026-01-01');
+             INSERT INTO product_prices (id, price_list_id, product_id, price, created_at, updated_at)
+                 VALUES ('pp1', 'l1', 'p3', 1.2, '2026-01-01', '2026-01-01');",
+        )
+        .unwrap();
+        let mut data = dto("cost_plus", 50.0);
+        data.filters = BulkPriceFilters {
+            price_list_id: Some("l1".to_string()),
+            ..Default::default()
+        };
+        apply(&conn, "t1", "u1", &data).unwrap();
+        let list_price: f64 = conn
+            .query_row(
+                "SELECT price FROM product_prices WHERE id = 'pp1'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(list_price, 1.5);
+        assert_eq!(sale_price(&conn, "p3"), 2.0);
+    }
+}
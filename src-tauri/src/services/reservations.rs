@@ -0,0 +1,409 @@
+//! Stock Reservations
+//!
+//! Draft invoices and accepted quotes hold stock in their warehouse until the
+//! document is issued, cancelled or the hold expires. Available-to-promise is
+//! on hand minus active holds. `products.reserved_quantity` and
+//! `variant_stock.reserved_quantity` keep the active total; expired holds are
+//! swept by `release_expired` before availability is read.
+
+use crate::models::reservation::{ReservationFilters, StockReservation};
+use crate::state::ServiceError;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rusqlite::{params, Connection};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// How long a draft holds stock
+pub const DRAFT_HOLD_HOURS: i64 = 48;
+/// How long an accepted quote without a due date holds stock
+pub const QUOTE_HOLD_DAYS: i64 = 7;
+
+fn db(e: rusqlite::Error) -> ServiceError {
+    ServiceError::Database(e.to_string())
+}
+
+/// A product (or variant) quantity to hold or check
+pub struct ReservationLine<'a> {
+    pub product_id: &'a str,
+    pub variant_id: Option<&'a str>,
+    pub quantity: f64,
+}
+
+/// The document stock is held for
+pub struct Hold<'a> {
+    pub tenant_id: &'a str,
+    pub warehouse_id: &'a str,
+    pub reference_type: &'a str,
+    pub reference_id: &'a str,
+    pub expires_at: &'a str,
+    pub user_id: Option<&'a str>,
+}
+
+/// Expiry of a draft's hold
+pub fn draft_expiry(now: DateTime<Utc>) -> String {
+    (now + Duration::hours(DRAFT_HOLD_HOURS)).to_rfc3339()
+}
+
+/// Expiry of an accepted quote's hold: the end of its due date, or a week
+pub fn quote_expiry(now: DateTime<Utc>, due_date: Option<&str>) -> String {
+    due_date
+        .and_then(|d| NaiveDate::parse_from_str(d.get(..10)?, "%Y-%m-%d").ok())
+        .and_then(|d| d.and_hms_opt(23, 59, 59))
+        .map(|d| d.and_utc())
+        .filter(|d| *d > now)
+        .unwrap_or(now + Duration::days(QUOTE_HOLD_DAYS))
+        .to_rfc3339()
+}
+
+fn adjust_reserved(
+    conn: &Connection,
+    product_id: &str,
+    variant_id: Option<&str>,
+    quantity: f64,
+) -> Result<(), ServiceError> {
+    conn.execute(
+        "UPDATE products SET reserved_quantity = MAX(COALESCE(reserved_quantity, 0) + ?1, 0)
+         WHERE id = ?2",
+        params![quantity, product_id],
+    )
+    .map_err(db)?;
+    if let Some(variant_id) = variant_id {
+        conn.execute(
+            "UPDATE variant_stock SET reserved_quantity = MAX(COALESCE(reserved_quantity, 0) + ?1, 0)
+             WHERE variant_id = ?2",
+            params![quantity, variant_id],
+        )
+        .map_err(db)?;
+    }
+    Ok(())
+}
+
+/// Set the status of active holds matching `condition` and take them off the totals
+fn close_where(
+    conn: &Connection,
+    condition: &str,
+    args: &[&dyn rusqlite::ToSql],
+    status: &str,
+    now: &str,
+) -> Result<usize, ServiceError> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, product_id, variant_id, quantity FROM stock_reservations
+             WHERE status = 'active' AND {}",
+            condition
+        ))
+        .map_err(db)?;
+    let rows: Vec<(String, String, Option<String>, f64)> = stmt
+        .query_map(args, |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+
+    for (id, product_id, variant_id, quantity) in &rows {
+        adjust_reserved(conn, product_id, variant_id.as_deref(), -quantity)?;
+        conn.execute(
+            "UPDATE stock_reservations SET status = ?1, updated_at = ?2 WHERE id = ?3",
+            params![status, now, id],
+        )
+        .map_err(db)?;
+    }
+    Ok(rows.len())
+}
+
+/// Expire the branch's holds past their expiry
+pub fn release_expired(conn: &Connection, tenant_id: &str) -> Result<usize, ServiceError> {
+    let now = Utc::now().to_rfc3339();
+    close_where(
+        conn,
+        "tenant_id = ?1 AND expires_at <= ?2",
+        &[&tenant_id, &now],
+        "expired",
+        &now,
+    )
+}
+
+/// End the holds of a document; `status` is "consumed" when the stock left
+/// with it, "released" otherwise
+pub fn release(
+    conn: &Connection,
+    tenant_id: &str,
+    reference_type: &str,
+    reference_id: &str,
+    status: &str,
+) -> Result<usize, ServiceError> {
+    let now = Utc::now().to_rfc3339();
+    close_where(
+        conn,
+        "tenant_id = ?1 AND reference_type = ?2 AND reference_id = ?3",
+        &[&tenant_id, &reference_type, &reference_id],
+        status,
+        &now,
+    )
+}
+
+/// Whether a product's stock is tracked (services are not)
+fn stock_product(conn: &Connection, product_id: &str) -> Result<(bool, String), ServiceError> {
+    conn.query_row(
+        "SELECT COALESCE(pt.affects_stock, 1), p.name FROM products p
+         LEFT JOIN product_types pt ON pt.id = p.product_type_id
+         WHERE p.id = ?1",
+        [product_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .map_err(|_| ServiceError::NotFound("Producto no encontrado".to_string()))
+}
+
+/// Available-to-promise in a warehouse: on hand minus other documents' holds
+pub fn available(
+    conn: &Connection,
+    warehouse_id: &str,
+    product_id: &str,
+    variant_id: Option<&str>,
+    exclude: Option<(&str, &str)>,
+) -> Result<f64, ServiceError> {
+    let on_hand: f64 = conn
+        .query_row(
+            "SELECT COALESCE(SUM(quantity), 0) FROM warehouse_stock
+             WHERE warehouse_id = ?1 AND product_id = ?2 AND (?3 IS NULL OR variant_id = ?3)",
+            params![warehouse_id, product_id, variant_id],
+            |row| row.get(0),
+        )
+        .map_err(db)?;
+    let (exclude_type, exclude_id) = exclude.unzip();
+    let reserved: f64 = conn
+        .query_row(
+            "SELECT COALESCE(SUM(quantity), 0) FROM stock_reservations
+             WHERE warehouse_id = ?1 AND product_id = ?2 AND (?3 IS NULL OR variant_id = ?3)
+               AND status = 'active' AND expires_at > ?4
+               AND NOT (reference_type IS ?5 AND reference_id IS ?6)",
+            params![
+                warehouse_id,
+                product_id,
+                variant_id,
+                Utc::now().to_rfc3339(),
+                exclude_type,
+                exclude_id
+            ],
+            |row| row.get(0),
+        )
+        .map_err(db)?;
+    Ok(on_hand - reserved)
+}
+
+/// Fail if any line asks for more than is available in the warehouse.
+/// `exclude` is the document being edited, whose own holds do not count.
+pub fn check_available(
+    conn: &Connection,
+    warehouse_id: &str,
+    lines: &[ReservationLine],
+    exclude: Option<(&str, &str)>,
+) -> Result<(), ServiceError> {
+    let mut requested: BTreeMap<(&str, Option<&str>), f64> = BTreeMap::new();
+    for line in lines {
+        *requested
+            .entry((line.product_id, line.variant_id))
+            .or_default() += line.quantity;
+    }
+
+    for ((product_id, variant_id), quantity) in requested {
+        let (tracked, name) = stock_product(conn, product_id)?;
+        if !tracked {
+            continue;
+        }
+        let available = available(conn, warehouse_id, product_id, variant_id, exclude)?;
+        if quantity > available {
+            return Err(ServiceError::Validation(format!(
+                "Stock insuficiente para {}: disponible {}, solicitado {}",
+                name,
+                available.max(0.0),
+                quantity
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Hold stock for a document, replacing its previous holds
+pub fn reserve(
+    conn: &Connection,
+    hold: &Hold,
+    lines: &[ReservationLine],
+) -> Result<(), ServiceError> {
+    release(
+        conn,
+        hold.tenant_id,
+        hold.reference_type,
+        hold.reference_id,
+        "released",
+    )?;
+
+    let now = Utc::now().to_rfc3339();
+    for line in lines {
+        if line.quantity <= 0.0 || !stock_product(conn, line.product_id)?.0 {
+            continue;
+        }
+        conn.execute(
+            "INSERT INTO stock_reservations
+                (id, tenant_id, warehouse_id, product_id, variant_id, quantity, reference_type,
+                 reference_id, status, expires_at, created_by, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 'active', ?9, ?10, ?11, ?11)",
+            params![
+                Uuid::new_v4().to_string(),
+                hold.tenant_id,
+                hold.warehouse_id,
+                line.product_id,
+                line.variant_id,
+                line.quantity,
+                hold.reference_type,
+                hold.reference_id,
+                hold.expires_at,
+                hold.user_id,
+                now
+            ],
+        )
+        .map_err(db)?;
+        adjust_reserved(conn, line.product_id, line.variant_id, line.quantity)?;
+    }
+    Ok(())
+}
+
+/// Holds of the branch, newest first
+pub fn list(
+    conn: &Connection,
+    tenant_id: &str,
+    filters: &ReservationFilters,
+) -> Result<Vec<StockReservation>, ServiceError> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT r.id, r.warehouse_id, r.product_id, p.name, r.variant_id, r.quantity,
+                    r.reference_type, r.reference_id, r.status, r.expires_at, r.created_by,
+                    r.created_at
+             FROM stock_reservations r
+             JOIN products p ON p.id = r.product_id
+             WHERE r.tenant_id = ?1
+               AND (?2 IS NULL OR r.product_id = ?2)
+               AND (?3 IS NULL OR r.variant_id = ?3)
+               AND (?4 IS NULL OR r.reference_id = ?4)
+               AND (?5 IS NULL OR r.status = ?5)
+             ORDER BY r.created_at DESC",
+        )
+        .map_err(db)?;
+
+    let reservations = stmt
+        .query_map(
+            params![
+                tenant_id,
+                filters.product_id,
+                filters.variant_id,
+                filters.reference_id,
+                filters.status
+            ],
+            |row| {
+                Ok(StockReservation {
+                    id: row.get(0)?,
+                    warehouse_id: row.get(1)?,
+                    product_id: row.get(2)?,
+                    product_name: row.get(3)?,
+                    variant_id: row.get(4)?,
+                    quantity: row.get(5)?,
+                    reference_type: row.get(6)?,
+                    reference_id: row.get(7)?,
+                    status: row.get(8)?,
+                    expires_at: row.get(9)?,
+                    created_by: row.get(10)?,
+                    created_at: row.get(11)?,
+                })
+            },
+        )
+        .map_err(db)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db)?;
+    Ok(reservations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::inventory;
+
+    fn setup() -> (Connection, String) {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Centro');
+             INSERT INTO products (id, tenant_id, sku, name, unit_price, stock_quantity, is_active)
+                 VALUES ('p1', 't1', 'SKU-1', 'Arroz', 2.0, 0, 1);",
+        )
+        .unwrap();
+        let warehouse = inventory::default_warehouse(&conn, "t1").unwrap();
+        inventory::apply(
+            &conn,
+            &inventory::StockChange {
+                tenant_id: "t1",
+                warehouse_id: &warehouse,
+                product_id: "p1",
+                variant_id: None,
+                lot_id: None,
+                quantity: 10.0,
+            },
+            &Utc::now().to_rfc3339(),
+        )
+        .unwrap();
+        (conn, warehouse)
+    }
+
+    fn reserved(conn: &Connection) -> f64 {
+        conn.query_row(
+            "SELECT reserved_quantity FROM products WHERE id = 'p1'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_holds_reduce_available_until_released_or_expired() {
+        let (conn, warehouse) = setup();
+        let line = |quantity| ReservationLine {
+            product_id: "p1",
+            variant_id: None,
+            quantity,
+        };
+        let expires = draft_expiry(Utc::now());
+        let hold = |reference_id, expires_at| Hold {
+            tenant_id: "t1",
+            warehouse_id: &warehouse,
+            reference_type: "invoice",
+            reference_id,
+            expires_at,
+            user_id: None,
+        };
+
+        reserve(&conn, &hold("inv-1", &expires), &[line(6.0)]).unwrap();
+        assert_eq!(reserved(&conn), 6.0);
+        assert_eq!(available(&conn, &warehouse, "p1", None, None).unwrap(), 4.0);
+
+        // Another document cannot take the held stock; the holder itself can
+        assert!(check_available(&conn, &warehouse, &[line(5.0)], None).is_err());
+        check_available(&conn, &warehouse, &[line(10.0)], Some(("invoice", "inv-1"))).unwrap();
+
+        // Re-reserving replaces the previous hold
+        reserve(&conn, &hold("inv-1", &expires), &[line(3.0)]).unwrap();
+        assert_eq!(reserved(&conn), 3.0);
+
+        release(&conn, "t1", "invoice", "inv-1", "released").unwrap();
+        assert_eq!(reserved(&conn), 0.0);
+
+        let past = (Utc::now() - Duration::hours(1)).to_rfc3339();
+        reserve(&conn, &hold("inv-2", &past), &[line(2.0)]).unwrap();
+        assert_eq!(
+            available(&conn, &warehouse, "p1", None, None).unwrap(),
+            10.0
+        );
+        assert_eq!(release_expired(&conn, "t1").unwrap(), 1);
+        assert_eq!(reserved(&conn), 0.0);
+    }
+}
@@ -1,367 +1,661 @@
 //! Sync Service
 //!
-//! Handles synchronization between local SQLite and Supabase.
-
-use crate::models::client::Client;
-use crate::models::sync::{OrganizationSync, TenantSync, UserSync};
-use rusqlite::{params, Connection};
-use serde::{de::DeserializeOwned, Serialize};
+//! Handles synchronization between local SQLite and the configured
+//! `SyncBackend`. Entities are described in `sync_entities`; the engine here
+//! is table- and backend-agnostic.
+
+use crate::models::sync::{OrganizationSync, SyncStatus, TenantSync, UserSync};
+use crate::services::sync_backend::{self, SyncBackend};
+use crate::services::sync_conflicts::{self, Reconciled};
+use crate::services::sync_entities::{self, sync_order, EntityDescriptor, TenantScope};
+use crate::services::sync_outbox::{self, Operation, OutboxEntry, TOMBSTONES_TABLE};
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension};
 
 use std::sync::Mutex;
 
-#[derive(Clone)]
-pub struct SupabaseClient {
-    client: reqwest::Client,
-    url: String,
-    api_key: String,
-}
+/// Rows uploaded per request
+const UPLOAD_BATCH_SIZE: usize = 200;
 
-impl SupabaseClient {
-    pub fn new(url: String, api_key: String) -> Self {
-        Self {
-            client: reqwest::Client::new(),
-            url,
-            api_key,
-        }
-    }
+pub type Row = serde_json::Map<String, serde_json::Value>;
 
-    /// Upsert a batch of records
-    pub async fn upsert<T: Serialize>(&self, table: &str, data: &Vec<T>) -> Result<(), String> {
-        if data.is_empty() {
-            return Ok(());
-        }
+// --- Per-entity watermarks ---
 
-        let response = self
-            .client
-            .post(format!("{}/rest/v1/{}", self.url, table))
-            .header("apikey", &self.api_key)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .header("Prefer", "resolution=merge-duplicates")
-            .json(data)
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
+/// (last_pulled_at, last_pushed_at) for an entity
+fn get_watermarks(
+    conn: &Connection,
+    entity: &str,
+) -> Result<(Option<String>, Option<String>), String> {
+    conn.query_row(
+        "SELECT last_pulled_at, last_pushed_at FROM sync_watermarks WHERE entity = ?1",
+        [entity],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+    .map(|w| w.unwrap_or((None, None)))
+    .map_err(|e| e.to_string())
+}
 
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(format!("Supabase error ({}): {}", table, error_text));
-        }
+fn set_watermark(conn: &Connection, entity: &str, column: &str, value: &str) -> Result<(), String> {
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        &format!(
+            "INSERT INTO sync_watermarks (entity, {col}, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(entity) DO UPDATE SET {col} = excluded.{col}, updated_at = excluded.updated_at",
+            col = column
+        ),
+        params![entity, value, now],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-        Ok(())
+// --- Row conversion ---
+
+fn sql_to_json(value: ValueRef<'_>, is_bool: bool) -> serde_json::Value {
+    use serde_json::Value;
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) if is_bool => Value::Bool(i != 0),
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => serde_json::Number::from_f64(f)
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        ValueRef::Text(t) => Value::String(String::from_utf8_lossy(t).to_string()),
+        ValueRef::Blob(b) => Value::String(hex::encode(b)),
     }
+}
 
-    /// Get count of records modified after a timestamp
-    pub async fn count_modified(&self, table: &str, since: Option<String>) -> Result<i64, String> {
-        let mut query = format!("{}/rest/v1/{}?select=*", self.url, table);
+fn json_to_sql(value: &serde_json::Value) -> SqlValue {
+    use serde_json::Value;
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => n
+            .as_i64()
+            .map(SqlValue::Integer)
+            .or_else(|| n.as_f64().map(SqlValue::Real))
+            .unwrap_or(SqlValue::Null),
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
 
-        if let Some(timestamp) = since {
-            // URL encode the timestamp manually (specifically + to %2B) to avoid it being treated as space
-            let encoded_ts = timestamp.replace("+", "%2B");
-            query.push_str(&format!("&updated_at=gt.{}", encoded_ts));
-        }
+/// Read local rows for upload by primary key.
+///
+/// Parent-scoped rows carry the parent's `tenant_id` so the cloud copy can be
+/// filtered per tenant like any other table. Missing ids are skipped.
+pub fn read_upload_rows(
+    conn: &Connection,
+    entity: &EntityDescriptor,
+    ids: &[String],
+) -> Result<Vec<Row>, String> {
+    let columns = entity
+        .columns
+        .iter()
+        .map(|c| format!("e.{}", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let (select, from) = match entity.scope {
+        TenantScope::Column => (columns, format!("{} e", entity.table)),
+        TenantScope::Parent { table, foreign_key } => (
+            format!("{}, p.tenant_id", columns),
+            format!(
+                "{} e LEFT JOIN {} p ON p.id = e.{}",
+                entity.table, table, foreign_key
+            ),
+        ),
+    };
 
-        println!("DEBUG: Checking count for {} with query: {}", table, query);
+    let sql = format!(
+        "SELECT {select} FROM {from}
+         WHERE e.{pk} IN (SELECT value FROM json_each(?1))
+         ORDER BY e.{change}, e.{pk}",
+        change = entity.change_column,
+        pk = entity.primary_key,
+    );
 
-        let response = self
-            .client
-            .head(&query)
-            .header("apikey", &self.api_key)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Prefer", "count=exact")
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
-
-        let status = response.status();
-        if !status.is_success() {
-            return Err(format!(
-                "Supabase error checking count for {}: Status {}",
-                table, status
-            ));
-        }
+    let mut names: Vec<&str> = entity.columns.to_vec();
+    if matches!(entity.scope, TenantScope::Parent { .. }) {
+        names.push("tenant_id");
+    }
+
+    let ids_json = serde_json::to_string(ids).map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([ids_json], |row| {
+            let mut map = Row::new();
+            for (idx, name) in names.iter().enumerate() {
+                let is_bool = entity.booleans.contains(name);
+                map.insert(name.to_string(), sql_to_json(row.get_ref(idx)?, is_bool));
+            }
+            Ok(map)
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
 
-        let content_range = response
-            .headers()
-            .get("content-range")
-            .and_then(|h| h.to_str().ok())
-            .unwrap_or("0-0/0");
+    Ok(rows)
+}
 
-        // Parse "0-5/6" or "*/6" -> 6
-        let total = content_range.split('/').last().unwrap_or("0");
-        total.parse::<i64>().map_err(|e| e.to_string())
-    }
+/// Read one local row by primary key (descriptor columns only)
+pub fn read_row(
+    conn: &Connection,
+    entity: &EntityDescriptor,
+    id: &str,
+) -> Result<Option<Row>, String> {
+    let sql = format!(
+        "SELECT {} FROM {} WHERE {} = ?1",
+        entity.columns.join(", "),
+        entity.table,
+        entity.primary_key
+    );
 
-    /// Select records modified after a timestamp
-    pub async fn select_modified<T: DeserializeOwned>(
-        &self,
-        table: &str,
-        since: Option<String>,
-    ) -> Result<Vec<T>, String> {
-        let mut query = format!("{}/rest/v1/{}?select=*", self.url, table);
-
-        if let Some(timestamp) = since {
-            let encoded_ts = timestamp.replace("+", "%2B");
-            query.push_str(&format!("&updated_at=gt.{}", encoded_ts));
+    conn.query_row(&sql, [id], |row| {
+        let mut map = Row::new();
+        for (idx, name) in entity.columns.iter().enumerate() {
+            let is_bool = entity.booleans.contains(name);
+            map.insert(name.to_string(), sql_to_json(row.get_ref(idx)?, is_bool));
         }
+        Ok(map)
+    })
+    .optional()
+    .map_err(|e| e.to_string())
+}
 
-        println!("DEBUG: Selecting modified from {} uri: {}", table, query);
+/// Insert or update one row; only descriptor columns present in `row` are written
+pub fn upsert_row(conn: &Connection, entity: &EntityDescriptor, row: &Row) -> Result<(), String> {
+    let columns: Vec<&str> = entity
+        .columns
+        .iter()
+        .copied()
+        .filter(|c| row.contains_key(*c))
+        .collect();
+    if !columns.contains(&entity.primary_key) {
+        return Err(format!("Fila de {} sin clave primaria", entity.table));
+    }
 
-        let response = self
-            .client
-            .get(&query)
-            .header("apikey", &self.api_key)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .send()
-            .await
-            .map_err(|e| e.to_string())?;
+    let placeholders = (1..=columns.len())
+        .map(|i| format!("?{}", i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let updates = columns
+        .iter()
+        .filter(|c| **c != entity.primary_key)
+        .map(|c| format!("{c} = excluded.{c}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let sql = if updates.is_empty() {
+        format!(
+            "INSERT OR IGNORE INTO {} ({}) VALUES ({})",
+            entity.table,
+            columns.join(", "),
+            placeholders
+        )
+    } else {
+        format!(
+            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT({}) DO UPDATE SET {}",
+            entity.table,
+            columns.join(", "),
+            placeholders,
+            entity.primary_key,
+            updates
+        )
+    };
 
-        let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
-            println!("DEBUG: Select failed status: {} body: {}", status, body);
-            return Err(format!("Supabase error ({}): {}", table, body));
-        }
+    let values: Vec<SqlValue> = columns.iter().map(|c| json_to_sql(&row[*c])).collect();
+    conn.execute(&sql, rusqlite::params_from_iter(values))
+        .map_err(|e| format!("Error applying {}: {}", entity.table, e))?;
 
-        let body = response.text().await.map_err(|e| e.to_string())?;
-        // println!("DEBUG: Select response body: {}", body); // Too verbose?
+    Ok(())
+}
 
-        serde_json::from_str(&body).map_err(|e| {
-            println!("DEBUG: JSON Parse Error: {} Body: {}", e, body);
-            e.to_string()
-        })
-    }
+/// Result of applying a batch of remote rows
+#[derive(Debug, Default)]
+pub struct ApplyOutcome {
+    pub applied: usize,
+    pub conflicts: usize,
+    pub max_change: Option<String>, // Latest change timestamp seen
 }
 
-// --- Helpers for Metadata ---
-fn get_last_down_sync(conn: &Connection) -> Result<Option<String>, String> {
-    let result = conn.query_row(
-        "SELECT value FROM security_metadata WHERE key = 'last_down_sync'",
-        [],
-        |row| row.get(0),
-    );
+/// Apply remote rows to the local table.
+///
+/// Each row goes through conflict detection first; rows with unsynced local
+/// edits are settled by the entity's conflict policy.
+pub fn apply_remote_rows(
+    conn: &Connection,
+    entity: &EntityDescriptor,
+    rows: &[Row],
+) -> Result<ApplyOutcome, String> {
+    let mut outcome = ApplyOutcome::default();
+    let policy = sync_conflicts::effective_policy(conn, entity)?;
+    let (_, last_pushed) = get_watermarks(conn, entity.table)?;
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    sync_outbox::set_suppressed(&tx, true)?;
+    for row in rows {
+        if let Some(change) = row.get(entity.change_column).and_then(|v| v.as_str()) {
+            if outcome.max_change.as_deref().is_none_or(|m| change > m) {
+                outcome.max_change = Some(change.to_string());
+            }
+        }
 
-    match result {
-        Ok(val) => Ok(Some(val)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.to_string()),
+        match sync_conflicts::reconcile(&tx, entity, policy, row, last_pushed.as_deref())? {
+            Reconciled::Apply(data) => {
+                upsert_row(&tx, entity, &data)?;
+                outcome.applied += 1;
+            }
+            Reconciled::ApplyAndUpload(data) => {
+                upsert_row(&tx, entity, &data)?;
+                if let Some(id) = data.get(entity.primary_key).and_then(|v| v.as_str()) {
+                    sync_outbox::enqueue(&tx, entity, id)?;
+                }
+                outcome.applied += 1;
+            }
+            Reconciled::KeepLocal => {}
+            Reconciled::Conflict => outcome.conflicts += 1,
+        }
     }
-}
+    sync_outbox::set_suppressed(&tx, false)?;
+    tx.commit().map_err(|e| e.to_string())?;
 
-fn set_last_down_sync(conn: &Connection, timestamp: &str) -> Result<(), String> {
-    conn.execute(
-        "INSERT OR REPLACE INTO security_metadata (key, value) VALUES ('last_down_sync', ?1)",
-        [timestamp],
-    )
-    .map_err(|e| e.to_string())?;
-    Ok(())
+    Ok(outcome)
 }
 
 /// Check for pending updates without downloading
 pub async fn check_updates(
-    client: &SupabaseClient,
+    backend: &dyn SyncBackend,
     db: &Mutex<Connection>,
-    _tenant_id: &str,
+    tenant_id: &str,
 ) -> Result<i64, String> {
-    let last_sync = {
+    let mut total = 0;
+
+    for entity in sync_order() {
+        let (last_pulled, _) = {
+            let conn = db.lock().map_err(|e| e.to_string())?;
+            get_watermarks(&conn, entity.table)?
+        };
+
+        total += backend
+            .count_modified(
+                entity.table,
+                entity.change_column,
+                last_pulled.as_deref(),
+                Some(tenant_id),
+            )
+            .await?;
+    }
+
+    let last_pulled = {
         let conn = db.lock().map_err(|e| e.to_string())?;
-        get_last_down_sync(&conn)?
+        get_watermarks(&conn, TOMBSTONES_TABLE)?.0
     };
+    total += backend
+        .count_modified(
+            TOMBSTONES_TABLE,
+            "deleted_at",
+            last_pulled.as_deref(),
+            Some(tenant_id),
+        )
+        .await?;
+
+    // Cached for get_last_sync_status
+    {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE sync_status SET pending_downloads = ?1 WHERE id = 1",
+            [total],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    Ok(total)
+}
+
+/// Outbox entries uploaded together: one entity, one operation
+struct UploadGroup {
+    entity: &'static EntityDescriptor,
+    operation: Operation,
+    entry_ids: Vec<i64>,
+    row_ids: Vec<String>,
+    rows: Vec<Row>, // Current rows for upserts, tombstones for deletes
+}
 
-    println!("DEBUG: Last down sync timestamp: {:?}", last_sync);
+/// Turn due outbox entries into upload groups: upserts parents first, deletes
+/// children first. Entries with nothing left to send are completed here.
+fn prepare_upload(conn: &Connection, tenant_id: &str) -> Result<(Vec<UploadGroup>, usize), String> {
+    let entries = sync_outbox::due_entries(conn, tenant_id, UPLOAD_BATCH_SIZE)?;
+    let due = entries.len();
+    let order = sync_order();
+
+    let mut obsolete: Vec<i64> = entries
+        .iter()
+        .filter(|e| sync_entities::find(&e.entity).is_none())
+        .map(|e| e.id)
+        .collect();
+    let mut groups = Vec::new();
+
+    for entity in &order {
+        let queued: Vec<&OutboxEntry> = entries
+            .iter()
+            .filter(|e| e.entity == entity.table && e.operation == Operation::Upsert)
+            .collect();
+        if queued.is_empty() {
+            continue;
+        }
+
+        // Rows under review are dropped; resolving them queues the row again
+        let held = sync_conflicts::pending_row_ids(conn, entity.table)?;
+        let ids: Vec<String> = queued
+            .iter()
+            .filter(|e| !held.contains(&e.row_id))
+            .map(|e| e.row_id.clone())
+            .collect();
+        let rows = read_upload_rows(conn, entity, &ids)?;
+
+        let mut group = UploadGroup {
+            entity,
+            operation: Operation::Upsert,
+            entry_ids: Vec::new(),
+            row_ids: Vec::new(),
+            rows,
+        };
+        for entry in queued {
+            let found = group
+                .rows
+                .iter()
+                .any(|r| r.get(entity.primary_key).and_then(|v| v.as_str()) == Some(&entry.row_id));
+            if found {
+                group.entry_ids.push(entry.id);
+                group.row_ids.push(entry.row_id.clone());
+            } else {
+                obsolete.push(entry.id);
+            }
+        }
+        if !group.rows.is_empty() {
+            groups.push(group);
+        }
+    }
+
+    for entity in order.iter().rev() {
+        let queued: Vec<&OutboxEntry> = entries
+            .iter()
+            .filter(|e| e.entity == entity.table && e.operation == Operation::Delete)
+            .collect();
+        if queued.is_empty() {
+            continue;
+        }
+
+        groups.push(UploadGroup {
+            entity,
+            operation: Operation::Delete,
+            entry_ids: queued.iter().map(|e| e.id).collect(),
+            row_ids: queued.iter().map(|e| e.row_id.clone()).collect(),
+            rows: queued
+                .iter()
+                .map(|e| sync_outbox::tombstone_row(e))
+                .collect(),
+        });
+    }
+
+    sync_outbox::complete(conn, &obsolete)?;
+    Ok((groups, due))
+}
+
+/// Record an uploaded group as the cloud state
+fn finish_upload(conn: &Connection, group: &UploadGroup) -> Result<(), String> {
+    sync_outbox::complete(conn, &group.entry_ids)?;
+
+    match group.operation {
+        Operation::Upsert => {
+            sync_conflicts::save_base_rows(conn, group.entity, &group.rows)?;
+            let latest = group
+                .rows
+                .iter()
+                .filter_map(|r| r.get(group.entity.change_column).and_then(|v| v.as_str()))
+                .max();
+            let (_, last_pushed) = get_watermarks(conn, group.entity.table)?;
+            if let Some(latest) = latest {
+                if last_pushed.as_deref().is_none_or(|p| latest > p) {
+                    set_watermark(conn, group.entity.table, "last_pushed_at", latest)?;
+                }
+            }
+        }
+        Operation::Delete => {
+            sync_conflicts::forget_base_rows(conn, group.entity.table, &group.row_ids)?;
+        }
+    }
 
-    // Check clients (expand to other tables later)
-    let count = client.count_modified("clients", last_sync).await?;
-    println!("DEBUG: Check updates count: {}", count);
-    Ok(count)
+    Ok(())
 }
 
 /// Sync local data to Cloud (Upload)
+///
+/// Drains the outbox in batches. A failed group is rescheduled with backoff
+/// and the run stops after the current batch.
 pub async fn sync_to_cloud(
-    client: &SupabaseClient,
+    backend: &dyn SyncBackend,
     db: &Mutex<Connection>,
     tenant_id: &str,
 ) -> Result<i32, String> {
     let mut total_uploaded = 0;
-    let mut clients_batch = Vec::new();
-
-    // 1. Sync Clients - Fetch (Lock Scope)
-    {
-        let conn = db.lock().map_err(|e| e.to_string())?;
-        let mut stmt = conn
-            .prepare(
-                "SELECT id, tenant_id, code, name, tax_id, tax_type, email, phone, address, city, state, notes, is_active, created_at, updated_at 
-             FROM clients 
-             WHERE tenant_id = ? 
-             AND (synced_at IS NULL OR updated_at > synced_at)
-             LIMIT 50", // Batch size
-            )
-            .map_err(|e| e.to_string())?;
+    let mut first_error: Option<String> = None;
 
-        let rows = stmt
-            .query_map([tenant_id], |row| {
-                Ok(Client {
-                    id: row.get(0)?,
-                    tenant_id: row.get(1)?,
-                    code: row.get(2)?,
-                    name: row.get(3)?,
-                    tax_id: row.get(4)?,
-                    tax_type: row.get(5)?,
-                    email: row.get(6)?,
-                    phone: row.get(7)?,
-                    address: row.get(8)?,
-                    city: row.get(9)?,
-                    state: row.get(10)?,
-                    notes: row.get(11)?,
-                    is_active: row.get(12)?,
-                    created_at: row.get(13)?,
-                    updated_at: row.get(14)?,
-                })
-            })
-            .map_err(|e| e.to_string())?;
+    loop {
+        // Prepare (Lock Scope)
+        let (groups, due) = {
+            let conn = db.lock().map_err(|e| e.to_string())?;
+            prepare_upload(&conn, tenant_id)?
+        }; // Lock released
 
-        for client_result in rows {
-            if let Ok(c) = client_result {
-                clients_batch.push(c);
-            }
+        if due == 0 {
+            break;
         }
-    } // Lock released
 
-    if !clients_batch.is_empty() {
-        // Upload (Async, No Lock)
-        client.upsert("clients", &clients_batch).await?;
+        for group in &groups {
+            // Upload (Async, No Lock)
+            let result = match group.operation {
+                Operation::Upsert => {
+                    backend
+                        .upsert(group.entity.table, group.entity.primary_key, &group.rows)
+                        .await
+                }
+                Operation::Delete => {
+                    match backend.upsert(TOMBSTONES_TABLE, "id", &group.rows).await {
+                        Ok(()) => {
+                            backend
+                                .delete(
+                                    group.entity.table,
+                                    group.entity.primary_key,
+                                    &group.row_ids,
+                                )
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+            };
 
-        // Update SyncedAt (Lock Scope)
-        {
+            // Record outcome (Lock Scope)
             let conn = db.lock().map_err(|e| e.to_string())?;
-            let mut stmt = conn
-                .prepare("UPDATE clients SET synced_at = CURRENT_TIMESTAMP WHERE id = ?")
-                .map_err(|e| e.to_string())?;
-
-            for c in &clients_batch {
-                stmt.execute([&c.id]).map_err(|e| e.to_string())?;
+            match result {
+                Ok(()) => {
+                    finish_upload(&conn, group)?;
+                    total_uploaded += group.rows.len() as i32;
+                }
+                Err(e) => {
+                    sync_outbox::fail(&conn, &group.entry_ids, &e)?;
+                    first_error.get_or_insert(e);
+                }
             }
         }
 
-        total_uploaded += clients_batch.len() as i32;
+        if first_error.is_some() || due < UPLOAD_BATCH_SIZE {
+            break;
+        }
     }
 
-    Ok(total_uploaded)
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(total_uploaded),
+    }
 }
 
 /// Sync Cloud data to Local (Download)
 pub async fn sync_from_cloud(
-    client: &SupabaseClient,
+    backend: &dyn SyncBackend,
     db: &Mutex<Connection>,
-    _tenant_id: &str,
+    tenant_id: &str,
 ) -> Result<i32, String> {
     let mut total_downloaded = 0;
 
-    // 1. Sync Clients - Get Last Down Sync (Lock Scope)
-    let last_sync: Option<String> = {
-        let conn = db.lock().map_err(|e| e.to_string())?;
-        get_last_down_sync(&conn)?
-    }; // Lock released
-
-    println!(
-        "DEBUG: SyncFromCloud (clients) - last_sync: {:?}",
-        last_sync
-    );
-
-    // Fetch (Async, No Lock)
-    let remote_clients: Vec<Client> = client.select_modified("clients", last_sync).await?;
-    println!(
-        "DEBUG: SyncFromCloud (clients) - found {} items",
-        remote_clients.len()
-    );
+    for entity in sync_order() {
+        // Get watermark (Lock Scope)
+        let (last_pulled, _) = {
+            let conn = db.lock().map_err(|e| e.to_string())?;
+            get_watermarks(&conn, entity.table)?
+        };
+
+        // Fetch (Async, No Lock)
+        let remote = backend
+            .select_modified(
+                entity.table,
+                entity.change_column,
+                last_pulled.as_deref(),
+                Some(tenant_id),
+            )
+            .await?;
 
-    if !remote_clients.is_empty() {
-        let mut max_updated_at = None;
+        if remote.is_empty() {
+            continue;
+        }
 
         // Apply Updates (Lock Scope)
         {
             let conn = db.lock().map_err(|e| e.to_string())?;
-            let mut stmt = conn.prepare(
-                "INSERT INTO clients (
-                    id, tenant_id, code, name, tax_id, tax_type, email, phone, address, city, state, notes, is_active, created_at, updated_at, synced_at
-                ) VALUES (
-                    ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, CURRENT_TIMESTAMP
-                )
-                ON CONFLICT(id) DO UPDATE SET
-                    code=excluded.code,
-                    name=excluded.name,
-                    tax_id=excluded.tax_id,
-                    tax_type=excluded.tax_type,
-                    email=excluded.email,
-                    phone=excluded.phone,
-                    address=excluded.address,
-                    city=excluded.city,
-                    state=excluded.state,
-                    notes=excluded.notes,
-                    is_active=excluded.is_active,
-                    updated_at=excluded.updated_at,
-                    synced_at=CURRENT_TIMESTAMP"
-            ).map_err(|e| e.to_string())?;
-
-            for c in &remote_clients {
-                println!(
-                    "DEBUG: Upserting client: {} (updated_at: {})",
-                    c.id, c.updated_at
-                );
-                // Determine max updated_at
-                // c.updated_at is Option<String> or String? Model says Option<String> usually but let's check.
-                // Assuming c.updated_at is String (RFC3339)
-                let ua = &c.updated_at;
-                match max_updated_at {
-                    None => max_updated_at = Some(ua.clone()),
-                    Some(ref max) => {
-                        if ua > max {
-                            max_updated_at = Some(ua.clone())
-                        }
-                    }
-                }
-
-                stmt.execute(params![
-                    c.id,
-                    c.tenant_id,
-                    c.code,
-                    c.name,
-                    c.tax_id,
-                    c.tax_type,
-                    c.email,
-                    c.phone,
-                    c.address,
-                    c.city,
-                    c.state,
-                    c.notes,
-                    c.is_active,
-                    c.created_at,
-                    c.updated_at
-                ])
-                .map_err(|e| e.to_string())?;
+            let outcome = apply_remote_rows(&conn, entity, &remote)?;
+            if let Some(timestamp) = outcome.max_change {
+                set_watermark(&conn, entity.table, "last_pulled_at", &timestamp)?;
             }
+            total_downloaded += outcome.applied as i32;
+        }
+    }
 
-            // Update last_down_sync
-            if let Some(timestamp) = max_updated_at {
-                println!("DEBUG: Updating last_down_sync watermark to: {}", timestamp);
-                set_last_down_sync(&conn, &timestamp)?;
-            }
+    // Deletions made on other devices
+    let last_pulled = {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        get_watermarks(&conn, TOMBSTONES_TABLE)?.0
+    };
+    let tombstones = backend
+        .select_modified(
+            TOMBSTONES_TABLE,
+            "deleted_at",
+            last_pulled.as_deref(),
+            Some(tenant_id),
+        )
+        .await?;
+    if !tombstones.is_empty() {
+        let conn = db.lock().map_err(|e| e.to_string())?;
+        let (deleted, latest) = sync_outbox::apply_tombstones(&conn, &tombstones)?;
+        if let Some(timestamp) = latest {
+            set_watermark(&conn, TOMBSTONES_TABLE, "last_pulled_at", &timestamp)?;
         }
-        total_downloaded += remote_clients.len() as i32;
+        total_downloaded += deleted as i32;
     }
 
     Ok(total_downloaded)
 }
 
-/// Sync installation data to Supabase (Organizations, Tenants, Users)
+/// Download then upload, recording the outcome for `get_status`.
+///
+/// Remote changes are applied first so they are checked against unsynced
+/// local edits before those are uploaded. Returns (downloaded, uploaded).
+pub async fn run_sync(
+    backend: &dyn SyncBackend,
+    db: &Mutex<Connection>,
+    tenant_id: &str,
+) -> Result<(i32, i32), String> {
+    let result = async {
+        let downloaded = sync_from_cloud(backend, db, tenant_id)
+            .await
+            .map_err(|e| format!("Download failed: {}", e))?;
+        let uploaded = sync_to_cloud(backend, db, tenant_id)
+            .await
+            .map_err(|e| format!("Upload failed: {}", e))?;
+        Ok::<_, String>((downloaded, uploaded))
+    }
+    .await;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let conn = db.lock().map_err(|e| e.to_string())?;
+    match &result {
+        Ok(_) => conn.execute(
+            "UPDATE sync_status
+             SET last_success_at = ?1, last_attempt_at = ?1, pending_downloads = 0
+             WHERE id = 1",
+            [&now],
+        ),
+        Err(e) => conn.execute(
+            "UPDATE sync_status
+             SET last_attempt_at = ?1, last_error = ?2, last_error_at = ?1
+             WHERE id = 1",
+            params![now, e],
+        ),
+    }
+    .map_err(|e| e.to_string())?;
+
+    result
+}
+
+/// Current sync status of a tenant
+pub fn get_status(
+    conn: &Connection,
+    tenant_id: &str,
+    is_syncing: bool,
+) -> Result<SyncStatus, String> {
+    let parse = |value: Option<String>| {
+        value
+            .and_then(|v| chrono::DateTime::parse_from_rfc3339(&v).ok())
+            .map(|d| d.with_timezone(&chrono::Utc))
+    };
+
+    let (last_success, last_error, last_error_at, pending_downloads): (
+        Option<String>,
+        Option<String>,
+        Option<String>,
+        i64,
+    ) = conn
+        .query_row(
+            "SELECT last_success_at, last_error, last_error_at, pending_downloads
+             FROM sync_status WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok(SyncStatus {
+        last_sync: parse(last_success),
+        pending_uploads: sync_outbox::count_pending(conn, tenant_id)? as u32,
+        pending_downloads: pending_downloads as u32,
+        failed_uploads: sync_outbox::count_poisoned(conn, tenant_id)? as u32,
+        pending_conflicts: sync_conflicts::count_pending(conn)? as u32,
+        is_syncing,
+        last_error,
+        last_error_at: parse(last_error_at),
+    })
+}
+
+/// Sync installation data to the backend (Organizations, Tenants, Users)
 /// This is called after setup_initial_admin to register the installation in the central database
 /// Returns Ok(()) on success, Err(msg) on failure (non-blocking - local installation continues)
 pub async fn sync_installation_to_cloud(
-    client: &SupabaseClient,
+    backend: &dyn SyncBackend,
     db: &Mutex<Connection>,
     tenant_id: &str,
 ) -> Result<(), String> {
-    println!("🔄 Starting installation sync to Supabase...");
+    println!("🔄 Starting installation sync to {}...", backend.name());
 
     // Fetch data from local DB (Lock Scope)
     let (org_data, tenant_data, user_data) = {
@@ -436,32 +730,161 @@ pub async fn sync_installation_to_cloud(
         (org, tenant, users)
     }; // Lock released
 
-    // Upload to Supabase (Async, No Lock)
+    // Upload to the backend (Async, No Lock)
     // Order matters: org → tenant → users (foreign keys)
 
     // 1. Upload Organization
     println!("📤 Syncing organization: {}", org_data.name);
-    client
-        .upsert("organizations", &vec![org_data])
+    backend
+        .upsert("organizations", "id", &sync_backend::to_rows(&[org_data])?)
         .await
         .map_err(|e| format!("Failed to sync organization: {}", e))?;
 
     // 2. Upload Tenant
     println!("📤 Syncing tenant: {}", tenant_data.name);
-    client
-        .upsert("tenants", &vec![tenant_data])
+    backend
+        .upsert("tenants", "id", &sync_backend::to_rows(&[tenant_data])?)
         .await
         .map_err(|e| format!("Failed to sync tenant: {}", e))?;
 
     // 3. Upload Users
     println!("📤 Syncing {} user(s)", user_data.len());
     if !user_data.is_empty() {
-        client
-            .upsert("users", &user_data)
+        backend
+            .upsert("users", "id", &sync_backend::to_rows(&user_data)?)
             .await
             .map_err(|e| format!("Failed to sync users: {}", e))?;
     }
 
-    println!("✅ Installation registered in Supabase successfully");
+    println!("✅ Installation registered successfully");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::sync_backend::MockBackend;
+    use crate::services::sync_entities::find;
+
+    fn migrated() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        sync_outbox::apply_sync_triggers(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Sede 1'), ('t2', 'o1', 'Sede 2');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_rows_roundtrip_between_databases() {
+        let source = migrated();
+        let target = migrated();
+        let categories = find("categories").unwrap();
+
+        source
+            .execute(
+                "INSERT INTO categories (id, tenant_id, name, is_active, updated_at)
+                 VALUES ('c1', 't1', 'Bebidas', 0, '2026-01-01T00:00:00+00:00'),
+                        ('c2', 't2', 'Otro tenant', 1, '2026-01-02T00:00:00+00:00')",
+                [],
+            )
+            .unwrap();
+
+        // Only the tenant's row is queued
+        let entries = sync_outbox::due_entries(&source, "t1", 50).unwrap();
+        assert_eq!(entries.len(), 1);
+
+        let rows = read_upload_rows(&source, categories, &[entries[0].row_id.clone()]).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["is_active"], serde_json::Value::Bool(false));
+
+        let outcome = apply_remote_rows(&target, categories, &rows).unwrap();
+        assert_eq!(outcome.applied, 1);
+        assert_eq!(
+            outcome.max_change.as_deref(),
+            Some("2026-01-01T00:00:00+00:00")
+        );
+        // Applied rows are not queued back for upload
+        assert_eq!(sync_outbox::count_pending(&target, "t1").unwrap(), 0);
+
+        // Applying twice updates in place
+        apply_remote_rows(&target, categories, &rows).unwrap();
+        let (name, active): (String, i64) = target
+            .query_row(
+                "SELECT name, is_active FROM categories WHERE id = 'c1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(name, "Bebidas");
+        assert_eq!(active, 0);
+    }
+
+    fn category_names(db: &Mutex<Connection>) -> Vec<String> {
+        let conn = db.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT name FROM categories ORDER BY id")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<String>, _>>()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_two_devices_sync_through_backend() {
+        let backend = MockBackend::default();
+        let device_a = Mutex::new(migrated());
+        let device_b = Mutex::new(migrated());
+
+        device_a
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO categories (id, tenant_id, name, updated_at)
+                 VALUES ('c1', 't1', 'Bebidas', '2026-01-01T00:00:00+00:00')",
+                [],
+            )
+            .unwrap();
+
+        assert_eq!(run_sync(&backend, &device_a, "t1").await.unwrap(), (0, 1));
+        assert_eq!(backend.rows("categories").len(), 1);
+
+        assert_eq!(check_updates(&backend, &device_b, "t1").await.unwrap(), 1);
+        assert_eq!(run_sync(&backend, &device_b, "t1").await.unwrap(), (1, 0));
+        assert_eq!(category_names(&device_b), vec!["Bebidas"]);
+
+        // Deleting on one device removes the row everywhere
+        device_b
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM categories WHERE id = 'c1'", [])
+            .unwrap();
+        run_sync(&backend, &device_b, "t1").await.unwrap();
+        assert!(backend.rows("categories").is_empty());
+        assert_eq!(backend.rows(TOMBSTONES_TABLE).len(), 1);
+
+        run_sync(&backend, &device_a, "t1").await.unwrap();
+        assert!(category_names(&device_a).is_empty());
+
+        // Offline: the change stays queued for the next run
+        backend.set_offline(true);
+        device_a
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO categories (id, tenant_id, name, updated_at)
+                 VALUES ('c2', 't1', 'Snacks', '2026-01-03T00:00:00+00:00')",
+                [],
+            )
+            .unwrap();
+        assert!(run_sync(&backend, &device_a, "t1").await.is_err());
+        assert_eq!(
+            sync_outbox::count_pending(&device_a.lock().unwrap(), "t1").unwrap(),
+            1
+        );
+    }
+}
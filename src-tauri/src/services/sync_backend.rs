@@ -0,0 +1,1436 @@
+//! Sync Backends
+//!
+//! The sync engine talks to a `SyncBackend` instead of a concrete cloud:
+//! a Supabase/PostgREST project, or the self-hosted `equinox-sync-server`
+//! for customers who cannot use a public cloud.
+//!
+//! Which backend is used, its URL and its credential are admin settings
+//! stored in `security_metadata`; the credential is additionally sealed with
+//! the hardware key-encryption key so it never leaves this machine in clear.
+//!
+//! That credential (Supabase anon key, server enrollment token) only
+//! registers the device. Every installation then syncs with its own
+//! short-lived access token, obtained with a refresh token the backend can
+//! revoke when a device is lost.
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+use crate::models::sync::{
+    DeviceCredentials, DeviceRegistration, SyncBackendKind, SyncBackendSettings, SyncDevice,
+    UpdateSyncBackendDto,
+};
+use crate::security::audit::{self, AuditEventType};
+use crate::services::sync::Row;
+
+const SETTINGS_KEY: &str = "sync_backend_settings";
+const SECRET_KEY: &str = "sync_backend_secret";
+const DEVICE_KEY: &str = "sync_device";
+const ENROLLMENT_KEY: &str = "sync_enrollment_token";
+const PING_TIMEOUT_SECS: u64 = 10;
+/// Access tokens are renewed this long before they expire
+const TOKEN_MARGIN_SECS: i64 = 60;
+
+const NOT_CONFIGURED: &str = "La sincronización no está configurada";
+const NOT_REGISTERED: &str = "Este equipo no está registrado en el servidor de sincronización";
+
+/// Used until an admin configures a backend (set at build time, never in source)
+const DEFAULT_SUPABASE_URL: Option<&str> = option_env!("EQUINOX_SUPABASE_URL");
+const DEFAULT_SUPABASE_KEY: Option<&str> = option_env!("EQUINOX_SUPABASE_ANON_KEY");
+
+/// Remote store the sync engine uploads to and downloads from
+#[async_trait]
+pub trait SyncBackend: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &'static str;
+
+    /// Check that the backend is reachable
+    async fn ping(&self) -> Result<(), String>;
+
+    /// Insert or merge rows by `key`
+    async fn upsert(&self, table: &str, key: &str, rows: &[Row]) -> Result<(), String>;
+
+    /// Delete rows by key
+    async fn delete(&self, table: &str, key: &str, ids: &[String]) -> Result<(), String>;
+
+    /// Count rows changed after `since`
+    async fn count_modified(
+        &self,
+        table: &str,
+        change_column: &str,
+        since: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> Result<i64, String>;
+
+    /// Rows changed after `since`, oldest change first
+    async fn select_modified(
+        &self,
+        table: &str,
+        change_column: &str,
+        since: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> Result<Vec<Row>, String>;
+
+    /// Device whose tokens are used for the calls above
+    fn device_id(&self) -> Option<String>;
+
+    fn set_device(&self, device: Option<DeviceKey>);
+
+    /// Register this installation with the configured credential
+    async fn register_device(
+        &self,
+        registration: &DeviceRegistration,
+    ) -> Result<DeviceCredentials, String>;
+
+    /// Devices registered for the tenant of this device
+    async fn list_devices(&self) -> Result<Vec<SyncDevice>, String>;
+
+    /// Invalidate a device's refresh token; its access tokens stop working
+    async fn revoke_device(&self, device_id: &str) -> Result<(), String>;
+
+    /// One-time code that lets another installation join this organization,
+    /// limited to one branch when `tenant_id` is given
+    async fn create_enrollment_token(&self, _tenant_id: Option<&str>) -> Result<String, String> {
+        Err("El servidor propio inscribe equipos con su token de inscripción".to_string())
+    }
+}
+
+/// Identity of a registered device
+pub struct DeviceKey {
+    pub device_id: String,
+    pub refresh_token: Zeroizing<String>,
+}
+
+/// Device tokens held by a backend client
+#[derive(Default)]
+struct DeviceAuth {
+    device: RwLock<Option<DeviceKey>>,
+    access: RwLock<Option<(Zeroizing<String>, DateTime<Utc>)>>,
+}
+
+impl DeviceAuth {
+    fn set(&self, device: Option<DeviceKey>) {
+        if let Ok(mut current) = self.device.write() {
+            *current = device;
+        }
+        if let Ok(mut access) = self.access.write() {
+            *access = None;
+        }
+    }
+
+    fn device_id(&self) -> Option<String> {
+        self.device
+            .read()
+            .ok()
+            .and_then(|d| d.as_ref().map(|d| d.device_id.clone()))
+    }
+
+    fn cached(&self) -> Option<String> {
+        let margin = chrono::Duration::seconds(TOKEN_MARGIN_SECS);
+        self.access
+            .read()
+            .ok()?
+            .as_ref()
+            .filter(|(_, expires_at)| *expires_at - margin > Utc::now())
+            .map(|(token, _)| token.to_string())
+    }
+
+    /// Current access token, renewed through `refresh` when it is about to expire
+    async fn bearer<F, Fut>(&self, refresh: F) -> Result<String, String>
+    where
+        F: FnOnce(Zeroizing<String>) -> Fut + Send,
+        Fut: Future<Output = Result<DeviceCredentials, String>> + Send,
+    {
+        if let Some(token) = self.cached() {
+            return Ok(token);
+        }
+
+        let refresh_token = self
+            .device
+            .read()
+            .ok()
+            .and_then(|d| d.as_ref().map(|d| d.refresh_token.clone()))
+            .ok_or(NOT_REGISTERED)?;
+        let credentials = refresh(refresh_token).await?;
+
+        let expires_at = DateTime::parse_from_rfc3339(&credentials.expires_at)
+            .map(|d| d.with_timezone(&Utc))
+            .map_err(|e| format!("Expiración de token inválida: {}", e))?;
+        if let Ok(mut access) = self.access.write() {
+            *access = Some((Zeroizing::new(credentials.access_token.clone()), expires_at));
+        }
+        Ok(credentials.access_token)
+    }
+}
+
+/// Serialize typed records into rows for `SyncBackend::upsert`
+pub fn to_rows<T: Serialize>(records: &[T]) -> Result<Vec<Row>, String> {
+    records
+        .iter()
+        .map(|record| match serde_json::to_value(record) {
+            Ok(serde_json::Value::Object(row)) => Ok(row),
+            Ok(_) => Err("Registro no serializable como fila".to_string()),
+            Err(e) => Err(e.to_string()),
+        })
+        .collect()
+}
+
+async fn error_body(response: reqwest::Response) -> String {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    if body.is_empty() {
+        format!("Status {}", status)
+    } else {
+        body
+    }
+}
+
+// --- Supabase / PostgREST ---
+
+/// Supabase project; the anon key only reaches the device RPCs in
+/// `supabase_schema.sql`, table access needs a device token
+pub struct SupabaseClient {
+    client: reqwest::Client,
+    url: String,
+    api_key: String,
+    auth: DeviceAuth,
+}
+
+impl SupabaseClient {
+    pub fn new(url: String, api_key: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+            api_key,
+            auth: DeviceAuth::default(),
+        }
+    }
+
+    /// Call a Postgres function through PostgREST
+    async fn rpc(
+        &self,
+        function: &str,
+        bearer: &str,
+        body: &serde_json::Value,
+    ) -> Result<String, String> {
+        let response = self
+            .client
+            .post(format!("{}/rest/v1/rpc/{}", self.url, function))
+            .header("apikey", &self.api_key)
+            .header("Authorization", format!("Bearer {}", bearer))
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Supabase error ({}): {}",
+                function,
+                error_body(response).await
+            ));
+        }
+
+        response.text().await.map_err(|e| e.to_string())
+    }
+
+    async fn bearer(&self) -> Result<String, String> {
+        self.auth
+            .bearer(|refresh_token| async move {
+                let body = serde_json::json!({ "p_refresh_token": refresh_token.as_str() });
+                let response = self
+                    .rpc("refresh_device_token", &self.api_key, &body)
+                    .await?;
+                serde_json::from_str(&response).map_err(|e| e.to_string())
+            })
+            .await
+    }
+
+    /// Build a PostgREST query for rows changed after a timestamp
+    fn modified_query(
+        &self,
+        table: &str,
+        change_column: &str,
+        since: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> String {
+        let mut query = format!("{}/rest/v1/{}?select=*", self.url, table);
+
+        if let Some(timestamp) = since {
+            // URL encode the timestamp manually (specifically + to %2B) to avoid it being treated as space
+            let encoded_ts = timestamp.replace("+", "%2B");
+            query.push_str(&format!("&{}=gt.{}", change_column, encoded_ts));
+        }
+        if let Some(tenant) = tenant_id {
+            query.push_str(&format!("&tenant_id=eq.{}", tenant));
+        }
+        query.push_str(&format!("&order={}.asc", change_column));
+
+        query
+    }
+}
+
+#[async_trait]
+impl SyncBackend for SupabaseClient {
+    fn name(&self) -> &'static str {
+        "supabase"
+    }
+
+    /// Any HTTP response counts as reachable
+    async fn ping(&self) -> Result<(), String> {
+        self.client
+            .head(format!("{}/rest/v1/", self.url))
+            .header("apikey", &self.api_key)
+            .timeout(Duration::from_secs(PING_TIMEOUT_SECS))
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// PostgREST resolves duplicates on the primary key, so `key` is implied
+    async fn upsert(&self, table: &str, _key: &str, rows: &[Row]) -> Result<(), String> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let bearer = self.bearer().await?;
+        let response = self
+            .client
+            .post(format!("{}/rest/v1/{}", self.url, table))
+            .header("apikey", &self.api_key)
+            .header("Authorization", format!("Bearer {}", bearer))
+            .header("Content-Type", "application/json")
+            .header("Prefer", "resolution=merge-duplicates")
+            .json(rows)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Supabase error ({}): {}",
+                table,
+                error_body(response).await
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, table: &str, key: &str, ids: &[String]) -> Result<(), String> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let list = ids
+            .iter()
+            .map(|id| format!("\"{}\"", id))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let bearer = self.bearer().await?;
+        let response = self
+            .client
+            .delete(format!(
+                "{}/rest/v1/{}?{}=in.({})",
+                self.url, table, key, list
+            ))
+            .header("apikey", &self.api_key)
+            .header("Authorization", format!("Bearer {}", bearer))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Supabase error ({}): {}",
+                table,
+                error_body(response).await
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn count_modified(
+        &self,
+        table: &str,
+        change_column: &str,
+        since: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> Result<i64, String> {
+        let query = self.modified_query(table, change_column, since, tenant_id);
+        let bearer = self.bearer().await?;
+
+        let response = self
+            .client
+            .head(&query)
+            .header("apikey", &self.api_key)
+            .header("Authorization", format!("Bearer {}", bearer))
+            .header("Prefer", "count=exact")
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!(
+                "Supabase error checking count for {}: Status {}",
+                table, status
+            ));
+        }
+
+        let content_range = response
+            .headers()
+            .get("content-range")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("0-0/0");
+
+        // Parse "0-5/6" or "*/6" -> 6
+        let total = content_range.split('/').next_back().unwrap_or("0");
+        total.parse::<i64>().map_err(|e| e.to_string())
+    }
+
+    async fn select_modified(
+        &self,
+        table: &str,
+        change_column: &str,
+        since: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> Result<Vec<Row>, String> {
+        let query = self.modified_query(table, change_column, since, tenant_id);
+        let bearer = self.bearer().await?;
+
+        let response = self
+            .client
+            .get(&query)
+            .header("apikey", &self.api_key)
+            .header("Authorization", format!("Bearer {}", bearer))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Supabase error ({}): {}",
+                table,
+                error_body(response).await
+            ));
+        }
+
+        let body = response.text().await.map_err(|e| e.to_string())?;
+        serde_json::from_str(&body).map_err(|e| format!("Supabase error ({}): {}", table, e))
+    }
+
+    fn device_id(&self) -> Option<String> {
+        self.auth.device_id()
+    }
+
+    fn set_device(&self, device: Option<DeviceKey>) {
+        self.auth.set(device);
+    }
+
+    async fn register_device(
+        &self,
+        registration: &DeviceRegistration,
+    ) -> Result<DeviceCredentials, String> {
+        let body = serde_json::json!({
+            "p_org_id": registration.org_id,
+            "p_org_name": registration.org_name,
+            "p_tenant_id": registration.tenant_id,
+            "p_tenant_name": registration.tenant_name,
+            "p_hardware_id": registration.hardware_id,
+            "p_device_name": registration.device_name,
+            "p_enrollment_token": registration.enrollment_token,
+        });
+        let response = self.rpc("register_device", &self.api_key, &body).await?;
+        serde_json::from_str(&response).map_err(|e| e.to_string())
+    }
+
+    async fn list_devices(&self) -> Result<Vec<SyncDevice>, String> {
+        let bearer = self.bearer().await?;
+        let response = self
+            .rpc("list_devices", &bearer, &serde_json::json!({}))
+            .await?;
+        serde_json::from_str(&response).map_err(|e| e.to_string())
+    }
+
+    async fn revoke_device(&self, device_id: &str) -> Result<(), String> {
+        let bearer = self.bearer().await?;
+        let body = serde_json::json!({ "p_device_id": device_id });
+        self.rpc("revoke_device", &bearer, &body).await.map(|_| ())
+    }
+
+    async fn create_enrollment_token(&self, tenant_id: Option<&str>) -> Result<String, String> {
+        let bearer = self.bearer().await?;
+        let body = serde_json::json!({ "p_tenant_id": tenant_id });
+        let response = self.rpc("create_enrollment_token", &bearer, &body).await?;
+        serde_json::from_str(&response).map_err(|e| e.to_string())
+    }
+}
+
+// --- Self-hosted Equinox sync server ---
+
+/// Client for `equinox-sync-server` (see `sync-server/` in the repository).
+/// The enrollment token configured by the admin only registers devices.
+pub struct EquinoxServerClient {
+    client: reqwest::Client,
+    url: String,
+    enrollment_token: String,
+    auth: DeviceAuth,
+}
+
+impl EquinoxServerClient {
+    pub fn new(url: String, enrollment_token: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+            enrollment_token,
+            auth: DeviceAuth::default(),
+        }
+    }
+
+    fn table_url(
+        &self,
+        table: &str,
+        action: &str,
+        params: &[(&str, &str)],
+    ) -> Result<reqwest::Url, String> {
+        reqwest::Url::parse_with_params(
+            &format!("{}/v1/tables/{}/{}", self.url, table, action),
+            params,
+        )
+        .map_err(|e| format!("URL de sincronización inválida: {}", e))
+    }
+
+    fn changes_params<'a>(
+        change_column: &'a str,
+        since: Option<&'a str>,
+        tenant_id: Option<&'a str>,
+    ) -> Vec<(&'a str, &'a str)> {
+        let mut params = vec![("column", change_column)];
+        if let Some(since) = since {
+            params.push(("since", since));
+        }
+        if let Some(tenant) = tenant_id {
+            params.push(("tenant_id", tenant));
+        }
+        params
+    }
+
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+        bearer: Option<&str>,
+        context: &str,
+    ) -> Result<String, String> {
+        let request = match bearer {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        let response = request.send().await.map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Sync server error ({}): {}",
+                context,
+                error_body(response).await
+            ));
+        }
+
+        response.text().await.map_err(|e| e.to_string())
+    }
+
+    async fn bearer(&self) -> Result<String, String> {
+        self.auth
+            .bearer(|refresh_token| async move {
+                let request = self
+                    .client
+                    .post(format!("{}/v1/devices/refresh", self.url))
+                    .json(&serde_json::json!({ "refresh_token": refresh_token.as_str() }));
+                let body = self.send(request, None, "devices").await?;
+                serde_json::from_str(&body).map_err(|e| e.to_string())
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl SyncBackend for EquinoxServerClient {
+    fn name(&self) -> &'static str {
+        "equinox_server"
+    }
+
+    async fn ping(&self) -> Result<(), String> {
+        let request = self
+            .client
+            .get(format!("{}/v1/health", self.url))
+            .timeout(Duration::from_secs(PING_TIMEOUT_SECS));
+        self.send(request, None, "health").await.map(|_| ())
+    }
+
+    async fn upsert(&self, table: &str, key: &str, rows: &[Row]) -> Result<(), String> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let url = self.table_url(table, "upsert", &[("key", key)])?;
+        let bearer = self.bearer().await?;
+        self.send(self.client.post(url).json(rows), Some(&bearer), table)
+            .await
+            .map(|_| ())
+    }
+
+    async fn delete(&self, table: &str, key: &str, ids: &[String]) -> Result<(), String> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let url = self.table_url(table, "delete", &[])?;
+        let bearer = self.bearer().await?;
+        let body = serde_json::json!({ "key": key, "ids": ids });
+        self.send(self.client.post(url).json(&body), Some(&bearer), table)
+            .await
+            .map(|_| ())
+    }
+
+    async fn count_modified(
+        &self,
+        table: &str,
+        change_column: &str,
+        since: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> Result<i64, String> {
+        let params = Self::changes_params(change_column, since, tenant_id);
+        let url = self.table_url(table, "changes/count", &params)?;
+        let bearer = self.bearer().await?;
+        let body = self
+            .send(self.client.get(url), Some(&bearer), table)
+            .await?;
+
+        let value: serde_json::Value = serde_json::from_str(&body)
+            .map_err(|e| format!("Sync server error ({}): {}", table, e))?;
+        value["count"]
+            .as_i64()
+            .ok_or_else(|| format!("Sync server error ({}): respuesta sin conteo", table))
+    }
+
+    async fn select_modified(
+        &self,
+        table: &str,
+        change_column: &str,
+        since: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> Result<Vec<Row>, String> {
+        let params = Self::changes_params(change_column, since, tenant_id);
+        let url = self.table_url(table, "changes", &params)?;
+        let bearer = self.bearer().await?;
+        let body = self
+            .send(self.client.get(url), Some(&bearer), table)
+            .await?;
+
+        serde_json::from_str(&body).map_err(|e| format!("Sync server error ({}): {}", table, e))
+    }
+
+    fn device_id(&self) -> Option<String> {
+        self.auth.device_id()
+    }
+
+    fn set_device(&self, device: Option<DeviceKey>) {
+        self.auth.set(device);
+    }
+
+    async fn register_device(
+        &self,
+        registration: &DeviceRegistration,
+    ) -> Result<DeviceCredentials, String> {
+        let request = self
+            .client
+            .post(format!("{}/v1/devices/register", self.url))
+            .json(registration);
+        let body = self
+            .send(request, Some(&self.enrollment_token), "devices")
+            .await?;
+        serde_json::from_str(&body).map_err(|e| e.to_string())
+    }
+
+    async fn list_devices(&self) -> Result<Vec<SyncDevice>, String> {
+        let bearer = self.bearer().await?;
+        let request = self.client.get(format!("{}/v1/devices", self.url));
+        let body = self.send(request, Some(&bearer), "devices").await?;
+        serde_json::from_str(&body).map_err(|e| e.to_string())
+    }
+
+    async fn revoke_device(&self, device_id: &str) -> Result<(), String> {
+        let bearer = self.bearer().await?;
+        let request = self
+            .client
+            .post(format!("{}/v1/devices/{}/revoke", self.url, device_id));
+        self.send(request, Some(&bearer), "devices")
+            .await
+            .map(|_| ())
+    }
+}
+
+// --- Active backend ---
+
+/// The configured backend, shared by commands and the scheduler and swapped
+/// when an admin changes the settings
+#[derive(Clone)]
+pub struct SyncBackendSlot {
+    inner: Arc<RwLock<Option<Arc<dyn SyncBackend>>>>,
+    kek: Arc<Zeroizing<[u8; 32]>>,
+    hardware_id: Arc<String>,
+    registering: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl SyncBackendSlot {
+    pub fn new(
+        backend: Option<Arc<dyn SyncBackend>>,
+        kek: Zeroizing<[u8; 32]>,
+        hardware_id: String,
+    ) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(backend)),
+            kek: Arc::new(kek),
+            hardware_id: Arc::new(hardware_id),
+            registering: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    /// Current backend, or an error when sync is not configured
+    pub fn get(&self) -> Result<Arc<dyn SyncBackend>, String> {
+        self.inner
+            .read()
+            .ok()
+            .and_then(|backend| backend.clone())
+            .ok_or_else(|| NOT_CONFIGURED.to_string())
+    }
+
+    pub fn replace(&self, backend: Option<Arc<dyn SyncBackend>>) {
+        if let Ok(mut current) = self.inner.write() {
+            *current = backend;
+        }
+    }
+
+    /// Current backend with device credentials, registering this
+    /// installation first if needed
+    pub async fn ready(
+        &self,
+        db: &Mutex<Connection>,
+        tenant_id: &str,
+    ) -> Result<Arc<dyn SyncBackend>, String> {
+        let backend = self.get()?;
+        if backend.device_id().is_some() {
+            // The device token only reaches the branch it was registered for
+            let conn = db.lock().map_err(|e| e.to_string())?;
+            if let Some(other) = stored_device(&conn)?
+                .and_then(|stored| stored.tenant_id)
+                .filter(|registered| registered != tenant_id)
+            {
+                return Err(format!(
+                    "Este equipo sincroniza la sucursal {}; cámbiese a ella para sincronizar",
+                    other
+                ));
+            }
+            return Ok(backend);
+        }
+
+        // One registration at a time; another caller may have finished it
+        let _guard = self.registering.lock().await;
+        if backend.device_id().is_some() {
+            return Ok(backend);
+        }
+
+        let registration = {
+            let conn = db.lock().map_err(|e| e.to_string())?;
+            let mut registration = device_registration(&conn, tenant_id, &self.hardware_id)?;
+            registration.enrollment_token = get_metadata(&conn, ENROLLMENT_KEY)?
+                .and_then(|sealed| unseal(&self.kek, &sealed))
+                .map(|token| token.to_string());
+            registration
+        };
+        let credentials = backend.register_device(&registration).await?;
+        let refresh_token = credentials
+            .refresh_token
+            .ok_or("El servidor de sincronización no devolvió token de renovación")?;
+
+        {
+            let conn = db.lock().map_err(|e| e.to_string())?;
+            save_device(
+                &conn,
+                &self.kek,
+                &credentials.device_id,
+                &refresh_token,
+                tenant_id,
+            )?;
+            // The enrollment token is single use
+            delete_metadata(&conn, ENROLLMENT_KEY)?;
+
+            let details = serde_json::json!({
+                "backend": backend.name(),
+                "device_name": registration.device_name,
+            })
+            .to_string();
+            audit::log_event(
+                &conn,
+                Some(tenant_id),
+                None,
+                AuditEventType::SyncDeviceRegistered,
+                Some("sync_device"),
+                Some(&credentials.device_id),
+                &details,
+            )
+            .ok();
+        }
+
+        println!("✅ Device registered for sync: {}", credentials.device_id);
+        backend.set_device(Some(DeviceKey {
+            device_id: credentials.device_id,
+            refresh_token: Zeroizing::new(refresh_token),
+        }));
+        Ok(backend)
+    }
+}
+
+fn connect(kind: SyncBackendKind, url: &str, secret: &str) -> Option<Arc<dyn SyncBackend>> {
+    let url = url.to_string();
+    let secret = secret.to_string();
+    match kind {
+        SyncBackendKind::Disabled => None,
+        SyncBackendKind::Supabase => Some(Arc::new(SupabaseClient::new(url, secret))),
+        SyncBackendKind::EquinoxServer => Some(Arc::new(EquinoxServerClient::new(url, secret))),
+    }
+}
+
+// --- Settings ---
+
+fn get_metadata(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT value FROM security_metadata WHERE key = ?1",
+        [key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+fn set_metadata(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT OR REPLACE INTO security_metadata (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn delete_metadata(conn: &Connection, key: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM security_metadata WHERE key = ?1", [key])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Encrypt a credential with AES-256-GCM; stored as "nonce:ciphertext" in hex
+fn seal(kek: &[u8; 32], secret: &str) -> Result<String, String> {
+    let cipher = Aes256Gcm::new_from_slice(kek).map_err(|e| e.to_string())?;
+    let mut nonce = [0u8; 12];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), secret.as_bytes())
+        .map_err(|_| "Error al cifrar la credencial".to_string())?;
+    Ok(format!(
+        "{}:{}",
+        hex::encode(nonce),
+        hex::encode(ciphertext)
+    ))
+}
+
+fn unseal(kek: &[u8; 32], sealed: &str) -> Option<Zeroizing<String>> {
+    let (nonce, ciphertext) = sealed.split_once(':')?;
+    let nonce = hex::decode(nonce).ok().filter(|n| n.len() == 12)?;
+    let ciphertext = hex::decode(ciphertext).ok()?;
+
+    let cipher = Aes256Gcm::new_from_slice(kek).ok()?;
+    let plain = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .ok()?;
+    String::from_utf8(plain).ok().map(Zeroizing::new)
+}
+
+// --- Device registration ---
+
+/// Device credentials kept in `security_metadata`
+#[derive(Serialize, Deserialize)]
+struct StoredDevice {
+    device_id: String,
+    refresh_token: String, // Sealed with the hardware key
+    registered_at: String,
+    #[serde(default)]
+    tenant_id: Option<String>, // Branch the device was registered for
+}
+
+fn stored_device(conn: &Connection) -> Result<Option<StoredDevice>, String> {
+    Ok(get_metadata(conn, DEVICE_KEY)?.and_then(|json| serde_json::from_str(&json).ok()))
+}
+
+fn save_device(
+    conn: &Connection,
+    kek: &[u8; 32],
+    device_id: &str,
+    refresh_token: &str,
+    tenant_id: &str,
+) -> Result<(), String> {
+    let stored = StoredDevice {
+        device_id: device_id.to_string(),
+        refresh_token: seal(kek, refresh_token)?,
+        registered_at: Utc::now().to_rfc3339(),
+        tenant_id: Some(tenant_id.to_string()),
+    };
+    let json = serde_json::to_string(&stored).map_err(|e| e.to_string())?;
+    set_metadata(conn, DEVICE_KEY, &json)
+}
+
+/// Give a freshly built backend the stored device credentials
+fn attach_device(conn: &Connection, kek: &[u8; 32], backend: &Arc<dyn SyncBackend>) {
+    let device = stored_device(conn).ok().flatten().and_then(|stored| {
+        let refresh_token = unseal(kek, &stored.refresh_token)?;
+        Some(DeviceKey {
+            device_id: stored.device_id,
+            refresh_token,
+        })
+    });
+    backend.set_device(device);
+}
+
+/// Forget this installation's device; the next sync registers it again
+pub fn forget_device(conn: &Connection) -> Result<(), String> {
+    delete_metadata(conn, DEVICE_KEY)
+}
+
+fn device_registration(
+    conn: &Connection,
+    tenant_id: &str,
+    hardware_id: &str,
+) -> Result<DeviceRegistration, String> {
+    let (org_id, org_name, tenant_name): (String, String, String) = conn
+        .query_row(
+            "SELECT o.id, o.name, t.name
+             FROM tenants t
+             JOIN organizations o ON o.id = t.org_id
+             WHERE t.id = ?1",
+            [tenant_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| format!("Error fetching tenant: {}", e))?;
+
+    let device_name = std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| tenant_name.clone());
+
+    Ok(DeviceRegistration {
+        org_id,
+        org_name,
+        tenant_id: tenant_id.to_string(),
+        tenant_name,
+        hardware_id: hardware_id.to_string(),
+        device_name,
+        enrollment_token: None,
+    })
+}
+
+/// Revoke a device at the backend; revoking this one also forgets it locally
+pub async fn revoke_device(
+    backend: &dyn SyncBackend,
+    db: &Mutex<Connection>,
+    tenant_id: &str,
+    user_id: &str,
+    device_id: &str,
+) -> Result<(), String> {
+    backend.revoke_device(device_id).await?;
+
+    let conn = db.lock().map_err(|e| e.to_string())?;
+    let is_self = backend.device_id().as_deref() == Some(device_id);
+    if is_self {
+        forget_device(&conn)?;
+        backend.set_device(None);
+    }
+
+    let details = serde_json::json!({ "backend": backend.name(), "this_device": is_self });
+    audit::log_event(
+        &conn,
+        Some(tenant_id),
+        Some(user_id),
+        AuditEventType::SyncDeviceRevoked,
+        Some("sync_device"),
+        Some(device_id),
+        &details.to_string(),
+    )
+    .ok();
+
+    Ok(())
+}
+
+/// Current backend settings
+pub fn get_settings(conn: &Connection) -> Result<SyncBackendSettings, String> {
+    let mut settings: SyncBackendSettings = match get_metadata(conn, SETTINGS_KEY)? {
+        Some(json) => serde_json::from_str(&json).unwrap_or_default(),
+        None => SyncBackendSettings::default(),
+    };
+    settings.has_credentials = get_metadata(conn, SECRET_KEY)?.is_some();
+    settings.device_id = stored_device(conn)?.map(|d| d.device_id);
+    Ok(settings)
+}
+
+/// Change the sync backend; returns the new settings and the backend to use
+pub fn update_settings(
+    conn: &Connection,
+    kek: &[u8; 32],
+    data: UpdateSyncBackendDto,
+    user_id: &str,
+) -> Result<(SyncBackendSettings, Option<Arc<dyn SyncBackend>>), String> {
+    let previous = get_settings(conn)?;
+
+    let url = data
+        .url
+        .as_deref()
+        .map(|u| u.trim().trim_end_matches('/').to_string())
+        .filter(|u| !u.is_empty());
+
+    let mut settings = SyncBackendSettings {
+        kind: data.kind,
+        url: None,
+        has_credentials: false,
+        updated_at: Some(Utc::now().to_rfc3339()),
+        updated_by: Some(user_id.to_string()),
+        device_id: None,
+    };
+
+    let backend = if data.kind == SyncBackendKind::Disabled {
+        delete_metadata(conn, SECRET_KEY)?;
+        delete_metadata(conn, ENROLLMENT_KEY)?;
+        None
+    } else {
+        let url = url.ok_or("La URL del servidor de sincronización es requerida")?;
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err("La URL debe comenzar con http:// o https://".to_string());
+        }
+        reqwest::Url::parse(&url).map_err(|e| format!("URL inválida: {}", e))?;
+
+        let secret = match data.api_key.as_deref().map(str::trim) {
+            Some("") => return Err("La credencial no puede estar vacía".to_string()),
+            Some(key) => {
+                set_metadata(conn, SECRET_KEY, &seal(kek, key)?)?;
+                Zeroizing::new(key.to_string())
+            }
+            None => get_metadata(conn, SECRET_KEY)?
+                .and_then(|sealed| unseal(kek, &sealed))
+                .ok_or("Debe ingresar la credencial del servidor de sincronización")?,
+        };
+
+        if let Some(token) = data
+            .enrollment_token
+            .as_deref()
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+        {
+            set_metadata(conn, ENROLLMENT_KEY, &seal(kek, token)?)?;
+        }
+
+        settings.url = Some(url.clone());
+        settings.has_credentials = true;
+        connect(data.kind, &url, &secret)
+    };
+
+    // A device is registered with one backend; a new one registers again
+    if previous.kind != settings.kind || previous.url != settings.url {
+        forget_device(conn)?;
+    }
+    if let Some(ref backend) = backend {
+        attach_device(conn, kek, backend);
+        settings.device_id = backend.device_id();
+    }
+
+    let json = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+    set_metadata(conn, SETTINGS_KEY, &json)?;
+
+    let details = serde_json::json!({
+        "previous_kind": previous.kind,
+        "previous_url": previous.url,
+        "kind": settings.kind,
+        "url": settings.url,
+        "credentials_changed": data.api_key.is_some(),
+    })
+    .to_string();
+    audit::log_event(
+        conn,
+        None,
+        Some(user_id),
+        AuditEventType::SyncBackendChanged,
+        Some("sync_backend"),
+        None,
+        &details,
+    )
+    .ok();
+
+    Ok((settings, backend))
+}
+
+/// Backend from the stored settings, or the build-time default when none
+/// were saved yet
+pub fn load(conn: &Connection, kek: &[u8; 32]) -> Result<Option<Arc<dyn SyncBackend>>, String> {
+    let backend = if get_metadata(conn, SETTINGS_KEY)?.is_none() {
+        match (DEFAULT_SUPABASE_URL, DEFAULT_SUPABASE_KEY) {
+            (Some(url), Some(key)) => connect(SyncBackendKind::Supabase, url, key),
+            _ => None,
+        }
+    } else {
+        let settings = get_settings(conn)?;
+        let Some(url) = settings
+            .url
+            .filter(|_| settings.kind != SyncBackendKind::Disabled)
+        else {
+            return Ok(None);
+        };
+        let secret = get_metadata(conn, SECRET_KEY)?
+            .and_then(|sealed| unseal(kek, &sealed))
+            .ok_or("La credencial de sincronización no se puede descifrar en este equipo")?;
+
+        connect(settings.kind, &url, &secret)
+    };
+
+    if let Some(ref backend) = backend {
+        attach_device(conn, kek, backend);
+    }
+    Ok(backend)
+}
+
+// --- In-memory backend for tests ---
+
+/// Backend keeping tables in memory, so several local databases can sync
+/// through it in tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockBackend {
+    tables: std::sync::Mutex<
+        std::collections::HashMap<String, std::collections::BTreeMap<String, Row>>,
+    >,
+    offline: std::sync::atomic::AtomicBool,
+    devices: Mutex<Vec<SyncDevice>>,
+    auth: DeviceAuth,
+}
+
+#[cfg(test)]
+impl MockBackend {
+    pub fn set_offline(&self, offline: bool) {
+        self.offline
+            .store(offline, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Every row stored for a table
+    pub fn rows(&self, table: &str) -> Vec<Row> {
+        self.tables
+            .lock()
+            .unwrap()
+            .get(table)
+            .map(|rows| rows.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn check_online(&self) -> Result<(), String> {
+        if self.offline.load(std::sync::atomic::Ordering::SeqCst) {
+            Err("Mock backend offline".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn key_text(value: Option<&serde_json::Value>) -> Option<String> {
+        match value? {
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Null => None,
+            other => Some(other.to_string()),
+        }
+    }
+
+    fn modified(
+        &self,
+        table: &str,
+        change_column: &str,
+        since: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> Vec<Row> {
+        let mut rows: Vec<Row> = self
+            .rows(table)
+            .into_iter()
+            .filter(|row| {
+                let changed = row.get(change_column).and_then(|v| v.as_str());
+                since.is_none_or(|since| changed.is_some_and(|c| c > since))
+            })
+            .filter(|row| {
+                tenant_id.is_none_or(|tenant| {
+                    row.get("tenant_id").and_then(|v| v.as_str()) == Some(tenant)
+                })
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            let a = a.get(change_column).and_then(|v| v.as_str());
+            let b = b.get(change_column).and_then(|v| v.as_str());
+            a.cmp(&b)
+        });
+        rows
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl SyncBackend for MockBackend {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    async fn ping(&self) -> Result<(), String> {
+        self.check_online()
+    }
+
+    async fn upsert(&self, table: &str, key: &str, rows: &[Row]) -> Result<(), String> {
+        self.check_online()?;
+        let mut tables = self.tables.lock().unwrap();
+        let stored = tables.entry(table.to_string()).or_default();
+
+        for row in rows {
+            let id = Self::key_text(row.get(key)).ok_or("Fila sin clave primaria")?;
+            // Merge like PostgREST: columns not sent keep their value
+            let current = stored.entry(id).or_default();
+            for (column, value) in row {
+                current.insert(column.clone(), value.clone());
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, table: &str, key: &str, ids: &[String]) -> Result<(), String> {
+        self.check_online()?;
+        if let Some(stored) = self.tables.lock().unwrap().get_mut(table) {
+            stored
+                .retain(|_, row| Self::key_text(row.get(key)).is_none_or(|id| !ids.contains(&id)));
+        }
+        Ok(())
+    }
+
+    async fn count_modified(
+        &self,
+        table: &str,
+        change_column: &str,
+        since: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> Result<i64, String> {
+        self.check_online()?;
+        Ok(self.modified(table, change_column, since, tenant_id).len() as i64)
+    }
+
+    async fn select_modified(
+        &self,
+        table: &str,
+        change_column: &str,
+        since: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> Result<Vec<Row>, String> {
+        self.check_online()?;
+        Ok(self.modified(table, change_column, since, tenant_id))
+    }
+
+    fn device_id(&self) -> Option<String> {
+        self.auth.device_id()
+    }
+
+    fn set_device(&self, device: Option<DeviceKey>) {
+        self.auth.set(device);
+    }
+
+    async fn register_device(
+        &self,
+        registration: &DeviceRegistration,
+    ) -> Result<DeviceCredentials, String> {
+        self.check_online()?;
+        let mut devices = self.devices.lock().unwrap();
+        if devices.iter().any(|d| {
+            d.hardware_id == registration.hardware_id
+                && d.tenant_id == registration.tenant_id
+                && d.revoked_at.is_some()
+        }) {
+            return Err("Dispositivo revocado".to_string());
+        }
+
+        let id = format!("device-{}", devices.len() + 1);
+        devices.push(SyncDevice {
+            id: id.clone(),
+            tenant_id: registration.tenant_id.clone(),
+            device_name: registration.device_name.clone(),
+            hardware_id: registration.hardware_id.clone(),
+            created_at: Utc::now().to_rfc3339(),
+            last_seen_at: None,
+            revoked_at: None,
+        });
+        Ok(DeviceCredentials {
+            access_token: format!("access-{}", id),
+            refresh_token: Some(format!("refresh-{}", id)),
+            expires_at: (Utc::now() + chrono::Duration::hours(1)).to_rfc3339(),
+            device_id: id,
+        })
+    }
+
+    async fn list_devices(&self) -> Result<Vec<SyncDevice>, String> {
+        self.check_online()?;
+        Ok(self.devices.lock().unwrap().clone())
+    }
+
+    async fn revoke_device(&self, device_id: &str) -> Result<(), String> {
+        self.check_online()?;
+        let mut devices = self.devices.lock().unwrap();
+        let device = devices
+            .iter_mut()
+            .find(|d| d.id == device_id)
+            .ok_or("Dispositivo no encontrado")?;
+        device.revoked_at = Some(Utc::now().to_rfc3339());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migrated() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_credentials_are_sealed_and_required() {
+        let conn = migrated();
+        let kek = [7u8; 32];
+
+        // Nothing stored and no build-time default in tests
+        assert!(get_settings(&conn).unwrap().kind == SyncBackendKind::Disabled);
+
+        let missing_key = UpdateSyncBackendDto {
+            kind: SyncBackendKind::EquinoxServer,
+            url: Some("https://sync.example.com/".to_string()),
+            api_key: None,
+            enrollment_token: None,
+        };
+        assert!(update_settings(&conn, &kek, missing_key.clone(), "u1").is_err());
+
+        let (settings, backend) = update_settings(
+            &conn,
+            &kek,
+            UpdateSyncBackendDto {
+                api_key: Some("s3cret-token".to_string()),
+                enrollment_token: Some(" join-code ".to_string()),
+                ..missing_key.clone()
+            },
+            "u1",
+        )
+        .unwrap();
+        assert_eq!(settings.url.as_deref(), Some("https://sync.example.com"));
+        assert!(settings.has_credentials);
+        assert_eq!(backend.unwrap().name(), "equinox_server");
+
+        // The credential is never stored in clear
+        let sealed = get_metadata(&conn, SECRET_KEY).unwrap().unwrap();
+        assert!(!sealed.contains("s3cret"));
+        assert_eq!(unseal(&kek, &sealed).unwrap().as_str(), "s3cret-token");
+        assert!(unseal(&[8u8; 32], &sealed).is_none());
+        let sealed = get_metadata(&conn, ENROLLMENT_KEY).unwrap().unwrap();
+        assert_eq!(unseal(&kek, &sealed).unwrap().as_str(), "join-code");
+
+        // Changing only the URL keeps the stored credential
+        let (_, backend) = update_settings(&conn, &kek, missing_key, "u1").unwrap();
+        assert!(backend.is_some());
+        assert!(load(&conn, &kek).unwrap().is_some());
+        assert!(load(&conn, &[8u8; 32]).is_err());
+
+        let (settings, backend) = update_settings(
+            &conn,
+            &kek,
+            UpdateSyncBackendDto {
+                kind: SyncBackendKind::Disabled,
+                url: None,
+                api_key: None,
+                enrollment_token: None,
+            },
+            "u1",
+        )
+        .unwrap();
+        assert!(backend.is_none() && !settings.has_credentials);
+        assert!(load(&conn, &kek).unwrap().is_none());
+        assert!(get_metadata(&conn, ENROLLMENT_KEY).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_device_registers_once_and_revocation_is_final() {
+        let kek = [7u8; 32];
+        let db = Mutex::new(migrated());
+        db.lock()
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+                 INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Sede 1');",
+            )
+            .unwrap();
+        set_metadata(
+            &db.lock().unwrap(),
+            ENROLLMENT_KEY,
+            &seal(&kek, "join-code").unwrap(),
+        )
+        .unwrap();
+
+        let mock: Arc<dyn SyncBackend> = Arc::new(MockBackend::default());
+        let slot =
+            SyncBackendSlot::new(Some(mock.clone()), Zeroizing::new(kek), "hw-1".to_string());
+
+        let backend = slot.ready(&db, "t1").await.unwrap();
+        let device_id = backend.device_id().unwrap();
+        slot.ready(&db, "t1").await.unwrap();
+        assert_eq!(mock.list_devices().await.unwrap().len(), 1);
+        // The enrollment token was spent on the registration
+        assert!(get_metadata(&db.lock().unwrap(), ENROLLMENT_KEY)
+            .unwrap()
+            .is_none());
+
+        // Stored sealed and restored for a new client
+        {
+            let conn = db.lock().unwrap();
+            let stored = stored_device(&conn).unwrap().unwrap();
+            assert!(!stored.refresh_token.contains("refresh-"));
+            assert_eq!(
+                get_settings(&conn).unwrap().device_id.as_deref(),
+                Some(device_id.as_str())
+            );
+
+            let restored: Arc<dyn SyncBackend> = Arc::new(MockBackend::default());
+            attach_device(&conn, &kek, &restored);
+            assert_eq!(restored.device_id(), Some(device_id.clone()));
+        }
+
+        // Revoking this device forgets it and blocks registering it again
+        revoke_device(backend.as_ref(), &db, "t1", "u1", &device_id)
+            .await
+            .unwrap();
+        assert!(backend.device_id().is_none());
+        assert!(stored_device(&db.lock().unwrap()).unwrap().is_none());
+        assert!(slot.ready(&db, "t1").await.is_err());
+    }
+}
@@ -0,0 +1,731 @@
+//! Sync Conflict Detection
+//!
+//! Decides what happens when a remote row arrives for a row that also has
+//! unsynced local edits. A row is dirty when it differs from its base
+//! snapshot (the last state known to match the cloud); without a snapshot
+//! its change timestamp is compared with the push watermark.
+//!
+//! Each entity has a default policy in `sync_entities`, overridable per
+//! installation. Conflicts that need a person are queued in `sync_conflicts`
+//! and held back from upload until resolved; rows kept or merged locally are
+//! queued in the outbox.
+
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashSet;
+
+use crate::models::sync::{ConflictPolicy, ConflictResolution, EntitySyncPolicy, SyncConflict};
+use crate::security::audit::{self, AuditEventType};
+use crate::services::sync::{read_row, upsert_row, Row};
+use crate::services::sync_entities::{self, EntityDescriptor, ENTITIES};
+use crate::services::sync_outbox;
+
+/// What to do with an incoming remote row
+#[derive(Debug)]
+pub enum Reconciled {
+    Apply(Row),          // Write this row locally
+    ApplyAndUpload(Row), // Write locally and queue for upload (merged rows)
+    KeepLocal,           // Nothing to write
+    Conflict,            // Queued for review; local row kept
+}
+
+/// Timestamp in the same format as the touch triggers
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, false)
+}
+
+// --- Policies ---
+
+/// Policy in effect for an entity (override or descriptor default)
+pub fn effective_policy(
+    conn: &Connection,
+    entity: &EntityDescriptor,
+) -> Result<ConflictPolicy, String> {
+    let stored: Option<String> = conn
+        .query_row(
+            "SELECT policy FROM sync_entity_policies WHERE entity = ?1",
+            [entity.table],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    Ok(stored
+        .as_deref()
+        .and_then(ConflictPolicy::parse)
+        .unwrap_or(entity.conflict_policy))
+}
+
+/// Policies of every synced entity
+pub fn list_policies(conn: &Connection) -> Result<Vec<EntitySyncPolicy>, String> {
+    ENTITIES
+        .iter()
+        .map(|entity| {
+            let policy = effective_policy(conn, entity)?;
+            Ok(EntitySyncPolicy {
+                entity: entity.table.to_string(),
+                policy,
+                default_policy: entity.conflict_policy,
+                overridden: policy != entity.conflict_policy,
+            })
+        })
+        .collect()
+}
+
+/// Override the conflict policy of an entity; `None` restores the default
+pub fn set_policy(
+    conn: &Connection,
+    table: &str,
+    policy: Option<ConflictPolicy>,
+    user_id: &str,
+) -> Result<EntitySyncPolicy, String> {
+    let entity =
+        sync_entities::find(table).ok_or_else(|| format!("Entidad no sincronizada: {}", table))?;
+
+    if entity.conflict_policy == ConflictPolicy::AppendOnly {
+        return Err(
+            "Los documentos fiscales solo admiten anexar; su política no se puede cambiar"
+                .to_string(),
+        );
+    }
+
+    match policy {
+        Some(policy) if policy != entity.conflict_policy => {
+            conn.execute(
+                "INSERT INTO sync_entity_policies (entity, policy, updated_at, updated_by)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(entity) DO UPDATE SET
+                    policy = excluded.policy,
+                    updated_at = excluded.updated_at,
+                    updated_by = excluded.updated_by",
+                params![entity.table, policy.as_str(), now(), user_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        _ => {
+            conn.execute(
+                "DELETE FROM sync_entity_policies WHERE entity = ?1",
+                [entity.table],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let effective = effective_policy(conn, entity)?;
+    audit::log_event(
+        conn,
+        None,
+        Some(user_id),
+        AuditEventType::SyncPolicyChanged,
+        Some("sync_entity"),
+        Some(entity.table),
+        &format!("policy={}", effective.as_str()),
+    )
+    .ok();
+
+    Ok(EntitySyncPolicy {
+        entity: entity.table.to_string(),
+        policy: effective,
+        default_policy: entity.conflict_policy,
+        overridden: effective != entity.conflict_policy,
+    })
+}
+
+// --- Base snapshots ---
+
+fn load_base(conn: &Connection, table: &str, id: &str) -> Result<Option<Row>, String> {
+    let data: Option<String> = conn
+        .query_row(
+            "SELECT data FROM sync_base_rows WHERE entity = ?1 AND row_id = ?2",
+            params![table, id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    Ok(data.and_then(|d| serde_json::from_str(&d).ok()))
+}
+
+fn save_base(conn: &Connection, table: &str, id: &str, row: &Row) -> Result<(), String> {
+    let data = serde_json::to_string(row).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO sync_base_rows (entity, row_id, data, synced_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(entity, row_id) DO UPDATE SET data = excluded.data, synced_at = excluded.synced_at",
+        params![table, id, data, now()],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Record uploaded rows as the known cloud state
+pub fn save_base_rows(
+    conn: &Connection,
+    entity: &EntityDescriptor,
+    rows: &[Row],
+) -> Result<(), String> {
+    for row in rows {
+        if let Some(id) = row_id(entity, row) {
+            save_base(conn, entity.table, id, row)?;
+        }
+    }
+    Ok(())
+}
+
+/// Forget the cloud state of deleted rows
+pub fn forget_base_rows(conn: &Connection, table: &str, ids: &[String]) -> Result<(), String> {
+    let ids = serde_json::to_string(ids).map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM sync_base_rows WHERE entity = ?1 AND row_id IN (SELECT value FROM json_each(?2))",
+        params![table, ids],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// --- Detection ---
+
+fn row_id<'a>(entity: &EntityDescriptor, row: &'a Row) -> Option<&'a str> {
+    row.get(entity.primary_key).and_then(|v| v.as_str())
+}
+
+fn change_of<'a>(entity: &EntityDescriptor, row: &'a Row) -> Option<&'a str> {
+    row.get(entity.change_column).and_then(|v| v.as_str())
+}
+
+/// Compare JSON values the way SQLite stores them (10 == 10.0, true == 1)
+fn values_equal(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        (Value::Bool(x), Value::Number(n)) | (Value::Number(n), Value::Bool(x)) => {
+            n.as_f64() == Some(*x as i64 as f64)
+        }
+        _ => a == b,
+    }
+}
+
+/// Data columns present in `other` whose value differs in `row`
+fn differing_fields(entity: &EntityDescriptor, row: &Row, other: &Row) -> Vec<String> {
+    entity
+        .columns
+        .iter()
+        .filter(|c| **c != entity.primary_key && **c != entity.change_column)
+        .filter(|c| other.contains_key(**c))
+        .filter(|c| {
+            let null = serde_json::Value::Null;
+            !values_equal(row.get(**c).unwrap_or(&null), &other[**c])
+        })
+        .map(|c| c.to_string())
+        .collect()
+}
+
+/// Three-way merge: columns changed on one side only take that side's value.
+/// Returns the merged row and the columns changed on both sides.
+fn merge_fields(
+    entity: &EntityDescriptor,
+    base: &Row,
+    local: &Row,
+    remote: &Row,
+) -> (Row, Vec<String>) {
+    let null = serde_json::Value::Null;
+    let mut merged = local.clone();
+    let mut overlapping = Vec::new();
+
+    for column in differing_fields(entity, local, remote) {
+        let base_value = base.get(&column).unwrap_or(&null);
+        let local_value = local.get(&column).unwrap_or(&null);
+
+        if values_equal(local_value, base_value) {
+            merged.insert(column.clone(), remote[&column].clone());
+        } else if !values_equal(&remote[&column], base_value) {
+            overlapping.push(column);
+        }
+    }
+
+    merged.insert(
+        entity.change_column.to_string(),
+        serde_json::Value::String(now()),
+    );
+    (merged, overlapping)
+}
+
+/// Keep only descriptor columns (drops the cloud-only tenant_id of child tables)
+fn descriptor_columns(entity: &EntityDescriptor, row: &Row) -> Row {
+    row.iter()
+        .filter(|(k, _)| entity.columns.contains(&k.as_str()))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect()
+}
+
+/// Decide what to do with one remote row
+pub fn reconcile(
+    conn: &Connection,
+    entity: &EntityDescriptor,
+    policy: ConflictPolicy,
+    incoming: &Row,
+    last_pushed: Option<&str>,
+) -> Result<Reconciled, String> {
+    let tenant_id = incoming.get("tenant_id").and_then(|v| v.as_str());
+    let remote = descriptor_columns(entity, incoming);
+    let Some(id) = row_id(entity, &remote) else {
+        return Ok(Reconciled::KeepLocal);
+    };
+
+    let Some(local) = read_row(conn, entity, id)? else {
+        save_base(conn, entity.table, id, &remote)?;
+        return Ok(Reconciled::Apply(remote));
+    };
+
+    let differing = differing_fields(entity, &local, &remote);
+    if differing.is_empty() {
+        save_base(conn, entity.table, id, &remote)?;
+        return Ok(Reconciled::KeepLocal);
+    }
+
+    let record = ConflictRecord {
+        entity,
+        policy,
+        tenant_id,
+        row_id: id,
+        local: &local,
+        remote: &remote,
+    };
+
+    // Fiscal records are never overwritten, dirty or not
+    if policy == ConflictPolicy::AppendOnly {
+        record_conflict(conn, &record, &differing, "pending")?;
+        return Ok(Reconciled::Conflict);
+    }
+
+    let base = load_base(conn, entity.table, id)?;
+    let local_dirty = match &base {
+        Some(base) => !differing_fields(entity, &local, base).is_empty(),
+        None => match (change_of(entity, &local), last_pushed) {
+            (Some(change), Some(pushed)) => change > pushed,
+            _ => true,
+        },
+    };
+
+    if !local_dirty {
+        save_base(conn, entity.table, id, &remote)?;
+        return Ok(Reconciled::Apply(remote));
+    }
+
+    // The remote row is the cloud state from here on
+    save_base(conn, entity.table, id, &remote)?;
+
+    match policy {
+        ConflictPolicy::LastWriterWins => {
+            let remote_change = change_of(entity, &remote).unwrap_or_default();
+            let local_change = change_of(entity, &local).unwrap_or_default();
+            if remote_change >= local_change {
+                record_conflict(conn, &record, &differing, "resolved_remote")?;
+                Ok(Reconciled::Apply(remote.clone()))
+            } else {
+                record_conflict(conn, &record, &differing, "resolved_local")?;
+                sync_outbox::enqueue(conn, entity, id)?;
+                Ok(Reconciled::KeepLocal)
+            }
+        }
+        ConflictPolicy::LocalWins => {
+            record_conflict(conn, &record, &differing, "resolved_local")?;
+            sync_outbox::enqueue(conn, entity, id)?;
+            Ok(Reconciled::KeepLocal)
+        }
+        ConflictPolicy::FieldMerge => match &base {
+            Some(base) => {
+                let (merged, overlapping) = merge_fields(entity, base, &local, &remote);
+                if overlapping.is_empty() {
+                    record_conflict(conn, &record, &differing, "resolved_merged")?;
+                    Ok(Reconciled::ApplyAndUpload(merged))
+                } else {
+                    record_conflict(conn, &record, &overlapping, "pending")?;
+                    Ok(Reconciled::Conflict)
+                }
+            }
+            None => {
+                record_conflict(conn, &record, &differing, "pending")?;
+                Ok(Reconciled::Conflict)
+            }
+        },
+        ConflictPolicy::Manual | ConflictPolicy::AppendOnly => {
+            record_conflict(conn, &record, &differing, "pending")?;
+            Ok(Reconciled::Conflict)
+        }
+    }
+}
+
+fn touch(conn: &Connection, entity: &EntityDescriptor, id: &str) -> Result<(), String> {
+    conn.execute(
+        &format!(
+            "UPDATE {} SET {} = ?1 WHERE {} = ?2",
+            entity.table, entity.change_column, entity.primary_key
+        ),
+        params![now(), id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// --- Conflicts queue ---
+
+struct ConflictRecord<'a> {
+    entity: &'a EntityDescriptor,
+    policy: ConflictPolicy,
+    tenant_id: Option<&'a str>,
+    row_id: &'a str,
+    local: &'a Row,
+    remote: &'a Row,
+}
+
+fn record_conflict(
+    conn: &Connection,
+    record: &ConflictRecord<'_>,
+    fields: &[String],
+    status: &str,
+) -> Result<(), String> {
+    let local = serde_json::to_string(record.local).map_err(|e| e.to_string())?;
+    let remote = serde_json::to_string(record.remote).map_err(|e| e.to_string())?;
+    let fields = serde_json::to_string(fields).map_err(|e| e.to_string())?;
+    let now = now();
+
+    // A newer remote version replaces the one already waiting for review
+    if status == "pending" {
+        let updated = conn
+            .execute(
+                "UPDATE sync_conflicts
+                 SET local_data = ?1, remote_data = ?2, conflicting_fields = ?3, detected_at = ?4
+                 WHERE entity = ?5 AND row_id = ?6 AND status = 'pending'",
+                params![
+                    local,
+                    remote,
+                    fields,
+                    now,
+                    record.entity.table,
+                    record.row_id
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated > 0 {
+            return Ok(());
+        }
+    }
+
+    let resolved_at = (status != "pending").then(|| now.clone());
+    conn.execute(
+        "INSERT INTO sync_conflicts (
+            id, tenant_id, entity, row_id, policy, local_data, remote_data,
+            conflicting_fields, status, detected_at, resolved_at
+         ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            uuid::Uuid::new_v4().to_string(),
+            record.tenant_id,
+            record.entity.table,
+            record.row_id,
+            record.policy.as_str(),
+            local,
+            remote,
+            fields,
+            status,
+            now,
+            resolved_at
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Rows of an entity waiting for review (held back from upload)
+pub fn pending_row_ids(conn: &Connection, table: &str) -> Result<HashSet<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT row_id FROM sync_conflicts WHERE entity = ?1 AND status = 'pending'")
+        .map_err(|e| e.to_string())?;
+    let ids = stmt
+        .query_map([table], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<HashSet<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+/// Number of conflicts waiting for review
+pub fn count_pending(conn: &Connection) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sync_conflicts WHERE status = 'pending'",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn map_conflict(row: &rusqlite::Row) -> rusqlite::Result<SyncConflict> {
+    let local: String = row.get(5)?;
+    let remote: String = row.get(6)?;
+    let fields: String = row.get(7)?;
+
+    Ok(SyncConflict {
+        id: row.get(0)?,
+        tenant_id: row.get(1)?,
+        entity: row.get(2)?,
+        row_id: row.get(3)?,
+        policy: row.get(4)?,
+        local_data: serde_json::from_str(&local).unwrap_or_default(),
+        remote_data: serde_json::from_str(&remote).unwrap_or_default(),
+        conflicting_fields: serde_json::from_str(&fields).unwrap_or_default(),
+        status: row.get(8)?,
+        detected_at: row.get(9)?,
+        resolved_at: row.get(10)?,
+        resolved_by: row.get(11)?,
+    })
+}
+
+const CONFLICT_COLUMNS: &str = "id, tenant_id, entity, row_id, policy, local_data, remote_data,
+    conflicting_fields, status, detected_at, resolved_at, resolved_by";
+
+/// List conflicts, newest first
+pub fn list_conflicts(
+    conn: &Connection,
+    status: Option<&str>,
+    entity: Option<&str>,
+) -> Result<Vec<SyncConflict>, String> {
+    let sql = format!(
+        "SELECT {} FROM sync_conflicts
+         WHERE (?1 IS NULL OR status = ?1) AND (?2 IS NULL OR entity = ?2)
+         ORDER BY detected_at DESC
+         LIMIT 500",
+        CONFLICT_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let conflicts = stmt
+        .query_map(params![status, entity], map_conflict)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(conflicts)
+}
+
+fn get_conflict(conn: &Connection, id: &str) -> Result<SyncConflict, String> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM sync_conflicts WHERE id = ?1",
+            CONFLICT_COLUMNS
+        ),
+        [id],
+        map_conflict,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| "Conflicto no encontrado".to_string())
+}
+
+/// Settle a pending conflict.
+///
+/// Keeping the local row or saving a merge touches the row so it is uploaded
+/// on the next sync.
+pub fn resolve_conflict(
+    conn: &Connection,
+    conflict_id: &str,
+    resolution: ConflictResolution,
+    merged: Option<serde_json::Value>,
+    user_id: &str,
+) -> Result<SyncConflict, String> {
+    let conflict = get_conflict(conn, conflict_id)?;
+    if conflict.status != "pending" {
+        return Err("El conflicto ya fue resuelto".to_string());
+    }
+    let entity = sync_entities::find(&conflict.entity)
+        .ok_or_else(|| format!("Entidad no sincronizada: {}", conflict.entity))?;
+
+    let append_only = conflict.policy == ConflictPolicy::AppendOnly.as_str()
+        || entity.conflict_policy == ConflictPolicy::AppendOnly;
+    if append_only && resolution != ConflictResolution::Local {
+        return Err(
+            "Los documentos fiscales no se sobrescriben; solo puede conservarse la versión local"
+                .to_string(),
+        );
+    }
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let status = match resolution {
+        ConflictResolution::Local => {
+            if !append_only {
+                touch(&tx, entity, &conflict.row_id)?;
+            }
+            "resolved_local"
+        }
+        ConflictResolution::Remote => {
+            let remote = conflict
+                .remote_data
+                .as_object()
+                .ok_or("Datos remotos inválidos")?;
+            // Already the cloud state; not queued for upload
+            sync_outbox::set_suppressed(&tx, true)?;
+            upsert_row(&tx, entity, &descriptor_columns(entity, remote))?;
+            sync_outbox::set_suppressed(&tx, false)?;
+            "resolved_remote"
+        }
+        ConflictResolution::Merged => {
+            let mut row = merged
+                .as_ref()
+                .and_then(|m| m.as_object())
+                .map(|m| descriptor_columns(entity, m))
+                .ok_or("Debe indicar los datos combinados")?;
+            if row_id(entity, &row) != Some(conflict.row_id.as_str()) {
+                return Err("Los datos combinados no corresponden al registro".to_string());
+            }
+            row.insert(
+                entity.change_column.to_string(),
+                serde_json::Value::String(now()),
+            );
+            upsert_row(&tx, entity, &row)?;
+            "resolved_merged"
+        }
+    };
+
+    tx.execute(
+        "UPDATE sync_conflicts SET status = ?1, resolved_at = ?2, resolved_by = ?3 WHERE id = ?4",
+        params![status, now(), user_id, conflict_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    audit::log_event(
+        &tx,
+        conflict.tenant_id.as_deref(),
+        Some(user_id),
+        AuditEventType::SyncConflictResolved,
+        Some(&conflict.entity),
+        Some(&conflict.row_id),
+        &format!("conflict={}, resolution={}", conflict_id, status),
+    )
+    .ok();
+
+    tx.commit().map_err(|e| e.to_string())?;
+
+    get_conflict(conn, conflict_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::sync::apply_remote_rows;
+    use serde_json::json;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Sede 1');
+             INSERT INTO clients (id, tenant_id, code, name, phone, updated_at)
+             VALUES ('c1', 't1', 'C1', 'Ana', '0412', '2026-01-01T00:00:00.000+00:00');",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn remote_client(name: &str, phone: &str, updated_at: &str) -> Row {
+        json!({
+            "id": "c1", "tenant_id": "t1", "code": "C1",
+            "name": name, "phone": phone, "updated_at": updated_at
+        })
+        .as_object()
+        .unwrap()
+        .clone()
+    }
+
+    fn client_row(conn: &Connection) -> (String, String) {
+        conn.query_row("SELECT name, phone FROM clients WHERE id = 'c1'", [], |r| {
+            Ok((r.get(0)?, r.get(1)?))
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_field_merge_combines_disjoint_edits() {
+        let conn = setup();
+        let clients = sync_entities::find("clients").unwrap();
+        let base = read_row(&conn, clients, "c1").unwrap().unwrap();
+        save_base(&conn, "clients", "c1", &base).unwrap();
+
+        // Local edits the phone, remote edits the name
+        conn.execute("UPDATE clients SET phone = '0414' WHERE id = 'c1'", [])
+            .unwrap();
+        let remote = remote_client("Ana María", "0412", "2026-01-02T00:00:00.000+00:00");
+
+        let outcome = apply_remote_rows(&conn, clients, &[remote]).unwrap();
+        assert_eq!(outcome.applied, 1);
+        assert_eq!(
+            client_row(&conn),
+            ("Ana María".to_string(), "0414".to_string())
+        );
+        assert_eq!(count_pending(&conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_overlapping_edits_are_queued_and_held() {
+        let conn = setup();
+        let clients = sync_entities::find("clients").unwrap();
+        let base = read_row(&conn, clients, "c1").unwrap().unwrap();
+        save_base(&conn, "clients", "c1", &base).unwrap();
+
+        conn.execute("UPDATE clients SET name = 'Ana Local' WHERE id = 'c1'", [])
+            .unwrap();
+        let remote = remote_client("Ana Remota", "0412", "2026-01-02T00:00:00.000+00:00");
+
+        let outcome = apply_remote_rows(&conn, clients, &[remote]).unwrap();
+        assert_eq!(outcome.conflicts, 1);
+        assert_eq!(client_row(&conn).0, "Ana Local");
+        assert!(pending_row_ids(&conn, "clients").unwrap().contains("c1"));
+
+        let pending = list_conflicts(&conn, Some("pending"), None).unwrap();
+        assert_eq!(pending[0].conflicting_fields, vec!["name".to_string()]);
+
+        resolve_conflict(
+            &conn,
+            &pending[0].id,
+            ConflictResolution::Remote,
+            None,
+            "u1",
+        )
+        .unwrap();
+        assert_eq!(client_row(&conn).0, "Ana Remota");
+        assert!(pending_row_ids(&conn, "clients").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fiscal_rows_are_never_overwritten() {
+        let conn = setup();
+        let movements = sync_entities::find("inventory_movements").unwrap();
+        conn.execute_batch(
+            "INSERT INTO products (id, tenant_id, sku, name, unit_price) VALUES ('p1', 't1', 'P1', 'Café', 10);
+             INSERT INTO inventory_movements (id, tenant_id, product_id, movement_type, quantity, created_at)
+             VALUES ('m1', 't1', 'p1', 'in', 5, '2026-01-01T00:00:00.000+00:00');",
+        )
+        .unwrap();
+
+        let mut remote = read_row(&conn, movements, "m1").unwrap().unwrap();
+        remote.insert("quantity".to_string(), json!(50));
+
+        let outcome = apply_remote_rows(&conn, movements, &[remote]).unwrap();
+        assert_eq!(outcome.applied, 0);
+        let quantity: f64 = conn
+            .query_row(
+                "SELECT quantity FROM inventory_movements WHERE id = 'm1'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(quantity, 5.0);
+
+        let pending = list_conflicts(&conn, Some("pending"), None).unwrap();
+        assert!(resolve_conflict(
+            &conn,
+            &pending[0].id,
+            ConflictResolution::Remote,
+            None,
+            "u1"
+        )
+        .is_err());
+    }
+}
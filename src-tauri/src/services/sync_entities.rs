@@ -0,0 +1,769 @@
+//! Sync Entity Descriptors
+//!
+//! Every table replicated by the sync engine is described here: synced
+//! columns, primary key, how rows are scoped to a tenant and which entities
+//! must be synced first. The engine itself is table-agnostic.
+
+use crate::models::sync::ConflictPolicy;
+
+/// How rows of an entity are tied to a tenant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TenantScope {
+    /// The table has its own `tenant_id` column
+    Column,
+    /// Rows belong to a parent row that carries `tenant_id`; the cloud copy
+    /// stores the parent's tenant_id alongside the row
+    Parent {
+        table: &'static str,
+        foreign_key: &'static str,
+    },
+}
+
+/// Sync descriptor for one table
+#[derive(Debug)]
+pub struct EntityDescriptor {
+    pub table: &'static str,
+    pub primary_key: &'static str,
+    pub columns: &'static [&'static str], // Synced columns, primary key included
+    pub booleans: &'static [&'static str], // INTEGER locally, boolean in the cloud
+    pub scope: TenantScope,
+    pub change_column: &'static str, // Monotonic change timestamp (updated_at, created_at)
+    pub depends_on: &'static [&'static str],
+    pub conflict_policy: ConflictPolicy, // Default; overridable per installation
+}
+
+pub static ENTITIES: &[EntityDescriptor] = &[
+    EntityDescriptor {
+        table: "categories",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "parent_id",
+            "name",
+            "description",
+            "sort_order",
+            "is_active",
+            "created_at",
+            "updated_at",
+        ],
+        booleans: &["is_active"],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &[],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "units",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "name",
+            "abbreviation",
+            "unit_type",
+            "base_unit_id",
+            "conversion_factor",
+            "is_active",
+            "created_at",
+            "updated_at",
+        ],
+        booleans: &["is_active"],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &[],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "product_types",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "code",
+            "name",
+            "description",
+            "affects_stock",
+            "is_system",
+            "is_active",
+            "created_at",
+            "updated_at",
+        ],
+        booleans: &["affects_stock", "is_system", "is_active"],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &[],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "clients",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "code",
+            "name",
+            "tax_id",
+            "tax_type",
+            "email",
+            "phone",
+            "address",
+            "city",
+            "state",
+            "notes",
+            "is_active",
+            "created_at",
+            "updated_at",
+        ],
+        booleans: &["is_active"],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &[],
+        conflict_policy: ConflictPolicy::FieldMerge,
+    },
+    EntityDescriptor {
+        table: "products",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "sku",
+            "barcode",
+            "name",
+            "description",
+            "category",
+            "unit",
+            "unit_price",
+            "cost_price",
+            "tax_rate",
+            "stock_quantity",
+            "min_stock",
+            "is_active",
+            "created_at",
+            "updated_at",
+            "category_id",
+            "unit_id",
+            "product_type_id",
+            "sale_price",
+            "margin_percent",
+            "margin_amount",
+            "supplier_reference",
+            "max_stock",
+            "image_url",
+            "has_variants",
+            "track_expiration",
+            "cost_method",
+        ],
+        booleans: &["is_active", "has_variants", "track_expiration"],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &["categories", "units", "product_types"],
+        conflict_policy: ConflictPolicy::FieldMerge,
+    },
+    EntityDescriptor {
+        table: "product_variants",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "product_id",
+            "sku",
+            "name",
+            "attributes",
+            "cost_price",
+            "sale_price",
+            "barcode",
+            "is_active",
+            "created_at",
+            "updated_at",
+        ],
+        booleans: &["is_active"],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &["products"],
+        conflict_policy: ConflictPolicy::FieldMerge,
+    },
+    EntityDescriptor {
+        table: "variant_stock",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "variant_id",
+            "quantity",
+            "reserved_quantity",
+            "last_updated",
+        ],
+        booleans: &[],
+        scope: TenantScope::Parent {
+            table: "product_variants",
+            foreign_key: "variant_id",
+        },
+        change_column: "last_updated",
+        depends_on: &["product_variants"],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "inventory_lots",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "product_id",
+            "variant_id",
+            "lot_number",
+            "quantity",
+            "cost_price",
+            "expiration_date",
+            "received_date",
+            "is_active",
+            "created_at",
+            "updated_at",
+        ],
+        booleans: &["is_active"],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &["products", "product_variants"],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "warehouses",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "code",
+            "name",
+            "is_default",
+            "is_active",
+            "created_at",
+            "updated_at",
+        ],
+        booleans: &["is_default", "is_active"],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &[],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "warehouse_stock",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "warehouse_id",
+            "product_id",
+            "variant_id",
+            "lot_id",
+            "quantity",
+            "min_stock",
+            "bin_location",
+            "updated_at",
+        ],
+        booleans: &[],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &[
+            "warehouses",
+            "products",
+            "product_variants",
+            "inventory_lots",
+        ],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "price_history",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "product_id",
+            "variant_id",
+            "price_type",
+            "old_price",
+            "new_price",
+            "changed_by",
+            "reason",
+            "created_at",
+            "price_list_id",
+            "batch_id",
+        ],
+        booleans: &[],
+        scope: TenantScope::Column,
+        change_column: "created_at",
+        depends_on: &["products", "product_variants", "price_lists"],
+        conflict_policy: ConflictPolicy::AppendOnly,
+    },
+    EntityDescriptor {
+        table: "inventory_movements",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "product_id",
+            "movement_type",
+            "quantity",
+            "reference_type",
+            "reference_id",
+            "notes",
+            "created_by",
+            "created_at",
+            "warehouse_id",
+        ],
+        booleans: &[],
+        scope: TenantScope::Column,
+        change_column: "created_at",
+        depends_on: &["products", "warehouses"],
+        conflict_policy: ConflictPolicy::AppendOnly,
+    },
+    EntityDescriptor {
+        table: "price_lists",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "name",
+            "description",
+            "currency",
+            "discount_percent",
+            "is_default",
+            "is_active",
+            "created_at",
+            "updated_at",
+            "base_price_list_id",
+            "round_to",
+            "round_mode",
+            "last_rate",
+            "repriced_at",
+        ],
+        booleans: &["is_default", "is_active"],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &[],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "exchange_rates",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "currency",
+            "rate",
+            "rate_date",
+            "source",
+            "created_by",
+            "created_at",
+        ],
+        booleans: &[],
+        scope: TenantScope::Column,
+        change_column: "created_at",
+        depends_on: &[],
+        conflict_policy: ConflictPolicy::AppendOnly,
+    },
+    EntityDescriptor {
+        table: "product_prices",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "price_list_id",
+            "product_id",
+            "variant_id",
+            "price",
+            "created_at",
+            "updated_at",
+        ],
+        booleans: &[],
+        scope: TenantScope::Parent {
+            table: "price_lists",
+            foreign_key: "price_list_id",
+        },
+        change_column: "updated_at",
+        depends_on: &["price_lists", "products", "product_variants"],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "discounts",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "name",
+            "discount_type",
+            "value",
+            "applies_to",
+            "target_id",
+            "min_quantity",
+            "max_uses",
+            "times_used",
+            "start_date",
+            "end_date",
+            "is_active",
+            "created_at",
+            "updated_at",
+            "stackable",
+            "priority",
+            "campaign",
+            "requires_coupon",
+            "per_client_limit",
+            "buy_quantity",
+            "get_quantity",
+            "start_time",
+            "end_time",
+            "weekdays",
+        ],
+        booleans: &["is_active", "stackable", "requires_coupon"],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &[],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "discount_coupons",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "discount_id",
+            "code",
+            "max_uses",
+            "times_used",
+            "client_id",
+            "batch_id",
+            "is_active",
+            "created_at",
+            "updated_at",
+        ],
+        booleans: &["is_active"],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &["discounts", "clients"],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "discount_bundle_items",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "discount_id",
+            "product_id",
+            "quantity",
+            "created_at",
+            "updated_at",
+        ],
+        booleans: &[],
+        scope: TenantScope::Parent {
+            table: "discounts",
+            foreign_key: "discount_id",
+        },
+        change_column: "updated_at",
+        depends_on: &["discounts", "products"],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "cash_registers",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "name",
+            "status",
+            "current_session_id",
+            "created_at",
+            "updated_at",
+            "warehouse_id",
+        ],
+        booleans: &[],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &["warehouses"],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "cash_register_sessions",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "register_id",
+            "user_id",
+            "status",
+            "start_time",
+            "end_time",
+            "opening_amount_usd",
+            "opening_amount_ves",
+            "opening_amount_eur",
+            "opening_exchange_rate_ves",
+            "opening_exchange_rate_eur",
+            "opening_notes",
+            "closing_amount_usd",
+            "closing_amount_ves",
+            "closing_amount_eur",
+            "closing_notes",
+            "expected_amount_usd",
+            "expected_amount_ves",
+            "expected_amount_eur",
+            "created_at",
+            "updated_at",
+        ],
+        booleans: &[],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &["cash_registers"],
+        conflict_policy: ConflictPolicy::LastWriterWins,
+    },
+    EntityDescriptor {
+        table: "billing_invoices",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "invoice_number",
+            "invoice_type",
+            "status",
+            "client_id",
+            "client_name",
+            "client_tax_id",
+            "client_address",
+            "price_list_id",
+            "currency",
+            "exchange_rate",
+            "issue_date",
+            "due_date",
+            "payment_terms",
+            "subtotal",
+            "discount_total",
+            "tax_total",
+            "total",
+            "paid_amount",
+            "notes",
+            "created_by",
+            "created_at",
+            "updated_at",
+            "warehouse_id",
+            "payment_method",
+            "coupon_codes",
+            "register_id",
+            "session_id",
+            "change_amount",
+            "change_currency",
+        ],
+        booleans: &[],
+        scope: TenantScope::Column,
+        change_column: "updated_at",
+        depends_on: &[
+            "clients",
+            "price_lists",
+            "warehouses",
+            "cash_registers",
+            "cash_register_sessions",
+        ],
+        conflict_policy: ConflictPolicy::AppendOnly,
+    },
+    EntityDescriptor {
+        table: "billing_invoice_items",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "invoice_id",
+            "product_id",
+            "variant_id",
+            "lot_id",
+            "code",
+            "description",
+            "quantity",
+            "unit_price",
+            "discount_percent",
+            "discount_amount",
+            "tax_rate",
+            "tax_amount",
+            "line_total",
+            "updated_at",
+            "pricing_rules",
+            "unit_cost",
+        ],
+        booleans: &[],
+        scope: TenantScope::Parent {
+            table: "billing_invoices",
+            foreign_key: "invoice_id",
+        },
+        change_column: "updated_at",
+        depends_on: &[
+            "billing_invoices",
+            "products",
+            "product_variants",
+            "inventory_lots",
+        ],
+        conflict_policy: ConflictPolicy::AppendOnly,
+    },
+    EntityDescriptor {
+        table: "billing_payments",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "invoice_id",
+            "amount",
+            "currency",
+            "exchange_rate",
+            "payment_method",
+            "reference",
+            "payment_date",
+            "notes",
+            "created_by",
+            "created_at",
+            "bank_account_id",
+            "received_amount",
+            "session_id",
+        ],
+        booleans: &[],
+        scope: TenantScope::Column,
+        change_column: "created_at",
+        depends_on: &["billing_invoices", "cash_register_sessions"],
+        conflict_policy: ConflictPolicy::AppendOnly,
+    },
+    EntityDescriptor {
+        table: "cash_movements",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "session_id",
+            "user_id",
+            "type",
+            "amount",
+            "currency",
+            "exchange_rate",
+            "reason",
+            "reference",
+            "created_at",
+        ],
+        booleans: &[],
+        scope: TenantScope::Column,
+        change_column: "created_at",
+        depends_on: &["cash_register_sessions"],
+        conflict_policy: ConflictPolicy::AppendOnly,
+    },
+    EntityDescriptor {
+        table: "cash_session_counts",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "session_id",
+            "currency",
+            "denomination",
+            "kind",
+            "quantity",
+            "amount",
+            "created_at",
+        ],
+        booleans: &[],
+        scope: TenantScope::Column,
+        change_column: "created_at",
+        depends_on: &["cash_register_sessions"],
+        conflict_policy: ConflictPolicy::AppendOnly,
+    },
+    EntityDescriptor {
+        table: "cash_session_reports",
+        primary_key: "id",
+        columns: &[
+            "id",
+            "tenant_id",
+            "session_id",
+            "register_id",
+            "report_type",
+            "report_number",
+            "content",
+            "hash",
+            "created_by",
+            "created_at",
+        ],
+        booleans: &[],
+        scope: TenantScope::Column,
+        change_column: "created_at",
+        depends_on: &["cash_registers", "cash_register_sessions"],
+        conflict_policy: ConflictPolicy::AppendOnly,
+    },
+];
+
+/// Find the descriptor for a table
+pub fn find(table: &str) -> Option<&'static EntityDescriptor> {
+    ENTITIES.iter().find(|e| e.table == table)
+}
+
+/// Entities in dependency order (parents before children).
+///
+/// Stable: entities without pending dependencies keep declaration order.
+pub fn sync_order() -> Vec<&'static EntityDescriptor> {
+    let mut ordered: Vec<&'static EntityDescriptor> = Vec::with_capacity(ENTITIES.len());
+    let mut remaining: Vec<&'static EntityDescriptor> = ENTITIES.iter().collect();
+
+    while !remaining.is_empty() {
+        let ready = remaining.iter().position(|e| {
+            e.depends_on
+                .iter()
+                .all(|dep| ordered.iter().any(|o| o.table == *dep) || find(dep).is_none())
+        });
+
+        match ready {
+            Some(idx) => ordered.push(remaining.remove(idx)),
+            // Dependency cycle: keep declaration order for the rest
+            None => ordered.append(&mut remaining),
+        }
+    }
+
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_sync_order_respects_dependencies() {
+        let order = sync_order();
+        assert_eq!(order.len(), ENTITIES.len());
+
+        for (idx, entity) in order.iter().enumerate() {
+            for dep in entity.depends_on {
+                let dep_idx = order.iter().position(|e| e.table == *dep).unwrap();
+                assert!(dep_idx < idx, "{} must sync after {}", entity.table, dep);
+            }
+        }
+    }
+
+    #[test]
+    fn test_descriptors_match_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+
+        for entity in ENTITIES {
+            let mut stmt = conn
+                .prepare("SELECT name FROM pragma_table_info(?1)")
+                .unwrap();
+            let columns: Vec<String> = stmt
+                .query_map([entity.table], |row| row.get(0))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+
+            for column in entity
+                .columns
+                .iter()
+                .chain(entity.booleans)
+                .chain([&entity.primary_key, &entity.change_column])
+            {
+                assert!(
+                    columns.iter().any(|c| c == column),
+                    "{}.{} does not exist",
+                    entity.table,
+                    column
+                );
+            }
+            if entity.scope == TenantScope::Column {
+                assert!(entity.columns.contains(&"tenant_id"), "{}", entity.table);
+            }
+        }
+    }
+}
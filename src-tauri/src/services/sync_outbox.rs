@@ -0,0 +1,541 @@
+//! Sync Outbox
+//!
+//! Local changes are queued by triggers generated from the entity
+//! descriptors, so uploads never scan whole tables. Deletes are queued as
+//! tombstones carrying the deleted row. Failed uploads back off
+//! exponentially and are parked as poisoned after `MAX_ATTEMPTS`.
+
+use chrono::{Duration, SecondsFormat, Utc};
+use rusqlite::{params, Connection};
+
+use crate::models::sync::{ConflictPolicy, OutboxItem};
+use crate::services::sync::Row;
+use crate::services::sync_entities::{self, sync_order, EntityDescriptor, TenantScope, ENTITIES};
+
+/// Attempts before an entry is parked as poisoned
+pub const MAX_ATTEMPTS: i64 = 8;
+const BASE_BACKOFF_SECS: i64 = 30;
+const MAX_BACKOFF_SECS: i64 = 3600;
+
+/// Cloud table holding deletions for other devices
+pub const TOMBSTONES_TABLE: &str = "sync_tombstones";
+
+const SQL_NOW: &str = "strftime('%Y-%m-%dT%H:%M:%f+00:00', 'now')";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Upsert,
+    Delete,
+}
+
+/// A queued change
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub entity: String,
+    pub row_id: String,
+    pub tenant_id: Option<String>,
+    pub operation: Operation,
+    pub payload: Option<String>,
+    pub created_at: String,
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, false)
+}
+
+/// SQL expression for the tenant of a row (`alias` is NEW, OLD or a table alias)
+fn tenant_expr(entity: &EntityDescriptor, alias: &str) -> String {
+    match entity.scope {
+        TenantScope::Column => format!("{}.tenant_id", alias),
+        TenantScope::Parent { table, foreign_key } => format!(
+            "(SELECT tenant_id FROM {} WHERE id = {}.{})",
+            table, alias, foreign_key
+        ),
+    }
+}
+
+fn trigger_sql(entity: &EntityDescriptor) -> String {
+    let table = entity.table;
+    let pk = entity.primary_key;
+    let guard = "(SELECT suppressed FROM sync_outbox_guard WHERE id = 1) = 0";
+    let snapshot = entity
+        .columns
+        .iter()
+        .map(|c| format!("'{c}', OLD.{c}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let upsert = |event: &str, suffix: &str| {
+        format!(
+            "DROP TRIGGER IF EXISTS trg_{table}_outbox_{suffix};
+            CREATE TRIGGER trg_{table}_outbox_{suffix} AFTER {event} ON {table}
+            WHEN {guard}
+            BEGIN
+                DELETE FROM sync_outbox WHERE entity = '{table}' AND row_id = NEW.{pk} AND status = 'pending';
+                INSERT INTO sync_outbox (entity, row_id, tenant_id, operation, next_attempt_at, created_at)
+                VALUES ('{table}', NEW.{pk}, {tenant}, 'upsert', {SQL_NOW}, {SQL_NOW});
+            END;",
+            tenant = tenant_expr(entity, "NEW"),
+        )
+    };
+
+    format!(
+        "{insert}
+        {update}
+        DROP TRIGGER IF EXISTS trg_{table}_outbox_delete;
+        CREATE TRIGGER trg_{table}_outbox_delete AFTER DELETE ON {table}
+        WHEN {guard}
+        BEGIN
+            DELETE FROM sync_outbox WHERE entity = '{table}' AND row_id = OLD.{pk} AND status = 'pending';
+            INSERT INTO sync_outbox (entity, row_id, tenant_id, operation, payload, next_attempt_at, created_at)
+            VALUES ('{table}', OLD.{pk}, {tenant}, 'delete', json_object({snapshot}), {SQL_NOW}, {SQL_NOW});
+        END;",
+        insert = upsert("INSERT", "insert"),
+        update = upsert("UPDATE", "update"),
+        tenant = tenant_expr(entity, "OLD"),
+    )
+}
+
+/// Create outbox triggers for every synced entity.
+///
+/// Tables getting triggers for the first time have their rows changed since
+/// the last push queued, so nothing written before the outbox is lost.
+pub fn apply_sync_triggers(conn: &Connection) -> Result<(), rusqlite::Error> {
+    for entity in ENTITIES {
+        let existed: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'trigger' AND name = ?1)",
+            [format!("trg_{}_outbox_insert", entity.table)],
+            |row| row.get(0),
+        )?;
+
+        conn.execute_batch(&trigger_sql(entity))?;
+
+        if !existed {
+            conn.execute(
+                &format!(
+                    "INSERT INTO sync_outbox (entity, row_id, tenant_id, operation, next_attempt_at, created_at)
+                     SELECT '{table}', e.{pk}, {tenant}, 'upsert', ?1, ?1 FROM {table} e
+                     WHERE (SELECT last_pushed_at FROM sync_watermarks WHERE entity = '{table}') IS NULL
+                        OR e.{change} IS NULL
+                        OR e.{change} > (SELECT last_pushed_at FROM sync_watermarks WHERE entity = '{table}')",
+                    table = entity.table,
+                    pk = entity.primary_key,
+                    change = entity.change_column,
+                    tenant = tenant_expr(entity, "e"),
+                ),
+                [now()],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Stop (or resume) queueing changes, while remote rows are being applied
+pub fn set_suppressed(conn: &Connection, suppressed: bool) -> Result<(), String> {
+    conn.execute(
+        "UPDATE sync_outbox_guard SET suppressed = ?1 WHERE id = 1",
+        [suppressed as i64],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Queue a row for upload explicitly (used while triggers are suppressed)
+pub fn enqueue(conn: &Connection, entity: &EntityDescriptor, id: &str) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM sync_outbox WHERE entity = ?1 AND row_id = ?2 AND status = 'pending'",
+        params![entity.table, id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        &format!(
+            "INSERT INTO sync_outbox (entity, row_id, tenant_id, operation, next_attempt_at, created_at)
+             SELECT ?1, e.{pk}, {tenant}, 'upsert', ?3, ?3 FROM {table} e WHERE e.{pk} = ?2",
+            table = entity.table,
+            pk = entity.primary_key,
+            tenant = tenant_expr(entity, "e"),
+        ),
+        params![entity.table, id, now()],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Entries of a tenant ready for upload, oldest first
+pub fn due_entries(
+    conn: &Connection,
+    tenant_id: &str,
+    limit: usize,
+) -> Result<Vec<OutboxEntry>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, entity, row_id, tenant_id, operation, payload, created_at
+             FROM sync_outbox
+             WHERE status = 'pending' AND tenant_id = ?1 AND next_attempt_at <= ?2
+             ORDER BY id
+             LIMIT ?3",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let entries = stmt
+        .query_map(params![tenant_id, now(), limit as i64], |row| {
+            let operation: String = row.get(4)?;
+            Ok(OutboxEntry {
+                id: row.get(0)?,
+                entity: row.get(1)?,
+                row_id: row.get(2)?,
+                tenant_id: row.get(3)?,
+                operation: if operation == "delete" {
+                    Operation::Delete
+                } else {
+                    Operation::Upsert
+                },
+                payload: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(entries)
+}
+
+/// Remove uploaded (or obsolete) entries
+pub fn complete(conn: &Connection, entry_ids: &[i64]) -> Result<(), String> {
+    let ids = serde_json::to_string(entry_ids).map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM sync_outbox WHERE id IN (SELECT value FROM json_each(?1))",
+        [ids],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Delay before the next attempt: 30s, 1m, 2m, ... capped at one hour
+pub fn backoff_secs(attempts: i64) -> i64 {
+    let exponent = (attempts - 1).clamp(0, 20) as u32;
+    (BASE_BACKOFF_SECS * 2_i64.pow(exponent)).min(MAX_BACKOFF_SECS)
+}
+
+/// Record a failed upload; entries past `MAX_ATTEMPTS` are poisoned
+pub fn fail(conn: &Connection, entry_ids: &[i64], error: &str) -> Result<(), String> {
+    for id in entry_ids {
+        let attempts: i64 = conn
+            .query_row(
+                "SELECT attempts + 1 FROM sync_outbox WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        let next = (Utc::now() + Duration::seconds(backoff_secs(attempts)))
+            .to_rfc3339_opts(SecondsFormat::Millis, false);
+        let status = if attempts >= MAX_ATTEMPTS {
+            "poisoned"
+        } else {
+            "pending"
+        };
+
+        conn.execute(
+            "UPDATE sync_outbox
+             SET attempts = ?1, status = ?2, last_error = ?3, next_attempt_at = ?4
+             WHERE id = ?5",
+            params![attempts, status, error, next, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Cloud tombstone for a queued delete
+pub fn tombstone_row(entry: &OutboxEntry) -> Row {
+    let mut row = Row::new();
+    row.insert(
+        "id".to_string(),
+        format!("{}:{}", entry.entity, entry.row_id).into(),
+    );
+    row.insert("entity".to_string(), entry.entity.clone().into());
+    row.insert("row_id".to_string(), entry.row_id.clone().into());
+    row.insert("tenant_id".to_string(), entry.tenant_id.clone().into());
+    row.insert("deleted_at".to_string(), entry.created_at.clone().into());
+    row.insert(
+        "data".to_string(),
+        entry
+            .payload
+            .as_deref()
+            .and_then(|p| serde_json::from_str(p).ok())
+            .unwrap_or_default(),
+    );
+    row
+}
+
+/// Apply remote tombstones, children first. Returns rows deleted and the
+/// latest `deleted_at` seen.
+///
+/// Fiscal records are never deleted, and a row with a pending local edit is
+/// kept so the edit is uploaded instead.
+pub fn apply_tombstones(
+    conn: &Connection,
+    tombstones: &[Row],
+) -> Result<(usize, Option<String>), String> {
+    let order = sync_order();
+    let position = |table: &str| order.iter().position(|e| e.table == table);
+
+    let mut sorted: Vec<&Row> = tombstones.iter().collect();
+    sorted.sort_by_key(|t| {
+        std::cmp::Reverse(position(
+            t.get("entity").and_then(|v| v.as_str()).unwrap_or(""),
+        ))
+    });
+
+    let mut deleted = 0;
+    let mut latest: Option<String> = None;
+
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    set_suppressed(&tx, true)?;
+    for tombstone in sorted {
+        if let Some(at) = tombstone.get("deleted_at").and_then(|v| v.as_str()) {
+            if latest.as_deref().is_none_or(|l| at > l) {
+                latest = Some(at.to_string());
+            }
+        }
+
+        let (Some(table), Some(row_id)) = (
+            tombstone.get("entity").and_then(|v| v.as_str()),
+            tombstone.get("row_id").and_then(|v| v.as_str()),
+        ) else {
+            continue;
+        };
+        let Some(entity) = sync_entities::find(table) else {
+            continue;
+        };
+        if entity.conflict_policy == ConflictPolicy::AppendOnly {
+            continue;
+        }
+
+        let pending_edit: bool = tx
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM sync_outbox
+                 WHERE entity = ?1 AND row_id = ?2 AND operation = 'upsert')",
+                params![table, row_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if pending_edit {
+            continue;
+        }
+
+        // Rows still referenced locally stay; the reference wins
+        let removed = tx
+            .execute(
+                &format!(
+                    "DELETE FROM {} WHERE {} = ?1",
+                    entity.table, entity.primary_key
+                ),
+                [row_id],
+            )
+            .unwrap_or(0);
+        if removed > 0 {
+            tx.execute(
+                "DELETE FROM sync_base_rows WHERE entity = ?1 AND row_id = ?2",
+                params![table, row_id],
+            )
+            .map_err(|e| e.to_string())?;
+            deleted += removed;
+        }
+    }
+    set_suppressed(&tx, false)?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok((deleted, latest))
+}
+
+// --- Status and poisoned entries ---
+
+/// Entries waiting for upload
+pub fn count_pending(conn: &Connection, tenant_id: &str) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sync_outbox WHERE status = 'pending' AND tenant_id = ?1",
+        [tenant_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Entries ready for upload now, leaving out those backing off
+pub fn count_due(conn: &Connection, tenant_id: &str) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sync_outbox
+         WHERE status = 'pending' AND tenant_id = ?1 AND next_attempt_at <= ?2",
+        params![tenant_id, now()],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Entries parked after too many failures
+pub fn count_poisoned(conn: &Connection, tenant_id: &str) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sync_outbox WHERE status = 'poisoned' AND tenant_id = ?1",
+        [tenant_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Entries that failed at least once, most attempts first
+pub fn list_failed(conn: &Connection, tenant_id: &str) -> Result<Vec<OutboxItem>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, entity, row_id, operation, attempts, status, last_error, next_attempt_at, created_at
+             FROM sync_outbox
+             WHERE tenant_id = ?1 AND attempts > 0
+             ORDER BY status DESC, attempts DESC, id
+             LIMIT 500",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let items = stmt
+        .query_map([tenant_id], |row| {
+            Ok(OutboxItem {
+                id: row.get(0)?,
+                entity: row.get(1)?,
+                row_id: row.get(2)?,
+                operation: row.get(3)?,
+                attempts: row.get(4)?,
+                status: row.get(5)?,
+                last_error: row.get(6)?,
+                next_attempt_at: row.get(7)?,
+                created_at: row.get(8)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(items)
+}
+
+/// Put poisoned entries back in the queue (one, or all of the tenant)
+pub fn retry_failed(
+    conn: &Connection,
+    tenant_id: &str,
+    entry_id: Option<i64>,
+) -> Result<usize, String> {
+    conn.execute(
+        "UPDATE sync_outbox
+         SET status = 'pending', attempts = 0, next_attempt_at = ?1
+         WHERE status = 'poisoned' AND tenant_id = ?2 AND (?3 IS NULL OR id = ?3)",
+        params![now(), tenant_id, entry_id],
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Drop a poisoned entry without uploading it
+pub fn discard_failed(conn: &Connection, tenant_id: &str, entry_id: i64) -> Result<(), String> {
+    let removed = conn
+        .execute(
+            "DELETE FROM sync_outbox WHERE id = ?1 AND tenant_id = ?2 AND status = 'poisoned'",
+            params![entry_id, tenant_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+    if removed == 0 {
+        return Err("Solo se pueden descartar envíos fallidos".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        apply_sync_triggers(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO organizations (id, name) VALUES ('o1', 'Org');
+             INSERT INTO tenants (id, org_id, name) VALUES ('t1', 'o1', 'Sede 1');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        assert_eq!(backoff_secs(1), 30);
+        assert_eq!(backoff_secs(2), 60);
+        assert_eq!(backoff_secs(4), 240);
+        assert_eq!(backoff_secs(30), MAX_BACKOFF_SECS);
+    }
+
+    #[test]
+    fn test_changes_are_queued_once_per_row() {
+        let conn = setup();
+        conn.execute(
+            "INSERT INTO clients (id, tenant_id, code, name) VALUES ('c1', 't1', 'C1', 'Ana')",
+            [],
+        )
+        .unwrap();
+        conn.execute("UPDATE clients SET name = 'Ana María' WHERE id = 'c1'", [])
+            .unwrap();
+
+        let entries = due_entries(&conn, "t1", 50).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].operation, Operation::Upsert);
+
+        conn.execute("DELETE FROM clients WHERE id = 'c1'", [])
+            .unwrap();
+        let entries = due_entries(&conn, "t1", 50).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].operation, Operation::Delete);
+
+        let payload: serde_json::Value =
+            serde_json::from_str(entries[0].payload.as_deref().unwrap()).unwrap();
+        assert_eq!(payload["name"], "Ana María");
+        assert_eq!(tombstone_row(&entries[0])["id"], "clients:c1");
+    }
+
+    #[test]
+    fn test_suppressed_changes_are_not_queued() {
+        let conn = setup();
+        set_suppressed(&conn, true).unwrap();
+        conn.execute(
+            "INSERT INTO clients (id, tenant_id, code, name) VALUES ('c1', 't1', 'C1', 'Ana')",
+            [],
+        )
+        .unwrap();
+        set_suppressed(&conn, false).unwrap();
+
+        assert_eq!(count_pending(&conn, "t1").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_failures_back_off_then_poison() {
+        let conn = setup();
+        conn.execute(
+            "INSERT INTO clients (id, tenant_id, code, name) VALUES ('c1', 't1', 'C1', 'Ana')",
+            [],
+        )
+        .unwrap();
+        let entry = due_entries(&conn, "t1", 50).unwrap().remove(0);
+
+        fail(&conn, &[entry.id], "timeout").unwrap();
+        assert!(due_entries(&conn, "t1", 50).unwrap().is_empty());
+        assert_eq!(count_pending(&conn, "t1").unwrap(), 1);
+        assert_eq!(count_due(&conn, "t1").unwrap(), 0);
+
+        for _ in 1..MAX_ATTEMPTS {
+            fail(&conn, &[entry.id], "timeout").unwrap();
+        }
+        assert_eq!(count_poisoned(&conn, "t1").unwrap(), 1);
+
+        assert_eq!(retry_failed(&conn, "t1", None).unwrap(), 1);
+        assert_eq!(due_entries(&conn, "t1", 50).unwrap().len(), 1);
+    }
+}
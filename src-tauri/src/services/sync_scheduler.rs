@@ -0,0 +1,266 @@
+//! Background Sync Scheduler
+//!
+//! A task owned by `AppState` that syncs the active tenant on an interval and
+//! shortly after local commits. It stays idle while no backend is configured.
+//! While the backend is unreachable it only probes connectivity; when it
+//! comes back the first sync waits a random delay so a store full of
+//! terminals does not reconnect at once.
+//!
+//! Progress and errors are emitted as Tauri events so the UI does not poll.
+
+use rand::Rng;
+use rusqlite::hooks::Action;
+use rusqlite::Connection;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Notify;
+
+use crate::models::sync::SyncProgressEvent;
+use crate::services::sync;
+use crate::services::sync_backend::SyncBackendSlot;
+use crate::services::sync_outbox;
+
+/// Sync progress (started, completed, offline, online)
+pub const EVENT_PROGRESS: &str = "sync://progress";
+/// Failed sync runs
+pub const EVENT_ERROR: &str = "sync://error";
+
+const INTERVAL_SECS: u64 = 300;
+const COMMIT_DEBOUNCE_SECS: u64 = 5;
+const OFFLINE_PROBE_SECS: u64 = 30;
+const RESUME_JITTER_SECS: u64 = 20;
+const SHUTDOWN_FLUSH_SECS: u64 = 10;
+
+/// Handle to the background sync task
+#[derive(Clone)]
+pub struct SyncScheduler {
+    app: AppHandle,
+    db: Arc<Mutex<Connection>>,
+    tenant_id: Arc<Mutex<Option<String>>>,
+    backend: SyncBackendSlot,
+    wake: Arc<Notify>,
+    stop: Arc<Notify>,
+    stopped: Arc<AtomicBool>,
+    syncing: Arc<AtomicBool>,
+    online: Arc<AtomicBool>,
+    task: Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+}
+
+impl SyncScheduler {
+    pub fn new(
+        app: AppHandle,
+        db: Arc<Mutex<Connection>>,
+        tenant_id: Arc<Mutex<Option<String>>>,
+        backend: SyncBackendSlot,
+    ) -> Self {
+        Self {
+            app,
+            db,
+            tenant_id,
+            backend,
+            wake: Arc::new(Notify::new()),
+            stop: Arc::new(Notify::new()),
+            stopped: Arc::new(AtomicBool::new(false)),
+            syncing: Arc::new(AtomicBool::new(false)),
+            online: Arc::new(AtomicBool::new(true)),
+            task: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Wake the scheduler after a committed transaction that queued uploads.
+    ///
+    /// Only outbox inserts count, so the sync path's own writes (status,
+    /// watermarks, backoff) do not wake the loop again.
+    pub fn watch_commits(&self, conn: &Connection) {
+        let queued = Arc::new(AtomicBool::new(false));
+
+        let on_insert = queued.clone();
+        conn.update_hook(Some(
+            move |action: Action, _db: &str, table: &str, _rowid: i64| {
+                if action == Action::SQLITE_INSERT && table == "sync_outbox" {
+                    on_insert.store(true, Ordering::SeqCst);
+                }
+            },
+        ));
+
+        let on_rollback = queued.clone();
+        conn.rollback_hook(Some(move || on_rollback.store(false, Ordering::SeqCst)));
+
+        let wake = self.wake.clone();
+        conn.commit_hook(Some(move || {
+            if queued.swap(false, Ordering::SeqCst) {
+                wake.notify_one();
+            }
+            false // Never veto the commit
+        }));
+    }
+
+    /// Spawn the background task
+    pub fn start(&self) {
+        let scheduler = self.clone();
+        let handle = tauri::async_runtime::spawn(async move { scheduler.run().await });
+
+        if let Ok(mut task) = self.task.lock() {
+            *task = Some(handle);
+        }
+    }
+
+    pub fn is_syncing(&self) -> bool {
+        self.syncing.load(Ordering::SeqCst)
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::SeqCst)
+    }
+
+    fn current_tenant(&self) -> Option<String> {
+        self.tenant_id.lock().ok().and_then(|t| t.clone())
+    }
+
+    fn emit(&self, event: SyncProgressEvent) {
+        if event.error.is_some() {
+            self.app.emit(EVENT_ERROR, &event).ok();
+        }
+        self.app.emit(EVENT_PROGRESS, &event).ok();
+    }
+
+    /// Run one full sync, unless another run is in progress.
+    /// Returns (downloaded, uploaded).
+    pub async fn sync_now(&self, tenant_id: &str, trigger: &str) -> Result<(i32, i32), String> {
+        self.backend.get()?;
+        if self.syncing.swap(true, Ordering::SeqCst) {
+            return Err("Ya hay una sincronización en curso".to_string());
+        }
+
+        self.emit(SyncProgressEvent::new("started", trigger));
+        let result = match self.backend.ready(&self.db, tenant_id).await {
+            Ok(backend) => sync::run_sync(backend.as_ref(), &self.db, tenant_id).await,
+            Err(e) => Err(e),
+        };
+        self.syncing.store(false, Ordering::SeqCst);
+
+        match &result {
+            Ok((downloaded, uploaded)) => self.emit(SyncProgressEvent {
+                downloaded: *downloaded,
+                uploaded: *uploaded,
+                ..SyncProgressEvent::new("completed", trigger)
+            }),
+            Err(e) => self.emit(SyncProgressEvent {
+                error: Some(e.clone()),
+                ..SyncProgressEvent::new("failed", trigger)
+            }),
+        }
+
+        result
+    }
+
+    /// Uploads due now; entries backing off after a failure wait for their turn
+    fn has_due_uploads(&self, tenant_id: &str) -> bool {
+        self.db
+            .lock()
+            .ok()
+            .and_then(|conn| sync_outbox::count_due(&conn, tenant_id).ok())
+            .is_some_and(|count| count > 0)
+    }
+
+    /// Sleep unless asked to stop first; false when stopping
+    async fn pause(&self, secs: u64) -> bool {
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(secs)) => !self.stopped.load(Ordering::SeqCst),
+            _ = self.stop.notified() => false,
+        }
+    }
+
+    async fn run(&self) {
+        loop {
+            let wait = if self.is_online() {
+                INTERVAL_SECS
+            } else {
+                OFFLINE_PROBE_SECS
+            };
+
+            let trigger = tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(wait)) => "interval",
+                _ = self.wake.notified() => "commit",
+                _ = self.stop.notified() => break,
+            };
+            if self.stopped.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let Some(tenant_id) = self.current_tenant() else {
+                continue;
+            };
+            // Not configured yet; settings changes take effect on the next tick
+            let Ok(backend) = self.backend.get() else {
+                continue;
+            };
+
+            // Let a burst of writes settle, then only sync if something is queued
+            if trigger == "commit" {
+                if !self.pause(COMMIT_DEBOUNCE_SECS).await {
+                    break;
+                }
+                if !self.has_due_uploads(&tenant_id) {
+                    continue;
+                }
+            }
+
+            if backend.ping().await.is_err() {
+                if self.online.swap(false, Ordering::SeqCst) {
+                    self.emit(SyncProgressEvent::new("offline", trigger));
+                }
+                continue;
+            }
+
+            if !self.online.swap(true, Ordering::SeqCst) {
+                self.emit(SyncProgressEvent::new("online", trigger));
+                let jitter = rand::thread_rng().gen_range(0..=RESUME_JITTER_SECS);
+                if !self.pause(jitter).await {
+                    break;
+                }
+            }
+
+            if let Err(e) = self.sync_now(&tenant_id, trigger).await {
+                eprintln!("❌ Background sync failed: {}", e);
+            }
+        }
+    }
+
+    /// Stop the background task and upload what is left in the outbox.
+    ///
+    /// Bounded by a timeout so closing the app never hangs; anything not
+    /// uploaded stays queued for the next start.
+    pub async fn shutdown(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.stop.notify_one();
+
+        let task = self.task.lock().ok().and_then(|mut t| t.take());
+        if let Some(task) = task {
+            tokio::time::timeout(Duration::from_secs(SHUTDOWN_FLUSH_SECS), task)
+                .await
+                .ok();
+        }
+
+        let (Some(tenant_id), Ok(backend)) = (self.current_tenant(), self.backend.get()) else {
+            return;
+        };
+        // Never registered: nothing was uploaded from here yet
+        if backend.device_id().is_none() {
+            return;
+        }
+        if !self.has_due_uploads(&tenant_id) || self.syncing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let flush = sync::sync_to_cloud(backend.as_ref(), &self.db, &tenant_id);
+        match tokio::time::timeout(Duration::from_secs(SHUTDOWN_FLUSH_SECS), flush).await {
+            Ok(Ok(count)) => println!("✅ Outbox flushed on shutdown: {} rows", count),
+            Ok(Err(e)) => eprintln!("❌ Outbox flush failed: {}", e),
+            Err(_) => eprintln!("❌ Outbox flush timed out"),
+        }
+        self.syncing.store(false, Ordering::SeqCst);
+    }
+}
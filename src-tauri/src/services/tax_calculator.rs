@@ -4,12 +4,12 @@ use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 
 /// Calculate IVA (Venezuela VAT)
-#[allow(dead_code)]
 pub fn calculate_iva(subtotal: Decimal, rate: Decimal) -> Decimal {
     subtotal * rate / dec!(100)
 }
 
 /// Calculate line total
+#[allow(dead_code)]
 pub fn calculate_line_total(
     quantity: Decimal,
     unit_price: Decimal,
@@ -232,8 +232,687 @@ CREATE POLICY "Tenant Isolation" ON public.payments
 CREATE POLICY "Can view own user" ON public.users
     FOR SELECT USING (auth.uid() = id);
 
+
+-- ============================================
+-- SYNC ENGINE MIRROR (descriptor-driven sync, see services/sync_entities.rs)
+-- ============================================
+-- Columns mirror the local SQLite schema. Timestamps are stored as TEXT so
+-- watermarks compare the same way locally and in the cloud. Child tables
+-- carry a denormalized tenant_id for filtering and RLS.
+
+CREATE TABLE IF NOT EXISTS public.categories (id TEXT PRIMARY KEY);
+ALTER TABLE public.categories
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS parent_id TEXT,
+    ADD COLUMN IF NOT EXISTS name TEXT,
+    ADD COLUMN IF NOT EXISTS description TEXT,
+    ADD COLUMN IF NOT EXISTS sort_order BIGINT,
+    ADD COLUMN IF NOT EXISTS is_active BOOLEAN,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT;
+ALTER TABLE public.categories ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.units (id TEXT PRIMARY KEY);
+ALTER TABLE public.units
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS name TEXT,
+    ADD COLUMN IF NOT EXISTS abbreviation TEXT,
+    ADD COLUMN IF NOT EXISTS unit_type TEXT,
+    ADD COLUMN IF NOT EXISTS base_unit_id TEXT,
+    ADD COLUMN IF NOT EXISTS conversion_factor NUMERIC,
+    ADD COLUMN IF NOT EXISTS is_active BOOLEAN,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT;
+ALTER TABLE public.units ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.product_types (id TEXT PRIMARY KEY);
+ALTER TABLE public.product_types
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS code TEXT,
+    ADD COLUMN IF NOT EXISTS name TEXT,
+    ADD COLUMN IF NOT EXISTS description TEXT,
+    ADD COLUMN IF NOT EXISTS affects_stock BOOLEAN,
+    ADD COLUMN IF NOT EXISTS is_system BOOLEAN,
+    ADD COLUMN IF NOT EXISTS is_active BOOLEAN,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT;
+ALTER TABLE public.product_types ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.clients (id TEXT PRIMARY KEY);
+ALTER TABLE public.clients
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS code TEXT,
+    ADD COLUMN IF NOT EXISTS name TEXT,
+    ADD COLUMN IF NOT EXISTS tax_id TEXT,
+    ADD COLUMN IF NOT EXISTS tax_type TEXT,
+    ADD COLUMN IF NOT EXISTS email TEXT,
+    ADD COLUMN IF NOT EXISTS phone TEXT,
+    ADD COLUMN IF NOT EXISTS address TEXT,
+    ADD COLUMN IF NOT EXISTS city TEXT,
+    ADD COLUMN IF NOT EXISTS state TEXT,
+    ADD COLUMN IF NOT EXISTS notes TEXT,
+    ADD COLUMN IF NOT EXISTS is_active BOOLEAN,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT;
+ALTER TABLE public.clients ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.products (id TEXT PRIMARY KEY);
+ALTER TABLE public.products
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS sku TEXT,
+    ADD COLUMN IF NOT EXISTS barcode TEXT,
+    ADD COLUMN IF NOT EXISTS name TEXT,
+    ADD COLUMN IF NOT EXISTS description TEXT,
+    ADD COLUMN IF NOT EXISTS category TEXT,
+    ADD COLUMN IF NOT EXISTS unit TEXT,
+    ADD COLUMN IF NOT EXISTS unit_price NUMERIC,
+    ADD COLUMN IF NOT EXISTS cost_price NUMERIC,
+    ADD COLUMN IF NOT EXISTS tax_rate NUMERIC,
+    ADD COLUMN IF NOT EXISTS stock_quantity NUMERIC,
+    ADD COLUMN IF NOT EXISTS min_stock NUMERIC,
+    ADD COLUMN IF NOT EXISTS is_active BOOLEAN,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT,
+    ADD COLUMN IF NOT EXISTS category_id TEXT,
+    ADD COLUMN IF NOT EXISTS unit_id TEXT,
+    ADD COLUMN IF NOT EXISTS product_type_id TEXT,
+    ADD COLUMN IF NOT EXISTS sale_price NUMERIC,
+    ADD COLUMN IF NOT EXISTS margin_percent NUMERIC,
+    ADD COLUMN IF NOT EXISTS margin_amount NUMERIC,
+    ADD COLUMN IF NOT EXISTS supplier_reference TEXT,
+    ADD COLUMN IF NOT EXISTS max_stock NUMERIC,
+    ADD COLUMN IF NOT EXISTS image_url TEXT,
+    ADD COLUMN IF NOT EXISTS has_variants BOOLEAN,
+    ADD COLUMN IF NOT EXISTS track_expiration BOOLEAN,
+    ADD COLUMN IF NOT EXISTS cost_method TEXT;
+ALTER TABLE public.products ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.product_variants (id TEXT PRIMARY KEY);
+ALTER TABLE public.product_variants
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS product_id TEXT,
+    ADD COLUMN IF NOT EXISTS sku TEXT,
+    ADD COLUMN IF NOT EXISTS name TEXT,
+    ADD COLUMN IF NOT EXISTS attributes TEXT,
+    ADD COLUMN IF NOT EXISTS cost_price NUMERIC,
+    ADD COLUMN IF NOT EXISTS sale_price NUMERIC,
+    ADD COLUMN IF NOT EXISTS barcode TEXT,
+    ADD COLUMN IF NOT EXISTS is_active BOOLEAN,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT;
+ALTER TABLE public.product_variants ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.variant_stock (id TEXT PRIMARY KEY);
+ALTER TABLE public.variant_stock
+    ADD COLUMN IF NOT EXISTS variant_id TEXT,
+    ADD COLUMN IF NOT EXISTS quantity NUMERIC,
+    ADD COLUMN IF NOT EXISTS reserved_quantity NUMERIC,
+    ADD COLUMN IF NOT EXISTS last_updated TEXT,
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT;
+ALTER TABLE public.variant_stock ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.inventory_lots (id TEXT PRIMARY KEY);
+ALTER TABLE public.inventory_lots
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS product_id TEXT,
+    ADD COLUMN IF NOT EXISTS variant_id TEXT,
+    ADD COLUMN IF NOT EXISTS lot_number TEXT,
+    ADD COLUMN IF NOT EXISTS quantity NUMERIC,
+    ADD COLUMN IF NOT EXISTS cost_price NUMERIC,
+    ADD COLUMN IF NOT EXISTS expiration_date TEXT,
+    ADD COLUMN IF NOT EXISTS received_date TEXT,
+    ADD COLUMN IF NOT EXISTS is_active BOOLEAN,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT;
+ALTER TABLE public.inventory_lots ENABLE ROW LEVEL SECURITY;
+
+-- public.warehouses exists above; add the columns the app syncs
+ALTER TABLE public.warehouses
+    ADD COLUMN IF NOT EXISTS is_active BOOLEAN,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT;
+
+CREATE TABLE IF NOT EXISTS public.warehouse_stock (id TEXT PRIMARY KEY);
+ALTER TABLE public.warehouse_stock
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS warehouse_id TEXT,
+    ADD COLUMN IF NOT EXISTS product_id TEXT,
+    ADD COLUMN IF NOT EXISTS variant_id TEXT,
+    ADD COLUMN IF NOT EXISTS lot_id TEXT,
+    ADD COLUMN IF NOT EXISTS quantity NUMERIC,
+    ADD COLUMN IF NOT EXISTS min_stock NUMERIC,
+    ADD COLUMN IF NOT EXISTS bin_location TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT;
+ALTER TABLE public.warehouse_stock ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.price_history (id TEXT PRIMARY KEY);
+ALTER TABLE public.price_history
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS product_id TEXT,
+    ADD COLUMN IF NOT EXISTS variant_id TEXT,
+    ADD COLUMN IF NOT EXISTS price_type TEXT,
+    ADD COLUMN IF NOT EXISTS old_price NUMERIC,
+    ADD COLUMN IF NOT EXISTS new_price NUMERIC,
+    ADD COLUMN IF NOT EXISTS changed_by TEXT,
+    ADD COLUMN IF NOT EXISTS reason TEXT,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS price_list_id TEXT,
+    ADD COLUMN IF NOT EXISTS batch_id TEXT;
+ALTER TABLE public.price_history ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.inventory_movements (id TEXT PRIMARY KEY);
+ALTER TABLE public.inventory_movements
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS product_id TEXT,
+    ADD COLUMN IF NOT EXISTS movement_type TEXT,
+    ADD COLUMN IF NOT EXISTS quantity NUMERIC,
+    ADD COLUMN IF NOT EXISTS reference_type TEXT,
+    ADD COLUMN IF NOT EXISTS reference_id TEXT,
+    ADD COLUMN IF NOT EXISTS notes TEXT,
+    ADD COLUMN IF NOT EXISTS created_by TEXT,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS warehouse_id TEXT;
+ALTER TABLE public.inventory_movements ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.price_lists (id TEXT PRIMARY KEY);
+ALTER TABLE public.price_lists
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS name TEXT,
+    ADD COLUMN IF NOT EXISTS description TEXT,
+    ADD COLUMN IF NOT EXISTS currency TEXT,
+    ADD COLUMN IF NOT EXISTS discount_percent NUMERIC,
+    ADD COLUMN IF NOT EXISTS is_default BOOLEAN,
+    ADD COLUMN IF NOT EXISTS is_active BOOLEAN,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT,
+    ADD COLUMN IF NOT EXISTS base_price_list_id TEXT,
+    ADD COLUMN IF NOT EXISTS round_to NUMERIC,
+    ADD COLUMN IF NOT EXISTS round_mode TEXT,
+    ADD COLUMN IF NOT EXISTS last_rate NUMERIC,
+    ADD COLUMN IF NOT EXISTS repriced_at TEXT;
+ALTER TABLE public.price_lists ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.exchange_rates (id TEXT PRIMARY KEY);
+ALTER TABLE public.exchange_rates
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS currency TEXT,
+    ADD COLUMN IF NOT EXISTS rate NUMERIC,
+    ADD COLUMN IF NOT EXISTS rate_date TEXT,
+    ADD COLUMN IF NOT EXISTS source TEXT,
+    ADD COLUMN IF NOT EXISTS created_by TEXT,
+    ADD COLUMN IF NOT EXISTS created_at TEXT;
+ALTER TABLE public.exchange_rates ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.product_prices (id TEXT PRIMARY KEY);
+ALTER TABLE public.product_prices
+    ADD COLUMN IF NOT EXISTS price_list_id TEXT,
+    ADD COLUMN IF NOT EXISTS product_id TEXT,
+    ADD COLUMN IF NOT EXISTS variant_id TEXT,
+    ADD COLUMN IF NOT EXISTS price NUMERIC,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT,
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT;
+ALTER TABLE public.product_prices ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.discounts (id TEXT PRIMARY KEY);
+ALTER TABLE public.discounts
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS name TEXT,
+    ADD COLUMN IF NOT EXISTS discount_type TEXT,
+    ADD COLUMN IF NOT EXISTS value NUMERIC,
+    ADD COLUMN IF NOT EXISTS applies_to TEXT,
+    ADD COLUMN IF NOT EXISTS target_id TEXT,
+    ADD COLUMN IF NOT EXISTS min_quantity NUMERIC,
+    ADD COLUMN IF NOT EXISTS max_uses BIGINT,
+    ADD COLUMN IF NOT EXISTS times_used BIGINT,
+    ADD COLUMN IF NOT EXISTS start_date TEXT,
+    ADD COLUMN IF NOT EXISTS end_date TEXT,
+    ADD COLUMN IF NOT EXISTS is_active BOOLEAN,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT,
+    ADD COLUMN IF NOT EXISTS stackable BOOLEAN,
+    ADD COLUMN IF NOT EXISTS priority BIGINT,
+    ADD COLUMN IF NOT EXISTS campaign TEXT,
+    ADD COLUMN IF NOT EXISTS requires_coupon BOOLEAN,
+    ADD COLUMN IF NOT EXISTS per_client_limit BIGINT,
+    ADD COLUMN IF NOT EXISTS buy_quantity NUMERIC,
+    ADD COLUMN IF NOT EXISTS get_quantity NUMERIC,
+    ADD COLUMN IF NOT EXISTS start_time TEXT,
+    ADD COLUMN IF NOT EXISTS end_time TEXT,
+    ADD COLUMN IF NOT EXISTS weekdays TEXT;
+ALTER TABLE public.discounts ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.discount_coupons (id TEXT PRIMARY KEY);
+ALTER TABLE public.discount_coupons
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS discount_id TEXT,
+    ADD COLUMN IF NOT EXISTS code TEXT,
+    ADD COLUMN IF NOT EXISTS max_uses BIGINT,
+    ADD COLUMN IF NOT EXISTS times_used BIGINT,
+    ADD COLUMN IF NOT EXISTS client_id TEXT,
+    ADD COLUMN IF NOT EXISTS batch_id TEXT,
+    ADD COLUMN IF NOT EXISTS is_active BOOLEAN,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT;
+ALTER TABLE public.discount_coupons ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.discount_bundle_items (id TEXT PRIMARY KEY);
+ALTER TABLE public.discount_bundle_items
+    ADD COLUMN IF NOT EXISTS discount_id TEXT,
+    ADD COLUMN IF NOT EXISTS product_id TEXT,
+    ADD COLUMN IF NOT EXISTS quantity NUMERIC,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT,
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT;
+ALTER TABLE public.discount_bundle_items ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.cash_registers (id TEXT PRIMARY KEY);
+ALTER TABLE public.cash_registers
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS name TEXT,
+    ADD COLUMN IF NOT EXISTS status TEXT,
+    ADD COLUMN IF NOT EXISTS current_session_id TEXT,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT,
+    ADD COLUMN IF NOT EXISTS warehouse_id TEXT;
+ALTER TABLE public.cash_registers ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.cash_register_sessions (id TEXT PRIMARY KEY);
+ALTER TABLE public.cash_register_sessions
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS register_id TEXT,
+    ADD COLUMN IF NOT EXISTS user_id TEXT,
+    ADD COLUMN IF NOT EXISTS status TEXT,
+    ADD COLUMN IF NOT EXISTS start_time TEXT,
+    ADD COLUMN IF NOT EXISTS end_time TEXT,
+    ADD COLUMN IF NOT EXISTS opening_amount_usd NUMERIC,
+    ADD COLUMN IF NOT EXISTS opening_amount_ves NUMERIC,
+    ADD COLUMN IF NOT EXISTS opening_amount_eur NUMERIC,
+    ADD COLUMN IF NOT EXISTS opening_exchange_rate_ves NUMERIC,
+    ADD COLUMN IF NOT EXISTS opening_exchange_rate_eur NUMERIC,
+    ADD COLUMN IF NOT EXISTS opening_notes TEXT,
+    ADD COLUMN IF NOT EXISTS closing_amount_usd NUMERIC,
+    ADD COLUMN IF NOT EXISTS closing_amount_ves NUMERIC,
+    ADD COLUMN IF NOT EXISTS closing_amount_eur NUMERIC,
+    ADD COLUMN IF NOT EXISTS closing_notes TEXT,
+    ADD COLUMN IF NOT EXISTS expected_amount_usd NUMERIC,
+    ADD COLUMN IF NOT EXISTS expected_amount_ves NUMERIC,
+    ADD COLUMN IF NOT EXISTS expected_amount_eur NUMERIC,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT;
+ALTER TABLE public.cash_register_sessions ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.billing_invoices (id TEXT PRIMARY KEY);
+ALTER TABLE public.billing_invoices
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS invoice_number TEXT,
+    ADD COLUMN IF NOT EXISTS invoice_type TEXT,
+    ADD COLUMN IF NOT EXISTS status TEXT,
+    ADD COLUMN IF NOT EXISTS client_id TEXT,
+    ADD COLUMN IF NOT EXISTS client_name TEXT,
+    ADD COLUMN IF NOT EXISTS client_tax_id TEXT,
+    ADD COLUMN IF NOT EXISTS client_address TEXT,
+    ADD COLUMN IF NOT EXISTS price_list_id TEXT,
+    ADD COLUMN IF NOT EXISTS currency TEXT,
+    ADD COLUMN IF NOT EXISTS exchange_rate NUMERIC,
+    ADD COLUMN IF NOT EXISTS issue_date TEXT,
+    ADD COLUMN IF NOT EXISTS due_date TEXT,
+    ADD COLUMN IF NOT EXISTS payment_terms TEXT,
+    ADD COLUMN IF NOT EXISTS subtotal NUMERIC,
+    ADD COLUMN IF NOT EXISTS discount_total NUMERIC,
+    ADD COLUMN IF NOT EXISTS tax_total NUMERIC,
+    ADD COLUMN IF NOT EXISTS total NUMERIC,
+    ADD COLUMN IF NOT EXISTS paid_amount NUMERIC,
+    ADD COLUMN IF NOT EXISTS notes TEXT,
+    ADD COLUMN IF NOT EXISTS created_by TEXT,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT,
+    ADD COLUMN IF NOT EXISTS warehouse_id TEXT,
+    ADD COLUMN IF NOT EXISTS payment_method TEXT,
+    ADD COLUMN IF NOT EXISTS coupon_codes TEXT,
+    ADD COLUMN IF NOT EXISTS register_id TEXT,
+    ADD COLUMN IF NOT EXISTS session_id TEXT,
+    ADD COLUMN IF NOT EXISTS change_amount NUMERIC,
+    ADD COLUMN IF NOT EXISTS change_currency TEXT;
+ALTER TABLE public.billing_invoices ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.billing_invoice_items (id TEXT PRIMARY KEY);
+ALTER TABLE public.billing_invoice_items
+    ADD COLUMN IF NOT EXISTS invoice_id TEXT,
+    ADD COLUMN IF NOT EXISTS product_id TEXT,
+    ADD COLUMN IF NOT EXISTS variant_id TEXT,
+    ADD COLUMN IF NOT EXISTS lot_id TEXT,
+    ADD COLUMN IF NOT EXISTS code TEXT,
+    ADD COLUMN IF NOT EXISTS description TEXT,
+    ADD COLUMN IF NOT EXISTS quantity NUMERIC,
+    ADD COLUMN IF NOT EXISTS unit_price NUMERIC,
+    ADD COLUMN IF NOT EXISTS discount_percent NUMERIC,
+    ADD COLUMN IF NOT EXISTS discount_amount NUMERIC,
+    ADD COLUMN IF NOT EXISTS tax_rate NUMERIC,
+    ADD COLUMN IF NOT EXISTS tax_amount NUMERIC,
+    ADD COLUMN IF NOT EXISTS line_total NUMERIC,
+    ADD COLUMN IF NOT EXISTS updated_at TEXT,
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS pricing_rules TEXT,
+    ADD COLUMN IF NOT EXISTS unit_cost NUMERIC;
+ALTER TABLE public.billing_invoice_items ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.billing_payments (id TEXT PRIMARY KEY);
+ALTER TABLE public.billing_payments
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS invoice_id TEXT,
+    ADD COLUMN IF NOT EXISTS amount NUMERIC,
+    ADD COLUMN IF NOT EXISTS currency TEXT,
+    ADD COLUMN IF NOT EXISTS exchange_rate NUMERIC,
+    ADD COLUMN IF NOT EXISTS payment_method TEXT,
+    ADD COLUMN IF NOT EXISTS reference TEXT,
+    ADD COLUMN IF NOT EXISTS payment_date TEXT,
+    ADD COLUMN IF NOT EXISTS notes TEXT,
+    ADD COLUMN IF NOT EXISTS created_by TEXT,
+    ADD COLUMN IF NOT EXISTS created_at TEXT,
+    ADD COLUMN IF NOT EXISTS bank_account_id TEXT,
+    ADD COLUMN IF NOT EXISTS received_amount NUMERIC,
+    ADD COLUMN IF NOT EXISTS session_id TEXT;
+ALTER TABLE public.billing_payments ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.cash_movements (id TEXT PRIMARY KEY);
+ALTER TABLE public.cash_movements
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS session_id TEXT,
+    ADD COLUMN IF NOT EXISTS user_id TEXT,
+    ADD COLUMN IF NOT EXISTS type TEXT,
+    ADD COLUMN IF NOT EXISTS amount NUMERIC,
+    ADD COLUMN IF NOT EXISTS currency TEXT,
+    ADD COLUMN IF NOT EXISTS exchange_rate NUMERIC,
+    ADD COLUMN IF NOT EXISTS reason TEXT,
+    ADD COLUMN IF NOT EXISTS reference TEXT,
+    ADD COLUMN IF NOT EXISTS created_at TEXT;
+ALTER TABLE public.cash_movements ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.cash_session_counts (id TEXT PRIMARY KEY);
+ALTER TABLE public.cash_session_counts
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS session_id TEXT,
+    ADD COLUMN IF NOT EXISTS currency TEXT,
+    ADD COLUMN IF NOT EXISTS denomination NUMERIC,
+    ADD COLUMN IF NOT EXISTS kind TEXT,
+    ADD COLUMN IF NOT EXISTS quantity INTEGER,
+    ADD COLUMN IF NOT EXISTS amount NUMERIC,
+    ADD COLUMN IF NOT EXISTS created_at TEXT;
+ALTER TABLE public.cash_session_counts ENABLE ROW LEVEL SECURITY;
+
+CREATE TABLE IF NOT EXISTS public.cash_session_reports (id TEXT PRIMARY KEY);
+ALTER TABLE public.cash_session_reports
+    ADD COLUMN IF NOT EXISTS tenant_id TEXT,
+    ADD COLUMN IF NOT EXISTS session_id TEXT,
+    ADD COLUMN IF NOT EXISTS register_id TEXT,
+    ADD COLUMN IF NOT EXISTS report_type TEXT,
+    ADD COLUMN IF NOT EXISTS report_number INTEGER,
+    ADD COLUMN IF NOT EXISTS content TEXT,
+    ADD COLUMN IF NOT EXISTS hash TEXT,
+    ADD COLUMN IF NOT EXISTS created_by TEXT,
+    ADD COLUMN IF NOT EXISTS created_at TEXT;
+ALTER TABLE public.cash_session_reports ENABLE ROW LEVEL SECURITY;
+
+-- Deletions made on any device; pulled by the others to delete locally
+CREATE TABLE IF NOT EXISTS public.sync_tombstones (
+    id TEXT PRIMARY KEY, -- "<entity>:<row_id>"
+    entity TEXT NOT NULL,
+    row_id TEXT NOT NULL,
+    tenant_id TEXT,
+    deleted_at TEXT NOT NULL,
+    data JSONB -- Row as it was when deleted
+);
+CREATE INDEX IF NOT EXISTS idx_sync_tombstones_tenant ON public.sync_tombstones(tenant_id, deleted_at);
+ALTER TABLE public.sync_tombstones ENABLE ROW LEVEL SECURITY;
+
+-- Tenant isolation for the new tables (their tenant_id is TEXT, as synced)
+CREATE POLICY "Tenant Isolation" ON public.sync_tombstones USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.categories USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.units USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.product_types USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.product_variants USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.variant_stock USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.inventory_lots USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.warehouse_stock USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.price_history USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.price_lists USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.exchange_rates USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.product_prices USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.discounts USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.discount_coupons USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.discount_bundle_items USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.cash_registers USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.cash_register_sessions USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.billing_invoices USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.billing_invoice_items USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.billing_payments USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.cash_movements USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.cash_session_counts USING (tenant_id = get_auth_tenant_id()::text);
+CREATE POLICY "Tenant Isolation" ON public.cash_session_reports USING (tenant_id = get_auth_tenant_id()::text);
+
 -- Grant access
 GRANT USAGE ON SCHEMA public TO anon, authenticated;
 GRANT ALL ON ALL TABLES IN SCHEMA public TO anon, authenticated;
 GRANT ALL ON ALL SEQUENCES IN SCHEMA public TO anon, authenticated;
 GRANT ALL ON ALL ROUTINES IN SCHEMA public TO anon, authenticated;
+
+-- ============================================
+-- DEVICE AUTHENTICATION (services/sync_backend.rs)
+-- ============================================
+-- The anon key only lets an installation register itself: the first one
+-- creates its organization, later ones must present a one-time enrollment
+-- token issued by an admin device of that organization. Registration
+-- returns a refresh token (stored hashed here) that is exchanged for a
+-- short-lived JWT with role `authenticated` and the device's tenant; RLS
+-- resolves the tenant from that claim. Revoked devices cannot refresh and
+-- their hardware cannot register again.
+CREATE EXTENSION IF NOT EXISTS pgjwt WITH SCHEMA extensions;
+
+CREATE TABLE IF NOT EXISTS public.sync_devices (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    org_id UUID NOT NULL REFERENCES public.organizations(id),
+    tenant_id UUID NOT NULL REFERENCES public.tenants(id),
+    hardware_id TEXT NOT NULL,
+    device_name TEXT NOT NULL,
+    refresh_token_hash TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL DEFAULT to_char(NOW() AT TIME ZONE 'utc', 'YYYY-MM-DD"T"HH24:MI:SS"+00:00"'),
+    last_seen_at TEXT,
+    revoked_at TEXT,
+    revoked_by UUID
+);
+CREATE INDEX IF NOT EXISTS idx_sync_devices_tenant ON public.sync_devices(tenant_id);
+
+-- Single-use codes for joining an existing organization (hashed)
+CREATE TABLE IF NOT EXISTS public.sync_enrollment_tokens (
+    token_hash TEXT PRIMARY KEY,
+    org_id UUID NOT NULL REFERENCES public.organizations(id),
+    tenant_id UUID REFERENCES public.tenants(id), -- NULL: any branch of the org
+    created_by UUID NOT NULL,
+    created_at TEXT NOT NULL DEFAULT to_char(NOW() AT TIME ZONE 'utc', 'YYYY-MM-DD"T"HH24:MI:SS"+00:00"'),
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TEXT,
+    used_by UUID
+);
+
+-- Only reachable through the functions below
+ALTER TABLE public.sync_devices ENABLE ROW LEVEL SECURITY;
+REVOKE ALL ON public.sync_devices FROM anon, authenticated;
+ALTER TABLE public.sync_enrollment_tokens ENABLE ROW LEVEL SECURITY;
+REVOKE ALL ON public.sync_enrollment_tokens FROM anon, authenticated;
+
+CREATE OR REPLACE FUNCTION public.sync_now_text()
+RETURNS TEXT AS $$
+  SELECT to_char(NOW() AT TIME ZONE 'utc', 'YYYY-MM-DD"T"HH24:MI:SS"+00:00"');
+$$ LANGUAGE sql STABLE;
+
+CREATE OR REPLACE FUNCTION public.issue_device_jwt(p_device public.sync_devices)
+RETURNS JSONB AS $$
+DECLARE
+  v_expires TIMESTAMPTZ := NOW() + INTERVAL '1 hour';
+BEGIN
+  RETURN jsonb_build_object(
+    'device_id', p_device.id,
+    'expires_at', to_char(v_expires AT TIME ZONE 'utc', 'YYYY-MM-DD"T"HH24:MI:SS"+00:00"'),
+    'access_token', extensions.sign(
+      json_build_object(
+        'role', 'authenticated',
+        'sub', p_device.id,
+        'device_id', p_device.id,
+        'org_id', p_device.org_id,
+        'tenant_id', p_device.tenant_id,
+        'exp', extract(epoch FROM v_expires)::BIGINT
+      ),
+      current_setting('app.settings.jwt_secret')
+    )
+  );
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER;
+
+DROP FUNCTION IF EXISTS public.register_device(UUID, TEXT, UUID, TEXT, TEXT, TEXT);
+CREATE OR REPLACE FUNCTION public.register_device(
+  p_org_id UUID, p_org_name TEXT, p_tenant_id UUID, p_tenant_name TEXT,
+  p_hardware_id TEXT, p_device_name TEXT, p_enrollment_token TEXT DEFAULT NULL
+)
+RETURNS JSONB AS $$
+DECLARE
+  v_refresh TEXT := encode(gen_random_bytes(32), 'hex');
+  v_device public.sync_devices;
+  v_token_hash TEXT;
+BEGIN
+  IF EXISTS (SELECT 1 FROM public.sync_devices
+             WHERE tenant_id = p_tenant_id AND hardware_id = p_hardware_id
+               AND revoked_at IS NOT NULL) THEN
+    RAISE EXCEPTION 'Device was revoked' USING ERRCODE = '42501';
+  END IF;
+
+  -- Joining an organization that already exists takes an unused token for it
+  IF EXISTS (SELECT 1 FROM public.organizations WHERE id = p_org_id) THEN
+    UPDATE public.sync_enrollment_tokens
+      SET used_at = public.sync_now_text()
+      WHERE token_hash = encode(digest(COALESCE(p_enrollment_token, ''), 'sha256'), 'hex')
+        AND org_id = p_org_id
+        AND (tenant_id IS NULL OR tenant_id = p_tenant_id)
+        AND used_at IS NULL
+        AND expires_at > NOW()
+      RETURNING token_hash INTO v_token_hash;
+    IF v_token_hash IS NULL THEN
+      RAISE EXCEPTION 'Enrollment token required' USING ERRCODE = '42501';
+    END IF;
+  END IF;
+
+  INSERT INTO public.organizations (id, name) VALUES (p_org_id, p_org_name)
+    ON CONFLICT (id) DO NOTHING;
+  INSERT INTO public.tenants (id, org_id, name, hardware_id)
+    VALUES (p_tenant_id, p_org_id, p_tenant_name, p_hardware_id)
+    ON CONFLICT (id) DO NOTHING;
+  IF NOT EXISTS (SELECT 1 FROM public.tenants WHERE id = p_tenant_id AND org_id = p_org_id) THEN
+    RAISE EXCEPTION 'Tenant belongs to another organization' USING ERRCODE = '42501';
+  END IF;
+
+  INSERT INTO public.sync_devices (org_id, tenant_id, hardware_id, device_name, refresh_token_hash)
+    VALUES (p_org_id, p_tenant_id, p_hardware_id, p_device_name,
+            encode(digest(v_refresh, 'sha256'), 'hex'))
+    RETURNING * INTO v_device;
+
+  IF v_token_hash IS NOT NULL THEN
+    UPDATE public.sync_enrollment_tokens SET used_by = v_device.id
+      WHERE token_hash = v_token_hash;
+  END IF;
+
+  RETURN public.issue_device_jwt(v_device) || jsonb_build_object('refresh_token', v_refresh);
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER;
+
+CREATE OR REPLACE FUNCTION public.refresh_device_token(p_refresh_token TEXT)
+RETURNS JSONB AS $$
+DECLARE
+  v_device public.sync_devices;
+BEGIN
+  UPDATE public.sync_devices SET last_seen_at = public.sync_now_text()
+    WHERE refresh_token_hash = encode(digest(p_refresh_token, 'sha256'), 'hex')
+      AND revoked_at IS NULL
+    RETURNING * INTO v_device;
+  IF v_device.id IS NULL THEN
+    RAISE EXCEPTION 'Invalid refresh token' USING ERRCODE = '28000';
+  END IF;
+  RETURN public.issue_device_jwt(v_device);
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER;
+
+CREATE OR REPLACE FUNCTION public.list_devices()
+RETURNS JSONB AS $$
+  SELECT COALESCE(jsonb_agg(jsonb_build_object(
+           'id', id, 'tenant_id', tenant_id, 'device_name', device_name,
+           'hardware_id', hardware_id, 'created_at', created_at,
+           'last_seen_at', last_seen_at, 'revoked_at', revoked_at
+         ) ORDER BY created_at), '[]'::jsonb)
+  FROM public.sync_devices
+  WHERE tenant_id = public.get_auth_tenant_id();
+$$ LANGUAGE sql SECURITY DEFINER;
+
+CREATE OR REPLACE FUNCTION public.revoke_device(p_device_id UUID)
+RETURNS VOID AS $$
+BEGIN
+  UPDATE public.sync_devices
+    SET revoked_at = COALESCE(revoked_at, public.sync_now_text()),
+        revoked_by = COALESCE(revoked_by, (auth.jwt() ->> 'device_id')::UUID)
+    WHERE id = p_device_id AND tenant_id = public.get_auth_tenant_id();
+  IF NOT FOUND THEN
+    RAISE EXCEPTION 'Device not found' USING ERRCODE = 'P0002';
+  END IF;
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER;
+
+-- Device JWTs resolve to their tenant while the device is not revoked
+CREATE OR REPLACE FUNCTION public.get_auth_tenant_id()
+RETURNS UUID AS $$
+BEGIN
+  IF auth.jwt() ? 'device_id' THEN
+    RETURN (SELECT tenant_id FROM public.sync_devices
+            WHERE id = (auth.jwt() ->> 'device_id')::UUID AND revoked_at IS NULL);
+  END IF;
+  RETURN (SELECT tenant_id FROM public.users WHERE id = auth.uid());
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER;
+
+CREATE OR REPLACE FUNCTION public.get_auth_org_id()
+RETURNS UUID AS $$
+  SELECT org_id FROM public.tenants WHERE id = public.get_auth_tenant_id();
+$$ LANGUAGE sql SECURITY DEFINER;
+
+-- Issued by a registered device for another installation of its organization
+CREATE OR REPLACE FUNCTION public.create_enrollment_token(
+  p_tenant_id UUID DEFAULT NULL, p_valid_hours INT DEFAULT 24
+)
+RETURNS TEXT AS $$
+DECLARE
+  v_org_id UUID := public.get_auth_org_id();
+  v_token TEXT := encode(gen_random_bytes(24), 'hex');
+BEGIN
+  IF v_org_id IS NULL THEN
+    RAISE EXCEPTION 'Not authenticated' USING ERRCODE = '28000';
+  END IF;
+  IF p_tenant_id IS NOT NULL AND NOT EXISTS (
+       SELECT 1 FROM public.tenants WHERE id = p_tenant_id AND org_id = v_org_id) THEN
+    RAISE EXCEPTION 'Tenant belongs to another organization' USING ERRCODE = '42501';
+  END IF;
+
+  INSERT INTO public.sync_enrollment_tokens (token_hash, org_id, tenant_id, created_by, expires_at)
+    VALUES (encode(digest(v_token, 'sha256'), 'hex'), v_org_id, p_tenant_id,
+            COALESCE((auth.jwt() ->> 'device_id')::UUID, auth.uid()),
+            NOW() + make_interval(hours => LEAST(GREATEST(p_valid_hours, 1), 168)));
+  RETURN v_token;
+END;
+$$ LANGUAGE plpgsql SECURITY DEFINER;
+
+CREATE POLICY "Own Organization" ON public.organizations USING (id = get_auth_org_id());
+CREATE POLICY "Own Tenant" ON public.tenants USING (id = get_auth_tenant_id());
+CREATE POLICY "Tenant Isolation" ON public.users USING (tenant_id = get_auth_tenant_id());
+
+-- The anon key no longer reaches data; it can only register and refresh
+REVOKE ALL ON ALL TABLES IN SCHEMA public FROM anon;
+REVOKE ALL ON ALL ROUTINES IN SCHEMA public FROM anon, PUBLIC;
+GRANT EXECUTE ON ALL ROUTINES IN SCHEMA public TO authenticated;
+REVOKE EXECUTE ON FUNCTION public.issue_device_jwt(public.sync_devices) FROM authenticated;
+GRANT EXECUTE ON FUNCTION public.register_device(UUID, TEXT, UUID, TEXT, TEXT, TEXT, TEXT) TO anon;
+GRANT EXECUTE ON FUNCTION public.refresh_device_token(TEXT) TO anon;
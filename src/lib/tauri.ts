@@ -64,12 +64,41 @@ export const auth = {
 // SYSTEM API
 // ============================================
 
+export type LicenseStatusKind =
+  | "valid"
+  | "grace"
+  | "trial"
+  | "expired"
+  | "invalid"
+  | "missing";
+
+export interface LicensePayload {
+  license_id: string;
+  org_name: string;
+  org_tax_id?: string;
+  plan: string;
+  modules: string[];
+  seats: number;
+  hardware_id?: string;
+  issued_at: string;
+  expires_at: string;
+  grace_days: number;
+}
+
+export interface LicenseState {
+  status: LicenseStatusKind;
+  license?: LicensePayload;
+  grace_until?: string;
+  days_remaining?: number;
+  active_users: number;
+  message?: string;
+}
+
 export const system = {
   getAppInfo: () =>
     invoke<{ version: string; tenant_id: string }>("get_app_info"),
 
-  checkLicense: () =>
-    invoke<{ status: string; expires_at?: string }>("check_license"),
+  checkLicense: () => invoke<LicenseState>("check_license"),
 };
 
 // ============================================
@@ -45,6 +45,7 @@ export function SetupWizard() {
   const [installMode, setInstallMode] = useState<InstallationMode>("new");
   const [previousInstall, setPreviousInstall] = useState<PreviousInstallation | null>(null);
   const [dbPath, setDbPath] = useState("Default (AppData)");
+  const [recoverySecret, setRecoverySecret] = useState("");
   const [isProcessing, setIsProcessing] = useState(false);
   const { toast } = useToast();
 
@@ -109,22 +110,23 @@ export function SetupWizard() {
   const handleDatabaseSelect = async (selectedPath: string) => {
     setIsProcessing(true);
     try {
+      let finalPath = selectedPath;
       // If it's a migration, copy the database
       if (installMode === "custom" && selectedPath !== dbPath) {
         const migratedPath: string = await invoke("migrate_database", {
           sourcePath: selectedPath,
           destinationPath: null, // Use default location
+          recoverySecret: recoverySecret.trim() || null, // Only needed for encrypted databases
         });
-        setDbPath(migratedPath);
+        finalPath = migratedPath;
         toast({ 
           title: "Base de datos migrada", 
           description: `Se copió la base de datos a ${migratedPath}` 
         });
-      } else {
-        setDbPath(selectedPath);
       }
+      setDbPath(finalPath);
       
-      await invoke("configure_database", { path: selectedPath });
+      await invoke("configure_database", { path: finalPath });
       setStep(3); // Skip to admin if using existing DB with users
     } catch (e: any) {
       toast({ title: "Error", description: e.toString(), variant: "destructive" });
@@ -316,10 +318,23 @@ export function SetupWizard() {
           )}
 
           {step === 2 && (installMode === "upgrade" || installMode === "custom") && (
-            <DatabaseSelector
-              onSelect={handleDatabaseSelect}
-              onUseNew={() => setInstallMode("new")}
-            />
+            <div className="space-y-4">
+              {installMode === "custom" && (
+                <div className="space-y-2">
+                  <label className="text-sm font-medium">Código de Recuperación</label>
+                  <Input
+                    type="password"
+                    value={recoverySecret}
+                    onChange={(e) => setRecoverySecret(e.target.value)}
+                    placeholder="Solo para bases de datos cifradas"
+                  />
+                </div>
+              )}
+              <DatabaseSelector
+                onSelect={handleDatabaseSelect}
+                onUseNew={() => setInstallMode("new")}
+              />
+            </div>
           )}
 
           {step === 3 && (
@@ -0,0 +1,356 @@
+//! Device Registry
+//!
+//! Installations register with the enrollment token and receive a refresh
+//! token; they sync with short-lived access tokens obtained from it. Only
+//! SHA-256 hashes of tokens are stored. Revoking a device drops its access
+//! tokens and refuses new registrations from the same hardware.
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::Mutex;
+
+const ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+
+/// Names the client also sends are not needed here
+#[derive(Debug, Deserialize)]
+pub struct Registration {
+    pub org_id: String,
+    pub tenant_id: String,
+    pub hardware_id: String,
+    pub device_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Credentials {
+    pub device_id: String,
+    pub access_token: String,
+    pub expires_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Device {
+    pub id: String,
+    pub tenant_id: String,
+    pub device_name: String,
+    pub hardware_id: String,
+    pub created_at: String,
+    pub last_seen_at: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+/// Device behind a valid access token
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub org_id: String,
+    pub tenant_id: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DeviceError {
+    Forbidden(String),
+    Unauthorized,
+    Storage(String),
+}
+
+fn storage(e: impl std::fmt::Display) -> DeviceError {
+    DeviceError::Storage(e.to_string())
+}
+
+fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+pub struct Devices {
+    conn: Mutex<Connection>,
+}
+
+impl Devices {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(|e| e.to_string())?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS devices (
+                 id TEXT PRIMARY KEY,
+                 org_id TEXT NOT NULL,
+                 tenant_id TEXT NOT NULL,
+                 hardware_id TEXT NOT NULL,
+                 device_name TEXT NOT NULL,
+                 refresh_hash TEXT NOT NULL UNIQUE,
+                 created_at TEXT NOT NULL,
+                 last_seen_at TEXT,
+                 revoked_at TEXT
+             );
+             CREATE INDEX IF NOT EXISTS idx_devices_tenant ON devices(tenant_id);
+             CREATE TABLE IF NOT EXISTS access_tokens (
+                 token_hash TEXT PRIMARY KEY,
+                 device_id TEXT NOT NULL REFERENCES devices(id),
+                 expires_at TEXT NOT NULL
+             );",
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn issue(conn: &Connection, device_id: &str) -> Result<Credentials, DeviceError> {
+        let now = Utc::now();
+        conn.execute(
+            "DELETE FROM access_tokens WHERE expires_at <= ?1",
+            [timestamp(now)],
+        )
+        .map_err(storage)?;
+
+        let token = new_token();
+        let expires_at = timestamp(now + Duration::minutes(ACCESS_TOKEN_TTL_MINUTES));
+        conn.execute(
+            "INSERT INTO access_tokens (token_hash, device_id, expires_at) VALUES (?1, ?2, ?3)",
+            params![hash(&token), device_id, expires_at],
+        )
+        .map_err(storage)?;
+
+        Ok(Credentials {
+            device_id: device_id.to_string(),
+            access_token: token,
+            expires_at,
+            refresh_token: None,
+        })
+    }
+
+    pub fn register(&self, registration: &Registration) -> Result<Credentials, DeviceError> {
+        let conn = self.conn.lock().map_err(storage)?;
+
+        let revoked: bool = conn
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM devices
+                 WHERE tenant_id = ?1 AND hardware_id = ?2 AND revoked_at IS NOT NULL)",
+                params![registration.tenant_id, registration.hardware_id],
+                |row| row.get(0),
+            )
+            .map_err(storage)?;
+        if revoked {
+            return Err(DeviceError::Forbidden("Device was revoked".to_string()));
+        }
+
+        let other_org: bool = conn
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM devices WHERE tenant_id = ?1 AND org_id != ?2)",
+                params![registration.tenant_id, registration.org_id],
+                |row| row.get(0),
+            )
+            .map_err(storage)?;
+        if other_org {
+            return Err(DeviceError::Forbidden(
+                "Tenant belongs to another organization".to_string(),
+            ));
+        }
+
+        let device_id = new_token()[..32].to_string();
+        let refresh_token = new_token();
+        conn.execute(
+            "INSERT INTO devices (id, org_id, tenant_id, hardware_id, device_name, refresh_hash, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                device_id,
+                registration.org_id,
+                registration.tenant_id,
+                registration.hardware_id,
+                registration.device_name,
+                hash(&refresh_token),
+                timestamp(Utc::now()),
+            ],
+        )
+        .map_err(storage)?;
+
+        println!(
+            "✅ Device registered: {} ({}, tenant {})",
+            device_id, registration.device_name, registration.tenant_id
+        );
+        Ok(Credentials {
+            refresh_token: Some(refresh_token),
+            ..Self::issue(&conn, &device_id)?
+        })
+    }
+
+    /// New access token for a device that was not revoked
+    pub fn refresh(&self, refresh_token: &str) -> Result<Credentials, DeviceError> {
+        let conn = self.conn.lock().map_err(storage)?;
+
+        let device_id: String = conn
+            .query_row(
+                "SELECT id FROM devices WHERE refresh_hash = ?1 AND revoked_at IS NULL",
+                [hash(refresh_token)],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(storage)?
+            .ok_or(DeviceError::Unauthorized)?;
+
+        conn.execute(
+            "UPDATE devices SET last_seen_at = ?1 WHERE id = ?2",
+            params![timestamp(Utc::now()), device_id],
+        )
+        .map_err(storage)?;
+
+        Self::issue(&conn, &device_id)
+    }
+
+    pub fn authenticate(&self, access_token: &str) -> Result<Option<Session>, DeviceError> {
+        let conn = self.conn.lock().map_err(storage)?;
+
+        conn.query_row(
+            "SELECT d.org_id, d.tenant_id
+             FROM access_tokens a
+             JOIN devices d ON d.id = a.device_id
+             WHERE a.token_hash = ?1 AND a.expires_at > ?2 AND d.revoked_at IS NULL",
+            params![hash(access_token), timestamp(Utc::now())],
+            |row| {
+                Ok(Session {
+                    org_id: row.get(0)?,
+                    tenant_id: row.get(1)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(storage)
+    }
+
+    /// Devices of a tenant, or all of them
+    pub fn list(&self, tenant_id: Option<&str>) -> Result<Vec<Device>, DeviceError> {
+        let conn = self.conn.lock().map_err(storage)?;
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, tenant_id, device_name, hardware_id, created_at, last_seen_at, revoked_at
+                 FROM devices
+                 WHERE ?1 IS NULL OR tenant_id = ?1
+                 ORDER BY created_at",
+            )
+            .map_err(storage)?;
+
+        let devices = stmt
+            .query_map([tenant_id], |row| {
+                Ok(Device {
+                    id: row.get(0)?,
+                    tenant_id: row.get(1)?,
+                    device_name: row.get(2)?,
+                    hardware_id: row.get(3)?,
+                    created_at: row.get(4)?,
+                    last_seen_at: row.get(5)?,
+                    revoked_at: row.get(6)?,
+                })
+            })
+            .map_err(storage)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(storage)?;
+        Ok(devices)
+    }
+
+    /// Revoke a device (of `tenant_id` when given); false if not found
+    pub fn revoke(&self, device_id: &str, tenant_id: Option<&str>) -> Result<bool, DeviceError> {
+        let conn = self.conn.lock().map_err(storage)?;
+
+        let updated = conn
+            .execute(
+                "UPDATE devices SET revoked_at = COALESCE(revoked_at, ?1)
+                 WHERE id = ?2 AND (?3 IS NULL OR tenant_id = ?3)",
+                params![timestamp(Utc::now()), device_id, tenant_id],
+            )
+            .map_err(storage)?;
+        if updated == 0 {
+            return Ok(false);
+        }
+
+        conn.execute(
+            "DELETE FROM access_tokens WHERE device_id = ?1",
+            [device_id],
+        )
+        .map_err(storage)?;
+
+        println!("⚠️ Device revoked: {}", device_id);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(hardware_id: &str) -> Registration {
+        Registration {
+            org_id: "o1".to_string(),
+            tenant_id: "t1".to_string(),
+            hardware_id: hardware_id.to_string(),
+            device_name: "Caja 1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_tokens_stop_working_after_revocation() {
+        let devices = Devices::open_in_memory().unwrap();
+
+        let registered = devices.register(&registration("hw-1")).unwrap();
+        let refresh_token = registered.refresh_token.clone().unwrap();
+        let session = devices
+            .authenticate(&registered.access_token)
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.tenant_id, "t1");
+        assert!(devices.authenticate("not-a-token").unwrap().is_none());
+
+        let refreshed = devices.refresh(&refresh_token).unwrap();
+        assert!(refreshed.refresh_token.is_none());
+        assert!(devices
+            .authenticate(&refreshed.access_token)
+            .unwrap()
+            .is_some());
+
+        // Another tenant cannot revoke it, nor drop its live tokens
+        assert!(!devices.revoke(&registered.device_id, Some("t2")).unwrap());
+        assert!(devices
+            .authenticate(&refreshed.access_token)
+            .unwrap()
+            .is_some());
+        assert!(devices.revoke(&registered.device_id, Some("t1")).unwrap());
+
+        assert!(devices
+            .authenticate(&refreshed.access_token)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            devices.refresh(&refresh_token).unwrap_err(),
+            DeviceError::Unauthorized
+        );
+        assert!(matches!(
+            devices.register(&registration("hw-1")),
+            Err(DeviceError::Forbidden(_))
+        ));
+        assert!(devices.register(&registration("hw-2")).is_ok());
+        assert_eq!(devices.list(Some("t1")).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,375 @@
+//! Equinox Sync Server
+//!
+//! Small self-hosted replacement for the Supabase project, for customers who
+//! cannot sync through a public cloud. It speaks the protocol of
+//! `EquinoxServerClient` in the desktop app.
+//!
+//! The configured token only enrolls installations (and administers them):
+//! each device syncs with its own short-lived access token and can only
+//! read or write rows of its tenant. See `devices`.
+//!
+//! Configuration (environment):
+//! - `EQUINOX_SYNC_TOKEN`  enrollment/admin bearer token (required)
+//! - `EQUINOX_SYNC_ADDR`   listen address (default `0.0.0.0:8787`)
+//! - `EQUINOX_SYNC_DB`     SQLite file (default `equinox-sync.db`)
+
+mod devices;
+mod store;
+
+use axum::extract::{Extension, Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+use devices::{DeviceError, Devices, Registration, Session};
+use store::{valid_identifier, Row, Store};
+
+const DEFAULT_ADDR: &str = "0.0.0.0:8787";
+const DEFAULT_DB: &str = "equinox-sync.db";
+
+struct AppState {
+    store: Store,
+    devices: Devices,
+    token: String,
+}
+
+/// Who sent an authenticated request
+#[derive(Clone)]
+enum Caller {
+    Admin,
+    Device(Session),
+}
+
+type Shared = Arc<AppState>;
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+fn internal(e: String) -> ApiError {
+    eprintln!("❌ {}", e);
+    ApiError(StatusCode::INTERNAL_SERVER_ERROR, e)
+}
+
+impl From<DeviceError> for ApiError {
+    fn from(e: DeviceError) -> Self {
+        match e {
+            DeviceError::Forbidden(message) => ApiError(StatusCode::FORBIDDEN, message),
+            DeviceError::Unauthorized => {
+                ApiError(StatusCode::UNAUTHORIZED, "Invalid token".to_string())
+            }
+            DeviceError::Storage(message) => internal(message),
+        }
+    }
+}
+
+fn forbidden(message: &str) -> ApiError {
+    ApiError(StatusCode::FORBIDDEN, message.to_string())
+}
+
+fn device_only(caller: Caller) -> Result<Session, ApiError> {
+    match caller {
+        Caller::Device(session) => Ok(session),
+        Caller::Admin => Err(forbidden("Data requires a device token")),
+    }
+}
+
+fn admin_only(caller: &Caller) -> Result<(), ApiError> {
+    match caller {
+        Caller::Admin => Ok(()),
+        Caller::Device(_) => Err(forbidden("Requires the enrollment token")),
+    }
+}
+
+fn check_table(table: &str) -> Result<(), ApiError> {
+    if valid_identifier(table) {
+        Ok(())
+    } else {
+        Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid table: {}", table),
+        ))
+    }
+}
+
+/// Compare without returning early on the first differing byte
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+async fn require_token(State(state): State<Shared>, mut request: Request, next: Next) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(str::to_string);
+
+    let caller = match token {
+        Some(token) if token_matches(&token, &state.token) => Caller::Admin,
+        Some(token) => match state.devices.authenticate(&token) {
+            Ok(Some(session)) => Caller::Device(session),
+            Ok(None) => return ApiError::from(DeviceError::Unauthorized).into_response(),
+            Err(e) => return ApiError::from(e).into_response(),
+        },
+        None => return ApiError::from(DeviceError::Unauthorized).into_response(),
+    };
+
+    request.extensions_mut().insert(caller);
+    next.run(request).await
+}
+
+async fn health() -> Json<serde_json::Value> {
+    Json(json!({ "status": "ok", "version": env!("CARGO_PKG_VERSION") }))
+}
+
+async fn register_device(
+    State(state): State<Shared>,
+    Extension(caller): Extension<Caller>,
+    Json(registration): Json<Registration>,
+) -> Result<Json<devices::Credentials>, ApiError> {
+    admin_only(&caller)?;
+    Ok(Json(state.devices.register(&registration)?))
+}
+
+#[derive(Deserialize)]
+struct RefreshBody {
+    refresh_token: String,
+}
+
+async fn refresh_device(
+    State(state): State<Shared>,
+    Json(body): Json<RefreshBody>,
+) -> Result<Json<devices::Credentials>, ApiError> {
+    Ok(Json(state.devices.refresh(&body.refresh_token)?))
+}
+
+/// Tenant a caller is limited to (`None` for the admin)
+fn caller_tenant(caller: &Caller) -> Option<&str> {
+    match caller {
+        Caller::Admin => None,
+        Caller::Device(session) => Some(session.tenant_id.as_str()),
+    }
+}
+
+async fn list_devices(
+    State(state): State<Shared>,
+    Extension(caller): Extension<Caller>,
+) -> Result<Json<Vec<devices::Device>>, ApiError> {
+    Ok(Json(state.devices.list(caller_tenant(&caller))?))
+}
+
+async fn revoke_device(
+    State(state): State<Shared>,
+    Extension(caller): Extension<Caller>,
+    Path(device_id): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if !state.devices.revoke(&device_id, caller_tenant(&caller))? {
+        return Err(ApiError(
+            StatusCode::NOT_FOUND,
+            format!("Device not found: {}", device_id),
+        ));
+    }
+    Ok(Json(json!({ "revoked": device_id })))
+}
+
+/// Rows a device may write: its organization, its tenant, or rows of its tenant
+fn check_rows(session: &Session, table: &str, key: &str, rows: &[Row]) -> Result<(), ApiError> {
+    for row in rows {
+        let allowed = match table {
+            "organizations" => row.get(key).and_then(|v| v.as_str()) == Some(&session.org_id),
+            "tenants" => row.get(key).and_then(|v| v.as_str()) == Some(&session.tenant_id),
+            _ => row.get("tenant_id").and_then(|v| v.as_str()) == Some(&session.tenant_id),
+        };
+        if !allowed {
+            return Err(forbidden("Row outside the device tenant"));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct UpsertParams {
+    key: String,
+}
+
+async fn upsert(
+    State(state): State<Shared>,
+    Extension(caller): Extension<Caller>,
+    Path(table): Path<String>,
+    Query(params): Query<UpsertParams>,
+    Json(rows): Json<Vec<Row>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    check_table(&table)?;
+    let session = device_only(caller)?;
+    check_rows(&session, &table, &params.key, &rows)?;
+
+    // Organization and tenant rows carry no tenant_id of their own
+    let owner = match table.as_str() {
+        "organizations" | "tenants" => None,
+        _ => Some(session.tenant_id.as_str()),
+    };
+    let count = state
+        .store
+        .upsert(&table, &params.key, &rows, owner)
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, e))?;
+    Ok(Json(json!({ "upserted": count })))
+}
+
+#[derive(Deserialize)]
+struct DeleteBody {
+    ids: Vec<String>,
+}
+
+async fn delete(
+    State(state): State<Shared>,
+    Extension(caller): Extension<Caller>,
+    Path(table): Path<String>,
+    Json(body): Json<DeleteBody>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    check_table(&table)?;
+    let session = device_only(caller)?;
+    let count = state
+        .store
+        .delete(&table, &body.ids, Some(&session.tenant_id))
+        .map_err(internal)?;
+    Ok(Json(json!({ "deleted": count })))
+}
+
+#[derive(Deserialize)]
+struct ChangesParams {
+    column: String,
+    since: Option<String>,
+    tenant_id: Option<String>,
+}
+
+fn changed_rows(
+    state: &AppState,
+    caller: Caller,
+    table: &str,
+    params: &ChangesParams,
+) -> Result<Vec<Row>, ApiError> {
+    check_table(table)?;
+    let session = device_only(caller)?;
+    if params
+        .tenant_id
+        .as_deref()
+        .is_some_and(|tenant_id| tenant_id != session.tenant_id)
+    {
+        return Err(forbidden("Tenant outside the device"));
+    }
+
+    if !valid_identifier(&params.column) {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid column: {}", params.column),
+        ));
+    }
+
+    state
+        .store
+        .changes(
+            table,
+            &params.column,
+            params.since.as_deref(),
+            Some(&session.tenant_id),
+        )
+        .map_err(internal)
+}
+
+async fn changes(
+    State(state): State<Shared>,
+    Extension(caller): Extension<Caller>,
+    Path(table): Path<String>,
+    Query(params): Query<ChangesParams>,
+) -> Result<Json<Vec<Row>>, ApiError> {
+    changed_rows(&state, caller, &table, &params).map(Json)
+}
+
+async fn count_changes(
+    State(state): State<Shared>,
+    Extension(caller): Extension<Caller>,
+    Path(table): Path<String>,
+    Query(params): Query<ChangesParams>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let rows = changed_rows(&state, caller, &table, &params)?;
+    Ok(Json(json!({ "count": rows.len() })))
+}
+
+fn router(state: Shared) -> Router {
+    let protected = Router::new()
+        .route("/v1/devices", get(list_devices))
+        .route("/v1/devices/register", post(register_device))
+        .route("/v1/devices/{id}/revoke", post(revoke_device))
+        .route("/v1/tables/{table}/upsert", post(upsert))
+        .route("/v1/tables/{table}/delete", post(delete))
+        .route("/v1/tables/{table}/changes", get(changes))
+        .route("/v1/tables/{table}/changes/count", get(count_changes))
+        .layer(middleware::from_fn_with_state(state.clone(), require_token));
+
+    Router::new()
+        .route("/v1/health", get(health))
+        .route("/v1/devices/refresh", post(refresh_device))
+        .merge(protected)
+        .with_state(state)
+}
+
+#[tokio::main]
+async fn main() {
+    let token = match std::env::var("EQUINOX_SYNC_TOKEN") {
+        Ok(token) if token.len() >= 16 => token,
+        _ => {
+            eprintln!("❌ EQUINOX_SYNC_TOKEN must be set (at least 16 characters)");
+            std::process::exit(1);
+        }
+    };
+    let addr = std::env::var("EQUINOX_SYNC_ADDR").unwrap_or_else(|_| DEFAULT_ADDR.to_string());
+    let db_path = std::env::var("EQUINOX_SYNC_DB").unwrap_or_else(|_| DEFAULT_DB.to_string());
+
+    let path = std::path::Path::new(&db_path);
+    let (store, devices) = match Store::open(path).and_then(|s| Ok((s, Devices::open(path)?))) {
+        Ok(opened) => opened,
+        Err(e) => {
+            eprintln!("❌ Cannot open {}: {}", db_path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let listener = match tokio::net::TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("❌ Cannot listen on {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    };
+    println!("✅ Equinox sync server listening on {} ({})", addr, db_path);
+
+    let app = router(Arc::new(AppState {
+        store,
+        devices,
+        token,
+    }));
+    let shutdown = async {
+        tokio::signal::ctrl_c().await.ok();
+    };
+    if let Err(e) = axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown)
+        .await
+    {
+        eprintln!("❌ Server error: {}", e);
+    }
+}
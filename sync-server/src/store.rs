@@ -0,0 +1,251 @@
+//! Row Store
+//!
+//! Every synced table is kept in one SQLite table of JSON documents keyed by
+//! (table, id). The server does not know the ERP schema: clients send whole
+//! rows and ask for the ones changed after a watermark.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{Map, Value};
+use std::path::Path;
+use std::sync::Mutex;
+
+pub type Row = Map<String, Value>;
+
+pub struct Store {
+    conn: Mutex<Connection>,
+}
+
+/// Table and column names are interpolated into JSON paths; keep them plain
+pub fn valid_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+fn key_text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        Self::init(conn)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, String> {
+        Self::init(Connection::open_in_memory().map_err(|e| e.to_string())?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS rows (
+                 tbl TEXT NOT NULL,
+                 id TEXT NOT NULL,
+                 tenant_id TEXT,
+                 data TEXT NOT NULL,
+                 PRIMARY KEY (tbl, id)
+             );
+             CREATE INDEX IF NOT EXISTS idx_rows_tenant ON rows(tbl, tenant_id);",
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Insert or merge rows; columns not sent keep their stored value.
+    /// With `tenant_id`, rows stored for another tenant are refused.
+    pub fn upsert(
+        &self,
+        table: &str,
+        key: &str,
+        rows: &[Row],
+        tenant_id: Option<&str>,
+    ) -> Result<usize, String> {
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+        for row in rows {
+            let id = key_text(row.get(key)).ok_or_else(|| format!("Row without {}", key))?;
+
+            let stored: Option<(String, Option<String>)> = tx
+                .query_row(
+                    "SELECT data, tenant_id FROM rows WHERE tbl = ?1 AND id = ?2",
+                    params![table, id],
+                    |r| Ok((r.get(0)?, r.get(1)?)),
+                )
+                .optional()
+                .map_err(|e| e.to_string())?;
+
+            if let (Some(expected), Some((_, Some(owner)))) = (tenant_id, &stored) {
+                if owner != expected {
+                    return Err(format!("Row {} belongs to another tenant", id));
+                }
+            }
+
+            let mut merged: Row = stored
+                .and_then(|(json, _)| serde_json::from_str(&json).ok())
+                .unwrap_or_default();
+            for (column, value) in row {
+                merged.insert(column.clone(), value.clone());
+            }
+
+            let tenant_id = merged
+                .get("tenant_id")
+                .and_then(|v| v.as_str())
+                .map(str::to_string);
+            let data = serde_json::to_string(&merged).map_err(|e| e.to_string())?;
+
+            tx.execute(
+                "INSERT OR REPLACE INTO rows (tbl, id, tenant_id, data) VALUES (?1, ?2, ?3, ?4)",
+                params![table, id, tenant_id, data],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(rows.len())
+    }
+
+    /// Delete rows by id (only those of `tenant_id` when given)
+    pub fn delete(
+        &self,
+        table: &str,
+        ids: &[String],
+        tenant_id: Option<&str>,
+    ) -> Result<usize, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let ids = serde_json::to_string(ids).map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "DELETE FROM rows
+             WHERE tbl = ?1 AND id IN (SELECT value FROM json_each(?2))
+               AND (?3 IS NULL OR tenant_id = ?3)",
+            params![table, ids, tenant_id],
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    /// Rows whose `column` is after `since`, oldest change first
+    pub fn changes(
+        &self,
+        table: &str,
+        column: &str,
+        since: Option<&str>,
+        tenant_id: Option<&str>,
+    ) -> Result<Vec<Row>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let path = format!("$.{}", column);
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT data FROM rows
+                 WHERE tbl = ?1
+                   AND (?2 IS NULL OR json_extract(data, ?3) > ?2)
+                   AND (?4 IS NULL OR tenant_id = ?4)
+                 ORDER BY json_extract(data, ?3), id",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map(params![table, since, path, tenant_id], |r| {
+                r.get::<_, String>(0)
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        rows.iter()
+            .map(|json| serde_json::from_str(json).map_err(|e| e.to_string()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn row(value: Value) -> Row {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_upsert_merges_and_filters_changes() {
+        let store = Store::open_in_memory().unwrap();
+
+        store
+            .upsert(
+                "clients",
+                "id",
+                &[
+                    row(json!({"id": "c1", "tenant_id": "t1", "name": "Ana", "updated_at": "2026-01-01T00:00:00+00:00"})),
+                    row(json!({"id": "c2", "tenant_id": "t2", "name": "Luis", "updated_at": "2026-01-02T00:00:00+00:00"})),
+                ],
+                None,
+            )
+            .unwrap();
+        store
+            .upsert(
+                "clients",
+                "id",
+                &[row(
+                    json!({"id": "c1", "phone": "555", "updated_at": "2026-01-03T00:00:00+00:00"}),
+                )],
+                Some("t1"),
+            )
+            .unwrap();
+
+        let changed = store
+            .changes(
+                "clients",
+                "updated_at",
+                Some("2026-01-01T00:00:00+00:00"),
+                None,
+            )
+            .unwrap();
+        assert_eq!(changed.len(), 2);
+        assert_eq!(changed[1]["name"], "Ana"); // Merged, not replaced
+        assert_eq!(changed[1]["phone"], "555");
+
+        let tenant = store
+            .changes("clients", "updated_at", None, Some("t2"))
+            .unwrap();
+        assert_eq!(tenant.len(), 1);
+
+        // A device of another tenant can neither overwrite nor delete it
+        assert!(store
+            .upsert("clients", "id", &[row(json!({"id": "c1"}))], Some("t2"))
+            .is_err());
+        assert_eq!(
+            store
+                .delete("clients", &["c1".to_string()], Some("t2"))
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(
+            store
+                .delete("clients", &["c1".to_string()], Some("t1"))
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            store
+                .changes("clients", "updated_at", None, None)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(!valid_identifier("clients; DROP"));
+    }
+}